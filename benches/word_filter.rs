@@ -0,0 +1,47 @@
+// Run with `cargo bench --bench word_filter`
+use nostr_types::WordFilter;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+fn term(i: usize) -> String {
+    // Distinct pseudo-words, with some wildcards and phrases mixed in
+    let word = format!("w{:x}q{}", i.wrapping_mul(2654435761) % 1_000_003, i % 97);
+    match i % 10 {
+        0 => format!("{word}*"),
+        1 => format!("{word} z{i}"),
+        _ => word,
+    }
+}
+
+fn time<F: FnMut()>(iterations: u32, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    start.elapsed() / iterations
+}
+
+fn main() {
+    let content = "Just setting up my nostr. Check out this note about relays, \
+                   zaps, and the outbox model; no airdrops or spam here, just a \
+                   normal length post of a few dozen words to scan through."
+        .repeat(4);
+
+    for count in [100, 1_000, 10_000, 100_000] {
+        let terms: Vec<String> = (0..count).map(term).collect();
+
+        let start = Instant::now();
+        let filter = WordFilter::new(&terms).unwrap();
+        let build = start.elapsed();
+
+        let miss = time(1000, || {
+            let _ = black_box(filter.is_match(black_box(&content)));
+        });
+        let hit_text = format!("{content} {}", terms[count - 1]);
+        let hit = time(1000, || {
+            let _ = black_box(filter.matches(black_box(&hit_text)));
+        });
+
+        println!("{count:>7} terms: build {build:>12?}, no match {miss:>10?}, match {hit:>10?}");
+    }
+}
@@ -0,0 +1,137 @@
+use base64::Engine;
+use nostr_types::{
+    AccountBackup, ClientMessage, PublicKey, RelayMessage, SubscriptionId, Unixtime,
+};
+use std::env;
+use std::net::TcpStream;
+use tungstenite::protocol::Message;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::WebSocket;
+
+const USAGE: &str = "Usage: account_backup export <RelayURL> <npub or hex pubkey> <file>\n       account_backup restore <RelayURL> <file>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(|a| a.as_str()).collect::<Vec<&str>>()[..] {
+        ["export", relay_url, pubkey, path] => export(relay_url, pubkey, path),
+        ["restore", relay_url, path] => restore(relay_url, path),
+        _ => panic!("{}", USAGE),
+    }
+}
+
+// Fetch the account's events from the relay and write them to a backup file
+fn export(relay_url: &str, pubkey: &str, path: &str) {
+    let pubkey = PublicKey::try_from_bech32_string(pubkey)
+        .or_else(|_| PublicKey::try_from_hex_string(pubkey))
+        .expect("Invalid public key");
+    let mut backup = AccountBackup::new(pubkey, Unixtime::now().unwrap());
+
+    let mut websocket = connect(relay_url);
+    let message = ClientMessage::Req(SubscriptionId("backup".to_owned()), vec![backup.filter()]);
+    send(&mut websocket, &message);
+
+    loop {
+        match receive(&mut websocket) {
+            RelayMessage::Event(_, event) => {
+                if let Err(e) = backup.add(*event) {
+                    eprintln!("Skipping event: {}", e);
+                }
+            }
+            RelayMessage::Eose(_) => break,
+            RelayMessage::Notice(s) => eprintln!("NOTICE: {}", s),
+            _ => {}
+        }
+    }
+    send(
+        &mut websocket,
+        &ClientMessage::Close(SubscriptionId("backup".to_owned())),
+    );
+    let _ = websocket.close(None);
+
+    std::fs::write(path, backup.to_json().expect("Could not serialize backup"))
+        .expect("Could not write backup file");
+    println!("Backed up {} events to {}", backup.events.len(), path);
+}
+
+// Publish every event in a backup file to the relay
+fn restore(relay_url: &str, path: &str) {
+    let json = std::fs::read_to_string(path).expect("Could not read backup file");
+    let backup = AccountBackup::from_json(&json).expect("Invalid backup file");
+
+    let mut websocket = connect(relay_url);
+    for event in &backup.events {
+        send(
+            &mut websocket,
+            &ClientMessage::Event(Box::new(event.clone())),
+        );
+    }
+
+    let mut pending = backup.events.len();
+    while pending > 0 {
+        match receive(&mut websocket) {
+            RelayMessage::Ok(id, ok, reason) => {
+                pending -= 1;
+                println!("{}: ok={} {}", id.as_hex_string(), ok, reason);
+            }
+            RelayMessage::Notice(s) => eprintln!("NOTICE: {}", s),
+            _ => {}
+        }
+    }
+    let _ = websocket.close(None);
+}
+
+fn connect(relay_url: &str) -> WebSocket<MaybeTlsStream<TcpStream>> {
+    let uri: http::Uri = relay_url.parse::<http::Uri>().expect("Could not parse url");
+    let authority = uri.authority().expect("Has no hostname").as_str();
+    let host = authority
+        .find('@')
+        .map(|idx| authority.split_at(idx + 1).1)
+        .unwrap_or_else(|| authority);
+    if host.is_empty() {
+        panic!("URL has empty hostname");
+    }
+
+    let key: [u8; 16] = rand::random();
+    let request = http::request::Request::builder()
+        .method("GET")
+        .header("Host", host)
+        .header("Connection", "Upgrade")
+        .header("Upgrade", "websocket")
+        .header("Sec-WebSocket-Version", "13")
+        .header(
+            "Sec-WebSocket-Key",
+            base64::engine::general_purpose::STANDARD.encode(key),
+        )
+        .uri(uri)
+        .body(())
+        .expect("Could not build request");
+
+    let (websocket, _response) = tungstenite::connect(request).expect("Could not connect to relay");
+    websocket
+}
+
+fn send(websocket: &mut WebSocket<MaybeTlsStream<TcpStream>>, message: &ClientMessage) {
+    let wire = serde_json::to_string(message).expect("Could not serialize message");
+    websocket
+        .write_message(Message::Text(wire))
+        .expect("Could not send message to relay");
+}
+
+// The next relay message, answering pings along the way
+fn receive(websocket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> RelayMessage {
+    loop {
+        match websocket
+            .read_message()
+            .expect("Problem reading from websocket")
+        {
+            Message::Text(s) => {
+                return serde_json::from_str(&s).expect("Unable to deserialize RelayMessage")
+            }
+            Message::Ping(vec) => websocket
+                .write_message(Message::Pong(vec))
+                .expect("Unable to write message"),
+            Message::Close(_) => panic!("Relay closed the connection"),
+            _ => {}
+        }
+    }
+}
@@ -2,16 +2,56 @@
 #![allow(clippy::uninlined_format_args)]
 
 use base64::Engine;
-use nostr_types::{ClientMessage, Filter, RelayMessage, SubscriptionId};
+use nostr_types::{
+    connect_relay, ClientMessage, ConnectConfig, EventStats, EventWriter, Filter, RelayMessage,
+    RelayUrl, SubscriptionId, Unixtime, WireLog,
+};
 use std::env;
+use std::fs::File;
 use tungstenite::protocol::Message;
 
 fn main() {
-    let mut args = env::args();
-    let _ = args.next(); // program name
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    // With --summary, print statistics about the events instead of the events
+    let summary = match args.iter().position(|a| a == "--summary") {
+        Some(index) => {
+            let _ = args.remove(index);
+            true
+        }
+        None => false,
+    };
+    let mut stats = EventStats::new();
+
+    // With --verbose, report DNS resolution and which address connected
+    let verbose = match args.iter().position(|a| a == "--verbose") {
+        Some(index) => {
+            let _ = args.remove(index);
+            true
+        }
+        None => false,
+    };
+
+    let mut args = args.into_iter();
     let relay_url = match args.next() {
         Some(u) => u,
-        None => panic!("Usage: dump_relay <RelayURL>"),
+        None => panic!("Usage: dump_relay [--summary] [--verbose] <RelayURL> [wire log file]"),
+    };
+
+    // Optionally record every frame, to replay later with replay_wire_log
+    let mut wire_log = args
+        .next()
+        .map(|path| WireLog::new(File::create(path).expect("Could not create wire log")));
+    let mut log = |frame: &str, inbound: bool| {
+        if let Some(wire_log) = wire_log.as_mut() {
+            let now = Unixtime::now().unwrap();
+            let result = if inbound {
+                wire_log.inbound(frame, now)
+            } else {
+                wire_log.outbound(frame, now)
+            };
+            result.expect("Could not write wire log");
+        }
     };
 
     let filter = Filter::new();
@@ -43,13 +83,22 @@ fn main() {
         .body(())
         .expect("Could not build request");
 
+    let relay = RelayUrl::try_from_str(&relay_url).expect("Invalid relay url");
+    let (stream, report) = connect_relay(&relay, &ConnectConfig::default());
+    if verbose || stream.is_err() {
+        eprintln!("{}", report);
+    }
+    let stream = stream.expect("Could not connect to relay");
     let (mut websocket, _response) =
-        tungstenite::connect(request).expect("Could not connect to relay");
+        tungstenite::client_tls(request, stream).expect("Could not connect to relay");
 
+    log(&wire, false);
     websocket
         .write_message(Message::Text(wire))
         .expect("Could not send message to relay");
 
+    let mut writer = EventWriter::new(std::io::stdout());
+
     loop {
         let message = websocket
             .read_message()
@@ -57,18 +106,31 @@ fn main() {
 
         match message {
             Message::Text(s) => {
+                log(&s, true);
                 let relay_message: RelayMessage =
                     serde_json::from_str(&s).expect("Unable to deserialize RelayMessage");
                 match relay_message {
-                    RelayMessage::Event(_, e) => println!(
-                        "{}",
-                        serde_json::to_string(&e).expect("Cannot serialize event")
-                    ),
+                    RelayMessage::Event(_, e) => {
+                        if summary {
+                            stats.record(&e, Unixtime::now().unwrap());
+                        } else {
+                            writer.write(&e).expect("Cannot write event");
+                            writer.flush().expect("Cannot flush output");
+                        }
+                    }
                     RelayMessage::Notice(s) => println!("NOTICE: {}", s),
                     RelayMessage::Eose(_) => {
+                        if summary {
+                            println!(
+                                "{}",
+                                serde_json::to_string_pretty(&stats)
+                                    .expect("Could not serialize stats")
+                            );
+                        }
                         let message = ClientMessage::Close(SubscriptionId("dump".to_owned()));
                         let wire =
                             serde_json::to_string(&message).expect("Could not serialize message");
+                        log(&wire, false);
                         websocket
                             .write_message(Message::Text(wire))
                             .expect("Could not write close subscription message");
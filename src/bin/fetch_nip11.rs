@@ -41,6 +41,44 @@ fn main() {
     let json = response.text().unwrap();
     if let Ok(rid) = serde_json::from_str::<RelayInformationDocument>(&json) {
         println!("{}", rid);
+        if let Some(url) = rid.payments_url() {
+            println!("Payments: {}", url);
+        }
+        let fees = rid.fees();
+        for (name, list) in [
+            ("admission", &fees.admission),
+            ("subscription", &fees.subscription),
+            ("publication", &fees.publication),
+        ] {
+            for fee in list.iter() {
+                print!("Fee ({}): {} {}", name, fee.amount, fee.unit);
+                if let Some(period) = fee.period {
+                    print!(" per {} seconds", period);
+                }
+                if !fee.kinds.is_empty() {
+                    print!(" for kinds {:?}", fee.kinds);
+                }
+                println!();
+            }
+        }
+        if let Some(icon) = rid.icon_url() {
+            let content_type = client
+                .get(icon.as_str())
+                .send()
+                .ok()
+                .and_then(|r| {
+                    r.headers()
+                        .get("Content-Type")
+                        .and_then(|v| v.to_str().ok())
+                        .map(|s| s.to_owned())
+                })
+                .unwrap_or_default();
+            if RelayInformationDocument::is_valid_icon_content_type(&content_type) {
+                println!("Icon: {} ({})", icon, content_type);
+            } else {
+                println!("Icon: {} is not an image ({:?})", icon, content_type);
+            }
+        }
     } else {
         println!("INVALID DECODE");
         println!("{}", json);
@@ -0,0 +1,103 @@
+use base64::Engine;
+use nostr_types::{connect_relay, ConnectConfig, RelayInformationDocument, RelayProbe, RelayUrl};
+use reqwest::blocking::Client;
+use reqwest::redirect::Policy;
+use std::env;
+use std::time::{Duration, Instant};
+use tungstenite::protocol::Message;
+
+fn main() {
+    let mut args = env::args();
+    let _ = args.next(); // program name
+    let relay_url = match args.next() {
+        Some(u) => u,
+        None => panic!("Usage: probe_relay <RelayURL>"),
+    };
+
+    let uri: http::Uri = relay_url.parse::<http::Uri>().expect("Could not parse url");
+    let authority = uri.authority().expect("Has no hostname").as_str();
+    let host = authority
+        .find('@')
+        .map(|idx| authority.split_at(idx + 1).1)
+        .unwrap_or_else(|| authority);
+    if host.is_empty() {
+        panic!("URL has empty hostname");
+    }
+
+    // What the relay claims, if it says
+    let client = Client::builder()
+        .redirect(Policy::none())
+        .timeout(Some(Duration::from_secs(30)))
+        .build()
+        .expect("Could not build client");
+    let nip11 = client
+        .get(format!("https://{host}"))
+        .header("Host", host)
+        .header("Accept", "application/nostr+json")
+        .send()
+        .and_then(|r| r.text())
+        .ok()
+        .and_then(|json| serde_json::from_str::<RelayInformationDocument>(&json).ok());
+    if nip11.is_none() {
+        eprintln!("No NIP-11 document");
+    }
+
+    let key: [u8; 16] = rand::random();
+    let request = http::request::Request::builder()
+        .method("GET")
+        .header("Host", host)
+        .header("Connection", "Upgrade")
+        .header("Upgrade", "websocket")
+        .header("Sec-WebSocket-Version", "13")
+        .header(
+            "Sec-WebSocket-Key",
+            base64::engine::general_purpose::STANDARD.encode(key),
+        )
+        .uri(uri)
+        .body(())
+        .expect("Could not build request");
+
+    let relay = RelayUrl::try_from_str(&relay_url).expect("Invalid relay url");
+    let (stream, report) = connect_relay(&relay, &ConnectConfig::default());
+    let stream = stream.unwrap_or_else(|e| panic!("Could not connect to relay: {e}\n{report}"));
+    let (mut websocket, _response) =
+        tungstenite::client_tls(request, stream.try_clone().expect("Could not clone stream"))
+            .expect("Could not connect to relay");
+    // Wake up now and then, so that silence can time out
+    stream
+        .set_read_timeout(Some(Duration::from_millis(250)))
+        .expect("Could not set read timeout");
+
+    let mut probe = RelayProbe::new(relay, nip11, Duration::from_secs(5), Instant::now());
+    let mut frames = probe.start(Instant::now());
+    while !probe.is_done() {
+        for frame in frames.drain(..) {
+            websocket
+                .write_message(Message::Text(frame))
+                .expect("Could not send message to relay");
+        }
+        match websocket.read_message() {
+            Ok(Message::Text(s)) => frames = probe.handle(&s, Instant::now()),
+            Ok(Message::Ping(vec)) => websocket
+                .write_message(Message::Pong(vec))
+                .expect("Unable to write message"),
+            Ok(Message::Close(_)) => break,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                frames = probe.poll(Instant::now())
+            }
+            Err(e) => panic!("Problem reading from websocket: {e}"),
+        }
+    }
+    for frame in frames {
+        let _ = websocket.write_message(Message::Text(frame));
+    }
+    let _ = websocket.close(None);
+
+    print!("{}", probe.finish());
+}
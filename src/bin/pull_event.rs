@@ -2,7 +2,7 @@
 #![allow(clippy::uninlined_format_args)]
 
 use base64::Engine;
-use nostr_types::{ClientMessage, Filter, IdHex, RelayMessage, SubscriptionId};
+use nostr_types::{ClientMessage, EventWriter, Filter, IdHex, RelayMessage, SubscriptionId};
 use std::env;
 use tungstenite::protocol::Message;
 
@@ -55,6 +55,8 @@ fn main() {
         .write_message(Message::Text(wire))
         .expect("Could not send message to relay");
 
+    let mut writer = EventWriter::new(std::io::stdout());
+
     loop {
         let message = websocket
             .read_message()
@@ -65,10 +67,10 @@ fn main() {
                 let relay_message: RelayMessage =
                     serde_json::from_str(&s).expect("Unable to deserialize RelayMessage");
                 match relay_message {
-                    RelayMessage::Event(_, e) => println!(
-                        "{}",
-                        serde_json::to_string(&e).expect("Cannot serialize event")
-                    ),
+                    RelayMessage::Event(_, e) => {
+                        writer.write(&e).expect("Cannot write event");
+                        writer.flush().expect("Cannot flush output");
+                    }
                     RelayMessage::Notice(s) => println!("NOTICE: {}", s),
                     RelayMessage::Eose(_) => {
                         let message = ClientMessage::Close(SubscriptionId("dump".to_owned()));
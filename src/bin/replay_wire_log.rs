@@ -0,0 +1,52 @@
+// TEMPORARILY
+#![allow(clippy::uninlined_format_args)]
+
+use nostr_types::WireReplay;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+// Replay a wire log recorded by dump_relay through the message parsers,
+// printing each frame and how it parsed
+fn main() {
+    let mut args = env::args();
+    let _ = args.next(); // program name
+    let path = match args.next() {
+        Some(p) => p,
+        None => {
+            println!("Usage: replay_wire_log <wire log file>");
+            process::exit(1);
+        }
+    };
+
+    let file = File::open(path).expect("Could not open wire log");
+    let mut failures = 0;
+    for (n, frame) in WireReplay::new(BufReader::new(file)).enumerate() {
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err(e) => {
+                println!("{}: NOT A FRAME: {}", n + 1, e);
+                failures += 1;
+                continue;
+            }
+        };
+        println!(
+            "{}: {} {:?} {}",
+            n + 1,
+            frame.at,
+            frame.direction,
+            frame.frame
+        );
+        match frame.parse() {
+            Ok(message) => println!("    {:?}", message),
+            Err(e) => {
+                println!("    PARSE FAILED: {}", e);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,55 @@
+// TEMPORARILY
+#![allow(clippy::uninlined_format_args)]
+
+use nostr_types::{Event, PreEvent, PrivateKey, Unixtime};
+use std::env;
+use std::process;
+
+// Sign an event from a JSON template file, printing the event. The private key
+// is taken from the NOSTR_PRIVATE_KEY environment variable (hex or nsec), so
+// that this can run unattended.
+fn main() {
+    let mut args = env::args();
+    let _ = args.next(); // program name
+    let path = match args.next() {
+        Some(p) => p,
+        None => {
+            println!("Usage: sign_event <template.json> [name=value ...]");
+            process::exit(1);
+        }
+    };
+    let pairs: Vec<(String, String)> = args
+        .map(|arg| match arg.split_once('=') {
+            Some((name, value)) => (name.to_owned(), value.to_owned()),
+            None => {
+                println!("Variables must be given as name=value, not {}", arg);
+                process::exit(1);
+            }
+        })
+        .collect();
+    let vars: Vec<(&str, &str)> = pairs
+        .iter()
+        .map(|(n, v)| (n.as_str(), v.as_str()))
+        .collect();
+
+    let key = env::var("NOSTR_PRIVATE_KEY").expect("NOSTR_PRIVATE_KEY is not set");
+    let key = key.trim();
+    let private_key = if key.starts_with("nsec1") {
+        PrivateKey::try_from_bech32_string(key)
+    } else {
+        PrivateKey::try_from_hex_string(key)
+    }
+    .expect("Could not read the private key");
+
+    let template = std::fs::read_to_string(&path).expect("Could not read the template");
+    let now = Unixtime::now().unwrap();
+    let pre = match PreEvent::from_template(&template, private_key.public_key(), now, &vars) {
+        Ok(pre) => pre,
+        Err(e) => {
+            println!("{}", e);
+            process::exit(1);
+        }
+    };
+    let event = Event::new(pre, &private_key).expect("Could not sign the event");
+    println!("{}", serde_json::to_string(&event).unwrap());
+}
@@ -1,7 +1,13 @@
+use crate::{IdHex, Unixtime};
+use std::fmt;
 use thiserror::Error;
 
 /// Errors that can occur in the nostr-proto crate
+///
+/// New variants may be added, so match on `code()` rather than on the variants
+/// where a stable identifier is needed.
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// Assertion failed
     #[error("Assertion failed: {0}")]
@@ -19,38 +25,116 @@ pub enum Error {
     #[error("Bech32 Error: {0}")]
     Bech32(#[from] bech32::Error),
 
+    /// Bech32 error at a known byte offset in the input
+    #[error("Bech32 Error at byte {offset}: {source}")]
+    Bech32At {
+        /// The byte offset of the offending character
+        offset: usize,
+
+        /// The underlying error
+        source: bech32::Error,
+    },
+
+    /// Bech32 string too short, most likely cut off when copied
+    #[error("Bech32 Error: {found} characters where {expected} were expected, it may have been truncated")]
+    Bech32Truncated {
+        /// The length the string should have
+        expected: usize,
+
+        /// The length of the string
+        found: usize,
+    },
+
+    /// Bech32 string with a common mistake, which `suggestion` says how to
+    /// fix
+    #[error("Bech32 Error: expected {expected}, {suggestion}")]
+    Bech32Mistake {
+        /// The kind of string expected, such as "npub"
+        expected: String,
+
+        /// How to fix it
+        suggestion: Bech32Suggestion,
+    },
+
     /// Encryption/Decryption Error
     #[error("Encryption/Decryption Error")]
     Encryption,
 
     /// Bech32 error
-    #[error("Wrong Bech32 Kind: Expected {0} found {0}")]
+    #[error("Wrong Bech32 Kind: Expected {0} found {1}")]
     WrongBech32(String, String),
 
     /// Signature error
     #[error("ECDSA Signature Error: {0}")]
     Signature(#[from] k256::ecdsa::Error),
 
+    /// A signer refused or could not carry out an operation
+    #[error("Signer refused: {0}")]
+    SignerRefused(String),
+
+    /// An event does not satisfy the conditions of its delegation
+    #[error("Delegation conditions not met: {0}")]
+    DelegationViolated(String),
+
     /// Event is in the future
-    #[error("Event is in the future")]
-    EventInFuture,
+    #[error("Event is in the future: created_at {created_at} is after {maxtime}")]
+    EventInFuture {
+        /// The event's created_at
+        created_at: Unixtime,
+
+        /// The latest time that was allowed
+        maxtime: Unixtime,
+    },
+
+    /// A message or event is larger than allowed
+    #[error("Too large: {size} bytes is more than the {max} allowed")]
+    TooLarge {
+        /// The size, in bytes
+        size: usize,
+
+        /// The largest size allowed
+        max: usize,
+    },
 
     /// Formatting error
     #[error("Formatting Error: {0}")]
     Fmt(#[from] std::fmt::Error),
 
-    /// A hash mismatch verification error
-    #[error("Hash Mismatch")]
-    HashMismatch,
+    /// A hash mismatch verification error: the event id is not the hash of
+    /// the event
+    #[error("Hash Mismatch: event claims id {claimed} but hashes to {computed}")]
+    HashMismatch {
+        /// The id in the event
+        claimed: IdHex,
+
+        /// The id computed from the event contents
+        computed: IdHex,
+    },
 
     /// Hex string decoding error
     #[error("Hex Decode Error: {0}")]
     HexDecode(#[from] hex::FromHexError),
 
+    /// I/O error
+    #[error("I/O Error: {0}")]
+    Io(#[from] std::io::Error),
+
     /// Invalid encrypted private key
     #[error("Invalid Encrypted Private Key")]
     InvalidEncryptedPrivateKey,
 
+    /// Invalid geohash
+    #[error("Invalid geohash: {0}")]
+    InvalidGeohash(String),
+
+    /// A key backup could not be imported
+    #[error("Invalid key backup: {0}")]
+    InvalidKeyBackup(String),
+
+    /// An event template could not be filled in
+    #[error("Invalid event template: {0}")]
+    InvalidTemplate(String),
+
     /// Invalid event Id
     #[error("Invalid event Id")]
     InvalidId,
@@ -71,10 +155,26 @@ pub enum Error {
     #[error("Invalid Public Key")]
     InvalidPublicKey,
 
+    /// An event's signature does not verify against its id and pubkey
+    #[error("Invalid Signature: {0}")]
+    InvalidSignature(k256::ecdsa::Error),
+
     /// Invalid public key prefix
     #[error("Invalid Public Key Prefix")]
     InvalidPublicKeyPrefix,
 
+    /// A tag could not be parsed, from `Event::from_json_with_profile()` (and
+    /// the message parsers that use it) or `Nutzap::try_from_event()`. Plain
+    /// serde deserialization reports bad tags as `SerdeJson` instead.
+    #[error("Invalid tag at index {index}: {message}")]
+    InvalidTag {
+        /// The index of the tag within the event's tags
+        index: usize,
+
+        /// What was wrong with it
+        message: String,
+    },
+
     /// Invalid URL
     #[error("Invalid URL: \"{0}\"")]
     InvalidUrl(#[from] url::ParseError),
@@ -91,10 +191,31 @@ pub enum Error {
     #[error("Missing URL Authority")]
     InvalidUrlMissingAuthority,
 
+    /// A word filter could not be compiled
+    #[error("Invalid word filter: {0}")]
+    InvalidWordFilter(String),
+
+    /// A NIP-44 payload could not be encrypted or decrypted
+    #[error("NIP-44 error: {0}")]
+    Nip44(String),
+
+    /// A remote signer (NIP-46) returned an error
+    #[error("Remote signer error: {0}")]
+    Nip46(String),
+
+    /// A remote signer (NIP-46) did not answer a request in time
+    #[error("Remote signer did not answer {0} in time")]
+    Nip46Timeout(String),
+
     /// Pad error
     #[error("Encryption/Decryption padding error")]
     Pad(#[from] inout::PadError),
 
+    /// LMDB storage error
+    #[cfg(feature = "lmdb")]
+    #[error("LMDB Error: {0}")]
+    Lmdb(#[from] heed::Error),
+
     /// Parse integer error
     #[error("Parse integer error")]
     ParseInt(#[from] std::num::ParseIntError),
@@ -107,6 +228,10 @@ pub enum Error {
     #[error("JSON (de)serialization error: {0}")]
     SerdeJson(#[from] serde_json::Error),
 
+    /// Input was rejected by `ParseProfile::Strict`
+    #[error("Rejected by strict parsing: {0}")]
+    StrictParse(String),
+
     /// Try from slice error
     #[error("Try From Slice error: {0}")]
     Slice(#[from] std::array::TryFromSliceError),
@@ -115,10 +240,18 @@ pub enum Error {
     #[error("System Time Error: {0}")]
     Time(#[from] std::time::SystemTimeError),
 
+    /// Unknown backup format version
+    #[error("Unknown backup version = {0}")]
+    UnknownBackupVersion(u32),
+
     /// Unknown event kind
     #[error("Unknown event kind = {0}")]
     UnknownEventKind(u64),
 
+    /// Unknown event kind name
+    #[error("Unknown event kind name = {0}")]
+    UnknownEventKindName(String),
+
     /// Unknown Key Security
     #[error("Unknown key security = {0}")]
     UnknownKeySecurity(u8),
@@ -146,3 +279,543 @@ pub enum Error {
     #[error("Wrong decryption password")]
     WrongDecryptionPassword,
 }
+
+/// A stable, machine-readable identifier for the kind of an `Error`
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    /// An assertion failed, such as a required field or tag being missing
+    AssertionFailed,
+
+    /// An encrypted message is malformed
+    BadEncryptedMessage,
+
+    /// Base64 decoding failed
+    Base64,
+
+    /// Bech32 decoding failed
+    Bech32,
+
+    /// A bech32 string is too short
+    Bech32Truncated,
+
+    /// A bech32 string has a common mistake
+    Bech32Mistake,
+
+    /// Encryption or decryption failed
+    Encryption,
+
+    /// A bech32 string has the wrong prefix
+    WrongBech32,
+
+    /// A signature is invalid
+    Signature,
+
+    /// A signer refused or could not carry out an operation
+    SignerRefused,
+
+    /// An event does not satisfy the conditions of its delegation
+    DelegationViolated,
+
+    /// An event is dated in the future
+    EventInFuture,
+
+    /// A message or event is too large
+    TooLarge,
+
+    /// Formatting failed
+    Fmt,
+
+    /// An event id does not match its contents
+    HashMismatch,
+
+    /// Hex decoding failed
+    HexDecode,
+
+    /// Reading or writing failed
+    Io,
+
+    /// An encrypted private key is invalid
+    InvalidEncryptedPrivateKey,
+
+    /// A geohash, or a location to encode as one, is invalid
+    InvalidGeohash,
+
+    /// A key backup is not in a known format, or is damaged
+    InvalidKeyBackup,
+
+    /// An event template could not be filled in
+    InvalidTemplate,
+
+    /// An event id is invalid
+    InvalidId,
+
+    /// An event id prefix is invalid
+    InvalidIdPrefix,
+
+    /// A digest has an invalid length
+    InvalidLength,
+
+    /// A profile is invalid
+    InvalidProfile,
+
+    /// A public key is invalid
+    InvalidPublicKey,
+
+    /// An event's signature does not verify
+    InvalidSignature,
+
+    /// A public key prefix is invalid
+    InvalidPublicKeyPrefix,
+
+    /// A tag could not be parsed
+    InvalidTag,
+
+    /// A URL could not be parsed
+    InvalidUrl,
+
+    /// A URL has an invalid host
+    InvalidUrlHost,
+
+    /// A URL has an invalid scheme
+    InvalidUrlScheme,
+
+    /// A URL has no authority
+    InvalidUrlMissingAuthority,
+
+    /// A word filter could not be compiled
+    InvalidWordFilter,
+
+    /// A NIP-44 payload could not be encrypted or decrypted
+    Nip44,
+
+    /// A remote signer (NIP-46) returned an error
+    Nip46,
+
+    /// A remote signer (NIP-46) did not answer in time
+    Nip46Timeout,
+
+    /// Encryption padding failed
+    Pad,
+
+    /// LMDB storage failed
+    Lmdb,
+
+    /// An integer could not be parsed
+    ParseInt,
+
+    /// Scrypt failed
+    Scrypt,
+
+    /// JSON (de)serialization failed
+    SerdeJson,
+
+    /// Input was rejected by strict parsing
+    StrictParse,
+
+    /// A slice had the wrong length
+    Slice,
+
+    /// The system time is invalid
+    Time,
+
+    /// A backup's format version is unknown
+    UnknownBackupVersion,
+
+    /// An event kind is unknown
+    UnknownEventKind,
+
+    /// An event kind name is unknown
+    UnknownEventKindName,
+
+    /// A key security level is unknown
+    UnknownKeySecurity,
+
+    /// Decryption padding failed
+    Unpad,
+
+    /// A URL is not a valid relay URL
+    Url,
+
+    /// Text is not valid UTF-8
+    Utf8,
+
+    /// An event has the wrong kind
+    WrongEventKind,
+
+    /// A hex string has the wrong length
+    WrongLengthHexString,
+
+    /// A decryption password is wrong
+    WrongDecryptionPassword,
+}
+
+impl ErrorCode {
+    /// The code as a string, which will not change between versions
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ErrorCode::AssertionFailed => "assertion_failed",
+            ErrorCode::BadEncryptedMessage => "bad_encrypted_message",
+            ErrorCode::Base64 => "base64",
+            ErrorCode::Bech32 => "bech32",
+            ErrorCode::Bech32Truncated => "bech32_truncated",
+            ErrorCode::Bech32Mistake => "bech32_mistake",
+            ErrorCode::Encryption => "encryption",
+            ErrorCode::WrongBech32 => "wrong_bech32",
+            ErrorCode::Signature => "signature",
+            ErrorCode::SignerRefused => "signer_refused",
+            ErrorCode::DelegationViolated => "delegation_violated",
+            ErrorCode::EventInFuture => "event_in_future",
+            ErrorCode::TooLarge => "too_large",
+            ErrorCode::Fmt => "fmt",
+            ErrorCode::HashMismatch => "hash_mismatch",
+            ErrorCode::HexDecode => "hex_decode",
+            ErrorCode::Io => "io",
+            ErrorCode::InvalidEncryptedPrivateKey => "invalid_encrypted_private_key",
+            ErrorCode::InvalidGeohash => "invalid_geohash",
+            ErrorCode::InvalidKeyBackup => "invalid_key_backup",
+            ErrorCode::InvalidTemplate => "invalid_template",
+            ErrorCode::InvalidId => "invalid_id",
+            ErrorCode::InvalidIdPrefix => "invalid_id_prefix",
+            ErrorCode::InvalidLength => "invalid_length",
+            ErrorCode::InvalidProfile => "invalid_profile",
+            ErrorCode::InvalidPublicKey => "invalid_public_key",
+            ErrorCode::InvalidSignature => "invalid_signature",
+            ErrorCode::InvalidPublicKeyPrefix => "invalid_public_key_prefix",
+            ErrorCode::InvalidTag => "invalid_tag",
+            ErrorCode::InvalidUrl => "invalid_url",
+            ErrorCode::InvalidUrlHost => "invalid_url_host",
+            ErrorCode::InvalidUrlScheme => "invalid_url_scheme",
+            ErrorCode::InvalidUrlMissingAuthority => "invalid_url_missing_authority",
+            ErrorCode::InvalidWordFilter => "invalid_word_filter",
+            ErrorCode::Nip44 => "nip44",
+            ErrorCode::Nip46 => "nip46",
+            ErrorCode::Nip46Timeout => "nip46_timeout",
+            ErrorCode::Pad => "pad",
+            ErrorCode::Lmdb => "lmdb",
+            ErrorCode::ParseInt => "parse_int",
+            ErrorCode::Scrypt => "scrypt",
+            ErrorCode::SerdeJson => "serde_json",
+            ErrorCode::StrictParse => "strict_parse",
+            ErrorCode::Slice => "slice",
+            ErrorCode::Time => "time",
+            ErrorCode::UnknownBackupVersion => "unknown_backup_version",
+            ErrorCode::UnknownEventKind => "unknown_event_kind",
+            ErrorCode::UnknownEventKindName => "unknown_event_kind_name",
+            ErrorCode::UnknownKeySecurity => "unknown_key_security",
+            ErrorCode::Unpad => "unpad",
+            ErrorCode::Url => "url",
+            ErrorCode::Utf8 => "utf8",
+            ErrorCode::WrongEventKind => "wrong_event_kind",
+            ErrorCode::WrongLengthHexString => "wrong_length_hex_string",
+            ErrorCode::WrongDecryptionPassword => "wrong_decryption_password",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Error {
+    /// The stable code for this error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::AssertionFailed(_) => ErrorCode::AssertionFailed,
+            Error::BadEncryptedMessage => ErrorCode::BadEncryptedMessage,
+            Error::Base64(_) => ErrorCode::Base64,
+            Error::Bech32(_) => ErrorCode::Bech32,
+            Error::Bech32At { .. } => ErrorCode::Bech32,
+            Error::Bech32Truncated { .. } => ErrorCode::Bech32Truncated,
+            Error::Bech32Mistake { .. } => ErrorCode::Bech32Mistake,
+            Error::Encryption => ErrorCode::Encryption,
+            Error::WrongBech32(_, _) => ErrorCode::WrongBech32,
+            Error::Signature(_) => ErrorCode::Signature,
+            Error::SignerRefused(_) => ErrorCode::SignerRefused,
+            Error::DelegationViolated(_) => ErrorCode::DelegationViolated,
+            Error::EventInFuture { .. } => ErrorCode::EventInFuture,
+            Error::TooLarge { .. } => ErrorCode::TooLarge,
+            Error::Fmt(_) => ErrorCode::Fmt,
+            Error::HashMismatch { .. } => ErrorCode::HashMismatch,
+            Error::HexDecode(_) => ErrorCode::HexDecode,
+            Error::Io(_) => ErrorCode::Io,
+            Error::InvalidEncryptedPrivateKey => ErrorCode::InvalidEncryptedPrivateKey,
+            Error::InvalidGeohash(_) => ErrorCode::InvalidGeohash,
+            Error::InvalidKeyBackup(_) => ErrorCode::InvalidKeyBackup,
+            Error::InvalidTemplate(_) => ErrorCode::InvalidTemplate,
+            Error::InvalidId => ErrorCode::InvalidId,
+            Error::InvalidIdPrefix => ErrorCode::InvalidIdPrefix,
+            Error::InvalidLength(_) => ErrorCode::InvalidLength,
+            Error::InvalidProfile => ErrorCode::InvalidProfile,
+            Error::InvalidPublicKey => ErrorCode::InvalidPublicKey,
+            Error::InvalidSignature(_) => ErrorCode::InvalidSignature,
+            Error::InvalidPublicKeyPrefix => ErrorCode::InvalidPublicKeyPrefix,
+            Error::InvalidTag { .. } => ErrorCode::InvalidTag,
+            Error::InvalidUrl(_) => ErrorCode::InvalidUrl,
+            Error::InvalidUrlHost(_) => ErrorCode::InvalidUrlHost,
+            Error::InvalidUrlScheme(_) => ErrorCode::InvalidUrlScheme,
+            Error::InvalidUrlMissingAuthority => ErrorCode::InvalidUrlMissingAuthority,
+            Error::InvalidWordFilter(_) => ErrorCode::InvalidWordFilter,
+            Error::Nip44(_) => ErrorCode::Nip44,
+            Error::Nip46(_) => ErrorCode::Nip46,
+            Error::Nip46Timeout(_) => ErrorCode::Nip46Timeout,
+            Error::Pad(_) => ErrorCode::Pad,
+            #[cfg(feature = "lmdb")]
+            Error::Lmdb(_) => ErrorCode::Lmdb,
+            Error::ParseInt(_) => ErrorCode::ParseInt,
+            Error::Scrypt => ErrorCode::Scrypt,
+            Error::SerdeJson(_) => ErrorCode::SerdeJson,
+            Error::StrictParse(_) => ErrorCode::StrictParse,
+            Error::Slice(_) => ErrorCode::Slice,
+            Error::Time(_) => ErrorCode::Time,
+            Error::UnknownBackupVersion(_) => ErrorCode::UnknownBackupVersion,
+            Error::UnknownEventKind(_) => ErrorCode::UnknownEventKind,
+            Error::UnknownEventKindName(_) => ErrorCode::UnknownEventKindName,
+            Error::UnknownKeySecurity(_) => ErrorCode::UnknownKeySecurity,
+            Error::Unpad(_) => ErrorCode::Unpad,
+            Error::Url(_) => ErrorCode::Url,
+            Error::Utf8Error(_) => ErrorCode::Utf8,
+            Error::WrongEventKind => ErrorCode::WrongEventKind,
+            Error::WrongLengthHexString => ErrorCode::WrongLengthHexString,
+            Error::WrongDecryptionPassword => ErrorCode::WrongDecryptionPassword,
+        }
+    }
+}
+
+/// A likely user mistake behind a failed bech32 decode, for showing a
+/// helpful message. See `Error::bech32_suggestion()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Bech32Suggestion {
+    /// It is a different kind of string, such as an "npub" where an "nsec"
+    /// was expected
+    DetectedOtherHrp(String),
+
+    /// It mixes upper and lower case; it must be all one case
+    MixedCase,
+
+    /// The '1' after the prefix is missing
+    MissingSeparator,
+
+    /// It was cut short by this many characters
+    Truncated(usize),
+
+    /// It is a "nostr:" URL; remove the "nostr:"
+    StripNostrPrefix,
+
+    /// It is hex, not bech32
+    HexGiven,
+}
+
+impl fmt::Display for Bech32Suggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bech32Suggestion::DetectedOtherHrp(hrp) => write!(f, "but this is an {hrp}"),
+            Bech32Suggestion::MixedCase => write!(f, "use all lowercase"),
+            Bech32Suggestion::MissingSeparator => write!(f, "the '1' after the prefix is missing"),
+            Bech32Suggestion::Truncated(missing) => {
+                write!(f, "{missing} characters are missing from the end")
+            }
+            Bech32Suggestion::StripNostrPrefix => write!(f, "remove the \"nostr:\" prefix"),
+            Bech32Suggestion::HexGiven => write!(f, "but this is hex"),
+        }
+    }
+}
+
+impl Error {
+    /// The likely user mistake behind a bech32 error, if recognized
+    pub fn bech32_suggestion(&self) -> Option<Bech32Suggestion> {
+        match self {
+            Error::WrongBech32(_, found) => Some(Bech32Suggestion::DetectedOtherHrp(found.clone())),
+            Error::Bech32Truncated { expected, found } => {
+                Some(Bech32Suggestion::Truncated(expected.saturating_sub(*found)))
+            }
+            Error::Bech32(bech32::Error::MixedCase)
+            | Error::Bech32At {
+                source: bech32::Error::MixedCase,
+                ..
+            } => Some(Bech32Suggestion::MixedCase),
+            Error::Bech32(bech32::Error::MissingSeparator) => {
+                Some(Bech32Suggestion::MissingSeparator)
+            }
+            Error::Bech32Mistake { suggestion, .. } => Some(suggestion.clone()),
+            _ => None,
+        }
+    }
+}
+
+// Decode bech32, reporting where in the input a bad character is
+pub(crate) fn bech32_decode(s: &str) -> Result<(String, Vec<bech32::u5>, bech32::Variant), Error> {
+    bech32::decode(s).map_err(|e| {
+        let offset = match e {
+            bech32::Error::InvalidChar(c) => s.find(c),
+            // The first letter in a different case to the first letter
+            bech32::Error::MixedCase => s.chars().find(|c| c.is_alphabetic()).and_then(|first| {
+                s.find(|c: char| c.is_alphabetic() && c.is_lowercase() != first.is_lowercase())
+            }),
+            _ => None,
+        };
+        match offset {
+            Some(offset) => Error::Bech32At { offset, source: e },
+            None => Error::Bech32(e),
+        }
+    })
+}
+
+// Decode bech32 expecting the human readable part `hrp`, returning the data.
+// The prefix is checked first, so that a key of another kind is reported as
+// such rather than as a bad checksum. `expected_len` is the full length of
+// fixed size encodings, used to recognize truncated input.
+pub(crate) fn bech32_decode_as(
+    s: &str,
+    hrp: &str,
+    expected_len: Option<usize>,
+) -> Result<Vec<bech32::u5>, Error> {
+    let mistake = |suggestion: Bech32Suggestion| Error::Bech32Mistake {
+        expected: hrp.to_owned(),
+        suggestion,
+    };
+    if s.starts_with("nostr:") {
+        return Err(mistake(Bech32Suggestion::StripNostrPrefix));
+    }
+    if s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(mistake(Bech32Suggestion::HexGiven));
+    }
+    // The data part never contains a '1', so without one the prefix runs on
+    if !s.contains('1') && s.to_lowercase().starts_with(hrp) {
+        return Err(mistake(Bech32Suggestion::MissingSeparator));
+    }
+    if let Some((found, _)) = s.rsplit_once('1') {
+        if !found.eq_ignore_ascii_case(hrp) {
+            return Err(Error::WrongBech32(hrp.to_owned(), found.to_lowercase()));
+        }
+    }
+    match bech32_decode(s) {
+        Ok((_, data, _)) => Ok(data),
+        Err(Error::Bech32(bech32::Error::InvalidChecksum | bech32::Error::InvalidLength))
+            if expected_len.map(|n| s.len() < n).unwrap_or(false) =>
+        {
+            Err(Error::Bech32Truncated {
+                expected: expected_len.unwrap_or(0),
+                found: s.len(),
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        assert_eq!(Error::WrongEventKind.code(), ErrorCode::WrongEventKind);
+        assert_eq!(Error::WrongEventKind.code().as_str(), "wrong_event_kind");
+        let error = Error::InvalidTag {
+            index: 3,
+            message: "bad amount".to_owned(),
+        };
+        assert_eq!(error.code().to_string(), "invalid_tag");
+        assert_eq!(format!("{error}"), "Invalid tag at index 3: bad amount");
+    }
+
+    #[test]
+    fn test_bech32_offsets() {
+        let npub = "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6";
+        assert!(bech32_decode(npub).is_ok());
+
+        let bad_char = npub.replacen('c', "o", 1);
+        match bech32_decode(&bad_char) {
+            Err(e @ Error::Bech32At { offset: 7, .. }) => assert_eq!(e.code(), ErrorCode::Bech32),
+            other => panic!("Unexpected {other:?}"),
+        }
+
+        let mixed_case = npub.replacen('v', "V", 1);
+        assert!(matches!(
+            bech32_decode(&mixed_case),
+            Err(Error::Bech32At { offset: 8, .. })
+        ));
+
+        assert!(matches!(bech32_decode("npub1"), Err(Error::Bech32(_))));
+    }
+
+    #[test]
+    fn test_bech32_causes() {
+        let npub = "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6";
+        assert_eq!(bech32_decode_as(npub, "npub", Some(63)).unwrap().len(), 52);
+
+        let truncated = npub.get(..60).unwrap();
+        let error = bech32_decode_as(truncated, "npub", Some(63)).unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Bech32Truncated {
+                expected: 63,
+                found: 60
+            }
+        ));
+        assert_eq!(error.code(), ErrorCode::Bech32Truncated);
+
+        // A checksum failure at full length is not truncation
+        let typo = npub.replacen("80c", "80d", 1);
+        assert!(matches!(
+            bech32_decode_as(&typo, "npub", Some(63)),
+            Err(Error::Bech32(bech32::Error::InvalidChecksum))
+        ));
+
+        // The prefix is checked before the checksum
+        let error = bech32_decode_as(truncated, "nsec", Some(63)).unwrap_err();
+        assert_eq!(
+            format!("{error}"),
+            "Wrong Bech32 Kind: Expected nsec found npub"
+        );
+
+        assert!(matches!(
+            bech32_decode_as(&npub.replacen('v', "V", 1), "npub", Some(63)),
+            Err(Error::Bech32At { offset: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn test_bech32_suggestions() {
+        let npub = "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6";
+        let suggest = |s: &str, hrp: &str| {
+            bech32_decode_as(s, hrp, Some(63))
+                .unwrap_err()
+                .bech32_suggestion()
+        };
+
+        assert_eq!(
+            suggest(npub, "nsec"),
+            Some(Bech32Suggestion::DetectedOtherHrp("npub".to_owned()))
+        );
+        assert_eq!(
+            suggest(&npub.replacen('v', "V", 1), "npub"),
+            Some(Bech32Suggestion::MixedCase)
+        );
+        assert_eq!(
+            suggest(&npub.replacen('1', "", 1), "npub"),
+            Some(Bech32Suggestion::MissingSeparator)
+        );
+        assert_eq!(
+            suggest(npub.get(..60).unwrap(), "npub"),
+            Some(Bech32Suggestion::Truncated(3))
+        );
+        assert_eq!(
+            suggest(&format!("nostr:{npub}"), "npub"),
+            Some(Bech32Suggestion::StripNostrPrefix)
+        );
+        let hex = "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d";
+        let error = bech32_decode_as(hex, "npub", Some(63)).unwrap_err();
+        assert_eq!(error.bech32_suggestion(), Some(Bech32Suggestion::HexGiven));
+        assert_eq!(error.code(), ErrorCode::Bech32Mistake);
+        assert_eq!(
+            format!("{error}"),
+            "Bech32 Error: expected npub, but this is hex"
+        );
+
+        let typo = npub.replacen("80c", "80d", 1);
+        assert_eq!(suggest(&typo, "npub"), None);
+    }
+}
@@ -27,14 +27,46 @@
 #![deny(clippy::string_slice)]
 
 mod error;
-pub use error::Error;
+pub use error::{Bech32Suggestion, Error, ErrorCode};
 
 mod types;
 pub use types::{
-    find_nostr_bech32_pos, find_nostr_url_pos, ClientMessage, DelegationConditions,
-    EncryptedPrivateKey, Event, EventDelegation, EventKind, EventKindIterator, EventPointer,
-    Filter, Id, IdHex, IdHexPrefix, KeySecurity, Metadata, Nip05, NostrBech32, NostrUrl,
-    PayRequestData, PreEvent, PrivateKey, Profile, PublicKey, PublicKeyHex, PublicKeyHexPrefix,
-    RelayInformationDocument, RelayLimitation, RelayMessage, RelayUrl, Signature, SignatureHex,
-    SimpleRelayList, SimpleRelayUsage, SubscriptionId, Tag, UncheckedUrl, Unixtime, Url,
+    canonical_escape, connect_relay, find_nostr_bech32_pos, find_nostr_url_pos,
+    giftwrap_for_recipients, giftwrap_for_recipients_with_rng, omit_client_tag,
+    plan_giftwrap_publish, plan_publish, register_tag_type, registered_tag_names,
+    reset_crypto_provider, set_crypto_provider, set_omit_client_tag, AccountBackup,
+    AddressPreference, AuthorResolver, Backfiller, BannedPubkeys, BunkerUrl, CashuDirection,
+    CashuDleq, CashuMintAnnouncement, CashuProof, CashuQuote, CashuSpendingHistory, CashuToken,
+    CashuWallet, ChangeSet, ClientMessage, ConnectAttempt, ConnectConfig, ConnectReport,
+    ConnectionHealth, Contact, ContactList, ContactListSplit, ContentReference, ContentStats,
+    CreatedAtBounds, CryptoProvider, DefaultCryptoProvider, DelegationConditions, DmRelayList,
+    EffectiveAuthor, EncryptedDirectMessage, EncryptedFile, EncryptedPrivateKey, EoseTracker,
+    EphemeralEvent, Escape, Event, EventAddr, EventCache, EventDelegation, EventKind,
+    EventKindIterator, EventOrder, EventPointer, EventReader, EventReference, EventStats,
+    EventStorage, EventWriter, FedimintAnnouncement, Filter, FilterEngine, Geohash, GiftWrap,
+    HintResolver, HintStrategy, Id, IdHex, IdHexPrefix, IdSetDigest, IdentityBundle, Imeta,
+    Keepalive, KeepaliveConfig, KeyMigration, KeySecurity, KeyWhitelist, KindAllowlist, LiveChat,
+    LiveChatItem, MaxEventSize, MessageSizeLimits, Metadata, MinimumPow, MintNetwork,
+    MintRecommendation, MuteEngine, NameResolver, Nip05, Nip46Decision, Nip46Policy, Nip46Request,
+    Nip46Response, Nip46Service, Nip46Session, Nip46Transport, NostrBech32, NostrUrl, NoticeKind,
+    Nutzap, NutzapInfo, NutzapMint, OkPrefix, OrderBy, ParseProfile, ParsedTag, PayRequestData,
+    PendingPublishes, Policy, PolicyDecision, PolicySet, PreEvent, PrivateDirectMessage,
+    PrivateKey, PrivateMessageBody, Profile, ProxyProtocol, PubkeyRateLimit, PubkeySet, PublicKey,
+    PublicKeyHex, PublicKeyHexPrefix, PublishOutcome, PublishReport, Publisher, RelayCapabilities,
+    RelayFee, RelayFees, RelayInformationDocument, RelayLimitation, RelayLimits, RelayMessage,
+    RelayPicker, RelayPicks, RelayPool, RelayProbe, RelayStats, RelayUrl, ReqViolation,
+    Resubscribed, Resubscriber, RetentionPolicy, Rumor, SanitizeOptions, SanitizeReport,
+    SanitizedContent, Seal, SeenEvent, SeenEvents, Signature, SignatureHex, Signer, SignerPolicy,
+    SimpleRelayList, SimpleRelayUsage, SkippedLine, SubscriptionId, SweepReport, Sweeper, Tag,
+    TagOrder, ThreadNode, ThreadTree, TieBreak, UncheckedUrl, Unixtime, Url, VanishRequest,
+    VanishTarget, VerifiedEvent, VerifierConfig, VerifierPool, VerifyOrder, Video, WhitelistEntry,
+    WikiArticle, WikiMergeRequest, WikiReference, WikiRelation, WireDirection, WireFrame, WireLog,
+    WireMessage, WireReplay, WordFilter,
 };
+
+#[cfg(feature = "lenient-serde")]
+pub use types::Lenient;
+#[cfg(feature = "lmdb")]
+pub use types::LmdbStorage;
+#[cfg(feature = "intern")]
+pub use types::{InternedStorage, TagInterner};
@@ -0,0 +1,199 @@
+use super::{Event, EventKind, Filter, PublicKey, PublicKeyHex, Unixtime};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+
+// The kinds kept in a backup: profile, follows, relay lists and NIP-51 lists
+const BACKUP_KINDS: &[EventKind] = &[
+    EventKind::Metadata,
+    EventKind::ContactList,
+    EventKind::RelayList,
+    EventKind::DmRelayList,
+    EventKind::MuteList,
+    EventKind::BookmarkList,
+    EventKind::CommunityList,
+    EventKind::PublicChatList,
+    EventKind::BlockedRelayList,
+    EventKind::SearchRelayList,
+    EventKind::SimpleGroupList,
+    EventKind::InterestList,
+    EventKind::EmojiList,
+    EventKind::FollowSets,
+    EventKind::RelaySets,
+    EventKind::BookmarkSets,
+    EventKind::CurationSets,
+    EventKind::VideoSets,
+    EventKind::KindMuteSets,
+    EventKind::InterestSets,
+    EventKind::EmojiSets,
+];
+
+/// A portable backup of a user's profile, contacts, relay lists and NIP-51
+/// lists, as one JSON file.
+///
+/// Every event in it is signed by the user, so it can be restored to any
+/// relay as it is, and `from_json()` rejects a backup that was tampered
+/// with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccountBackup {
+    /// The format version, `AccountBackup::VERSION` when written
+    pub version: u32,
+
+    /// Whose account this is
+    pub pubkey: PublicKey,
+
+    /// When the backup was made
+    pub created_at: Unixtime,
+
+    /// The latest version of each event, ordered by kind
+    pub events: Vec<Event>,
+}
+
+#[derive(Deserialize)]
+struct BackupVersion {
+    version: u32,
+}
+
+impl AccountBackup {
+    /// The format version this library writes
+    pub const VERSION: u32 = 1;
+
+    /// An empty backup of `pubkey`'s account, made at `now`
+    pub fn new(pubkey: PublicKey, now: Unixtime) -> AccountBackup {
+        AccountBackup {
+            version: Self::VERSION,
+            pubkey,
+            created_at: now,
+            events: Vec::new(),
+        }
+    }
+
+    /// The kinds of event a backup keeps
+    pub fn kinds() -> &'static [EventKind] {
+        BACKUP_KINDS
+    }
+
+    /// A filter for all the events to back up
+    pub fn filter(&self) -> Filter {
+        let mut filter = Filter::new();
+        filter.add_author(PublicKeyHex::from(self.pubkey));
+        for kind in BACKUP_KINDS {
+            filter.add_event_kind(*kind);
+        }
+        filter
+    }
+
+    /// Add an event, replacing an older version of it. Returns false if a
+    /// newer version is already held.
+    ///
+    /// Fails if the event is not by this user, not of a kind kept, or does
+    /// not verify.
+    pub fn add(&mut self, event: Event) -> Result<bool, Error> {
+        self.check(&event)?;
+        let address = |e: &Event| (e.kind, e.parameter().unwrap_or_default());
+        match self
+            .events
+            .iter()
+            .position(|e| address(e) == address(&event))
+        {
+            Some(index) => {
+                if !event.replaces(&self.events[index]) {
+                    return Ok(false);
+                }
+                self.events[index] = event;
+            }
+            None => {
+                self.events.push(event);
+                self.events.sort_by_key(|e| u64::from(e.kind));
+            }
+        }
+        Ok(true)
+    }
+
+    /// Write as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String, Error> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Read a backup, checking its version and every event in it
+    pub fn from_json(json: &str) -> Result<AccountBackup, Error> {
+        let version: BackupVersion = serde_json::from_str(json)?;
+        if version.version == 0 || version.version > Self::VERSION {
+            return Err(Error::UnknownBackupVersion(version.version));
+        }
+        let backup: AccountBackup = serde_json::from_str(json)?;
+        for event in &backup.events {
+            backup.check(event)?;
+        }
+        Ok(backup)
+    }
+
+    fn check(&self, event: &Event) -> Result<(), Error> {
+        if !BACKUP_KINDS.contains(&event.kind) {
+            return Err(Error::WrongEventKind);
+        }
+        if event.pubkey != self.pubkey {
+            return Err(Error::AssertionFailed(
+                "Backup event is by someone else".to_owned(),
+            ));
+        }
+        event.verify(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PreEvent, PrivateKey, Tag};
+
+    #[test]
+    fn test_account_backup() {
+        let key = PrivateKey::generate();
+        let event = |kind: EventKind, created_at: i64, tags: Vec<Tag>| {
+            let pre = PreEvent {
+                pubkey: key.public_key(),
+                created_at: Unixtime(created_at),
+                kind,
+                tags,
+                content: "".to_owned(),
+                ots: None,
+            };
+            Event::new(pre, &key).unwrap()
+        };
+        let set = |d: &str| vec![Tag::Identifier(d.to_owned())];
+
+        let mut backup = AccountBackup::new(key.public_key(), Unixtime(1700001000));
+        let contacts = event(EventKind::ContactList, 1700000100, vec![]);
+        assert!(backup.add(contacts.clone()).unwrap());
+        assert!(backup
+            .add(event(EventKind::Metadata, 1700000000, vec![]))
+            .unwrap());
+        assert!(!backup
+            .add(event(EventKind::ContactList, 1700000000, vec![]))
+            .unwrap());
+        assert!(backup
+            .add(event(EventKind::FollowSets, 1700000000, set("a")))
+            .unwrap());
+        assert!(backup
+            .add(event(EventKind::FollowSets, 1700000000, set("b")))
+            .unwrap());
+        assert!(matches!(
+            backup.add(event(EventKind::TextNote, 1700000000, vec![])),
+            Err(Error::WrongEventKind)
+        ));
+        assert!(backup.add(Event::mock()).is_err());
+        assert_eq!(backup.events.len(), 4);
+        assert_eq!(backup.events[0].kind, EventKind::Metadata);
+        assert_eq!(backup.events[1], contacts);
+
+        let json = backup.to_json().unwrap();
+        assert_eq!(AccountBackup::from_json(&json).unwrap(), backup);
+
+        let tampered = json.replacen("\"kind\": 3", "\"kind\": 10000", 1);
+        assert!(AccountBackup::from_json(&tampered).is_err());
+        let future = json.replacen("\"version\": 1", "\"version\": 2", 1);
+        assert!(matches!(
+            AccountBackup::from_json(&future),
+            Err(Error::UnknownBackupVersion(2))
+        ));
+    }
+}
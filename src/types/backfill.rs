@@ -0,0 +1,169 @@
+use super::{Event, Filter, Unixtime};
+use std::time::Duration;
+
+/// Pages backwards through history with successive since/until windows, so
+/// that all matching events can be fetched from relays which cap `limit`.
+///
+/// Call `next_filter()` to get the filter for a REQ, then pass the events that
+/// came back (up to EOSE) to `record()`, until `next_filter()` returns None.
+/// Windows are sized from the density of events seen so far, aiming to fill
+/// about half of the limit. When a window comes back full the relay may have
+/// cut it short, so the next window picks up from the oldest event received.
+/// That event's second is fetched again, so expect (and drop) duplicates.
+#[derive(Clone, Debug)]
+pub struct Backfiller {
+    filter: Filter,
+    since: i64,
+    cursor: i64,
+    window: i64,
+    limit: usize,
+    current: Option<(i64, i64)>,
+}
+
+impl Backfiller {
+    /// Backfill events matching `filter` from `until` back to `since`,
+    /// expecting relays to return no more than `limit` events per REQ. Any
+    /// since, until or limit in the filter are replaced.
+    pub fn new(filter: Filter, since: Unixtime, until: Unixtime, limit: usize) -> Backfiller {
+        Backfiller {
+            filter,
+            since: since.0,
+            cursor: until.0,
+            window: 3600,
+            limit: limit.max(1),
+            current: None,
+        }
+    }
+
+    /// Start with a window of this length rather than the default of an hour
+    pub fn initial_window(mut self, window: Duration) -> Backfiller {
+        self.window = (window.as_secs() as i64).max(1);
+        self
+    }
+
+    /// Has the whole range been fetched?
+    pub fn is_done(&self) -> bool {
+        self.cursor < self.since
+    }
+
+    /// The filter for the next window, or None if the backfill is done. This
+    /// returns the same window again until `record()` is called.
+    pub fn next_filter(&mut self) -> Option<Filter> {
+        if self.is_done() {
+            return None;
+        }
+        let (start, end) = match self.current {
+            Some(window) => window,
+            None => {
+                let start = (self.cursor - self.window + 1).max(self.since);
+                self.current = Some((start, self.cursor));
+                (start, self.cursor)
+            }
+        };
+
+        let mut filter = self.filter.clone();
+        filter.since = Some(Unixtime(start));
+        filter.until = Some(Unixtime(end));
+        filter.limit = Some(self.limit);
+        Some(filter)
+    }
+
+    /// Record the events that the relay returned for the last window
+    pub fn record(&mut self, events: &[Event]) {
+        let (start, end) = match self.current.take() {
+            Some(window) => window,
+            None => return,
+        };
+        let target = (self.limit / 2).max(1) as i64;
+
+        if events.len() < self.limit {
+            // The window is complete. Size the next one for the density seen.
+            self.cursor = start - 1;
+            let span = end - start + 1;
+            self.window = if events.is_empty() {
+                span * 2
+            } else {
+                (span * target / events.len() as i64).clamp(1, span * 4)
+            };
+            return;
+        }
+
+        // The relay may have held some back. It returns the newest first, so
+        // everything after the oldest event we got is complete.
+        let oldest = events
+            .iter()
+            .map(|e| e.created_at.0)
+            .min()
+            .unwrap_or(start)
+            .max(start);
+        let span = end - oldest + 1;
+        self.window = (span * target / events.len() as i64).max(1);
+        self.cursor = if oldest == end {
+            // A whole limit of events within a single second can't be paged
+            // through by time, so skip past that second
+            oldest - 1
+        } else {
+            oldest
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EventKind, PreEvent, PrivateKey};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_backfiller() {
+        let private_key = PrivateKey::generate();
+        // A quiet period, then a busy one
+        let times: Vec<i64> = (0..20)
+            .map(|i| 1000 + i * 100)
+            .chain((0..60).map(|i| 4000 + i * 5))
+            .collect();
+        let events: Vec<Event> = times
+            .iter()
+            .map(|t| {
+                let pre = PreEvent {
+                    pubkey: private_key.public_key(),
+                    created_at: Unixtime(*t),
+                    kind: EventKind::TextNote,
+                    tags: vec![],
+                    content: format!("{t}"),
+                    ots: None,
+                };
+                Event::new(pre, &private_key).unwrap()
+            })
+            .collect();
+
+        // A relay returning the newest matches, no more than its limit
+        let relay = |filter: &Filter| -> Vec<Event> {
+            let mut matches: Vec<Event> = events
+                .iter()
+                .filter(|e| filter.event_matches(e))
+                .cloned()
+                .collect();
+            matches.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+            matches.truncate(filter.limit.unwrap());
+            matches
+        };
+
+        let mut backfiller = Backfiller::new(Filter::new(), Unixtime(0), Unixtime(5000), 10)
+            .initial_window(Duration::from_secs(2000));
+        let mut seen = HashSet::new();
+        let mut requests = 0;
+        while let Some(filter) = backfiller.next_filter() {
+            assert_eq!(filter.limit, Some(10));
+            let received = relay(&filter);
+            for event in received.iter() {
+                let _ = seen.insert(event.id);
+            }
+            backfiller.record(&received);
+            requests += 1;
+            assert!(requests < 100);
+        }
+        assert!(backfiller.is_done());
+        assert_eq!(seen.len(), events.len());
+    }
+}
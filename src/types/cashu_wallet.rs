@@ -0,0 +1,499 @@
+use super::{Event, EventKind, Id, PreEvent, PublicKey, Tag, UncheckedUrl, Unixtime};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+
+// NIP-60 event contents are NIP-44 encrypted by the author to themselves. This
+// module works on the plaintext, and the caller supplies the encryption and
+// decryption as closures (typically self-encryption with their own private key).
+
+/// A DLEQ proof attached to a Cashu proof (NUT-12)
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CashuDleq {
+    /// e
+    pub e: String,
+
+    /// s
+    pub s: String,
+
+    /// r, the blinding factor, if the proof is being transferred
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub r: Option<String>,
+}
+
+/// An unblinded Cashu proof (a single ecash note)
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CashuProof {
+    /// The keyset id of the mint key that signed this proof
+    pub id: String,
+
+    /// The amount
+    pub amount: u64,
+
+    /// The secret
+    pub secret: String,
+
+    /// The unblinded signature, as hex
+    #[serde(rename = "C")]
+    pub c: String,
+
+    /// A witness for spending conditions (e.g. a P2PK signature), as a JSON string
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub witness: Option<String>,
+
+    /// A DLEQ proof
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub dleq: Option<CashuDleq>,
+}
+
+impl CashuProof {
+    /// If the secret is a P2PK spending condition (NUT-11), the compressed hex
+    /// public key the proof is locked to
+    pub fn p2pk_pubkey(&self) -> Option<String> {
+        let secret: serde_json::Value = serde_json::from_str(&self.secret).ok()?;
+        let array = secret.as_array()?;
+        if array.first()?.as_str()? != "P2PK" {
+            return None;
+        }
+        Some(array.get(1)?.get("data")?.as_str()?.to_owned())
+    }
+
+    // Mock data for testing
+    #[allow(dead_code)]
+    pub(crate) fn mock() -> CashuProof {
+        CashuProof {
+            id: "005c2502034d4f12".to_owned(),
+            amount: 1,
+            secret: "z+zyxAVLRqN9lEjxuNPSyRJzEstbl69Jc1vtimvtkPg=".to_owned(),
+            c: "0241d98a8197ef238a192d47edf191a9de78b657308937b4f7dd0aa53beae72c46".to_owned(),
+            witness: None,
+            dleq: None,
+        }
+    }
+}
+
+/// A Cashu wallet (NIP-60), kind 17375
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CashuWallet {
+    /// The hex private key used to unlock P2PK ecash sent to this wallet. This is
+    /// not the user's nostr private key.
+    pub privkey: String,
+
+    /// The mints this wallet uses
+    pub mints: Vec<UncheckedUrl>,
+}
+
+impl CashuWallet {
+    /// Create a `PreEvent` for this wallet. `encrypt` is given the plaintext content
+    /// and must return the NIP-44 ciphertext.
+    pub fn to_pre_event<F>(&self, pubkey: PublicKey, encrypt: F) -> Result<PreEvent, Error>
+    where
+        F: Fn(&str) -> Result<String, Error>,
+    {
+        let mut pairs: Vec<Vec<String>> = vec![vec!["privkey".to_owned(), self.privkey.clone()]];
+        for mint in self.mints.iter() {
+            pairs.push(vec!["mint".to_owned(), mint.0.clone()]);
+        }
+        let plaintext = serde_json::to_string(&pairs)?;
+
+        Ok(PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::CashuWallet,
+            tags: vec![],
+            content: encrypt(&plaintext)?,
+            ots: None,
+        })
+    }
+
+    /// Interpret an event as a wallet. `decrypt` is given the event content and must
+    /// return the NIP-44 plaintext.
+    pub fn try_from_event<F>(event: &Event, decrypt: F) -> Result<CashuWallet, Error>
+    where
+        F: Fn(&str) -> Result<String, Error>,
+    {
+        if event.kind != EventKind::CashuWallet {
+            return Err(Error::WrongEventKind);
+        }
+
+        let pairs: Vec<Vec<String>> = serde_json::from_str(&decrypt(&event.content)?)?;
+        let mut privkey: Option<String> = None;
+        let mut mints: Vec<UncheckedUrl> = Vec::new();
+        for pair in pairs.iter() {
+            match (pair.first().map(|s| s.as_str()), pair.get(1)) {
+                (Some("privkey"), Some(key)) => privkey = Some(key.clone()),
+                (Some("mint"), Some(mint)) => mints.push(UncheckedUrl(mint.clone())),
+                _ => {}
+            }
+        }
+
+        Ok(CashuWallet {
+            privkey: privkey
+                .ok_or_else(|| Error::AssertionFailed("Cashu wallet has no privkey".to_owned()))?,
+            mints,
+        })
+    }
+}
+
+/// Unspent Cashu proofs held by a wallet (NIP-60), kind 7375
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CashuToken {
+    /// The mint the proofs belong to
+    pub mint: UncheckedUrl,
+
+    /// The unit of the proofs, "sat" if not specified
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub unit: Option<String>,
+
+    /// The unspent proofs
+    pub proofs: Vec<CashuProof>,
+
+    /// Token events which were destroyed in creating this one
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub del: Vec<Id>,
+}
+
+impl CashuToken {
+    /// The total amount of the proofs
+    pub fn amount(&self) -> u64 {
+        self.proofs.iter().map(|p| p.amount).sum()
+    }
+
+    /// Create a `PreEvent` for this token. `encrypt` is given the plaintext content
+    /// and must return the NIP-44 ciphertext.
+    pub fn to_pre_event<F>(&self, pubkey: PublicKey, encrypt: F) -> Result<PreEvent, Error>
+    where
+        F: Fn(&str) -> Result<String, Error>,
+    {
+        Ok(PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::CashuToken,
+            tags: vec![],
+            content: encrypt(&serde_json::to_string(self)?)?,
+            ots: None,
+        })
+    }
+
+    /// Interpret an event as a token. `decrypt` is given the event content and must
+    /// return the NIP-44 plaintext.
+    pub fn try_from_event<F>(event: &Event, decrypt: F) -> Result<CashuToken, Error>
+    where
+        F: Fn(&str) -> Result<String, Error>,
+    {
+        if event.kind != EventKind::CashuToken {
+            return Err(Error::WrongEventKind);
+        }
+        Ok(serde_json::from_str(&decrypt(&event.content)?)?)
+    }
+
+    // Mock data for testing
+    #[allow(dead_code)]
+    pub(crate) fn mock() -> CashuToken {
+        CashuToken {
+            mint: UncheckedUrl::from_str("https://stablenut.umint.cash"),
+            unit: None,
+            proofs: vec![CashuProof::mock()],
+            del: vec![],
+        }
+    }
+}
+
+/// The direction of a wallet transaction
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CashuDirection {
+    /// Received
+    In,
+
+    /// Sent
+    Out,
+}
+
+/// A record of a wallet transaction (NIP-60), kind 7376
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CashuSpendingHistory {
+    /// Whether funds came in or went out
+    pub direction: CashuDirection,
+
+    /// The amount
+    pub amount: u64,
+
+    /// The unit, "sat" if not specified
+    pub unit: Option<String>,
+
+    /// Token events that were created by this transaction (encrypted)
+    pub created: Vec<Id>,
+
+    /// Token events that were destroyed by this transaction (encrypted)
+    pub destroyed: Vec<Id>,
+
+    /// Nutzap events that were redeemed by this transaction (public)
+    pub redeemed: Vec<Id>,
+}
+
+impl CashuSpendingHistory {
+    /// Create a `PreEvent` for this record. `encrypt` is given the plaintext content
+    /// and must return the NIP-44 ciphertext.
+    pub fn to_pre_event<F>(&self, pubkey: PublicKey, encrypt: F) -> Result<PreEvent, Error>
+    where
+        F: Fn(&str) -> Result<String, Error>,
+    {
+        let direction = match self.direction {
+            CashuDirection::In => "in",
+            CashuDirection::Out => "out",
+        };
+        let mut pairs: Vec<Vec<String>> = vec![
+            vec!["direction".to_owned(), direction.to_owned()],
+            vec!["amount".to_owned(), format!("{}", self.amount)],
+        ];
+        if let Some(unit) = &self.unit {
+            pairs.push(vec!["unit".to_owned(), unit.clone()]);
+        }
+        for (ids, marker) in [(&self.created, "created"), (&self.destroyed, "destroyed")] {
+            for id in ids.iter() {
+                pairs.push(vec![
+                    "e".to_owned(),
+                    id.as_hex_string(),
+                    "".to_owned(),
+                    marker.to_owned(),
+                ]);
+            }
+        }
+        let plaintext = serde_json::to_string(&pairs)?;
+
+        let tags: Vec<Tag> = self
+            .redeemed
+            .iter()
+            .map(|id| Tag::Event {
+                id: *id,
+                recommended_relay_url: Some(UncheckedUrl::default()),
+                marker: Some("redeemed".to_owned()),
+                pubkey: None,
+            })
+            .collect();
+
+        Ok(PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::CashuSpendingHistory,
+            tags,
+            content: encrypt(&plaintext)?,
+            ots: None,
+        })
+    }
+
+    /// Interpret an event as a spending history record. `decrypt` is given the event
+    /// content and must return the NIP-44 plaintext.
+    pub fn try_from_event<F>(event: &Event, decrypt: F) -> Result<CashuSpendingHistory, Error>
+    where
+        F: Fn(&str) -> Result<String, Error>,
+    {
+        if event.kind != EventKind::CashuSpendingHistory {
+            return Err(Error::WrongEventKind);
+        }
+
+        let pairs: Vec<Vec<String>> = serde_json::from_str(&decrypt(&event.content)?)?;
+        let mut direction: Option<CashuDirection> = None;
+        let mut amount: Option<u64> = None;
+        let mut unit: Option<String> = None;
+        let mut created: Vec<Id> = Vec::new();
+        let mut destroyed: Vec<Id> = Vec::new();
+        for pair in pairs.iter() {
+            match (pair.first().map(|s| s.as_str()), pair.get(1)) {
+                (Some("direction"), Some(d)) if d == "in" => direction = Some(CashuDirection::In),
+                (Some("direction"), Some(d)) if d == "out" => direction = Some(CashuDirection::Out),
+                (Some("amount"), Some(a)) => amount = Some(a.parse::<u64>()?),
+                (Some("unit"), Some(u)) => unit = Some(u.clone()),
+                (Some("e"), Some(id)) => match pair.get(3).map(|s| s.as_str()) {
+                    Some("created") => created.push(Id::try_from_hex_string(id)?),
+                    Some("destroyed") => destroyed.push(Id::try_from_hex_string(id)?),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        let redeemed: Vec<Id> = event
+            .tags
+            .iter()
+            .filter_map(|t| match t {
+                Tag::Event {
+                    id,
+                    marker: Some(m),
+                    ..
+                } if m == "redeemed" => Some(*id),
+                _ => None,
+            })
+            .collect();
+
+        let missing =
+            |what: &str| Error::AssertionFailed(format!("Spending history has no {what}"));
+        Ok(CashuSpendingHistory {
+            direction: direction.ok_or_else(|| missing("direction"))?,
+            amount: amount.ok_or_else(|| missing("amount"))?,
+            unit,
+            created,
+            destroyed,
+            redeemed,
+        })
+    }
+}
+
+/// A pending mint quote (NIP-60), kind 7374
+///
+/// This lets a wallet recover a paid-but-unclaimed quote from another device.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CashuQuote {
+    /// The mint's quote id (encrypted)
+    pub quote_id: String,
+
+    /// The mint the quote is from
+    pub mint: UncheckedUrl,
+
+    /// When the quote expires; relays may delete the event after this
+    pub expiration: Unixtime,
+}
+
+impl CashuQuote {
+    /// Create a `PreEvent` for this quote. `encrypt` is given the quote id and must
+    /// return the NIP-44 ciphertext.
+    pub fn to_pre_event<F>(&self, pubkey: PublicKey, encrypt: F) -> Result<PreEvent, Error>
+    where
+        F: Fn(&str) -> Result<String, Error>,
+    {
+        Ok(PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::CashuQuote,
+            tags: vec![
+                Tag::Expiration(self.expiration),
+                Tag::Other {
+                    tag: "mint".to_owned(),
+                    data: vec![self.mint.0.clone()],
+                },
+            ],
+            content: encrypt(&self.quote_id)?,
+            ots: None,
+        })
+    }
+
+    /// Interpret an event as a mint quote. `decrypt` is given the event content and
+    /// must return the NIP-44 plaintext.
+    pub fn try_from_event<F>(event: &Event, decrypt: F) -> Result<CashuQuote, Error>
+    where
+        F: Fn(&str) -> Result<String, Error>,
+    {
+        if event.kind != EventKind::CashuQuote {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mint = event.tags.iter().find_map(|t| match t {
+            Tag::Other { tag, data } if tag == "mint" && !data.is_empty() => {
+                Some(UncheckedUrl(data[0].clone()))
+            }
+            _ => None,
+        });
+
+        let missing = |what: &str| Error::AssertionFailed(format!("Mint quote has no {what}"));
+        Ok(CashuQuote {
+            quote_id: decrypt(&event.content)?,
+            mint: mint.ok_or_else(|| missing("mint tag"))?,
+            expiration: event.expiration().ok_or_else(|| missing("expiration"))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PrivateKey;
+
+    // These tests use an identity "encryption" so they exercise only the structure
+    fn identity(s: &str) -> Result<String, Error> {
+        Ok(s.to_owned())
+    }
+
+    fn sign(pre_event: PreEvent, privkey: &PrivateKey) -> Event {
+        Event::new(pre_event, privkey).unwrap()
+    }
+
+    #[test]
+    fn test_cashu_wallet() {
+        let privkey = PrivateKey::mock();
+        let wallet = CashuWallet {
+            privkey: "a5f8e0d1".to_owned(),
+            mints: vec![UncheckedUrl::from_str("https://mint.example.com")],
+        };
+        let event = sign(
+            wallet.to_pre_event(privkey.public_key(), identity).unwrap(),
+            &privkey,
+        );
+        assert_eq!(
+            event.content,
+            r#"[["privkey","a5f8e0d1"],["mint","https://mint.example.com"]]"#
+        );
+        assert_eq!(
+            CashuWallet::try_from_event(&event, identity).unwrap(),
+            wallet
+        );
+    }
+
+    #[test]
+    fn test_cashu_token() {
+        let privkey = PrivateKey::mock();
+        let plaintext = r#"{"mint":"https://stablenut.umint.cash","proofs":[{"id":"005c2502034d4f12","amount":1,"secret":"z+zyxAVLRqN9lEjxuNPSyRJzEstbl69Jc1vtimvtkPg=","C":"0241d98a8197ef238a192d47edf191a9de78b657308937b4f7dd0aa53beae72c46"}]}"#;
+        let token: CashuToken = serde_json::from_str(plaintext).unwrap();
+        assert_eq!(token, CashuToken::mock());
+        assert_eq!(token.amount(), 1);
+        assert_eq!(serde_json::to_string(&token).unwrap(), plaintext);
+
+        let event = sign(
+            token.to_pre_event(privkey.public_key(), identity).unwrap(),
+            &privkey,
+        );
+        assert_eq!(CashuToken::try_from_event(&event, identity).unwrap(), token);
+    }
+
+    #[test]
+    fn test_cashu_spending_history() {
+        let privkey = PrivateKey::mock();
+        let history = CashuSpendingHistory {
+            direction: CashuDirection::In,
+            amount: 21,
+            unit: Some("sat".to_owned()),
+            created: vec![Id::mock()],
+            destroyed: vec![],
+            redeemed: vec![Id::mock()],
+        };
+        let event = sign(
+            history
+                .to_pre_event(privkey.public_key(), identity)
+                .unwrap(),
+            &privkey,
+        );
+        assert_eq!(
+            CashuSpendingHistory::try_from_event(&event, identity).unwrap(),
+            history
+        );
+    }
+
+    #[test]
+    fn test_cashu_quote() {
+        let privkey = PrivateKey::mock();
+        let quote = CashuQuote {
+            quote_id: "quote-123".to_owned(),
+            mint: UncheckedUrl::from_str("https://mint.example.com"),
+            expiration: Unixtime(1700000000),
+        };
+        let event = sign(
+            quote.to_pre_event(privkey.public_key(), identity).unwrap(),
+            &privkey,
+        );
+        assert_eq!(CashuQuote::try_from_event(&event, identity).unwrap(), quote);
+        assert!(CashuToken::try_from_event(&event, identity).is_err());
+    }
+}
@@ -1,4 +1,5 @@
-use super::{Event, Filter, SubscriptionId};
+use super::{Event, Filter, ParseProfile, RelayLimits, SubscriptionId};
+use crate::Error;
 use serde::de::Error as DeError;
 use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
 use serde::ser::{Serialize, SerializeSeq, Serializer};
@@ -20,7 +21,148 @@ pub enum ClientMessage {
     Auth(Box<Event>),
 }
 
+/// A way in which a REQ breaks a relay's limits
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReqViolation {
+    /// The subscription id is empty
+    EmptySubscriptionId,
+
+    /// The subscription id is longer than the relay allows
+    SubscriptionIdTooLong {
+        /// Length of the subscription id, in characters
+        length: usize,
+        /// The relay's maximum
+        max: usize,
+    },
+
+    /// There are more filters than the relay allows
+    TooManyFilters {
+        /// Number of filters
+        count: usize,
+        /// The relay's maximum
+        max: usize,
+    },
+
+    /// A filter asks for more events than the relay allows
+    LimitTooHigh {
+        /// Index of the filter
+        filter: usize,
+        /// The filter's limit
+        limit: usize,
+        /// The relay's maximum
+        max: usize,
+    },
+
+    /// The serialized message is longer than the relay allows
+    MessageTooLong {
+        /// Length of the message, in bytes
+        length: usize,
+        /// The relay's maximum
+        max: usize,
+    },
+}
+
+impl fmt::Display for ReqViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReqViolation::EmptySubscriptionId => write!(f, "Subscription id is empty"),
+            ReqViolation::SubscriptionIdTooLong { length, max } => {
+                write!(f, "Subscription id is {length} characters, max is {max}")
+            }
+            ReqViolation::TooManyFilters { count, max } => {
+                write!(f, "REQ has {count} filters, max is {max}")
+            }
+            ReqViolation::LimitTooHigh { filter, limit, max } => {
+                write!(f, "Filter {filter} has limit {limit}, max is {max}")
+            }
+            ReqViolation::MessageTooLong { length, max } => {
+                write!(f, "REQ is {length} bytes, max is {max}")
+            }
+        }
+    }
+}
+
 impl ClientMessage {
+    /// Create a REQ, first checking it against the relay's limits. All the
+    /// violations found are returned, so that they can be fixed at once.
+    pub fn req_checked(
+        subid: SubscriptionId,
+        filters: Vec<Filter>,
+        limits: &RelayLimits,
+    ) -> Result<ClientMessage, Vec<ReqViolation>> {
+        let mut violations: Vec<ReqViolation> = Vec::new();
+
+        let length = subid.0.chars().count();
+        if length == 0 {
+            violations.push(ReqViolation::EmptySubscriptionId);
+        }
+        if let Some(max) = limits.max_subid_length {
+            if length > max {
+                violations.push(ReqViolation::SubscriptionIdTooLong { length, max });
+            }
+        }
+
+        if let Some(max) = limits.max_filters {
+            if filters.len() > max {
+                violations.push(ReqViolation::TooManyFilters {
+                    count: filters.len(),
+                    max,
+                });
+            }
+        }
+
+        if let Some(max) = limits.max_limit {
+            for (index, filter) in filters.iter().enumerate() {
+                if let Some(limit) = filter.limit {
+                    if limit > max {
+                        violations.push(ReqViolation::LimitTooHigh {
+                            filter: index,
+                            limit,
+                            max,
+                        });
+                    }
+                }
+            }
+        }
+
+        let message = ClientMessage::Req(subid, filters);
+
+        if let Some(max) = limits.max_message_length {
+            let length = serde_json::to_string(&message)
+                .map(|s| s.len())
+                .unwrap_or(0);
+            if length > max {
+                violations.push(ReqViolation::MessageTooLong { length, max });
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(message)
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Deserialize a message from JSON under a `ParseProfile`, which applies to
+    /// the event of an EVENT or AUTH. Events are not verified.
+    pub fn from_json_with_profile(
+        json: &str,
+        profile: ParseProfile,
+    ) -> Result<ClientMessage, Error> {
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        if let Some(array) = value.as_array_mut() {
+            if matches!(
+                array.first().and_then(|w| w.as_str()),
+                Some("EVENT" | "AUTH")
+            ) {
+                if let Some(event) = array.get_mut(1) {
+                    profile.prepare_event(event)?;
+                }
+            }
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
     // Mock data for testing
     #[allow(dead_code)]
     pub(crate) fn mock() -> ClientMessage {
@@ -87,7 +229,7 @@ impl<'de> Visitor<'de> for ClientMessageVisitor {
     where
         A: SeqAccess<'de>,
     {
-        let word: &str = seq
+        let word: String = seq
             .next_element()?
             .ok_or_else(|| DeError::custom("Message missing initial string field"))?;
         if word == "EVENT" {
@@ -127,6 +269,64 @@ impl<'de> Visitor<'de> for ClientMessageVisitor {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::PublicKeyHexPrefix;
 
     test_serde! {ClientMessage, test_client_message_serde}
+
+    #[test]
+    fn test_req_checked() {
+        let limits = RelayLimits {
+            max_message_length: Some(100),
+            max_filters: Some(2),
+            max_limit: Some(500),
+            max_subid_length: Some(8),
+        };
+
+        let mut filter = Filter::new();
+        filter.limit = Some(100);
+        let message =
+            ClientMessage::req_checked(SubscriptionId("sub1".to_owned()), vec![filter], &limits)
+                .unwrap();
+        assert!(matches!(message, ClientMessage::Req(_, _)));
+
+        let mut big = Filter::new();
+        big.limit = Some(1000);
+        let violations = ClientMessage::req_checked(
+            SubscriptionId("much-too-long".to_owned()),
+            vec![Filter::new(), big, Filter::new()],
+            &limits,
+        )
+        .unwrap_err();
+        assert_eq!(
+            violations,
+            vec![
+                ReqViolation::SubscriptionIdTooLong { length: 13, max: 8 },
+                ReqViolation::TooManyFilters { count: 3, max: 2 },
+                ReqViolation::LimitTooHigh {
+                    filter: 1,
+                    limit: 1000,
+                    max: 500
+                },
+            ]
+        );
+
+        let mut filter = Filter::new();
+        filter.authors = vec![PublicKeyHexPrefix::mock(); 8];
+        let violations =
+            ClientMessage::req_checked(SubscriptionId("".to_owned()), vec![filter], &limits)
+                .unwrap_err();
+        assert_eq!(violations[0], ReqViolation::EmptySubscriptionId);
+        assert!(matches!(
+            violations[1],
+            ReqViolation::MessageTooLong { max: 100, .. }
+        ));
+
+        // No limits known
+        assert!(ClientMessage::req_checked(
+            SubscriptionId("anything-goes".to_owned()),
+            vec![Filter::new(); 50],
+            &RelayLimits::new()
+        )
+        .is_ok());
+    }
 }
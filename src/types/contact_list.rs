@@ -0,0 +1,359 @@
+use super::{
+    ChangeSet, Event, EventKind, PreEvent, PublicKey, PublicKeyHex, Tag, UncheckedUrl, Unixtime,
+};
+use crate::Error;
+use std::collections::HashSet;
+
+// The 'd' tag prefix of the follow sets that hold what a contact list could
+// not fit
+const OVERFLOW_PREFIX: &str = "contacts-overflow-";
+
+// Roughly the size of a signed event without its tags and content: the id,
+// pubkey, signature, created_at and kind, with their JSON field names
+const EVENT_OVERHEAD: usize = 400;
+
+// Room for the 'd' tag of an overflow follow set
+const OVERFLOW_TAG_SIZE: usize = 40;
+
+/// A followed pubkey in a contact list
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Contact {
+    /// The followed pubkey
+    pub pubkey: PublicKeyHex,
+
+    /// A relay where they can be found
+    pub relay_url: Option<UncheckedUrl>,
+
+    /// The user's name for them
+    pub petname: Option<String>,
+}
+
+/// A contact list split to fit under a relay's size limit, from
+/// `ContactList::split()`
+#[derive(Clone, Debug)]
+pub struct ContactListSplit {
+    /// The contact list event (kind 3), with as many contacts as fit
+    pub contact_list: PreEvent,
+
+    /// Follow sets (NIP-51, kind 30000) holding the rest, if any
+    pub overflow: Vec<PreEvent>,
+}
+
+/// The follows in a contact list event (NIP-02, kind 3)
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ContactList {
+    /// The contacts, in the order of the event's 'p' tags
+    pub contacts: Vec<Contact>,
+}
+
+impl ContactList {
+    /// Read the 'p' tags of a contact list event
+    pub fn from_event(event: &Event) -> Result<ContactList, Error> {
+        if event.kind != EventKind::ContactList {
+            return Err(Error::WrongEventKind);
+        }
+        Ok(ContactList {
+            contacts: contacts_in(event),
+        })
+    }
+
+    /// Merge versions of a user's contact list, such as those from
+    /// different relays, along with the overflow follow sets made by
+    /// `split()`.
+    ///
+    /// The newest contact list is the base, so unfollows in it are kept.
+    /// Its contacts come first, then those of the overflow sets made with it
+    /// (those with the same `created_at`) in order. Overflow sets left over
+    /// from older lists are ignored. Where a contact is listed more than
+    /// once, the first entry wins.
+    pub fn merge(versions: &[Event]) -> Result<ContactList, Error> {
+        for event in versions.iter() {
+            if event.kind != EventKind::ContactList && overflow_index(event).is_none() {
+                return Err(Error::WrongEventKind);
+            }
+            if event.pubkey != versions[0].pubkey {
+                return Err(Error::AssertionFailed(
+                    "Contact list versions are by different authors".to_owned(),
+                ));
+            }
+        }
+        let base = match versions
+            .iter()
+            .filter(|e| e.kind == EventKind::ContactList)
+            .max_by_key(|e| e.created_at)
+        {
+            Some(base) => base,
+            None => return Ok(ContactList::default()),
+        };
+        let mut overflow: Vec<(usize, &Event)> = versions
+            .iter()
+            .filter(|e| e.created_at == base.created_at)
+            .filter_map(|e| overflow_index(e).map(|n| (n, e)))
+            .collect();
+        overflow.sort_by_key(|(n, _)| *n);
+
+        let mut seen: HashSet<PublicKeyHex> = HashSet::new();
+        let mut merged = ContactList::default();
+        for event in std::iter::once(base).chain(overflow.into_iter().map(|(_, e)| e)) {
+            for contact in contacts_in(event) {
+                if seen.insert(contact.pubkey.clone()) {
+                    merged.contacts.push(contact);
+                }
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Split into a contact list event for `pubkey` at `now` that serializes
+    /// to at most about `max_bytes`, and as many overflow follow sets of the
+    /// same size as are needed for the rest. `merge()` puts them back
+    /// together.
+    ///
+    /// `previous` are the overflow sets published before, if any. Those
+    /// numbered past the new ones get empty replacements, so no stale follows
+    /// are left behind.
+    ///
+    /// Fails if even one contact does not fit in `max_bytes`.
+    pub fn split(
+        &self,
+        pubkey: PublicKey,
+        now: Unixtime,
+        max_bytes: usize,
+        previous: &[Event],
+    ) -> Result<ContactListSplit, Error> {
+        let mut chunks: Vec<Vec<Tag>> = vec![vec![]];
+        let mut size = EVENT_OVERHEAD;
+        for tag in self.to_tags() {
+            let tag_size = serde_json::to_string(&tag)?.len() + 1;
+            if EVENT_OVERHEAD + OVERFLOW_TAG_SIZE + tag_size > max_bytes {
+                return Err(Error::AssertionFailed(
+                    "A contact does not fit in the size limit".to_owned(),
+                ));
+            }
+            if size + tag_size > max_bytes {
+                chunks.push(vec![]);
+                size = EVENT_OVERHEAD + OVERFLOW_TAG_SIZE;
+            }
+            size += tag_size;
+            chunks.last_mut().unwrap().push(tag);
+        }
+
+        let mut chunks = chunks.into_iter();
+        let contact_list = PreEvent {
+            pubkey,
+            created_at: now,
+            kind: EventKind::ContactList,
+            tags: chunks.next().unwrap_or_default(),
+            content: "".to_owned(),
+            ots: None,
+        };
+        let highest = previous
+            .iter()
+            .filter(|e| e.pubkey == pubkey)
+            .filter_map(overflow_index)
+            .max()
+            .unwrap_or(0);
+        let empties = highest.saturating_sub(chunks.len());
+        let overflow = chunks
+            .chain(std::iter::repeat(vec![]).take(empties))
+            .enumerate()
+            .map(|(n, mut tags)| {
+                tags.insert(0, Tag::Identifier(format!("{OVERFLOW_PREFIX}{}", n + 1)));
+                PreEvent {
+                    pubkey,
+                    created_at: now,
+                    kind: EventKind::FollowSets,
+                    tags,
+                    content: "".to_owned(),
+                    ots: None,
+                }
+            })
+            .collect();
+        Ok(ContactListSplit {
+            contact_list,
+            overflow,
+        })
+    }
+
+    /// The 'p' tags for a contact list event
+    pub fn to_tags(&self) -> Vec<Tag> {
+        self.contacts
+            .iter()
+            .map(|contact| Tag::Pubkey {
+                pubkey: contact.pubkey.clone(),
+                recommended_relay_url: contact.relay_url.clone(),
+                petname: contact.petname.clone(),
+            })
+            .collect()
+    }
+
+    /// The contacts followed, unfollowed, or given a different relay or
+    /// petname in `newer` compared to this list
+    pub fn diff(&self, newer: &ContactList) -> ChangeSet<PublicKeyHex, Contact> {
+        let entries = |list: &ContactList| -> Vec<(PublicKeyHex, Contact)> {
+            list.contacts
+                .iter()
+                .map(|c| (c.pubkey.clone(), c.clone()))
+                .collect()
+        };
+        ChangeSet::compare(entries(self), entries(newer))
+    }
+}
+
+// The N of an overflow follow set's "contacts-overflow-N" 'd' tag
+fn overflow_index(event: &Event) -> Option<usize> {
+    if event.kind != EventKind::FollowSets {
+        return None;
+    }
+    event
+        .parameter()?
+        .strip_prefix(OVERFLOW_PREFIX)?
+        .parse::<usize>()
+        .ok()
+        .filter(|n| *n > 0)
+}
+
+// The contacts in the 'p' tags of an event
+fn contacts_in(event: &Event) -> Vec<Contact> {
+    event
+        .tags
+        .iter()
+        .filter_map(|tag| match tag {
+            Tag::Pubkey {
+                pubkey,
+                recommended_relay_url,
+                petname,
+            } => Some(Contact {
+                pubkey: pubkey.clone(),
+                relay_url: recommended_relay_url.clone(),
+                petname: petname.clone(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PreEvent, PrivateKey, Unixtime};
+
+    #[test]
+    fn test_contact_list_diff() {
+        let private_key = PrivateKey::generate();
+        let contact = |petname: Option<&str>| Contact {
+            pubkey: PublicKeyHex::from(PrivateKey::generate().public_key()),
+            relay_url: None,
+            petname: petname.map(|p| p.to_owned()),
+        };
+        let alice = contact(Some("alice"));
+        let bob = contact(None);
+        let carol = contact(None);
+
+        let old = ContactList {
+            contacts: vec![alice.clone(), bob.clone()],
+        };
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::ContactList,
+            tags: old.to_tags(),
+            content: "".to_owned(),
+            ots: None,
+        };
+        let event = Event::new(pre, &private_key).unwrap();
+        assert_eq!(ContactList::from_event(&event).unwrap(), old);
+
+        let mut renamed = alice.clone();
+        renamed.petname = Some("Alice".to_owned());
+        let new = ContactList {
+            contacts: vec![renamed.clone(), carol.clone()],
+        };
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec![(carol.pubkey.clone(), carol)]);
+        assert_eq!(diff.removed, vec![(bob.pubkey.clone(), bob)]);
+        assert_eq!(diff.changed, vec![(alice.pubkey.clone(), alice, renamed)]);
+        assert!(new.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn test_contact_list_merge_and_split() {
+        let private_key = PrivateKey::generate();
+        let contacts: Vec<Contact> = (0..50)
+            .map(|n| Contact {
+                pubkey: PublicKeyHex::from(PrivateKey::generate().public_key()),
+                relay_url: Some(UncheckedUrl(format!("wss://relay{n}.example.com"))),
+                petname: None,
+            })
+            .collect();
+        let sign = |pre: PreEvent| Event::new(pre, &private_key).unwrap();
+        let list = |contacts: &[Contact], created_at: i64| {
+            sign(PreEvent {
+                pubkey: private_key.public_key(),
+                created_at: Unixtime(created_at),
+                kind: EventKind::ContactList,
+                tags: ContactList {
+                    contacts: contacts.to_vec(),
+                }
+                .to_tags(),
+                content: "".to_owned(),
+                ots: None,
+            })
+        };
+
+        // The newest version wins, so contacts dropped from it stay dropped
+        let mut renamed = contacts.get(..30).unwrap().to_vec();
+        renamed[5].petname = Some("five".to_owned());
+        let older = list(&contacts, 1700000000);
+        let newer = list(&renamed, 1700000100);
+        let merged = ContactList::merge(&[newer.clone(), older]).unwrap();
+        assert_eq!(merged.contacts, renamed);
+
+        let full = ContactList {
+            contacts: contacts.clone(),
+        };
+        let split = full
+            .split(private_key.public_key(), Unixtime(1700000200), 2000, &[])
+            .unwrap();
+        assert!(!split.overflow.is_empty());
+        let mut events = vec![sign(split.contact_list)];
+        events.extend(split.overflow.into_iter().map(sign));
+        for event in &events {
+            assert!(serde_json::to_string(event).unwrap().len() <= 2000);
+        }
+        assert_eq!(events[1].parameter().unwrap(), "contacts-overflow-1");
+        assert_eq!(ContactList::merge(&events).unwrap(), full);
+
+        // Shrinking empties the overflow sets no longer needed, and those
+        // left from the older split are not merged in
+        let shrunk = ContactList {
+            contacts: contacts.get(..10).unwrap().to_vec(),
+        };
+        let split = shrunk
+            .split(
+                private_key.public_key(),
+                Unixtime(1700000300),
+                2000,
+                &events,
+            )
+            .unwrap();
+        assert_eq!(split.overflow.len(), events.len() - 1);
+        assert!(split
+            .overflow
+            .iter()
+            .all(|pre| pre.tags.len() == 1 && pre.created_at == Unixtime(1700000300)));
+        let mut all = events.clone();
+        all.push(sign(split.contact_list));
+        assert_eq!(ContactList::merge(&all).unwrap(), shrunk);
+
+        assert!(full
+            .split(private_key.public_key(), Unixtime(1700000200), 400, &[])
+            .is_err());
+        let mut other = newer;
+        other.kind = EventKind::FollowSets;
+        assert!(matches!(
+            ContactList::merge(&[other]),
+            Err(Error::WrongEventKind)
+        ));
+    }
+}
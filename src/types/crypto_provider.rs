@@ -0,0 +1,170 @@
+use lazy_static::lazy_static;
+use rand_core::{CryptoRng, OsRng, RngCore};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, RwLock};
+
+/// A source of randomness and SHA-256, for environments that cannot use the
+/// defaults (an HSM, wasm with its own entropy, or deterministic tests).
+///
+/// Install one with `set_crypto_provider()`. It is then used for key
+/// generation, encryption nonces and salts, event ids (and so proof of
+/// work), and gift wrap timestamp fuzzing. Signing and signature
+/// verification still hash internally.
+pub trait CryptoProvider: Send + Sync {
+    /// Fill `dest` with cryptographically secure random bytes
+    fn fill_bytes(&self, dest: &mut [u8]);
+
+    /// The SHA-256 hash of `data`
+    fn sha256(&self, data: &[u8]) -> [u8; 32];
+}
+
+/// The provider used unless another is set: the operating system's
+/// randomness and the `sha2` crate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DefaultCryptoProvider;
+
+impl CryptoProvider for DefaultCryptoProvider {
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        OsRng.fill_bytes(dest);
+    }
+
+    fn sha256(&self, data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+}
+
+lazy_static! {
+    static ref CRYPTO_PROVIDER: RwLock<Arc<dyn CryptoProvider>> =
+        RwLock::new(Arc::new(DefaultCryptoProvider));
+}
+
+/// Use `provider` for all randomness and hashing from now on, in every thread
+pub fn set_crypto_provider(provider: Arc<dyn CryptoProvider>) {
+    *CRYPTO_PROVIDER.write().unwrap() = provider;
+}
+
+/// Go back to the `DefaultCryptoProvider`
+pub fn reset_crypto_provider() {
+    set_crypto_provider(Arc::new(DefaultCryptoProvider));
+}
+
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    CRYPTO_PROVIDER.read().unwrap().sha256(data)
+}
+
+// The installed provider, for loops (such as proof of work) that should not
+// take the lock on every hash
+pub(crate) fn current() -> Arc<dyn CryptoProvider> {
+    CRYPTO_PROVIDER.read().unwrap().clone()
+}
+
+// An rng drawing from the installed provider, to pass where an rng is taken
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ProviderRng;
+
+impl RngCore for ProviderRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        current().fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for ProviderRng {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Event, EventKind, PreEvent, PrivateKey, Unixtime};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Mutex, MutexGuard};
+
+    // Held by tests that install a provider, so that they do not swap it
+    // under each other
+    static PROVIDER_LOCK: Mutex<()> = Mutex::new(());
+
+    // Puts the default provider back when dropped, even if the test fails
+    struct Installed(#[allow(dead_code)] MutexGuard<'static, ()>);
+
+    fn install(provider: Arc<dyn CryptoProvider>) -> Installed {
+        let lock = PROVIDER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        set_crypto_provider(provider);
+        Installed(lock)
+    }
+
+    impl Drop for Installed {
+        fn drop(&mut self) {
+            reset_crypto_provider();
+        }
+    }
+
+    #[derive(Default)]
+    struct Counting {
+        random: AtomicUsize,
+        hashes: AtomicUsize,
+    }
+
+    impl CryptoProvider for Arc<Counting> {
+        fn fill_bytes(&self, dest: &mut [u8]) {
+            let _ = self.random.fetch_add(1, Ordering::SeqCst);
+            DefaultCryptoProvider.fill_bytes(dest);
+        }
+
+        fn sha256(&self, data: &[u8]) -> [u8; 32] {
+            let _ = self.hashes.fetch_add(1, Ordering::SeqCst);
+            DefaultCryptoProvider.sha256(data)
+        }
+    }
+
+    #[test]
+    fn test_crypto_provider() {
+        assert_eq!(
+            DefaultCryptoProvider.sha256(b"abc"),
+            <[u8; 32]>::try_from(
+                hex::decode("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+                    .unwrap()
+            )
+            .unwrap()
+        );
+
+        // Other tests may run meanwhile, so only check that the counts grew.
+        // The provider still draws real randomness, so they are unaffected.
+        let counting = Arc::new(Counting::default());
+        let _installed = install(Arc::new(counting.clone()));
+        let private_key = PrivateKey::generate();
+        assert!(counting.random.load(Ordering::SeqCst) > 0);
+        let hashes = counting.hashes.load(Ordering::SeqCst);
+        let event = Event::mock();
+        assert!(counting.hashes.load(Ordering::SeqCst) > hashes);
+        assert!(event.verify(None).is_ok());
+
+        // Proof of work hashes with the provider it started with
+        let hashes = counting.hashes.load(Ordering::SeqCst);
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::TextNote,
+            tags: vec![],
+            content: "mined".to_owned(),
+            ots: None,
+        };
+        let event = Event::new_with_pow(pre, &private_key, 4).unwrap();
+        assert!(event.verify(None).is_ok());
+        assert!(counting.hashes.load(Ordering::SeqCst) > hashes);
+    }
+}
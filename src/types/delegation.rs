@@ -104,6 +104,33 @@ impl DelegationConditions {
         );
         pubkey_delegater.verify(input.as_bytes(), signature)
     }
+
+    /// Check that an event of this kind created at this time is within the
+    /// delegation
+    pub fn check(&self, kind: EventKind, created_at: Unixtime) -> Result<(), Error> {
+        if let Some(delegated_kind) = self.kind {
+            if kind != delegated_kind {
+                return Err(Error::DelegationViolated(
+                    "Event Kind not delegated".to_owned(),
+                ));
+            }
+        }
+        if let Some(created_after) = self.created_after {
+            if created_at < created_after {
+                return Err(Error::DelegationViolated(
+                    "Event created before delegation started".to_owned(),
+                ));
+            }
+        }
+        if let Some(created_before) = self.created_before {
+            if created_at > created_before {
+                return Err(Error::DelegationViolated(
+                    "Event created after delegation ended".to_owned(),
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Serialize for DelegationConditions {
@@ -0,0 +1,196 @@
+use super::{Metadata, SimpleRelayList, SimpleRelayUsage, UncheckedUrl};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// The differences between two versions of a replaceable event's data, keyed
+/// by field name, relay url, pubkey, etc. as suits the data.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChangeSet<K, V> {
+    /// Entries only in the newer version
+    pub added: Vec<(K, V)>,
+
+    /// Entries only in the older version
+    pub removed: Vec<(K, V)>,
+
+    /// Entries in both versions with different values: (key, old, new)
+    pub changed: Vec<(K, V, V)>,
+}
+
+impl<K, V> Default for ChangeSet<K, V> {
+    fn default() -> ChangeSet<K, V> {
+        ChangeSet {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: PartialEq + Clone> ChangeSet<K, V> {
+    /// Compare the entries of an older and a newer version. Added and changed
+    /// entries are in the order of the newer version, removed ones in the
+    /// order of the older. If a key repeats, its last entry counts.
+    pub fn compare(old: Vec<(K, V)>, new: Vec<(K, V)>) -> ChangeSet<K, V> {
+        let old_map: HashMap<K, V> = old.iter().cloned().collect();
+        let new_map: HashMap<K, V> = new.iter().cloned().collect();
+        let mut changes = ChangeSet::default();
+
+        for (key, value) in new.into_iter() {
+            if new_map.get(&key) != Some(&value) {
+                continue;
+            }
+            match old_map.get(&key) {
+                None => changes.added.push((key, value)),
+                Some(old_value) if *old_value != value => {
+                    changes.changed.push((key, old_value.clone(), value))
+                }
+                Some(_) => {}
+            }
+        }
+        for (key, value) in old.into_iter() {
+            if old_map.get(&key) == Some(&value) && !new_map.contains_key(&key) {
+                changes.removed.push((key, value));
+            }
+        }
+
+        // A key repeated with the same value would be listed twice
+        dedup_by_key(&mut changes.added, |(k, _)| k);
+        dedup_by_key(&mut changes.removed, |(k, _)| k);
+        dedup_by_key(&mut changes.changed, |(k, _, _)| k);
+        changes
+    }
+}
+
+impl<K, V> ChangeSet<K, V> {
+    /// Are the two versions the same?
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+fn dedup_by_key<T, K: Eq + Hash + Clone>(list: &mut Vec<T>, key: impl Fn(&T) -> &K) {
+    let mut seen: HashSet<K> = HashSet::new();
+    list.retain(|item| seen.insert(key(item).clone()));
+}
+
+impl Metadata {
+    /// The fields changed in `newer` compared to this metadata. Fields that
+    /// are null are treated as absent.
+    pub fn diff(&self, newer: &Metadata) -> ChangeSet<String, Value> {
+        ChangeSet::compare(metadata_fields(self), metadata_fields(newer))
+    }
+}
+
+fn metadata_fields(metadata: &Metadata) -> Vec<(String, Value)> {
+    let mut fields: Vec<(String, Value)> = Vec::new();
+    let known = [
+        ("name", &metadata.name),
+        ("about", &metadata.about),
+        ("picture", &metadata.picture),
+        ("nip05", &metadata.nip05),
+    ];
+    for (key, value) in known {
+        if let Some(value) = value {
+            fields.push((key.to_owned(), Value::String(value.to_owned())));
+        }
+    }
+    for (key, value) in metadata.other.iter() {
+        if !value.is_null() {
+            fields.push((key.to_owned(), value.clone()));
+        }
+    }
+    fields
+}
+
+impl SimpleRelayList {
+    /// The relays added, removed, or used differently in `newer` compared to
+    /// this list
+    pub fn diff(&self, newer: &SimpleRelayList) -> ChangeSet<UncheckedUrl, SimpleRelayUsage> {
+        ChangeSet::compare(sorted_relays(self), sorted_relays(newer))
+    }
+}
+
+fn sorted_relays(list: &SimpleRelayList) -> Vec<(UncheckedUrl, SimpleRelayUsage)> {
+    let mut relays: Vec<(UncheckedUrl, SimpleRelayUsage)> =
+        list.0.iter().map(|(u, r)| (u.clone(), *r)).collect();
+    relays.sort_by(|a, b| a.0.cmp(&b.0));
+    relays
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_metadata_diff() {
+        let old = Metadata::mock();
+        assert!(old.diff(&old).is_empty());
+
+        let mut new = old.clone();
+        new.name = Some("jb".to_owned());
+        new.nip05 = None;
+        new.about = Some("hi".to_owned());
+        let _ = new.other.insert("display_name".to_owned(), Value::Null);
+        let _ = new.other.insert(
+            "lud16".to_owned(),
+            Value::String("jb@example.com".to_owned()),
+        );
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.added,
+            vec![
+                ("about".to_owned(), Value::String("hi".to_owned())),
+                (
+                    "lud16".to_owned(),
+                    Value::String("jb@example.com".to_owned())
+                ),
+            ]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![
+                ("nip05".to_owned(), Value::String("jb55.com".to_owned())),
+                (
+                    "display_name".to_owned(),
+                    Value::String("William Caserin".to_owned())
+                ),
+            ]
+        );
+        assert_eq!(
+            diff.changed,
+            vec![(
+                "name".to_owned(),
+                Value::String("jb55".to_owned()),
+                Value::String("jb".to_owned())
+            )]
+        );
+    }
+
+    #[test]
+    fn test_relay_list_diff() {
+        let old = SimpleRelayList::mock();
+        assert!(old.diff(&old).is_empty());
+
+        let mut new = SimpleRelayList::default();
+        assert_eq!(old.diff(&new).removed.len(), old.0.len());
+
+        let url = UncheckedUrl::from_str("wss://new.example.com");
+        let _ = new.0.insert(url.clone(), SimpleRelayUsage::default());
+        let diff = SimpleRelayList::default().diff(&new);
+        assert_eq!(diff.added, vec![(url, SimpleRelayUsage::default())]);
+        assert!(diff.removed.is_empty());
+    }
+
+    #[test]
+    fn test_change_set_compare() {
+        let changes = ChangeSet::compare(
+            vec![("a", 1), ("b", 2), ("c", 3)],
+            vec![("b", 2), ("c", 4), ("d", 5), ("d", 5)],
+        );
+        assert_eq!(changes.added, vec![("d", 5)]);
+        assert_eq!(changes.removed, vec![("a", 1)]);
+        assert_eq!(changes.changed, vec![("c", 3, 4)]);
+    }
+}
@@ -0,0 +1,187 @@
+use super::{Event, EventKind, PreEvent, PrivateKey, PublicKey, Tag, UncheckedUrl, Unixtime};
+use crate::Error;
+use base64::Engine;
+
+/// A NIP-04 encrypted direct message (kind 4).
+///
+/// NIP-04 leaks who is talking to whom and when; prefer gift wrapped
+/// messages (NIP-17) where the other side supports them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedDirectMessage {
+    /// Who sent it
+    pub sender: PublicKey,
+
+    /// Who it is for
+    pub recipient: PublicKey,
+
+    /// A relay where the recipient may be found
+    pub relay_url: Option<UncheckedUrl>,
+
+    /// When it was sent
+    pub created_at: Unixtime,
+
+    /// The encrypted message, as "ciphertext?iv=iv" in base64
+    pub content: String,
+}
+
+impl EncryptedDirectMessage {
+    /// Encrypt `message` from the holder of `private_key` to `recipient`,
+    /// sent at `now`
+    pub fn new(
+        private_key: &PrivateKey,
+        recipient: PublicKey,
+        relay_url: Option<UncheckedUrl>,
+        message: &str,
+        now: Unixtime,
+    ) -> Result<EncryptedDirectMessage, Error> {
+        let (iv, ciphertext) = private_key.nip04_encrypt(&recipient, message.as_bytes())?;
+        let content = format!(
+            "{}?iv={}",
+            base64::engine::general_purpose::STANDARD.encode(ciphertext),
+            base64::engine::general_purpose::STANDARD.encode(iv)
+        );
+        Ok(EncryptedDirectMessage {
+            sender: private_key.public_key(),
+            recipient,
+            relay_url,
+            created_at: now,
+            content,
+        })
+    }
+
+    /// Read a kind 4 event. It must have exactly one 'p' tag, naming the
+    /// recipient. This does not verify the event.
+    pub fn from_event(event: &Event) -> Result<EncryptedDirectMessage, Error> {
+        if event.kind != EventKind::EncryptedDirectMessage {
+            return Err(Error::WrongEventKind);
+        }
+        let mut recipients = event.tags.iter().filter_map(|tag| match tag {
+            Tag::Pubkey {
+                pubkey,
+                recommended_relay_url,
+                ..
+            } => Some((pubkey, recommended_relay_url)),
+            _ => None,
+        });
+        let (recipient, relay_url) = match (recipients.next(), recipients.next()) {
+            (Some(recipient), None) => recipient,
+            _ => {
+                return Err(Error::AssertionFailed(
+                    "A direct message needs exactly one 'p' tag".to_owned(),
+                ))
+            }
+        };
+        Ok(EncryptedDirectMessage {
+            sender: event.pubkey,
+            recipient: PublicKey::try_from_hex_string(recipient.as_str())?,
+            relay_url: relay_url.clone(),
+            created_at: event.created_at,
+            content: event.content.clone(),
+        })
+    }
+
+    /// The unsigned event, ready to sign with the sender's key
+    pub fn to_pre_event(&self) -> PreEvent {
+        PreEvent {
+            pubkey: self.sender,
+            created_at: self.created_at,
+            kind: EventKind::EncryptedDirectMessage,
+            tags: vec![Tag::Pubkey {
+                pubkey: self.recipient.into(),
+                recommended_relay_url: self.relay_url.clone(),
+                petname: None,
+            }],
+            content: self.content.clone(),
+            ots: None,
+        }
+    }
+
+    /// The other side of the conversation, from the point of view of `me`.
+    /// Fails if `me` is neither the sender nor the recipient.
+    pub fn counterparty(&self, me: &PublicKey) -> Result<PublicKey, Error> {
+        if *me == self.sender {
+            Ok(self.recipient)
+        } else if *me == self.recipient {
+            Ok(self.sender)
+        } else {
+            Err(Error::AssertionFailed(
+                "Not a party to this direct message".to_owned(),
+            ))
+        }
+    }
+
+    /// Decrypt with the key of either the sender or the recipient, giving
+    /// the message and the other side of the conversation
+    pub fn decrypt(&self, private_key: &PrivateKey) -> Result<(String, PublicKey), Error> {
+        let counterparty = self.counterparty(&private_key.public_key())?;
+        let (ciphertext, iv) = self
+            .content
+            .split_once("?iv=")
+            .ok_or(Error::BadEncryptedMessage)?;
+        let ciphertext = base64::engine::general_purpose::STANDARD
+            .decode(ciphertext)
+            .map_err(|_| Error::BadEncryptedMessage)?;
+        let iv: [u8; 16] = base64::engine::general_purpose::STANDARD
+            .decode(iv)
+            .map_err(|_| Error::BadEncryptedMessage)?
+            .try_into()
+            .map_err(|_| Error::BadEncryptedMessage)?;
+        let plaintext = private_key.nip04_decrypt(&counterparty, &ciphertext, iv)?;
+        let plaintext = String::from_utf8(plaintext).map_err(|_| Error::BadEncryptedMessage)?;
+        Ok((plaintext, counterparty))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encrypted_direct_message() {
+        let alice = PrivateKey::generate();
+        let bob = PrivateKey::generate();
+        let relay = UncheckedUrl::from_str("wss://relay.example.com");
+
+        let dm = EncryptedDirectMessage::new(
+            &alice,
+            bob.public_key(),
+            Some(relay.clone()),
+            "hi bob",
+            Unixtime(1700000000),
+        )
+        .unwrap();
+        let event = Event::new(dm.to_pre_event(), &alice).unwrap();
+        assert_eq!(event.kind, EventKind::EncryptedDirectMessage);
+
+        let read = EncryptedDirectMessage::from_event(&event).unwrap();
+        assert_eq!(read, dm);
+        assert_eq!(read.relay_url, Some(relay));
+        assert_eq!(
+            read.decrypt(&bob).unwrap(),
+            ("hi bob".to_owned(), alice.public_key())
+        );
+        // The sender can read their own message too
+        assert_eq!(
+            read.decrypt(&alice).unwrap(),
+            ("hi bob".to_owned(), bob.public_key())
+        );
+        assert!(read.decrypt(&PrivateKey::generate()).is_err());
+
+        let mut broken = read;
+        broken.content = "abc".to_owned();
+        assert!(matches!(
+            broken.decrypt(&bob),
+            Err(Error::BadEncryptedMessage)
+        ));
+        broken.content = "abc?iv=AAAA".to_owned();
+        assert!(matches!(
+            broken.decrypt(&bob),
+            Err(Error::BadEncryptedMessage)
+        ));
+
+        assert!(matches!(
+            EncryptedDirectMessage::from_event(&Event::mock()),
+            Err(Error::WrongEventKind)
+        ));
+    }
+}
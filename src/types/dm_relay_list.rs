@@ -0,0 +1,77 @@
+use super::{Event, EventKind, PreEvent, PublicKey, RelayUrl, Tag, Unixtime};
+use crate::Error;
+
+/// The relays a user wants to receive direct messages on (kind 10050,
+/// NIP-17). Gift wraps for them should be sent only to these.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DmRelayList {
+    /// The relays, in order of preference
+    pub relays: Vec<RelayUrl>,
+}
+
+impl DmRelayList {
+    /// Create a list of these relays
+    pub fn new(relays: Vec<RelayUrl>) -> DmRelayList {
+        DmRelayList { relays }
+    }
+
+    /// Build the event for this list
+    pub fn to_pre_event(&self, pubkey: PublicKey, now: Unixtime) -> PreEvent {
+        PreEvent {
+            pubkey,
+            created_at: now,
+            kind: EventKind::DmRelayList,
+            tags: self
+                .relays
+                .iter()
+                .map(|relay| Tag::Relay(relay.to_unchecked_url()))
+                .collect(),
+            content: "".to_owned(),
+            ots: None,
+        }
+    }
+
+    /// Read a list from its event. Invalid and repeated relay urls are
+    /// skipped.
+    pub fn try_from_event(event: &Event) -> Result<DmRelayList, Error> {
+        if event.kind != EventKind::DmRelayList {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut relays: Vec<RelayUrl> = Vec::new();
+        for tag in event.tags.iter() {
+            if let Tag::Relay(url) = tag {
+                if let Ok(relay) = RelayUrl::try_from_unchecked_url(url) {
+                    if !relays.contains(&relay) {
+                        relays.push(relay);
+                    }
+                }
+            }
+        }
+        Ok(DmRelayList { relays })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn test_dm_relay_list() {
+        let private_key = PrivateKey::generate();
+        let list = DmRelayList::new(vec![
+            RelayUrl::try_from_str("wss://inbox.example").unwrap(),
+            RelayUrl::try_from_str("wss://dm.example").unwrap(),
+        ]);
+        let mut pre = list.to_pre_event(private_key.public_key(), Unixtime(1700000000));
+        pre.tags.push(Tag::Relay(list.relays[0].to_unchecked_url()));
+        let event = Event::new(pre, &private_key).unwrap();
+        assert_eq!(DmRelayList::try_from_event(&event).unwrap(), list);
+
+        assert!(matches!(
+            DmRelayList::try_from_event(&Event::mock()),
+            Err(Error::WrongEventKind)
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+use super::{Event, EventDelegation, PublicKey};
+use crate::Error;
+use std::collections::HashMap;
+
+/// Who is responsible for an event, as found by `AuthorResolver`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EffectiveAuthor {
+    /// The event was signed by its author
+    Direct(PublicKey),
+
+    /// The event was signed by `delegatee` under a valid NIP-26 delegation,
+    /// so `delegator` is responsible for it
+    Delegated {
+        /// Who delegated, and is responsible
+        delegator: PublicKey,
+
+        /// Who signed
+        delegatee: PublicKey,
+    },
+
+    /// The event was signed by its author's key held in a NIP-46 remote
+    /// signer
+    RemoteSigned {
+        /// The author, who is responsible
+        author: PublicKey,
+
+        /// The remote signer's own key
+        remote_signer: PublicKey,
+    },
+
+    /// The event claims a delegation that is invalid, so its signer alone
+    /// is responsible
+    InvalidDelegation {
+        /// The signer, who is responsible
+        author: PublicKey,
+
+        /// Why the delegation is invalid
+        reason: String,
+    },
+}
+
+impl EffectiveAuthor {
+    /// The key responsible for the event, for moderation and display
+    pub fn pubkey(&self) -> PublicKey {
+        match self {
+            EffectiveAuthor::Direct(author) => *author,
+            EffectiveAuthor::Delegated { delegator, .. } => *delegator,
+            EffectiveAuthor::RemoteSigned { author, .. } => *author,
+            EffectiveAuthor::InvalidDelegation { author, .. } => *author,
+        }
+    }
+
+    /// The key that signed the event
+    pub fn signer(&self) -> PublicKey {
+        match self {
+            EffectiveAuthor::Delegated { delegatee, .. } => *delegatee,
+            other => other.pubkey(),
+        }
+    }
+}
+
+/// Works out who is responsible for events, considering NIP-26 delegation
+/// and the NIP-46 remote signers this client knows its users sign through.
+///
+/// Remote signing leaves no trace in an event, so it is only reported for
+/// authors registered with `add_remote_signer()`.
+#[derive(Clone, Debug, Default)]
+pub struct AuthorResolver {
+    remote_signers: HashMap<PublicKey, PublicKey>,
+}
+
+impl AuthorResolver {
+    /// A resolver that knows of no remote signers
+    pub fn new() -> AuthorResolver {
+        Default::default()
+    }
+
+    /// Record that `author` signs through the remote signer `remote_signer`
+    pub fn add_remote_signer(&mut self, author: PublicKey, remote_signer: PublicKey) {
+        let _ = self.remote_signers.insert(author, remote_signer);
+    }
+
+    /// Forget the remote signer of `author`
+    pub fn remove_remote_signer(&mut self, author: &PublicKey) {
+        let _ = self.remote_signers.remove(author);
+    }
+
+    /// Find who is responsible for `event`. Fails if its signature does not
+    /// verify, since then nobody can be held to it.
+    pub fn resolve(&self, event: &Event) -> Result<EffectiveAuthor, Error> {
+        event.verify(None)?;
+        Ok(match event.delegation() {
+            EventDelegation::DelegatedBy(delegator) => EffectiveAuthor::Delegated {
+                delegator,
+                delegatee: event.pubkey,
+            },
+            EventDelegation::InvalidDelegation(reason) => EffectiveAuthor::InvalidDelegation {
+                author: event.pubkey,
+                reason,
+            },
+            EventDelegation::NotDelegated => match self.remote_signers.get(&event.pubkey) {
+                Some(remote_signer) => EffectiveAuthor::RemoteSigned {
+                    author: event.pubkey,
+                    remote_signer: *remote_signer,
+                },
+                None => EffectiveAuthor::Direct(event.pubkey),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{DelegationConditions, EventKind, PreEvent, PrivateKey, Tag, Unixtime};
+
+    #[test]
+    fn test_author_resolver() {
+        let delegator_key = PrivateKey::generate();
+        let delegator = delegator_key.public_key();
+        let key = PrivateKey::generate();
+        let pubkey = key.public_key();
+        let bunker = PrivateKey::generate().public_key();
+
+        let conditions = DelegationConditions::try_from_str("kind=1").unwrap();
+        let sig = conditions
+            .generate_signature(pubkey.into(), delegator_key)
+            .unwrap();
+        let delegation = Tag::Delegation {
+            pubkey: delegator.into(),
+            conditions,
+            sig,
+        };
+        let event = |kind: EventKind, tags: Vec<Tag>| {
+            let pre = PreEvent {
+                pubkey,
+                created_at: Unixtime(1700000000),
+                kind,
+                tags,
+                content: "".to_owned(),
+                ots: None,
+            };
+            Event::new(pre, &key).unwrap()
+        };
+
+        let mut resolver = AuthorResolver::new();
+        let plain = event(EventKind::TextNote, vec![]);
+        assert_eq!(
+            resolver.resolve(&plain).unwrap(),
+            EffectiveAuthor::Direct(pubkey)
+        );
+
+        let delegated = resolver
+            .resolve(&event(EventKind::TextNote, vec![delegation.clone()]))
+            .unwrap();
+        assert_eq!(delegated.pubkey(), delegator);
+        assert_eq!(delegated.signer(), pubkey);
+
+        // The delegation is only for kind 1
+        let invalid = resolver
+            .resolve(&event(EventKind::Reaction, vec![delegation]))
+            .unwrap();
+        assert!(matches!(invalid, EffectiveAuthor::InvalidDelegation { .. }));
+        assert_eq!(invalid.pubkey(), pubkey);
+
+        resolver.add_remote_signer(pubkey, bunker);
+        assert_eq!(
+            resolver.resolve(&plain).unwrap(),
+            EffectiveAuthor::RemoteSigned {
+                author: pubkey,
+                remote_signer: bunker
+            }
+        );
+
+        let mut forged = plain;
+        forged.content = "changed".to_owned();
+        assert!(resolver.resolve(&forged).is_err());
+    }
+}
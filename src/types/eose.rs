@@ -0,0 +1,133 @@
+use super::{RelayMessage, RelayUrl, SubscriptionId, Unixtime};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Tracks EOSE from each relay a subscription was sent to, so a UI can tell
+/// when the initial load is complete.
+///
+/// This does no I/O. Pass each message from each relay to `handle()`, and
+/// call `record_disconnected()` when a relay drops, then check `all_eose()`
+/// or `any_eose()`. Relays which have not answered within the timeout are
+/// given up on, so neither waits forever on a slow relay.
+#[derive(Clone, Debug)]
+pub struct EoseTracker {
+    subscription_id: SubscriptionId,
+    relays: BTreeMap<RelayUrl, bool>,
+    deadline: Unixtime,
+}
+
+impl EoseTracker {
+    /// Track `subscription_id`, sent to `relays` at `now`, giving up on
+    /// relays after `timeout`
+    pub fn new<I>(
+        subscription_id: SubscriptionId,
+        relays: I,
+        now: Unixtime,
+        timeout: Duration,
+    ) -> EoseTracker
+    where
+        I: IntoIterator<Item = RelayUrl>,
+    {
+        EoseTracker {
+            subscription_id,
+            relays: relays.into_iter().map(|relay| (relay, false)).collect(),
+            deadline: now + timeout,
+        }
+    }
+
+    /// The subscription tracked
+    pub fn subscription_id(&self) -> &SubscriptionId {
+        &self.subscription_id
+    }
+
+    /// Handle a message from `relay`. Returns true if it was this
+    /// subscription's first EOSE from that relay.
+    pub fn handle(&mut self, relay: &RelayUrl, message: &RelayMessage) -> bool {
+        match message {
+            RelayMessage::Eose(sub) if *sub == self.subscription_id => self.finish(relay),
+            _ => false,
+        }
+    }
+
+    /// Record that `relay` disconnected, so no EOSE will come from it.
+    /// Returns true if it was still pending.
+    pub fn record_disconnected(&mut self, relay: &RelayUrl) -> bool {
+        self.finish(relay)
+    }
+
+    /// Have all relays finished (by EOSE or disconnecting), or has the
+    /// timeout passed?
+    pub fn all_eose(&self, now: Unixtime) -> bool {
+        self.relays.values().all(|done| *done) || self.timed_out(now)
+    }
+
+    /// Has any relay finished, or has the timeout passed? A UI can show
+    /// results from then on rather than waiting for the slowest relay.
+    pub fn any_eose(&self, now: Unixtime) -> bool {
+        self.relays.values().any(|done| *done) || self.timed_out(now)
+    }
+
+    /// Has the timeout passed with relays still pending?
+    pub fn timed_out(&self, now: Unixtime) -> bool {
+        now >= self.deadline && self.relays.values().any(|done| !*done)
+    }
+
+    /// The relays which have not finished yet
+    pub fn pending_relays(&self) -> Vec<&RelayUrl> {
+        self.relays
+            .iter()
+            .filter(|(_, done)| !**done)
+            .map(|(relay, _)| relay)
+            .collect()
+    }
+
+    fn finish(&mut self, relay: &RelayUrl) -> bool {
+        match self.relays.get_mut(relay) {
+            Some(done) if !*done => {
+                *done = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_eose_tracker() {
+        let a = RelayUrl::try_from_str("wss://a.example.com").unwrap();
+        let b = RelayUrl::try_from_str("wss://b.example.com").unwrap();
+        let c = RelayUrl::try_from_str("wss://c.example.com").unwrap();
+        let sub = SubscriptionId::mock();
+        let start = Unixtime(1700000000);
+        let mut tracker = EoseTracker::new(
+            sub.clone(),
+            [a.clone(), b.clone(), c.clone()],
+            start,
+            Duration::from_secs(10),
+        );
+
+        assert!(!tracker.any_eose(start));
+        let other = RelayMessage::Eose(SubscriptionId("other".to_owned()));
+        assert!(!tracker.handle(&a, &other));
+        assert!(!tracker.handle(&a, &RelayMessage::Notice("hi".to_owned())));
+
+        assert!(tracker.handle(&a, &RelayMessage::Eose(sub.clone())));
+        assert!(!tracker.handle(&a, &RelayMessage::Eose(sub.clone())));
+        assert!(tracker.any_eose(start));
+        assert!(!tracker.all_eose(start));
+
+        assert!(tracker.record_disconnected(&b));
+        assert_eq!(tracker.pending_relays(), vec![&c]);
+        assert!(!tracker.timed_out(start + Duration::from_secs(9)));
+        assert!(tracker.timed_out(start + Duration::from_secs(10)));
+        assert!(tracker.all_eose(start + Duration::from_secs(10)));
+
+        assert!(tracker.handle(&c, &RelayMessage::Eose(sub)));
+        assert!(tracker.all_eose(start));
+        assert!(!tracker.timed_out(start + Duration::from_secs(60)));
+    }
+}
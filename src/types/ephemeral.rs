@@ -0,0 +1,149 @@
+use super::{EventKind, Id, PreEvent, PublicKey, Tag, Unixtime};
+use crate::Error;
+use std::time::Duration;
+
+/// A builder for an ephemeral event (kinds 20000 to 29999), which relays
+/// pass on to subscribers but do not store.
+///
+/// Every event built carries an expiration (NIP-40) a short `ttl` after it
+/// is created, so that it is dropped even by relays that would store it.
+/// `EventStorage::put` never stores ephemeral kinds.
+///
+/// ```
+/// # use nostr_types::{EphemeralEvent, PrivateKey, Unixtime};
+/// # use std::time::Duration;
+/// let alice = PrivateKey::generate().public_key();
+/// let bob = PrivateKey::generate().public_key();
+/// let pre_event = EphemeralEvent::typing(bob, true)
+///     .ttl(Duration::from_secs(5))
+///     .to_pre_event(alice, Unixtime(1700000000));
+/// assert!(pre_event.kind.is_ephemeral());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EphemeralEvent {
+    kind: EventKind,
+    tags: Vec<Tag>,
+    content: String,
+    ttl: Duration,
+}
+
+impl EphemeralEvent {
+    /// How long an ephemeral event lasts unless set with `ttl()`
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+    /// The kind used for typing indicators. No NIP assigns one, so both
+    /// sides must agree on it.
+    pub const TYPING_KIND: EventKind = EventKind::Ephemeral(20001);
+
+    /// The kind used for presence ("online", "away"). No NIP assigns one,
+    /// so both sides must agree on it.
+    pub const PRESENCE_KIND: EventKind = EventKind::Ephemeral(20002);
+
+    /// An event of `kind`, which must be ephemeral
+    pub fn new(kind: EventKind, content: String) -> Result<EphemeralEvent, Error> {
+        if !kind.is_ephemeral() {
+            return Err(Error::WrongEventKind);
+        }
+        Ok(EphemeralEvent {
+            kind,
+            tags: vec![],
+            content,
+            ttl: EphemeralEvent::DEFAULT_TTL,
+        })
+    }
+
+    /// Tell `to` that we have started (or stopped) typing
+    pub fn typing(to: PublicKey, typing: bool) -> EphemeralEvent {
+        EphemeralEvent {
+            kind: EphemeralEvent::TYPING_KIND,
+            tags: vec![Tag::Pubkey {
+                pubkey: to.into(),
+                recommended_relay_url: None,
+                petname: None,
+            }],
+            content: if typing { "typing" } else { "stopped" }.to_owned(),
+            ttl: EphemeralEvent::DEFAULT_TTL,
+        }
+    }
+
+    /// Tell everyone following us our status, such as "online" or "away"
+    pub fn presence(status: &str) -> EphemeralEvent {
+        EphemeralEvent {
+            kind: EphemeralEvent::PRESENCE_KIND,
+            tags: vec![],
+            content: status.to_owned(),
+            ttl: EphemeralEvent::DEFAULT_TTL,
+        }
+    }
+
+    /// Scope the event to a conversation or thread, by the id of its root
+    pub fn about(self, root: Id) -> EphemeralEvent {
+        self.tag(Tag::Event {
+            id: root,
+            recommended_relay_url: None,
+            marker: Some("root".to_owned()),
+            pubkey: None,
+        })
+    }
+
+    /// Add a tag
+    pub fn tag(mut self, tag: Tag) -> EphemeralEvent {
+        self.tags.push(tag);
+        self
+    }
+
+    /// How long the event lasts
+    pub fn ttl(mut self, ttl: Duration) -> EphemeralEvent {
+        self.ttl = ttl;
+        self
+    }
+
+    /// The unsigned event by `pubkey`, created at `now` and expiring `ttl`
+    /// later
+    pub fn to_pre_event(&self, pubkey: PublicKey, now: Unixtime) -> PreEvent {
+        let mut tags = self.tags.clone();
+        tags.push(Tag::Expiration(now + self.ttl));
+        PreEvent {
+            pubkey,
+            created_at: now,
+            kind: self.kind,
+            tags,
+            content: self.content.clone(),
+            ots: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Event, PrivateKey};
+
+    #[test]
+    fn test_ephemeral_event() {
+        let alice = PrivateKey::generate();
+        let bob = PrivateKey::generate().public_key();
+        let now = Unixtime(1700000000);
+
+        let pre = EphemeralEvent::typing(bob, true)
+            .about(Event::mock().id)
+            .to_pre_event(alice.public_key(), now);
+        assert_eq!(pre.kind, EphemeralEvent::TYPING_KIND);
+        let event = Event::new(pre, &alice).unwrap();
+        assert_eq!(event.expiration(), Some(Unixtime(1700000030)));
+        assert_eq!(event.content, "typing");
+
+        let pre = EphemeralEvent::presence("away")
+            .ttl(Duration::from_secs(300))
+            .to_pre_event(alice.public_key(), now);
+        assert_eq!(pre.kind, EphemeralEvent::PRESENCE_KIND);
+        let event = Event::new(pre, &alice).unwrap();
+        assert_eq!(event.expiration(), Some(Unixtime(1700000300)));
+
+        assert!(EphemeralEvent::new(EventKind::Ephemeral(25000), String::new()).is_ok());
+        assert!(matches!(
+            EphemeralEvent::new(EventKind::TextNote, String::new()),
+            Err(Error::WrongEventKind)
+        ));
+    }
+}
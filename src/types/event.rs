@@ -1,15 +1,20 @@
+use super::crypto_provider::{self, CryptoProvider};
+use super::sanitize::sanitize;
 use super::{
-    EventDelegation, EventKind, Id, Metadata, PrivateKey, PublicKey, PublicKeyHex, RelayUrl,
-    Signature, Tag, Unixtime,
+    find_nostr_bech32_pos, EncryptedDirectMessage, EventAddr, EventDelegation, EventKind,
+    EventReference, Geohash, Id, Metadata, NostrBech32, ParseProfile, ParsedTag, PrivateKey,
+    ProxyProtocol, PublicKey, PublicKeyHex, RelayUrl, SanitizeOptions, SanitizedContent, Signature,
+    Tag, UncheckedUrl, Unixtime,
 };
 use crate::Error;
-use base64::Engine;
-use k256::sha2::{Digest, Sha256};
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ops::Range;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::thread::JoinHandle;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// The main event type
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -44,18 +49,106 @@ pub struct Event {
     pub sig: Signature,
 }
 
-macro_rules! serialize_inner_event {
-    ($pubkey:expr, $created_at:expr, $kind:expr, $tags:expr,
-     $content:expr) => {{
-        format!(
-            "[0,{},{},{},{},{}]",
-            serde_json::to_string($pubkey)?,
-            serde_json::to_string($created_at)?,
-            serde_json::to_string($kind)?,
-            serde_json::to_string($tags)?,
-            serde_json::to_string($content)?
-        )
-    }};
+/// Escape a string the way it is written when computing an event id
+/// (NIP-01), without the surrounding quotes.
+///
+/// Line feed, carriage return, tab, backspace and form feed become `\n`,
+/// `\r`, `\t`, `\b` and `\f`, and double quote and backslash are preceded
+/// by a backslash. The other control characters (below U+0020) become
+/// `\u00xx` with lowercase hex, as `JSON.stringify` writes them. Everything
+/// else, including `/`, DEL and all non-ASCII characters, is written as is,
+/// never as a `\u` escape.
+pub fn canonical_escape(s: &str) -> String {
+    let mut output = String::with_capacity(s.len() + 2);
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            '\u{08}' => output.push_str("\\b"),
+            '\u{0c}' => output.push_str("\\f"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+// Replace the ${name} placeholders in every string within a template
+fn fill_placeholders<F>(value: &mut serde_json::Value, lookup: &F) -> Result<(), Error>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match value {
+        serde_json::Value::String(s) => {
+            let mut output = String::with_capacity(s.len());
+            let mut rest: &str = s;
+            while let Some(start) = rest.find("${") {
+                let (before, after) = rest.split_at(start);
+                output.push_str(before);
+                let end = after.find('}').ok_or_else(|| {
+                    Error::InvalidTemplate(format!("unclosed placeholder in {}", s))
+                })?;
+                let name = after.get(2..end).unwrap_or("");
+                let replacement = lookup(name)
+                    .ok_or_else(|| Error::InvalidTemplate(format!("no value for ${{{}}}", name)))?;
+                output.push_str(&replacement);
+                rest = after.get(end + 1..).unwrap_or("");
+            }
+            output.push_str(rest);
+            *s = output;
+        }
+        serde_json::Value::Array(values) => {
+            for v in values.iter_mut() {
+                fill_placeholders(v, lookup)?;
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for v in map.values_mut() {
+                fill_placeholders(v, lookup)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// The serialization of an event that is hashed to give its id (NIP-01):
+// [0,<pubkey>,<created_at>,<kind>,<tags>,<content>]
+fn serialize_inner_event(
+    pubkey: &PublicKey,
+    created_at: Unixtime,
+    kind: EventKind,
+    tags: &[Tag],
+    content: &str,
+) -> String {
+    format!(
+        "[0,\"{}\",{},{},{},{}]",
+        pubkey.as_hex_string(),
+        created_at.0,
+        u64::from(kind),
+        tags_json(tags),
+        json_string(content)
+    )
+}
+
+// A string as JSON, quoted and escaped as NIP-01 specifies
+fn json_string(s: &str) -> String {
+    format!("\"{}\"", canonical_escape(s))
+}
+
+// Tags as a JSON array of arrays of strings
+fn tags_json(tags: &[Tag]) -> String {
+    let tags: Vec<String> = tags
+        .iter()
+        .map(|tag| {
+            let strings: Vec<String> = tag.to_strings().iter().map(|s| json_string(s)).collect();
+            format!("[{}]", strings.join(","))
+        })
+        .collect();
+    format!("[{}]", tags.join(","))
 }
 
 /// Data used to construct an event
@@ -75,55 +168,342 @@ pub struct PreEvent {
     pub ots: Option<String>,
 }
 
+static OMIT_CLIENT_TAG: AtomicBool = AtomicBool::new(false);
+
+/// Stop `PreEvent::client()` from adding 'client' tags, for users who would
+/// rather not reveal which app they use. This applies process-wide.
+pub fn set_omit_client_tag(omit: bool) {
+    OMIT_CLIENT_TAG.store(omit, Ordering::Relaxed);
+}
+
+/// Whether `PreEvent::client()` leaves out 'client' tags
+pub fn omit_client_tag() -> bool {
+    OMIT_CLIENT_TAG.load(Ordering::Relaxed)
+}
+
+/// How `PreEvent::dedup_tags()` orders the tags it keeps
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagOrder {
+    /// Keep the order the tags were added in. Use this when position matters,
+    /// as with positional 'e' tags (NIP-10, deprecated).
+    Insertion,
+
+    /// Sort by tag name and then by value, so that the same set of tags
+    /// always comes out the same
+    Canonical,
+}
+
+/// Measurements of an event's content, from `Event::content_stats()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContentStats {
+    /// Length in bytes
+    pub bytes: usize,
+
+    /// Number of user-perceived characters (extended grapheme clusters)
+    pub graphemes: usize,
+
+    /// Number of lines
+    pub lines: usize,
+
+    /// ISO 639-3 code of the language the content seems to be written in, if
+    /// it could be reliably detected. This needs the `language` feature.
+    pub language: Option<&'static str>,
+}
+
+/// A mention found in an event's content, from
+/// `Event::references_in_content()`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContentReference {
+    /// Where it is in the content, in bytes, including any `nostr:` prefix
+    pub range: Range<usize>,
+
+    /// What it refers to
+    pub bech32: NostrBech32,
+
+    /// Whether it was written as a `nostr:` URI (NIP-21) rather than bare
+    pub is_uri: bool,
+}
+
 impl PreEvent {
+    /// Attach a delegation tag (as made by the delegator), replacing any
+    /// other. This checks first that the delegation is to this pubkey and
+    /// that the event's kind and time are within its conditions, so that an
+    /// event which would fail `Event::delegation()` is never signed.
+    pub fn delegated_by(mut self, delegation: Tag) -> Result<PreEvent, Error> {
+        let (pubkey, conditions, sig) = match &delegation {
+            Tag::Delegation {
+                pubkey,
+                conditions,
+                sig,
+            } => (pubkey, conditions, sig),
+            _ => return Err(Error::DelegationViolated("not a delegation tag".to_owned())),
+        };
+        let delegator = PublicKey::try_from_hex_string(pubkey)?;
+        let signature = Signature::try_from_hex_string(sig)?;
+        conditions
+            .verify_signature(&delegator, &self.pubkey, signature)
+            .map_err(|_| {
+                Error::DelegationViolated("delegation is not to this pubkey".to_owned())
+            })?;
+        conditions.check(self.kind, self.created_at)?;
+
+        self.tags.retain(|t| !matches!(t, Tag::Delegation { .. }));
+        self.tags.push(delegation);
+        Ok(self)
+    }
+
     /// Create a NIP-04 EncryptedDirectMessage PreEvent.
     ///
     /// Note that this creates the 'p' tag, but does not add a recommended_relay_url to it,
-    /// so the caller should handle that.
+    /// so the caller should handle that. See `EncryptedDirectMessage` for
+    /// more control.
     pub fn new_nip04(
         private_key: &PrivateKey,
         recipient_public_key: PublicKey,
         message: &str,
     ) -> Result<PreEvent, Error> {
-        let input: &[u8] = message.as_bytes();
-        let (iv, ciphertext) = private_key.nip04_encrypt(&recipient_public_key, input)?;
-        let content = format!(
-            "{}?iv={}",
-            base64::engine::general_purpose::STANDARD.encode(ciphertext),
-            base64::engine::general_purpose::STANDARD.encode(iv)
-        );
+        Ok(EncryptedDirectMessage::new(
+            private_key,
+            recipient_public_key,
+            None,
+            message,
+            Unixtime::now().unwrap(),
+        )?
+        .to_pre_event())
+    }
+
+    /// Create a TextNote PreEvent, tagged for the mentions in its content
+    /// (see `mention_tags()`)
+    pub fn new_text_note_with_mentions(
+        pubkey: PublicKey,
+        created_at: Unixtime,
+        content: &str,
+    ) -> PreEvent {
+        PreEvent {
+            pubkey,
+            created_at,
+            kind: EventKind::TextNote,
+            tags: vec![],
+            content: content.to_owned(),
+            ots: None,
+        }
+        .mention_tags()
+    }
+
+    /// Tag what the content mentions (NIP-27): a 'p' tag for each npub or
+    /// nprofile, so that the people mentioned are notified, and a 'q' tag
+    /// (NIP-18) for each note, nevent or naddr quoted. Tags already present
+    /// are not duplicated.
+    pub fn mention_tags(mut self) -> PreEvent {
+        for bech32 in NostrBech32::find_all_in_string(&self.content) {
+            let tag = match bech32 {
+                NostrBech32::Pubkey(pubkey) => Tag::Pubkey {
+                    pubkey: pubkey.into(),
+                    recommended_relay_url: None,
+                    petname: None,
+                },
+                NostrBech32::Profile(profile) => Tag::Pubkey {
+                    pubkey: profile.pubkey.into(),
+                    recommended_relay_url: profile.relays.first().cloned(),
+                    petname: None,
+                },
+                NostrBech32::Id(id) => quote_tag(id.as_hex_string(), None),
+                NostrBech32::EventPointer(ep) => {
+                    quote_tag(ep.id.as_hex_string(), ep.relays.first())
+                }
+                NostrBech32::EventAddr(ea) => quote_tag(ea.as_address(), ea.relays.first()),
+                NostrBech32::Relay(_) => continue,
+            };
+            let already = self.tags.iter().any(|t| match (t, &tag) {
+                (Tag::Pubkey { pubkey: a, .. }, Tag::Pubkey { pubkey: b, .. }) => a == b,
+                (Tag::Other { tag: a, data: x }, Tag::Other { tag: b, data: y }) => {
+                    a == b && x.first() == y.first()
+                }
+                _ => false,
+            });
+            if !already {
+                self.tags.push(tag);
+            }
+        }
+        self
+    }
+
+    /// The length in bytes of the EVENT message that will carry this event
+    /// once signed, to check against a relay's `max_message_length` before
+    /// spending work on proof of work or signing. Mining proof of work adds a
+    /// 'nonce' tag, which this does not count unless one is already present.
+    pub fn estimated_wire_size(&self) -> usize {
+        // ["EVENT",{"id":"..","pubkey":"..","created_at":..,"kind":..,
+        //   "tags":..,"content":..,"ots":..,"sig":".."}]
+        let ots = match &self.ots {
+            Some(ots) => r#","ots":"#.len() + json_string(ots).len(),
+            None => 0,
+        };
+        r#"["EVENT",{"id":"","pubkey":"","created_at":,"kind":,"tags":,"content":,"sig":""}]"#.len()
+            + 64
+            + 64
+            + self.created_at.0.to_string().len()
+            + u64::from(self.kind).to_string().len()
+            + tags_json(&self.tags).len()
+            + json_string(&self.content).len()
+            + ots
+            + 128
+    }
+
+    /// Drop tags identical to an earlier tag, such as repeated 'p' tags which
+    /// inflate the event and which some relays reject, then order the rest
+    /// by `order`
+    pub fn dedup_tags(mut self, order: TagOrder) -> PreEvent {
+        let mut seen: HashSet<Vec<String>> = HashSet::new();
+        self.tags.retain(|tag| seen.insert(tag.to_strings()));
+        if order == TagOrder::Canonical {
+            self.tags.sort_by_cached_key(|tag| tag.to_strings());
+        }
+        self
+    }
+
+    /// Create a PreEvent from a JSON template such as
+    /// `{"kind":1,"content":"Build ${build} passed","tags":[["t","ci"]]}`,
+    /// filling in `${name}` placeholders in its strings from `vars`, then
+    /// `${now}` (the unix time `now`) and `${pubkey}` (hex). `kind` and
+    /// `created_at` may be numbers or strings of digits, so that they can
+    /// come from placeholders; `created_at` defaults to `now`. Any `pubkey`
+    /// in the template is ignored.
+    pub fn from_template(
+        json: &str,
+        pubkey: PublicKey,
+        now: Unixtime,
+        vars: &[(&str, &str)],
+    ) -> Result<PreEvent, Error> {
+        let invalid = Error::InvalidTemplate;
+        let mut template: serde_json::Value = serde_json::from_str(json)?;
+        let lookup = |name: &str| -> Option<String> {
+            match vars.iter().find(|(n, _)| *n == name) {
+                Some((_, value)) => Some(value.to_string()),
+                None if name == "now" => Some(now.0.to_string()),
+                None if name == "pubkey" => Some(pubkey.as_hex_string()),
+                None => None,
+            }
+        };
+        fill_placeholders(&mut template, &lookup)?;
+
+        let number = |field: &str| -> Result<Option<i64>, Error> {
+            match template.get(field) {
+                None => Ok(None),
+                Some(serde_json::Value::Number(n)) => n
+                    .as_i64()
+                    .map(Some)
+                    .ok_or_else(|| invalid(format!("{} is not an integer", field))),
+                Some(serde_json::Value::String(s)) => s
+                    .parse::<i64>()
+                    .map(Some)
+                    .map_err(|_| invalid(format!("{} is not an integer: {}", field, s))),
+                Some(_) => Err(invalid(format!("{} is not an integer", field))),
+            }
+        };
+        let kind = number("kind")?.ok_or_else(|| invalid("kind is missing".to_owned()))?;
+        let created_at = number("created_at")?.map(Unixtime).unwrap_or(now);
+        let content = match template.get("content") {
+            None => "".to_owned(),
+            Some(serde_json::Value::String(s)) => s.to_owned(),
+            Some(_) => return Err(invalid("content is not a string".to_owned())),
+        };
+        let tags: Vec<Tag> = match template.get("tags") {
+            None => vec![],
+            Some(tags) => serde_json::from_value(tags.clone())?,
+        };
 
         Ok(PreEvent {
-            pubkey: private_key.public_key(),
-            created_at: Unixtime::now().unwrap(),
-            kind: EventKind::EncryptedDirectMessage,
-            tags: vec![Tag::Pubkey {
-                pubkey: recipient_public_key.into(),
-                recommended_relay_url: None, // FIXME,
-                petname: None,
-            }],
+            pubkey,
+            created_at,
+            kind: EventKind::from(
+                u64::try_from(kind).map_err(|_| invalid(format!("kind is negative: {}", kind)))?,
+            ),
+            tags,
             content,
             ots: None,
         })
     }
+
+    /// Mark the event as protected (NIP-70), so that relays only accept it when
+    /// published by its authenticated author
+    pub fn protected(mut self) -> PreEvent {
+        if !self.tags.iter().any(|t| matches!(t, Tag::Protected)) {
+            self.tags.push(Tag::Protected);
+        }
+        self
+    }
+
+    /// Attribute the event to the app creating it with a 'client' tag,
+    /// replacing any already present. `address` is that of the app's NIP-89
+    /// handler information event ("31990:<pubkey>:<d>"), and `relay_url` a
+    /// relay where it can be found. Nothing is added if
+    /// `set_omit_client_tag(true)` has been called.
+    pub fn client(
+        mut self,
+        name: &str,
+        address: Option<&str>,
+        relay_url: Option<UncheckedUrl>,
+    ) -> PreEvent {
+        if omit_client_tag() {
+            return self;
+        }
+        self.tags.retain(|t| !matches!(t, Tag::Client { .. }));
+        self.tags.push(Tag::Client {
+            name: name.to_owned(),
+            address: address.map(|a| a.to_owned()),
+            relay_url,
+        });
+        self
+    }
+
+    /// Locate the event, with a 'g' tag for the geohash of the location at
+    /// `precision` and for each less precise geohash covering it, so that it
+    /// can be found by area
+    pub fn location(
+        mut self,
+        latitude: f64,
+        longitude: f64,
+        precision: usize,
+    ) -> Result<PreEvent, Error> {
+        let geohash = Geohash::encode(latitude, longitude, precision)?;
+        for prefix in geohash.prefixes() {
+            let tag = Tag::Geohash(prefix.as_str().to_owned());
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        Ok(self)
+    }
+}
+
+fn quote_tag(value: String, relay: Option<&UncheckedUrl>) -> Tag {
+    let mut data = vec![value];
+    if let Some(relay) = relay {
+        data.push(relay.0.clone());
+    }
+    Tag::Other {
+        tag: "q".to_owned(),
+        data,
+    }
 }
 
 impl Event {
-    fn hash(input: &PreEvent) -> Result<Id, Error> {
-        let serialized: String = serialize_inner_event!(
+    pub(crate) fn hash(input: &PreEvent) -> Result<Id, Error> {
+        Self::hash_with(input, &*crypto_provider::current())
+    }
+
+    fn hash_with(input: &PreEvent, provider: &dyn CryptoProvider) -> Result<Id, Error> {
+        let serialized: String = serialize_inner_event(
             &input.pubkey,
-            &input.created_at,
-            &input.kind,
+            input.created_at,
+            input.kind,
             &input.tags,
-            &input.content
+            &input.content,
         );
 
         // Hash
-        let mut hasher = Sha256::new();
-        hasher.update(serialized.as_bytes());
-        let id = hasher.finalize();
-        let id: [u8; 32] = id.into();
-        Ok(Id(id))
+        Ok(Id(provider.sha256(serialized.as_bytes())))
     }
 
     /// Create a new event
@@ -168,6 +548,10 @@ impl Event {
 
         let cores = num_cpus::get();
 
+        // Fetched once, so that mining does not take the provider's lock for
+        // every attempt
+        let provider = crypto_provider::current();
+
         let quitting = Arc::new(AtomicBool::new(false));
         let nonce = Arc::new(AtomicU64::new(0)); // will store the nonce that works
 
@@ -181,6 +565,7 @@ impl Event {
             let quitting = quitting.clone();
             let nonce = nonce.clone();
             let zero_bits = zero_bits;
+            let provider = provider.clone();
             let join_handle = thread::spawn(move || {
                 loop {
                     if quitting.load(Ordering::Relaxed) {
@@ -192,7 +577,7 @@ impl Event {
                         target: target.clone(),
                     };
 
-                    let id = Self::hash(&input).unwrap();
+                    let id = Self::hash_with(&input, &*provider).unwrap();
 
                     if get_leading_zero_bits(&id.0) >= zero_bits {
                         nonce.store(attempt, Ordering::Relaxed);
@@ -217,7 +602,7 @@ impl Event {
             nonce: format!("{}", nonce.load(Ordering::Relaxed)),
             target,
         };
-        let id = Self::hash(&input).unwrap();
+        let id = Self::hash_with(&input, &*provider).unwrap();
 
         // Signature
         let signature = privkey.sign_id(id)?;
@@ -234,42 +619,74 @@ impl Event {
         })
     }
 
+    /// Deserialize an event from JSON under a `ParseProfile`. This does not
+    /// verify it.
+    pub fn from_json_with_profile(json: &str, profile: ParseProfile) -> Result<Event, Error> {
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        profile.prepare_event(&mut value)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
     /// Check the validity of an event. This is useful if you deserialize an event
     /// from the network. If you create an event using new() it should already be
     /// trustworthy.
+    ///
+    /// The checks are made in order and the error says which one failed:
+    /// `Error::HashMismatch` (with the recomputed id) if the id is not the hash
+    /// of the event, `Error::InvalidSignature` if the signature does not verify,
+    /// and `Error::EventInFuture` if `created_at` is after `maxtime`. An
+    /// unparsable pubkey, id or signature is already rejected when deserializing.
     pub fn verify(&self, maxtime: Option<Unixtime>) -> Result<(), Error> {
         use k256::schnorr::signature::Verifier;
 
-        let serialized: String = serialize_inner_event!(
+        let serialized: String = serialize_inner_event(
             &self.pubkey,
-            &self.created_at,
-            &self.kind,
+            self.created_at,
+            self.kind,
             &self.tags,
-            &self.content
+            &self.content,
         );
 
-        // Verify the signature
-        self.pubkey.0.verify(serialized.as_bytes(), &self.sig.0)?;
+        // Verify the ID is the SHA256
+        let computed = Id(crypto_provider::sha256(serialized.as_bytes()));
+        if computed != self.id {
+            return Err(Error::HashMismatch {
+                claimed: self.id.into(),
+                computed: computed.into(),
+            });
+        }
 
-        // Also verify the ID is the SHA256
-        // (the above verify function also does it internally,
-        //  so there is room for improvement here)
-        let mut hasher = Sha256::new();
-        hasher.update(serialized.as_bytes());
-        let id = hasher.finalize();
+        // Verify the signature
+        self.pubkey
+            .0
+            .verify(serialized.as_bytes(), &self.sig.0)
+            .map_err(Error::InvalidSignature)?;
 
         // Optional verify that the message was in the past
-        if let Some(mt) = maxtime {
-            if self.created_at > mt {
-                return Err(Error::EventInFuture);
+        if let Some(maxtime) = maxtime {
+            if self.created_at > maxtime {
+                return Err(Error::EventInFuture {
+                    created_at: self.created_at,
+                    maxtime,
+                });
             }
         }
 
-        if *id != self.id.0 {
-            Err(Error::HashMismatch)
-        } else {
-            Ok(())
-        }
+        Ok(())
+    }
+
+    /// Get the event in a form fit to pass on to other relays, as a bridge or
+    /// mirror does. Fields outside of NIP-01 (`ots`, and anything a relay added,
+    /// which is dropped when deserializing) are removed and hex is lowercase.
+    /// The id and signature are checked against the result, so an event that
+    /// did not survive parsing byte-exact is never passed on.
+    ///
+    /// Returns the JSON of the event.
+    pub fn sanitize_for_rebroadcast(&self) -> Result<String, Error> {
+        let mut event = self.clone();
+        event.ots = None;
+        event.verify(None)?;
+        Ok(serde_json::to_string(&event)?)
     }
 
     // Mock data for testing
@@ -299,6 +716,30 @@ impl Event {
         Event::new(input, privkey)
     }
 
+    /// Create an authentication event (NIP-42) answering a relay's challenge
+    pub fn new_auth(
+        privkey: &PrivateKey,
+        relay: &RelayUrl,
+        challenge: &str,
+        created_at: Unixtime,
+    ) -> Result<Event, Error> {
+        let pre = PreEvent {
+            pubkey: privkey.public_key(),
+            created_at,
+            kind: EventKind::Auth,
+            tags: vec![
+                Tag::Relay(relay.to_unchecked_url()),
+                Tag::Other {
+                    tag: "challenge".to_string(),
+                    data: vec![challenge.to_string()],
+                },
+            ],
+            content: "".to_string(),
+            ots: None,
+        };
+        Event::new(pre, privkey)
+    }
+
     /// Create a ZapRequest event
     /// These events are not published to nostr, they are sent to a lnurl.
     pub fn new_zap_request(
@@ -337,29 +778,18 @@ impl Event {
                 id: ze,
                 recommended_relay_url: None,
                 marker: None,
+                pubkey: None,
             });
         }
 
         Event::new(pre_event, privkey)
     }
 
-    /// If an event is an EncryptedDirectMessage, decrypt it's contents
+    /// If an event is an EncryptedDirectMessage, decrypt it's contents.
+    /// This works with the key of either the sender or the recipient.
     pub fn decrypted_contents(&self, private_key: &PrivateKey) -> Result<String, Error> {
-        if self.kind != EventKind::EncryptedDirectMessage {
-            return Err(Error::WrongEventKind);
-        }
-        let parts: Vec<&str> = self.content.split("?iv=").collect();
-        if parts.len() != 2 {
-            return Err(Error::BadEncryptedMessage);
-        }
-
-        let ciphertext: Vec<u8> = base64::engine::general_purpose::STANDARD.decode(parts[0])?;
-        let iv_vec: Vec<u8> = base64::engine::general_purpose::STANDARD.decode(parts[1])?;
-        let iv: [u8; 16] = iv_vec.try_into().unwrap();
-
-        let decrypted_bytes = private_key.nip04_decrypt(&self.pubkey, &ciphertext, iv)?;
-        let s: String = String::from_utf8_lossy(&decrypted_bytes).into();
-        Ok(s)
+        let dm = EncryptedDirectMessage::from_event(self)?;
+        Ok(dm.decrypt(private_key)?.0)
     }
 
     /// If the event refers to people, get all the PublicKeys it refers to
@@ -416,7 +846,7 @@ impl Event {
     }
 
     /// Is the event a reply?
-    #[deprecated(since = "0.2.0", note = "please use `replies_to` instead")]
+    #[deprecated(since = "0.2.0", note = "please use `reply_target` instead")]
     pub fn is_reply(&self) -> bool {
         if self.kind != EventKind::TextNote {
             return false;
@@ -433,6 +863,10 @@ impl Event {
 
     /// If this event replies to another, get that other event's Id along with
     /// an optional recommended_relay_url
+    #[deprecated(
+        since = "0.5.0",
+        note = "please use `reply_target`, which also covers addressable events"
+    )]
     pub fn replies_to(&self) -> Option<(Id, Option<RelayUrl>)> {
         // must be a text note
         if self.kind != EventKind::TextNote {
@@ -455,6 +889,7 @@ impl Event {
                 id,
                 recommended_relay_url,
                 marker,
+                ..
             } = tag
             {
                 if marker.is_some() && marker.as_deref().unwrap() == "reply" {
@@ -474,6 +909,7 @@ impl Event {
                 id,
                 recommended_relay_url,
                 marker,
+                ..
             } = tag
             {
                 if marker.is_some() && marker.as_deref().unwrap() == "root" {
@@ -492,6 +928,7 @@ impl Event {
             id,
             recommended_relay_url,
             marker,
+            ..
         }) = self
             .tags
             .iter()
@@ -527,6 +964,7 @@ impl Event {
                 id,
                 recommended_relay_url,
                 marker,
+                ..
             } = tag
             {
                 if marker.is_some() && marker.as_deref().unwrap() == "root" {
@@ -554,6 +992,7 @@ impl Event {
             id,
             recommended_relay_url,
             marker,
+            ..
         }) = self.tags.iter().find(|t| matches!(t, Tag::Event { .. }))
         {
             if marker.is_none() {
@@ -580,6 +1019,7 @@ impl Event {
                 id,
                 recommended_relay_url,
                 marker: _,
+                ..
             } = tag
             {
                 output.push((
@@ -610,6 +1050,7 @@ impl Event {
                 id,
                 recommended_relay_url,
                 marker,
+                ..
             } = tag
             {
                 if marker.is_some() && marker.as_deref().unwrap() == "mention" {
@@ -636,6 +1077,7 @@ impl Event {
                     id,
                     recommended_relay_url,
                     marker,
+                    ..
                 } = tag
                 {
                     if marker.is_none() {
@@ -655,6 +1097,10 @@ impl Event {
 
     /// If this event reacts to another, get that other event's Id,
     /// the reaction content, and an optional Recommended relay Url
+    #[deprecated(
+        since = "0.5.0",
+        note = "please use `reaction_target`, which also covers addressable events"
+    )]
     pub fn reacts_to(&self) -> Option<(Id, String, Option<RelayUrl>)> {
         if self.kind != EventKind::Reaction {
             return None;
@@ -665,6 +1111,7 @@ impl Event {
             id,
             recommended_relay_url,
             marker: _,
+            ..
         }) = self
             .tags
             .iter()
@@ -685,6 +1132,10 @@ impl Event {
 
     /// If this event deletes others, get all the Ids of the events that it deletes
     /// along with the reason for the deletion
+    #[deprecated(
+        since = "0.5.0",
+        note = "please use `deletion_targets`, which also covers addresses"
+    )]
     pub fn deletes(&self) -> Option<(Vec<Id>, String)> {
         if self.kind != EventKind::EventDeletion {
             return None;
@@ -698,6 +1149,7 @@ impl Event {
                 id,
                 recommended_relay_url: _,
                 marker: _,
+                ..
             } = tag
             {
                 ids.push(*id);
@@ -711,19 +1163,194 @@ impl Event {
         }
     }
 
+    /// The events this event refers to by 'e' and 'a' tags (and, for the
+    /// root of a comment, 'E' and 'A' tags), with their markers
+    pub fn references(&self) -> Vec<(EventReference, Option<&str>)> {
+        self.tags
+            .iter()
+            .filter_map(EventReference::from_tag)
+            .collect()
+    }
+
+    /// What this text note or comment replies to, which may be an addressable
+    /// event
+    pub fn reply_target(&self) -> Option<EventReference> {
+        if self.kind == EventKind::Comment {
+            return self.lowercase_references().next();
+        }
+        if self.kind != EventKind::TextNote {
+            return None;
+        }
+        let references = self.references();
+        for wanted in ["reply", "root"] {
+            if let Some((r, _)) = references.iter().find(|(_, m)| *m == Some(wanted)) {
+                return Some(r.clone());
+            }
+        }
+        // Otherwise the last 'e' tag, if it is unmarked
+        match references.iter().rev().find(|(r, _)| r.id().is_some()) {
+            Some((r, None)) => Some(r.clone()),
+            _ => None,
+        }
+    }
+
+    /// The root of the thread this text note or comment is in, if it is a
+    /// reply
+    pub fn thread_root(&self) -> Option<EventReference> {
+        if self.kind == EventKind::Comment {
+            return self
+                .tags
+                .iter()
+                .filter(|t| matches!(t, Tag::Other { .. }))
+                .find_map(EventReference::from_tag)
+                .map(|(r, _)| r);
+        }
+        if self.kind != EventKind::TextNote {
+            return None;
+        }
+        let references = self.references();
+        if let Some((r, _)) = references.iter().find(|(_, m)| *m == Some("root")) {
+            return Some(r.clone());
+        }
+        // Otherwise the first of several 'e' tags, if it is unmarked
+        let mut ids = references.iter().filter(|(r, _)| r.id().is_some());
+        match (ids.next(), ids.next()) {
+            (Some((r, None)), Some(_)) => Some(r.clone()),
+            _ => None,
+        }
+    }
+
+    /// What this reaction reacts to: the addressable event of its 'a' tag if
+    /// it has one, otherwise the event of its last 'e' tag (NIP-25)
+    pub fn reaction_target(&self) -> Option<EventReference> {
+        if self.kind != EventKind::Reaction {
+            return None;
+        }
+        let references: Vec<EventReference> = self.lowercase_references().collect();
+        references
+            .iter()
+            .find(|r| r.addr().is_some())
+            .or(references.last())
+            .cloned()
+    }
+
+    /// The events and addresses this deletion request deletes (NIP-09)
+    pub fn deletion_targets(&self) -> Vec<EventReference> {
+        if self.kind != EventKind::EventDeletion {
+            return vec![];
+        }
+        self.lowercase_references().collect()
+    }
+
+    // References by 'e' and 'a' tags only
+    fn lowercase_references(&self) -> impl Iterator<Item = EventReference> + '_ {
+        self.tags
+            .iter()
+            .filter(|t| matches!(t, Tag::Event { .. } | Tag::Address { .. }))
+            .filter_map(|t| EventReference::from_tag(t).map(|(r, _)| r))
+    }
+
     /// If this event specifies the client that created it, return that client string
     pub fn client(&self) -> Option<String> {
         for tag in self.tags.iter() {
-            if let Tag::Other { tag, data } = tag {
-                if tag == "client" && !data.is_empty() {
-                    return Some(data[0].clone());
-                }
+            if let Tag::Client { name, .. } = tag {
+                return Some(name.clone());
             }
         }
 
         None
     }
 
+    /// If this event specifies a title, return that title string
+    pub fn title(&self) -> Option<String> {
+        for tag in self.tags.iter() {
+            if let Tag::Title(title) = tag {
+                return Some(title.clone());
+            }
+        }
+
+        None
+    }
+
+    /// If this event specifies a summary, return that summary string
+    pub fn summary(&self) -> Option<String> {
+        for tag in self.tags.iter() {
+            if let Tag::Summary(summary) = tag {
+                return Some(summary.clone());
+            }
+        }
+
+        None
+    }
+
+    /// If this event specifies an image, return the image URL and optional dimensions
+    pub fn image(&self) -> Option<(UncheckedUrl, Option<String>)> {
+        for tag in self.tags.iter() {
+            if let Tag::Image { url, dimensions } = tag {
+                return Some((url.clone(), dimensions.clone()));
+            }
+        }
+
+        None
+    }
+
+    /// If this event specifies when its content was first published, return that time
+    pub fn published_at(&self) -> Option<Unixtime> {
+        for tag in self.tags.iter() {
+            if let Tag::PublishedAt(time) = tag {
+                return Some(*time);
+            }
+        }
+
+        None
+    }
+
+    /// If this event specifies when it expires, return that time
+    pub fn expiration(&self) -> Option<Unixtime> {
+        for tag in self.tags.iter() {
+            if let Tag::Expiration(time) = tag {
+                return Some(*time);
+            }
+        }
+
+        None
+    }
+
+    /// If this event specifies an alt description, return that description string
+    pub fn alt(&self) -> Option<String> {
+        for tag in self.tags.iter() {
+            if let Tag::Alt(alt) = tag {
+                return Some(alt.clone());
+            }
+        }
+
+        None
+    }
+
+    /// Is this event protected (NIP-70)? Relays should only accept protected events
+    /// from their author.
+    pub fn is_protected(&self) -> bool {
+        self.tags.iter().any(|t| matches!(t, Tag::Protected))
+    }
+
+    /// For relays: should this event be rejected under NIP-70, given the pubkey the
+    /// connection has authenticated as (if any)? If so, this returns the message to
+    /// send back in the `OK` response.
+    pub fn protected_rejection(&self, authenticated: Option<PublicKey>) -> Option<String> {
+        if !self.is_protected() {
+            return None;
+        }
+        match authenticated {
+            None => {
+                Some("auth-required: this event may only be published by its author".to_owned())
+            }
+            Some(pk) if pk != self.pubkey => {
+                Some("restricted: this event may only be published by its author".to_owned())
+            }
+            Some(_) => None,
+        }
+    }
+
     /// If this event specifies a subject, return that subject string
     pub fn subject(&self) -> Option<String> {
         for tag in self.tags.iter() {
@@ -735,6 +1362,17 @@ impl Event {
         None
     }
 
+    /// The most precise valid geohash in the event's 'g' tags, if any
+    pub fn geohash(&self) -> Option<Geohash> {
+        self.tags
+            .iter()
+            .filter_map(|tag| match tag {
+                Tag::Geohash(g) => Geohash::try_from_str(g).ok(),
+                _ => None,
+            })
+            .max_by_key(|g| g.precision())
+    }
+
     /// If this event specifies a content warning, return that subject string
     pub fn content_warning(&self) -> Option<String> {
         for tag in self.tags.iter() {
@@ -746,12 +1384,64 @@ impl Event {
         None
     }
 
-    /// If this is a parameterized event, get the parameter
+    /// Measure the content, for rendering decisions or relay policy
+    pub fn content_stats(&self) -> ContentStats {
+        ContentStats {
+            bytes: self.content.len(),
+            graphemes: self.content.graphemes(true).count(),
+            lines: self.content.lines().count(),
+            language: detect_language(&self.content),
+        }
+    }
+
+    /// The content made safe to display: normalized (NFC), without control
+    /// or bidirectional override characters, and with long runs of line
+    /// breaks shortened and escaping as `options` say. The report says what
+    /// was changed.
+    pub fn content_sanitized(&self, options: &SanitizeOptions) -> SanitizedContent {
+        sanitize(&self.content, options)
+    }
+
+    /// Find the pubkeys, events, addresses and relays mentioned in the content
+    /// (NIP-27), as `nostr:` URIs or bare bech32, in order. Each comes with
+    /// where it is, so that it can be rendered in place.
+    pub fn references_in_content(&self) -> Vec<ContentReference> {
+        let mut output: Vec<ContentReference> = Vec::new();
+        let mut cursor = 0;
+        while let Some((start, end)) = self.content.get(cursor..).and_then(find_nostr_bech32_pos) {
+            let (start, end) = (cursor + start, cursor + end);
+            cursor = end;
+            let bech32 = match self
+                .content
+                .get(start..end)
+                .and_then(NostrBech32::try_from_string)
+            {
+                Some(bech32) => bech32,
+                None => continue,
+            };
+            let is_uri = start >= 6
+                && self
+                    .content
+                    .get(start - 6..start)
+                    .map(|prefix| prefix.eq_ignore_ascii_case("nostr:"))
+                    .unwrap_or(false);
+            output.push(ContentReference {
+                range: if is_uri { start - 6 } else { start }..end,
+                bech32,
+                is_uri,
+            });
+        }
+        output
+    }
+
+    /// If this is an addressable (parameterized replaceable) event, get the parameter
     pub fn parameter(&self) -> Option<String> {
-        if self.kind.is_parameterized_replaceable() {
+        if self.kind.is_addressable() {
             for tag in self.tags.iter() {
-                if let Tag::Parameter(param) = tag {
-                    return Some(param.to_owned());
+                match tag {
+                    Tag::Identifier(param) => return Some(param.to_owned()),
+                    Tag::Parameter(param) => return Some(param.to_owned()),
+                    _ => {}
                 }
             }
             Some("".to_owned()) // implicit
@@ -760,6 +1450,30 @@ impl Event {
         }
     }
 
+    /// Does this event replace the `other` event?
+    ///
+    /// This is true if both events are replaceable or addressable, share the same
+    /// author, kind (and parameter, if addressable), and this event is newer. Per
+    /// NIP-01, if both have the same `created_at` the one with the lowest id wins.
+    pub fn replaces(&self, other: &Event) -> bool {
+        if !self.kind.is_replaceable() && !self.kind.is_addressable() {
+            return false;
+        }
+        if self.pubkey != other.pubkey || self.kind != other.kind {
+            return false;
+        }
+        if self.kind.is_addressable() && self.parameter() != other.parameter() {
+            return false;
+        }
+        self.created_at > other.created_at
+            || (self.created_at == other.created_at && self.id < other.id)
+    }
+
+    /// Return all the tags of a given `ParsedTag` type
+    pub fn tags_of_type<T: ParsedTag>(&self) -> Vec<T> {
+        self.tags.iter().filter_map(T::from_tag).collect()
+    }
+
     /// Return all the hashtags this event refers to
     pub fn hashtags(&self) -> Vec<String> {
         if self.kind != EventKind::TextNote {
@@ -777,6 +1491,47 @@ impl Event {
         output
     }
 
+    /// Return the kinds of the events this event refers to, from its 'k'
+    /// tags (as in generic reposts, reactions, comments and DVM results)
+    pub fn referenced_kinds(&self) -> Vec<EventKind> {
+        self.tags
+            .iter()
+            .filter_map(|tag| match tag {
+                Tag::KindReference(kind) => Some(*kind),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Return the addressable events this event refers to, from its 'a'
+    /// tags, each with its relay hint if it has one
+    pub fn referenced_addresses(&self) -> Vec<EventAddr> {
+        self.tags
+            .iter()
+            .filter_map(|tag| match tag {
+                Tag::Address { address, .. } => {
+                    let mut address = address.clone();
+                    address.relays.retain(|u| !u.0.is_empty());
+                    Some(address)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Return the objects this event was bridged from, from its 'proxy' tags
+    /// (NIP-48). Clients can use these to recognize the same content arriving
+    /// through different bridges.
+    pub fn proxies(&self) -> Vec<(ProxyProtocol, String)> {
+        self.tags
+            .iter()
+            .filter_map(|tag| match tag {
+                Tag::Proxy { id, protocol } => Some((protocol.clone(), id.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Return all the URLs this event refers to
     pub fn urls(&self) -> Vec<RelayUrl> {
         if self.kind != EventKind::TextNote {
@@ -838,27 +1593,10 @@ impl Event {
                 // Verify the delegation tag
                 match conditions.verify_signature(&delegator_pubkey, &self.pubkey, signature) {
                     Ok(_) => {
-                        // Check conditions
-                        if let Some(kind) = conditions.kind {
-                            if self.kind != kind {
-                                return EventDelegation::InvalidDelegation(
-                                    "Event Kind not delegated".to_owned(),
-                                );
-                            }
-                        }
-                        if let Some(created_after) = conditions.created_after {
-                            if self.created_at < created_after {
-                                return EventDelegation::InvalidDelegation(
-                                    "Event created before delegation started".to_owned(),
-                                );
-                            }
-                        }
-                        if let Some(created_before) = conditions.created_before {
-                            if self.created_at > created_before {
-                                return EventDelegation::InvalidDelegation(
-                                    "Event created after delegation ended".to_owned(),
-                                );
-                            }
+                        if let Err(Error::DelegationViolated(reason)) =
+                            conditions.check(self.kind, self.created_at)
+                        {
+                            return EventDelegation::InvalidDelegation(reason);
                         }
                         return EventDelegation::DelegatedBy(delegator_pubkey);
                     }
@@ -887,12 +1625,139 @@ fn get_leading_zero_bits(bytes: &[u8]) -> u8 {
     res
 }
 
+#[cfg(feature = "language")]
+fn detect_language(text: &str) -> Option<&'static str> {
+    whatlang::detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().code())
+}
+
+#[cfg(not(feature = "language"))]
+fn detect_language(_text: &str) -> Option<&'static str> {
+    None
+}
+
 #[cfg(test)]
 mod test {
     use crate::types::*;
+    use crate::{Error, ErrorCode};
 
     test_serde! {Event, test_event_serde}
 
+    #[test]
+    fn test_canonical_escape() {
+        assert_eq!(
+            canonical_escape("a\"b\\c\nd\re\tf\u{8}\u{c}\u{1}\u{1f}/\u{7f}é😀"),
+            "a\\\"b\\\\c\\nd\\re\\tf\\b\\f\\u0001\\u001f/\u{7f}é😀"
+        );
+
+        // The same as serde_json, for every character up to U+0200 and some
+        // beyond, including the separators JavaScript once treated specially
+        let mut samples: Vec<char> = (0..0x200).filter_map(char::from_u32).collect();
+        samples.extend(['\u{2028}', '\u{2029}', '\u{feff}', '\u{fffd}', '😀']);
+        for c in samples {
+            let s = c.to_string();
+            assert_eq!(
+                format!("\"{}\"", canonical_escape(&s)),
+                serde_json::to_string(&s).unwrap(),
+                "U+{:04X}",
+                c as u32
+            );
+        }
+    }
+
+    #[test]
+    fn test_event_hash_tricky_content() {
+        // The expected id is the SHA-256 of the serialization that Python's
+        // json.dumps(..., ensure_ascii=False, separators=(',', ':')) gives
+        let pre = PreEvent {
+            pubkey: PublicKey::try_from_hex_string(
+                "b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9",
+            )
+            .unwrap(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::TextNote,
+            tags: vec![
+                Tag::Hashtag("émoji😀".to_string()),
+                Tag::Other {
+                    tag: "x".to_string(),
+                    data: vec![
+                        "line1\nline2".to_string(),
+                        "tab\there".to_string(),
+                        "back\\slash".to_string(),
+                        "nul\u{0}".to_string(),
+                    ],
+                },
+            ],
+            content: "Hello\n\"world\"\t\\ ünïcödé 😀 </script> \u{1}\u{7f}\r\u{8}\u{c} "
+                .to_string(),
+            ots: None,
+        };
+        assert_eq!(
+            Event::hash(&pre).unwrap().as_hex_string(),
+            "0197148cc317e762d9fc9f7f01968147d90a4a32b5c4ecaa338f93212f0b3384"
+        );
+
+        // And it survives a round trip through JSON
+        let private_key = PrivateKey::generate();
+        let mut pre = pre;
+        pre.pubkey = private_key.public_key();
+        let event = Event::new(pre, &private_key).unwrap();
+        let json = serde_json::to_string(&event).unwrap();
+        let event: Event = serde_json::from_str(&json).unwrap();
+        assert!(event.verify(None).is_ok());
+    }
+
+    #[test]
+    fn test_pre_event_from_template() {
+        let pubkey = PrivateKey::generate().public_key();
+        let template = r#"{
+            "kind": "${kind}",
+            "content": "Build ${build} passed at ${now}\n\"quoted\"",
+            "tags": [["t", "ci"], ["p", "${pubkey}"]]
+        }"#;
+        let pre = PreEvent::from_template(
+            template,
+            pubkey,
+            Unixtime(1700000000),
+            &[("kind", "1"), ("build", "#42")],
+        )
+        .unwrap();
+        assert_eq!(pre.kind, EventKind::TextNote);
+        assert_eq!(pre.created_at, Unixtime(1700000000));
+        assert_eq!(pre.content, "Build #42 passed at 1700000000\n\"quoted\"");
+        assert_eq!(
+            pre.tags,
+            vec![
+                Tag::Hashtag("ci".to_string()),
+                Tag::Pubkey {
+                    pubkey: pubkey.into(),
+                    recommended_relay_url: None,
+                    petname: None,
+                }
+            ]
+        );
+
+        let from = |json: &str| PreEvent::from_template(json, pubkey, Unixtime(0), &[]);
+        assert_eq!(
+            from(r#"{"kind":1,"created_at":5}"#).unwrap().created_at,
+            Unixtime(5)
+        );
+        assert!(matches!(
+            from(r#"{"kind":1,"content":"${missing}"}"#),
+            Err(Error::InvalidTemplate(_))
+        ));
+        assert!(matches!(
+            from(r#"{"kind":1,"content":"${now"}"#),
+            Err(Error::InvalidTemplate(_))
+        ));
+        assert!(matches!(
+            from(r#"{"content":"x"}"#),
+            Err(Error::InvalidTemplate(_))
+        ));
+        assert!(from("not json").is_err());
+    }
+
     #[test]
     fn test_event_new_and_verify() {
         let privkey = PrivateKey::mock();
@@ -905,6 +1770,7 @@ mod test {
                 id: Id::mock(),
                 recommended_relay_url: Some(UncheckedUrl::mock()),
                 marker: None,
+                pubkey: None,
             }],
             content: "Hello World!".to_string(),
             ots: None,
@@ -931,6 +1797,198 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_event_verify_diagnostics() {
+        let event = Event::mock();
+
+        let mut modified = event.clone();
+        modified.content = "Changed".to_owned();
+        let recomputed = Event::new(
+            PreEvent {
+                pubkey: modified.pubkey,
+                created_at: modified.created_at,
+                kind: modified.kind,
+                tags: modified.tags.clone(),
+                content: modified.content.clone(),
+                ots: None,
+            },
+            &PrivateKey::mock(),
+        )
+        .unwrap();
+        match modified.verify(None) {
+            Err(Error::HashMismatch { claimed, computed }) => {
+                assert_eq!(claimed, IdHex::from(event.id));
+                assert_eq!(computed, IdHex::from(recomputed.id));
+            }
+            other => panic!("Expected a hash mismatch, got {other:?}"),
+        }
+
+        let mut resigned = event.clone();
+        resigned.sig = recomputed.sig;
+        assert!(matches!(
+            resigned.verify(None),
+            Err(Error::InvalidSignature(_))
+        ));
+
+        let maxtime = event.created_at - std::time::Duration::from_secs(1);
+        match event.verify(Some(maxtime)) {
+            Err(e @ Error::EventInFuture { .. }) => {
+                assert_eq!(e.code(), ErrorCode::EventInFuture)
+            }
+            other => panic!("Expected an event in the future, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_event_sanitize_for_rebroadcast() {
+        let event = Event::mock();
+        let clean = serde_json::to_string(&event).unwrap();
+
+        // A relay added a field and another wrote the id in uppercase
+        let id_hex = event.id.as_hex_string();
+        let dirty = clean
+            .replacen('{', r#"{"seen_on":["wss://relay.example.com"],"#, 1)
+            .replace(&id_hex, &id_hex.to_uppercase());
+        let mut received: Event = serde_json::from_str(&dirty).unwrap();
+        received.ots = Some("opentimestamp".to_owned());
+        assert_eq!(received.sanitize_for_rebroadcast().unwrap(), clean);
+
+        received.content = "Corrupted".to_owned();
+        assert!(received.sanitize_for_rebroadcast().is_err());
+    }
+
+    #[test]
+    fn test_event_replaces() {
+        let privkey = PrivateKey::mock();
+        let make = |kind: EventKind, created_at: i64, tags: Vec<Tag>| {
+            let preevent = PreEvent {
+                pubkey: privkey.public_key(),
+                created_at: Unixtime(created_at),
+                kind,
+                tags,
+                content: "".to_string(),
+                ots: None,
+            };
+            Event::new(preevent, &privkey).unwrap()
+        };
+
+        let old = make(EventKind::Metadata, 1680000000, vec![]);
+        let new = make(EventKind::Metadata, 1680000100, vec![]);
+        assert!(new.replaces(&old));
+        assert!(!old.replaces(&new));
+
+        let note1 = make(EventKind::TextNote, 1680000000, vec![]);
+        let note2 = make(EventKind::TextNote, 1680000100, vec![]);
+        assert!(!note2.replaces(&note1));
+
+        let a_old = make(
+            EventKind::LongFormContent,
+            1680000000,
+            vec![Tag::Identifier("a".to_string())],
+        );
+        let a_new = make(
+            EventKind::LongFormContent,
+            1680000100,
+            vec![Tag::Identifier("a".to_string())],
+        );
+        let b_new = make(
+            EventKind::LongFormContent,
+            1680000100,
+            vec![Tag::Identifier("b".to_string())],
+        );
+        assert!(a_new.replaces(&a_old));
+        assert!(!b_new.replaces(&a_old));
+    }
+
+    #[test]
+    fn test_event_metadata_tag_accessors() {
+        let privkey = PrivateKey::mock();
+        let preevent = PreEvent {
+            pubkey: privkey.public_key(),
+            created_at: Unixtime::mock(),
+            kind: EventKind::LongFormContent,
+            tags: vec![
+                Tag::Title("Title".to_string()),
+                Tag::Summary("Summary".to_string()),
+                Tag::Image {
+                    url: UncheckedUrl::from_str("https://example.com/a.png"),
+                    dimensions: None,
+                },
+                Tag::PublishedAt(Unixtime(1296962229)),
+                Tag::Client {
+                    name: "gossip".to_string(),
+                    address: None,
+                    relay_url: None,
+                },
+                Tag::Alt("Long-form article".to_string()),
+                Tag::Expiration(Unixtime(1996962229)),
+                Tag::KindReference(EventKind::TextNote),
+                Tag::Address {
+                    address: EventAddr::mock(),
+                    marker: None,
+                },
+                Tag::Proxy {
+                    id: "at://did:plc:abc/app.bsky.feed.post/3jt5hlibeol2i".to_string(),
+                    protocol: ProxyProtocol::AtProto,
+                },
+            ],
+            content: "".to_string(),
+            ots: None,
+        };
+        let event = Event::new(preevent, &privkey).unwrap();
+        assert_eq!(event.title().as_deref(), Some("Title"));
+        assert_eq!(event.summary().as_deref(), Some("Summary"));
+        assert_eq!(
+            event.image(),
+            Some((UncheckedUrl::from_str("https://example.com/a.png"), None))
+        );
+        assert_eq!(event.published_at(), Some(Unixtime(1296962229)));
+        assert_eq!(event.client().as_deref(), Some("gossip"));
+        assert_eq!(event.alt().as_deref(), Some("Long-form article"));
+        assert_eq!(event.expiration(), Some(Unixtime(1996962229)));
+        assert_eq!(event.referenced_kinds(), vec![EventKind::TextNote]);
+        assert_eq!(event.referenced_addresses(), vec![EventAddr::mock()]);
+        assert_eq!(
+            event.proxies(),
+            vec![(
+                ProxyProtocol::AtProto,
+                "at://did:plc:abc/app.bsky.feed.post/3jt5hlibeol2i".to_string()
+            )]
+        );
+        assert!(!event.is_protected());
+    }
+
+    #[test]
+    fn test_event_protected() {
+        let privkey = PrivateKey::mock();
+        let preevent = PreEvent {
+            pubkey: privkey.public_key(),
+            created_at: Unixtime::mock(),
+            kind: EventKind::TextNote,
+            tags: vec![],
+            content: "Only from me".to_string(),
+            ots: None,
+        }
+        .protected()
+        .protected();
+        assert_eq!(preevent.tags, vec![Tag::Protected]);
+        let event = Event::new(preevent, &privkey).unwrap();
+        assert!(event.is_protected());
+
+        assert!(event
+            .protected_rejection(None)
+            .unwrap()
+            .starts_with("auth-required:"));
+        let other = PrivateKey::mock().public_key();
+        assert!(event
+            .protected_rejection(Some(other))
+            .unwrap()
+            .starts_with("restricted:"));
+        assert!(event.protected_rejection(Some(event.pubkey)).is_none());
+
+        assert!(Event::mock().protected_rejection(None).is_none());
+    }
+
     // helper
     fn create_event_with_delegation(delegator_privkey: PrivateKey, created_at: Unixtime) -> Event {
         let privkey = PrivateKey::mock();
@@ -955,6 +2013,7 @@ mod test {
                     id: Id::mock(),
                     recommended_relay_url: Some(UncheckedUrl::mock()),
                     marker: None,
+                    pubkey: None,
                 },
                 Tag::Delegation {
                     pubkey: PublicKeyHex::try_from_string(delegator_pubkey.as_hex_string())
@@ -1020,4 +2079,343 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_pre_event_delegated_by() {
+        let delegator_privkey = PrivateKey::mock();
+        let delegator_pubkey = delegator_privkey.public_key();
+        let privkey = PrivateKey::mock();
+        let conditions = DelegationConditions::try_from_str(
+            "kind=1&created_at>1680000000&created_at<1680050000",
+        )
+        .unwrap();
+        let sig = conditions
+            .generate_signature(privkey.public_key().into(), delegator_privkey)
+            .unwrap();
+        let delegation = Tag::Delegation {
+            pubkey: delegator_pubkey.into(),
+            conditions,
+            sig,
+        };
+        let pre = |kind: EventKind, created_at: Unixtime| PreEvent {
+            pubkey: privkey.public_key(),
+            created_at,
+            kind,
+            tags: vec![],
+            content: "".to_owned(),
+            ots: None,
+        };
+
+        let ok = pre(EventKind::TextNote, Unixtime(1680000100))
+            .delegated_by(delegation.clone())
+            .unwrap();
+        assert_eq!(ok.tags, vec![delegation.clone()]);
+        let event = Event::new(ok, &privkey).unwrap();
+        assert!(matches!(
+            event.delegation(),
+            EventDelegation::DelegatedBy(_)
+        ));
+
+        for (kind, created_at) in [
+            (EventKind::Reaction, Unixtime(1680000100)),
+            (EventKind::TextNote, Unixtime(1670000000)),
+            (EventKind::TextNote, Unixtime(1690000000)),
+        ] {
+            assert!(matches!(
+                pre(kind, created_at).delegated_by(delegation.clone()),
+                Err(Error::DelegationViolated(_))
+            ));
+        }
+
+        let mut other = pre(EventKind::TextNote, Unixtime(1680000100));
+        other.pubkey = PrivateKey::generate().public_key();
+        assert!(other.delegated_by(delegation).is_err());
+    }
+
+    #[test]
+    fn test_event_nip04() {
+        let alice = PrivateKey::generate();
+        let bob = PrivateKey::generate();
+        let pre = PreEvent::new_nip04(&alice, bob.public_key(), "hi bob").unwrap();
+        let mut event = Event::new(pre, &alice).unwrap();
+        assert_eq!(event.decrypted_contents(&bob).unwrap(), "hi bob");
+        // The sender can read their own message too
+        assert_eq!(event.decrypted_contents(&alice).unwrap(), "hi bob");
+
+        // A bad iv is an error, not a panic
+        event.content = "abc?iv=AAAA".to_owned();
+        assert!(matches!(
+            event.decrypted_contents(&bob),
+            Err(Error::BadEncryptedMessage)
+        ));
+    }
+
+    #[test]
+    fn test_event_references() {
+        let private_key = PrivateKey::generate();
+        let event = |kind: EventKind, tags: Vec<Tag>| {
+            let pre = PreEvent {
+                pubkey: private_key.public_key(),
+                created_at: Unixtime(1700000000),
+                kind,
+                tags,
+                content: "".to_owned(),
+                ots: None,
+            };
+            Event::new(pre, &private_key).unwrap()
+        };
+        let root = EventReference::from(Id::mock());
+        let article = EventReference::Addr(EventAddr {
+            d: "slug".to_owned(),
+            relays: vec![],
+            kind: EventKind::LongFormContent,
+            author: private_key.public_key(),
+        });
+
+        let reply = event(
+            EventKind::TextNote,
+            vec![root.to_tag(Some("root")), article.to_tag(Some("reply"))],
+        );
+        assert_eq!(reply.thread_root(), Some(root.clone()));
+        assert_eq!(reply.reply_target(), Some(article.clone()));
+
+        let comment = event(
+            EventKind::Comment,
+            vec![article.to_root_tag(), root.to_tag(None)],
+        );
+        assert_eq!(comment.thread_root(), Some(article.clone()));
+        assert_eq!(comment.reply_target(), Some(root.clone()));
+
+        let reaction = event(
+            EventKind::Reaction,
+            vec![root.to_tag(None), article.to_tag(None)],
+        );
+        assert_eq!(reaction.reaction_target(), Some(article.clone()));
+
+        let deletion = event(
+            EventKind::EventDeletion,
+            vec![root.to_tag(None), article.to_tag(None)],
+        );
+        assert_eq!(deletion.deletion_targets(), vec![root, article]);
+        assert!(reaction.deletion_targets().is_empty());
+    }
+
+    #[test]
+    fn test_content_stats() {
+        let private_key = PrivateKey::generate();
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::TextNote,
+            tags: vec![],
+            content: "Héllo 👋🏽\nsecond line".to_string(),
+            ots: None,
+        };
+        let event = Event::new(pre, &private_key).unwrap();
+        let stats = event.content_stats();
+        assert_eq!(stats.bytes, event.content.len());
+        assert_eq!(stats.graphemes, 19);
+        assert_eq!(stats.lines, 2);
+        if cfg!(not(feature = "language")) {
+            assert_eq!(stats.language, None);
+        }
+    }
+
+    #[cfg(feature = "language")]
+    #[test]
+    fn test_content_stats_language() {
+        let mut event = Event::mock();
+        event.content = "The quick brown fox jumps over the lazy dog, and then it runs far away into the forest where nobody can find it.".to_string();
+        assert_eq!(event.content_stats().language, Some("eng"));
+        event.content = "".to_string();
+        assert_eq!(event.content_stats().language, None);
+    }
+
+    #[test]
+    fn test_references_in_content() {
+        let npub = "npub1sn0wdenkukak0d9dfczzeacvhkrgz92ak56egt7vdgzn8pv2wfqqhrjdv9";
+        let note = "note1fntxtkcy9pjwucqwa9mddn7v03wwwsu9j330jj350nvhpky2tuaspk6nqc";
+        let mut event = Event::mock();
+        event.content = format!("Héllo nostr:{npub}, see {note} and note1qqqqqqqq.");
+
+        let references = event.references_in_content();
+        assert_eq!(references.len(), 2);
+
+        let start = event.content.find("nostr:").unwrap();
+        assert_eq!(references[0].range, start..start + 6 + npub.len());
+        assert!(references[0].is_uri);
+        assert_eq!(
+            references[0].bech32,
+            NostrBech32::Pubkey(PublicKey::try_from_bech32_string(npub).unwrap())
+        );
+
+        let start = event.content.find(note).unwrap();
+        assert_eq!(references[1].range, start..start + note.len());
+        assert!(!references[1].is_uri);
+        assert_eq!(event.content.get(references[1].range.clone()), Some(note));
+        assert!(matches!(references[1].bech32, NostrBech32::Id(_)));
+
+        event.content = "nothing here".to_owned();
+        assert!(event.references_in_content().is_empty());
+    }
+
+    #[test]
+    fn test_client_tag() {
+        let private_key = PrivateKey::generate();
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::TextNote,
+            tags: vec![],
+            content: "hello".to_string(),
+            ots: None,
+        };
+        let address = "31990:a872bee017fdf03e5b5d4b82a2ff0e5c3f0f2f4a9b5e4d4e1c2f0e4f1b8a2c3d:app";
+
+        let tagged = pre.clone().client("old", None, None).client(
+            "My App",
+            Some(address),
+            Some(UncheckedUrl::from_str("wss://relay.example.com")),
+        );
+        assert_eq!(
+            tagged.tags,
+            vec![Tag::Client {
+                name: "My App".to_string(),
+                address: Some(address.to_string()),
+                relay_url: Some(UncheckedUrl::from_str("wss://relay.example.com")),
+            }]
+        );
+
+        set_omit_client_tag(true);
+        let untagged = pre.client("My App", None, None);
+        set_omit_client_tag(false);
+        assert!(untagged.tags.is_empty());
+    }
+
+    #[test]
+    fn test_text_note_with_mentions() {
+        let bob = PrivateKey::generate().public_key();
+        let carol = Profile::mock();
+        let quoted = EventPointer::mock();
+        let article = EventAddr::mock();
+        let content = format!(
+            "hi nostr:{} and nostr:{}, also {}. See nostr:{} and nostr:{} and nostr:{}",
+            bob.as_bech32_string(),
+            carol.as_bech32_string(),
+            bob.as_bech32_string(),
+            quoted.as_bech32_string().unwrap(),
+            Id::mock().as_bech32_string(),
+            article.as_bech32_string(),
+        );
+
+        let pre = PreEvent::new_text_note_with_mentions(bob, Unixtime(1700000000), &content);
+        assert_eq!(pre.kind, EventKind::TextNote);
+        assert_eq!(pre.content, content);
+        let relay = |url: &str| Some(UncheckedUrl::from_str(url));
+        let quote = |data: Vec<String>| Tag::Other {
+            tag: "q".to_string(),
+            data,
+        };
+        assert_eq!(
+            pre.tags,
+            vec![
+                Tag::Pubkey {
+                    pubkey: bob.into(),
+                    recommended_relay_url: None,
+                    petname: None,
+                },
+                Tag::Pubkey {
+                    pubkey: carol.pubkey.into(),
+                    recommended_relay_url: relay("wss://relay.example.com"),
+                    petname: None,
+                },
+                quote(vec![
+                    quoted.id.as_hex_string(),
+                    "wss://relay.example.com".to_string()
+                ]),
+                quote(vec![Id::mock().as_hex_string()]),
+                quote(vec![
+                    article.as_address(),
+                    "wss://relay.example.com".to_string()
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pre_event_dedup_tags() {
+        let p = |pubkey: &PublicKeyHex| Tag::Pubkey {
+            pubkey: pubkey.clone(),
+            recommended_relay_url: None,
+            petname: None,
+        };
+        let alice = PublicKeyHex::mock();
+        let bob = PublicKeyHex::mock();
+        let pre = PreEvent {
+            pubkey: PublicKey::mock(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::TextNote,
+            tags: vec![
+                Tag::Hashtag("zebra".to_owned()),
+                p(&alice),
+                p(&bob),
+                p(&alice),
+                Tag::Hashtag("apple".to_owned()),
+                Tag::Hashtag("zebra".to_owned()),
+            ],
+            content: "".to_owned(),
+            ots: None,
+        };
+
+        let kept = pre.clone().dedup_tags(TagOrder::Insertion);
+        assert_eq!(
+            kept.tags,
+            vec![
+                Tag::Hashtag("zebra".to_owned()),
+                p(&alice),
+                p(&bob),
+                Tag::Hashtag("apple".to_owned()),
+            ]
+        );
+
+        let sorted = pre.dedup_tags(TagOrder::Canonical);
+        let (first, second) = if alice.as_str() < bob.as_str() {
+            (&alice, &bob)
+        } else {
+            (&bob, &alice)
+        };
+        assert_eq!(
+            sorted.tags,
+            vec![
+                p(first),
+                p(second),
+                Tag::Hashtag("apple".to_owned()),
+                Tag::Hashtag("zebra".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pre_event_estimated_wire_size() {
+        let privkey = PrivateKey::mock();
+        let mut pre = PreEvent {
+            pubkey: privkey.public_key(),
+            created_at: Unixtime(-5),
+            kind: EventKind::LongFormContent,
+            tags: vec![
+                Tag::Title("\"Quoted\" \\ and \u{1}".to_owned()),
+                Tag::Hashtag("ünïcödé 🎉".to_owned()),
+                Tag::Empty,
+            ],
+            content: "line\nbreak\ttab \u{7f} \u{2028}".to_owned(),
+            ots: None,
+        };
+        for ots in [None, Some("\"ots\"\n".to_owned())] {
+            pre.ots = ots;
+            let estimate = pre.estimated_wire_size();
+            let event = Event::new(pre.clone(), &privkey).unwrap();
+            let message = crate::ClientMessage::Event(Box::new(event));
+            assert_eq!(estimate, serde_json::to_string(&message).unwrap().len());
+        }
+    }
 }
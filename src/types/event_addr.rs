@@ -0,0 +1,191 @@
+use super::{Event, EventKind, PublicKey, UncheckedUrl};
+use crate::Error;
+use bech32::{FromBase32, ToBase32};
+use serde::{Deserialize, Serialize};
+
+/// The address of a parameterized replaceable event, along with some relays
+/// in which it may be found ("naddr", NIP-19)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EventAddr {
+    /// The 'd' tag of the event
+    pub d: String,
+
+    /// Some of the relays where this could be in
+    pub relays: Vec<UncheckedUrl>,
+
+    /// The kind of the event
+    pub kind: EventKind,
+
+    /// The author of the event
+    pub author: PublicKey,
+}
+
+impl EventAddr {
+    /// The address of `event`, for sharing it as an "naddr". Fails unless
+    /// the event is replaceable or addressable.
+    pub fn for_event(event: &Event, relays: Vec<UncheckedUrl>) -> Result<EventAddr, Error> {
+        if !event.kind.is_replaceable() && !event.kind.is_addressable() {
+            return Err(Error::WrongEventKind);
+        }
+        Ok(EventAddr {
+            d: event.parameter().unwrap_or_default(),
+            relays,
+            kind: event.kind,
+            author: event.pubkey,
+        })
+    }
+
+    /// The address as used in 'a' and 'q' tags ("<kind>:<pubkey>:<d>")
+    pub fn as_address(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            u64::from(self.kind),
+            self.author.as_hex_string(),
+            self.d
+        )
+    }
+
+    /// Parse an address as used in 'a' tags ("<kind>:<pubkey>:<d>"). The
+    /// 'd' part may itself contain colons.
+    pub fn try_from_address(s: &str) -> Result<EventAddr, Error> {
+        let invalid = || Error::AssertionFailed(format!("Invalid event address: {s}"));
+        let mut parts = s.splitn(3, ':');
+        let (kind, author, d) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(kind), Some(author), Some(d)) => (kind, author, d),
+            _ => return Err(invalid()),
+        };
+        Ok(EventAddr {
+            d: d.to_owned(),
+            relays: vec![],
+            kind: EventKind::from(kind.parse::<u64>().map_err(|_| invalid())?),
+            author: PublicKey::try_from_hex_string(author)?,
+        })
+    }
+
+    /// Export as a bech32 encoded string ("naddr")
+    pub fn as_bech32_string(&self) -> String {
+        let mut tlv: Vec<u8> = Vec::new();
+
+        // Push the identifier
+        tlv.push(0);
+        tlv.push(self.d.len() as u8);
+        tlv.extend(self.d.as_bytes());
+
+        // Push relays
+        for relay in &self.relays {
+            tlv.push(1);
+            tlv.push(relay.0.len() as u8);
+            tlv.extend(relay.0.as_bytes());
+        }
+
+        // Push the author
+        tlv.push(2);
+        tlv.push(32);
+        tlv.extend(self.author.0.to_bytes());
+
+        // Push the kind, as a big-endian 32-bit number
+        tlv.push(3);
+        tlv.push(4);
+        tlv.extend((u64::from(self.kind) as u32).to_be_bytes());
+
+        bech32::encode("naddr", tlv.to_base32(), bech32::Variant::Bech32).unwrap()
+    }
+
+    /// Import from a bech32 encoded string ("naddr")
+    pub fn try_from_bech32_string(s: &str) -> Result<EventAddr, Error> {
+        let data = crate::error::bech32_decode_as(s, "naddr", None)?;
+        let tlv = Vec::<u8>::from_base32(&data)?;
+
+        let mut d: Option<String> = None;
+        let mut relays: Vec<UncheckedUrl> = Vec::new();
+        let mut author: Option<PublicKey> = None;
+        let mut kind: Option<EventKind> = None;
+        let mut pos = 0;
+        while tlv.len() >= pos + 2 {
+            let typ = tlv[pos];
+            let len = tlv[pos + 1] as usize;
+            pos += 2;
+            let value = tlv.get(pos..pos + len).ok_or(Error::InvalidProfile)?;
+            match typ {
+                0 => d = Some(std::str::from_utf8(value)?.to_owned()),
+                1 => relays.push(UncheckedUrl::from_str(std::str::from_utf8(value)?)),
+                2 => author = Some(PublicKey::from_bytes(value)?),
+                3 => {
+                    let bytes: [u8; 4] = value.try_into().map_err(|_| Error::InvalidProfile)?;
+                    kind = Some(EventKind::from(u32::from_be_bytes(bytes) as u64));
+                }
+                // Unknown types are ignored, as NIP-19 asks
+                _ => {}
+            }
+            pos += len;
+        }
+
+        match (d, author, kind) {
+            (Some(d), Some(author), Some(kind)) => Ok(EventAddr {
+                d,
+                relays,
+                kind,
+                author,
+            }),
+            _ => Err(Error::InvalidProfile),
+        }
+    }
+
+    // Mock data for testing
+    #[allow(dead_code)]
+    pub(crate) fn mock() -> EventAddr {
+        let author = PublicKey::try_from_hex_string(
+            "b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9",
+        )
+        .unwrap();
+
+        EventAddr {
+            d: "my-article".to_owned(),
+            relays: vec![UncheckedUrl::from_str("wss://relay.example.com")],
+            kind: EventKind::LongFormContent,
+            author,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    test_serde! {EventAddr, test_event_addr_serde}
+
+    #[test]
+    fn test_event_addr_bech32() {
+        let addr = EventAddr::mock();
+        let bech32 = addr.as_bech32_string();
+        assert!(bech32.starts_with("naddr1"));
+        assert_eq!(EventAddr::try_from_bech32_string(&bech32).unwrap(), addr);
+        assert_eq!(
+            addr.as_address(),
+            "30023:b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9:my-article"
+        );
+
+        let parsed = EventAddr::try_from_address(&addr.as_address()).unwrap();
+        assert_eq!(parsed.d, addr.d);
+        assert_eq!(parsed.kind, addr.kind);
+        assert_eq!(parsed.author, addr.author);
+        let with_colons = EventAddr::try_from_address(
+            "30023:b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9:a:b",
+        )
+        .unwrap();
+        assert_eq!(with_colons.d, "a:b");
+        assert!(EventAddr::try_from_address("30023:not-a-key:x").is_err());
+        assert!(EventAddr::try_from_address("30023").is_err());
+
+        let mut event = Event::mock();
+        assert!(EventAddr::for_event(&event, vec![]).is_err());
+        event.kind = EventKind::LongFormContent;
+        event.tags = vec![crate::Tag::Identifier("my-article".to_owned())];
+        event.pubkey = addr.author;
+        let for_event = EventAddr::for_event(&event, addr.relays.clone()).unwrap();
+        assert_eq!(for_event, addr);
+
+        let nprofile = crate::Profile::mock().as_bech32_string();
+        assert!(EventAddr::try_from_bech32_string(&nprofile).is_err());
+    }
+}
@@ -0,0 +1,308 @@
+use super::{Event, EventAddr, Id};
+use std::collections::{BTreeMap, HashMap};
+
+// Roughly what an event takes beyond its content and tags
+const EVENT_OVERHEAD: usize = 256;
+
+/// An in-memory cache of events, for rendering, that evicts the least
+/// recently used events to stay within a byte budget.
+///
+/// Replaceable and addressable events can be pinned by address: the latest
+/// version of a pinned address is kept whatever the budget, and replaced when
+/// a newer version is inserted. This suits profiles and relay lists, which
+/// are needed all the time.
+#[derive(Clone, Debug)]
+pub struct EventCache {
+    max_bytes: usize,
+    bytes: usize,
+    events: HashMap<Id, (Event, usize, u64)>,
+    recency: BTreeMap<u64, Id>,
+    tick: u64,
+    pinned: HashMap<String, Option<Id>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl EventCache {
+    /// Create a cache holding about `max_bytes` of events
+    pub fn new(max_bytes: usize) -> EventCache {
+        EventCache {
+            max_bytes,
+            bytes: 0,
+            events: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            pinned: HashMap::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Add an event, evicting others if over budget. Returns false if it was
+    /// already cached, or is an older version of a pinned address.
+    ///
+    /// An unpinned event larger than the whole budget is evicted at once.
+    pub fn insert(&mut self, event: Event) -> bool {
+        if self.events.contains_key(&event.id) {
+            self.touch(event.id);
+            return false;
+        }
+
+        let size = event_size(&event);
+        let id = event.id;
+        match address(&event).filter(|a| self.pinned.contains_key(a)) {
+            Some(pinned) => {
+                if let Some(Some(current)) = self.pinned.get(&pinned) {
+                    let current = *current;
+                    if !event.replaces(&self.events[&current].0) {
+                        return false;
+                    }
+                    let _ = self.remove(current);
+                }
+                let _ = self.pinned.insert(pinned, Some(id));
+                let _ = self.events.insert(id, (event, size, 0));
+            }
+            None => {
+                self.tick += 1;
+                let _ = self.recency.insert(self.tick, id);
+                let _ = self.events.insert(id, (event, size, self.tick));
+            }
+        }
+        self.bytes += size;
+        self.evict();
+        true
+    }
+
+    /// Get an event, counting a hit or a miss and marking it as recently used
+    pub fn get(&mut self, id: Id) -> Option<&Event> {
+        if self.events.contains_key(&id) {
+            self.hits += 1;
+            self.touch(id);
+            self.events.get(&id).map(|(event, _, _)| event)
+        } else {
+            self.misses += 1;
+            None
+        }
+    }
+
+    /// Get an event without counting it or marking it as used
+    pub fn peek(&self, id: Id) -> Option<&Event> {
+        self.events.get(&id).map(|(event, _, _)| event)
+    }
+
+    /// Is the event cached?
+    pub fn contains(&self, id: Id) -> bool {
+        self.events.contains_key(&id)
+    }
+
+    /// Remove an event
+    pub fn remove(&mut self, id: Id) -> Option<Event> {
+        let (event, size, tick) = self.events.remove(&id)?;
+        let _ = self.recency.remove(&tick);
+        self.bytes -= size;
+        if let Some(address) = address(&event) {
+            if let Some(pinned) = self.pinned.get_mut(&address) {
+                if *pinned == Some(id) {
+                    *pinned = None;
+                }
+            }
+        }
+        Some(event)
+    }
+
+    /// Keep the latest version of the event at `addr` (its relays are
+    /// ignored) regardless of the budget
+    pub fn pin(&mut self, addr: &EventAddr) {
+        let wanted = addr.as_address();
+        if self.pinned.contains_key(&wanted) {
+            return;
+        }
+        let newest: Option<Id> = self
+            .events
+            .values()
+            .filter(|(event, _, _)| address(event).as_deref() == Some(wanted.as_str()))
+            .max_by_key(|(event, _, _)| event.created_at)
+            .map(|(event, _, _)| event.id);
+        if let Some(id) = newest {
+            if let Some((_, _, tick)) = self.events.get_mut(&id) {
+                let _ = self.recency.remove(&*tick);
+                *tick = 0;
+            }
+        }
+        let _ = self.pinned.insert(wanted, newest);
+    }
+
+    /// Stop pinning `addr`. Its event, if cached, becomes the most recently
+    /// used.
+    pub fn unpin(&mut self, addr: &EventAddr) {
+        if let Some(Some(id)) = self.pinned.remove(&addr.as_address()) {
+            self.tick += 1;
+            let _ = self.recency.insert(self.tick, id);
+            if let Some((_, _, tick)) = self.events.get_mut(&id) {
+                *tick = self.tick;
+            }
+            self.evict();
+        }
+    }
+
+    /// The latest cached version of a pinned address
+    pub fn get_pinned(&self, addr: &EventAddr) -> Option<&Event> {
+        match self.pinned.get(&addr.as_address()) {
+            Some(Some(id)) => self.peek(*id),
+            _ => None,
+        }
+    }
+
+    /// How many events are cached
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Is the cache empty?
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// About how many bytes the cached events take
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    /// How many `get()`s found their event
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// How many `get()`s did not find their event
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// The fraction of `get()`s that found their event, if there were any
+    pub fn hit_rate(&self) -> Option<f64> {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / total as f64)
+        }
+    }
+
+    fn touch(&mut self, id: Id) {
+        if let Some((_, _, tick)) = self.events.get_mut(&id) {
+            // Pinned events are not in the recency order
+            if *tick == 0 {
+                return;
+            }
+            let _ = self.recency.remove(&*tick);
+            self.tick += 1;
+            *tick = self.tick;
+            let _ = self.recency.insert(self.tick, id);
+        }
+    }
+
+    fn evict(&mut self) {
+        while self.bytes > self.max_bytes {
+            let id = match self.recency.values().next() {
+                Some(id) => *id,
+                None => break,
+            };
+            let _ = self.remove(id);
+        }
+    }
+}
+
+// The address of a replaceable or addressable event
+fn address(event: &Event) -> Option<String> {
+    if !event.kind.is_replaceable() && !event.kind.is_addressable() {
+        return None;
+    }
+    Some(format!(
+        "{}:{}:{}",
+        u64::from(event.kind),
+        event.pubkey.as_hex_string(),
+        event.parameter().unwrap_or_default()
+    ))
+}
+
+// Roughly how much memory an event takes
+fn event_size(event: &Event) -> usize {
+    let tags: usize = event
+        .tags
+        .iter()
+        .map(|tag| tag.to_strings().iter().map(|s| s.len() + 24).sum::<usize>())
+        .sum();
+    EVENT_OVERHEAD + event.content.len() + tags
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EventKind, PreEvent, PrivateKey, Unixtime};
+
+    #[test]
+    fn test_event_cache() {
+        let key = PrivateKey::generate();
+        let event = |kind: EventKind, created_at: i64| {
+            let pre = PreEvent {
+                pubkey: key.public_key(),
+                created_at: Unixtime(created_at),
+                kind,
+                tags: vec![],
+                content: "x".repeat(100),
+                ots: None,
+            };
+            Event::new(pre, &key).unwrap()
+        };
+        let size = EVENT_OVERHEAD + 100;
+
+        let mut cache = EventCache::new(size * 3);
+        let notes: Vec<Event> = (0..4)
+            .map(|n| event(EventKind::TextNote, 1700000000 + n))
+            .collect();
+        assert!(cache.insert(notes[0].clone()));
+        assert!(cache.insert(notes[1].clone()));
+        assert!(cache.insert(notes[2].clone()));
+        assert!(!cache.insert(notes[0].clone()));
+        assert_eq!(cache.bytes(), size * 3);
+
+        // notes[0] was used most recently, so notes[1] goes
+        assert!(cache.get(notes[2].id).is_some());
+        assert!(cache.get(notes[0].id).is_some());
+        assert!(cache.insert(notes[3].clone()));
+        assert!(!cache.contains(notes[1].id));
+        assert!(cache.get(notes[1].id).is_none());
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.hits(), 2);
+        assert_eq!(cache.misses(), 1);
+
+        // A pinned profile survives, and is replaced only by newer versions
+        let profile_addr = EventAddr {
+            d: "".to_owned(),
+            relays: vec![],
+            kind: EventKind::Metadata,
+            author: key.public_key(),
+        };
+        cache.pin(&profile_addr);
+        let profile = event(EventKind::Metadata, 1700000000);
+        let newer = event(EventKind::Metadata, 1700000100);
+        assert!(cache.insert(newer.clone()));
+        assert!(!cache.insert(profile));
+        assert_eq!(cache.get_pinned(&profile_addr), Some(&newer));
+        assert!(cache.contains(newer.id));
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.bytes(), size * 3);
+
+        for n in 4..10 {
+            let _ = cache.insert(event(EventKind::TextNote, 1700000000 + n));
+        }
+        assert!(cache.contains(newer.id));
+
+        cache.unpin(&profile_addr);
+        assert!(cache.get_pinned(&profile_addr).is_none());
+        for n in 100..103 {
+            let _ = cache.insert(event(EventKind::TextNote, 1700000000 + n));
+        }
+        assert!(!cache.contains(newer.id));
+    }
+}
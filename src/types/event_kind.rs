@@ -1,8 +1,10 @@
+use crate::Error;
 use serde::de::Error as DeError;
 use serde::de::{Deserialize, Deserializer, Visitor};
 use serde::ser::{Serialize, Serializer};
 use std::convert::From;
 use std::fmt;
+use std::str::FromStr;
 
 /// A kind of Event
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -25,6 +27,28 @@ pub enum EventKind {
     Repost,
     /// Event is a reaction to a `TextNote` event
     Reaction,
+    /// Badge Award (NIP-58)
+    BadgeAward,
+    /// Chat Message (NIP-C7)
+    ChatMessage,
+    /// Thread (NIP-7D)
+    Thread,
+    /// Seal (NIP-59)
+    Seal,
+    /// Private Direct Message, sealed and gift wrapped (NIP-17)
+    PrivateDirectMessage,
+    /// File Message, sealed and gift wrapped (NIP-17)
+    FileMessage,
+    /// Generic Repost (NIP-18)
+    GenericRepost,
+    /// Reaction to a website (NIP-25)
+    WebsiteReaction,
+    /// Picture (NIP-68)
+    Picture,
+    /// Video (NIP-71)
+    Video,
+    /// Short-form Portrait Video (NIP-71)
+    ShortVideo,
     /// Event creates a public channel
     ChannelCreation,
     /// Event sets metadata on a public channel
@@ -45,20 +69,234 @@ pub enum EventKind {
     PublicChatReserved48,
     /// Reserved for future public channel usage
     PublicChatReserved49,
+    /// Request to Vanish (NIP-62)
+    RequestToVanish,
+    /// Chess game in PGN format (NIP-64)
+    ChessPgn,
+    /// Wiki Merge Request (NIP-54)
+    WikiMergeRequest,
+    /// Poll Response (NIP-88)
+    PollResponse,
+    /// Auction Bid (NIP-15)
+    Bid,
+    /// Auction Bid Confirmation (NIP-15)
+    BidConfirmation,
+    /// OpenTimestamps attestation (NIP-03)
+    OpenTimestamps,
+    /// Gift Wrap (NIP-59)
+    GiftWrap,
+    /// File Metadata (NIP-94)
+    FileMetadata,
+    /// Poll (NIP-88)
+    Poll,
+    /// Comment (NIP-22)
+    Comment,
+    /// Live Chat Message (NIP-53)
+    LiveChatMessage,
+    /// Git Patch (NIP-34)
+    GitPatch,
+    /// Git Issue (NIP-34)
+    GitIssue,
+    /// Git Reply (NIP-34)
+    GitReply,
+    /// Git Status: Open (NIP-34)
+    GitStatusOpen,
+    /// Git Status: Applied, Merged or Resolved (NIP-34)
+    GitStatusApplied,
+    /// Git Status: Closed (NIP-34)
+    GitStatusClosed,
+    /// Git Status: Draft (NIP-34)
+    GitStatusDraft,
+    /// Key Migration Whitelist (draft NIP-41)
+    KeyWhitelist,
+    /// Key Migration (draft NIP-41)
+    KeyMigration,
+    /// Problem Tracker
+    ProblemTracker,
+    /// Reporting (NIP-56)
+    Reporting,
+    /// Label (NIP-32)
+    Label,
+    /// Relay Review
+    RelayReview,
+    /// Torrent (NIP-35)
+    Torrent,
+    /// Torrent Comment (NIP-35)
+    TorrentComment,
+    /// Coinjoin Pool
+    CoinjoinPool,
+    /// Community Post Approval (NIP-72)
+    CommunityPostApproval,
+    /// Data Vending Machine Job Request (NIP-90)
+    JobRequest(u64),
+    /// Data Vending Machine Job Result (NIP-90)
+    JobResult(u64),
+    /// Data Vending Machine Job Feedback (NIP-90)
+    JobFeedback,
+    /// Cashu Wallet Mint Quote (NIP-60)
+    CashuQuote,
+    /// Cashu Wallet Unspent Proofs (NIP-60)
+    CashuToken,
+    /// Cashu Wallet Spending History (NIP-60)
+    CashuSpendingHistory,
+    /// Group moderation: put user (NIP-29)
+    GroupPutUser,
+    /// Group moderation: remove user (NIP-29)
+    GroupRemoveUser,
+    /// Group moderation: edit metadata (NIP-29)
+    GroupEditMetadata,
+    /// Group moderation: delete event (NIP-29)
+    GroupDeleteEvent,
+    /// Group moderation: create group (NIP-29)
+    GroupCreateGroup,
+    /// Group moderation: delete group (NIP-29)
+    GroupDeleteGroup,
+    /// Group moderation: create invite (NIP-29)
+    GroupCreateInvite,
+    /// Group join request (NIP-29)
+    GroupJoinRequest,
+    /// Group leave request (NIP-29)
+    GroupLeaveRequest,
+    /// Zap Goal (NIP-75)
+    ZapGoal,
+    /// Nutzap (NIP-61)
+    Nutzap,
     /// Zap Request
     ZapRequest,
     /// Zap
     Zap,
+    /// Highlight (NIP-84)
+    Highlight,
+    /// Mute List (NIP-51)
+    MuteList,
     /// Relays List (NIP-23)
     RelaysListNip23,
     /// Relays List (NIP-65)
     RelayList,
+    /// Bookmark List (NIP-51)
+    BookmarkList,
+    /// Communities List (NIP-51)
+    CommunityList,
+    /// Public Chats List (NIP-51)
+    PublicChatList,
+    /// Blocked Relays List (NIP-51)
+    BlockedRelayList,
+    /// Search Relays List (NIP-51)
+    SearchRelayList,
+    /// Simple Groups List (NIP-51)
+    SimpleGroupList,
+    /// Interests List (NIP-51)
+    InterestList,
+    /// Nutzap Mint Recommendation (NIP-61)
+    NutzapMintRecommendation,
+    /// User Emoji List (NIP-51)
+    EmojiList,
+    /// Relay List to receive Direct Messages (NIP-17)
+    DmRelayList,
+    /// User Server List (Blossom)
+    UserServerList,
+    /// File Storage Server List (NIP-96)
+    FileStorageServerList,
+    /// Room Presence (NIP-53)
+    RoomPresence,
+    /// Wallet Info (NIP-47)
+    WalletInfo,
+    /// Cashu Wallet (NIP-60)
+    CashuWallet,
     /// Authentication
     Auth,
+    /// Wallet Request (NIP-47)
+    WalletRequest,
+    /// Wallet Response (NIP-47)
+    WalletResponse,
+    /// Nostr Connect (NIP-46)
+    NostrConnect,
+    /// HTTP Auth (NIP-98)
+    HttpAuth,
+    /// Follow Sets (NIP-51)
+    FollowSets,
+    /// Relay Sets (NIP-51)
+    RelaySets,
+    /// Bookmark Sets (NIP-51)
+    BookmarkSets,
+    /// Curation Sets (NIP-51)
+    CurationSets,
+    /// Video Sets (NIP-51)
+    VideoSets,
+    /// Kind Mute Sets (NIP-51)
+    KindMuteSets,
+    /// Profile Badges (NIP-58)
+    ProfileBadges,
+    /// Badge Definition (NIP-58)
+    BadgeDefinition,
+    /// Interest Sets (NIP-51)
+    InterestSets,
+    /// Marketplace Stall (NIP-15)
+    Stall,
+    /// Marketplace Product (NIP-15)
+    Product,
+    /// Marketplace UI/UX (NIP-15)
+    MarketplaceUi,
+    /// Marketplace Product sold as an Auction (NIP-15)
+    AuctionProduct,
     /// Long-form Content
     LongFormContent,
+    /// Draft Long-form Content (NIP-23)
+    DraftLongFormContent,
+    /// Emoji Sets (NIP-51)
+    EmojiSets,
+    /// Application-specific Data (NIP-78)
+    AppSpecificData,
+    /// Live Event (NIP-53)
+    LiveEvent,
+    /// User Status (NIP-38)
+    UserStatus,
+    /// Classified Listing (NIP-99)
+    ClassifiedListing,
+    /// Draft Classified Listing (NIP-99)
+    DraftClassifiedListing,
+    /// Repository Announcement (NIP-34)
+    RepositoryAnnouncement,
+    /// Repository State Announcement (NIP-34)
+    RepositoryState,
+    /// Wiki Article (NIP-54)
+    WikiArticle,
+    /// Wiki Redirect (NIP-54)
+    WikiRedirect,
     /// Client Settings
     ClientSettings,
+    /// Date-Based Calendar Event (NIP-52)
+    DateBasedCalendarEvent,
+    /// Time-Based Calendar Event (NIP-52)
+    TimeBasedCalendarEvent,
+    /// Calendar (NIP-52)
+    Calendar,
+    /// Calendar Event RSVP (NIP-52)
+    CalendarEventRsvp,
+    /// Handler Recommendation (NIP-89)
+    HandlerRecommendation,
+    /// Handler Information (NIP-89)
+    HandlerInformation,
+    /// Addressable Video (NIP-71)
+    AddressableVideo,
+    /// Addressable Short-form Portrait Video (NIP-71)
+    AddressableShortVideo,
+    /// Community Definition (NIP-72)
+    CommunityDefinition,
+    /// Cashu or Fedimint Mint Recommendation (NIP-87)
+    MintRecommendation,
+    /// Cashu Mint Announcement (NIP-87)
+    CashuMintAnnouncement,
+    /// Fedimint Announcement (NIP-87)
+    FedimintAnnouncement,
+    /// Group Metadata, relay-signed (NIP-29)
+    GroupMetadata,
+    /// Group Admins, relay-signed (NIP-29)
+    GroupAdmins,
+    /// Group Members, relay-signed (NIP-29)
+    GroupMembers,
+    /// Group Roles, relay-signed (NIP-29)
+    GroupRoles,
     /// Relay-specific replaceable event
     Replaceable(u64),
     /// Ephemeral event, sent to all clients with matching filters and should not be stored
@@ -76,8 +314,16 @@ impl EventKind {
         TextNote
     }
 
-    /// If this event kind is a replaceable event
-    /// NOTE: this does NOT count parameterized replaceable events
+    /// If this event kind is a regular event, which relays are expected to store
+    /// in full. Per NIP-01 that is everything that is not replaceable, ephemeral,
+    /// or addressable.
+    pub fn is_regular(&self) -> bool {
+        !self.is_replaceable() && !self.is_ephemeral() && !self.is_addressable()
+    }
+
+    /// If this event kind is a replaceable event, where only the latest event per
+    /// pubkey and kind is kept. This includes the legacy kinds 0 and 3.
+    /// NOTE: this does NOT count addressable (parameterized replaceable) events
     pub fn is_replaceable(&self) -> bool {
         match *self {
             Metadata => true,
@@ -89,16 +335,48 @@ impl EventKind {
         }
     }
 
-    /// If this event kind is ephemeral
+    /// If this event kind is ephemeral, and not expected to be stored by relays
     pub fn is_ephemeral(&self) -> bool {
         let u: u64 = From::from(*self);
         (20000..=29999).contains(&u)
     }
 
+    /// If this event kind is addressable, where only the latest event per pubkey,
+    /// kind and 'd' tag is kept. These were formerly called parameterized
+    /// replaceable events.
+    pub fn is_addressable(&self) -> bool {
+        let u: u64 = From::from(*self);
+        (30000..=39999).contains(&u)
+    }
+
     /// If this event kind is parameterized replaceable
+    #[deprecated(since = "0.5.0", note = "please use `is_addressable` instead")]
     pub fn is_parameterized_replaceable(&self) -> bool {
+        self.is_addressable()
+    }
+
+    /// If this event kind is a Data Vending Machine job request (NIP-90)
+    pub fn is_job_request(&self) -> bool {
         let u: u64 = From::from(*self);
-        (30000..=39999).contains(&u)
+        (5000..=5999).contains(&u)
+    }
+
+    /// If this event kind is a Data Vending Machine job result (NIP-90)
+    pub fn is_job_result(&self) -> bool {
+        let u: u64 = From::from(*self);
+        (6000..=6999).contains(&u)
+    }
+
+    /// If this event kind is a relay-based group moderation event (NIP-29)
+    pub fn is_group_moderation(&self) -> bool {
+        let u: u64 = From::from(*self);
+        (9000..=9020).contains(&u)
+    }
+
+    /// If this event kind is relay-signed relay-based group metadata (NIP-29)
+    pub fn is_group_metadata(&self) -> bool {
+        let u: u64 = From::from(*self);
+        (39000..=39009).contains(&u)
     }
 
     /// If this event kind is feed related.
@@ -124,6 +402,180 @@ impl EventKind {
     pub fn iter() -> EventKindIterator {
         EventKindIterator::new()
     }
+
+    /// The short kebab-case name of a well-known EventKind (e.g. "text-note"),
+    /// or None if this kind is not well-known
+    pub fn name(&self) -> Option<&'static str> {
+        let name = match *self {
+            Metadata => "metadata",
+            TextNote => "text-note",
+            RecommendRelay => "recommend-relay",
+            ContactList => "contact-list",
+            EncryptedDirectMessage => "encrypted-direct-message",
+            EventDeletion => "event-deletion",
+            Repost => "repost",
+            Reaction => "reaction",
+            BadgeAward => "badge-award",
+            ChatMessage => "chat-message",
+            Thread => "thread",
+            Seal => "seal",
+            PrivateDirectMessage => "private-direct-message",
+            FileMessage => "file-message",
+            GenericRepost => "generic-repost",
+            WebsiteReaction => "website-reaction",
+            Picture => "picture",
+            Video => "video",
+            ShortVideo => "short-video",
+            ChannelCreation => "channel-creation",
+            ChannelMetadata => "channel-metadata",
+            ChannelMessage => "channel-message",
+            ChannelHideMessage => "channel-hide-message",
+            ChannelMuteUser => "channel-mute-user",
+            PublicChatReserved45 => "public-chat-reserved45",
+            PublicChatReserved46 => "public-chat-reserved46",
+            PublicChatReserved47 => "public-chat-reserved47",
+            PublicChatReserved48 => "public-chat-reserved48",
+            PublicChatReserved49 => "public-chat-reserved49",
+            RequestToVanish => "request-to-vanish",
+            ChessPgn => "chess-pgn",
+            WikiMergeRequest => "wiki-merge-request",
+            PollResponse => "poll-response",
+            Bid => "bid",
+            BidConfirmation => "bid-confirmation",
+            OpenTimestamps => "open-timestamps",
+            GiftWrap => "gift-wrap",
+            FileMetadata => "file-metadata",
+            Poll => "poll",
+            Comment => "comment",
+            LiveChatMessage => "live-chat-message",
+            GitPatch => "git-patch",
+            GitIssue => "git-issue",
+            GitReply => "git-reply",
+            GitStatusOpen => "git-status-open",
+            GitStatusApplied => "git-status-applied",
+            GitStatusClosed => "git-status-closed",
+            GitStatusDraft => "git-status-draft",
+            KeyWhitelist => "key-whitelist",
+            KeyMigration => "key-migration",
+            ProblemTracker => "problem-tracker",
+            Reporting => "reporting",
+            Label => "label",
+            RelayReview => "relay-review",
+            Torrent => "torrent",
+            TorrentComment => "torrent-comment",
+            CoinjoinPool => "coinjoin-pool",
+            CommunityPostApproval => "community-post-approval",
+            JobFeedback => "job-feedback",
+            CashuQuote => "cashu-quote",
+            CashuToken => "cashu-token",
+            CashuSpendingHistory => "cashu-spending-history",
+            GroupPutUser => "group-put-user",
+            GroupRemoveUser => "group-remove-user",
+            GroupEditMetadata => "group-edit-metadata",
+            GroupDeleteEvent => "group-delete-event",
+            GroupCreateGroup => "group-create-group",
+            GroupDeleteGroup => "group-delete-group",
+            GroupCreateInvite => "group-create-invite",
+            GroupJoinRequest => "group-join-request",
+            GroupLeaveRequest => "group-leave-request",
+            ZapGoal => "zap-goal",
+            Nutzap => "nutzap",
+            ZapRequest => "zap-request",
+            Zap => "zap",
+            Highlight => "highlight",
+            MuteList => "mute-list",
+            RelaysListNip23 => "relays-list-nip23",
+            RelayList => "relay-list",
+            BookmarkList => "bookmark-list",
+            CommunityList => "community-list",
+            PublicChatList => "public-chat-list",
+            BlockedRelayList => "blocked-relay-list",
+            SearchRelayList => "search-relay-list",
+            SimpleGroupList => "simple-group-list",
+            InterestList => "interest-list",
+            NutzapMintRecommendation => "nutzap-mint-recommendation",
+            EmojiList => "emoji-list",
+            DmRelayList => "dm-relay-list",
+            UserServerList => "user-server-list",
+            FileStorageServerList => "file-storage-server-list",
+            RoomPresence => "room-presence",
+            WalletInfo => "wallet-info",
+            CashuWallet => "cashu-wallet",
+            Auth => "auth",
+            WalletRequest => "wallet-request",
+            WalletResponse => "wallet-response",
+            NostrConnect => "nostr-connect",
+            HttpAuth => "http-auth",
+            FollowSets => "follow-sets",
+            RelaySets => "relay-sets",
+            BookmarkSets => "bookmark-sets",
+            CurationSets => "curation-sets",
+            VideoSets => "video-sets",
+            KindMuteSets => "kind-mute-sets",
+            ProfileBadges => "profile-badges",
+            BadgeDefinition => "badge-definition",
+            InterestSets => "interest-sets",
+            Stall => "stall",
+            Product => "product",
+            MarketplaceUi => "marketplace-ui",
+            AuctionProduct => "auction-product",
+            LongFormContent => "long-form-content",
+            DraftLongFormContent => "draft-long-form-content",
+            EmojiSets => "emoji-sets",
+            AppSpecificData => "app-specific-data",
+            LiveEvent => "live-event",
+            UserStatus => "user-status",
+            ClassifiedListing => "classified-listing",
+            DraftClassifiedListing => "draft-classified-listing",
+            RepositoryAnnouncement => "repository-announcement",
+            RepositoryState => "repository-state",
+            WikiArticle => "wiki-article",
+            WikiRedirect => "wiki-redirect",
+            ClientSettings => "client-settings",
+            DateBasedCalendarEvent => "date-based-calendar-event",
+            TimeBasedCalendarEvent => "time-based-calendar-event",
+            Calendar => "calendar",
+            CalendarEventRsvp => "calendar-event-rsvp",
+            HandlerRecommendation => "handler-recommendation",
+            HandlerInformation => "handler-information",
+            AddressableVideo => "addressable-video",
+            AddressableShortVideo => "addressable-short-video",
+            CommunityDefinition => "community-definition",
+            MintRecommendation => "mint-recommendation",
+            CashuMintAnnouncement => "cashu-mint-announcement",
+            FedimintAnnouncement => "fedimint-announcement",
+            GroupMetadata => "group-metadata",
+            GroupAdmins => "group-admins",
+            GroupMembers => "group-members",
+            GroupRoles => "group-roles",
+            _ => return None,
+        };
+        Some(name)
+    }
+
+    /// Parse an EventKind from its name, such as "text-note". Names are matched
+    /// case-insensitively and may use spaces or underscores in place of dashes,
+    /// so the `Display` form ("Text Note") is also accepted. A plain number is
+    /// also accepted.
+    pub fn from_name(s: &str) -> Result<EventKind, Error> {
+        let s = s.trim();
+        if let Ok(u) = s.parse::<u64>() {
+            return Ok(From::from(u));
+        }
+        let wanted: String = s
+            .chars()
+            .map(|c| match c {
+                ' ' | '_' => '-',
+                c => c.to_ascii_lowercase(),
+            })
+            .collect();
+        for kind in EventKind::iter() {
+            if kind.name() == Some(&*wanted) {
+                return Ok(kind);
+            }
+        }
+        Err(Error::UnknownEventKindName(s.to_owned()))
+    }
 }
 
 /// Iterator over well known `EventKind`s
@@ -141,6 +593,17 @@ static WELL_KNOWN_KINDS: &[EventKind] = &[
     EventDeletion,
     Repost,
     Reaction,
+    BadgeAward,
+    ChatMessage,
+    Thread,
+    Seal,
+    PrivateDirectMessage,
+    FileMessage,
+    GenericRepost,
+    WebsiteReaction,
+    Picture,
+    Video,
+    ShortVideo,
     ChannelCreation,
     ChannelMetadata,
     ChannelMessage,
@@ -151,13 +614,118 @@ static WELL_KNOWN_KINDS: &[EventKind] = &[
     PublicChatReserved47,
     PublicChatReserved48,
     PublicChatReserved49,
+    RequestToVanish,
+    ChessPgn,
+    WikiMergeRequest,
+    PollResponse,
+    Bid,
+    BidConfirmation,
+    OpenTimestamps,
+    GiftWrap,
+    FileMetadata,
+    Poll,
+    Comment,
+    LiveChatMessage,
+    GitPatch,
+    GitIssue,
+    GitReply,
+    GitStatusOpen,
+    GitStatusApplied,
+    GitStatusClosed,
+    GitStatusDraft,
+    KeyWhitelist,
+    KeyMigration,
+    ProblemTracker,
+    Reporting,
+    Label,
+    RelayReview,
+    Torrent,
+    TorrentComment,
+    CoinjoinPool,
+    CommunityPostApproval,
+    JobFeedback,
+    CashuQuote,
+    CashuToken,
+    CashuSpendingHistory,
+    GroupPutUser,
+    GroupRemoveUser,
+    GroupEditMetadata,
+    GroupDeleteEvent,
+    GroupCreateGroup,
+    GroupDeleteGroup,
+    GroupCreateInvite,
+    GroupJoinRequest,
+    GroupLeaveRequest,
+    ZapGoal,
+    Nutzap,
     ZapRequest,
     Zap,
+    Highlight,
+    MuteList,
     RelaysListNip23,
     RelayList,
+    BookmarkList,
+    CommunityList,
+    PublicChatList,
+    BlockedRelayList,
+    SearchRelayList,
+    SimpleGroupList,
+    InterestList,
+    NutzapMintRecommendation,
+    EmojiList,
+    DmRelayList,
+    UserServerList,
+    FileStorageServerList,
+    RoomPresence,
+    WalletInfo,
+    CashuWallet,
     Auth,
+    WalletRequest,
+    WalletResponse,
+    NostrConnect,
+    HttpAuth,
+    FollowSets,
+    RelaySets,
+    BookmarkSets,
+    CurationSets,
+    VideoSets,
+    KindMuteSets,
+    ProfileBadges,
+    BadgeDefinition,
+    InterestSets,
+    Stall,
+    Product,
+    MarketplaceUi,
+    AuctionProduct,
     LongFormContent,
+    DraftLongFormContent,
+    EmojiSets,
+    AppSpecificData,
+    LiveEvent,
+    UserStatus,
+    ClassifiedListing,
+    DraftClassifiedListing,
+    RepositoryAnnouncement,
+    RepositoryState,
+    WikiArticle,
+    WikiRedirect,
     ClientSettings,
+    DateBasedCalendarEvent,
+    TimeBasedCalendarEvent,
+    Calendar,
+    CalendarEventRsvp,
+    HandlerRecommendation,
+    HandlerInformation,
+    AddressableVideo,
+    AddressableShortVideo,
+    CommunityDefinition,
+    MintRecommendation,
+    CashuMintAnnouncement,
+    FedimintAnnouncement,
+    GroupMetadata,
+    GroupAdmins,
+    GroupMembers,
+    GroupRoles,
 ];
 
 impl EventKindIterator {
@@ -184,6 +752,14 @@ impl Iterator for EventKindIterator {
     }
 }
 
+impl FromStr for EventKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<EventKind, Error> {
+        EventKind::from_name(s)
+    }
+}
+
 impl From<u64> for EventKind {
     fn from(u: u64) -> Self {
         match u {
@@ -195,6 +771,17 @@ impl From<u64> for EventKind {
             5 => EventDeletion,
             6 => Repost,
             7 => Reaction,
+            8 => BadgeAward,
+            9 => ChatMessage,
+            11 => Thread,
+            13 => Seal,
+            14 => PrivateDirectMessage,
+            15 => FileMessage,
+            16 => GenericRepost,
+            17 => WebsiteReaction,
+            20 => Picture,
+            21 => Video,
+            22 => ShortVideo,
             40 => ChannelCreation,
             41 => ChannelMetadata,
             42 => ChannelMessage,
@@ -205,13 +792,120 @@ impl From<u64> for EventKind {
             47 => PublicChatReserved47,
             48 => PublicChatReserved48,
             49 => PublicChatReserved49,
+            62 => RequestToVanish,
+            64 => ChessPgn,
+            818 => WikiMergeRequest,
+            1018 => PollResponse,
+            1021 => Bid,
+            1022 => BidConfirmation,
+            1040 => OpenTimestamps,
+            1059 => GiftWrap,
+            1063 => FileMetadata,
+            1068 => Poll,
+            1111 => Comment,
+            1311 => LiveChatMessage,
+            1617 => GitPatch,
+            1621 => GitIssue,
+            1622 => GitReply,
+            1630 => GitStatusOpen,
+            1631 => GitStatusApplied,
+            1632 => GitStatusClosed,
+            1633 => GitStatusDraft,
+            1776 => KeyWhitelist,
+            1777 => KeyMigration,
+            1971 => ProblemTracker,
+            1984 => Reporting,
+            1985 => Label,
+            1986 => RelayReview,
+            2003 => Torrent,
+            2004 => TorrentComment,
+            2022 => CoinjoinPool,
+            4550 => CommunityPostApproval,
+            x if (5_000..6_000).contains(&x) => JobRequest(x),
+            x if (6_000..7_000).contains(&x) => JobResult(x),
+            7000 => JobFeedback,
+            7374 => CashuQuote,
+            7375 => CashuToken,
+            7376 => CashuSpendingHistory,
+            9000 => GroupPutUser,
+            9001 => GroupRemoveUser,
+            9002 => GroupEditMetadata,
+            9005 => GroupDeleteEvent,
+            9007 => GroupCreateGroup,
+            9008 => GroupDeleteGroup,
+            9009 => GroupCreateInvite,
+            9021 => GroupJoinRequest,
+            9022 => GroupLeaveRequest,
+            9041 => ZapGoal,
+            9321 => Nutzap,
             9734 => ZapRequest,
             9735 => Zap,
+            9802 => Highlight,
+            10000 => MuteList,
             10001 => RelaysListNip23,
             10002 => RelayList,
+            10003 => BookmarkList,
+            10004 => CommunityList,
+            10005 => PublicChatList,
+            10006 => BlockedRelayList,
+            10007 => SearchRelayList,
+            10009 => SimpleGroupList,
+            10015 => InterestList,
+            10019 => NutzapMintRecommendation,
+            10030 => EmojiList,
+            10050 => DmRelayList,
+            10063 => UserServerList,
+            10096 => FileStorageServerList,
+            10312 => RoomPresence,
+            13194 => WalletInfo,
+            17375 => CashuWallet,
             22242 => Auth,
+            23194 => WalletRequest,
+            23195 => WalletResponse,
+            24133 => NostrConnect,
+            27235 => HttpAuth,
+            30000 => FollowSets,
+            30002 => RelaySets,
+            30003 => BookmarkSets,
+            30004 => CurationSets,
+            30005 => VideoSets,
+            30007 => KindMuteSets,
+            30008 => ProfileBadges,
+            30009 => BadgeDefinition,
+            30015 => InterestSets,
+            30017 => Stall,
+            30018 => Product,
+            30019 => MarketplaceUi,
+            30020 => AuctionProduct,
             30023 => LongFormContent,
+            30024 => DraftLongFormContent,
+            30030 => EmojiSets,
+            30078 => AppSpecificData,
+            30311 => LiveEvent,
+            30315 => UserStatus,
+            30402 => ClassifiedListing,
+            30403 => DraftClassifiedListing,
+            30617 => RepositoryAnnouncement,
+            30618 => RepositoryState,
+            30818 => WikiArticle,
+            30819 => WikiRedirect,
             31111 => ClientSettings,
+            31922 => DateBasedCalendarEvent,
+            31923 => TimeBasedCalendarEvent,
+            31924 => Calendar,
+            31925 => CalendarEventRsvp,
+            31989 => HandlerRecommendation,
+            31990 => HandlerInformation,
+            34235 => AddressableVideo,
+            34236 => AddressableShortVideo,
+            34550 => CommunityDefinition,
+            38000 => MintRecommendation,
+            38172 => CashuMintAnnouncement,
+            38173 => FedimintAnnouncement,
+            39000 => GroupMetadata,
+            39001 => GroupAdmins,
+            39002 => GroupMembers,
+            39003 => GroupRoles,
             x if (10_000..20_000).contains(&x) => Replaceable(x),
             x if (20_000..30_000).contains(&x) => Ephemeral(x),
             x => Other(x),
@@ -230,6 +924,17 @@ impl From<EventKind> for u64 {
             EventDeletion => 5,
             Repost => 6,
             Reaction => 7,
+            BadgeAward => 8,
+            ChatMessage => 9,
+            Thread => 11,
+            Seal => 13,
+            PrivateDirectMessage => 14,
+            FileMessage => 15,
+            GenericRepost => 16,
+            WebsiteReaction => 17,
+            Picture => 20,
+            Video => 21,
+            ShortVideo => 22,
             ChannelCreation => 40,
             ChannelMetadata => 41,
             ChannelMessage => 42,
@@ -240,13 +945,120 @@ impl From<EventKind> for u64 {
             PublicChatReserved47 => 47,
             PublicChatReserved48 => 48,
             PublicChatReserved49 => 49,
+            RequestToVanish => 62,
+            ChessPgn => 64,
+            WikiMergeRequest => 818,
+            PollResponse => 1018,
+            Bid => 1021,
+            BidConfirmation => 1022,
+            OpenTimestamps => 1040,
+            GiftWrap => 1059,
+            FileMetadata => 1063,
+            Poll => 1068,
+            Comment => 1111,
+            LiveChatMessage => 1311,
+            GitPatch => 1617,
+            GitIssue => 1621,
+            GitReply => 1622,
+            GitStatusOpen => 1630,
+            GitStatusApplied => 1631,
+            GitStatusClosed => 1632,
+            GitStatusDraft => 1633,
+            KeyWhitelist => 1776,
+            KeyMigration => 1777,
+            ProblemTracker => 1971,
+            Reporting => 1984,
+            Label => 1985,
+            RelayReview => 1986,
+            Torrent => 2003,
+            TorrentComment => 2004,
+            CoinjoinPool => 2022,
+            CommunityPostApproval => 4550,
+            JobRequest(u) => u,
+            JobResult(u) => u,
+            JobFeedback => 7000,
+            CashuQuote => 7374,
+            CashuToken => 7375,
+            CashuSpendingHistory => 7376,
+            GroupPutUser => 9000,
+            GroupRemoveUser => 9001,
+            GroupEditMetadata => 9002,
+            GroupDeleteEvent => 9005,
+            GroupCreateGroup => 9007,
+            GroupDeleteGroup => 9008,
+            GroupCreateInvite => 9009,
+            GroupJoinRequest => 9021,
+            GroupLeaveRequest => 9022,
+            ZapGoal => 9041,
+            Nutzap => 9321,
             ZapRequest => 9734,
             Zap => 9735,
+            Highlight => 9802,
+            MuteList => 10000,
             RelaysListNip23 => 10001,
             RelayList => 10002,
+            BookmarkList => 10003,
+            CommunityList => 10004,
+            PublicChatList => 10005,
+            BlockedRelayList => 10006,
+            SearchRelayList => 10007,
+            SimpleGroupList => 10009,
+            InterestList => 10015,
+            NutzapMintRecommendation => 10019,
+            EmojiList => 10030,
+            DmRelayList => 10050,
+            UserServerList => 10063,
+            FileStorageServerList => 10096,
+            RoomPresence => 10312,
+            WalletInfo => 13194,
+            CashuWallet => 17375,
             Auth => 22242,
+            WalletRequest => 23194,
+            WalletResponse => 23195,
+            NostrConnect => 24133,
+            HttpAuth => 27235,
+            FollowSets => 30000,
+            RelaySets => 30002,
+            BookmarkSets => 30003,
+            CurationSets => 30004,
+            VideoSets => 30005,
+            KindMuteSets => 30007,
+            ProfileBadges => 30008,
+            BadgeDefinition => 30009,
+            InterestSets => 30015,
+            Stall => 30017,
+            Product => 30018,
+            MarketplaceUi => 30019,
+            AuctionProduct => 30020,
             LongFormContent => 30023,
+            DraftLongFormContent => 30024,
+            EmojiSets => 30030,
+            AppSpecificData => 30078,
+            LiveEvent => 30311,
+            UserStatus => 30315,
+            ClassifiedListing => 30402,
+            DraftClassifiedListing => 30403,
+            RepositoryAnnouncement => 30617,
+            RepositoryState => 30618,
+            WikiArticle => 30818,
+            WikiRedirect => 30819,
             ClientSettings => 31111,
+            DateBasedCalendarEvent => 31922,
+            TimeBasedCalendarEvent => 31923,
+            Calendar => 31924,
+            CalendarEventRsvp => 31925,
+            HandlerRecommendation => 31989,
+            HandlerInformation => 31990,
+            AddressableVideo => 34235,
+            AddressableShortVideo => 34236,
+            CommunityDefinition => 34550,
+            MintRecommendation => 38000,
+            CashuMintAnnouncement => 38172,
+            FedimintAnnouncement => 38173,
+            GroupMetadata => 39000,
+            GroupAdmins => 39001,
+            GroupMembers => 39002,
+            GroupRoles => 39003,
             Replaceable(u) => u,
             Ephemeral(u) => u,
             Other(u) => u,
@@ -254,6 +1066,159 @@ impl From<EventKind> for u64 {
     }
 }
 
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Metadata => write!(f, "Metadata"),
+            TextNote => write!(f, "Text Note"),
+            RecommendRelay => write!(f, "Recommend Relay"),
+            ContactList => write!(f, "Contact List"),
+            EncryptedDirectMessage => write!(f, "Encrypted Direct Message"),
+            EventDeletion => write!(f, "Event Deletion"),
+            Repost => write!(f, "Repost"),
+            Reaction => write!(f, "Reaction"),
+            BadgeAward => write!(f, "Badge Award"),
+            ChatMessage => write!(f, "Chat Message"),
+            Thread => write!(f, "Thread"),
+            Seal => write!(f, "Seal"),
+            PrivateDirectMessage => write!(f, "Private Direct Message"),
+            FileMessage => write!(f, "File Message"),
+            GenericRepost => write!(f, "Generic Repost"),
+            WebsiteReaction => write!(f, "Website Reaction"),
+            Picture => write!(f, "Picture"),
+            Video => write!(f, "Video"),
+            ShortVideo => write!(f, "Short Video"),
+            ChannelCreation => write!(f, "Channel Creation"),
+            ChannelMetadata => write!(f, "Channel Metadata"),
+            ChannelMessage => write!(f, "Channel Message"),
+            ChannelHideMessage => write!(f, "Channel Hide Message"),
+            ChannelMuteUser => write!(f, "Channel Mute User"),
+            PublicChatReserved45 => write!(f, "Public Chat Reserved (45)"),
+            PublicChatReserved46 => write!(f, "Public Chat Reserved (46)"),
+            PublicChatReserved47 => write!(f, "Public Chat Reserved (47)"),
+            PublicChatReserved48 => write!(f, "Public Chat Reserved (48)"),
+            PublicChatReserved49 => write!(f, "Public Chat Reserved (49)"),
+            RequestToVanish => write!(f, "Request to Vanish"),
+            ChessPgn => write!(f, "Chess (PGN)"),
+            WikiMergeRequest => write!(f, "Wiki Merge Request"),
+            PollResponse => write!(f, "Poll Response"),
+            Bid => write!(f, "Bid"),
+            BidConfirmation => write!(f, "Bid Confirmation"),
+            OpenTimestamps => write!(f, "OpenTimestamps"),
+            GiftWrap => write!(f, "Gift Wrap"),
+            FileMetadata => write!(f, "File Metadata"),
+            Poll => write!(f, "Poll"),
+            Comment => write!(f, "Comment"),
+            LiveChatMessage => write!(f, "Live Chat Message"),
+            GitPatch => write!(f, "Git Patch"),
+            GitIssue => write!(f, "Git Issue"),
+            GitReply => write!(f, "Git Reply"),
+            GitStatusOpen => write!(f, "Git Status Open"),
+            GitStatusApplied => write!(f, "Git Status Applied"),
+            GitStatusClosed => write!(f, "Git Status Closed"),
+            GitStatusDraft => write!(f, "Git Status Draft"),
+            KeyWhitelist => write!(f, "Key Whitelist"),
+            KeyMigration => write!(f, "Key Migration"),
+            ProblemTracker => write!(f, "Problem Tracker"),
+            Reporting => write!(f, "Reporting"),
+            Label => write!(f, "Label"),
+            RelayReview => write!(f, "Relay Review"),
+            Torrent => write!(f, "Torrent"),
+            TorrentComment => write!(f, "Torrent Comment"),
+            CoinjoinPool => write!(f, "Coinjoin Pool"),
+            CommunityPostApproval => write!(f, "Community Post Approval"),
+            JobRequest(u) => write!(f, "Job Request ({u})"),
+            JobResult(u) => write!(f, "Job Result ({u})"),
+            JobFeedback => write!(f, "Job Feedback"),
+            CashuQuote => write!(f, "Cashu Quote"),
+            CashuToken => write!(f, "Cashu Token"),
+            CashuSpendingHistory => write!(f, "Cashu Spending History"),
+            GroupPutUser => write!(f, "Group Put User"),
+            GroupRemoveUser => write!(f, "Group Remove User"),
+            GroupEditMetadata => write!(f, "Group Edit Metadata"),
+            GroupDeleteEvent => write!(f, "Group Delete Event"),
+            GroupCreateGroup => write!(f, "Group Create Group"),
+            GroupDeleteGroup => write!(f, "Group Delete Group"),
+            GroupCreateInvite => write!(f, "Group Create Invite"),
+            GroupJoinRequest => write!(f, "Group Join Request"),
+            GroupLeaveRequest => write!(f, "Group Leave Request"),
+            ZapGoal => write!(f, "Zap Goal"),
+            Nutzap => write!(f, "Nutzap"),
+            ZapRequest => write!(f, "Zap Request"),
+            Zap => write!(f, "Zap"),
+            Highlight => write!(f, "Highlight"),
+            MuteList => write!(f, "Mute List"),
+            RelaysListNip23 => write!(f, "Relays List (NIP-23)"),
+            RelayList => write!(f, "Relay List"),
+            BookmarkList => write!(f, "Bookmark List"),
+            CommunityList => write!(f, "Community List"),
+            PublicChatList => write!(f, "Public Chat List"),
+            BlockedRelayList => write!(f, "Blocked Relay List"),
+            SearchRelayList => write!(f, "Search Relay List"),
+            SimpleGroupList => write!(f, "Simple Group List"),
+            InterestList => write!(f, "Interest List"),
+            NutzapMintRecommendation => write!(f, "Nutzap Mint Recommendation"),
+            EmojiList => write!(f, "Emoji List"),
+            DmRelayList => write!(f, "DM Relay List"),
+            UserServerList => write!(f, "User Server List"),
+            FileStorageServerList => write!(f, "File Storage Server List"),
+            RoomPresence => write!(f, "Room Presence"),
+            WalletInfo => write!(f, "Wallet Info"),
+            CashuWallet => write!(f, "Cashu Wallet"),
+            Auth => write!(f, "Auth"),
+            WalletRequest => write!(f, "Wallet Request"),
+            WalletResponse => write!(f, "Wallet Response"),
+            NostrConnect => write!(f, "Nostr Connect"),
+            HttpAuth => write!(f, "HTTP Auth"),
+            FollowSets => write!(f, "Follow Sets"),
+            RelaySets => write!(f, "Relay Sets"),
+            BookmarkSets => write!(f, "Bookmark Sets"),
+            CurationSets => write!(f, "Curation Sets"),
+            VideoSets => write!(f, "Video Sets"),
+            KindMuteSets => write!(f, "Kind Mute Sets"),
+            ProfileBadges => write!(f, "Profile Badges"),
+            BadgeDefinition => write!(f, "Badge Definition"),
+            InterestSets => write!(f, "Interest Sets"),
+            Stall => write!(f, "Stall"),
+            Product => write!(f, "Product"),
+            MarketplaceUi => write!(f, "Marketplace UI"),
+            AuctionProduct => write!(f, "Auction Product"),
+            LongFormContent => write!(f, "Long-form Content"),
+            DraftLongFormContent => write!(f, "Draft Long-form Content"),
+            EmojiSets => write!(f, "Emoji Sets"),
+            AppSpecificData => write!(f, "App-specific Data"),
+            LiveEvent => write!(f, "Live Event"),
+            UserStatus => write!(f, "User Status"),
+            ClassifiedListing => write!(f, "Classified Listing"),
+            DraftClassifiedListing => write!(f, "Draft Classified Listing"),
+            RepositoryAnnouncement => write!(f, "Repository Announcement"),
+            RepositoryState => write!(f, "Repository State"),
+            WikiArticle => write!(f, "Wiki Article"),
+            WikiRedirect => write!(f, "Wiki Redirect"),
+            ClientSettings => write!(f, "Client Settings"),
+            DateBasedCalendarEvent => write!(f, "Date-Based Calendar Event"),
+            TimeBasedCalendarEvent => write!(f, "Time-Based Calendar Event"),
+            Calendar => write!(f, "Calendar"),
+            CalendarEventRsvp => write!(f, "Calendar Event RSVP"),
+            HandlerRecommendation => write!(f, "Handler Recommendation"),
+            HandlerInformation => write!(f, "Handler Information"),
+            AddressableVideo => write!(f, "Addressable Video"),
+            AddressableShortVideo => write!(f, "Addressable Short Video"),
+            CommunityDefinition => write!(f, "Community Definition"),
+            MintRecommendation => write!(f, "Mint Recommendation"),
+            CashuMintAnnouncement => write!(f, "Cashu Mint Announcement"),
+            FedimintAnnouncement => write!(f, "Fedimint Announcement"),
+            GroupMetadata => write!(f, "Group Metadata"),
+            GroupAdmins => write!(f, "Group Admins"),
+            GroupMembers => write!(f, "Group Members"),
+            GroupRoles => write!(f, "Group Roles"),
+            Replaceable(u) => write!(f, "Replaceable ({u})"),
+            Ephemeral(u) => write!(f, "Ephemeral ({u})"),
+            Other(u) => write!(f, "Other ({u})"),
+        }
+    }
+}
+
 impl Serialize for EventKind {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -306,7 +1271,67 @@ mod test {
         assert!(!TextNote.is_ephemeral());
         assert!(Auth.is_ephemeral());
 
-        assert!(!TextNote.is_parameterized_replaceable());
-        assert!(LongFormContent.is_parameterized_replaceable());
+        assert!(!TextNote.is_addressable());
+        assert!(LongFormContent.is_addressable());
+
+        assert!(TextNote.is_regular());
+        assert!(EncryptedDirectMessage.is_regular());
+        assert!(Zap.is_regular());
+        assert!(!Metadata.is_regular());
+        assert!(ContactList.is_replaceable());
+        assert!(!ContactList.is_regular());
+        assert!(!Auth.is_regular());
+        assert!(!LongFormContent.is_regular());
+        assert!(EventKind::from(40000).is_regular());
+    }
+
+    #[test]
+    fn test_well_known_kinds_round_trip() {
+        for kind in EventKind::iter() {
+            let u: u64 = From::from(kind);
+            assert_eq!(EventKind::from(u), kind);
+        }
+    }
+
+    #[test]
+    fn test_kind_names() {
+        assert_eq!(TextNote.name().unwrap(), "text-note");
+        assert_eq!(DmRelayList.name().unwrap(), "dm-relay-list");
+        assert_eq!(JobRequest(5300).name(), None);
+
+        assert_eq!(EventKind::from_name("text-note").unwrap(), TextNote);
+        assert_eq!(EventKind::from_name("Gift Wrap").unwrap(), GiftWrap);
+        assert_eq!(
+            EventKind::from_name("long_form_content").unwrap(),
+            LongFormContent
+        );
+        assert_eq!(EventKind::from_name("5300").unwrap(), JobRequest(5300));
+        assert!(EventKind::from_name("not-a-kind").is_err());
+
+        let kinds: Vec<EventKind> = "text-note,reaction"
+            .split(',')
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(kinds, vec![TextNote, Reaction]);
+
+        for kind in EventKind::iter() {
+            assert_eq!(EventKind::from_name(kind.name().unwrap()).unwrap(), kind);
+        }
+    }
+
+    #[test]
+    fn test_kind_ranges_and_display() {
+        assert_eq!(EventKind::from(5300), JobRequest(5300));
+        assert!(JobRequest(5300).is_job_request());
+        assert_eq!(EventKind::from(6300), JobResult(6300));
+        assert!(JobResult(6300).is_job_result());
+        assert!(!JobFeedback.is_job_result());
+        assert!(GroupPutUser.is_group_moderation());
+        assert!(GroupMetadata.is_group_metadata());
+
+        assert_eq!(format!("{}", TextNote), "Text Note");
+        assert_eq!(format!("{}", GiftWrap), "Gift Wrap");
+        assert_eq!(format!("{}", JobRequest(5300)), "Job Request (5300)");
+        assert_eq!(format!("{}", EventKind::from(12345)), "Replaceable (12345)");
     }
 }
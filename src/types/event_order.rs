@@ -0,0 +1,178 @@
+use super::{Event, SeenEvent, SeenEvents, Unixtime};
+use std::cmp::Ordering;
+
+/// Which time to order events by
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OrderBy {
+    /// The event's `created_at`, which its author chose freely
+    #[default]
+    CreatedAt,
+
+    /// When the event was first seen locally. Events never seen fall back to
+    /// their `created_at`.
+    FirstSeen,
+
+    /// The earlier of the two, so that an event dated in the future cannot
+    /// jump ahead of events that arrived after it
+    Earliest,
+}
+
+/// How to order events whose times are equal
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TieBreak {
+    /// By id, lowest first whatever the direction, as relays do (NIP-01)
+    #[default]
+    Id,
+
+    /// By `created_at` in the same direction, then by id
+    CreatedAt,
+
+    /// By first seen time in the same direction, then by id
+    FirstSeen,
+}
+
+/// An ordering of events for feeds: by `created_at`, which is easily faked,
+/// or by when events were first seen locally.
+///
+/// ```
+/// # use nostr_types::{EventOrder, OrderBy, TieBreak};
+/// let order = EventOrder::new(OrderBy::Earliest).tie_break(TieBreak::CreatedAt);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventOrder {
+    by: OrderBy,
+    tie_break: TieBreak,
+    oldest_first: bool,
+}
+
+impl EventOrder {
+    /// Order newest first by `by`, breaking ties by id
+    pub fn new(by: OrderBy) -> EventOrder {
+        EventOrder {
+            by,
+            ..Default::default()
+        }
+    }
+
+    /// Break ties this way
+    pub fn tie_break(mut self, tie_break: TieBreak) -> EventOrder {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Order oldest first instead
+    pub fn oldest_first(mut self) -> EventOrder {
+        self.oldest_first = true;
+        self
+    }
+
+    /// The time an event is ordered by, given when it was first seen
+    pub fn time(&self, event: &Event, first_seen: Option<Unixtime>) -> Unixtime {
+        let first_seen = first_seen.unwrap_or(event.created_at);
+        match self.by {
+            OrderBy::CreatedAt => event.created_at,
+            OrderBy::FirstSeen => first_seen,
+            OrderBy::Earliest => event.created_at.min(first_seen),
+        }
+    }
+
+    /// Compare two events, given when each was first seen
+    pub fn compare(
+        &self,
+        a: &Event,
+        a_first_seen: Option<Unixtime>,
+        b: &Event,
+        b_first_seen: Option<Unixtime>,
+    ) -> Ordering {
+        let directed = |ordering: Ordering| {
+            if self.oldest_first {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        };
+        let times = directed(self.time(a, a_first_seen).cmp(&self.time(b, b_first_seen)));
+        let tie = match self.tie_break {
+            TieBreak::Id => Ordering::Equal,
+            TieBreak::CreatedAt => directed(a.created_at.cmp(&b.created_at)),
+            TieBreak::FirstSeen => directed(
+                a_first_seen
+                    .unwrap_or(a.created_at)
+                    .cmp(&b_first_seen.unwrap_or(b.created_at)),
+            ),
+        };
+        times.then(tie).then_with(|| a.id.cmp(&b.id))
+    }
+
+    /// Sort events, looking up when they were first seen in `seen`
+    pub fn sort(&self, events: &mut [Event], seen: &SeenEvents) {
+        let first_seen = |event: &Event| seen.get(event.id).map(|s| s.first_seen);
+        events.sort_by(|a, b| self.compare(a, first_seen(a), b, first_seen(b)));
+    }
+
+    /// Sort events along with when they were first seen
+    pub fn sort_seen(&self, events: &mut [SeenEvent]) {
+        events.sort_by(|a, b| {
+            self.compare(&a.event, Some(a.first_seen), &b.event, Some(b.first_seen))
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Id, RelayUrl};
+
+    #[test]
+    fn test_event_order() {
+        let relay = RelayUrl::try_from_str("wss://relay.example.com").unwrap();
+        let event = |id: u8, created_at: i64| {
+            let mut event = Event::mock();
+            event.id = Id([id; 32]);
+            event.created_at = Unixtime(created_at);
+            event
+        };
+        // 'future' claims to be newest but arrived first
+        let future = event(1, 2000);
+        let early = event(2, 100);
+        let late = event(3, 150);
+        let tie = event(0, 150);
+
+        let mut seen = SeenEvents::new();
+        let _ = seen.record(future.clone(), relay.clone(), Unixtime(100));
+        let _ = seen.record(early.clone(), relay.clone(), Unixtime(110));
+        let _ = seen.record(late.clone(), relay.clone(), Unixtime(160));
+        let _ = seen.record(tie.clone(), relay.clone(), Unixtime(150));
+        let all = vec![early.clone(), future.clone(), tie.clone(), late.clone()];
+        let ids = |events: &[Event]| -> Vec<u8> { events.iter().map(|e| e.id.0[0]).collect() };
+
+        let mut events = all.clone();
+        EventOrder::new(OrderBy::CreatedAt).sort(&mut events, &seen);
+        assert_eq!(ids(&events), vec![1, 0, 3, 2]);
+
+        EventOrder::new(OrderBy::CreatedAt)
+            .tie_break(TieBreak::FirstSeen)
+            .sort(&mut events, &seen);
+        assert_eq!(ids(&events), vec![1, 3, 0, 2]);
+
+        EventOrder::new(OrderBy::FirstSeen).sort(&mut events, &seen);
+        assert_eq!(ids(&events), vec![3, 0, 2, 1]);
+
+        EventOrder::new(OrderBy::Earliest)
+            .oldest_first()
+            .sort(&mut events, &seen);
+        assert_eq!(ids(&events), vec![1, 2, 0, 3]);
+
+        // Events never seen are ordered by created_at
+        let mut events = all;
+        EventOrder::new(OrderBy::FirstSeen).sort(&mut events, &SeenEvents::new());
+        assert_eq!(ids(&events), vec![1, 0, 3, 2]);
+
+        let mut seen_events: Vec<SeenEvent> = [&late, &future]
+            .iter()
+            .map(|e| seen.get(e.id).unwrap().clone())
+            .collect();
+        EventOrder::new(OrderBy::FirstSeen).sort_seen(&mut seen_events);
+        assert_eq!(seen_events[0].event.id, late.id);
+    }
+}
@@ -1,9 +1,10 @@
-use super::{Id, UncheckedUrl};
+use super::{EventKind, Id, PublicKey, UncheckedUrl};
 use crate::Error;
 use bech32::{FromBase32, ToBase32};
 use serde::{Deserialize, Serialize};
 
-/// An event id along with some relays in which that event may be found.
+/// An event id along with some relays in which that event may be found, and
+/// optionally its author and kind (NIP-19 "nevent").
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct EventPointer {
     /// Event id
@@ -11,61 +12,111 @@ pub struct EventPointer {
 
     /// Some of the relays where this could be in
     pub relays: Vec<UncheckedUrl>,
+
+    /// The event's author, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<PublicKey>,
+
+    /// The event's kind, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<EventKind>,
 }
 
 impl EventPointer {
-    /// Export as a bech32 encoded string ("nevent")
-    pub fn as_bech32_string(&self) -> String {
+    /// Point to the event with `id`, with no relays, author or kind
+    pub fn new(id: Id) -> EventPointer {
+        EventPointer {
+            id,
+            relays: vec![],
+            author: None,
+            kind: None,
+        }
+    }
+
+    /// Export as a bech32 encoded string ("nevent"). This fails if a relay
+    /// is longer than 255 bytes or the kind does not fit in 32 bits, which
+    /// the format can't hold.
+    pub fn as_bech32_string(&self) -> Result<String, Error> {
         // Compose
         let mut tlv: Vec<u8> = Vec::new();
 
-        // Push Public Key
-        tlv.push(0); // the special value, in this case the public key
-        tlv.push(32); // the length of the value (always 32 for public key)
+        // Push the event id
+        tlv.push(0); // the special value, in this case the event id
+        tlv.push(32); // the length of the value (always 32 for an id)
         tlv.extend(self.id.0);
 
         // Push relays
         for relay in &self.relays {
+            let len = u8::try_from(relay.0.len()).map_err(|_| Error::TooLarge {
+                size: relay.0.len(),
+                max: u8::MAX as usize,
+            })?;
             tlv.push(1); // type 'relay'
-            tlv.push(relay.0.len() as u8); // the length of the string
+            tlv.push(len); // the length of the string
             tlv.extend(relay.0.as_bytes());
         }
 
-        bech32::encode("nevent", tlv.to_base32(), bech32::Variant::Bech32).unwrap()
+        // Push the author
+        if let Some(author) = &self.author {
+            tlv.push(2);
+            tlv.push(32);
+            tlv.extend(author.0.to_bytes());
+        }
+
+        // Push the kind, as a big-endian 32-bit number
+        if let Some(kind) = self.kind {
+            let number = u32::try_from(u64::from(kind)).map_err(|_| {
+                Error::AssertionFailed(format!("kind {} does not fit in a nevent", u64::from(kind)))
+            })?;
+            tlv.push(3);
+            tlv.push(4);
+            tlv.extend(number.to_be_bytes());
+        }
+
+        Ok(bech32::encode("nevent", tlv.to_base32(), bech32::Variant::Bech32).unwrap())
     }
 
     /// Import from a bech32 encoded string ("nevent")
     pub fn try_from_bech32_string(s: &str) -> Result<EventPointer, Error> {
-        let data = bech32::decode(s)?;
-        if data.0 != "nevent" {
-            Err(Error::WrongBech32("nevent".to_string(), data.0))
-        } else {
-            let tlv = Vec::<u8>::from_base32(&data.1)?;
-            if tlv[0] != 0 || tlv[1] != 32 {
-                return Err(Error::InvalidProfile);
-            }
-            let id: Id = Id(tlv[2..2 + 32]
-                .try_into()
-                .map_err(|_| Error::WrongLengthHexString)?);
-            let mut relays: Vec<UncheckedUrl> = Vec::new();
-            let mut pos = 2 + 32;
-            while tlv.len() >= pos + 2 {
-                let typ = tlv[pos];
-                let len = tlv[pos + 1];
-                pos += 2;
-                if typ != 1 {
-                    return Err(Error::InvalidProfile);
+        let data = crate::error::bech32_decode_as(s, "nevent", None)?;
+        let tlv = Vec::<u8>::from_base32(&data)?;
+
+        let mut id: Option<Id> = None;
+        let mut relays: Vec<UncheckedUrl> = Vec::new();
+        let mut author: Option<PublicKey> = None;
+        let mut kind: Option<EventKind> = None;
+        let mut pos = 0;
+        while tlv.len() >= pos + 2 {
+            let typ = tlv[pos];
+            let len = tlv[pos + 1] as usize;
+            pos += 2;
+            let value = tlv.get(pos..pos + len).ok_or(Error::InvalidProfile)?;
+            match typ {
+                0 => {
+                    let bytes: [u8; 32] =
+                        value.try_into().map_err(|_| Error::WrongLengthHexString)?;
+                    id = Some(Id(bytes));
                 }
-                if tlv.len() < pos + len as usize {
-                    return Err(Error::InvalidProfile);
+                1 => relays.push(UncheckedUrl::from_str(std::str::from_utf8(value)?)),
+                2 => author = Some(PublicKey::from_bytes(value)?),
+                3 => {
+                    let bytes: [u8; 4] = value.try_into().map_err(|_| Error::InvalidProfile)?;
+                    kind = Some(EventKind::from(u32::from_be_bytes(bytes) as u64));
                 }
-                let relay_bytes = &tlv[pos..pos + (len as usize)];
-                let relay_str = std::str::from_utf8(relay_bytes)?;
-                let relay = UncheckedUrl::from_str(relay_str);
-                relays.push(relay);
-                pos += len as usize;
+                // Unknown types are ignored, as NIP-19 asks
+                _ => {}
             }
-            Ok(EventPointer { id, relays })
+            pos += len;
+        }
+
+        match id {
+            Some(id) => Ok(EventPointer {
+                id,
+                relays,
+                author,
+                kind,
+            }),
+            None => Err(Error::InvalidProfile),
         }
     }
 
@@ -83,6 +134,8 @@ impl EventPointer {
                 UncheckedUrl::from_str("wss://relay.example.com"),
                 UncheckedUrl::from_str("wss://relay2.example.com"),
             ],
+            author: None,
+            kind: None,
         }
     }
 }
@@ -95,7 +148,7 @@ mod test {
 
     #[test]
     fn test_profile_bech32() {
-        let bech32 = EventPointer::mock().as_bech32_string();
+        let bech32 = EventPointer::mock().as_bech32_string().unwrap();
         println!("{bech32}");
         assert_eq!(
             EventPointer::mock(),
@@ -114,12 +167,14 @@ mod test {
                 UncheckedUrl::from_str("wss://r.x.com"),
                 UncheckedUrl::from_str("wss://djbas.sadkb.com"),
             ],
+            author: None,
+            kind: None,
         };
 
         let bech32 = "nevent1qqsrhuxx8l9ex335q7he0f09aej04zpazpl0ne2cgukyawd24mayt8gpp4mhxue69uhhytnc9e3k7mgpz4mhxue69uhkg6nzv9ejuumpv34kytnrdaks343fay";
 
         // Try converting profile to bech32
-        assert_eq!(event_pointer.as_bech32_string(), bech32);
+        assert_eq!(event_pointer.as_bech32_string().unwrap(), bech32);
 
         // Try converting bech32 to profile
         assert_eq!(
@@ -127,4 +182,47 @@ mod test {
             EventPointer::try_from_bech32_string(bech32).unwrap()
         );
     }
+
+    #[test]
+    fn test_event_pointer_author_and_kind() {
+        let mut pointer = EventPointer::mock();
+        pointer.author = Some(
+            PublicKey::try_from_hex_string(
+                "b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9",
+            )
+            .unwrap(),
+        );
+        pointer.kind = Some(EventKind::LongFormContent);
+        let bech32 = pointer.as_bech32_string().unwrap();
+        assert_eq!(
+            EventPointer::try_from_bech32_string(&bech32).unwrap(),
+            pointer
+        );
+
+        // Unknown types are skipped, a missing id or truncated value is not
+        let mut tlv: Vec<u8> = vec![9, 2, 0xab, 0xcd, 0, 32];
+        tlv.extend(pointer.id.0);
+        let unknown = bech32::encode("nevent", tlv.to_base32(), bech32::Variant::Bech32).unwrap();
+        assert_eq!(
+            EventPointer::try_from_bech32_string(&unknown).unwrap(),
+            EventPointer::new(pointer.id)
+        );
+        let no_id =
+            bech32::encode("nevent", [1u8, 0].to_base32(), bech32::Variant::Bech32).unwrap();
+        assert!(EventPointer::try_from_bech32_string(&no_id).is_err());
+        let truncated =
+            bech32::encode("nevent", [0u8, 32, 1].to_base32(), bech32::Variant::Bech32).unwrap();
+        assert!(EventPointer::try_from_bech32_string(&truncated).is_err());
+
+        // Kinds and relays the format can't hold are refused, not truncated
+        let mut too_big = pointer.clone();
+        too_big.kind = Some(EventKind::from(u64::from(u32::MAX) + 1));
+        assert!(too_big.as_bech32_string().is_err());
+        let mut too_long = pointer;
+        too_long.relays = vec![UncheckedUrl(format!("wss://{}.com", "a".repeat(250)))];
+        assert!(matches!(
+            too_long.as_bech32_string(),
+            Err(Error::TooLarge { max: 255, .. })
+        ));
+    }
 }
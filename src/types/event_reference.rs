@@ -0,0 +1,282 @@
+use super::{
+    Event, EventAddr, EventKind, EventPointer, Id, NostrBech32, PublicKey, Tag, UncheckedUrl,
+};
+use crate::Error;
+
+/// A reference from one event to another, by id with relay hints (as in 'e'
+/// tags and "note"/"nevent") or by address (as in 'a' tags and "naddr").
+///
+/// The thread, deletion, reaction and comment accessors on `Event` all
+/// return these, and `to_tag()` turns one back into the tag that refers to
+/// it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventReference {
+    /// An event id with relay hints
+    Pointer(EventPointer),
+
+    /// An addressable event's address with relay hints
+    Addr(EventAddr),
+}
+
+impl From<Id> for EventReference {
+    fn from(id: Id) -> EventReference {
+        EventReference::Pointer(EventPointer::new(id))
+    }
+}
+
+impl From<EventPointer> for EventReference {
+    fn from(pointer: EventPointer) -> EventReference {
+        EventReference::Pointer(pointer)
+    }
+}
+
+impl From<EventAddr> for EventReference {
+    fn from(addr: EventAddr) -> EventReference {
+        EventReference::Addr(addr)
+    }
+}
+
+impl EventReference {
+    /// Refer to `event`: by address if it is addressable, otherwise by id
+    pub fn for_event(event: &Event, relays: Vec<UncheckedUrl>) -> EventReference {
+        if event.kind.is_addressable() {
+            EventReference::Addr(EventAddr {
+                d: event.parameter().unwrap_or_default(),
+                relays,
+                kind: event.kind,
+                author: event.pubkey,
+            })
+        } else {
+            EventReference::Pointer(EventPointer {
+                id: event.id,
+                relays,
+                author: Some(event.pubkey),
+                kind: Some(event.kind),
+            })
+        }
+    }
+
+    /// The id referred to, if this refers by id
+    pub fn id(&self) -> Option<Id> {
+        match self {
+            EventReference::Pointer(pointer) => Some(pointer.id),
+            EventReference::Addr(_) => None,
+        }
+    }
+
+    /// The address referred to, if this refers by address
+    pub fn addr(&self) -> Option<&EventAddr> {
+        match self {
+            EventReference::Pointer(_) => None,
+            EventReference::Addr(addr) => Some(addr),
+        }
+    }
+
+    /// The kind of the event referred to, if known
+    pub fn kind(&self) -> Option<EventKind> {
+        match self {
+            EventReference::Pointer(pointer) => pointer.kind,
+            EventReference::Addr(addr) => Some(addr.kind),
+        }
+    }
+
+    /// Relays where the event referred to may be found
+    pub fn relays(&self) -> &[UncheckedUrl] {
+        match self {
+            EventReference::Pointer(pointer) => &pointer.relays,
+            EventReference::Addr(addr) => &addr.relays,
+        }
+    }
+
+    /// Is `event` the event referred to (or, for an address, a version of
+    /// it)?
+    pub fn matches(&self, event: &Event) -> bool {
+        match self {
+            EventReference::Pointer(pointer) => event.id == pointer.id,
+            EventReference::Addr(addr) => {
+                event.pubkey == addr.author
+                    && event.kind == addr.kind
+                    && event.parameter().unwrap_or_default() == addr.d
+            }
+        }
+    }
+
+    /// The 'e' or 'a' tag referring to the event, with the first relay hint
+    /// and an optional marker (such as "root" or "reply")
+    pub fn to_tag(&self, marker: Option<&str>) -> Tag {
+        let marker = marker.map(|m| m.to_owned());
+        match self {
+            EventReference::Pointer(pointer) => Tag::Event {
+                id: pointer.id,
+                recommended_relay_url: pointer.relays.first().cloned(),
+                marker,
+                pubkey: pointer.author.map(|author| author.into()),
+            },
+            EventReference::Addr(addr) => Tag::Address {
+                address: EventAddr {
+                    relays: addr.relays.iter().take(1).cloned().collect(),
+                    ..addr.clone()
+                },
+                marker,
+            },
+        }
+    }
+
+    /// The uppercase 'E' or 'A' tag a comment uses to refer to its root
+    /// (NIP-22)
+    pub fn to_root_tag(&self) -> Tag {
+        let relay = self
+            .relays()
+            .first()
+            .map(|r| r.0.clone())
+            .unwrap_or_default();
+        match self {
+            EventReference::Pointer(pointer) => Tag::Other {
+                tag: "E".to_owned(),
+                data: vec![pointer.id.as_hex_string(), relay],
+            },
+            EventReference::Addr(addr) => Tag::Other {
+                tag: "A".to_owned(),
+                data: vec![addr.as_address(), relay],
+            },
+        }
+    }
+
+    /// The reference made by an 'e' or 'a' tag, or (for comments) an 'E' or
+    /// 'A' tag, along with its marker if any
+    pub fn from_tag(tag: &Tag) -> Option<(EventReference, Option<&str>)> {
+        match tag {
+            Tag::Event {
+                id,
+                recommended_relay_url,
+                marker,
+                pubkey,
+            } => Some((
+                EventReference::Pointer(EventPointer {
+                    id: *id,
+                    relays: recommended_relay_url.iter().cloned().collect(),
+                    author: pubkey
+                        .as_ref()
+                        .and_then(|pk| PublicKey::try_from_hex_string(pk.as_str()).ok()),
+                    kind: None,
+                }),
+                marker.as_deref(),
+            )),
+            Tag::Address { address, marker } => {
+                Some((EventReference::Addr(address.clone()), marker.as_deref()))
+            }
+            Tag::Other { tag, data } if tag == "E" || tag == "A" => {
+                let relays: Vec<UncheckedUrl> = data
+                    .get(1)
+                    .filter(|r| !r.is_empty())
+                    .map(|r| UncheckedUrl::from_str(r))
+                    .into_iter()
+                    .collect();
+                let value = data.first()?;
+                let reference = if tag == "E" {
+                    EventReference::Pointer(EventPointer {
+                        id: Id::try_from_hex_string(value).ok()?,
+                        relays,
+                        author: data
+                            .get(2)
+                            .and_then(|pk| PublicKey::try_from_hex_string(pk).ok()),
+                        kind: None,
+                    })
+                } else {
+                    EventReference::Addr(EventAddr {
+                        relays,
+                        ..EventAddr::try_from_address(value).ok()?
+                    })
+                };
+                Some((reference, None))
+            }
+            _ => None,
+        }
+    }
+
+    /// Export as a bech32 encoded string ("nevent" or "naddr"). This fails
+    /// for a pointer the nevent format can't hold (see
+    /// `EventPointer::as_bech32_string`).
+    pub fn as_bech32_string(&self) -> Result<String, Error> {
+        match self {
+            EventReference::Pointer(pointer) => pointer.as_bech32_string(),
+            EventReference::Addr(addr) => Ok(addr.as_bech32_string()),
+        }
+    }
+
+    /// Import from a bech32 encoded string ("note", "nevent" or "naddr"),
+    /// optionally prefixed with "nostr:"
+    pub fn try_from_bech32_string(s: &str) -> Result<EventReference, Error> {
+        let s = s.strip_prefix("nostr:").unwrap_or(s);
+        match NostrBech32::try_from_string(s) {
+            Some(NostrBech32::Id(id)) => Ok(id.into()),
+            Some(NostrBech32::EventPointer(pointer)) => Ok(pointer.into()),
+            Some(NostrBech32::EventAddr(addr)) => Ok(addr.into()),
+            _ => Err(Error::AssertionFailed(format!(
+                "Not an event reference: {s}"
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn test_event_reference_tags() {
+        let pointer = EventReference::Pointer(EventPointer {
+            id: Id::mock(),
+            relays: vec![
+                UncheckedUrl::from_str("wss://a.example/"),
+                UncheckedUrl::from_str("wss://b.example/"),
+            ],
+            author: None,
+            kind: None,
+        });
+        let addr = EventReference::Addr(EventAddr {
+            d: "slug".to_owned(),
+            relays: vec![UncheckedUrl::from_str("wss://a.example/")],
+            kind: EventKind::LongFormContent,
+            author: PublicKey::mock(),
+        });
+
+        let tag = pointer.to_tag(Some("root"));
+        let (back, marker) = EventReference::from_tag(&tag).unwrap();
+        assert_eq!(marker, Some("root"));
+        assert_eq!(back.id(), pointer.id());
+        assert_eq!(back.relays().len(), 1);
+
+        for reference in [pointer.clone(), addr.clone()] {
+            let tag = reference.to_root_tag();
+            let tag = Tag::from_strings(tag.to_strings());
+            let (back, marker) = EventReference::from_tag(&tag).unwrap();
+            assert_eq!(marker, None);
+            assert_eq!(back.relays().first(), reference.relays().first());
+            assert_eq!(back.id(), reference.id());
+            assert_eq!(
+                back.addr().map(|a| a.as_address()),
+                reference.addr().map(|a| a.as_address())
+            );
+
+            let bech32 = format!("nostr:{}", reference.as_bech32_string().unwrap());
+            assert_eq!(
+                EventReference::try_from_bech32_string(&bech32).unwrap(),
+                reference
+            );
+        }
+        assert_eq!(
+            EventReference::try_from_bech32_string(&Id::mock().as_bech32_string()).unwrap(),
+            EventReference::from(Id::mock())
+        );
+        assert!(EventReference::try_from_bech32_string(
+            &PrivateKey::generate().public_key().as_bech32_string()
+        )
+        .is_err());
+
+        let event = Event::mock();
+        assert!(!addr.matches(&event));
+        assert!(EventReference::for_event(&event, vec![]).matches(&event));
+    }
+}
@@ -0,0 +1,201 @@
+use super::{Event, EventKind, EventStorage, Filter, PublicKey, Unixtime};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Statistics over a set of events: how many of each kind and from each
+/// author, how fast they came, and how large they are.
+///
+/// Feed it events as they arrive with `record()`, or from storage with
+/// `from_storage()`. It serializes to JSON for dashboards and reports.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EventStats {
+    total: u64,
+    bytes: u64,
+    kinds: BTreeMap<u64, u64>,
+    authors: BTreeMap<String, u64>,
+    first: Option<Unixtime>,
+    last: Option<Unixtime>,
+    size_histogram: Vec<u64>,
+}
+
+impl EventStats {
+    /// No events yet
+    pub fn new() -> EventStats {
+        Default::default()
+    }
+
+    /// Count an event, received at `now`
+    pub fn record(&mut self, event: &Event, now: Unixtime) {
+        let size = serde_json::to_string(event)
+            .map(|json| json.len())
+            .unwrap_or(0);
+
+        self.total += 1;
+        self.bytes += size as u64;
+        *self.kinds.entry(u64::from(event.kind)).or_insert(0) += 1;
+        *self
+            .authors
+            .entry(event.pubkey.as_hex_string())
+            .or_insert(0) += 1;
+        self.first = Some(self.first.map_or(now, |first| first.min(now)));
+        self.last = Some(self.last.map_or(now, |last| last.max(now)));
+
+        let bucket = size_bucket(size);
+        if self.size_histogram.len() <= bucket {
+            self.size_histogram.resize(bucket + 1, 0);
+        }
+        self.size_histogram[bucket] += 1;
+    }
+
+    /// Collect statistics over the stored events matching `filter`, taking
+    /// each event's `created_at` as when it arrived
+    pub fn from_storage<S: EventStorage>(
+        storage: &S,
+        filter: &Filter,
+    ) -> Result<EventStats, Error> {
+        let mut stats = EventStats::new();
+        for event in storage.query(filter)?.iter() {
+            stats.record(event, event.created_at);
+        }
+        Ok(stats)
+    }
+
+    /// How many events were counted
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The total size of the events, in bytes of JSON
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// How many events of `kind` were counted
+    pub fn count_of_kind(&self, kind: EventKind) -> u64 {
+        self.kinds.get(&u64::from(kind)).copied().unwrap_or(0)
+    }
+
+    /// The kinds counted, most common first
+    pub fn kinds(&self) -> Vec<(EventKind, u64)> {
+        let mut kinds: Vec<(EventKind, u64)> = self
+            .kinds
+            .iter()
+            .map(|(kind, count)| (EventKind::from(*kind), *count))
+            .collect();
+        kinds.sort_by(|a, b| b.1.cmp(&a.1));
+        kinds
+    }
+
+    /// How many events by `author` were counted
+    pub fn count_by_author(&self, author: PublicKey) -> u64 {
+        self.authors
+            .get(&author.as_hex_string())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// The `n` authors with the most events, most first
+    pub fn top_authors(&self, n: usize) -> Vec<(PublicKey, u64)> {
+        let mut authors: Vec<(PublicKey, u64)> = self
+            .authors
+            .iter()
+            .filter_map(|(hex, count)| {
+                PublicKey::try_from_hex_string(hex)
+                    .ok()
+                    .map(|pk| (pk, *count))
+            })
+            .collect();
+        authors.sort_by(|a, b| b.1.cmp(&a.1));
+        authors.truncate(n);
+        authors
+    }
+
+    /// The average rate events arrived at, if they span some time
+    pub fn events_per_second(&self) -> Option<f64> {
+        let (first, last) = (self.first?, self.last?);
+        if last <= first {
+            return None;
+        }
+        Some(self.total as f64 / (last.0 - first.0) as f64)
+    }
+
+    /// Event sizes, as counts of events whose JSON is under 64 bytes, then
+    /// under 128, 256, and so on doubling
+    pub fn size_histogram(&self) -> &[u64] {
+        &self.size_histogram
+    }
+}
+
+// Bucket 0 is under 64 bytes; each later bucket doubles the bound
+fn size_bucket(size: usize) -> usize {
+    let bits = usize::BITS - (size >> 6).leading_zeros();
+    bits as usize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PreEvent, PrivateKey};
+
+    #[test]
+    fn test_event_stats() {
+        let alice = PrivateKey::generate();
+        let bob = PrivateKey::generate();
+        let event = |key: &PrivateKey, kind: EventKind, content: &str| {
+            let pre = PreEvent {
+                pubkey: key.public_key(),
+                created_at: Unixtime(1700000000),
+                kind,
+                tags: vec![],
+                content: content.to_owned(),
+                ots: None,
+            };
+            Event::new(pre, key).unwrap()
+        };
+
+        let mut stats = EventStats::new();
+        assert_eq!(stats.events_per_second(), None);
+        stats.record(
+            &event(&alice, EventKind::TextNote, "hi"),
+            Unixtime(1700000000),
+        );
+        stats.record(
+            &event(&alice, EventKind::TextNote, "hello"),
+            Unixtime(1700000001),
+        );
+        stats.record(&event(&bob, EventKind::Reaction, "+"), Unixtime(1700000001));
+        stats.record(
+            &event(&bob, EventKind::TextNote, &"long ".repeat(200)),
+            Unixtime(1700000004),
+        );
+
+        assert_eq!(stats.total(), 4);
+        assert_eq!(stats.count_of_kind(EventKind::TextNote), 3);
+        assert_eq!(stats.count_of_kind(EventKind::Metadata), 0);
+        assert_eq!(stats.kinds()[0], (EventKind::TextNote, 3));
+        assert_eq!(stats.count_by_author(bob.public_key()), 2);
+        assert_eq!(stats.top_authors(1).len(), 1);
+        assert_eq!(stats.events_per_second(), Some(1.0));
+
+        // Small events are around 350 bytes, the long one over 1024
+        let histogram = stats.size_histogram();
+        assert_eq!(histogram.iter().sum::<u64>(), 4);
+        assert_eq!(histogram[3], 3);
+        assert_eq!(histogram[5], 1);
+        assert!(stats.bytes() > 1600);
+
+        let json = serde_json::to_string(&stats).unwrap();
+        let back: EventStats = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, stats);
+    }
+
+    #[test]
+    fn test_size_bucket() {
+        assert_eq!(size_bucket(0), 0);
+        assert_eq!(size_bucket(63), 0);
+        assert_eq!(size_bucket(64), 1);
+        assert_eq!(size_bucket(127), 1);
+        assert_eq!(size_bucket(128), 2);
+    }
+}
@@ -1,4 +1,6 @@
-use super::{EventKind, IdHex, IdHexPrefix, PublicKeyHex, PublicKeyHexPrefix, Unixtime};
+use super::{
+    Event, EventKind, Geohash, IdHex, IdHexPrefix, PublicKeyHex, PublicKeyHexPrefix, Unixtime,
+};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::ops::Deref;
@@ -141,6 +143,18 @@ fn del_substr<T: Deref<Target = String>>(vec: &mut Vec<T>, del: T) {
     }
 }
 
+// An empty set of values matches any event, otherwise the event must have a
+// `name` tag whose first value is one of them
+fn tag_matches<T: AsRef<str>>(event: &Event, name: &str, values: &[T]) -> bool {
+    if values.is_empty() {
+        return true;
+    }
+    event.tags.iter().any(|tag| {
+        let strings = tag.to_strings();
+        strings.len() >= 2 && strings[0] == name && values.iter().any(|v| v.as_ref() == strings[1])
+    })
+}
+
 impl Filter {
     /// Create a new Filter object
     pub fn new() -> Filter {
@@ -216,6 +230,71 @@ impl Filter {
         }
     }
 
+    /// Match events located within this geohash's area. This relies on events
+    /// being tagged with every prefix of their geohash.
+    pub fn within_geohash(&mut self, geohash: &Geohash) {
+        let g = geohash.as_str().to_owned();
+        if self.g.contains(&g) {
+            return;
+        }
+        self.g.push(g);
+    }
+
+    /// Does the `event` match this filter? Every field that is set must match,
+    /// and any one of the values within a field may match. `limit` is not
+    /// considered.
+    pub fn event_matches(&self, event: &Event) -> bool {
+        // Odd length prefixes can't be compared by bytes, so those fall back to
+        // comparing hex strings
+        if !self.ids.is_empty()
+            && !self.ids.iter().any(|prefix| {
+                event
+                    .id
+                    .matches_hex_prefix(prefix.as_str())
+                    .unwrap_or_else(|_| prefix.matches(&event.id.into()))
+            })
+        {
+            return false;
+        }
+
+        if !self.authors.is_empty()
+            && !self.authors.iter().any(|prefix| {
+                event
+                    .pubkey
+                    .matches_hex_prefix(prefix.as_str())
+                    .unwrap_or_else(|_| prefix.matches(&event.pubkey.into()))
+            })
+        {
+            return false;
+        }
+
+        if !self.kinds.is_empty() && !self.kinds.contains(&event.kind) {
+            return false;
+        }
+
+        if let Some(since) = self.since {
+            if event.created_at < since {
+                return false;
+            }
+        }
+
+        if let Some(until) = self.until {
+            if event.created_at > until {
+                return false;
+            }
+        }
+
+        let e: Vec<&str> = self.e.iter().map(|id| id.as_str()).collect();
+        let p: Vec<&str> = self.p.iter().map(|pk| pk.as_str()).collect();
+        tag_matches(event, "a", &self.a)
+            && tag_matches(event, "d", &self.d)
+            && tag_matches(event, "e", &e)
+            && tag_matches(event, "g", &self.g)
+            && tag_matches(event, "p", &p)
+            && tag_matches(event, "r", &self.r)
+            && tag_matches(event, "t", &self.t)
+    }
+
     // Mock data for testing
     #[allow(dead_code)]
     pub(crate) fn mock() -> Filter {
@@ -237,6 +316,7 @@ impl Filter {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{PreEvent, PrivateKey};
 
     test_serde! {Filter, test_filters_serde}
 
@@ -299,4 +379,66 @@ mod test {
     }
 
     // add_remove_author would be very similar to the above
+
+    #[test]
+    fn test_event_matches() {
+        let event = Event::mock();
+
+        let mut filter = Filter::new();
+        assert!(filter.event_matches(&event));
+
+        filter.add_id(IdHex::from(event.id).prefix(10));
+        filter.add_author(PublicKeyHex::from(event.pubkey));
+        filter.add_event_kind(event.kind);
+        filter.since = Some(event.created_at);
+        filter.until = Some(event.created_at);
+        assert!(filter.event_matches(&event));
+
+        // Both mock tags refer to the mock id
+        filter.add_e_tag_ids(IdHex::mock());
+        assert!(filter.event_matches(&event));
+
+        filter.t = vec!["nostr".to_owned()];
+        assert!(!filter.event_matches(&event));
+        filter.t = vec![];
+
+        filter.since = Some(event.created_at + std::time::Duration::from_secs(1));
+        assert!(!filter.event_matches(&event));
+
+        // Odd length prefixes still match
+        let mut filter = Filter::new();
+        filter.add_id(IdHex::from(event.id).prefix(9));
+        assert!(filter.event_matches(&event));
+        filter.ids = vec![IdHexPrefix::try_from_str("0").unwrap()];
+        assert_eq!(
+            filter.event_matches(&event),
+            event.id.as_hex_string().starts_with('0')
+        );
+    }
+
+    #[test]
+    fn test_filter_within_geohash() {
+        let private_key = PrivateKey::generate();
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::TextNote,
+            tags: vec![],
+            content: "Hello from Aalborg".to_owned(),
+            ots: None,
+        }
+        .location(57.64911, 10.40744, 7)
+        .unwrap();
+        let event = Event::new(pre, &private_key).unwrap();
+
+        let mut filter = Filter::new();
+        filter.within_geohash(&Geohash::try_from_str("u4p").unwrap());
+        filter.within_geohash(&Geohash::try_from_str("u4p").unwrap());
+        assert_eq!(filter.g, vec!["u4p".to_owned()]);
+        assert!(filter.event_matches(&event));
+
+        let mut filter = Filter::new();
+        filter.within_geohash(&Geohash::try_from_str("u4q").unwrap());
+        assert!(!filter.event_matches(&event));
+    }
 }
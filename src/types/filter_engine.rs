@@ -0,0 +1,248 @@
+use super::{Event, EventKind, Filter, SubscriptionId};
+use std::collections::{HashMap, HashSet};
+
+/// Many subscriptions' filters, compiled into an index so that an incoming
+/// event can be matched against all of them at once. This is the core loop of
+/// a relay: each new event goes to the subscriptions returned by `matches()`.
+///
+/// Each filter is indexed under its most selective field: full event ids,
+/// then full author keys, then one of its tag fields, then its kinds. Only
+/// filters with none of those (or with only id or author prefixes) are
+/// checked against every event. Candidates found through the index are then
+/// checked in full with `Filter::event_matches()`.
+#[derive(Clone, Debug, Default)]
+pub struct FilterEngine {
+    filters: HashMap<u64, (SubscriptionId, Filter)>,
+    subscriptions: HashMap<SubscriptionId, Vec<u64>>,
+    by_id: HashMap<String, Vec<u64>>,
+    by_author: HashMap<String, Vec<u64>>,
+    by_tag: HashMap<(String, String), Vec<u64>>,
+    by_kind: HashMap<u64, Vec<u64>>,
+    unindexed: Vec<u64>,
+    next_slot: u64,
+}
+
+// Where a filter is indexed
+enum IndexKey {
+    Ids(Vec<String>),
+    Authors(Vec<String>),
+    Tag(&'static str, Vec<String>),
+    Kinds(Vec<EventKind>),
+    None,
+}
+
+impl FilterEngine {
+    /// Create an empty engine
+    pub fn new() -> FilterEngine {
+        Default::default()
+    }
+
+    /// Add a subscription, replacing any with the same id as a REQ does. An
+    /// event matches the subscription if it matches any of its filters.
+    pub fn add(&mut self, subscription_id: SubscriptionId, filters: Vec<Filter>) {
+        let _ = self.remove(&subscription_id);
+        let mut slots: Vec<u64> = Vec::with_capacity(filters.len());
+        for filter in filters {
+            let slot = self.next_slot;
+            self.next_slot += 1;
+            match index_key(&filter) {
+                IndexKey::Ids(ids) => {
+                    for id in ids {
+                        self.by_id.entry(id).or_default().push(slot);
+                    }
+                }
+                IndexKey::Authors(authors) => {
+                    for author in authors {
+                        self.by_author.entry(author).or_default().push(slot);
+                    }
+                }
+                IndexKey::Tag(name, values) => {
+                    for value in values {
+                        self.by_tag
+                            .entry((name.to_owned(), value))
+                            .or_default()
+                            .push(slot);
+                    }
+                }
+                IndexKey::Kinds(kinds) => {
+                    for kind in kinds {
+                        self.by_kind.entry(u64::from(kind)).or_default().push(slot);
+                    }
+                }
+                IndexKey::None => self.unindexed.push(slot),
+            }
+            let _ = self.filters.insert(slot, (subscription_id.clone(), filter));
+            slots.push(slot);
+        }
+        let _ = self.subscriptions.insert(subscription_id, slots);
+    }
+
+    /// Remove a subscription, as on CLOSE. Returns false if there was none.
+    pub fn remove(&mut self, subscription_id: &SubscriptionId) -> bool {
+        let slots = match self.subscriptions.remove(subscription_id) {
+            Some(slots) => slots,
+            None => return false,
+        };
+        for slot in slots.iter() {
+            let _ = self.filters.remove(slot);
+        }
+        let keep = |list: &mut Vec<u64>| {
+            list.retain(|s| !slots.contains(s));
+            !list.is_empty()
+        };
+        self.by_id.retain(|_, list| keep(list));
+        self.by_author.retain(|_, list| keep(list));
+        self.by_tag.retain(|_, list| keep(list));
+        self.by_kind.retain(|_, list| keep(list));
+        self.unindexed.retain(|s| !slots.contains(s));
+        true
+    }
+
+    /// The number of subscriptions
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Are there no subscriptions?
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// The subscriptions with a filter that `event` matches, sorted and
+    /// without duplicates
+    pub fn matches(&self, event: &Event) -> Vec<SubscriptionId> {
+        let mut candidates: HashSet<u64> = self.unindexed.iter().copied().collect();
+        if let Some(slots) = self.by_id.get(&event.id.as_hex_string()) {
+            candidates.extend(slots);
+        }
+        if let Some(slots) = self.by_author.get(&event.pubkey.as_hex_string()) {
+            candidates.extend(slots);
+        }
+        if let Some(slots) = self.by_kind.get(&u64::from(event.kind)) {
+            candidates.extend(slots);
+        }
+        if !self.by_tag.is_empty() {
+            for tag in event.tags.iter() {
+                let mut strings = tag.to_strings().into_iter();
+                if let (Some(name), Some(value)) = (strings.next(), strings.next()) {
+                    if let Some(slots) = self.by_tag.get(&(name, value)) {
+                        candidates.extend(slots);
+                    }
+                }
+            }
+        }
+
+        let mut matched: Vec<SubscriptionId> = candidates
+            .iter()
+            .filter_map(|slot| self.filters.get(slot))
+            .filter(|(_, filter)| filter.event_matches(event))
+            .map(|(subscription_id, _)| subscription_id.clone())
+            .collect();
+        matched.sort_by(|a, b| a.0.cmp(&b.0));
+        matched.dedup();
+        matched
+    }
+}
+
+// Pick the most selective field of the filter to index it under. Ids and
+// authors are only indexed when all of them are complete, not prefixes.
+fn index_key(filter: &Filter) -> IndexKey {
+    let complete = |values: Vec<&str>| -> Option<Vec<String>> {
+        if values.is_empty() || values.iter().any(|v| v.len() != 64) {
+            return None;
+        }
+        Some(values.iter().map(|v| v.to_lowercase()).collect())
+    };
+
+    if let Some(ids) = complete(filter.ids.iter().map(|id| id.as_str()).collect()) {
+        return IndexKey::Ids(ids);
+    }
+    if let Some(authors) = complete(filter.authors.iter().map(|pk| pk.as_str()).collect()) {
+        return IndexKey::Authors(authors);
+    }
+
+    let e: Vec<String> = filter.e.iter().map(|id| id.as_str().to_owned()).collect();
+    let p: Vec<String> = filter.p.iter().map(|pk| pk.as_str().to_owned()).collect();
+    let tags: [(&'static str, Vec<String>); 7] = [
+        ("e", e),
+        ("p", p),
+        ("a", filter.a.clone()),
+        ("d", filter.d.clone()),
+        ("t", filter.t.clone()),
+        ("r", filter.r.clone()),
+        ("g", filter.g.clone()),
+    ];
+    if let Some((name, values)) = tags.into_iter().find(|(_, values)| !values.is_empty()) {
+        return IndexKey::Tag(name, values);
+    }
+
+    if !filter.kinds.is_empty() {
+        return IndexKey::Kinds(filter.kinds.clone());
+    }
+    IndexKey::None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{IdHex, PublicKey, PublicKeyHex, Tag};
+
+    fn sub(s: &str) -> SubscriptionId {
+        SubscriptionId(s.to_owned())
+    }
+
+    #[test]
+    fn test_filter_engine() {
+        let mut event = Event::mock();
+        event.kind = EventKind::TextNote;
+        event.tags = vec![Tag::Hashtag("nostr".to_owned())];
+        let author: PublicKeyHex = event.pubkey.into();
+
+        let mut engine = FilterEngine::new();
+
+        let mut by_author = Filter::new();
+        by_author.add_author(author.clone());
+        by_author.add_event_kind(EventKind::TextNote);
+        engine.add(sub("author"), vec![by_author]);
+
+        let mut by_prefix = Filter::new();
+        by_prefix.add_author(author.prefix(8));
+        engine.add(sub("prefix"), vec![by_prefix]);
+
+        let mut by_tag = Filter::new();
+        by_tag.t = vec!["nostr".to_owned()];
+        engine.add(sub("tag"), vec![by_tag]);
+
+        let mut by_kind = Filter::new();
+        by_kind.add_event_kind(EventKind::Metadata);
+        let mut by_e = Filter::new();
+        by_e.add_e_tag_ids(IdHex::mock());
+        engine.add(sub("miss"), vec![by_kind, by_e]);
+
+        let mut other_author = Filter::new();
+        other_author.add_author(PublicKeyHex::from(PublicKey::mock()));
+        let mut any_text_note = Filter::new();
+        any_text_note.add_event_kind(EventKind::TextNote);
+        engine.add(sub("two"), vec![other_author, any_text_note.clone()]);
+
+        assert_eq!(engine.len(), 5);
+        assert_eq!(
+            engine.matches(&event),
+            vec![sub("author"), sub("prefix"), sub("tag"), sub("two")]
+        );
+
+        event.kind = EventKind::Metadata;
+        assert_eq!(
+            engine.matches(&event),
+            vec![sub("miss"), sub("prefix"), sub("tag")]
+        );
+
+        // Re-adding replaces, and removing drops from every index
+        engine.add(sub("tag"), vec![any_text_note]);
+        assert_eq!(engine.matches(&event), vec![sub("miss"), sub("prefix")]);
+        assert!(engine.remove(&sub("prefix")));
+        assert!(!engine.remove(&sub("prefix")));
+        assert_eq!(engine.matches(&event), vec![sub("miss")]);
+        assert_eq!(engine.len(), 4);
+    }
+}
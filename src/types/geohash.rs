@@ -0,0 +1,146 @@
+use crate::Error;
+use std::fmt;
+
+const BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// The longest geohash we handle. 12 characters is a cell a few centimeters
+/// across.
+const MAX_PRECISION: usize = 12;
+
+/// A geohash, as used in 'g' tags to give an event a location.
+///
+/// Each character narrows down the area, so a geohash lies within every
+/// prefix of itself. Relays only match 'g' tags exactly, so events are tagged
+/// with every prefix of their geohash (see `PreEvent::location`) and can then
+/// be found by area with `Filter::within_geohash`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Geohash(String);
+
+impl Geohash {
+    /// Parse a geohash, in either case
+    pub fn try_from_str(s: &str) -> Result<Geohash, Error> {
+        let s = s.to_ascii_lowercase();
+        if s.is_empty() || s.len() > MAX_PRECISION {
+            return Err(Error::InvalidGeohash(s));
+        }
+        if !s.bytes().all(|b| BASE32.contains(&b)) {
+            return Err(Error::InvalidGeohash(s));
+        }
+        Ok(Geohash(s))
+    }
+
+    /// Encode a location to a geohash of `precision` characters (1 to 12)
+    pub fn encode(latitude: f64, longitude: f64, precision: usize) -> Result<Geohash, Error> {
+        if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+            return Err(Error::InvalidGeohash(format!(
+                "location {latitude},{longitude} is out of range"
+            )));
+        }
+        if precision == 0 || precision > MAX_PRECISION {
+            return Err(Error::InvalidGeohash(format!(
+                "precision {precision} is not between 1 and {MAX_PRECISION}"
+            )));
+        }
+
+        let mut lat_range = (-90.0, 90.0);
+        let mut lon_range = (-180.0, 180.0);
+        let mut output = String::with_capacity(precision);
+        let mut bits = 0;
+        let mut index = 0;
+        // Bits alternate between longitude and latitude, longitude first
+        let mut even = true;
+        while output.len() < precision {
+            let (range, value) = if even {
+                (&mut lon_range, longitude)
+            } else {
+                (&mut lat_range, latitude)
+            };
+            let mid = (range.0 + range.1) / 2.0;
+            index <<= 1;
+            if value >= mid {
+                index |= 1;
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            even = !even;
+            bits += 1;
+            if bits == 5 {
+                output.push(BASE32[index] as char);
+                bits = 0;
+                index = 0;
+            }
+        }
+        Ok(Geohash(output))
+    }
+
+    /// The geohash as a string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The number of characters
+    pub fn precision(&self) -> usize {
+        self.0.len()
+    }
+
+    /// A less precise geohash covering this one. If it is already no more
+    /// precise than `precision`, it is returned unchanged.
+    pub fn truncate(&self, precision: usize) -> Geohash {
+        let mut s = self.0.clone();
+        s.truncate(precision.max(1));
+        Geohash(s)
+    }
+
+    /// Every geohash covering this one, least precise first, ending with
+    /// this one
+    pub fn prefixes(&self) -> Vec<Geohash> {
+        (1..=self.precision()).map(|p| self.truncate(p)).collect()
+    }
+
+    /// Does this geohash's area include `other`?
+    pub fn contains(&self, other: &Geohash) -> bool {
+        other.0.starts_with(&self.0)
+    }
+}
+
+impl fmt::Display for Geohash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_geohash_encode() {
+        let geohash = Geohash::encode(57.64911, 10.40744, 11).unwrap();
+        assert_eq!(geohash.as_str(), "u4pruydqqvj");
+        assert_eq!(geohash.truncate(5).as_str(), "u4pru");
+        assert_eq!(geohash.truncate(20), geohash);
+
+        let geohash = Geohash::encode(-33.8688, 151.2093, 6).unwrap();
+        assert_eq!(geohash.as_str(), "r3gx2f");
+
+        assert!(Geohash::encode(91.0, 0.0, 5).is_err());
+        assert!(Geohash::encode(f64::NAN, 0.0, 5).is_err());
+        assert!(Geohash::encode(0.0, 0.0, 0).is_err());
+        assert!(Geohash::encode(0.0, 0.0, 13).is_err());
+    }
+
+    #[test]
+    fn test_geohash_parse() {
+        let geohash = Geohash::try_from_str("U4PRU").unwrap();
+        assert_eq!(geohash.as_str(), "u4pru");
+        assert!(Geohash::try_from_str("").is_err());
+        assert!(Geohash::try_from_str("u4pra").is_err());
+        assert!(Geohash::try_from_str("u4pruydqqvjxx").is_err());
+
+        let prefixes: Vec<String> = geohash.prefixes().iter().map(|g| g.to_string()).collect();
+        assert_eq!(prefixes, vec!["u", "u4", "u4p", "u4pr", "u4pru"]);
+        assert!(geohash.truncate(2).contains(&geohash));
+        assert!(!geohash.contains(&geohash.truncate(2)));
+    }
+}
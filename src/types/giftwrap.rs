@@ -0,0 +1,440 @@
+use super::crypto_provider::ProviderRng;
+use super::{Event, EventKind, Id, PreEvent, PrivateKey, PublicKey, Tag, Unixtime};
+use crate::Error;
+use rand_core::CryptoRngCore;
+use serde::{Deserialize, Serialize};
+
+// Seals and wraps are dated up to this long before the rumor, so that relays
+// cannot match them up by time
+const MAX_TIME_TWEAK: u32 = 2 * 24 * 60 * 60;
+
+/// An unsigned event, the innermost layer of a gift wrap (NIP-59). It has an
+/// id but no signature, so its author can deny having written it.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Rumor {
+    /// The hash of the rumor's contents, as for an event
+    pub id: Id,
+
+    /// The author
+    pub pubkey: PublicKey,
+
+    /// When it was written
+    pub created_at: Unixtime,
+
+    /// The kind of event
+    pub kind: EventKind,
+
+    /// The tags
+    pub tags: Vec<Tag>,
+
+    /// The content
+    pub content: String,
+}
+
+impl Rumor {
+    /// Create a rumor from unsigned event data
+    pub fn new(input: PreEvent) -> Result<Rumor, Error> {
+        let id = Event::hash(&input)?;
+        Ok(Rumor {
+            id,
+            pubkey: input.pubkey,
+            created_at: input.created_at,
+            kind: input.kind,
+            tags: input.tags,
+            content: input.content,
+        })
+    }
+}
+
+/// Seal and gift wrap a rumor for each recipient, and for its author, as
+/// NIP-17 requires so that the author can read their own sent messages.
+///
+/// The rumor must be authored by `signer`, who signs the seals. Each seal
+/// and wrap is encrypted with NIP-44, and each wrap is signed by a new random
+/// key.
+///
+/// The wraps are returned in the order of `recipients`, with the author's own
+/// copy last.
+pub fn giftwrap_for_recipients(
+    rumor: &Rumor,
+    signer: &PrivateKey,
+    recipients: &[PublicKey],
+) -> Result<Vec<Event>, Error> {
+    giftwrap_for_recipients_with_rng(rumor, signer, recipients, &mut ProviderRng)
+}
+
+/// As `giftwrap_for_recipients`, but drawing the wrapping keys and time
+/// offsets from `rng`. NIP-44 still draws its own nonces.
+pub fn giftwrap_for_recipients_with_rng(
+    rumor: &Rumor,
+    signer: &PrivateKey,
+    recipients: &[PublicKey],
+    rng: &mut impl CryptoRngCore,
+) -> Result<Vec<Event>, Error> {
+    let rumor_json = serde_json::to_string(rumor)?;
+    let everyone = everyone(rumor, signer, recipients)?;
+    let encrypt = &PrivateKey::nip44_encrypt;
+    let mut wraps: Vec<Event> = Vec::with_capacity(everyone.len());
+    for recipient in everyone.iter() {
+        let seal = seal(rumor, &rumor_json, signer, recipient, encrypt, rng)?;
+        wraps.push(wrap(&seal, rumor.created_at, recipient, encrypt, rng)?);
+    }
+    Ok(wraps)
+}
+
+// As `giftwrap_for_recipients_with_rng`, with `encrypt` standing in for
+// NIP-44 so that tests can look inside and get reproducible wraps
+#[cfg(test)]
+fn giftwrap_for_recipients_with<F>(
+    rumor: &Rumor,
+    signer: &PrivateKey,
+    recipients: &[PublicKey],
+    encrypt: F,
+    rng: &mut impl CryptoRngCore,
+) -> Result<Vec<Event>, Error>
+where
+    F: Fn(&PrivateKey, &PublicKey, &str) -> Result<String, Error>,
+{
+    let rumor_json = serde_json::to_string(rumor)?;
+    let everyone = everyone(rumor, signer, recipients)?;
+    let mut wraps: Vec<Event> = Vec::with_capacity(everyone.len());
+    for recipient in everyone.iter() {
+        let seal = seal(rumor, &rumor_json, signer, recipient, &encrypt, rng)?;
+        wraps.push(wrap(&seal, rumor.created_at, recipient, &encrypt, rng)?);
+    }
+    Ok(wraps)
+}
+
+// The recipients without repeats, then the rumor's author, who must be
+// `signer`
+fn everyone(
+    rumor: &Rumor,
+    signer: &PrivateKey,
+    recipients: &[PublicKey],
+) -> Result<Vec<PublicKey>, Error> {
+    let author = signer.public_key();
+    if rumor.pubkey != author {
+        return Err(Error::AssertionFailed(
+            "Rumor is not authored by the signer".to_owned(),
+        ));
+    }
+
+    let mut everyone: Vec<PublicKey> = Vec::new();
+    for pubkey in recipients.iter() {
+        if *pubkey != author && !everyone.contains(pubkey) {
+            everyone.push(*pubkey);
+        }
+    }
+    everyone.push(author);
+    Ok(everyone)
+}
+
+/// A rumor sealed for one recipient (kind 13, NIP-59): encrypted to them
+/// and signed by its author
+#[derive(Clone, Debug, PartialEq)]
+pub struct Seal(pub Event);
+
+impl Seal {
+    /// Seal `rumor`, which must be authored by `signer`, for `recipient`
+    /// with NIP-44
+    pub fn new(rumor: &Rumor, signer: &PrivateKey, recipient: &PublicKey) -> Result<Seal, Error> {
+        if rumor.pubkey != signer.public_key() {
+            return Err(Error::AssertionFailed(
+                "Rumor is not authored by the signer".to_owned(),
+            ));
+        }
+        let rumor_json = serde_json::to_string(rumor)?;
+        seal(
+            rumor,
+            &rumor_json,
+            signer,
+            recipient,
+            &PrivateKey::nip44_encrypt,
+            &mut ProviderRng,
+        )
+        .map(Seal)
+    }
+
+    /// Take a kind 13 event as a seal
+    pub fn from_event(event: Event) -> Result<Seal, Error> {
+        if event.kind != EventKind::Seal {
+            return Err(Error::WrongEventKind);
+        }
+        Ok(Seal(event))
+    }
+
+    /// Verify and decrypt the seal with the recipient's key. Fails unless
+    /// the rumor is by the seal's signer and its id is right.
+    pub fn open(&self, private_key: &PrivateKey) -> Result<Rumor, Error> {
+        self.0.verify(None)?;
+        let json = private_key.nip44_decrypt(&self.0.pubkey, &self.0.content)?;
+        let rumor: Rumor = serde_json::from_str(&json)?;
+        if rumor.pubkey != self.0.pubkey {
+            return Err(Error::AssertionFailed(
+                "Rumor is not authored by the seal's signer".to_owned(),
+            ));
+        }
+        let id = Event::hash(&PreEvent {
+            pubkey: rumor.pubkey,
+            created_at: rumor.created_at,
+            kind: rumor.kind,
+            tags: rumor.tags.clone(),
+            content: rumor.content.clone(),
+            ots: None,
+        })?;
+        if id != rumor.id {
+            return Err(Error::AssertionFailed("Rumor id does not match".to_owned()));
+        }
+        Ok(rumor)
+    }
+}
+
+/// A gift wrap (kind 1059, NIP-59): a seal encrypted to its recipient and
+/// signed by a one-time key, so that relays see neither the author nor
+/// when it was written
+#[derive(Clone, Debug, PartialEq)]
+pub struct GiftWrap(pub Event);
+
+impl GiftWrap {
+    /// Seal `rumor`, which must be authored by `signer`, and wrap it for
+    /// `recipient` with NIP-44
+    pub fn new(
+        rumor: &Rumor,
+        signer: &PrivateKey,
+        recipient: &PublicKey,
+    ) -> Result<GiftWrap, Error> {
+        let seal = Seal::new(rumor, signer, recipient)?;
+        wrap(
+            &seal.0,
+            rumor.created_at,
+            recipient,
+            &PrivateKey::nip44_encrypt,
+            &mut ProviderRng,
+        )
+        .map(GiftWrap)
+    }
+
+    /// Take a kind 1059 event as a gift wrap
+    pub fn from_event(event: Event) -> Result<GiftWrap, Error> {
+        if event.kind != EventKind::GiftWrap {
+            return Err(Error::WrongEventKind);
+        }
+        Ok(GiftWrap(event))
+    }
+
+    /// Who it is for, from its 'p' tag
+    pub fn recipient(&self) -> Option<PublicKey> {
+        self.0.tags.iter().find_map(|tag| match tag {
+            Tag::Pubkey { pubkey, .. } => PublicKey::try_from_hex_string(pubkey.as_str()).ok(),
+            _ => None,
+        })
+    }
+
+    /// Verify and decrypt the wrap and its seal with the recipient's key,
+    /// giving the rumor. Its author is the seal's signer.
+    pub fn unwrap_rumor(&self, private_key: &PrivateKey) -> Result<Rumor, Error> {
+        self.0.verify(None)?;
+        let json = private_key.nip44_decrypt(&self.0.pubkey, &self.0.content)?;
+        Seal::from_event(serde_json::from_str(&json)?)?.open(private_key)
+    }
+}
+
+fn seal<F>(
+    rumor: &Rumor,
+    rumor_json: &str,
+    signer: &PrivateKey,
+    recipient: &PublicKey,
+    encrypt: &F,
+    rng: &mut impl CryptoRngCore,
+) -> Result<Event, Error>
+where
+    F: Fn(&PrivateKey, &PublicKey, &str) -> Result<String, Error>,
+{
+    Event::new(
+        PreEvent {
+            pubkey: signer.public_key(),
+            created_at: tweak(rumor.created_at, rng),
+            kind: EventKind::Seal,
+            tags: vec![],
+            content: encrypt(signer, recipient, rumor_json)?,
+            ots: None,
+        },
+        signer,
+    )
+}
+
+// Wrapped at up to two days before `created_at`, the rumor's time
+fn wrap<F>(
+    seal: &Event,
+    created_at: Unixtime,
+    recipient: &PublicKey,
+    encrypt: &F,
+    rng: &mut impl CryptoRngCore,
+) -> Result<Event, Error>
+where
+    F: Fn(&PrivateKey, &PublicKey, &str) -> Result<String, Error>,
+{
+    let wrapper = PrivateKey::generate_with_rng(rng);
+    Event::new(
+        PreEvent {
+            pubkey: wrapper.public_key(),
+            created_at: tweak(created_at, rng),
+            kind: EventKind::GiftWrap,
+            tags: vec![Tag::Pubkey {
+                pubkey: (*recipient).into(),
+                recommended_relay_url: None,
+                petname: None,
+            }],
+            content: encrypt(&wrapper, recipient, &serde_json::to_string(seal)?)?,
+            ots: None,
+        },
+        &wrapper,
+    )
+}
+
+fn tweak(created_at: Unixtime, rng: &mut impl CryptoRngCore) -> Unixtime {
+    Unixtime(created_at.0 - (rng.next_u32() % MAX_TIME_TWEAK) as i64)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    // Stands in for NIP-44, reversibly
+    fn encrypt(_key: &PrivateKey, recipient: &PublicKey, plaintext: &str) -> Result<String, Error> {
+        Ok(format!("{}:{}", recipient.as_hex_string(), plaintext))
+    }
+
+    fn decrypt(ciphertext: &str) -> &str {
+        ciphertext.split_once(':').unwrap().1
+    }
+
+    #[test]
+    fn test_giftwrap_for_recipients() {
+        let sender = PrivateKey::generate();
+        let alice = PrivateKey::generate().public_key();
+        let bob = PrivateKey::generate().public_key();
+        let rumor = Rumor::new(PreEvent {
+            pubkey: sender.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::PrivateDirectMessage,
+            tags: vec![],
+            content: "hello".to_owned(),
+            ots: None,
+        })
+        .unwrap();
+
+        let wraps = giftwrap_for_recipients_with(
+            &rumor,
+            &sender,
+            &[alice, bob, alice, sender.public_key()],
+            encrypt,
+            &mut ProviderRng,
+        )
+        .unwrap();
+        let recipients: Vec<PublicKey> = wraps
+            .iter()
+            .map(|w| match &w.tags[0] {
+                Tag::Pubkey { pubkey, .. } => PublicKey::try_from(pubkey.clone()).unwrap(),
+                _ => panic!("wrap has no p tag"),
+            })
+            .collect();
+        assert_eq!(recipients, vec![alice, bob, sender.public_key()]);
+
+        for wrap in wraps.iter() {
+            assert_eq!(wrap.kind, EventKind::GiftWrap);
+            assert!(wrap.verify(None).is_ok());
+            assert_ne!(wrap.pubkey, sender.public_key());
+            assert!(wrap.created_at <= rumor.created_at);
+
+            let seal: Event = serde_json::from_str(decrypt(&wrap.content)).unwrap();
+            assert_eq!(seal.kind, EventKind::Seal);
+            assert_eq!(seal.pubkey, sender.public_key());
+            assert!(seal.verify(None).is_ok());
+            let inner: Rumor = serde_json::from_str(decrypt(&seal.content)).unwrap();
+            assert_eq!(inner, rumor);
+        }
+
+        // Rumors must be from the signer
+        let other = PrivateKey::generate();
+        assert!(giftwrap_for_recipients(&rumor, &other, &[alice]).is_err());
+
+        // The real thing opens with each recipient's key
+        let carol = PrivateKey::generate();
+        let wraps = giftwrap_for_recipients(&rumor, &sender, &[carol.public_key()]).unwrap();
+        assert_eq!(wraps.len(), 2);
+        for (wrap, key) in wraps.into_iter().zip([&carol, &sender]) {
+            let wrap = GiftWrap::from_event(wrap).unwrap();
+            assert_eq!(wrap.unwrap_rumor(key).unwrap(), rumor);
+        }
+    }
+
+    #[test]
+    fn test_giftwrap_reproducible() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let sender = PrivateKey::generate_with_rng(&mut rng);
+        let recipient = PrivateKey::generate_with_rng(&mut rng).public_key();
+        let rumor = Rumor::new(PreEvent {
+            pubkey: sender.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::PrivateDirectMessage,
+            tags: vec![],
+            content: "hello".to_owned(),
+            ots: None,
+        })
+        .unwrap();
+
+        let wrap = |seed: u64| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            giftwrap_for_recipients_with(&rumor, &sender, &[recipient], encrypt, &mut rng).unwrap()
+        };
+        assert_eq!(wrap(1), wrap(1));
+        assert_ne!(wrap(1), wrap(2));
+    }
+
+    #[test]
+    fn test_gift_wrap_nip44() {
+        let sender = PrivateKey::generate();
+        let recipient = PrivateKey::generate();
+        let rumor = Rumor::new(PreEvent {
+            pubkey: sender.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::PrivateDirectMessage,
+            tags: vec![],
+            content: "hello".to_owned(),
+            ots: None,
+        })
+        .unwrap();
+
+        let wrap = GiftWrap::new(&rumor, &sender, &recipient.public_key()).unwrap();
+        assert_eq!(wrap.0.kind, EventKind::GiftWrap);
+        assert_ne!(wrap.0.pubkey, sender.public_key());
+        assert!(wrap.0.created_at <= rumor.created_at);
+        assert_eq!(wrap.recipient(), Some(recipient.public_key()));
+
+        let received = GiftWrap::from_event(wrap.0.clone()).unwrap();
+        assert_eq!(received.unwrap_rumor(&recipient).unwrap(), rumor);
+        assert!(received.unwrap_rumor(&sender).is_err());
+        assert!(matches!(
+            GiftWrap::from_event(Event::mock()),
+            Err(Error::WrongEventKind)
+        ));
+
+        // A seal signed by someone other than the rumor's author is refused
+        let forger = PrivateKey::generate();
+        let rumor_json = serde_json::to_string(&rumor).unwrap();
+        let forged = seal(
+            &rumor,
+            &rumor_json,
+            &forger,
+            &recipient.public_key(),
+            &PrivateKey::nip44_encrypt,
+            &mut ProviderRng,
+        )
+        .unwrap();
+        assert!(Seal(forged).open(&recipient).is_err());
+        assert!(Seal::new(&rumor, &forger, &recipient.public_key()).is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use super::{
+    Event, EventKind, EventReference, Filter, IdHex, PublicKey, PublicKeyHex, RelayUrl,
+    UncheckedUrl,
+};
+use crate::Error;
+use std::collections::HashMap;
+
+/// Fetches events from relays, for `HintResolver`
+pub trait RelayPool {
+    /// Query `relays` with `filter`, returning the events they sent before
+    /// EOSE or a timeout. Relays which cannot be reached should just return
+    /// nothing; an error stops the resolution.
+    fn fetch(&self, relays: &[RelayUrl], filter: &Filter) -> Result<Vec<Event>, Error>;
+}
+
+/// Which relays a referenced event was found on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HintStrategy {
+    /// The relays hinted in the reference
+    Hints,
+
+    /// The author's write relays (NIP-65)
+    AuthorOutbox,
+
+    /// The resolver's default relays
+    Defaults,
+}
+
+/// Fetches the event a reference points to, trying in turn the relays hinted
+/// in the reference, then the author's write relays (NIP-65, for addresses
+/// and for pointers that name their author), then some default relays. Each relay is asked
+/// once. Events that don't verify or don't match the reference are ignored.
+///
+/// Authors' write relays are taken from `set_outboxes()` when known, and
+/// otherwise from their relay list fetched from the default relays, which is
+/// then remembered.
+#[derive(Clone, Debug)]
+pub struct HintResolver {
+    defaults: Vec<RelayUrl>,
+    outboxes: HashMap<PublicKey, Vec<RelayUrl>>,
+}
+
+impl HintResolver {
+    /// Create a resolver that falls back to `defaults`
+    pub fn new(defaults: Vec<RelayUrl>) -> HintResolver {
+        HintResolver {
+            defaults,
+            outboxes: HashMap::new(),
+        }
+    }
+
+    /// Set the relays an author writes to, replacing any previously set
+    pub fn set_outboxes(&mut self, pubkey: PublicKey, relays: Vec<RelayUrl>) {
+        let _ = self.outboxes.insert(pubkey, relays);
+    }
+
+    /// Fetch the event `reference` points to, with the strategy that found
+    /// it, or None if no relay had it. For an address, the newest version
+    /// found by the first successful strategy is returned.
+    pub fn resolve<P: RelayPool>(
+        &mut self,
+        pool: &P,
+        reference: &EventReference,
+    ) -> Result<Option<(Event, HintStrategy)>, Error> {
+        let (filter, hints, author) = match reference {
+            EventReference::Pointer(pointer) => {
+                let mut filter = Filter::new();
+                filter.add_id(IdHex::from(pointer.id));
+                (filter, &pointer.relays, pointer.author)
+            }
+            EventReference::Addr(addr) => {
+                let mut filter = Filter::new();
+                filter.add_author(PublicKeyHex::from(addr.author));
+                filter.add_event_kind(addr.kind);
+                filter.d = vec![addr.d.clone()];
+                (filter, &addr.relays, Some(addr.author))
+            }
+        };
+
+        let mut tried: Vec<RelayUrl> = Vec::new();
+        let mut attempt = |relays: Vec<RelayUrl>| -> Result<Option<Event>, Error> {
+            let relays: Vec<RelayUrl> = relays.into_iter().filter(|r| !tried.contains(r)).collect();
+            if relays.is_empty() {
+                return Ok(None);
+            }
+            tried.extend(relays.iter().cloned());
+            let found = pool
+                .fetch(&relays, &filter)?
+                .into_iter()
+                .filter(|e| reference.matches(e) && e.verify(None).is_ok())
+                .max_by_key(|e| e.created_at);
+            Ok(found)
+        };
+
+        let hints: Vec<RelayUrl> = hints.iter().filter_map(relay_url).collect();
+        if let Some(event) = attempt(hints)? {
+            return Ok(Some((event, HintStrategy::Hints)));
+        }
+
+        if let Some(author) = author {
+            let outboxes = match self.outboxes.get(&author) {
+                Some(outboxes) => outboxes.clone(),
+                None => {
+                    let outboxes = fetch_outboxes(pool, &self.defaults, author)?;
+                    self.set_outboxes(author, outboxes.clone());
+                    outboxes
+                }
+            };
+            if let Some(event) = attempt(outboxes)? {
+                return Ok(Some((event, HintStrategy::AuthorOutbox)));
+            }
+        }
+
+        if let Some(event) = attempt(self.defaults.clone())? {
+            return Ok(Some((event, HintStrategy::Defaults)));
+        }
+        Ok(None)
+    }
+}
+
+fn relay_url(url: &UncheckedUrl) -> Option<RelayUrl> {
+    RelayUrl::try_from_unchecked_url(url).ok()
+}
+
+// The author's write relays, from the newest relay list on the default relays
+fn fetch_outboxes<P: RelayPool>(
+    pool: &P,
+    defaults: &[RelayUrl],
+    author: PublicKey,
+) -> Result<Vec<RelayUrl>, Error> {
+    if defaults.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut filter = Filter::new();
+    filter.add_author(PublicKeyHex::from(author));
+    filter.add_event_kind(EventKind::RelayList);
+    let relay_list = pool
+        .fetch(defaults, &filter)?
+        .into_iter()
+        .filter(|e| e.pubkey == author && e.kind == EventKind::RelayList && e.verify(None).is_ok())
+        .max_by_key(|e| e.created_at);
+    Ok(relay_list
+        .map(|e| super::relay_picker::write_relays(&e))
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EventAddr, EventPointer, PreEvent, PrivateKey, Tag, Unixtime};
+    use std::cell::RefCell;
+
+    // Relays holding events, recording which relays were asked
+    #[derive(Default)]
+    struct MockPool {
+        relays: HashMap<RelayUrl, Vec<Event>>,
+        asked: RefCell<Vec<Vec<RelayUrl>>>,
+    }
+
+    impl RelayPool for MockPool {
+        fn fetch(&self, relays: &[RelayUrl], filter: &Filter) -> Result<Vec<Event>, Error> {
+            self.asked.borrow_mut().push(relays.to_vec());
+            Ok(relays
+                .iter()
+                .filter_map(|r| self.relays.get(r))
+                .flatten()
+                .filter(|e| filter.event_matches(e))
+                .cloned()
+                .collect())
+        }
+    }
+
+    fn relay(s: &str) -> RelayUrl {
+        RelayUrl::try_from_str(s).unwrap()
+    }
+
+    fn event(key: &PrivateKey, kind: EventKind, tags: Vec<Tag>, created_at: i64) -> Event {
+        let pre = PreEvent {
+            pubkey: key.public_key(),
+            created_at: Unixtime(created_at),
+            kind,
+            tags,
+            content: "".to_owned(),
+            ots: None,
+        };
+        Event::new(pre, key).unwrap()
+    }
+
+    #[test]
+    fn test_hint_resolver() {
+        let key = PrivateKey::mock();
+        let hinted = relay("wss://hinted.example.com");
+        let outbox = relay("wss://outbox.example.com");
+        let default = relay("wss://default.example.com");
+
+        let note = event(&key, EventKind::TextNote, vec![], 1700000000);
+        let old = event(
+            &key,
+            EventKind::LongFormContent,
+            vec![Tag::Identifier("article".to_owned())],
+            1700000000,
+        );
+        let new = event(
+            &key,
+            EventKind::LongFormContent,
+            vec![Tag::Identifier("article".to_owned())],
+            1700000100,
+        );
+        let relay_list = event(
+            &key,
+            EventKind::RelayList,
+            vec![Tag::Reference {
+                url: outbox.to_unchecked_url(),
+                marker: Some("write".to_owned()),
+            }],
+            1700000000,
+        );
+
+        let mut pool = MockPool::default();
+        let _ = pool.relays.insert(hinted.clone(), vec![note.clone()]);
+        let _ = pool
+            .relays
+            .insert(outbox.clone(), vec![old.clone(), new.clone()]);
+        let _ = pool
+            .relays
+            .insert(default.clone(), vec![relay_list, old.clone()]);
+        let mut resolver = HintResolver::new(vec![default.clone()]);
+
+        // Found on the hinted relay
+        let pointer = EventPointer {
+            id: note.id,
+            relays: vec![hinted.to_unchecked_url()],
+            author: None,
+            kind: None,
+        };
+        let found = resolver.resolve(&pool, &pointer.into()).unwrap();
+        assert_eq!(found, Some((note.clone(), HintStrategy::Hints)));
+
+        // Not hinted anywhere useful, so the defaults are tried
+        let pointer = EventPointer {
+            id: old.id,
+            relays: vec![UncheckedUrl::from_str("not a url")],
+            author: None,
+            kind: None,
+        };
+        let found = resolver.resolve(&pool, &pointer.into()).unwrap();
+        assert_eq!(found, Some((old.clone(), HintStrategy::Defaults)));
+
+        // An address goes to the author's outbox, found through their relay
+        // list, and gets the newest version
+        pool.asked.borrow_mut().clear();
+        let addr = EventAddr {
+            d: "article".to_owned(),
+            relays: vec![hinted.to_unchecked_url()],
+            kind: EventKind::LongFormContent,
+            author: key.public_key(),
+        };
+        let found = resolver.resolve(&pool, &addr.clone().into()).unwrap();
+        assert_eq!(found, Some((new.clone(), HintStrategy::AuthorOutbox)));
+        assert_eq!(
+            *pool.asked.borrow(),
+            vec![vec![hinted.clone()], vec![default.clone()], vec![outbox]]
+        );
+
+        // The outbox is remembered, and each relay is asked once
+        pool.asked.borrow_mut().clear();
+        let missing = EventAddr {
+            d: "missing".to_owned(),
+            ..addr
+        };
+        assert_eq!(resolver.resolve(&pool, &missing.into()).unwrap(), None);
+        assert_eq!(pool.asked.borrow().len(), 3);
+
+        // A pointer that names its author also goes to the author's outbox
+        let pointer = EventPointer {
+            id: new.id,
+            relays: vec![],
+            author: Some(key.public_key()),
+            kind: None,
+        };
+        let found = resolver.resolve(&pool, &pointer.into()).unwrap();
+        assert_eq!(found, Some((new, HintStrategy::AuthorOutbox)));
+    }
+}
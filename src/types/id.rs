@@ -35,22 +35,28 @@ impl Id {
 
     /// Import from a bech32 encoded string ("note")
     pub fn try_from_bech32_string(s: &str) -> Result<Id, Error> {
-        let data = bech32::decode(s)?;
-        if data.0 != "note" {
-            Err(Error::WrongBech32("note".to_string(), data.0))
+        let data = crate::error::bech32_decode_as(s, "note", Some(63))?;
+        let decoded = Vec::<u8>::from_base32(&data)?;
+        if decoded.len() != 32 {
+            Err(Error::InvalidId)
         } else {
-            let decoded = Vec::<u8>::from_base32(&data.1)?;
-            if decoded.len() != 32 {
-                Err(Error::InvalidId)
-            } else {
-                match <[u8; 32]>::try_from(decoded) {
-                    Ok(array) => Ok(Id(array)),
-                    _ => Err(Error::InvalidId),
-                }
+            match <[u8; 32]>::try_from(decoded) {
+                Ok(array) => Ok(Id(array)),
+                _ => Err(Error::InvalidId),
             }
         }
     }
 
+    /// Does this id start with the hex `prefix`? The prefix must be whole bytes
+    /// (an even number of hex digits) and no longer than the id. Case is ignored.
+    pub fn matches_hex_prefix(&self, prefix: &str) -> Result<bool, Error> {
+        if prefix.len() > 64 || prefix.len() % 2 != 0 {
+            return Err(Error::InvalidIdPrefix);
+        }
+        let bytes = hex::decode(prefix).map_err(|_| Error::InvalidIdPrefix)?;
+        Ok(self.0.starts_with(&bytes))
+    }
+
     // Mock data for testing
     #[allow(dead_code)]
     pub(crate) fn mock() -> Id {
@@ -270,10 +276,48 @@ mod test {
     test_serde! {IdHex, test_id_hex_serde}
     test_serde! {IdHexPrefix, test_id_hex_prefix_serde}
 
+    #[test]
+    fn test_id_matches_hex_prefix() {
+        let id = Id::mock();
+        assert!(id.matches_hex_prefix("").unwrap());
+        assert!(id.matches_hex_prefix("5df6").unwrap());
+        assert!(id.matches_hex_prefix("5DF64B").unwrap());
+        assert!(id.matches_hex_prefix(&id.as_hex_string()).unwrap());
+        assert!(!id.matches_hex_prefix("5df7").unwrap());
+        assert!(id.matches_hex_prefix("5df").is_err());
+        assert!(id.matches_hex_prefix("5dfg").is_err());
+        assert!(id
+            .matches_hex_prefix(&format!("{}00", id.as_hex_string()))
+            .is_err());
+    }
+
     #[test]
     fn test_id_bech32() {
         let bech32 = Id::mock().as_bech32_string();
         println!("{bech32}");
         assert_eq!(Id::mock(), Id::try_from_bech32_string(&bech32).unwrap());
     }
+
+    #[test]
+    fn test_id_bech32_vectors() {
+        let note = "note1fntxtkcy9pjwucqwa9mddn7v03wwwsu9j330jj350nvhpky2tuaspk6nqc";
+        let id = Id::try_from_hex_string(
+            "4cd665db042864ee600ee976d6cfcc7c5ce743859462f94a347cd970d88a5f3b",
+        )
+        .unwrap();
+        assert_eq!(id.as_bech32_string(), note);
+        assert_eq!(Id::try_from_bech32_string(note).unwrap(), id);
+        assert_eq!(
+            Id::try_from_bech32_string(&note.to_uppercase()).unwrap(),
+            id
+        );
+
+        // An npub is not a note, even though it encodes 32 bytes too
+        let npub = "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6";
+        assert!(matches!(
+            Id::try_from_bech32_string(npub),
+            Err(Error::WrongBech32(_, _))
+        ));
+        assert!(Id::try_from_bech32_string(note.get(..60).unwrap()).is_err());
+    }
 }
@@ -0,0 +1,194 @@
+use super::{Event, Id, Unixtime};
+use crate::Error;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// A small summary of a set of event ids, for two stores (e.g. a user's
+/// devices) to cheaply check whether they hold the same events before running
+/// a full reconciliation such as negentropy or re-querying by time window.
+///
+/// It holds the number of ids, their XOR and their sum modulo 2^256. Equal
+/// sets always have equal digests, and different sets practically never do.
+/// Ids can be added and removed one at a time, so a store can keep its digest
+/// up to date as events come and go.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct IdSetDigest {
+    count: u64,
+    xor: [u8; 32],
+    sum: [u8; 32],
+}
+
+impl IdSetDigest {
+    /// The digest of the empty set
+    pub fn new() -> IdSetDigest {
+        Default::default()
+    }
+
+    /// Add an id. Adding an id already in the set corrupts the digest, so
+    /// the caller must only add new ids.
+    pub fn add(&mut self, id: Id) {
+        self.count = self.count.wrapping_add(1);
+        for (x, b) in self.xor.iter_mut().zip(id.0.iter()) {
+            *x ^= b;
+        }
+        let mut carry = 0u16;
+        for (s, b) in self.sum.iter_mut().zip(id.0.iter()) {
+            let total = *s as u16 + *b as u16 + carry;
+            *s = total as u8;
+            carry = total >> 8;
+        }
+    }
+
+    /// Remove an id that is in the set
+    pub fn remove(&mut self, id: Id) {
+        self.count = self.count.wrapping_sub(1);
+        for (x, b) in self.xor.iter_mut().zip(id.0.iter()) {
+            *x ^= b;
+        }
+        let mut borrow = 0i16;
+        for (s, b) in self.sum.iter_mut().zip(id.0.iter()) {
+            let mut total = *s as i16 - *b as i16 - borrow;
+            borrow = 0;
+            if total < 0 {
+                total += 256;
+                borrow = 1;
+            }
+            *s = total as u8;
+        }
+    }
+
+    /// Combine with the digest of a disjoint set, giving the digest of their
+    /// union
+    pub fn merge(&mut self, other: &IdSetDigest) {
+        self.count = self.count.wrapping_add(other.count);
+        for (x, b) in self.xor.iter_mut().zip(other.xor.iter()) {
+            *x ^= b;
+        }
+        let mut carry = 0u16;
+        for (s, b) in self.sum.iter_mut().zip(other.sum.iter()) {
+            let total = *s as u16 + *b as u16 + carry;
+            *s = total as u8;
+            carry = total >> 8;
+        }
+    }
+
+    /// The number of ids in the set
+    pub fn len(&self) -> u64 {
+        self.count
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Digest events by `created_at` in windows of `window` length, keyed by
+    /// the start of each window. Comparing two stores' windows shows which
+    /// time ranges need re-querying.
+    pub fn by_window<'a, I>(events: I, window: Duration) -> BTreeMap<Unixtime, IdSetDigest>
+    where
+        I: IntoIterator<Item = &'a Event>,
+    {
+        let size = (window.as_secs() as i64).max(1);
+        let mut windows: BTreeMap<Unixtime, IdSetDigest> = BTreeMap::new();
+        for event in events {
+            let start = Unixtime(event.created_at.0.div_euclid(size) * size);
+            windows.entry(start).or_default().add(event.id);
+        }
+        windows
+    }
+
+    /// Render as 144 hex characters, to send to another device
+    pub fn as_hex_string(&self) -> String {
+        let mut bytes: Vec<u8> = Vec::with_capacity(72);
+        bytes.extend(self.count.to_be_bytes());
+        bytes.extend(self.xor);
+        bytes.extend(self.sum);
+        hex::encode(bytes)
+    }
+
+    /// Parse from the form given by `as_hex_string()`
+    pub fn try_from_hex_string(s: &str) -> Result<IdSetDigest, Error> {
+        let bytes: Vec<u8> = hex::decode(s)?;
+        if bytes.len() != 72 {
+            return Err(Error::WrongLengthHexString);
+        }
+        let mut digest = IdSetDigest::new();
+        let mut count = [0; 8];
+        count.copy_from_slice(&bytes[..8]);
+        digest.count = u64::from_be_bytes(count);
+        digest.xor.copy_from_slice(&bytes[8..40]);
+        digest.sum.copy_from_slice(&bytes[40..]);
+        Ok(digest)
+    }
+}
+
+impl FromIterator<Id> for IdSetDigest {
+    fn from_iter<I: IntoIterator<Item = Id>>(iter: I) -> IdSetDigest {
+        let mut digest = IdSetDigest::new();
+        for id in iter {
+            digest.add(id);
+        }
+        digest
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn id(n: u8) -> Id {
+        let mut bytes = [0xff; 32];
+        bytes[0] = n;
+        Id(bytes)
+    }
+
+    #[test]
+    fn test_id_set_digest() {
+        let a: IdSetDigest = [id(1), id(2), id(3)].into_iter().collect();
+        let b: IdSetDigest = [id(3), id(1), id(2)].into_iter().collect();
+        assert_eq!(a, b);
+        assert_eq!(a.len(), 3);
+
+        // Duplicates cancel out of the XOR, but not out of the count and sum
+        let c: IdSetDigest = [id(1), id(2), id(3), id(4), id(4)].into_iter().collect();
+        assert_ne!(a, c);
+        let d: IdSetDigest = [id(1), id(2), id(4)].into_iter().collect();
+        assert_ne!(a, d);
+
+        let mut e = a;
+        e.add(id(9));
+        assert_ne!(a, e);
+        e.remove(id(9));
+        assert_eq!(a, e);
+
+        let mut f: IdSetDigest = [id(1)].into_iter().collect();
+        f.merge(&[id(2), id(3)].into_iter().collect());
+        assert_eq!(a, f);
+
+        let mut g = IdSetDigest::new();
+        g.remove(id(5));
+        g.add(id(5));
+        assert_eq!(g, IdSetDigest::new());
+
+        let hex = a.as_hex_string();
+        assert_eq!(hex.len(), 144);
+        assert_eq!(IdSetDigest::try_from_hex_string(&hex).unwrap(), a);
+        assert!(IdSetDigest::try_from_hex_string("00").is_err());
+    }
+
+    #[test]
+    fn test_id_set_digest_by_window() {
+        let mut early = Event::mock();
+        early.created_at = Unixtime(1000);
+        early.id = id(1);
+        let mut late = Event::mock();
+        late.created_at = Unixtime(4000);
+        late.id = id(2);
+
+        let windows = IdSetDigest::by_window([&early, &late], Duration::from_secs(3600));
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[&Unixtime(0)], IdSetDigest::from_iter([id(1)]));
+        assert_eq!(windows[&Unixtime(3600)], IdSetDigest::from_iter([id(2)]));
+    }
+}
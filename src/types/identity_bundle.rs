@@ -0,0 +1,277 @@
+use super::relay_picker::write_relays;
+use super::{
+    ContactList, Event, EventKind, Filter, Metadata, PreEvent, PublicKey, PublicKeyHex, RelayPool,
+    RelayUrl, Signer, SimpleRelayUsage, Tag, Unixtime,
+};
+use crate::Error;
+
+/// A user's profile (kind 0), follows (kind 3) and relay list (kind 10002),
+/// which together are what a client needs to start following them.
+///
+/// `sign()` makes all three at once, with the same time, for publishing
+/// together; `fetch()` gathers the newest valid ones from relays.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdentityBundle {
+    /// The metadata event, if found
+    pub metadata: Option<Event>,
+
+    /// The contact list event, if found
+    pub contact_list: Option<Event>,
+
+    /// The relay list event, if found
+    pub relay_list: Option<Event>,
+}
+
+impl IdentityBundle {
+    /// Sign all three events, dated `now`. Relays used for neither reading
+    /// nor writing are left out of the relay list.
+    pub fn sign<S: Signer>(
+        signer: &S,
+        metadata: &Metadata,
+        contacts: &ContactList,
+        relays: &[(RelayUrl, SimpleRelayUsage)],
+        now: Unixtime,
+    ) -> Result<IdentityBundle, Error> {
+        let pre = |kind: EventKind, tags: Vec<Tag>, content: String| PreEvent {
+            pubkey: signer.public_key(),
+            created_at: now,
+            kind,
+            tags,
+            content,
+            ots: None,
+        };
+
+        let relay_tags: Vec<Tag> = relays
+            .iter()
+            .filter_map(|(relay, usage)| {
+                let marker = match (usage.read, usage.write) {
+                    (true, true) => None,
+                    (true, false) => Some("read".to_owned()),
+                    (false, true) => Some("write".to_owned()),
+                    (false, false) => return None,
+                };
+                Some(Tag::Reference {
+                    url: relay.to_unchecked_url(),
+                    marker,
+                })
+            })
+            .collect();
+
+        Ok(IdentityBundle {
+            metadata: Some(signer.sign_event(pre(
+                EventKind::Metadata,
+                vec![],
+                serde_json::to_string(metadata)?,
+            ))?),
+            contact_list: Some(signer.sign_event(pre(
+                EventKind::ContactList,
+                contacts.to_tags(),
+                "".to_owned(),
+            ))?),
+            relay_list: Some(signer.sign_event(pre(
+                EventKind::RelayList,
+                relay_tags,
+                "".to_owned(),
+            ))?),
+        })
+    }
+
+    /// Gather the newest valid events of `pubkey` from `relays`, then from
+    /// the write relays of the relay list found there, which may have newer
+    /// versions. Events that don't verify or are by someone else are ignored.
+    pub fn fetch<P: RelayPool>(
+        pubkey: PublicKey,
+        pool: &P,
+        relays: &[RelayUrl],
+    ) -> Result<IdentityBundle, Error> {
+        let mut filter = Filter::new();
+        filter.add_author(PublicKeyHex::from(pubkey));
+        filter.add_event_kind(EventKind::Metadata);
+        filter.add_event_kind(EventKind::ContactList);
+        filter.add_event_kind(EventKind::RelayList);
+
+        let mut bundle = IdentityBundle::default();
+        for event in pool.fetch(relays, &filter)? {
+            bundle.offer(pubkey, event);
+        }
+
+        let outboxes: Vec<RelayUrl> = bundle
+            .relay_list
+            .as_ref()
+            .map(write_relays)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|relay| !relays.contains(relay))
+            .collect();
+        if !outboxes.is_empty() {
+            for event in pool.fetch(&outboxes, &filter)? {
+                bundle.offer(pubkey, event);
+            }
+        }
+
+        Ok(bundle)
+    }
+
+    // Keep `event` if it is a valid and newer part of the bundle
+    fn offer(&mut self, pubkey: PublicKey, event: Event) {
+        if event.pubkey != pubkey || event.verify(None).is_err() {
+            return;
+        }
+        let slot = match event.kind {
+            EventKind::Metadata => &mut self.metadata,
+            EventKind::ContactList => &mut self.contact_list,
+            EventKind::RelayList => &mut self.relay_list,
+            _ => return,
+        };
+        if slot.as_ref().map(|e| event.replaces(e)).unwrap_or(true) {
+            *slot = Some(event);
+        }
+    }
+
+    /// Check that the events present are valid, of the right kinds, and all
+    /// by `pubkey`
+    pub fn verify(&self, pubkey: PublicKey) -> Result<(), Error> {
+        for (event, kind) in [
+            (&self.metadata, EventKind::Metadata),
+            (&self.contact_list, EventKind::ContactList),
+            (&self.relay_list, EventKind::RelayList),
+        ] {
+            if let Some(event) = event {
+                if event.kind != kind {
+                    return Err(Error::WrongEventKind);
+                }
+                if event.pubkey != pubkey {
+                    return Err(Error::AssertionFailed(
+                        "Identity bundle event is by someone else".to_owned(),
+                    ));
+                }
+                event.verify(None)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Are all three events present?
+    pub fn is_complete(&self) -> bool {
+        self.metadata.is_some() && self.contact_list.is_some() && self.relay_list.is_some()
+    }
+
+    /// The events present, to publish
+    pub fn events(&self) -> Vec<&Event> {
+        [&self.metadata, &self.contact_list, &self.relay_list]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// The profile, if present and valid
+    pub fn profile(&self) -> Option<Metadata> {
+        serde_json::from_str(&self.metadata.as_ref()?.content).ok()
+    }
+
+    /// The follows, if present
+    pub fn contacts(&self) -> Option<ContactList> {
+        ContactList::from_event(self.contact_list.as_ref()?).ok()
+    }
+
+    /// The relays the user writes to, empty if there is no relay list
+    pub fn write_relays(&self) -> Vec<RelayUrl> {
+        self.relay_list
+            .as_ref()
+            .map(write_relays)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Contact, PrivateKey};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    struct MockPool {
+        events: HashMap<RelayUrl, Vec<Event>>,
+        asked: RefCell<Vec<RelayUrl>>,
+    }
+
+    impl RelayPool for MockPool {
+        fn fetch(&self, relays: &[RelayUrl], filter: &Filter) -> Result<Vec<Event>, Error> {
+            self.asked.borrow_mut().extend(relays.iter().cloned());
+            Ok(relays
+                .iter()
+                .filter_map(|r| self.events.get(r))
+                .flatten()
+                .filter(|e| filter.event_matches(e))
+                .cloned()
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_identity_bundle() {
+        let key = PrivateKey::generate();
+        let pubkey = key.public_key();
+        let bootstrap = RelayUrl::try_from_str("wss://bootstrap.example.com").unwrap();
+        let outbox = RelayUrl::try_from_str("wss://outbox.example.com").unwrap();
+
+        let mut metadata = Metadata::new();
+        metadata.name = Some("alice".to_owned());
+        let contacts = ContactList {
+            contacts: vec![Contact {
+                pubkey: PrivateKey::generate().public_key().into(),
+                relay_url: None,
+                petname: None,
+            }],
+        };
+        let write = SimpleRelayUsage {
+            write: true,
+            read: false,
+        };
+        let old = IdentityBundle::sign(
+            &key,
+            &metadata,
+            &ContactList::default(),
+            &[(outbox.clone(), write)],
+            Unixtime(1700000000),
+        )
+        .unwrap();
+        assert!(old.is_complete());
+        assert!(old.verify(pubkey).is_ok());
+        assert!(old.verify(PrivateKey::generate().public_key()).is_err());
+        assert_eq!(old.write_relays(), vec![outbox.clone()]);
+
+        metadata.name = Some("alice2".to_owned());
+        let new = IdentityBundle::sign(
+            &key,
+            &metadata,
+            &contacts,
+            &[(outbox.clone(), write)],
+            Unixtime(1700000100),
+        )
+        .unwrap();
+
+        // The bootstrap relay has the old bundle and the newer metadata, the
+        // outbox has the newer contact list
+        let mut pool = MockPool::default();
+        let mut on_bootstrap: Vec<Event> = old.events().into_iter().cloned().collect();
+        on_bootstrap.push(new.metadata.clone().unwrap());
+        let mut forged = new.contact_list.clone().unwrap();
+        forged.pubkey = PrivateKey::generate().public_key();
+        on_bootstrap.push(forged);
+        let _ = pool.events.insert(bootstrap.clone(), on_bootstrap);
+        let _ = pool
+            .events
+            .insert(outbox.clone(), vec![new.contact_list.clone().unwrap()]);
+
+        let fetched = IdentityBundle::fetch(pubkey, &pool, &[bootstrap.clone()]).unwrap();
+        assert_eq!(*pool.asked.borrow(), vec![bootstrap, outbox]);
+        assert_eq!(fetched.metadata, new.metadata);
+        assert_eq!(fetched.contact_list, new.contact_list);
+        assert_eq!(fetched.relay_list, old.relay_list);
+        assert_eq!(fetched.profile().unwrap().name.as_deref(), Some("alice2"));
+        assert_eq!(fetched.contacts(), Some(contacts));
+        assert!(fetched.verify(pubkey).is_ok());
+    }
+}
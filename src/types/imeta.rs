@@ -0,0 +1,180 @@
+use super::{ParsedTag, UncheckedUrl};
+
+/// Inline media metadata, the 'imeta' tag (NIP-92).
+///
+/// Each entry in the tag is a space-delimited key/value pair, such as
+/// "m video/mp4". Entries we do not model are kept in `other`, in order:
+/// unknown keys, repeats of the single-valued keys, and durations or
+/// bitrates that are not numbers. So that `to_strings()` reads back the
+/// same, `other` must not hold an 'image' or 'fallback' entry, nor a
+/// readable value for a single-valued key whose field is None.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Imeta {
+    /// The URL of the media ('url')
+    pub url: UncheckedUrl,
+
+    /// The MIME type of the media ('m')
+    pub mime_type: Option<String>,
+
+    /// The dimensions of the media in "<width>x<height>" form ('dim')
+    pub dimensions: Option<String>,
+
+    /// The hex SHA-256 hash of the media ('x')
+    pub hash: Option<String>,
+
+    /// A blurhash placeholder ('blurhash')
+    pub blurhash: Option<String>,
+
+    /// Alternative description for accessibility ('alt')
+    pub alt: Option<String>,
+
+    /// Duration of audio or video media in seconds ('duration')
+    pub duration: Option<f64>,
+
+    /// Bitrate of audio or video media in bits per second ('bitrate')
+    pub bitrate: Option<u64>,
+
+    /// Preview or thumbnail images ('image')
+    pub images: Vec<UncheckedUrl>,
+
+    /// Fallback URLs for the same media ('fallback')
+    pub fallbacks: Vec<UncheckedUrl>,
+
+    /// Any other key/value pairs, in order
+    pub other: Vec<(String, String)>,
+}
+
+impl Imeta {
+    /// Create a new Imeta for a URL
+    pub fn new(url: UncheckedUrl) -> Imeta {
+        Imeta {
+            url,
+            ..Default::default()
+        }
+    }
+
+    // Mock data for testing
+    #[allow(dead_code)]
+    pub(crate) fn mock() -> Imeta {
+        Imeta {
+            url: UncheckedUrl::from_str("https://example.com/video.mp4"),
+            mime_type: Some("video/mp4".to_owned()),
+            dimensions: Some("1920x1080".to_owned()),
+            duration: Some(29.5),
+            images: vec![UncheckedUrl::from_str("https://example.com/thumb.jpg")],
+            ..Default::default()
+        }
+    }
+}
+
+impl ParsedTag for Imeta {
+    const TAGNAME: &'static str = "imeta";
+
+    fn from_strings(strings: &[String]) -> Option<Imeta> {
+        if strings.first().map(|s| s.as_str()) != Some(Self::TAGNAME) {
+            return None;
+        }
+
+        let mut url: Option<UncheckedUrl> = None;
+        let mut imeta = Imeta::default();
+        for entry in strings[1..].iter() {
+            let (key, value) = entry.split_once(' ')?;
+            let value = value.to_owned();
+            match key {
+                "url" if url.is_none() => url = Some(UncheckedUrl(value)),
+                "m" if imeta.mime_type.is_none() => imeta.mime_type = Some(value),
+                "dim" if imeta.dimensions.is_none() => imeta.dimensions = Some(value),
+                "x" if imeta.hash.is_none() => imeta.hash = Some(value),
+                "blurhash" if imeta.blurhash.is_none() => imeta.blurhash = Some(value),
+                "alt" if imeta.alt.is_none() => imeta.alt = Some(value),
+                "duration" if imeta.duration.is_none() && parses_finite(&value) => {
+                    imeta.duration = value.parse().ok()
+                }
+                "bitrate" if imeta.bitrate.is_none() && value.parse::<u64>().is_ok() => {
+                    imeta.bitrate = value.parse().ok()
+                }
+                "image" => imeta.images.push(UncheckedUrl(value)),
+                "fallback" => imeta.fallbacks.push(UncheckedUrl(value)),
+                _ => imeta.other.push((key.to_owned(), value)),
+            }
+        }
+
+        imeta.url = url?;
+        Some(imeta)
+    }
+
+    fn to_strings(&self) -> Vec<String> {
+        let mut output = vec![Self::TAGNAME.to_owned(), format!("url {}", self.url)];
+        if let Some(m) = &self.mime_type {
+            output.push(format!("m {m}"));
+        }
+        if let Some(dim) = &self.dimensions {
+            output.push(format!("dim {dim}"));
+        }
+        if let Some(x) = &self.hash {
+            output.push(format!("x {x}"));
+        }
+        if let Some(blurhash) = &self.blurhash {
+            output.push(format!("blurhash {blurhash}"));
+        }
+        if let Some(alt) = &self.alt {
+            output.push(format!("alt {alt}"));
+        }
+        if let Some(duration) = self.duration {
+            output.push(format!("duration {duration}"));
+        }
+        if let Some(bitrate) = self.bitrate {
+            output.push(format!("bitrate {bitrate}"));
+        }
+        for image in self.images.iter() {
+            output.push(format!("image {image}"));
+        }
+        for fallback in self.fallbacks.iter() {
+            output.push(format!("fallback {fallback}"));
+        }
+        for (k, v) in self.other.iter() {
+            output.push(format!("{k} {v}"));
+        }
+        output
+    }
+}
+
+// Whether a duration reads back as the same number. NaN never compares
+// equal, and is kept in `other` instead.
+fn parses_finite(value: &str) -> bool {
+    value.parse::<f64>().map(|d| d.is_finite()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Tag;
+
+    #[test]
+    fn test_imeta_round_trip() {
+        let imeta = Imeta::mock();
+        let tag = imeta.to_tag();
+        assert_eq!(Imeta::from_tag(&tag).unwrap(), imeta);
+
+        let wire = r#"["imeta","url https://example.com/a.jpg","m image/jpeg","dim 3024x4032","service nip96"]"#;
+        let tag: Tag = serde_json::from_str(wire).unwrap();
+        let imeta = Imeta::from_tag(&tag).unwrap();
+        assert_eq!(imeta.dimensions.as_deref(), Some("3024x4032"));
+        assert_eq!(
+            imeta.other,
+            vec![("service".to_owned(), "nip96".to_owned())]
+        );
+        assert_eq!(serde_json::to_string(&imeta.to_tag()).unwrap(), wire);
+
+        // Repeats and unreadable numbers are kept, and read back the same
+        let wire = r#"["imeta","url https://example.com/a.jpg","m image/jpeg","duration NaN","m image/png","bitrate fast","service nip96"]"#;
+        let tag: Tag = serde_json::from_str(wire).unwrap();
+        let imeta = Imeta::from_tag(&tag).unwrap();
+        assert_eq!(imeta.duration, None);
+        assert_eq!(imeta.other.len(), 4);
+        assert_eq!(Imeta::from_tag(&imeta.to_tag()).unwrap(), imeta);
+
+        let no_url: Tag = serde_json::from_str(r#"["imeta","m image/jpeg"]"#).unwrap();
+        assert!(Imeta::from_tag(&no_url).is_none());
+    }
+}
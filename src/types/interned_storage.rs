@@ -0,0 +1,244 @@
+use super::{Event, EventKind, EventStorage, Filter, Id, PublicKey, Signature, Tag, Unixtime};
+use crate::Error;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// Shares repeated strings, so that each distinct value is held once
+#[derive(Clone, Debug, Default)]
+pub struct TagInterner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl TagInterner {
+    /// An empty interner
+    pub fn new() -> TagInterner {
+        Default::default()
+    }
+
+    /// The shared copy of `s`, adding it if new
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(shared) = self.strings.get(s) {
+            return shared.clone();
+        }
+        let shared: Arc<str> = Arc::from(s);
+        let _ = self.strings.insert(shared.clone());
+        shared
+    }
+
+    /// How many distinct strings are held
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether no strings are held
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Forget the strings nothing else uses any more
+    pub fn purge(&mut self) {
+        self.strings.retain(|s| Arc::strong_count(s) > 1);
+    }
+}
+
+// An event with its tag values interned
+#[derive(Debug)]
+struct CompactEvent {
+    id: Id,
+    pubkey: PublicKey,
+    created_at: Unixtime,
+    kind: EventKind,
+    tags: Box<[Box<[Arc<str>]>]>,
+    content: Box<str>,
+    sig: Signature,
+}
+
+impl CompactEvent {
+    fn new(event: &Event, interner: &mut TagInterner) -> CompactEvent {
+        let tags = event
+            .tags
+            .iter()
+            .map(|tag| {
+                tag.to_strings()
+                    .iter()
+                    .map(|s| interner.intern(s))
+                    .collect()
+            })
+            .collect();
+        CompactEvent {
+            id: event.id,
+            pubkey: event.pubkey,
+            created_at: event.created_at,
+            kind: event.kind,
+            tags,
+            content: event.content.as_str().into(),
+            sig: event.sig,
+        }
+    }
+
+    fn to_event(&self) -> Event {
+        Event {
+            id: self.id,
+            pubkey: self.pubkey,
+            created_at: self.created_at,
+            kind: self.kind,
+            tags: self
+                .tags
+                .iter()
+                .map(|strings| Tag::from_strings(strings.iter().map(|s| s.to_string()).collect()))
+                .collect(),
+            content: self.content.to_string(),
+            ots: None,
+            sig: self.sig,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    interner: TagInterner,
+    events: HashMap<Id, CompactEvent>,
+}
+
+/// An in-memory `EventStorage` that holds each distinct tag value once.
+///
+/// Pubkeys, relay URLs and hashtags repeat across thousands of events, so
+/// this takes much less memory than holding the events as they are, at the
+/// cost of rebuilding each event that is read or matched by a query. `ots`
+/// is not kept. Requires the `intern` feature.
+#[derive(Debug, Default)]
+pub struct InternedStorage {
+    inner: Mutex<Inner>,
+}
+
+impl InternedStorage {
+    /// An empty storage
+    pub fn new() -> InternedStorage {
+        Default::default()
+    }
+
+    /// How many events are stored
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().events.len()
+    }
+
+    /// Whether no events are stored
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// How many distinct tag values are held
+    pub fn interned_values(&self) -> usize {
+        self.inner.lock().unwrap().interner.len()
+    }
+
+    /// Forget the tag values no stored event uses any more, as after
+    /// deleting many events
+    pub fn purge(&self) {
+        self.inner.lock().unwrap().interner.purge();
+    }
+}
+
+impl EventStorage for InternedStorage {
+    fn insert(&self, event: &Event) -> Result<(), Error> {
+        let mut inner = self.inner.lock().unwrap();
+        let compact = CompactEvent::new(event, &mut inner.interner);
+        let _ = inner.events.insert(event.id, compact);
+        Ok(())
+    }
+
+    fn get(&self, id: Id) -> Result<Option<Event>, Error> {
+        Ok(self
+            .inner
+            .lock()
+            .unwrap()
+            .events
+            .get(&id)
+            .map(|e| e.to_event()))
+    }
+
+    fn query(&self, filter: &Filter) -> Result<Vec<Event>, Error> {
+        let mut events: Vec<Event> = self
+            .inner
+            .lock()
+            .unwrap()
+            .events
+            .values()
+            .filter(|e| filter.kinds.is_empty() || filter.kinds.contains(&e.kind))
+            .map(|e| e.to_event())
+            .filter(|e| filter.event_matches(e))
+            .collect();
+        events.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        if let Some(limit) = filter.limit {
+            events.truncate(limit);
+        }
+        Ok(events)
+    }
+
+    fn delete(&self, id: Id) -> Result<bool, Error> {
+        Ok(self.inner.lock().unwrap().events.remove(&id).is_some())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PreEvent, PrivateKey, PublicKeyHex, UncheckedUrl};
+
+    #[test]
+    fn test_interned_storage() {
+        let storage = InternedStorage::new();
+        let private_key = PrivateKey::generate();
+        let friend = PrivateKey::generate().public_key();
+        let mut ids: Vec<Id> = Vec::new();
+        for created_at in [300, 100, 200] {
+            let pre = PreEvent {
+                pubkey: private_key.public_key(),
+                created_at: Unixtime(created_at),
+                kind: EventKind::TextNote,
+                tags: vec![
+                    Tag::Pubkey {
+                        pubkey: friend.into(),
+                        recommended_relay_url: Some(UncheckedUrl::from_str(
+                            "wss://relay.example.com",
+                        )),
+                        petname: None,
+                    },
+                    Tag::Hashtag("nostr".to_owned()),
+                ],
+                content: format!("note at {created_at}"),
+                ots: None,
+            };
+            let event = Event::new(pre, &private_key).unwrap();
+            assert!(storage.put(&event).unwrap());
+            ids.push(event.id);
+        }
+
+        // "p", the pubkey, the relay, "t" and "nostr"
+        assert_eq!(storage.interned_values(), 5);
+        assert_eq!(storage.len(), 3);
+
+        let event = storage.get(ids[0]).unwrap().unwrap();
+        assert!(event.verify(None).is_ok());
+        assert_eq!(event.content, "note at 300");
+
+        let mut filter = Filter::new();
+        filter.add_author(PublicKeyHex::from(private_key.public_key()));
+        filter.add_event_kind(EventKind::TextNote);
+        let times: Vec<i64> = storage
+            .query(&filter)
+            .unwrap()
+            .iter()
+            .map(|e| e.created_at.0)
+            .collect();
+        assert_eq!(times, vec![300, 200, 100]);
+
+        for id in ids {
+            assert!(storage.delete(id).unwrap());
+        }
+        assert!(storage.is_empty());
+        assert_eq!(storage.interned_values(), 5);
+        storage.purge();
+        assert_eq!(storage.interned_values(), 0);
+    }
+}
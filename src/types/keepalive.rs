@@ -0,0 +1,219 @@
+use super::{RelayMessage, SubscriptionId, Unixtime};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Timing for `Keepalive`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    /// Ping a relay after this long without hearing from it
+    pub ping_interval: Duration,
+
+    /// Consider the connection dead if a ping is not answered within this
+    pub pong_timeout: Duration,
+
+    /// Report a subscription as idle after this long without events or EOSE,
+    /// if set
+    pub idle_subscription: Option<Duration>,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> KeepaliveConfig {
+        KeepaliveConfig {
+            ping_interval: Duration::from_secs(30),
+            pong_timeout: Duration::from_secs(10),
+            idle_subscription: Some(Duration::from_secs(300)),
+        }
+    }
+}
+
+/// Something the connection's owner should act on, from `Keepalive::poll()`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionHealth {
+    /// Nothing has been heard for the ping interval; send a websocket ping
+    /// and call `record_ping_sent()`
+    PingDue,
+
+    /// A ping went unanswered, so the connection is probably half-open.
+    /// Drop it and reconnect.
+    Unresponsive,
+
+    /// A subscription has been silent for the idle timeout. It may be fine
+    /// (a quiet filter), or the relay may have silently dropped it; resending
+    /// the REQ is cheap insurance.
+    IdleSubscription(SubscriptionId),
+}
+
+/// Keeps track of whether a relay connection is alive, so that a websocket
+/// which has silently gone half-open is noticed instead of leaving a feed
+/// stalled.
+///
+/// This does no I/O. Record what the connection sends and receives, and call
+/// `poll()` periodically (`next_deadline()` says when it next needs calling).
+/// Each condition is reported once, until activity resets it.
+#[derive(Clone, Debug)]
+pub struct Keepalive {
+    config: KeepaliveConfig,
+    last_received: Unixtime,
+    ping_sent: Option<Unixtime>,
+    unresponsive_reported: bool,
+    subscriptions: HashMap<SubscriptionId, (Unixtime, bool)>,
+}
+
+impl Keepalive {
+    /// Start tracking a connection opened at `now`
+    pub fn new(config: KeepaliveConfig, now: Unixtime) -> Keepalive {
+        Keepalive {
+            config,
+            last_received: now,
+            ping_sent: None,
+            unresponsive_reported: false,
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    /// Record any frame received from the relay, including a pong
+    pub fn record_received(&mut self, now: Unixtime) {
+        self.last_received = now;
+        self.ping_sent = None;
+        self.unresponsive_reported = false;
+    }
+
+    /// Record a message received from the relay. Events and EOSE also count
+    /// as activity on their subscription.
+    pub fn handle(&mut self, message: &RelayMessage, now: Unixtime) {
+        self.record_received(now);
+        let sub = match message {
+            RelayMessage::Event(sub, _) | RelayMessage::Eose(sub) => sub,
+            _ => return,
+        };
+        if let Some(activity) = self.subscriptions.get_mut(sub) {
+            *activity = (now, false);
+        }
+    }
+
+    /// Record that a ping was sent
+    pub fn record_ping_sent(&mut self, now: Unixtime) {
+        self.ping_sent = Some(now);
+    }
+
+    /// Watch a subscription for idleness, from when its REQ was sent
+    pub fn watch_subscription(&mut self, subscription_id: SubscriptionId, now: Unixtime) {
+        let _ = self.subscriptions.insert(subscription_id, (now, false));
+    }
+
+    /// Stop watching a subscription, as on CLOSE or CLOSED
+    pub fn unwatch_subscription(&mut self, subscription_id: &SubscriptionId) {
+        let _ = self.subscriptions.remove(subscription_id);
+    }
+
+    /// Check for anything needing action at `now`
+    pub fn poll(&mut self, now: Unixtime) -> Vec<ConnectionHealth> {
+        let mut output: Vec<ConnectionHealth> = Vec::new();
+
+        match self.ping_sent {
+            Some(sent) => {
+                if !self.unresponsive_reported && now >= sent + self.config.pong_timeout {
+                    self.unresponsive_reported = true;
+                    output.push(ConnectionHealth::Unresponsive);
+                }
+            }
+            None => {
+                if now >= self.last_received + self.config.ping_interval {
+                    output.push(ConnectionHealth::PingDue);
+                }
+            }
+        }
+
+        if let Some(idle) = self.config.idle_subscription {
+            let mut idle_subs: Vec<&SubscriptionId> = Vec::new();
+            for (sub, (last, reported)) in self.subscriptions.iter_mut() {
+                if !*reported && now >= *last + idle {
+                    *reported = true;
+                    idle_subs.push(sub);
+                }
+            }
+            idle_subs.sort_by(|a, b| a.0.cmp(&b.0));
+            output.extend(
+                idle_subs
+                    .into_iter()
+                    .map(|sub| ConnectionHealth::IdleSubscription(sub.clone())),
+            );
+        }
+
+        output
+    }
+
+    /// When `poll()` next has something to report, if nothing else happens
+    pub fn next_deadline(&self) -> Option<Unixtime> {
+        let connection = match self.ping_sent {
+            Some(_) if self.unresponsive_reported => None,
+            Some(sent) => Some(sent + self.config.pong_timeout),
+            None => Some(self.last_received + self.config.ping_interval),
+        };
+        let subscriptions = self.config.idle_subscription.and_then(|idle| {
+            self.subscriptions
+                .values()
+                .filter(|(_, reported)| !*reported)
+                .map(|(last, _)| *last + idle)
+                .min()
+        });
+        connection.into_iter().chain(subscriptions).min()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Event;
+
+    #[test]
+    fn test_keepalive() {
+        let start = Unixtime(1700000000);
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let config = KeepaliveConfig {
+            ping_interval: Duration::from_secs(30),
+            pong_timeout: Duration::from_secs(10),
+            idle_subscription: Some(Duration::from_secs(60)),
+        };
+        let mut keepalive = Keepalive::new(config, start);
+        let quiet = SubscriptionId("quiet".to_owned());
+        let busy = SubscriptionId("busy".to_owned());
+        keepalive.watch_subscription(quiet.clone(), start);
+        keepalive.watch_subscription(busy.clone(), start);
+
+        assert!(keepalive.poll(at(29)).is_empty());
+        assert_eq!(keepalive.next_deadline(), Some(at(30)));
+        assert_eq!(keepalive.poll(at(30)), vec![ConnectionHealth::PingDue]);
+
+        // A pong resets the connection, but not the subscriptions
+        keepalive.record_ping_sent(at(30));
+        assert_eq!(keepalive.next_deadline(), Some(at(40)));
+        keepalive.record_received(at(31));
+        assert!(keepalive.poll(at(40)).is_empty());
+
+        let event = RelayMessage::Event(busy.clone(), Box::new(Event::mock()));
+        keepalive.handle(&event, at(50));
+        assert_eq!(
+            keepalive.poll(at(80)),
+            vec![
+                ConnectionHealth::PingDue,
+                ConnectionHealth::IdleSubscription(quiet.clone())
+            ]
+        );
+
+        // Now the relay stops answering
+        keepalive.record_ping_sent(at(80));
+        assert!(keepalive.poll(at(89)).is_empty());
+        assert_eq!(keepalive.poll(at(90)), vec![ConnectionHealth::Unresponsive]);
+        assert_eq!(
+            keepalive.poll(at(200)),
+            vec![ConnectionHealth::IdleSubscription(busy.clone())]
+        );
+        assert!(keepalive.poll(at(300)).is_empty());
+        assert_eq!(keepalive.next_deadline(), None);
+
+        keepalive.unwatch_subscription(&quiet);
+        keepalive.handle(&RelayMessage::Eose(busy), at(301));
+        assert_eq!(keepalive.next_deadline(), Some(at(331)));
+    }
+}
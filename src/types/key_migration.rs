@@ -0,0 +1,367 @@
+use super::crypto_provider::ProviderRng;
+use super::{Event, EventKind, Id, PreEvent, PrivateKey, PublicKey, PublicKeyHex, Tag, Unixtime};
+use crate::Error;
+use rand_core::CryptoRngCore;
+use sha2::{Digest, Sha256};
+
+/// A key that an account may later migrate to, as listed in a key whitelist
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WhitelistEntry {
+    /// The new key, in the open
+    Open(PublicKey),
+
+    /// A commitment to the new key, so that it stays unknown (and cannot be
+    /// targeted) until the migration reveals it. This is the SHA-256 of a
+    /// secret 32-byte salt followed by the new key.
+    Blinded([u8; 32]),
+}
+
+impl WhitelistEntry {
+    /// Commit to `pubkey` with `salt`, which must be kept secret alongside the
+    /// new key's backup until the migration
+    pub fn blinded(pubkey: &PublicKey, salt: &[u8; 32]) -> WhitelistEntry {
+        let mut hasher = Sha256::new();
+        hasher.update(salt);
+        hasher.update(pubkey.as_bytes());
+        WhitelistEntry::Blinded(hasher.finalize().into())
+    }
+
+    /// Commit to `pubkey` with a new random salt, returning the salt too
+    pub fn blinded_random(pubkey: &PublicKey) -> (WhitelistEntry, [u8; 32]) {
+        Self::blinded_with_rng(pubkey, &mut ProviderRng)
+    }
+
+    /// Commit to `pubkey` with a random salt from `rng`, returning the salt too
+    pub fn blinded_with_rng(
+        pubkey: &PublicKey,
+        rng: &mut impl CryptoRngCore,
+    ) -> (WhitelistEntry, [u8; 32]) {
+        let mut salt = [0u8; 32];
+        rng.fill_bytes(&mut salt);
+        (Self::blinded(pubkey, &salt), salt)
+    }
+
+    /// Does this entry allow migrating to `pubkey`? A blinded entry needs the
+    /// salt it was made with.
+    pub fn allows(&self, pubkey: &PublicKey, salt: Option<&[u8; 32]>) -> bool {
+        match (self, salt) {
+            (WhitelistEntry::Open(pk), _) => pk == pubkey,
+            (WhitelistEntry::Blinded(_), Some(salt)) => Self::blinded(pubkey, salt) == *self,
+            (WhitelistEntry::Blinded(_), None) => false,
+        }
+    }
+}
+
+/// A key whitelist (draft NIP-41), kind 1776.
+///
+/// Published by an account ahead of time, listing the keys it may migrate to
+/// if its key is ever compromised. It should be timestamped with
+/// OpenTimestamps (NIP-03) when published, as that proof is what stops an
+/// attacker holding the old key from backdating a whitelist of their own.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeyWhitelist {
+    /// The keys that may be migrated to
+    pub entries: Vec<WhitelistEntry>,
+}
+
+impl KeyWhitelist {
+    /// Create an unsigned kind 1776 event for this whitelist
+    pub fn to_pre_event(&self, pubkey: PublicKey, now: Unixtime) -> PreEvent {
+        let mut tags: Vec<Tag> = self
+            .entries
+            .iter()
+            .map(|entry| match entry {
+                WhitelistEntry::Open(pk) => Tag::Pubkey {
+                    pubkey: PublicKeyHex::from(*pk),
+                    recommended_relay_url: None,
+                    petname: None,
+                },
+                WhitelistEntry::Blinded(commitment) => Tag::Other {
+                    tag: "commitment".to_owned(),
+                    data: vec![hex::encode(commitment)],
+                },
+            })
+            .collect();
+        tags.push(Tag::Alt("pubkey whitelisting event".to_owned()));
+
+        PreEvent {
+            pubkey,
+            created_at: now,
+            kind: EventKind::KeyWhitelist,
+            tags,
+            content: "".to_owned(),
+            ots: None,
+        }
+    }
+
+    /// Interpret a kind 1776 event as a key whitelist
+    pub fn try_from_event(event: &Event) -> Result<KeyWhitelist, Error> {
+        if event.kind != EventKind::KeyWhitelist {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut entries: Vec<WhitelistEntry> = Vec::new();
+        for tag in event.tags.iter() {
+            match tag {
+                Tag::Pubkey { pubkey, .. } => {
+                    entries.push(WhitelistEntry::Open(PublicKey::try_from(pubkey.clone())?));
+                }
+                Tag::Other { tag, data } if tag == "commitment" => {
+                    let commitment = data
+                        .first()
+                        .and_then(|c| hex::decode(c).ok())
+                        .and_then(|c| <[u8; 32]>::try_from(c).ok())
+                        .ok_or_else(|| {
+                            Error::AssertionFailed("Malformed whitelist commitment".to_owned())
+                        })?;
+                    entries.push(WhitelistEntry::Blinded(commitment));
+                }
+                _ => {}
+            }
+        }
+
+        if entries.is_empty() {
+            return Err(Error::AssertionFailed(
+                "Key whitelist has no entries".to_owned(),
+            ));
+        }
+        Ok(KeyWhitelist { entries })
+    }
+}
+
+/// A key migration (draft NIP-41), kind 1777.
+///
+/// Signed by the new key, this announces that the account has moved from the
+/// old key, which is to be treated as revoked: anything it signs after the
+/// migration should be distrusted. It names the whitelist event that
+/// authorized the new key, and for a blinded entry reveals the salt.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeyMigration {
+    /// The key being migrated away from
+    pub old_pubkey: PublicKey,
+
+    /// The id of the old key's whitelist event listing the new key
+    pub whitelist: Id,
+
+    /// The salt of the blinded whitelist entry, if it was blinded
+    pub salt: Option<[u8; 32]>,
+
+    /// An optional message to followers
+    pub message: String,
+}
+
+impl KeyMigration {
+    /// Create and sign a kind 1777 event for this migration with the new key
+    pub fn to_event(&self, new_privkey: &PrivateKey, now: Unixtime) -> Result<Event, Error> {
+        let mut tags: Vec<Tag> = vec![
+            Tag::Pubkey {
+                pubkey: PublicKeyHex::from(self.old_pubkey),
+                recommended_relay_url: None,
+                petname: None,
+            },
+            Tag::Event {
+                id: self.whitelist,
+                recommended_relay_url: None,
+                marker: None,
+                pubkey: None,
+            },
+        ];
+        if let Some(salt) = &self.salt {
+            tags.push(Tag::Other {
+                tag: "salt".to_owned(),
+                data: vec![hex::encode(salt)],
+            });
+        }
+        tags.push(Tag::Alt("pubkey migration event".to_owned()));
+
+        let pre_event = PreEvent {
+            pubkey: new_privkey.public_key(),
+            created_at: now,
+            kind: EventKind::KeyMigration,
+            tags,
+            content: self.message.clone(),
+            ots: None,
+        };
+        Event::new(pre_event, new_privkey)
+    }
+
+    /// Interpret a kind 1777 event as a key migration
+    pub fn try_from_event(event: &Event) -> Result<KeyMigration, Error> {
+        if event.kind != EventKind::KeyMigration {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut old_pubkey: Option<PublicKey> = None;
+        let mut whitelist: Option<Id> = None;
+        let mut salt: Option<[u8; 32]> = None;
+        for tag in event.tags.iter() {
+            match tag {
+                Tag::Pubkey { pubkey, .. } if old_pubkey.is_none() => {
+                    old_pubkey = Some(PublicKey::try_from(pubkey.clone())?);
+                }
+                Tag::Event { id, .. } if whitelist.is_none() => whitelist = Some(*id),
+                Tag::Other { tag, data } if tag == "salt" => {
+                    salt = Some(
+                        data.first()
+                            .and_then(|s| hex::decode(s).ok())
+                            .and_then(|s| <[u8; 32]>::try_from(s).ok())
+                            .ok_or_else(|| {
+                                Error::AssertionFailed("Malformed migration salt".to_owned())
+                            })?,
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        Ok(KeyMigration {
+            old_pubkey: old_pubkey.ok_or_else(|| {
+                Error::AssertionFailed("Key migration names no old key".to_owned())
+            })?,
+            whitelist: whitelist.ok_or_else(|| {
+                Error::AssertionFailed("Key migration names no whitelist".to_owned())
+            })?,
+            salt,
+            message: event.content.clone(),
+        })
+    }
+
+    /// Check that `migration` is authorized by `whitelist`: both are validly
+    /// signed, the whitelist is the one named and was signed by the old key
+    /// before the migration, and it lists the new key.
+    ///
+    /// This does not check the whitelist's OpenTimestamps proof, which the
+    /// caller must verify to be sure the whitelist predates any compromise.
+    pub fn verify(migration: &Event, whitelist: &Event) -> Result<KeyMigration, Error> {
+        migration.verify(None)?;
+        whitelist.verify(None)?;
+        let parsed = KeyMigration::try_from_event(migration)?;
+        let entries = KeyWhitelist::try_from_event(whitelist)?.entries;
+
+        if whitelist.id != parsed.whitelist {
+            return Err(Error::AssertionFailed(
+                "Migration names a different whitelist".to_owned(),
+            ));
+        }
+        if whitelist.pubkey != parsed.old_pubkey {
+            return Err(Error::AssertionFailed(
+                "Whitelist is not signed by the old key".to_owned(),
+            ));
+        }
+        if whitelist.created_at > migration.created_at {
+            return Err(Error::AssertionFailed(
+                "Whitelist is newer than the migration".to_owned(),
+            ));
+        }
+        if !entries
+            .iter()
+            .any(|entry| entry.allows(&migration.pubkey, parsed.salt.as_ref()))
+        {
+            return Err(Error::AssertionFailed(
+                "New key is not whitelisted".to_owned(),
+            ));
+        }
+        Ok(parsed)
+    }
+
+    /// Is `event` revoked by the `migration` event, being signed by the old
+    /// key after the migration?
+    pub fn revokes(migration: &Event, event: &Event) -> bool {
+        match KeyMigration::try_from_event(migration) {
+            Ok(parsed) => {
+                event.pubkey == parsed.old_pubkey && event.created_at >= migration.created_at
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_key_migration() {
+        let old = PrivateKey::generate();
+        let new = PrivateKey::generate();
+        let other = PrivateKey::generate();
+        let then = Unixtime(1700000000);
+        let now = Unixtime(1800000000);
+
+        let (blinded, salt) = WhitelistEntry::blinded_random(&new.public_key());
+        let whitelist = KeyWhitelist {
+            entries: vec![WhitelistEntry::Open(other.public_key()), blinded],
+        };
+        let whitelist_event =
+            Event::new(whitelist.to_pre_event(old.public_key(), then), &old).unwrap();
+        assert_eq!(
+            KeyWhitelist::try_from_event(&whitelist_event).unwrap(),
+            whitelist
+        );
+        // The new key is not revealed by the whitelist
+        let json = serde_json::to_string(&whitelist_event).unwrap();
+        assert!(!json.contains(&new.public_key().as_hex_string()));
+
+        let migration = KeyMigration {
+            old_pubkey: old.public_key(),
+            whitelist: whitelist_event.id,
+            salt: Some(salt),
+            message: "My old key leaked".to_owned(),
+        };
+        let migration_event = migration.to_event(&new, now).unwrap();
+        assert_eq!(
+            KeyMigration::try_from_event(&migration_event).unwrap(),
+            migration
+        );
+        assert_eq!(
+            KeyMigration::verify(&migration_event, &whitelist_event).unwrap(),
+            migration
+        );
+
+        // Without the salt, the blinded entry doesn't match
+        let unsalted = KeyMigration {
+            salt: None,
+            ..migration.clone()
+        };
+        let unsalted_event = unsalted.to_event(&new, now).unwrap();
+        assert!(KeyMigration::verify(&unsalted_event, &whitelist_event).is_err());
+
+        // The open entry works without a salt, but not for an unlisted key
+        let open_event = unsalted.to_event(&other, now).unwrap();
+        assert!(KeyMigration::verify(&open_event, &whitelist_event).is_ok());
+        let stranger = PrivateKey::generate();
+        let stranger_event = migration.to_event(&stranger, now).unwrap();
+        assert!(KeyMigration::verify(&stranger_event, &whitelist_event).is_err());
+
+        // An attacker's later whitelist from the old key is not accepted
+        // for a migration that predates it
+        let late = Event::new(
+            whitelist.to_pre_event(old.public_key(), Unixtime(1900000000)),
+            &old,
+        )
+        .unwrap();
+        let late_migration = KeyMigration {
+            whitelist: late.id,
+            ..migration.clone()
+        }
+        .to_event(&new, now)
+        .unwrap();
+        assert!(KeyMigration::verify(&late_migration, &late).is_err());
+
+        let note = |created_at: Unixtime| {
+            let pre_event = PreEvent {
+                pubkey: old.public_key(),
+                created_at,
+                kind: EventKind::TextNote,
+                tags: vec![],
+                content: "".to_owned(),
+                ots: None,
+            };
+            Event::new(pre_event, &old).unwrap()
+        };
+        let before = note(then);
+        let after = note(Unixtime(1800000001));
+        assert!(!KeyMigration::revokes(&migration_event, &before));
+        assert!(KeyMigration::revokes(&migration_event, &after));
+    }
+}
@@ -0,0 +1,125 @@
+use super::{Id, PrivateKey, PublicKey};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroize;
+
+/// Accepts bech32 (note, npub, nsec) as well as hex where serde expects an
+/// `Id`, `PublicKey` or `PrivateKey`.
+///
+/// This is for input people write by hand, such as config files. The plain
+/// types stay strict, so events and other protocol messages still only accept
+/// hex; wrap just the fields that need it. A `Lenient` serializes as the value
+/// it wraps. Requires the `lenient-serde` feature.
+///
+/// ```
+/// # use nostr_types::{Lenient, PublicKey};
+/// #[derive(serde::Deserialize)]
+/// struct Config {
+///     follows: Vec<Lenient<PublicKey>>,
+/// }
+/// ```
+///
+/// A `Lenient<PrivateKey>` has `KeySecurity::Weak`, and deliberately can't be
+/// serialized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Lenient<T>(pub T);
+
+impl<T: Serialize> Serialize for Lenient<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Lenient<Id> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let id = if s.starts_with("note1") {
+            Id::try_from_bech32_string(&s)
+        } else {
+            Id::try_from_hex_string(&s)
+        };
+        id.map(Lenient).map_err(D::Error::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Lenient<PublicKey> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        let pubkey = if s.starts_with("npub1") {
+            PublicKey::try_from_bech32_string(&s)
+        } else {
+            PublicKey::try_from_hex_string(&s)
+        };
+        pubkey.map(Lenient).map_err(D::Error::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Lenient<PrivateKey> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut s = String::deserialize(deserializer)?;
+        let key = if s.starts_with("nsec1") {
+            PrivateKey::try_from_bech32_string(&s)
+        } else {
+            PrivateKey::try_from_hex_string(&s)
+        };
+        s.zeroize();
+        key.map(Lenient).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Event, KeySecurity};
+
+    #[test]
+    fn test_lenient_id_and_pubkey() {
+        let id = Id::mock();
+        for encoded in [id.as_hex_string(), id.as_bech32_string()] {
+            let json = format!("\"{encoded}\"");
+            assert_eq!(serde_json::from_str::<Lenient<Id>>(&json).unwrap().0, id);
+        }
+
+        let pk = PublicKey::mock();
+        for encoded in [pk.as_hex_string(), pk.as_bech32_string()] {
+            let json = format!("\"{encoded}\"");
+            let decoded: Lenient<PublicKey> = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded.0, pk);
+            assert_eq!(
+                serde_json::to_string(&decoded).unwrap(),
+                format!("\"{}\"", pk.as_hex_string())
+            );
+        }
+        assert!(serde_json::from_str::<Lenient<PublicKey>>("\"npub1xyz\"").is_err());
+
+        // The plain types stay strict
+        let json = format!("\"{}\"", pk.as_bech32_string());
+        assert!(serde_json::from_str::<PublicKey>(&json).is_err());
+        let mut event = serde_json::to_value(Event::mock()).unwrap();
+        event["pubkey"] = serde_json::Value::String(pk.as_bech32_string());
+        assert!(serde_json::from_value::<Event>(event).is_err());
+    }
+
+    #[test]
+    fn test_lenient_private_key() {
+        let pk = PrivateKey::generate();
+        for encoded in [pk.as_hex_string(), pk.as_bech32_string()] {
+            let json = format!("\"{encoded}\"");
+            let decoded: Lenient<PrivateKey> = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded.0.public_key(), pk.public_key());
+            assert_eq!(decoded.0.key_security(), KeySecurity::Weak);
+        }
+    }
+}
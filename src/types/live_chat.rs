@@ -0,0 +1,207 @@
+use super::{Event, EventAddr, EventKind, Filter, Id, PublicKey, Tag, Unixtime};
+use crate::Error;
+use std::collections::{BTreeMap, HashMap};
+
+/// An event in a `LiveChat`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LiveChatItem<'a> {
+    /// A chat message (kind 1311)
+    Message(&'a Event),
+
+    /// A zap receipt (kind 9735)
+    Zap(&'a Event),
+
+    /// Someone's latest presence in the room (kind 10312)
+    Presence(&'a Event),
+}
+
+impl<'a> LiveChatItem<'a> {
+    /// The event, whatever it is
+    pub fn event(&self) -> &'a Event {
+        match self {
+            LiveChatItem::Message(e) | LiveChatItem::Zap(e) | LiveChatItem::Presence(e) => e,
+        }
+    }
+}
+
+/// The chat around a live activity (kind 30311, NIP-53): its messages,
+/// zaps and presence, merged into one stream, oldest first.
+///
+/// Subscribe with `filters()`, pass whatever arrives to `insert()`, and read
+/// the stream with `iter()`. Only each author's latest presence is kept,
+/// since presence events are replaceable.
+#[derive(Clone, Debug)]
+pub struct LiveChat {
+    address: String,
+    events: BTreeMap<(Unixtime, Id), Event>,
+    presence: HashMap<PublicKey, (Unixtime, Id)>,
+}
+
+impl LiveChat {
+    /// The chat for the live activity at `activity`. Fails unless it is the
+    /// address of a kind 30311 event.
+    pub fn new(activity: &EventAddr) -> Result<LiveChat, Error> {
+        if activity.kind != EventKind::LiveEvent {
+            return Err(Error::WrongEventKind);
+        }
+        Ok(LiveChat {
+            address: activity.as_address(),
+            events: BTreeMap::new(),
+            presence: HashMap::new(),
+        })
+    }
+
+    /// The address of the live activity, as used in 'a' tags
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Filters for the chat messages, zaps and presence, from `since` on.
+    /// They are kept apart so that relays can apply a `limit` to each.
+    pub fn filters(&self, since: Option<Unixtime>) -> Vec<Filter> {
+        [
+            EventKind::LiveChatMessage,
+            EventKind::Zap,
+            EventKind::RoomPresence,
+        ]
+        .into_iter()
+        .map(|kind| Filter {
+            kinds: vec![kind],
+            a: vec![self.address.clone()],
+            since,
+            ..Default::default()
+        })
+        .collect()
+    }
+
+    /// Add an event. Returns false if it is not a message, zap or presence
+    /// for this activity, was already present, or is an older presence than
+    /// one already held.
+    pub fn insert(&mut self, event: Event) -> bool {
+        if !matches!(
+            event.kind,
+            EventKind::LiveChatMessage | EventKind::Zap | EventKind::RoomPresence
+        ) {
+            return false;
+        }
+        let for_us = event.tags.iter().any(|tag| match tag {
+            Tag::Address { address, .. } => address.as_address() == self.address,
+            _ => false,
+        });
+        if !for_us {
+            return false;
+        }
+
+        let key = (event.created_at, event.id);
+        if self.events.contains_key(&key) {
+            return false;
+        }
+        if event.kind == EventKind::RoomPresence {
+            if let Some(old) = self.presence.get(&event.pubkey).copied() {
+                if old > key {
+                    return false;
+                }
+                let _ = self.events.remove(&old);
+            }
+            let _ = self.presence.insert(event.pubkey, key);
+        }
+        let _ = self.events.insert(key, event);
+        true
+    }
+
+    /// How many events are held
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Whether no events are held
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// The time of the newest event, to resume from after reconnecting
+    pub fn latest(&self) -> Option<Unixtime> {
+        self.events.keys().next_back().map(|(time, _)| *time)
+    }
+
+    /// The stream, oldest first, with ties broken by id
+    pub fn iter(&self) -> impl Iterator<Item = LiveChatItem<'_>> {
+        self.events.values().map(|event| match event.kind {
+            EventKind::LiveChatMessage => LiveChatItem::Message(event),
+            EventKind::Zap => LiveChatItem::Zap(event),
+            _ => LiveChatItem::Presence(event),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PreEvent, PrivateKey};
+
+    #[test]
+    fn test_live_chat() {
+        let host = PrivateKey::generate();
+        let viewer = PrivateKey::generate();
+        let activity = EventAddr {
+            d: "stream".to_owned(),
+            relays: vec![],
+            kind: EventKind::LiveEvent,
+            author: host.public_key(),
+        };
+        let mut chat = LiveChat::new(&activity).unwrap();
+
+        let filters = chat.filters(Some(Unixtime(1000)));
+        assert_eq!(filters.len(), 3);
+        assert_eq!(filters[0].kinds, vec![EventKind::LiveChatMessage]);
+        assert_eq!(filters[2].a, vec![activity.as_address()]);
+
+        let event = |kind: EventKind, created_at: i64, address: &EventAddr| {
+            let pre = PreEvent {
+                pubkey: viewer.public_key(),
+                created_at: Unixtime(created_at),
+                kind,
+                tags: vec![Tag::Address {
+                    address: address.clone(),
+                    marker: None,
+                }],
+                content: String::new(),
+                ots: None,
+            };
+            Event::new(pre, &viewer).unwrap()
+        };
+
+        let message = event(EventKind::LiveChatMessage, 1020, &activity);
+        assert!(chat.insert(message.clone()));
+        assert!(!chat.insert(message));
+        assert!(chat.insert(event(EventKind::Zap, 1010, &activity)));
+        assert!(chat.insert(event(EventKind::RoomPresence, 1005, &activity)));
+        assert!(chat.insert(event(EventKind::RoomPresence, 1030, &activity)));
+        assert!(!chat.insert(event(EventKind::RoomPresence, 1001, &activity)));
+
+        // Not for this activity
+        let mut other = activity.clone();
+        other.d = "other".to_owned();
+        assert!(!chat.insert(event(EventKind::LiveChatMessage, 1040, &other)));
+        assert!(!chat.insert(event(EventKind::TextNote, 1040, &activity)));
+
+        let times: Vec<(i64, bool)> = chat
+            .iter()
+            .map(|item| {
+                (
+                    item.event().created_at.0,
+                    matches!(item, LiveChatItem::Presence(_)),
+                )
+            })
+            .collect();
+        assert_eq!(times, vec![(1010, false), (1020, false), (1030, true)]);
+        assert_eq!(chat.latest(), Some(Unixtime(1030)));
+
+        let mut not_live = activity;
+        not_live.kind = EventKind::LongFormContent;
+        assert!(matches!(
+            LiveChat::new(&not_live),
+            Err(Error::WrongEventKind)
+        ));
+    }
+}
@@ -0,0 +1,350 @@
+use super::{Event, EventStorage, Filter, Id, PublicKey, Unixtime};
+use crate::Error;
+use heed::types::{Bytes, Unit};
+use heed::{Database, Env, EnvOpenOptions, RoTxn, RwTxn};
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::Bound;
+use std::path::Path;
+
+/// An `EventStorage` in an LMDB environment.
+///
+/// Events are stored as JSON keyed by id, and indexed by created_at and by
+/// author so that queries return the newest events first without loading
+/// everything. Requires the `lmdb` feature.
+pub struct LmdbStorage {
+    env: Env,
+    events: Database<Bytes, Bytes>,
+    created_index: Database<Bytes, Unit>,
+    author_index: Database<Bytes, Unit>,
+}
+
+impl fmt::Debug for LmdbStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LmdbStorage")
+            .field("path", &self.env.path())
+            .finish()
+    }
+}
+
+impl LmdbStorage {
+    /// Open (or create) the storage in the directory at `path`, which must
+    /// exist. `map_size` is the maximum size of the database in bytes.
+    pub fn open<P: AsRef<Path>>(path: P, map_size: usize) -> Result<LmdbStorage, Error> {
+        // Safety: the environment must not be opened more than once in the same
+        // process, which is up to the caller
+        let env = unsafe {
+            EnvOpenOptions::new()
+                .map_size(map_size)
+                .max_dbs(3)
+                .open(path)?
+        };
+
+        let mut txn = env.write_txn()?;
+        let events = env.create_database(&mut txn, Some("events"))?;
+        let created_index = env.create_database(&mut txn, Some("created_index"))?;
+        let author_index = env.create_database(&mut txn, Some("author_index"))?;
+        txn.commit()?;
+
+        Ok(LmdbStorage {
+            env,
+            events,
+            created_index,
+            author_index,
+        })
+    }
+
+    fn get_in(&self, txn: &RoTxn, id: &Id) -> Result<Option<Event>, Error> {
+        match self.events.get(txn, id.0.as_slice())? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Collect the matching events in an index between the time bounds, newest
+    // first, stopping at the filter's limit. Index keys are the `prefix`
+    // followed by created_at and the id.
+    fn scan_index(
+        &self,
+        txn: &RoTxn,
+        index: &Database<Bytes, Unit>,
+        prefix: &[u8],
+        filter: &Filter,
+        events: &mut Vec<Event>,
+    ) -> Result<(), Error> {
+        let since = filter.since.unwrap_or(Unixtime(0));
+        let until = filter.until.unwrap_or(Unixtime(i64::MAX));
+        let start = index_key(prefix, since, &Id([0; 32]));
+        let end = index_key(prefix, until, &Id([255; 32]));
+        let range = (
+            Bound::Included(start.as_slice()),
+            Bound::Included(end.as_slice()),
+        );
+
+        let mut found = 0;
+        for result in index.rev_range(txn, &range)? {
+            if filter.limit.map(|limit| found >= limit).unwrap_or(false) {
+                break;
+            }
+            let (key, _) = result?;
+            let id: [u8; 32] = key[key.len() - 32..].try_into()?;
+            if let Some(event) = self.get_in(txn, &Id(id))? {
+                if filter.event_matches(&event) {
+                    events.push(event);
+                    found += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn query_in(&self, txn: &RoTxn, filter: &Filter) -> Result<Vec<Event>, Error> {
+        let mut events = Vec::new();
+
+        if !filter.ids.is_empty() && filter.ids.iter().all(|id| id.as_str().len() == 64) {
+            // Full ids can be fetched directly
+            for prefix in filter.ids.iter() {
+                let id = Id::try_from_hex_string(prefix.as_str())?;
+                if let Some(event) = self.get_in(txn, &id)? {
+                    if filter.event_matches(&event) {
+                        events.push(event);
+                    }
+                }
+            }
+        } else if !filter.authors.is_empty()
+            && filter.authors.iter().all(|a| a.as_str().len() == 64)
+        {
+            // Full authors can use the author index
+            for prefix in filter.authors.iter() {
+                let pubkey = PublicKey::try_from_hex_string(prefix.as_str())?;
+                let bytes = pubkey.as_bytes();
+                self.scan_index(txn, &self.author_index, &bytes, filter, &mut events)?;
+            }
+        } else {
+            self.scan_index(txn, &self.created_index, &[], filter, &mut events)?;
+        }
+
+        // Events from several authors are not in order
+        events.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(a.id.cmp(&b.id)));
+        events.dedup_by_key(|e| e.id);
+        if let Some(limit) = filter.limit {
+            events.truncate(limit);
+        }
+        Ok(events)
+    }
+
+    fn insert_in(&self, txn: &mut RwTxn, event: &Event) -> Result<(), Error> {
+        let bytes = serde_json::to_vec(event)?;
+        self.events.put(txn, event.id.0.as_slice(), &bytes)?;
+        self.created_index
+            .put(txn, &index_key(&[], event.created_at, &event.id), &())?;
+        self.author_index.put(
+            txn,
+            &index_key(&event.pubkey.as_bytes(), event.created_at, &event.id),
+            &(),
+        )?;
+        Ok(())
+    }
+
+    fn delete_in(&self, txn: &mut RwTxn, id: Id) -> Result<bool, Error> {
+        let event: Event = match self.events.get(txn, id.0.as_slice())? {
+            Some(bytes) => serde_json::from_slice(bytes)?,
+            None => return Ok(false),
+        };
+        let _ = self.events.delete(txn, id.0.as_slice())?;
+        let _ = self
+            .created_index
+            .delete(txn, &index_key(&[], event.created_at, &id))?;
+        let _ = self.author_index.delete(
+            txn,
+            &index_key(&event.pubkey.as_bytes(), event.created_at, &id),
+        )?;
+        Ok(true)
+    }
+}
+
+// The created_at is stored big-endian so that keys sort in time order. Events
+// dated before 1970 are indexed as if at 0.
+fn index_key(prefix: &[u8], created_at: Unixtime, id: &Id) -> Vec<u8> {
+    let mut key = Vec::with_capacity(prefix.len() + 8 + 32);
+    key.extend_from_slice(prefix);
+    key.extend_from_slice(&(created_at.0.max(0) as u64).to_be_bytes());
+    key.extend_from_slice(id.0.as_slice());
+    key
+}
+
+impl EventStorage for LmdbStorage {
+    fn insert(&self, event: &Event) -> Result<(), Error> {
+        let mut txn = self.env.write_txn()?;
+        self.insert_in(&mut txn, event)?;
+        txn.commit()?;
+        Ok(())
+    }
+
+    fn get(&self, id: Id) -> Result<Option<Event>, Error> {
+        let txn = self.env.read_txn()?;
+        self.get_in(&txn, &id)
+    }
+
+    fn query(&self, filter: &Filter) -> Result<Vec<Event>, Error> {
+        let txn = self.env.read_txn()?;
+        self.query_in(&txn, filter)
+    }
+
+    fn delete(&self, id: Id) -> Result<bool, Error> {
+        let mut txn = self.env.write_txn()?;
+        let deleted = self.delete_in(&mut txn, id)?;
+        txn.commit()?;
+        Ok(deleted)
+    }
+
+    // The rules are applied within one write transaction, so that no other
+    // writer sees or changes the store half way through
+    fn put(&self, event: &Event) -> Result<bool, Error> {
+        let in_txn = InTxn {
+            storage: self,
+            txn: RefCell::new(self.env.write_txn()?),
+        };
+        let stored = in_txn.put(event)?;
+        in_txn.txn.into_inner().commit()?;
+        Ok(stored)
+    }
+}
+
+// The storage as seen through a single write transaction
+struct InTxn<'a> {
+    storage: &'a LmdbStorage,
+    txn: RefCell<RwTxn<'a>>,
+}
+
+impl EventStorage for InTxn<'_> {
+    fn insert(&self, event: &Event) -> Result<(), Error> {
+        self.storage.insert_in(&mut self.txn.borrow_mut(), event)
+    }
+
+    fn get(&self, id: Id) -> Result<Option<Event>, Error> {
+        self.storage.get_in(&self.txn.borrow(), &id)
+    }
+
+    fn query(&self, filter: &Filter) -> Result<Vec<Event>, Error> {
+        self.storage.query_in(&self.txn.borrow(), filter)
+    }
+
+    fn delete(&self, id: Id) -> Result<bool, Error> {
+        self.storage.delete_in(&mut self.txn.borrow_mut(), id)
+    }
+
+    fn persists(&self, event: &Event) -> bool {
+        self.storage.persists(event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EventAddr, EventKind, IdHex, PreEvent, PrivateKey, PublicKeyHex, Tag};
+
+    #[test]
+    fn test_lmdb_storage() {
+        let dir =
+            std::env::temp_dir().join(format!("nostr-types-lmdb-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let storage = LmdbStorage::open(&dir, 10 * 1024 * 1024).unwrap();
+
+        let private_key = PrivateKey::generate();
+        let mut ids = Vec::new();
+        for created_at in [300, 100, 200] {
+            let pre = PreEvent {
+                pubkey: private_key.public_key(),
+                created_at: Unixtime(created_at),
+                kind: EventKind::TextNote,
+                tags: vec![Tag::Hashtag("nostr".to_owned())],
+                content: format!("note at {created_at}"),
+                ots: None,
+            };
+            let event = Event::new(pre, &private_key).unwrap();
+            assert!(storage.put(&event).unwrap());
+            ids.push(event.id);
+        }
+        assert!(storage.put(&Event::mock()).unwrap());
+
+        let mut filter = Filter::new();
+        filter.add_author(PublicKeyHex::from(private_key.public_key()));
+        let events = storage.query(&filter).unwrap();
+        let times: Vec<i64> = events.iter().map(|e| e.created_at.0).collect();
+        assert_eq!(times, vec![300, 200, 100]);
+
+        let mut filter = Filter::new();
+        filter.t = vec!["nostr".to_owned()];
+        filter.since = Some(Unixtime(150));
+        filter.limit = Some(1);
+        let events = storage.query(&filter).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id, ids[0]);
+
+        assert!(storage.delete(ids[0]).unwrap());
+        assert!(!storage.delete(ids[0]).unwrap());
+        assert!(storage.get(ids[0]).unwrap().is_none());
+        let mut filter = Filter::new();
+        filter.add_id(IdHex::from(ids[2]));
+        assert_eq!(storage.query(&filter).unwrap().len(), 1);
+
+        drop(storage);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_lmdb_put_address_deletion() {
+        let dir = std::env::temp_dir().join(format!(
+            "nostr-types-lmdb-deletion-test-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let storage = LmdbStorage::open(&dir, 10 * 1024 * 1024).unwrap();
+
+        let private_key = PrivateKey::generate();
+        let event = |kind: EventKind, created_at: i64, tags: Vec<Tag>| {
+            let pre = PreEvent {
+                pubkey: private_key.public_key(),
+                created_at: Unixtime(created_at),
+                kind,
+                tags,
+                content: "".to_owned(),
+                ots: None,
+            };
+            Event::new(pre, &private_key).unwrap()
+        };
+        let d = || Tag::Identifier("post".to_owned());
+
+        let article = event(EventKind::LongFormContent, 100, vec![d()]);
+        assert!(storage.put(&article).unwrap());
+        let deletion = event(
+            EventKind::EventDeletion,
+            200,
+            vec![Tag::Address {
+                address: EventAddr::for_event(&article, vec![]).unwrap(),
+                marker: None,
+            }],
+        );
+        assert!(storage.put(&deletion).unwrap());
+        assert!(storage.get(article.id).unwrap().is_none());
+        assert!(!storage
+            .put(&event(EventKind::LongFormContent, 150, vec![d()]))
+            .unwrap());
+        assert!(storage
+            .put(&event(EventKind::LongFormContent, 300, vec![d()]))
+            .unwrap());
+
+        let mut filter = Filter::new();
+        filter.add_author(PublicKeyHex::from(private_key.public_key()));
+        filter.limit = Some(1);
+        let events = storage.query(&filter).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].created_at, Unixtime(300));
+
+        drop(storage);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
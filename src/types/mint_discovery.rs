@@ -0,0 +1,365 @@
+use super::{Event, EventAddr, EventKind, PreEvent, PublicKey, Tag, UncheckedUrl, Unixtime};
+use crate::Error;
+
+/// The network a mint operates on (the 'n' tag)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MintNetwork {
+    /// Bitcoin mainnet
+    Mainnet,
+
+    /// Bitcoin testnet
+    Testnet,
+
+    /// Bitcoin signet
+    Signet,
+
+    /// Bitcoin regtest
+    Regtest,
+
+    /// Some other network
+    Other(String),
+}
+
+impl MintNetwork {
+    /// The string used in the 'n' tag
+    pub fn as_str(&self) -> &str {
+        match self {
+            MintNetwork::Mainnet => "mainnet",
+            MintNetwork::Testnet => "testnet",
+            MintNetwork::Signet => "signet",
+            MintNetwork::Regtest => "regtest",
+            MintNetwork::Other(s) => s,
+        }
+    }
+
+    fn from_str(s: &str) -> MintNetwork {
+        match s {
+            "mainnet" => MintNetwork::Mainnet,
+            "testnet" => MintNetwork::Testnet,
+            "signet" => MintNetwork::Signet,
+            "regtest" => MintNetwork::Regtest,
+            _ => MintNetwork::Other(s.to_owned()),
+        }
+    }
+
+    fn to_tag(&self) -> Tag {
+        other_tag("n", vec![self.as_str().to_owned()])
+    }
+}
+
+/// A Cashu mint announcing itself (NIP-87), kind 38172
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CashuMintAnnouncement {
+    /// The mint's public key (the 'd' identifier)
+    pub mint_pubkey: String,
+
+    /// URLs the mint is reachable at
+    pub urls: Vec<UncheckedUrl>,
+
+    /// The NUTs the mint supports
+    pub nuts: Vec<u32>,
+
+    /// The network the mint operates on
+    pub network: Option<MintNetwork>,
+
+    /// Optional kind-0 style metadata JSON (the event content)
+    pub metadata: String,
+}
+
+impl CashuMintAnnouncement {
+    /// Does the mint support this NUT?
+    pub fn supports_nut(&self, nut: u32) -> bool {
+        self.nuts.contains(&nut)
+    }
+
+    /// Create a `PreEvent` for this announcement
+    pub fn to_pre_event(&self, pubkey: PublicKey) -> PreEvent {
+        let mut tags: Vec<Tag> = vec![Tag::Identifier(self.mint_pubkey.clone())];
+        for url in self.urls.iter() {
+            tags.push(other_tag("u", vec![url.0.clone()]));
+        }
+        if !self.nuts.is_empty() {
+            let nuts: Vec<String> = self.nuts.iter().map(|n| format!("{n}")).collect();
+            tags.push(other_tag("nuts", vec![nuts.join(",")]));
+        }
+        if let Some(network) = &self.network {
+            tags.push(network.to_tag());
+        }
+
+        PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::CashuMintAnnouncement,
+            tags,
+            content: self.metadata.clone(),
+            ots: None,
+        }
+    }
+
+    /// Interpret an event as a Cashu mint announcement
+    pub fn try_from_event(event: &Event) -> Result<CashuMintAnnouncement, Error> {
+        if event.kind != EventKind::CashuMintAnnouncement {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut nuts: Vec<u32> = Vec::new();
+        if let Some(list) = first_value(event, "nuts") {
+            for nut in list.split(',').map(|n| n.trim()).filter(|n| !n.is_empty()) {
+                nuts.push(nut.parse()?);
+            }
+        }
+
+        Ok(CashuMintAnnouncement {
+            mint_pubkey: event.parameter().ok_or_else(|| {
+                Error::AssertionFailed("Mint announcement has no 'd' tag".to_owned())
+            })?,
+            urls: all_values(event, "u").map(UncheckedUrl).collect(),
+            nuts,
+            network: first_value(event, "n").map(|n| MintNetwork::from_str(&n)),
+            metadata: event.content.clone(),
+        })
+    }
+}
+
+/// A Fedimint federation announcing itself (NIP-87), kind 38173
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FedimintAnnouncement {
+    /// The federation id (the 'd' identifier)
+    pub federation_id: String,
+
+    /// Invite codes for joining the federation
+    pub invite_codes: Vec<String>,
+
+    /// The modules the federation runs
+    pub modules: Vec<String>,
+
+    /// The network the federation operates on
+    pub network: Option<MintNetwork>,
+
+    /// Optional kind-0 style metadata JSON (the event content)
+    pub metadata: String,
+}
+
+impl FedimintAnnouncement {
+    /// Create a `PreEvent` for this announcement
+    pub fn to_pre_event(&self, pubkey: PublicKey) -> PreEvent {
+        let mut tags: Vec<Tag> = vec![Tag::Identifier(self.federation_id.clone())];
+        for code in self.invite_codes.iter() {
+            tags.push(other_tag("u", vec![code.clone()]));
+        }
+        if !self.modules.is_empty() {
+            tags.push(other_tag("modules", vec![self.modules.join(",")]));
+        }
+        if let Some(network) = &self.network {
+            tags.push(network.to_tag());
+        }
+
+        PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::FedimintAnnouncement,
+            tags,
+            content: self.metadata.clone(),
+            ots: None,
+        }
+    }
+
+    /// Interpret an event as a Fedimint announcement
+    pub fn try_from_event(event: &Event) -> Result<FedimintAnnouncement, Error> {
+        if event.kind != EventKind::FedimintAnnouncement {
+            return Err(Error::WrongEventKind);
+        }
+
+        Ok(FedimintAnnouncement {
+            federation_id: event.parameter().ok_or_else(|| {
+                Error::AssertionFailed("Fedimint announcement has no 'd' tag".to_owned())
+            })?,
+            invite_codes: all_values(event, "u").collect(),
+            modules: first_value(event, "modules")
+                .map(|m| {
+                    m.split(',')
+                        .map(|s| s.trim().to_owned())
+                        .filter(|s| !s.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            network: first_value(event, "n").map(|n| MintNetwork::from_str(&n)),
+            metadata: event.content.clone(),
+        })
+    }
+}
+
+/// A user's recommendation of a mint (NIP-87), kind 38000
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MintRecommendation {
+    /// The kind of mint being recommended, `CashuMintAnnouncement` or
+    /// `FedimintAnnouncement` (the 'k' tag)
+    pub mint_kind: EventKind,
+
+    /// The 'd' identifier: the mint pubkey or federation id being recommended
+    pub identifier: String,
+
+    /// URLs or invite codes for the mint
+    pub urls: Vec<UncheckedUrl>,
+
+    /// Addresses of announcement events for the mint. Only the first relay of
+    /// each is kept in the tag.
+    pub announcements: Vec<EventAddr>,
+
+    /// An optional review (the event content)
+    pub review: String,
+}
+
+impl MintRecommendation {
+    /// Create a `PreEvent` for this recommendation
+    pub fn to_pre_event(&self, pubkey: PublicKey) -> PreEvent {
+        let mut tags: Vec<Tag> = vec![
+            Tag::KindReference(self.mint_kind),
+            Tag::Identifier(self.identifier.clone()),
+        ];
+        for url in self.urls.iter() {
+            tags.push(other_tag("u", vec![url.0.clone()]));
+        }
+        for address in self.announcements.iter() {
+            tags.push(Tag::Address {
+                address: address.clone(),
+                marker: None,
+            });
+        }
+
+        PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::MintRecommendation,
+            tags,
+            content: self.review.clone(),
+            ots: None,
+        }
+    }
+
+    /// Interpret an event as a mint recommendation
+    pub fn try_from_event(event: &Event) -> Result<MintRecommendation, Error> {
+        if event.kind != EventKind::MintRecommendation {
+            return Err(Error::WrongEventKind);
+        }
+
+        let missing =
+            |what: &str| Error::AssertionFailed(format!("Mint recommendation has no {what}"));
+        let mint_kind: EventKind = match event.referenced_kinds().first() {
+            Some(kind) => *kind,
+            None => first_value(event, "k")
+                .ok_or_else(|| missing("'k' tag"))?
+                .parse::<u64>()?
+                .into(),
+        };
+
+        let announcements = event
+            .tags
+            .iter()
+            .filter_map(|t| match t {
+                Tag::Address { address, .. } => {
+                    let mut address = address.clone();
+                    address.relays.retain(|r| !r.0.is_empty());
+                    Some(address)
+                }
+                _ => None,
+            })
+            .collect();
+
+        Ok(MintRecommendation {
+            mint_kind,
+            identifier: event.parameter().ok_or_else(|| missing("'d' tag"))?,
+            urls: all_values(event, "u").map(UncheckedUrl).collect(),
+            announcements,
+            review: event.content.clone(),
+        })
+    }
+}
+
+fn other_tag(tag: &str, data: Vec<String>) -> Tag {
+    Tag::Other {
+        tag: tag.to_owned(),
+        data,
+    }
+}
+
+fn all_values<'a>(event: &'a Event, name: &'a str) -> impl Iterator<Item = String> + 'a {
+    event.tags.iter().filter_map(move |t| match t {
+        Tag::Other { tag, data } if tag == name => data.first().cloned(),
+        _ => None,
+    })
+}
+
+fn first_value(event: &Event, name: &str) -> Option<String> {
+    all_values(event, name).next()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn test_cashu_mint_announcement_round_trip() {
+        let privkey = PrivateKey::mock();
+        let announcement = CashuMintAnnouncement {
+            mint_pubkey: "02a1b2c3".to_owned(),
+            urls: vec![UncheckedUrl::from_str("https://cashu.example.com")],
+            nuts: vec![1, 2, 3, 4, 5, 7, 11, 12],
+            network: Some(MintNetwork::Mainnet),
+            metadata: "".to_owned(),
+        };
+        let event = Event::new(announcement.to_pre_event(privkey.public_key()), &privkey).unwrap();
+        let parsed = CashuMintAnnouncement::try_from_event(&event).unwrap();
+        assert_eq!(parsed, announcement);
+        assert!(parsed.supports_nut(11));
+        assert!(!parsed.supports_nut(6));
+    }
+
+    #[test]
+    fn test_fedimint_and_recommendation_round_trip() {
+        let privkey = PrivateKey::mock();
+        let federation = FedimintAnnouncement {
+            federation_id: "fed-id".to_owned(),
+            invite_codes: vec!["fed11abc".to_owned()],
+            modules: vec!["lightning".to_owned(), "mint".to_owned()],
+            network: Some(MintNetwork::Other("mutinynet".to_owned())),
+            metadata: "".to_owned(),
+        };
+        let event = Event::new(federation.to_pre_event(privkey.public_key()), &privkey).unwrap();
+        assert_eq!(
+            FedimintAnnouncement::try_from_event(&event).unwrap(),
+            federation
+        );
+
+        let recommendation = MintRecommendation {
+            mint_kind: EventKind::CashuMintAnnouncement,
+            identifier: "02a1b2c3".to_owned(),
+            urls: vec![UncheckedUrl::from_str("https://cashu.example.com")],
+            announcements: vec![EventAddr {
+                d: "02a1b2c3".to_owned(),
+                relays: vec![UncheckedUrl::from_str("wss://relay.example.com")],
+                kind: EventKind::CashuMintAnnouncement,
+                author: privkey.public_key(),
+            }],
+            review: "Reliable".to_owned(),
+        };
+        let event =
+            Event::new(recommendation.to_pre_event(privkey.public_key()), &privkey).unwrap();
+        assert_eq!(
+            MintRecommendation::try_from_event(&event).unwrap(),
+            recommendation
+        );
+        let event: Event = serde_json::from_str(&serde_json::to_string(&event).unwrap()).unwrap();
+        assert_eq!(
+            event.referenced_kinds(),
+            vec![EventKind::CashuMintAnnouncement]
+        );
+        assert_eq!(event.referenced_addresses().len(), 1);
+        assert_eq!(
+            MintRecommendation::try_from_event(&event).unwrap(),
+            recommendation
+        );
+        assert!(CashuMintAnnouncement::try_from_event(&event).is_err());
+    }
+}
@@ -11,14 +11,58 @@ macro_rules! test_serde {
     };
 }
 
+mod account_backup;
+pub use account_backup::AccountBackup;
+
+mod backfill;
+pub use backfill::Backfiller;
+
+mod cashu_wallet;
+pub use cashu_wallet::{
+    CashuDirection, CashuDleq, CashuProof, CashuQuote, CashuSpendingHistory, CashuToken,
+    CashuWallet,
+};
+
 mod client_message;
-pub use client_message::ClientMessage;
+pub use client_message::{ClientMessage, ReqViolation};
+
+mod contact_list;
+pub use contact_list::{Contact, ContactList, ContactListSplit};
+
+mod crypto_provider;
+pub use crypto_provider::{
+    reset_crypto_provider, set_crypto_provider, CryptoProvider, DefaultCryptoProvider,
+};
 
 mod delegation;
 pub use delegation::{DelegationConditions, EventDelegation};
 
+mod diff;
+pub use diff::ChangeSet;
+
+mod direct_message;
+pub use direct_message::EncryptedDirectMessage;
+
+mod dm_relay_list;
+pub use dm_relay_list::DmRelayList;
+
+mod effective_author;
+pub use effective_author::{AuthorResolver, EffectiveAuthor};
+
+mod eose;
+pub use eose::EoseTracker;
+
+mod ephemeral;
+pub use ephemeral::EphemeralEvent;
+
 mod event;
-pub use event::{Event, PreEvent};
+pub use event::{
+    canonical_escape, omit_client_tag, set_omit_client_tag, ContentReference, ContentStats, Event,
+    PreEvent, TagOrder,
+};
+
+mod event_addr;
+pub use event_addr::EventAddr;
 
 mod event_kind;
 pub use event_kind::{EventKind, EventKindIterator};
@@ -26,50 +70,189 @@ pub use event_kind::{EventKind, EventKindIterator};
 mod filter;
 pub use filter::Filter;
 
+mod filter_engine;
+pub use filter_engine::FilterEngine;
+
+mod geohash;
+pub use geohash::Geohash;
+
+mod giftwrap;
+pub use giftwrap::{
+    giftwrap_for_recipients, giftwrap_for_recipients_with_rng, GiftWrap, Rumor, Seal,
+};
+
+mod hint_resolver;
+pub use hint_resolver::{HintResolver, HintStrategy, RelayPool};
+
 mod id;
 pub use id::{Id, IdHex, IdHexPrefix};
 
+mod id_digest;
+pub use id_digest::IdSetDigest;
+
+mod identity_bundle;
+pub use identity_bundle::IdentityBundle;
+
+mod event_cache;
+pub use event_cache::EventCache;
+
+mod event_order;
+pub use event_order::{EventOrder, OrderBy, TieBreak};
+
 mod event_pointer;
 pub use event_pointer::EventPointer;
 
+mod event_reference;
+pub use event_reference::EventReference;
+
+mod event_stats;
+pub use event_stats::EventStats;
+
+mod imeta;
+pub use imeta::Imeta;
+
+#[cfg(feature = "intern")]
+mod interned_storage;
+#[cfg(feature = "intern")]
+pub use interned_storage::{InternedStorage, TagInterner};
+
+mod keepalive;
+pub use keepalive::{ConnectionHealth, Keepalive, KeepaliveConfig};
+
+mod key_migration;
+pub use key_migration::{KeyMigration, KeyWhitelist, WhitelistEntry};
+
+mod live_chat;
+pub use live_chat::{LiveChat, LiveChatItem};
+
+#[cfg(feature = "lenient-serde")]
+mod lenient;
+#[cfg(feature = "lenient-serde")]
+pub use lenient::Lenient;
+
+#[cfg(feature = "lmdb")]
+mod lmdb_storage;
+#[cfg(feature = "lmdb")]
+pub use lmdb_storage::LmdbStorage;
+
 mod metadata;
 pub use metadata::Metadata;
 
+mod mute;
+pub use mute::MuteEngine;
+
+mod name_resolver;
+pub use name_resolver::NameResolver;
+
 mod nip05;
 pub use nip05::Nip05;
 
+mod nip44;
+
+mod nip46;
+pub use nip46::{
+    BunkerUrl, Nip46Decision, Nip46Policy, Nip46Request, Nip46Response, Nip46Service, Nip46Session,
+    Nip46Transport, Signer,
+};
+
+mod ndjson;
+pub use ndjson::{EventReader, EventWriter, SkippedLine};
+
 mod nostr_url;
 pub use nostr_url::{find_nostr_bech32_pos, find_nostr_url_pos, NostrBech32, NostrUrl};
 
+mod mint_discovery;
+pub use mint_discovery::{
+    CashuMintAnnouncement, FedimintAnnouncement, MintNetwork, MintRecommendation,
+};
+
+mod nutzap;
+pub use nutzap::{Nutzap, NutzapInfo, NutzapMint};
+
+mod parse_profile;
+pub use parse_profile::ParseProfile;
+
+mod parsed_tag;
+pub use parsed_tag::{register_tag_type, registered_tag_names, ParsedTag};
+
 mod pay_request_data;
 pub use pay_request_data::PayRequestData;
 
+mod policy;
+pub use policy::{
+    BannedPubkeys, CreatedAtBounds, KindAllowlist, MaxEventSize, MinimumPow, OkPrefix, Policy,
+    PolicyDecision, PolicySet, PubkeyRateLimit,
+};
+
 mod private_key;
 pub use private_key::{EncryptedPrivateKey, KeySecurity, PrivateKey};
 
+mod private_message;
+pub use private_message::{EncryptedFile, PrivateDirectMessage, PrivateMessageBody};
+
 mod profile;
 pub use profile::Profile;
 
+mod pubkey_set;
+pub use pubkey_set::PubkeySet;
+
 mod public_key;
 pub use public_key::{PublicKey, PublicKeyHex, PublicKeyHexPrefix};
 
+mod publish;
+pub use publish::{PendingPublishes, PublishOutcome, PublishReport, Publisher};
+
 mod relay_message;
-pub use relay_message::RelayMessage;
+pub use relay_message::{MessageSizeLimits, NoticeKind, RelayMessage};
+
+mod relay_connect;
+pub use relay_connect::{
+    connect_relay, AddressPreference, ConnectAttempt, ConnectConfig, ConnectReport,
+};
 
 mod relay_information_document;
-pub use relay_information_document::{RelayInformationDocument, RelayLimitation};
+pub use relay_information_document::{
+    RelayFee, RelayFees, RelayInformationDocument, RelayLimitation, RelayLimits, RetentionPolicy,
+};
+
+mod seen;
+pub use seen::{SeenEvent, SeenEvents};
+
+mod sanitize;
+pub use sanitize::{Escape, SanitizeOptions, SanitizeReport, SanitizedContent};
 
 mod signature;
 pub use signature::{Signature, SignatureHex};
 
+mod relay_picker;
+pub use relay_picker::{plan_giftwrap_publish, plan_publish, RelayPicker, RelayPicks};
+
+mod relay_probe;
+pub use relay_probe::{RelayCapabilities, RelayProbe};
+
+mod relay_stats;
+pub use relay_stats::RelayStats;
+
 mod relay_list;
 pub use relay_list::{SimpleRelayList, SimpleRelayUsage};
 
+mod resubscribe;
+pub use resubscribe::{Resubscribed, Resubscriber};
+
+mod signer_policy;
+pub use signer_policy::SignerPolicy;
+
+mod storage;
+pub use storage::{EventStorage, SweepReport, Sweeper};
+
 mod subscription_id;
 pub use subscription_id::SubscriptionId;
 
 mod tag;
-pub use tag::Tag;
+pub use tag::{ProxyProtocol, Tag};
+
+mod thread;
+pub use thread::{ThreadNode, ThreadTree};
 
 mod unixtime;
 pub use unixtime::Unixtime;
@@ -77,6 +260,24 @@ pub use unixtime::Unixtime;
 mod url;
 pub use self::url::{RelayUrl, UncheckedUrl, Url};
 
+mod vanish;
+pub use vanish::{VanishRequest, VanishTarget};
+
+mod verifier_pool;
+pub use verifier_pool::{VerifiedEvent, VerifierConfig, VerifierPool, VerifyOrder};
+
+mod video;
+pub use video::Video;
+
+mod wiki;
+pub use wiki::{WikiArticle, WikiMergeRequest, WikiReference, WikiRelation};
+
+mod wire_log;
+pub use wire_log::{WireDirection, WireFrame, WireLog, WireMessage, WireReplay};
+
+mod word_filter;
+pub use word_filter::WordFilter;
+
 #[cfg(test)]
 mod test {
     use crate::*;
@@ -0,0 +1,261 @@
+use super::{Event, EventKind, Id, PubkeySet, PublicKey, Tag, WordFilter};
+use crate::Error;
+use std::collections::HashSet;
+
+/// Decides which events to hide according to a NIP-51 mute list.
+///
+/// The list's public tags are read directly. The private tags are encrypted by
+/// the author to themselves, so the caller supplies the decryption as a closure
+/// (typically NIP-04 or NIP-44 with their own private key).
+///
+/// Hashtags and words are matched case-insensitively, words anywhere in the
+/// content. A `WordFilter` can be added for whole-word and wildcard matching.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MuteEngine {
+    pubkeys: PubkeySet,
+    hashtags: HashSet<String>,
+    words: Vec<String>,
+    threads: HashSet<Id>,
+    word_filter: WordFilter,
+}
+
+impl MuteEngine {
+    /// Create an engine which mutes nothing
+    pub fn new() -> MuteEngine {
+        Default::default()
+    }
+
+    /// Build from the public part of a mute list event
+    pub fn from_event(event: &Event) -> Result<MuteEngine, Error> {
+        if event.kind != EventKind::MuteList {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut engine = MuteEngine::new();
+        for tag in event.tags.iter() {
+            engine.add_tag(tag);
+        }
+        Ok(engine)
+    }
+
+    /// Build from both the public and private parts of a mute list event.
+    /// `decrypt` is given the event content and must return the plaintext, a
+    /// JSON array of tags. It is not called if the content is empty.
+    pub fn from_event_with_private<F>(event: &Event, decrypt: F) -> Result<MuteEngine, Error>
+    where
+        F: Fn(&str) -> Result<String, Error>,
+    {
+        let mut engine = MuteEngine::from_event(event)?;
+        if !event.content.is_empty() {
+            let tags: Vec<Tag> = serde_json::from_str(&decrypt(&event.content)?)?;
+            for tag in tags.iter() {
+                engine.add_tag(tag);
+            }
+        }
+        Ok(engine)
+    }
+
+    /// Add an entry from a mute list tag: 'p' for pubkeys, 't' for hashtags,
+    /// 'word' for words and 'e' for threads. Other tags are ignored.
+    pub fn add_tag(&mut self, tag: &Tag) {
+        match tag {
+            Tag::Pubkey { pubkey, .. } => {
+                if let Ok(pubkey) = PublicKey::try_from(pubkey.clone()) {
+                    self.mute_pubkey(pubkey);
+                }
+            }
+            Tag::Hashtag(hashtag) => self.mute_hashtag(hashtag),
+            Tag::Event { id, .. } => self.mute_thread(*id),
+            Tag::Other { tag, data } if tag == "word" => {
+                if let Some(word) = data.first() {
+                    self.mute_word(word);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Mute everything by this author
+    pub fn mute_pubkey(&mut self, pubkey: PublicKey) {
+        let _ = self.pubkeys.insert(pubkey);
+    }
+
+    /// Mute events with this hashtag
+    pub fn mute_hashtag(&mut self, hashtag: &str) {
+        let _ = self.hashtags.insert(hashtag.to_lowercase());
+    }
+
+    /// Mute events whose content contains this word
+    pub fn mute_word(&mut self, word: &str) {
+        let word = word.to_lowercase();
+        if !word.is_empty() && !self.words.contains(&word) {
+            self.words.push(word);
+        }
+    }
+
+    /// Mute this event and the thread under it
+    pub fn mute_thread(&mut self, id: Id) {
+        let _ = self.threads.insert(id);
+    }
+
+    /// Also mute events whose content matches this filter, replacing any
+    /// previously set
+    pub fn set_word_filter(&mut self, filter: WordFilter) {
+        self.word_filter = filter;
+    }
+
+    /// Is nothing muted?
+    pub fn is_empty(&self) -> bool {
+        self.pubkeys.is_empty()
+            && self.hashtags.is_empty()
+            && self.words.is_empty()
+            && self.threads.is_empty()
+            && self.word_filter.is_empty()
+    }
+
+    /// Should this event be hidden?
+    pub fn is_muted(&self, event: &Event) -> bool {
+        if self.pubkeys.contains(&event.pubkey) {
+            return true;
+        }
+
+        if !self.threads.is_empty() {
+            if self.threads.contains(&event.id) {
+                return true;
+            }
+            let root = event.thread_root().and_then(|r| r.id());
+            let parent = event.reply_target().and_then(|r| r.id());
+            if root
+                .iter()
+                .chain(parent.iter())
+                .any(|id| self.threads.contains(id))
+            {
+                return true;
+            }
+        }
+
+        if !self.hashtags.is_empty() {
+            for tag in event.tags.iter() {
+                if let Tag::Hashtag(hashtag) = tag {
+                    if self.hashtags.contains(&hashtag.to_lowercase()) {
+                        return true;
+                    }
+                }
+            }
+        }
+
+        if !self.words.is_empty() {
+            let content = event.content.to_lowercase();
+            if self
+                .words
+                .iter()
+                .any(|word| content.contains(word.as_str()))
+            {
+                return true;
+            }
+        }
+
+        if !self.word_filter.is_empty() && self.word_filter.is_match_event(event) {
+            return true;
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PreEvent, PrivateKey, PublicKeyHex, Unixtime};
+
+    fn event(private_key: &PrivateKey, kind: EventKind, tags: Vec<Tag>, content: &str) -> Event {
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(1700000000),
+            kind,
+            tags,
+            content: content.to_owned(),
+            ots: None,
+        };
+        Event::new(pre, private_key).unwrap()
+    }
+
+    #[test]
+    fn test_mute_engine() {
+        let me = PrivateKey::generate();
+        let spammer = PrivateKey::generate();
+        let friend = PrivateKey::generate();
+        let muted_thread = event(&friend, EventKind::TextNote, vec![], "a flame war");
+
+        let public_tags = vec![
+            Tag::Pubkey {
+                pubkey: PublicKeyHex::from(spammer.public_key()),
+                recommended_relay_url: None,
+                petname: None,
+            },
+            Tag::Hashtag("Crypto".to_owned()),
+        ];
+        let private_tags = vec![
+            Tag::Other {
+                tag: "word".to_owned(),
+                data: vec!["Airdrop".to_owned()],
+            },
+            Tag::Event {
+                id: muted_thread.id,
+                recommended_relay_url: None,
+                marker: None,
+                pubkey: None,
+            },
+        ];
+        // Stand in for encryption to self
+        let private = serde_json::to_string(&private_tags).unwrap();
+        let mute_list = event(&me, EventKind::MuteList, public_tags, "ciphertext");
+
+        let public_only = MuteEngine::from_event(&mute_list).unwrap();
+        let engine = MuteEngine::from_event_with_private(&mute_list, |content| {
+            assert_eq!(content, "ciphertext");
+            Ok(private.clone())
+        })
+        .unwrap();
+
+        let spam = event(&spammer, EventKind::TextNote, vec![], "hello");
+        assert!(public_only.is_muted(&spam));
+        assert!(engine.is_muted(&spam));
+
+        let tagged = event(
+            &friend,
+            EventKind::TextNote,
+            vec![Tag::Hashtag("crypto".to_owned())],
+            "hello",
+        );
+        assert!(engine.is_muted(&tagged));
+
+        let airdrop = event(&friend, EventKind::TextNote, vec![], "Free AIRDROP now");
+        assert!(!public_only.is_muted(&airdrop));
+        assert!(engine.is_muted(&airdrop));
+
+        let reply = event(
+            &friend,
+            EventKind::TextNote,
+            vec![Tag::Event {
+                id: muted_thread.id,
+                recommended_relay_url: None,
+                marker: Some("root".to_owned()),
+                pubkey: None,
+            }],
+            "more flames",
+        );
+        assert!(engine.is_muted(&muted_thread));
+        assert!(engine.is_muted(&reply));
+
+        let fine = event(&friend, EventKind::TextNote, vec![], "hello");
+        assert!(!engine.is_muted(&fine));
+        assert!(MuteEngine::new().is_empty());
+
+        let mut filtered = public_only;
+        filtered.set_word_filter(WordFilter::new(["air*"]).unwrap());
+        assert!(filtered.is_muted(&airdrop));
+        assert!(!filtered.is_muted(&fine));
+        assert!(MuteEngine::from_event(&fine).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use super::{Event, EventKind, Metadata, Nip05, PublicKey, PublicKeyHex, Tag, Unixtime};
+use crate::Error;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default)]
+struct NameEntry {
+    // display_name, or name, from the latest metadata event
+    metadata_name: Option<String>,
+    metadata_nip05: Option<String>,
+    metadata_at: Option<Unixtime>,
+
+    // The nip05 identifier as last verified, and when
+    verified_nip05: Option<(String, Unixtime)>,
+
+    // From the user's own contact list
+    petname: Option<String>,
+}
+
+/// Resolves pubkeys to the names to display for them.
+///
+/// Names come from three places, in order of precedence:
+/// 1. a petname the user gave in their own contact list (NIP-02)
+/// 2. a NIP-05 identifier, if it was verified recently enough
+/// 3. the `display_name` or `name` from the person's metadata
+///
+/// If none is known the shortened npub is used. NIP-05 verification needs a
+/// network fetch, so it is left to the caller: `nip05_to_check()` lists the
+/// identifiers that need (re)checking and `verify_nip05()` records the result.
+#[derive(Clone, Debug)]
+pub struct NameResolver {
+    entries: HashMap<PublicKey, NameEntry>,
+    contact_list_at: Option<Unixtime>,
+    nip05_max_age: Duration,
+}
+
+impl NameResolver {
+    /// Create a resolver, trusting NIP-05 verifications for `nip05_max_age`
+    pub fn new(nip05_max_age: Duration) -> NameResolver {
+        NameResolver {
+            entries: HashMap::new(),
+            contact_list_at: None,
+            nip05_max_age,
+        }
+    }
+
+    /// Take names from a metadata event. Returns false if we already have
+    /// newer metadata for that pubkey.
+    pub fn update_metadata(&mut self, event: &Event) -> Result<bool, Error> {
+        if event.kind != EventKind::Metadata {
+            return Err(Error::WrongEventKind);
+        }
+
+        let entry = self.entries.entry(event.pubkey).or_default();
+        if entry
+            .metadata_at
+            .map(|t| t >= event.created_at)
+            .unwrap_or(false)
+        {
+            return Ok(false);
+        }
+
+        let metadata: Metadata = serde_json::from_str(&event.content)?;
+        let display_name = match metadata.other.get("display_name") {
+            Some(Value::String(s)) => Some(s.to_owned()),
+            _ => None,
+        };
+        entry.metadata_name = display_name
+            .or(metadata.name)
+            .filter(|s| !s.trim().is_empty());
+        entry.metadata_nip05 = metadata.nip05.filter(|s| !s.trim().is_empty());
+        entry.metadata_at = Some(event.created_at);
+        Ok(true)
+    }
+
+    /// Take petnames from the user's own contact list, replacing those from any
+    /// older contact list. Returns false if it is older than the one we have.
+    pub fn update_contact_list(&mut self, event: &Event) -> Result<bool, Error> {
+        if event.kind != EventKind::ContactList {
+            return Err(Error::WrongEventKind);
+        }
+        if self
+            .contact_list_at
+            .map(|t| t >= event.created_at)
+            .unwrap_or(false)
+        {
+            return Ok(false);
+        }
+
+        for entry in self.entries.values_mut() {
+            entry.petname = None;
+        }
+        for tag in event.tags.iter() {
+            if let Tag::Pubkey {
+                pubkey,
+                petname: Some(petname),
+                ..
+            } = tag
+            {
+                if petname.trim().is_empty() {
+                    continue;
+                }
+                if let Ok(pubkey) = PublicKey::try_from(pubkey.clone()) {
+                    self.entries.entry(pubkey).or_default().petname = Some(petname.to_owned());
+                }
+            }
+        }
+        self.contact_list_at = Some(event.created_at);
+        Ok(true)
+    }
+
+    /// Check a pubkey's NIP-05 identifier against the `nostr.json` fetched from
+    /// its domain, recording the verification if it matches. Returns whether
+    /// it matched.
+    pub fn verify_nip05(
+        &mut self,
+        pubkey: PublicKey,
+        nip05: &str,
+        document: &Nip05,
+        now: Unixtime,
+    ) -> bool {
+        let user = match nip05.split_once('@') {
+            Some((user, _domain)) => user.to_lowercase(),
+            None => return false,
+        };
+        let matches = document
+            .names
+            .iter()
+            .any(|(name, pk)| name.to_lowercase() == user && *pk == PublicKeyHex::from(pubkey));
+
+        let entry = self.entries.entry(pubkey).or_default();
+        if matches {
+            entry.verified_nip05 = Some((nip05.to_owned(), now));
+        } else if entry.verified_nip05.as_ref().map(|(n, _)| n.as_str()) == Some(nip05) {
+            entry.verified_nip05 = None;
+        }
+        matches
+    }
+
+    /// The NIP-05 identifiers from metadata which have not been verified, or
+    /// whose verification is stale
+    pub fn nip05_to_check(&self, now: Unixtime) -> Vec<(PublicKey, String)> {
+        self.entries
+            .iter()
+            .filter_map(|(pubkey, entry)| {
+                let nip05 = entry.metadata_nip05.as_ref()?;
+                if self.fresh_nip05(entry, now) == Some(nip05.as_str()) {
+                    None
+                } else {
+                    Some((*pubkey, nip05.to_owned()))
+                }
+            })
+            .collect()
+    }
+
+    /// The petname the user gave this pubkey, if any
+    pub fn petname(&self, pubkey: &PublicKey) -> Option<&str> {
+        self.entries.get(pubkey)?.petname.as_deref()
+    }
+
+    /// The name to display for a pubkey
+    pub fn display_name(&self, pubkey: &PublicKey, now: Unixtime) -> String {
+        if let Some(entry) = self.entries.get(pubkey) {
+            if let Some(petname) = &entry.petname {
+                return petname.to_owned();
+            }
+            if let Some(nip05) = self.fresh_nip05(entry, now) {
+                // "_@domain" is the root identifier for the domain
+                return match nip05.strip_prefix("_@") {
+                    Some(domain) => domain.to_owned(),
+                    None => nip05.to_owned(),
+                };
+            }
+            if let Some(name) = &entry.metadata_name {
+                return name.to_owned();
+            }
+        }
+
+        pubkey.abbrev()
+    }
+
+    // The verified NIP-05 identifier, if verified within the max age and still
+    // the one in the metadata
+    fn fresh_nip05<'a>(&self, entry: &'a NameEntry, now: Unixtime) -> Option<&'a str> {
+        let (nip05, verified_at) = entry.verified_nip05.as_ref()?;
+        if *verified_at < now - self.nip05_max_age {
+            return None;
+        }
+        if entry.metadata_nip05.as_deref() != Some(nip05.as_str()) {
+            return None;
+        }
+        Some(nip05.as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PreEvent, PrivateKey};
+
+    fn event(
+        private_key: &PrivateKey,
+        kind: EventKind,
+        created_at: i64,
+        tags: Vec<Tag>,
+        content: String,
+    ) -> Event {
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(created_at),
+            kind,
+            tags,
+            content,
+            ots: None,
+        };
+        Event::new(pre, private_key).unwrap()
+    }
+
+    fn metadata(private_key: &PrivateKey, created_at: i64, name: &str, nip05: &str) -> Event {
+        let mut metadata = Metadata::new();
+        metadata.name = Some(name.to_owned());
+        metadata.nip05 = Some(nip05.to_owned());
+        let content = serde_json::to_string(&metadata).unwrap();
+        event(
+            private_key,
+            EventKind::Metadata,
+            created_at,
+            vec![],
+            content,
+        )
+    }
+
+    #[test]
+    fn test_name_resolver() {
+        let me = PrivateKey::generate();
+        let bob = PrivateKey::generate();
+        let pubkey = bob.public_key();
+        let now = Unixtime(1_700_000_000);
+        let mut resolver = NameResolver::new(Duration::from_secs(86400));
+
+        assert!(resolver.display_name(&pubkey, now).starts_with("npub1"));
+
+        assert!(resolver
+            .update_metadata(&metadata(&bob, 200, "bob", "bob@example.com"))
+            .unwrap());
+        assert!(!resolver
+            .update_metadata(&metadata(&bob, 100, "old bob", "bob@old.example.com"))
+            .unwrap());
+        assert_eq!(resolver.display_name(&pubkey, now), "bob");
+        assert_eq!(
+            resolver.nip05_to_check(now),
+            vec![(pubkey, "bob@example.com".to_owned())]
+        );
+
+        let mut document = Nip05 {
+            names: HashMap::new(),
+            relays: HashMap::new(),
+        };
+        assert!(!resolver.verify_nip05(pubkey, "bob@example.com", &document, now));
+        let _ = document
+            .names
+            .insert("Bob".to_owned(), PublicKeyHex::from(pubkey));
+        assert!(resolver.verify_nip05(pubkey, "bob@example.com", &document, now));
+        assert_eq!(resolver.display_name(&pubkey, now), "bob@example.com");
+        assert!(resolver.nip05_to_check(now).is_empty());
+
+        // Verification goes stale
+        let later = now + Duration::from_secs(86401);
+        assert_eq!(resolver.display_name(&pubkey, later), "bob");
+        assert_eq!(resolver.nip05_to_check(later).len(), 1);
+
+        // Petnames win
+        let contacts = event(
+            &me,
+            EventKind::ContactList,
+            300,
+            vec![Tag::Pubkey {
+                pubkey: PublicKeyHex::from(pubkey),
+                recommended_relay_url: None,
+                petname: Some("Uncle Bob".to_owned()),
+            }],
+            "".to_owned(),
+        );
+        assert!(resolver.update_contact_list(&contacts).unwrap());
+        assert_eq!(resolver.display_name(&pubkey, now), "Uncle Bob");
+
+        let newer_contacts = event(&me, EventKind::ContactList, 400, vec![], "".to_owned());
+        assert!(resolver.update_contact_list(&newer_contacts).unwrap());
+        assert!(!resolver.update_contact_list(&contacts).unwrap());
+        assert_eq!(resolver.petname(&pubkey), None);
+        assert_eq!(resolver.display_name(&pubkey, now), "bob@example.com");
+    }
+}
@@ -0,0 +1,192 @@
+use super::Event;
+use crate::Error;
+use std::io::{BufRead, Write};
+
+/// A line that `EventReader` could not parse as an event
+#[derive(Debug)]
+pub struct SkippedLine {
+    /// The line number, counting from 1
+    pub line: usize,
+
+    /// Why it was skipped
+    pub error: Error,
+}
+
+/// Reads events from newline-delimited JSON, one event per line.
+///
+/// Blank lines are ignored. Lines that are not valid events are skipped so
+/// that one bad line does not spoil an import, and are listed by `skipped()`.
+/// The iterator only yields an error if reading fails, after which it ends.
+#[derive(Debug)]
+pub struct EventReader<R> {
+    reader: R,
+    line: usize,
+    verify: bool,
+    skipped: Vec<SkippedLine>,
+    failed: bool,
+}
+
+impl<R: BufRead> EventReader<R> {
+    /// Read events from `reader`
+    pub fn new(reader: R) -> EventReader<R> {
+        EventReader {
+            reader,
+            line: 0,
+            verify: false,
+            skipped: Vec::new(),
+            failed: false,
+        }
+    }
+
+    /// Also skip events whose id or signature does not verify
+    pub fn verify(mut self, verify: bool) -> EventReader<R> {
+        self.verify = verify;
+        self
+    }
+
+    /// The lines skipped so far
+    pub fn skipped(&self) -> &[SkippedLine] {
+        &self.skipped
+    }
+
+    fn parse(&self, line: &str) -> Result<Event, Error> {
+        let event: Event = serde_json::from_str(line)?;
+        if self.verify {
+            event.verify(None)?;
+        }
+        Ok(event)
+    }
+}
+
+impl<R: BufRead> Iterator for EventReader<R> {
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Result<Event, Error>> {
+        if self.failed {
+            return None;
+        }
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match self.reader.read_until(b'\n', &mut buf) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => {
+                    self.failed = true;
+                    return Some(Err(e.into()));
+                }
+            }
+            self.line += 1;
+
+            // A line that is not UTF-8 is skipped like any other bad line
+            let line = match std::str::from_utf8(&buf) {
+                Ok(line) => line.trim(),
+                Err(e) => {
+                    self.skipped.push(SkippedLine {
+                        line: self.line,
+                        error: e.into(),
+                    });
+                    continue;
+                }
+            };
+            if line.is_empty() {
+                continue;
+            }
+            match self.parse(line) {
+                Ok(event) => return Some(Ok(event)),
+                Err(error) => self.skipped.push(SkippedLine {
+                    line: self.line,
+                    error,
+                }),
+            }
+        }
+    }
+}
+
+/// Writes events as newline-delimited JSON, one event per line
+#[derive(Debug)]
+pub struct EventWriter<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> EventWriter<W> {
+    /// Write events to `writer`
+    pub fn new(writer: W) -> EventWriter<W> {
+        EventWriter { writer, count: 0 }
+    }
+
+    /// Write an event
+    pub fn write(&mut self, event: &Event) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.writer, event)?;
+        self.writer.write_all(b"\n")?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// The number of events written
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Get the underlying writer back
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ndjson_round_trip() {
+        let a = Event::mock();
+        let mut b = Event::mock();
+        b.content = "tampered".to_owned();
+
+        let mut writer = EventWriter::new(Vec::new());
+        writer.write(&a).unwrap();
+        writer.write(&b).unwrap();
+        assert_eq!(writer.count(), 2);
+        let mut output = writer.into_inner();
+        output.extend(b"\n{\"not\": \"an event\"}\n");
+        output.extend(serde_json::to_vec(&a).unwrap());
+
+        let mut reader = EventReader::new(&output[..]);
+        let events: Vec<Event> = reader.by_ref().map(|r| r.unwrap()).collect();
+        assert_eq!(events, vec![a.clone(), b, a.clone()]);
+        assert_eq!(reader.skipped().len(), 1);
+        assert_eq!(reader.skipped()[0].line, 4);
+
+        let mut reader = EventReader::new(&output[..]).verify(true);
+        let events: Vec<Event> = reader.by_ref().map(|r| r.unwrap()).collect();
+        assert_eq!(events, vec![a.clone(), a]);
+        let lines: Vec<usize> = reader.skipped().iter().map(|s| s.line).collect();
+        assert_eq!(lines, vec![2, 4]);
+        assert!(matches!(
+            reader.skipped()[0].error,
+            Error::HashMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn test_ndjson_invalid_utf8() {
+        let a = Event::mock();
+        let mut input = b"\xff\xfe not utf-8\n".to_vec();
+        input.extend(serde_json::to_vec(&a).unwrap());
+
+        let mut reader = EventReader::new(&input[..]);
+        let events: Vec<Event> = reader.by_ref().map(|r| r.unwrap()).collect();
+        assert_eq!(events, vec![a]);
+        assert_eq!(reader.skipped().len(), 1);
+        assert_eq!(reader.skipped()[0].line, 1);
+        assert!(matches!(reader.skipped()[0].error, Error::Utf8Error(_)));
+    }
+}
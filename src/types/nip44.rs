@@ -0,0 +1,272 @@
+use super::crypto_provider::ProviderRng;
+use super::{PrivateKey, PublicKey};
+use crate::Error;
+use base64::Engine;
+use chacha20::cipher::{KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand_core::RngCore;
+use sha2::Sha256;
+
+const VERSION: u8 = 2;
+const MAX_PLAINTEXT: usize = 65535;
+
+impl PrivateKey {
+    /// The NIP-44 conversation key shared with `other`, which is the same
+    /// from either side
+    pub fn nip44_conversation_key(&self, other: &PublicKey) -> [u8; 32] {
+        conversation_key(&self.shared_secret_bytes(other))
+    }
+
+    /// Encrypt `plaintext` for `other` with NIP-44 version 2, giving the
+    /// base64 payload. The plaintext must be 1 to 65535 bytes long.
+    pub fn nip44_encrypt(&self, other: &PublicKey, plaintext: &str) -> Result<String, Error> {
+        nip44_encrypt_with_shared_secret(&self.shared_secret_bytes(other), plaintext)
+    }
+
+    /// Decrypt a NIP-44 version 2 payload from `other`
+    pub fn nip44_decrypt(&self, other: &PublicKey, payload: &str) -> Result<String, Error> {
+        decrypt(&self.nip44_conversation_key(other), payload)
+    }
+}
+
+fn conversation_key(shared_secret: &[u8; 32]) -> [u8; 32] {
+    let (prk, _) = Hkdf::<Sha256>::extract(Some(b"nip44-v2"), shared_secret);
+    prk.into()
+}
+
+// NIP-44 with the x coordinate of the shared secret, for `Signer`s which only
+// expose `ecdh()`
+pub(crate) fn nip44_encrypt_with_shared_secret(
+    shared_secret: &[u8; 32],
+    plaintext: &str,
+) -> Result<String, Error> {
+    let mut nonce = [0u8; 32];
+    ProviderRng.fill_bytes(&mut nonce);
+    encrypt(&conversation_key(shared_secret), plaintext, &nonce)
+}
+
+pub(crate) fn nip44_decrypt_with_shared_secret(
+    shared_secret: &[u8; 32],
+    payload: &str,
+) -> Result<String, Error> {
+    decrypt(&conversation_key(shared_secret), payload)
+}
+
+fn encrypt(
+    conversation_key: &[u8; 32],
+    plaintext: &str,
+    nonce: &[u8; 32],
+) -> Result<String, Error> {
+    let (chacha_key, chacha_nonce, hmac_key) = message_keys(conversation_key, nonce);
+    let mut buffer = pad(plaintext)?;
+    ChaCha20::new(&chacha_key.into(), &chacha_nonce.into()).apply_keystream(&mut buffer);
+    let mac = hmac_aad(&hmac_key, &buffer, nonce).finalize().into_bytes();
+
+    let mut payload: Vec<u8> = Vec::with_capacity(1 + 32 + buffer.len() + 32);
+    payload.push(VERSION);
+    payload.extend_from_slice(nonce);
+    payload.extend_from_slice(&buffer);
+    payload.extend_from_slice(&mac);
+    Ok(base64::engine::general_purpose::STANDARD.encode(payload))
+}
+
+fn decrypt(conversation_key: &[u8; 32], payload: &str) -> Result<String, Error> {
+    let invalid = |why: &str| Error::Nip44(why.to_owned());
+    if payload.starts_with('#') {
+        return Err(invalid("unsupported encryption version"));
+    }
+    if payload.len() < 132 || payload.len() > 87472 {
+        return Err(invalid("invalid payload length"));
+    }
+    let data = base64::engine::general_purpose::STANDARD.decode(payload)?;
+    if data.len() < 99 || data.len() > 65603 {
+        return Err(invalid("invalid data length"));
+    }
+    if data[0] != VERSION {
+        return Err(invalid("unknown encryption version"));
+    }
+    let mut nonce = [0u8; 32];
+    nonce.copy_from_slice(&data[1..33]);
+    let ciphertext = &data[33..data.len() - 32];
+    let mac = &data[data.len() - 32..];
+
+    let (chacha_key, chacha_nonce, hmac_key) = message_keys(conversation_key, &nonce);
+    hmac_aad(&hmac_key, ciphertext, &nonce)
+        .verify_slice(mac)
+        .map_err(|_| invalid("invalid MAC"))?;
+    let mut buffer = ciphertext.to_vec();
+    ChaCha20::new(&chacha_key.into(), &chacha_nonce.into()).apply_keystream(&mut buffer);
+    unpad(&buffer)
+}
+
+// The ChaCha20 key, ChaCha20 nonce and HMAC key for one message
+fn message_keys(conversation_key: &[u8; 32], nonce: &[u8; 32]) -> ([u8; 32], [u8; 12], [u8; 32]) {
+    let hkdf = Hkdf::<Sha256>::from_prk(conversation_key).unwrap(); // 32 bytes is long enough
+    let mut keys = [0u8; 76];
+    hkdf.expand(nonce, &mut keys).unwrap(); // 76 bytes is short enough
+    let mut chacha_key = [0u8; 32];
+    let mut chacha_nonce = [0u8; 12];
+    let mut hmac_key = [0u8; 32];
+    chacha_key.copy_from_slice(&keys[..32]);
+    chacha_nonce.copy_from_slice(&keys[32..44]);
+    hmac_key.copy_from_slice(&keys[44..]);
+    (chacha_key, chacha_nonce, hmac_key)
+}
+
+// HMAC-SHA256 of the ciphertext, with the nonce as associated data
+fn hmac_aad(key: &[u8; 32], ciphertext: &[u8], nonce: &[u8; 32]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap(); // any key length works
+    mac.update(nonce);
+    mac.update(ciphertext);
+    mac
+}
+
+// The length plaintext of `len` bytes is padded to, hiding its exact size
+fn padded_len(len: usize) -> usize {
+    if len <= 32 {
+        return 32;
+    }
+    let next_power = 1usize << (usize::BITS - (len - 1).leading_zeros());
+    let chunk = if next_power <= 256 {
+        32
+    } else {
+        next_power / 8
+    };
+    chunk * ((len - 1) / chunk + 1)
+}
+
+fn pad(plaintext: &str) -> Result<Vec<u8>, Error> {
+    let bytes = plaintext.as_bytes();
+    if bytes.is_empty() || bytes.len() > MAX_PLAINTEXT {
+        return Err(Error::Nip44("invalid plaintext length".to_owned()));
+    }
+    let mut padded: Vec<u8> = Vec::with_capacity(2 + padded_len(bytes.len()));
+    padded.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    padded.extend_from_slice(bytes);
+    padded.resize(2 + padded_len(bytes.len()), 0);
+    Ok(padded)
+}
+
+fn unpad(padded: &[u8]) -> Result<String, Error> {
+    let invalid = || Error::Nip44("invalid padding".to_owned());
+    if padded.len() < 2 {
+        return Err(invalid());
+    }
+    let len = u16::from_be_bytes([padded[0], padded[1]]) as usize;
+    if len == 0 || padded.len() != 2 + padded_len(len) {
+        return Err(invalid());
+    }
+    String::from_utf8(padded[2..2 + len].to_vec()).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_nip44_padded_len() {
+        for (len, padded) in [
+            (16, 32),
+            (32, 32),
+            (33, 64),
+            (37, 64),
+            (45, 64),
+            (49, 64),
+            (64, 64),
+            (65, 96),
+            (100, 128),
+            (111, 128),
+            (200, 224),
+            (250, 256),
+            (320, 320),
+            (383, 384),
+            (384, 384),
+            (400, 448),
+            (500, 512),
+            (512, 512),
+            (515, 640),
+            (700, 768),
+            (800, 896),
+            (900, 1024),
+            (1020, 1024),
+            (65536, 65536),
+        ] {
+            assert_eq!(padded_len(len), padded, "length {len}");
+        }
+    }
+
+    // From the official NIP-44 test vectors
+    #[test]
+    fn test_nip44_vectors() {
+        let key = |hex: &str| PrivateKey::try_from_hex_string(hex).unwrap();
+        let sec1 = key("0000000000000000000000000000000000000000000000000000000000000001");
+        let sec2 = key("0000000000000000000000000000000000000000000000000000000000000002");
+        let conversation_key = sec1.nip44_conversation_key(&sec2.public_key());
+        assert_eq!(
+            hex::encode(conversation_key),
+            "c41c775356fd92eadc63ff5a0dc1da211b268cbea22316767095b2871ea1412d"
+        );
+        assert_eq!(
+            sec2.nip44_conversation_key(&sec1.public_key()),
+            conversation_key
+        );
+
+        let mut nonce = [0u8; 32];
+        nonce[31] = 1;
+        let payload = "AgAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAABee0G5VSK0/9YypIObAtDKfYEAjD35uVkHyB0F4DwrcNaCXlCWZKaArsGrY6M9wnuTMxWfp1RTN9Xga8no+kF5Vsb";
+        assert_eq!(encrypt(&conversation_key, "a", &nonce).unwrap(), payload);
+        assert_eq!(decrypt(&conversation_key, payload).unwrap(), "a");
+
+        let nonce: [u8; 32] =
+            hex::decode("f00000000000000000000000000000f00000000000000000000000000000000f")
+                .unwrap()
+                .try_into()
+                .unwrap();
+        let payload = "AvAAAAAAAAAAAAAAAAAAAPAAAAAAAAAAAAAAAAAAAAAPSKSK6is9ngkX2+cSq85Th16oRTISAOfhStnixqZziKMDvB0QQzgFZdjLTPicCJaV8nDITO+QfaQ61+KbWQIOO2Yj";
+        assert_eq!(encrypt(&conversation_key, "🍕🫃", &nonce).unwrap(), payload);
+        assert_eq!(decrypt(&conversation_key, payload).unwrap(), "🍕🫃");
+
+        let sec1 = key("315e59ff51cb9209768cf7da80791ddcaae56ac9775eb25b6dee1234bc5d2268");
+        let pub2 = PublicKey::try_from_hex_string(
+            "c2f9d9948dc8c7c38321e4b85c8558872eafa0641cd269db76848a6073e69133",
+        )
+        .unwrap();
+        assert_eq!(
+            hex::encode(sec1.nip44_conversation_key(&pub2)),
+            "3dfef0ce2a4d80a25e7a328accf73448ef67096f65f79588e358d9a0eb9013f1"
+        );
+    }
+
+    #[test]
+    fn test_nip44_round_trip() {
+        let alice = PrivateKey::generate();
+        let bob = PrivateKey::generate();
+        let message = "hello ".repeat(100);
+        let payload = alice.nip44_encrypt(&bob.public_key(), &message).unwrap();
+        assert_eq!(
+            bob.nip44_decrypt(&alice.public_key(), &payload).unwrap(),
+            message
+        );
+
+        // Tampering breaks the MAC
+        let mut data = base64::engine::general_purpose::STANDARD
+            .decode(&payload)
+            .unwrap();
+        data[40] ^= 1;
+        let tampered = base64::engine::general_purpose::STANDARD.encode(data);
+        assert!(matches!(
+            bob.nip44_decrypt(&alice.public_key(), &tampered),
+            Err(Error::Nip44(_))
+        ));
+
+        assert!(alice.nip44_encrypt(&bob.public_key(), "").is_err());
+        assert!(bob
+            .nip44_decrypt(
+                &alice.public_key(),
+                &format!("#{}", payload.get(1..).unwrap())
+            )
+            .is_err());
+    }
+}
@@ -0,0 +1,1124 @@
+use super::nip44::{nip44_decrypt_with_shared_secret, nip44_encrypt_with_shared_secret};
+use super::private_key::{nip04_decrypt_with_shared_secret, nip04_encrypt_with_shared_secret};
+use super::{Event, EventKind, PreEvent, PrivateKey, PublicKey, RelayUrl, Tag, Unixtime};
+use crate::Error;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Something that can sign events as a user: a local private key, or a remote
+/// signer reached through a `Nip46Session`
+pub trait Signer {
+    /// The public key events are signed as
+    fn public_key(&self) -> PublicKey;
+
+    /// Sign an event. Its `pubkey` must be the signer's public key.
+    fn sign_event(&self, input: PreEvent) -> Result<Event, Error>;
+
+    /// The ECDH shared secret with `peer` (the x coordinate of the shared
+    /// point), as used by NIP-04 and NIP-44. Signers which do not expose it
+    /// return `Error::SignerRefused`.
+    fn ecdh(&self, _peer: &PublicKey) -> Result<[u8; 32], Error> {
+        Err(Error::SignerRefused("ECDH is not supported".to_owned()))
+    }
+}
+
+impl Signer for PrivateKey {
+    fn public_key(&self) -> PublicKey {
+        PrivateKey::public_key(self)
+    }
+
+    fn sign_event(&self, input: PreEvent) -> Result<Event, Error> {
+        Event::new(input, self)
+    }
+
+    fn ecdh(&self, peer: &PublicKey) -> Result<[u8; 32], Error> {
+        Ok(self.shared_secret_bytes(peer))
+    }
+}
+
+/// A request to a remote signer (NIP-46)
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Nip46Request {
+    /// A string identifying the request, repeated in the response
+    pub id: String,
+
+    /// The method, e.g. "connect" or "sign_event"
+    pub method: String,
+
+    /// The method's parameters
+    pub params: Vec<String>,
+}
+
+/// A remote signer's response to a `Nip46Request`
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Nip46Response {
+    /// The id of the request
+    pub id: String,
+
+    /// The result, or "auth_url" if the user must approve the request at the
+    /// URL given in `error`
+    #[serde(default)]
+    pub result: String,
+
+    /// An error message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A remote signer's connection string:
+/// "bunker://<remote-signer-pubkey>?relay=<url>&relay=<url>&secret=<secret>"
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BunkerUrl {
+    /// The remote signer's public key
+    pub remote_signer: PublicKey,
+
+    /// The relays it listens on
+    pub relays: Vec<RelayUrl>,
+
+    /// A single-use secret to present on connecting
+    pub secret: Option<String>,
+}
+
+impl BunkerUrl {
+    /// Parse a bunker URL
+    pub fn try_from_str(s: &str) -> Result<BunkerUrl, Error> {
+        let url = url::Url::parse(s.trim())?;
+        if url.scheme() != "bunker" {
+            return Err(Error::InvalidUrlScheme(url.scheme().to_owned()));
+        }
+        let remote_signer = PublicKey::try_from_hex_string(url.host_str().unwrap_or(""))?;
+        let mut relays: Vec<RelayUrl> = Vec::new();
+        let mut secret: Option<String> = None;
+        for (key, value) in url.query_pairs() {
+            match &*key {
+                "relay" => relays.push(RelayUrl::try_from_str(&value)?),
+                "secret" => secret = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+        Ok(BunkerUrl {
+            remote_signer,
+            relays,
+            secret,
+        })
+    }
+}
+
+/// Carries NIP-46 messages to and from the remote signer's relays
+pub trait Nip46Transport {
+    /// Publish a request event to the remote signer's relays
+    fn send(&self, event: Event) -> Result<(), Error>;
+
+    /// Wait up to `timeout` for the next kind 24133 event tagged with the
+    /// client's public key. Returns None if none arrived in time.
+    fn receive(&self, timeout: Duration) -> Result<Option<Event>, Error>;
+}
+
+type Cipher = Box<dyn Fn(&PrivateKey, &PublicKey, &str) -> Result<String, Error> + Send + Sync>;
+
+/// A session with a remote signer (NIP-46), which signs events for the user
+/// so the app never holds their key.
+///
+/// The session makes requests from its own client key over a `Nip46Transport`
+/// and blocks until the answer arrives or the timeout passes. Requests may be
+/// made from several threads at once; responses that arrive for another
+/// request are queued for it. When the signer asks the user to approve a
+/// request at a URL, the `on_auth_url` callback is called and the request
+/// waits for up to the timeout again.
+///
+/// Once connected it is a `Signer`, so it can stand in for a local key:
+///
+/// ```ignore
+/// let signer: Box<dyn Signer> = Box::new(Nip46Session::new(transport, key, &bunker).connect()?);
+/// ```
+///
+/// Messages are encrypted with NIP-44. Call `nip04()` for signers that only
+/// support NIP-04, or `cipher()` to provide another encryption.
+pub struct Nip46Session<T: Nip46Transport> {
+    transport: T,
+    client_key: PrivateKey,
+    remote_signer: PublicKey,
+    user_pubkey: PublicKey,
+    secret: Option<String>,
+    permissions: Vec<String>,
+    timeout: Duration,
+    on_auth_url: Option<Box<dyn Fn(&str) + Send + Sync>>,
+    encrypt: Cipher,
+    decrypt: Cipher,
+    next_id: AtomicU64,
+    pending: Mutex<HashSet<String>>,
+    queue: Mutex<HashMap<String, Nip46Response>>,
+}
+
+impl<T: Nip46Transport> fmt::Debug for Nip46Session<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Nip46Session")
+            .field("remote_signer", &self.remote_signer)
+            .field("user_pubkey", &self.user_pubkey)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+impl<T: Nip46Transport> Nip46Session<T> {
+    /// Create a session with the signer at `bunker`, making requests as
+    /// `client_key`. It must `connect()` before use.
+    pub fn new(transport: T, client_key: PrivateKey, bunker: &BunkerUrl) -> Nip46Session<T> {
+        Nip46Session {
+            transport,
+            client_key,
+            remote_signer: bunker.remote_signer,
+            user_pubkey: bunker.remote_signer,
+            secret: bunker.secret.clone(),
+            permissions: vec![],
+            timeout: Duration::from_secs(30),
+            on_auth_url: None,
+            encrypt: Box::new(nip44_encrypt),
+            decrypt: Box::new(nip44_decrypt),
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashSet::new()),
+            queue: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Ask for these permissions on connecting, e.g. "sign_event:1"
+    pub fn permissions(mut self, permissions: &[&str]) -> Nip46Session<T> {
+        self.permissions = permissions.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    /// How long to wait for each response. The default is 30 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Nip46Session<T> {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Call `f` with the URL when the signer needs the user to approve a
+    /// request there
+    pub fn on_auth_url<F>(mut self, f: F) -> Nip46Session<T>
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.on_auth_url = Some(Box::new(f));
+        self
+    }
+
+    /// Encrypt messages with NIP-04 rather than NIP-44
+    pub fn nip04(self) -> Nip46Session<T> {
+        self.cipher(nip04_encrypt, nip04_decrypt)
+    }
+
+    /// Encrypt and decrypt messages with these functions rather than NIP-44.
+    /// Each is given the client key, the remote signer and the text.
+    pub fn cipher<E, D>(mut self, encrypt: E, decrypt: D) -> Nip46Session<T>
+    where
+        E: Fn(&PrivateKey, &PublicKey, &str) -> Result<String, Error> + Send + Sync + 'static,
+        D: Fn(&PrivateKey, &PublicKey, &str) -> Result<String, Error> + Send + Sync + 'static,
+    {
+        self.encrypt = Box::new(encrypt);
+        self.decrypt = Box::new(decrypt);
+        self
+    }
+
+    /// Connect to the remote signer, presenting the secret and asking for the
+    /// permissions, then learn the user's public key
+    pub fn connect(mut self) -> Result<Nip46Session<T>, Error> {
+        let params = vec![
+            self.remote_signer.as_hex_string(),
+            self.secret.clone().unwrap_or_default(),
+            self.permissions.join(","),
+        ];
+        let result = self.request("connect", params)?;
+        if result != "ack" && Some(&result) != self.secret.as_ref() {
+            return Err(Error::Nip46(format!(
+                "unexpected connect result: {}",
+                result
+            )));
+        }
+        let pubkey = self.request("get_public_key", vec![])?;
+        self.user_pubkey = PublicKey::try_from_hex_string(&pubkey)?;
+        Ok(self)
+    }
+
+    /// The remote signer's public key
+    pub fn remote_signer(&self) -> PublicKey {
+        self.remote_signer
+    }
+
+    /// Make a request and wait for its result
+    pub fn request(&self, method: &str, params: Vec<String>) -> Result<String, Error> {
+        let request = Nip46Request {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed).to_string(),
+            method: method.to_owned(),
+            params,
+        };
+        let content = (self.encrypt)(
+            &self.client_key,
+            &self.remote_signer,
+            &serde_json::to_string(&request)?,
+        )?;
+        let event = Event::new(
+            PreEvent {
+                pubkey: self.client_key.public_key(),
+                created_at: Unixtime::now()?,
+                kind: EventKind::NostrConnect,
+                tags: vec![Tag::Pubkey {
+                    pubkey: self.remote_signer.into(),
+                    recommended_relay_url: None,
+                    petname: None,
+                }],
+                content,
+                ots: None,
+            },
+            &self.client_key,
+        )?;
+
+        let _ = self.pending.lock().unwrap().insert(request.id.clone());
+        let result = self.transport.send(event).and_then(|_| self.wait(&request));
+        let _ = self.pending.lock().unwrap().remove(&request.id);
+        let _ = self.queue.lock().unwrap().remove(&request.id);
+        result
+    }
+
+    fn wait(&self, request: &Nip46Request) -> Result<String, Error> {
+        let mut deadline = Instant::now() + self.timeout;
+        loop {
+            let queued = self.queue.lock().unwrap().remove(&request.id);
+            if let Some(response) = queued {
+                if response.result == "auth_url" {
+                    if let (Some(f), Some(url)) = (&self.on_auth_url, &response.error) {
+                        f(url);
+                    }
+                    // The user may take a while to approve it
+                    deadline = Instant::now() + self.timeout;
+                    continue;
+                }
+                return match response.error {
+                    Some(error) if !error.is_empty() => Err(Error::Nip46(error)),
+                    _ => Ok(response.result),
+                };
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::Nip46Timeout(request.method.clone()));
+            }
+            if let Some(event) = self.transport.receive(deadline - now)? {
+                if let Some(response) = self.open(&event) {
+                    if self.pending.lock().unwrap().contains(&response.id) {
+                        let _ = self
+                            .queue
+                            .lock()
+                            .unwrap()
+                            .insert(response.id.clone(), response);
+                    }
+                }
+            }
+        }
+    }
+
+    // Read a response, ignoring events which are not one from our signer
+    fn open(&self, event: &Event) -> Option<Nip46Response> {
+        if event.kind != EventKind::NostrConnect
+            || event.pubkey != self.remote_signer
+            || event.verify(None).is_err()
+        {
+            return None;
+        }
+        let json = (self.decrypt)(&self.client_key, &self.remote_signer, &event.content).ok()?;
+        serde_json::from_str(&json).ok()
+    }
+}
+
+impl<T: Nip46Transport> Signer for Nip46Session<T> {
+    fn public_key(&self) -> PublicKey {
+        self.user_pubkey
+    }
+
+    fn sign_event(&self, input: PreEvent) -> Result<Event, Error> {
+        if input.pubkey != self.user_pubkey {
+            return Err(Error::AssertionFailed(
+                "Event is not authored by the signer".to_owned(),
+            ));
+        }
+        let unsigned = serde_json::json!({
+            "kind": input.kind,
+            "content": input.content,
+            "tags": input.tags,
+            "created_at": input.created_at,
+        });
+        let result = self.request("sign_event", vec![unsigned.to_string()])?;
+        let event: Event = serde_json::from_str(&result)?;
+        event.verify(None)?;
+        if event.pubkey != input.pubkey
+            || event.kind != input.kind
+            || event.content != input.content
+            || event.tags != input.tags
+            || event.created_at != input.created_at
+        {
+            return Err(Error::Nip46(
+                "signed event does not match the request".to_owned(),
+            ));
+        }
+        Ok(event)
+    }
+}
+
+/// A remote signer's decision about a request (see `Nip46Policy`)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Nip46Decision {
+    /// Carry out the request
+    Allow,
+
+    /// Refuse the request, with a reason for the client
+    Deny(String),
+
+    /// Ask the user to approve the request at this URL. The client keeps
+    /// waiting, and the signer should handle the request again once approved.
+    AuthUrl(String),
+}
+
+/// Decides which requests a `Nip46Service` carries out. Any
+/// `Fn(&PublicKey, &Nip46Request) -> Nip46Decision` is also a policy.
+pub trait Nip46Policy: Send + Sync {
+    /// Decide on a request from the client with public key `client`
+    fn decide(&self, client: &PublicKey, request: &Nip46Request) -> Nip46Decision;
+}
+
+impl<F> Nip46Policy for F
+where
+    F: Fn(&PublicKey, &Nip46Request) -> Nip46Decision + Send + Sync,
+{
+    fn decide(&self, client: &PublicKey, request: &Nip46Request) -> Nip46Decision {
+        self(client, request)
+    }
+}
+
+/// The signer side of NIP-46, for building a remote signer ("bunker").
+///
+/// Feed it the kind 24133 events addressed to `service_key` and publish the
+/// responses it returns. Clients must first connect with the secret, if one
+/// is set; secrets are single-use. After that their requests go to the
+/// policy, if one is set, and allowed ones are carried out by the local
+/// `Signer`.
+///
+/// A service with neither a secret nor a policy refuses every connect, since
+/// otherwise anyone could have events signed.
+///
+/// It answers "connect", "get_public_key", "ping", "sign_event", and the
+/// "nip04_*" and "nip44_*" methods, which use the signer's `ecdh()` so that
+/// a `SignerPolicy` around it decides which peers they may be used with.
+pub struct Nip46Service<S: Signer> {
+    signer: S,
+    service_key: PrivateKey,
+    secret: Option<String>,
+    secret_used: AtomicBool,
+    policy: Option<Box<dyn Nip46Policy>>,
+    encrypt: Cipher,
+    decrypt: Cipher,
+    clients: Mutex<HashSet<PublicKey>>,
+}
+
+impl<S: Signer> fmt::Debug for Nip46Service<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Nip46Service")
+            .field("signer", &self.signer.public_key())
+            .field("service", &self.service_key.public_key())
+            .field("clients", &self.clients.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl<S: Signer> Nip46Service<S> {
+    /// Sign with `signer` for clients that reach us at `service_key`'s public
+    /// key, which may be the user's own
+    pub fn new(signer: S, service_key: PrivateKey) -> Nip46Service<S> {
+        Nip46Service {
+            signer,
+            service_key,
+            secret: None,
+            secret_used: AtomicBool::new(false),
+            policy: None,
+            encrypt: Box::new(nip44_encrypt),
+            decrypt: Box::new(nip44_decrypt),
+            clients: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Require clients to connect with this secret. Only the first client to
+    /// present it is let in; every later connect is refused.
+    pub fn secret(mut self, secret: &str) -> Nip46Service<S> {
+        self.secret = Some(secret.to_owned());
+        self
+    }
+
+    /// Decide on requests with this policy. Without one, connected clients
+    /// may make any request.
+    pub fn policy<P: Nip46Policy + 'static>(mut self, policy: P) -> Nip46Service<S> {
+        self.policy = Some(Box::new(policy));
+        self
+    }
+
+    /// Encrypt messages with NIP-04 rather than NIP-44
+    pub fn nip04(self) -> Nip46Service<S> {
+        self.cipher(nip04_encrypt, nip04_decrypt)
+    }
+
+    /// Encrypt and decrypt messages with these functions rather than NIP-44.
+    /// Each is given the service key, the client and the text.
+    pub fn cipher<E, D>(mut self, encrypt: E, decrypt: D) -> Nip46Service<S>
+    where
+        E: Fn(&PrivateKey, &PublicKey, &str) -> Result<String, Error> + Send + Sync + 'static,
+        D: Fn(&PrivateKey, &PublicKey, &str) -> Result<String, Error> + Send + Sync + 'static,
+    {
+        self.encrypt = Box::new(encrypt);
+        self.decrypt = Box::new(decrypt);
+        self
+    }
+
+    /// The bunker URL for clients to connect with
+    pub fn bunker_url(&self, relays: Vec<RelayUrl>) -> BunkerUrl {
+        BunkerUrl {
+            remote_signer: self.service_key.public_key(),
+            relays,
+            secret: self.secret.clone(),
+        }
+    }
+
+    /// Has this client connected?
+    pub fn is_connected(&self, client: &PublicKey) -> bool {
+        self.clients.lock().unwrap().contains(client)
+    }
+
+    /// Handle an incoming event, returning the response event to publish.
+    /// Returns None for events that are not requests to this service.
+    pub fn handle(&self, event: &Event, now: Unixtime) -> Result<Option<Event>, Error> {
+        if event.kind != EventKind::NostrConnect || event.verify(None).is_err() {
+            return Ok(None);
+        }
+        let service = self.service_key.public_key();
+        let addressed = event.tags.iter().any(|tag| match tag {
+            Tag::Pubkey { pubkey, .. } => PublicKey::try_from(pubkey.clone()).ok() == Some(service),
+            _ => false,
+        });
+        if !addressed {
+            return Ok(None);
+        }
+        let json = match (self.decrypt)(&self.service_key, &event.pubkey, &event.content) {
+            Ok(json) => json,
+            Err(_) => return Ok(None),
+        };
+        let request: Nip46Request = match serde_json::from_str(&json) {
+            Ok(request) => request,
+            Err(_) => return Ok(None),
+        };
+
+        let response = self.answer(&event.pubkey, &request);
+        let content = (self.encrypt)(
+            &self.service_key,
+            &event.pubkey,
+            &serde_json::to_string(&response)?,
+        )?;
+        let pre = PreEvent {
+            pubkey: service,
+            created_at: now,
+            kind: EventKind::NostrConnect,
+            tags: vec![Tag::Pubkey {
+                pubkey: event.pubkey.into(),
+                recommended_relay_url: None,
+                petname: None,
+            }],
+            content,
+            ots: None,
+        };
+        Ok(Some(Event::new(pre, &self.service_key)?))
+    }
+
+    // Carry out a request, or explain why not
+    fn answer(&self, client: &PublicKey, request: &Nip46Request) -> Nip46Response {
+        let respond = |result: String, error: Option<String>| Nip46Response {
+            id: request.id.clone(),
+            result,
+            error,
+        };
+        let deny = |reason: &str| respond("".to_owned(), Some(reason.to_owned()));
+
+        if request.method == "connect" {
+            match &self.secret {
+                Some(expected) => {
+                    if request.params.get(1) != Some(expected)
+                        || self.secret_used.load(Ordering::SeqCst)
+                    {
+                        return deny("invalid secret");
+                    }
+                }
+                None if self.policy.is_none() => return deny("connections are not allowed"),
+                None => {}
+            }
+        } else if !self.is_connected(client) {
+            return deny("not connected");
+        }
+
+        let decision = match &self.policy {
+            Some(policy) => policy.decide(client, request),
+            None => Nip46Decision::Allow,
+        };
+        match decision {
+            Nip46Decision::Allow => {}
+            Nip46Decision::Deny(reason) => return deny(&reason),
+            Nip46Decision::AuthUrl(url) => return respond("auth_url".to_owned(), Some(url)),
+        }
+
+        match request.method.as_str() {
+            "connect" => {
+                // Checked again, so that of two clients racing with the
+                // secret only one gets in
+                if self.secret.is_some() && self.secret_used.swap(true, Ordering::SeqCst) {
+                    return deny("invalid secret");
+                }
+                let _ = self.clients.lock().unwrap().insert(*client);
+                respond("ack".to_owned(), None)
+            }
+            "ping" => respond("pong".to_owned(), None),
+            "get_public_key" => respond(self.signer.public_key().as_hex_string(), None),
+            "sign_event" => match self.sign(request) {
+                Ok(json) => respond(json, None),
+                Err(e) => deny(&e.to_string()),
+            },
+            "nip04_encrypt" | "nip04_decrypt" | "nip44_encrypt" | "nip44_decrypt" => {
+                match self.crypt(request) {
+                    Ok(text) => respond(text, None),
+                    Err(e) => deny(&e.to_string()),
+                }
+            }
+            _ => deny("unsupported method"),
+        }
+    }
+
+    fn sign(&self, request: &Nip46Request) -> Result<String, Error> {
+        let json = request
+            .params
+            .first()
+            .ok_or_else(|| Error::AssertionFailed("sign_event needs an event".to_owned()))?;
+        let unsigned: serde_json::Value = serde_json::from_str(json)?;
+        let pre = PreEvent {
+            pubkey: self.signer.public_key(),
+            created_at: serde_json::from_value(unsigned["created_at"].clone())?,
+            kind: serde_json::from_value(unsigned["kind"].clone())?,
+            tags: serde_json::from_value(unsigned["tags"].clone())?,
+            content: serde_json::from_value(unsigned["content"].clone())?,
+            ots: None,
+        };
+        Ok(serde_json::to_string(&self.signer.sign_event(pre)?)?)
+    }
+
+    // Encrypt or decrypt for a third party with the signer's shared secret
+    fn crypt(&self, request: &Nip46Request) -> Result<String, Error> {
+        let missing =
+            || Error::AssertionFailed(format!("{} needs a public key and a text", request.method));
+        let peer = PublicKey::try_from_hex_string(request.params.first().ok_or_else(missing)?)?;
+        let text = request.params.get(1).ok_or_else(missing)?;
+        let shared_secret = self.signer.ecdh(&peer)?;
+        match request.method.as_str() {
+            "nip04_encrypt" => Ok(nip04_seal(&shared_secret, text)),
+            "nip04_decrypt" => nip04_open(&shared_secret, text),
+            "nip44_encrypt" => nip44_encrypt_with_shared_secret(&shared_secret, text),
+            _ => nip44_decrypt_with_shared_secret(&shared_secret, text),
+        }
+    }
+}
+
+fn nip44_encrypt(key: &PrivateKey, other: &PublicKey, plaintext: &str) -> Result<String, Error> {
+    key.nip44_encrypt(other, plaintext)
+}
+
+fn nip44_decrypt(key: &PrivateKey, other: &PublicKey, payload: &str) -> Result<String, Error> {
+    key.nip44_decrypt(other, payload)
+}
+
+fn nip04_encrypt(key: &PrivateKey, other: &PublicKey, plaintext: &str) -> Result<String, Error> {
+    Ok(nip04_seal(&key.shared_secret_bytes(other), plaintext))
+}
+
+fn nip04_decrypt(key: &PrivateKey, other: &PublicKey, content: &str) -> Result<String, Error> {
+    nip04_open(&key.shared_secret_bytes(other), content)
+}
+
+// NIP-04 content is "<ciphertext>?iv=<iv>", both in base64
+fn nip04_seal(shared_secret: &[u8; 32], plaintext: &str) -> String {
+    let (iv, ciphertext) = nip04_encrypt_with_shared_secret(shared_secret, plaintext.as_bytes());
+    format!(
+        "{}?iv={}",
+        base64::engine::general_purpose::STANDARD.encode(ciphertext),
+        base64::engine::general_purpose::STANDARD.encode(iv)
+    )
+}
+
+fn nip04_open(shared_secret: &[u8; 32], content: &str) -> Result<String, Error> {
+    let (ciphertext, iv) = content
+        .split_once("?iv=")
+        .ok_or(Error::BadEncryptedMessage)?;
+    let ciphertext: Vec<u8> = base64::engine::general_purpose::STANDARD.decode(ciphertext)?;
+    let iv: [u8; 16] = base64::engine::general_purpose::STANDARD
+        .decode(iv)?
+        .try_into()
+        .map_err(|_| Error::BadEncryptedMessage)?;
+    let plaintext = nip04_decrypt_with_shared_secret(shared_secret, &ciphertext, iv)?;
+    Ok(String::from_utf8_lossy(&plaintext).into())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Arc;
+
+    // The mock bunker's user, as hex so that tests can sign with it locally too
+    const USER_KEY: &str = "ee35e8bb71131c02c1d7e73231daa48e9953d329a4b701f7133c8f46dd21139c";
+
+    // A remote signer answering requests as they are sent
+    struct MockBunker {
+        signer_key: PrivateKey,
+        user_key: PrivateKey,
+        secret: String,
+        ask_approval: bool,
+        nip04: bool,
+        inbox: Mutex<VecDeque<Event>>,
+    }
+
+    impl MockBunker {
+        fn respond(&self, client: &PublicKey, response: Nip46Response) {
+            let encrypt = if self.nip04 {
+                nip04_encrypt
+            } else {
+                nip44_encrypt
+            };
+            let content = encrypt(
+                &self.signer_key,
+                client,
+                &serde_json::to_string(&response).unwrap(),
+            )
+            .unwrap();
+            let pre = PreEvent {
+                pubkey: self.signer_key.public_key(),
+                created_at: Unixtime::now().unwrap(),
+                kind: EventKind::NostrConnect,
+                tags: vec![],
+                content,
+                ots: None,
+            };
+            let event = Event::new(pre, &self.signer_key).unwrap();
+            self.inbox.lock().unwrap().push_back(event);
+        }
+    }
+
+    impl Nip46Transport for Arc<MockBunker> {
+        fn send(&self, event: Event) -> Result<(), Error> {
+            let decrypt = if self.nip04 {
+                nip04_decrypt
+            } else {
+                nip44_decrypt
+            };
+            let json = decrypt(&self.signer_key, &event.pubkey, &event.content)?;
+            let request: Nip46Request = serde_json::from_str(&json)?;
+            let answer = |result: &str, error: Option<&str>| Nip46Response {
+                id: request.id.clone(),
+                result: result.to_owned(),
+                error: error.map(|e| e.to_owned()),
+            };
+            let response = match request.method.as_str() {
+                "connect" if request.params.get(1) == Some(&self.secret) => answer("ack", None),
+                "connect" => answer("", Some("bad secret")),
+                "get_public_key" => answer(&self.user_key.public_key().as_hex_string(), None),
+                "sign_event" => {
+                    if self.ask_approval {
+                        self.respond(
+                            &event.pubkey,
+                            answer("auth_url", Some("https://bunker.example.com/approve")),
+                        );
+                    }
+                    let unsigned: serde_json::Value = serde_json::from_str(&request.params[0])?;
+                    let pre = PreEvent {
+                        pubkey: self.user_key.public_key(),
+                        created_at: serde_json::from_value(unsigned["created_at"].clone())?,
+                        kind: serde_json::from_value(unsigned["kind"].clone())?,
+                        tags: serde_json::from_value(unsigned["tags"].clone())?,
+                        content: serde_json::from_value(unsigned["content"].clone())?,
+                        ots: None,
+                    };
+                    let signed = Event::new(pre, &self.user_key)?;
+                    answer(&serde_json::to_string(&signed)?, None)
+                }
+                _ => answer("", Some("unsupported")),
+            };
+            self.respond(&event.pubkey, response);
+            Ok(())
+        }
+
+        fn receive(&self, timeout: Duration) -> Result<Option<Event>, Error> {
+            let event = self.inbox.lock().unwrap().pop_front();
+            if event.is_none() {
+                std::thread::sleep(timeout);
+            }
+            Ok(event)
+        }
+    }
+
+    fn bunker(ask_approval: bool, nip04: bool) -> (Arc<MockBunker>, BunkerUrl) {
+        let mock = Arc::new(MockBunker {
+            signer_key: PrivateKey::generate(),
+            user_key: PrivateKey::try_from_hex_string(USER_KEY).unwrap(),
+            secret: "s3cret".to_owned(),
+            ask_approval,
+            nip04,
+            inbox: Mutex::new(VecDeque::new()),
+        });
+        let url = format!(
+            "bunker://{}?relay=wss://relay.example.com&secret=s3cret",
+            mock.signer_key.public_key().as_hex_string()
+        );
+        (mock, BunkerUrl::try_from_str(&url).unwrap())
+    }
+
+    #[test]
+    fn test_bunker_url() {
+        let (mock, url) = bunker(false, false);
+        assert_eq!(url.remote_signer, mock.signer_key.public_key());
+        assert_eq!(
+            url.relays,
+            vec![RelayUrl::try_from_str("wss://relay.example.com").unwrap()]
+        );
+        assert_eq!(url.secret.as_deref(), Some("s3cret"));
+        assert!(BunkerUrl::try_from_str("nostrconnect://abc").is_err());
+    }
+
+    #[test]
+    fn test_nip46_session() {
+        let (mock, url) = bunker(true, false);
+        let approvals = Arc::new(Mutex::new(Vec::new()));
+        let seen = approvals.clone();
+        let session = Nip46Session::new(mock.clone(), PrivateKey::generate(), &url)
+            .permissions(&["sign_event:1"])
+            .on_auth_url(move |url| seen.lock().unwrap().push(url.to_owned()))
+            .connect()
+            .unwrap();
+
+        let signer: Box<dyn Signer> = Box::new(session);
+        assert_eq!(signer.public_key(), mock.user_key.public_key());
+        let pre = PreEvent {
+            pubkey: signer.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::TextNote,
+            tags: vec![],
+            content: "signed remotely".to_owned(),
+            ots: None,
+        };
+        let event = signer.sign_event(pre.clone()).unwrap();
+        assert!(event.verify(None).is_ok());
+        assert_eq!(event.content, pre.content);
+        assert_eq!(
+            *approvals.lock().unwrap(),
+            vec!["https://bunker.example.com/approve".to_owned()]
+        );
+
+        // The same code signs with a local key
+        let local: Box<dyn Signer> = Box::new(PrivateKey::try_from_hex_string(USER_KEY).unwrap());
+        assert_eq!(local.sign_event(pre).unwrap().pubkey, event.pubkey);
+    }
+
+    #[test]
+    fn test_nip46_session_errors() {
+        let (mock, mut url) = bunker(false, false);
+        url.secret = Some("wrong".to_owned());
+        let result = Nip46Session::new(mock.clone(), PrivateKey::generate(), &url).connect();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Remote signer error: bad secret"
+        );
+
+        // A signer that never answers
+        struct Silent;
+        impl Nip46Transport for Silent {
+            fn send(&self, _event: Event) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn receive(&self, timeout: Duration) -> Result<Option<Event>, Error> {
+                std::thread::sleep(timeout);
+                Ok(None)
+            }
+        }
+        let result = Nip46Session::new(Silent, PrivateKey::generate(), &url)
+            .timeout(Duration::from_millis(20))
+            .connect();
+        assert!(matches!(result, Err(Error::Nip46Timeout(_))));
+    }
+
+    #[test]
+    fn test_nip46_session_nip04() {
+        let (mock, url) = bunker(false, true);
+        let session = Nip46Session::new(mock.clone(), PrivateKey::generate(), &url)
+            .nip04()
+            .connect()
+            .unwrap();
+        assert_eq!(session.public_key(), mock.user_key.public_key());
+    }
+
+    #[test]
+    fn test_nip46_auth_url_extends_timeout() {
+        // Answers with an auth_url and then the result, each after most of
+        // the timeout
+        struct SlowApproval {
+            signer_key: PrivateKey,
+            replies: Mutex<VecDeque<Event>>,
+        }
+        impl Nip46Transport for SlowApproval {
+            fn send(&self, event: Event) -> Result<(), Error> {
+                let json = nip44_decrypt(&self.signer_key, &event.pubkey, &event.content)?;
+                let request: Nip46Request = serde_json::from_str(&json)?;
+                let approve = Some("https://bunker.example.com/approve".to_owned());
+                for (result, error) in [("auth_url", approve), ("pong", None)] {
+                    let response = Nip46Response {
+                        id: request.id.clone(),
+                        result: result.to_owned(),
+                        error,
+                    };
+                    let pre = PreEvent {
+                        pubkey: self.signer_key.public_key(),
+                        created_at: Unixtime::now()?,
+                        kind: EventKind::NostrConnect,
+                        tags: vec![],
+                        content: nip44_encrypt(
+                            &self.signer_key,
+                            &event.pubkey,
+                            &serde_json::to_string(&response)?,
+                        )?,
+                        ots: None,
+                    };
+                    self.replies
+                        .lock()
+                        .unwrap()
+                        .push_back(Event::new(pre, &self.signer_key)?);
+                }
+                Ok(())
+            }
+
+            fn receive(&self, timeout: Duration) -> Result<Option<Event>, Error> {
+                let delay = Duration::from_millis(150);
+                if timeout < delay {
+                    std::thread::sleep(timeout);
+                    return Ok(None);
+                }
+                std::thread::sleep(delay);
+                Ok(self.replies.lock().unwrap().pop_front())
+            }
+        }
+
+        let signer_key = PrivateKey::generate();
+        let url = BunkerUrl {
+            remote_signer: signer_key.public_key(),
+            relays: vec![],
+            secret: None,
+        };
+        let transport = SlowApproval {
+            signer_key,
+            replies: Mutex::new(VecDeque::new()),
+        };
+        let session = Nip46Session::new(transport, PrivateKey::generate(), &url)
+            .timeout(Duration::from_millis(200));
+        assert_eq!(session.request("ping", vec![]).unwrap(), "pong");
+    }
+
+    // Carries requests straight to a service
+    struct Loopback<S: Signer> {
+        service: Nip46Service<S>,
+        inbox: Mutex<VecDeque<Event>>,
+    }
+
+    impl<S: Signer> Nip46Transport for Arc<Loopback<S>> {
+        fn send(&self, event: Event) -> Result<(), Error> {
+            if let Some(response) = self.service.handle(&event, Unixtime::now()?)? {
+                self.inbox.lock().unwrap().push_back(response);
+            }
+            Ok(())
+        }
+
+        fn receive(&self, timeout: Duration) -> Result<Option<Event>, Error> {
+            let event = self.inbox.lock().unwrap().pop_front();
+            if event.is_none() {
+                std::thread::sleep(timeout);
+            }
+            Ok(event)
+        }
+    }
+
+    #[test]
+    fn test_nip46_service() {
+        let user_key = PrivateKey::generate();
+        let no_dms = |_: &PublicKey, request: &Nip46Request| {
+            if request.method == "sign_event" && request.params[0].contains("\"kind\":4,") {
+                Nip46Decision::Deny("kind 4 is not allowed".to_owned())
+            } else {
+                Nip46Decision::Allow
+            }
+        };
+        let user = user_key.public_key();
+        let service = Nip46Service::new(user_key, PrivateKey::generate())
+            .secret("abc")
+            .policy(no_dms);
+        let url = service.bunker_url(vec![
+            RelayUrl::try_from_str("wss://relay.example.com").unwrap()
+        ]);
+        assert_eq!(url.secret.as_deref(), Some("abc"));
+        let loopback = Arc::new(Loopback {
+            service,
+            inbox: Mutex::new(VecDeque::new()),
+        });
+
+        let client_key = PrivateKey::generate();
+        let client = client_key.public_key();
+        let session = Nip46Session::new(loopback.clone(), client_key, &url)
+            .timeout(Duration::from_millis(200))
+            .connect()
+            .unwrap();
+        assert!(loopback.service.is_connected(&client));
+        assert_eq!(session.public_key(), user);
+        assert_eq!(session.request("ping", vec![]).unwrap(), "pong");
+
+        let mut pre = PreEvent {
+            pubkey: user,
+            created_at: Unixtime(1700000000),
+            kind: EventKind::TextNote,
+            tags: vec![],
+            content: "hello".to_owned(),
+            ots: None,
+        };
+        let event = session.sign_event(pre.clone()).unwrap();
+        assert_eq!(event.pubkey, user);
+        pre.kind = EventKind::EncryptedDirectMessage;
+        assert_eq!(
+            session.sign_event(pre).unwrap_err().to_string(),
+            "Remote signer error: kind 4 is not allowed"
+        );
+
+        // The secret was used up, and unconnected clients are refused
+        let result = Nip46Session::new(loopback.clone(), PrivateKey::generate(), &url)
+            .timeout(Duration::from_millis(200))
+            .connect();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Remote signer error: invalid secret"
+        );
+        let mut no_secret = url.clone();
+        no_secret.secret = None;
+        let result = Nip46Session::new(loopback.clone(), PrivateKey::generate(), &no_secret)
+            .timeout(Duration::from_millis(200))
+            .connect();
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Remote signer error: invalid secret"
+        );
+        let stranger = Nip46Session::new(loopback, PrivateKey::generate(), &url)
+            .timeout(Duration::from_millis(200));
+        assert_eq!(
+            stranger
+                .request("get_public_key", vec![])
+                .unwrap_err()
+                .to_string(),
+            "Remote signer error: not connected"
+        );
+    }
+
+    #[test]
+    fn test_nip46_service_encryption() {
+        let user_key = PrivateKey::generate();
+        let user = user_key.public_key();
+        let friend_key = PrivateKey::generate();
+        let friend = friend_key.public_key();
+        let signer = crate::SignerPolicy::new(user_key).allow_peers([friend]);
+        let service = Nip46Service::new(signer, PrivateKey::generate()).secret("abc");
+        let url = service.bunker_url(vec![]);
+        let loopback = Arc::new(Loopback {
+            service,
+            inbox: Mutex::new(VecDeque::new()),
+        });
+        let session = Nip46Session::new(loopback, PrivateKey::generate(), &url)
+            .timeout(Duration::from_millis(200))
+            .connect()
+            .unwrap();
+        let request = |method: &str, text: String| {
+            session.request(method, vec![friend.as_hex_string(), text])
+        };
+
+        let payload = request("nip44_encrypt", "hi".to_owned()).unwrap();
+        assert_eq!(friend_key.nip44_decrypt(&user, &payload).unwrap(), "hi");
+        let reply = friend_key.nip44_encrypt(&user, "bye").unwrap();
+        assert_eq!(request("nip44_decrypt", reply).unwrap(), "bye");
+        let content = request("nip04_encrypt", "hello".to_owned()).unwrap();
+        assert_eq!(request("nip04_decrypt", content).unwrap(), "hello");
+
+        // The policy keeps the signer's secret from other peers
+        let stranger = PrivateKey::generate().public_key();
+        let result = session.request(
+            "nip44_encrypt",
+            vec![stranger.as_hex_string(), "hi".to_owned()],
+        );
+        assert!(matches!(result, Err(Error::Nip46(_))));
+    }
+
+    #[test]
+    fn test_nip46_service_fails_closed() {
+        let open = |service: Nip46Service<PrivateKey>| {
+            let url = service.bunker_url(vec![]);
+            let loopback = Arc::new(Loopback {
+                service,
+                inbox: Mutex::new(VecDeque::new()),
+            });
+            Nip46Session::new(loopback, PrivateKey::generate(), &url)
+                .timeout(Duration::from_millis(200))
+                .connect()
+        };
+
+        // Neither a secret nor a policy: nobody gets in
+        let service = Nip46Service::new(PrivateKey::generate(), PrivateKey::generate());
+        assert_eq!(
+            open(service).unwrap_err().to_string(),
+            "Remote signer error: connections are not allowed"
+        );
+
+        // A policy decides who gets in
+        let client_key = PrivateKey::generate();
+        let client = client_key.public_key();
+        let service = Nip46Service::new(PrivateKey::generate(), PrivateKey::generate()).policy(
+            move |from: &PublicKey, _: &Nip46Request| {
+                if *from == client {
+                    Nip46Decision::Allow
+                } else {
+                    Nip46Decision::Deny("unknown client".to_owned())
+                }
+            },
+        );
+        let url = service.bunker_url(vec![]);
+        let loopback = Arc::new(Loopback {
+            service,
+            inbox: Mutex::new(VecDeque::new()),
+        });
+        assert!(Nip46Session::new(loopback.clone(), client_key, &url)
+            .timeout(Duration::from_millis(200))
+            .connect()
+            .is_ok());
+        assert_eq!(
+            Nip46Session::new(loopback, PrivateKey::generate(), &url)
+                .timeout(Duration::from_millis(200))
+                .connect()
+                .unwrap_err()
+                .to_string(),
+            "Remote signer error: unknown client"
+        );
+    }
+}
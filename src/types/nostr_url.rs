@@ -1,9 +1,9 @@
-use super::{EventPointer, Id, Profile, PublicKey};
+use super::{EventAddr, EventPointer, Id, Profile, PublicKey, UncheckedUrl};
 use lazy_static::lazy_static;
 
 /// A bech32 sequence representing a nostr object (or set of objects)
 // note, internally we store them as the object the sequence represents
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum NostrBech32 {
     /// npub - a NostrBech32 representing a public key
     Pubkey(PublicKey),
@@ -13,6 +13,10 @@ pub enum NostrBech32 {
     Id(Id),
     /// nevent - a NostrBech32 representing an event and a set of relay URLs
     EventPointer(EventPointer),
+    /// naddr - a NostrBech32 representing a parameterized replaceable event and a set of relay URLs
+    EventAddr(EventAddr),
+    /// nrelay - a NostrBech32 representing a relay URL
+    Relay(UncheckedUrl),
 }
 
 impl std::fmt::Display for NostrBech32 {
@@ -21,7 +25,18 @@ impl std::fmt::Display for NostrBech32 {
             NostrBech32::Pubkey(pk) => write!(f, "{}", pk.as_bech32_string()),
             NostrBech32::Profile(p) => write!(f, "{}", p.as_bech32_string()),
             NostrBech32::Id(i) => write!(f, "{}", i.as_bech32_string()),
-            NostrBech32::EventPointer(ep) => write!(f, "{}", ep.as_bech32_string()),
+            // Pointers and urls the format can't hold have no display
+            NostrBech32::EventPointer(ep) => {
+                write!(f, "{}", ep.as_bech32_string().map_err(|_| std::fmt::Error)?)
+            }
+            NostrBech32::EventAddr(ea) => write!(f, "{}", ea.as_bech32_string()),
+            NostrBech32::Relay(url) => {
+                write!(
+                    f,
+                    "{}",
+                    url.as_bech32_string().map_err(|_| std::fmt::Error)?
+                )
+            }
         }
     }
 }
@@ -47,6 +62,16 @@ impl NostrBech32 {
         NostrBech32::EventPointer(ep)
     }
 
+    /// Create from an `EventAddr`
+    pub fn new_event_addr(ea: EventAddr) -> NostrBech32 {
+        NostrBech32::EventAddr(ea)
+    }
+
+    /// Create from a relay URL
+    pub fn new_relay(url: UncheckedUrl) -> NostrBech32 {
+        NostrBech32::Relay(url)
+    }
+
     /// Try to convert a string into a NostrBech32. Must not have leading or trailing
     /// junk for this to work.
     pub fn try_from_string(s: &str) -> Option<NostrBech32> {
@@ -66,10 +91,23 @@ impl NostrBech32 {
             if let Ok(ep) = EventPointer::try_from_bech32_string(s) {
                 return Some(NostrBech32::EventPointer(ep));
             }
+        } else if s.get(..6) == Some("naddr1") {
+            if let Ok(ea) = EventAddr::try_from_bech32_string(s) {
+                return Some(NostrBech32::EventAddr(ea));
+            }
+        } else if s.get(..7) == Some("nrelay1") {
+            if let Ok(url) = UncheckedUrl::try_from_bech32_string(s) {
+                return Some(NostrBech32::Relay(url));
+            }
         }
         None
     }
 
+    /// Render as a NIP-21 `nostr:` URI
+    pub fn to_uri_string(&self) -> String {
+        format!("nostr:{self}")
+    }
+
     /// Find all `NostrBech32`s in a string, returned in the order found
     pub fn find_all_in_string(s: &str) -> Vec<NostrBech32> {
         let mut output: Vec<NostrBech32> = Vec::new();
@@ -87,7 +125,7 @@ impl NostrBech32 {
 }
 
 /// A Nostr URL (starting with 'nostr:')
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct NostrUrl(pub NostrBech32);
 
 impl std::fmt::Display for NostrUrl {
@@ -104,14 +142,20 @@ impl NostrUrl {
     }
 
     /// Try to convert a string into a NostrUrl. Must not have leading or trailing
-    /// junk for this to work.
+    /// junk for this to work. The scheme is matched case-insensitively, as
+    /// URI schemes are.
     pub fn try_from_string(s: &str) -> Option<NostrUrl> {
-        if s.get(..6) != Some("nostr:") {
+        if !s.get(..6)?.eq_ignore_ascii_case("nostr:") {
             return None;
         }
         NostrBech32::try_from_string(s.get(6..).unwrap()).map(NostrUrl)
     }
 
+    /// Render as a NIP-21 `nostr:` URI
+    pub fn to_uri_string(&self) -> String {
+        self.to_string()
+    }
+
     /// Find all `NostrUrl`s in a string, returned in the order found
     /// (If not prefixed with 'nostr:' they will not count, see NostrBech32)
     pub fn find_all_in_string(s: &str) -> Vec<NostrUrl> {
@@ -157,7 +201,7 @@ pub fn find_nostr_bech32_pos(s: &str) -> Option<(usize, usize)> {
     use regex::Regex;
     lazy_static! {
         static ref BECH32_RE: Regex = Regex::new(
-            r#"(?:^|[^a-zA-Z0-9])((?:note|nevent|nprofile|npub)1[ac-hj-np-z02-9]{58,})(?:$|[^a-zA-Z0-9])"#
+            r#"(?:^|[^a-zA-Z0-9])((?:note|nevent|nprofile|npub|naddr)1[ac-hj-np-z02-9]{58,}|nrelay1[ac-hj-np-z02-9]{12,})(?:$|[^a-zA-Z0-9])"#
         ).expect("Could not compile nostr URL regex");
     }
     BECH32_RE.captures(s).map(|cap| {
@@ -174,7 +218,7 @@ pub fn find_nostr_url_pos(s: &str) -> Option<(usize, usize)> {
     use regex::Regex;
     lazy_static! {
         static ref NOSTRURL_RE: Regex = Regex::new(
-            r#"(?:^|[^a-zA-Z0-9])(nostr:(?:note|nevent|nprofile|npub)1[ac-hj-np-z02-9]{58,})(?:$|[^a-zA-Z0-9])"#
+            r#"(?:^|[^a-zA-Z0-9])(nostr:(?:(?:note|nevent|nprofile|npub|naddr)1[ac-hj-np-z02-9]{58,}|nrelay1[ac-hj-np-z02-9]{12,}))(?:$|[^a-zA-Z0-9])"#
         ).expect("Could not compile nostr URL regex");
     }
     NOSTRURL_RE.captures(s).map(|cap| {
@@ -205,6 +249,18 @@ mod test {
         let nurl = NostrBech32::try_from_string(d).unwrap();
         assert!(matches!(nurl, NostrBech32::EventPointer(..)));
 
+        let e = EventAddr::mock().as_bech32_string();
+        let nurl = NostrBech32::try_from_string(&e).unwrap();
+        assert!(matches!(nurl, NostrBech32::EventAddr(..)));
+
+        let f = UncheckedUrl::from_str("wss://r.x.com")
+            .as_bech32_string()
+            .unwrap();
+        let nurl = NostrBech32::try_from_string(&f).unwrap();
+        assert!(matches!(nurl, NostrBech32::Relay(..)));
+        let found = NostrBech32::find_all_in_string(&format!("relay: {f}, see?"));
+        assert!(matches!(found.as_slice(), [NostrBech32::Relay(..)]));
+
         // too short
         let short = "npub1sn0wdenkukak0d9dfczzeacvhkrgz92ak56egt7vdgzn8pv2wfqqhrjdv";
         assert!(NostrBech32::try_from_string(short).is_none());
@@ -218,6 +274,34 @@ mod test {
         assert!(NostrBech32::try_from_string(unknown).is_none());
     }
 
+    #[test]
+    fn test_nostr_url_round_trip() {
+        let uris = [
+            "nostr:npub1sn0wdenkukak0d9dfczzeacvhkrgz92ak56egt7vdgzn8pv2wfqqhrjdv9",
+            "nostr:note1fntxtkcy9pjwucqwa9mddn7v03wwwsu9j330jj350nvhpky2tuaspk6nqc",
+        ];
+        for uri in uris {
+            let url = NostrUrl::try_from_string(uri).unwrap();
+            assert_eq!(url.to_uri_string(), uri);
+            assert_eq!(url.0.to_uri_string(), uri);
+        }
+        assert!(matches!(
+            NostrUrl::try_from_string(&uris[1].replace("nostr:", "NOSTR:")),
+            Some(NostrUrl(NostrBech32::Id(_)))
+        ));
+
+        let pubkey = crate::PrivateKey::generate().public_key();
+        let url = NostrUrl::new(NostrBech32::new_pubkey(pubkey));
+        let parsed = NostrUrl::try_from_string(&url.to_uri_string()).unwrap();
+        assert!(matches!(parsed.0, NostrBech32::Pubkey(pk) if pk == pubkey));
+
+        assert!(NostrUrl::try_from_string("nostr:").is_none());
+        assert!(NostrUrl::try_from_string(
+            "npub1sn0wdenkukak0d9dfczzeacvhkrgz92ak56egt7vdgzn8pv2wfqqhrjdv9"
+        )
+        .is_none());
+    }
+
     #[test]
     fn test_nostr_urlize() {
         let sample = r#"This is now the offical Gossip Client account.  Please follow it.  I will be reposting it's messages for some time until it catches on.
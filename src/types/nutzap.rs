@@ -0,0 +1,356 @@
+use super::{
+    CashuProof, Event, EventKind, Id, PreEvent, PublicKey, PublicKeyHex, Tag, UncheckedUrl,
+    Unixtime,
+};
+use crate::Error;
+
+/// A mint that a user accepts nutzaps from, with the units they accept
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NutzapMint {
+    /// The mint URL
+    pub url: UncheckedUrl,
+
+    /// The accepted units. If empty, "sat" is implied.
+    pub units: Vec<String>,
+}
+
+/// How a user wants to receive nutzaps (NIP-61), kind 10019
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NutzapInfo {
+    /// Relays where nutzaps to this user should be published
+    pub relays: Vec<UncheckedUrl>,
+
+    /// Mints that this user accepts nutzaps from
+    pub mints: Vec<NutzapMint>,
+
+    /// The public key that nutzaps must be P2PK-locked to. This is not the user's
+    /// nostr public key.
+    pub p2pk_pubkey: String,
+}
+
+impl NutzapInfo {
+    /// The P2PK public key in the compressed form proofs are locked with. A bare
+    /// 32-byte x-only key is given the "02" prefix.
+    pub fn p2pk_lock_pubkey(&self) -> String {
+        normalize_p2pk(&self.p2pk_pubkey)
+    }
+
+    /// Does this user accept nutzaps from this mint?
+    pub fn accepts_mint(&self, mint: &UncheckedUrl) -> bool {
+        self.mints.iter().any(|m| same_mint(&m.url, mint))
+    }
+
+    /// Create a `PreEvent` for this nutzap info
+    pub fn to_pre_event(&self, pubkey: PublicKey) -> PreEvent {
+        let mut tags: Vec<Tag> = self.relays.iter().cloned().map(Tag::Relay).collect();
+        for mint in self.mints.iter() {
+            let mut data = vec![mint.url.0.clone()];
+            data.extend(mint.units.iter().cloned());
+            tags.push(Tag::Other {
+                tag: "mint".to_owned(),
+                data,
+            });
+        }
+        tags.push(Tag::Other {
+            tag: "pubkey".to_owned(),
+            data: vec![self.p2pk_pubkey.clone()],
+        });
+
+        PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::NutzapMintRecommendation,
+            tags,
+            content: "".to_owned(),
+            ots: None,
+        }
+    }
+
+    /// Interpret an event as nutzap info
+    pub fn try_from_event(event: &Event) -> Result<NutzapInfo, Error> {
+        if event.kind != EventKind::NutzapMintRecommendation {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut relays: Vec<UncheckedUrl> = Vec::new();
+        let mut mints: Vec<NutzapMint> = Vec::new();
+        let mut p2pk_pubkey: Option<String> = None;
+        for tag in event.tags.iter() {
+            match tag {
+                Tag::Relay(url) => relays.push(url.clone()),
+                Tag::Other { tag, data } if tag == "mint" && !data.is_empty() => {
+                    mints.push(NutzapMint {
+                        url: UncheckedUrl(data[0].clone()),
+                        units: data[1..].to_vec(),
+                    })
+                }
+                Tag::Other { tag, data }
+                    if tag == "pubkey" && !data.is_empty() && p2pk_pubkey.is_none() =>
+                {
+                    p2pk_pubkey = Some(data[0].clone())
+                }
+                _ => {}
+            }
+        }
+
+        Ok(NutzapInfo {
+            relays,
+            mints,
+            p2pk_pubkey: p2pk_pubkey.ok_or_else(|| {
+                Error::AssertionFailed("Nutzap info has no pubkey tag".to_owned())
+            })?,
+        })
+    }
+}
+
+/// A nutzap (NIP-61), kind 9321: Cashu proofs P2PK-locked to the recipient
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Nutzap {
+    /// An optional comment (the event content)
+    pub comment: String,
+
+    /// The proofs being sent
+    pub proofs: Vec<CashuProof>,
+
+    /// The mint the proofs are from
+    pub mint: UncheckedUrl,
+
+    /// The recipient
+    pub recipient: PublicKeyHex,
+
+    /// The event being zapped, with an optional relay hint
+    pub zapped_event: Option<(Id, Option<UncheckedUrl>)>,
+
+    /// The kind of the event being zapped
+    pub zapped_kind: Option<EventKind>,
+}
+
+impl Nutzap {
+    /// The total amount of the proofs
+    pub fn amount(&self) -> u64 {
+        self.proofs.iter().map(|p| p.amount).sum()
+    }
+
+    /// Create a `PreEvent` for this nutzap
+    pub fn to_pre_event(&self, pubkey: PublicKey) -> Result<PreEvent, Error> {
+        let mut tags: Vec<Tag> = Vec::new();
+        for proof in self.proofs.iter() {
+            tags.push(Tag::Other {
+                tag: "proof".to_owned(),
+                data: vec![serde_json::to_string(proof)?],
+            });
+        }
+        tags.push(Tag::Other {
+            tag: "u".to_owned(),
+            data: vec![self.mint.0.clone()],
+        });
+        if let Some((id, relay)) = &self.zapped_event {
+            tags.push(Tag::Event {
+                id: *id,
+                recommended_relay_url: relay.clone(),
+                marker: None,
+                pubkey: None,
+            });
+        }
+        if let Some(kind) = self.zapped_kind {
+            tags.push(Tag::KindReference(kind));
+        }
+        tags.push(Tag::Pubkey {
+            pubkey: self.recipient.clone(),
+            recommended_relay_url: None,
+            petname: None,
+        });
+
+        Ok(PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::Nutzap,
+            tags,
+            content: self.comment.clone(),
+            ots: None,
+        })
+    }
+
+    /// Interpret an event as a nutzap. This only parses; see `validate`.
+    pub fn try_from_event(event: &Event) -> Result<Nutzap, Error> {
+        if event.kind != EventKind::Nutzap {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut proofs: Vec<CashuProof> = Vec::new();
+        let mut mint: Option<UncheckedUrl> = None;
+        let mut recipient: Option<PublicKeyHex> = None;
+        let mut zapped_event: Option<(Id, Option<UncheckedUrl>)> = None;
+        let mut zapped_kind: Option<EventKind> = None;
+        for (index, tag) in event.tags.iter().enumerate() {
+            let invalid = |message: String| Error::InvalidTag { index, message };
+            match tag {
+                Tag::Other { tag, data } if tag == "proof" && !data.is_empty() => proofs.push(
+                    serde_json::from_str(&data[0]).map_err(|e| invalid(format!("proof: {e}")))?,
+                ),
+                Tag::Other { tag, data } if tag == "u" && !data.is_empty() && mint.is_none() => {
+                    mint = Some(UncheckedUrl(data[0].clone()))
+                }
+                Tag::KindReference(kind) => zapped_kind = Some(*kind),
+                Tag::Other { tag, data } if tag == "k" && !data.is_empty() => {
+                    let kind = data[0]
+                        .parse::<u64>()
+                        .map_err(|e| invalid(format!("kind: {e}")))?;
+                    zapped_kind = Some(kind.into())
+                }
+                Tag::Event {
+                    id,
+                    recommended_relay_url,
+                    ..
+                } if zapped_event.is_none() => {
+                    zapped_event = Some((*id, recommended_relay_url.clone()))
+                }
+                Tag::Pubkey { pubkey, .. } if recipient.is_none() => {
+                    recipient = Some(pubkey.clone())
+                }
+                _ => {}
+            }
+        }
+
+        let missing = |what: &str| Error::AssertionFailed(format!("Nutzap has no {what}"));
+        if proofs.is_empty() {
+            return Err(missing("proof tags"));
+        }
+
+        Ok(Nutzap {
+            comment: event.content.clone(),
+            proofs,
+            mint: mint.ok_or_else(|| missing("'u' tag"))?,
+            recipient: recipient.ok_or_else(|| missing("'p' tag"))?,
+            zapped_event,
+            zapped_kind,
+        })
+    }
+
+    /// Check this nutzap against the recipient's nutzap info: the mint must be
+    /// one they accept and every proof must be P2PK-locked to their pubkey.
+    ///
+    /// `verify_proofs` is then called with the mint and proofs so the caller can
+    /// do the cryptographic checks this crate cannot (e.g. DLEQ verification
+    /// against the mint's keys, or checking the proofs are unspent).
+    pub fn validate<F>(&self, info: &NutzapInfo, verify_proofs: F) -> Result<(), Error>
+    where
+        F: Fn(&UncheckedUrl, &[CashuProof]) -> Result<(), Error>,
+    {
+        if !info.accepts_mint(&self.mint) {
+            return Err(Error::AssertionFailed(format!(
+                "Nutzap mint {} is not accepted by the recipient",
+                self.mint
+            )));
+        }
+
+        let lock = info.p2pk_lock_pubkey();
+        for proof in self.proofs.iter() {
+            match proof.p2pk_pubkey() {
+                Some(pubkey) if normalize_p2pk(&pubkey) == lock => {}
+                _ => {
+                    return Err(Error::AssertionFailed(
+                        "Nutzap proof is not locked to the recipient".to_owned(),
+                    ))
+                }
+            }
+        }
+
+        verify_proofs(&self.mint, &self.proofs)
+    }
+}
+
+fn normalize_p2pk(pubkey: &str) -> String {
+    let pubkey = pubkey.to_lowercase();
+    if pubkey.len() == 64 {
+        format!("02{pubkey}")
+    } else {
+        pubkey
+    }
+}
+
+fn same_mint(a: &UncheckedUrl, b: &UncheckedUrl) -> bool {
+    a.0.trim_end_matches('/') == b.0.trim_end_matches('/')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PrivateKey;
+
+    const P2PK: &str = "eb1d8a4e4b2d3b1f4ec11a9b2b1b1ef6c6a0f9d5a6c1bdc3e0a4c6b4e1a2d3f4";
+
+    fn locked_proof(pubkey: &str) -> CashuProof {
+        let mut proof = CashuProof::mock();
+        proof.secret = format!(
+            r#"["P2PK",{{"nonce":"859d4935c4907062a6297cf4e663e2835d90d97ecdd510745d32f6816323a41f","data":"{pubkey}","tags":[["sigflag","SIG_INPUTS"]]}}]"#
+        );
+        proof
+    }
+
+    fn mock_info() -> NutzapInfo {
+        NutzapInfo {
+            relays: vec![UncheckedUrl::from_str("wss://relay.example.com")],
+            mints: vec![NutzapMint {
+                url: UncheckedUrl::from_str("https://mint.example.com"),
+                units: vec!["sat".to_owned()],
+            }],
+            p2pk_pubkey: P2PK.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_nutzap_info_round_trip() {
+        let privkey = PrivateKey::mock();
+        let info = mock_info();
+        let event = Event::new(info.to_pre_event(privkey.public_key()), &privkey).unwrap();
+        assert_eq!(NutzapInfo::try_from_event(&event).unwrap(), info);
+        assert!(info.accepts_mint(&UncheckedUrl::from_str("https://mint.example.com/")));
+    }
+
+    #[test]
+    fn test_nutzap_round_trip_and_validate() {
+        let privkey = PrivateKey::mock();
+        let info = mock_info();
+        let nutzap = Nutzap {
+            comment: "Thanks!".to_owned(),
+            proofs: vec![locked_proof(&format!("02{P2PK}"))],
+            mint: UncheckedUrl::from_str("https://mint.example.com"),
+            recipient: PublicKeyHex::mock(),
+            zapped_event: Some((Id::mock(), None)),
+            zapped_kind: Some(EventKind::TextNote),
+        };
+        let event =
+            Event::new(nutzap.to_pre_event(privkey.public_key()).unwrap(), &privkey).unwrap();
+        let parsed = Nutzap::try_from_event(&event).unwrap();
+        assert_eq!(parsed, nutzap);
+        assert_eq!(parsed.amount(), 1);
+        assert!(parsed.validate(&info, |_, _| Ok(())).is_ok());
+        assert!(parsed
+            .validate(&info, |_, _| Err(Error::AssertionFailed(
+                "spent".to_owned()
+            )))
+            .is_err());
+
+        let mut unlocked = nutzap.clone();
+        unlocked.proofs = vec![CashuProof::mock()];
+        assert!(unlocked.validate(&info, |_, _| Ok(())).is_err());
+
+        let mut other_mint = nutzap;
+        other_mint.mint = UncheckedUrl::from_str("https://other.example.com");
+        assert!(other_mint.validate(&info, |_, _| Ok(())).is_err());
+
+        // Parse errors report which tag was bad
+        let mut pre = other_mint.to_pre_event(privkey.public_key()).unwrap();
+        pre.tags.push(Tag::Other {
+            tag: "k".to_owned(),
+            data: vec!["one".to_owned()],
+        });
+        let index = pre.tags.len() - 1;
+        let event = Event::new(pre, &privkey).unwrap();
+        match Nutzap::try_from_event(&event) {
+            Err(Error::InvalidTag { index: i, .. }) => assert_eq!(i, index),
+            other => panic!("Expected an invalid tag error, got {other:?}"),
+        }
+    }
+}
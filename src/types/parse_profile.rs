@@ -0,0 +1,287 @@
+use crate::Error;
+use serde_json::{Map, Value};
+
+/// How strictly to parse events, tags and messages from the wire.
+///
+/// Pass one to `Event::from_json_with_profile()`,
+/// `Tag::from_strings_with_profile()`, `RelayMessage::from_json_with_profile()`
+/// or `ClientMessage::from_json_with_profile()`. Plain serde deserialization
+/// behaves as `Interop`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ParseProfile {
+    /// Only what the NIPs allow: unknown event fields, uppercase hex and
+    /// well-known tags with the wrong number of fields are rejected. For
+    /// relays that want to hold clients to the spec, and for testing.
+    Strict,
+
+    /// What other implementations send in practice: unknown event fields are
+    /// ignored, hex may be in either case, and malformed tags are kept as
+    /// `Tag::Other`.
+    #[default]
+    Interop,
+
+    /// As `Interop`, and also tolerates events missing their tags or content
+    /// and relay messages with extra trailing elements or an OK without a
+    /// message, as some old relays send.
+    Permissive,
+}
+
+// The fields of an event on the wire
+const EVENT_FIELDS: [&str; 8] = [
+    "id",
+    "pubkey",
+    "created_at",
+    "kind",
+    "tags",
+    "content",
+    "sig",
+    "ots",
+];
+
+impl ParseProfile {
+    /// Are fields an event doesn't define an error?
+    pub fn rejects_unknown_fields(&self) -> bool {
+        *self == ParseProfile::Strict
+    }
+
+    /// Is uppercase hex in ids, keys and signatures an error?
+    pub fn rejects_uppercase_hex(&self) -> bool {
+        *self == ParseProfile::Strict
+    }
+
+    /// Is a well-known tag with too few or too many fields an error?
+    pub fn checks_tag_cardinality(&self) -> bool {
+        *self == ParseProfile::Strict
+    }
+
+    /// Are missing tags and content filled in as empty, and are relay
+    /// messages with extra or missing trailing elements fixed up?
+    pub fn fills_missing_fields(&self) -> bool {
+        *self == ParseProfile::Permissive
+    }
+
+    // Check (and for Permissive, fix up) an event before deserializing it
+    pub(crate) fn prepare_event(&self, value: &mut Value) -> Result<(), Error> {
+        let map: &mut Map<String, Value> = match value.as_object_mut() {
+            Some(map) => map,
+            None => return Ok(()), // let serde report it
+        };
+
+        if self.rejects_unknown_fields() {
+            if let Some(field) = map.keys().find(|k| !EVENT_FIELDS.contains(&k.as_str())) {
+                return Err(Error::StrictParse(format!(
+                    "unknown event field \"{field}\""
+                )));
+            }
+        }
+
+        if self.rejects_uppercase_hex() {
+            for field in ["id", "pubkey", "sig"] {
+                if let Some(Value::String(s)) = map.get(field) {
+                    check_lowercase_hex(field, s)?;
+                }
+            }
+        }
+
+        if self.fills_missing_fields() {
+            let _ = map.entry("tags").or_insert_with(|| Value::Array(vec![]));
+            let _ = map
+                .entry("content")
+                .or_insert_with(|| Value::String("".to_owned()));
+        }
+
+        // Checked here rather than left to serde, so that errors say which
+        // tag failed
+        if let Some(Value::Array(tags)) = map.get("tags") {
+            for (index, tag) in tags.iter().enumerate() {
+                let invalid = |message: String| Error::InvalidTag { index, message };
+                let strings: Vec<String> = match tag.as_array() {
+                    Some(fields) => fields
+                        .iter()
+                        .map(|f| f.as_str().map(|s| s.to_owned()))
+                        .collect::<Option<Vec<String>>>()
+                        .ok_or_else(|| invalid("fields must be strings".to_owned()))?,
+                    None => return Err(invalid("not an array".to_owned())),
+                };
+                if self.checks_tag_cardinality() {
+                    self.check_tag(&strings).map_err(|e| match e {
+                        Error::StrictParse(message) => invalid(message),
+                        e => invalid(e.to_string()),
+                    })?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Check a tag's field count, and the case of the hex in 'e' and 'p' tags
+    pub(crate) fn check_tag(&self, strings: &[String]) -> Result<(), Error> {
+        let name = match strings.first() {
+            Some(name) => name.as_str(),
+            None => return Ok(()),
+        };
+
+        if self.checks_tag_cardinality() {
+            if let Some((min, max)) = tag_cardinality(name) {
+                if strings.len() < min || strings.len() > max {
+                    return Err(Error::StrictParse(format!(
+                        "'{name}' tag has {} fields",
+                        strings.len()
+                    )));
+                }
+            }
+        }
+
+        if self.rejects_uppercase_hex() && (name == "e" || name == "p") {
+            if let Some(value) = strings.get(1) {
+                check_lowercase_hex(name, value)?;
+            }
+            if name == "e" {
+                if let Some(value) = strings.get(4) {
+                    check_lowercase_hex(name, value)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn check_lowercase_hex(field: &str, s: &str) -> Result<(), Error> {
+    if s.chars().any(|c| c.is_ascii_uppercase()) {
+        Err(Error::StrictParse(format!("uppercase hex in {field}")))
+    } else {
+        Ok(())
+    }
+}
+
+// The allowed number of fields (including the name) of well-known tags
+fn tag_cardinality(name: &str) -> Option<(usize, usize)> {
+    Some(match name {
+        "-" => (1, 1),
+        "content-warning" => (1, 2),
+        "e" => (2, 5),
+        "p" | "a" | "client" => (2, 4),
+        "r" | "image" | "nonce" => (2, 3),
+        "proxy" => (3, 3),
+        "delegation" => (4, 4),
+        "t" | "d" | "g" | "k" | "alt" | "title" | "summary" | "subject" | "relay"
+        | "expiration" | "published_at" => (2, 2),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ClientMessage, Event, RelayMessage, Tag};
+
+    #[test]
+    fn test_tag_checks() {
+        let strings = |s: &[&str]| -> Vec<String> { s.iter().map(|s| s.to_string()).collect() };
+
+        let extra = strings(&["t", "nostr", "extra"]);
+        assert!(ParseProfile::Interop.check_tag(&extra).is_ok());
+        assert!(ParseProfile::Strict.check_tag(&extra).is_err());
+        assert!(ParseProfile::Strict
+            .check_tag(&strings(&["emoji", "a", "b", "c"]))
+            .is_ok());
+
+        let upper = strings(&[
+            "p",
+            "221115830CED1CA94352002485FCC7A75DCFE30D1B07F5F6FBE9C0407CFA59A1",
+        ]);
+        assert!(ParseProfile::Permissive.check_tag(&upper).is_ok());
+        assert!(ParseProfile::Strict.check_tag(&upper).is_err());
+        assert!(Tag::from_strings_with_profile(upper, ParseProfile::Strict).is_err());
+    }
+
+    #[test]
+    fn test_invalid_tag_index() {
+        let event = Event::mock();
+        let mut value = serde_json::to_value(&event).unwrap();
+        let _ = value.as_object_mut().unwrap().insert(
+            "tags".to_owned(),
+            serde_json::json!([["t", "nostr"], ["t", "nostr", "extra"]]),
+        );
+        let json = value.to_string();
+        assert!(matches!(
+            Event::from_json_with_profile(&json, ParseProfile::Strict),
+            Err(Error::InvalidTag { index: 1, .. })
+        ));
+
+        let _ = value.as_object_mut().unwrap().insert(
+            "tags".to_owned(),
+            serde_json::json!([["t", "nostr"], ["t", 5]]),
+        );
+        let json = value.to_string();
+        for profile in [ParseProfile::Strict, ParseProfile::Interop] {
+            assert!(matches!(
+                Event::from_json_with_profile(&json, profile),
+                Err(Error::InvalidTag { index: 1, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_profiles() {
+        let event = Event::mock();
+        let json = serde_json::to_string(&event).unwrap();
+        for profile in [
+            ParseProfile::Strict,
+            ParseProfile::Interop,
+            ParseProfile::Permissive,
+        ] {
+            assert_eq!(
+                Event::from_json_with_profile(&json, profile).unwrap(),
+                event
+            );
+        }
+
+        let mut value: Value = serde_json::from_str(&json).unwrap();
+        let _ = value
+            .as_object_mut()
+            .unwrap()
+            .insert("extra".to_owned(), Value::Bool(true));
+        let extra = value.to_string();
+        assert!(Event::from_json_with_profile(&extra, ParseProfile::Interop).is_ok());
+        assert!(matches!(
+            Event::from_json_with_profile(&extra, ParseProfile::Strict),
+            Err(Error::StrictParse(_))
+        ));
+
+        let upper = json.replace(
+            &event.id.as_hex_string(),
+            &event.id.as_hex_string().to_uppercase(),
+        );
+        assert_eq!(
+            Event::from_json_with_profile(&upper, ParseProfile::Interop).unwrap(),
+            event
+        );
+        assert!(Event::from_json_with_profile(&upper, ParseProfile::Strict).is_err());
+
+        let mut value: Value = serde_json::from_str(&json).unwrap();
+        let _ = value.as_object_mut().unwrap().remove("tags");
+        let untagged = value.to_string();
+        assert!(Event::from_json_with_profile(&untagged, ParseProfile::Interop).is_err());
+        assert!(Event::from_json_with_profile(&untagged, ParseProfile::Permissive).is_ok());
+
+        let message = format!(r#"["EVENT","sub",{extra}]"#);
+        assert!(RelayMessage::from_json_with_profile(&message, ParseProfile::Interop).is_ok());
+        assert!(RelayMessage::from_json_with_profile(&message, ParseProfile::Strict).is_err());
+        let message = format!(r#"["EVENT",{extra}]"#);
+        assert!(ClientMessage::from_json_with_profile(&message, ParseProfile::Interop).is_ok());
+        assert!(ClientMessage::from_json_with_profile(&message, ParseProfile::Strict).is_err());
+
+        let ok = format!(r#"["OK","{}",true]"#, event.id.as_hex_string());
+        assert!(RelayMessage::from_json_with_profile(&ok, ParseProfile::Interop).is_err());
+        assert_eq!(
+            RelayMessage::from_json_with_profile(&ok, ParseProfile::Permissive).unwrap(),
+            RelayMessage::Ok(event.id, true, "".to_owned())
+        );
+        let notice = r#"["NOTICE","hello","extra"]"#;
+        assert!(RelayMessage::from_json_with_profile(notice, ParseProfile::Interop).is_err());
+        assert!(RelayMessage::from_json_with_profile(notice, ParseProfile::Permissive).is_ok());
+    }
+}
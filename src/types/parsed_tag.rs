@@ -0,0 +1,123 @@
+use super::Tag;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// A typed view of a tag, for tags this crate does not model itself.
+///
+/// Downstream crates can implement this for their own tag types and then use
+/// `Event::tags_of_type::<T>()` to pull them out of an event. Implementations
+/// must be lossless: `from_strings(&t.to_strings())` must give back `t`, otherwise
+/// events built with them will not round trip.
+pub trait ParsedTag: Sized {
+    /// The tag name (the first string in the tag array)
+    const TAGNAME: &'static str;
+
+    /// Parse from the full array of tag strings, including the tag name.
+    /// Return None if the strings do not represent this kind of tag.
+    fn from_strings(strings: &[String]) -> Option<Self>;
+
+    /// Convert into the full array of tag strings, including the tag name
+    fn to_strings(&self) -> Vec<String>;
+
+    /// Parse from a `Tag`
+    fn from_tag(tag: &Tag) -> Option<Self> {
+        if matches!(tag, Tag::Empty) || tag.tagname() != Self::TAGNAME {
+            return None;
+        }
+        Self::from_strings(&tag.to_strings())
+    }
+
+    /// Convert into a `Tag`
+    fn to_tag(&self) -> Tag {
+        Tag::from_strings(self.to_strings())
+    }
+}
+
+type TagValidator = fn(&Tag) -> bool;
+
+lazy_static! {
+    static ref TAG_REGISTRY: RwLock<HashMap<&'static str, TagValidator>> =
+        RwLock::new(HashMap::new());
+}
+
+fn validate<T: ParsedTag>(tag: &Tag) -> bool {
+    T::from_tag(tag).is_some()
+}
+
+/// Register a custom `ParsedTag` type so that `Tag::is_recognized()` knows about it.
+/// Registering a second type with the same tag name replaces the first.
+pub fn register_tag_type<T: ParsedTag>() {
+    let _ = TAG_REGISTRY
+        .write()
+        .unwrap()
+        .insert(T::TAGNAME, validate::<T>);
+}
+
+/// The tag names of all registered custom `ParsedTag` types
+pub fn registered_tag_names() -> Vec<&'static str> {
+    TAG_REGISTRY.read().unwrap().keys().copied().collect()
+}
+
+pub(crate) fn registered_tag_recognizes(tag: &Tag) -> bool {
+    if matches!(tag, Tag::Empty) {
+        return false;
+    }
+    match TAG_REGISTRY.read().unwrap().get(tag.tagname().as_str()) {
+        Some(validator) => validator(tag),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Emoji {
+        shortcode: String,
+        url: String,
+    }
+
+    impl ParsedTag for Emoji {
+        const TAGNAME: &'static str = "emoji";
+
+        fn from_strings(strings: &[String]) -> Option<Self> {
+            if strings.len() != 3 {
+                return None;
+            }
+            Some(Emoji {
+                shortcode: strings[1].clone(),
+                url: strings[2].clone(),
+            })
+        }
+
+        fn to_strings(&self) -> Vec<String> {
+            vec![
+                Self::TAGNAME.to_owned(),
+                self.shortcode.clone(),
+                self.url.clone(),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_parsed_tag_registry() {
+        let tag: Tag =
+            serde_json::from_str(r#"["emoji","soapbox","https://example.com/soapbox.png"]"#)
+                .unwrap();
+        let emoji = Emoji::from_tag(&tag).unwrap();
+        assert_eq!(emoji.shortcode, "soapbox");
+        assert_eq!(emoji.to_tag(), tag);
+
+        let bad: Tag = serde_json::from_str(r#"["emoji","soapbox"]"#).unwrap();
+        assert!(Emoji::from_tag(&bad).is_none());
+
+        assert!(!tag.is_recognized());
+        register_tag_type::<Emoji>();
+        assert!(registered_tag_names().contains(&"emoji"));
+        assert!(tag.is_recognized());
+        assert!(!bad.is_recognized());
+        assert!(Tag::Hashtag("nostr".to_owned()).is_recognized());
+    }
+}
@@ -0,0 +1,405 @@
+use super::{Event, EventKind, PubkeySet, PublicKey, RelayMessage, Unixtime, WordFilter};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A machine-readable prefix for the message in an OK (NIP-01)
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OkPrefix {
+    /// The relay already has the event
+    Duplicate,
+
+    /// The event does not have enough proof of work
+    Pow,
+
+    /// The author or content is blocked
+    Blocked,
+
+    /// The author is publishing too fast
+    RateLimited,
+
+    /// The event is malformed or breaks the relay's rules
+    Invalid,
+
+    /// The author is not permitted to publish here
+    Restricted,
+
+    /// The client must AUTH first (NIP-42)
+    AuthRequired,
+
+    /// The relay failed internally
+    Error,
+}
+
+impl OkPrefix {
+    /// The prefix as it appears in the OK message
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            OkPrefix::Duplicate => "duplicate",
+            OkPrefix::Pow => "pow",
+            OkPrefix::Blocked => "blocked",
+            OkPrefix::RateLimited => "rate-limited",
+            OkPrefix::Invalid => "invalid",
+            OkPrefix::Restricted => "restricted",
+            OkPrefix::AuthRequired => "auth-required",
+            OkPrefix::Error => "error",
+        }
+    }
+}
+
+/// The decision of a `Policy` about an event
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PolicyDecision {
+    /// Accept the event
+    Accept,
+
+    /// Reject the event
+    Reject {
+        /// The OK message prefix
+        prefix: OkPrefix,
+
+        /// A human-readable explanation
+        message: String,
+    },
+}
+
+impl PolicyDecision {
+    /// Create a rejection
+    pub fn reject(prefix: OkPrefix, message: impl Into<String>) -> PolicyDecision {
+        PolicyDecision::Reject {
+            prefix,
+            message: message.into(),
+        }
+    }
+
+    /// Was the event accepted?
+    pub fn is_accept(&self) -> bool {
+        matches!(*self, PolicyDecision::Accept)
+    }
+
+    /// The message part of an OK, e.g. "pow: difficulty 8 is less than 20".
+    /// This is empty for an acceptance.
+    pub fn ok_message(&self) -> String {
+        match self {
+            PolicyDecision::Accept => "".to_owned(),
+            PolicyDecision::Reject { prefix, message } => {
+                format!("{}: {}", prefix.as_str(), message)
+            }
+        }
+    }
+
+    /// The OK relay message answering `event` with this decision
+    pub fn to_relay_message(&self, event: &Event) -> RelayMessage {
+        RelayMessage::Ok(event.id, self.is_accept(), self.ok_message())
+    }
+}
+
+/// A relay-side rule deciding whether to admit an event.
+///
+/// Policies are composed with `PolicySet`. Any `Fn(&Event, Unixtime) -> PolicyDecision`
+/// is also a policy.
+pub trait Policy: Send + Sync {
+    /// Decide whether to admit `event`, which arrived at time `now`
+    fn check(&self, event: &Event, now: Unixtime) -> PolicyDecision;
+}
+
+impl<F> Policy for F
+where
+    F: Fn(&Event, Unixtime) -> PolicyDecision + Send + Sync,
+{
+    fn check(&self, event: &Event, now: Unixtime) -> PolicyDecision {
+        self(event, now)
+    }
+}
+
+/// A list of policies which must all accept an event. They are checked in order
+/// and the first rejection is returned, so put cheap policies first and stateful
+/// ones (such as `PubkeyRateLimit`) last.
+#[derive(Default)]
+pub struct PolicySet {
+    policies: Vec<Box<dyn Policy>>,
+}
+
+impl fmt::Debug for PolicySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PolicySet")
+            .field("policies", &self.policies.len())
+            .finish()
+    }
+}
+
+impl PolicySet {
+    /// Create an empty policy set, which accepts everything
+    pub fn new() -> PolicySet {
+        Default::default()
+    }
+
+    /// Add a policy to the end of the set
+    pub fn with<P: Policy + 'static>(mut self, policy: P) -> PolicySet {
+        self.policies.push(Box::new(policy));
+        self
+    }
+
+    /// Add a policy to the end of the set
+    pub fn add<P: Policy + 'static>(&mut self, policy: P) {
+        self.policies.push(Box::new(policy));
+    }
+}
+
+impl Policy for PolicySet {
+    fn check(&self, event: &Event, now: Unixtime) -> PolicyDecision {
+        for policy in self.policies.iter() {
+            let decision = policy.check(event, now);
+            if !decision.is_accept() {
+                return decision;
+            }
+        }
+        PolicyDecision::Accept
+    }
+}
+
+/// Reject events whose JSON serialization is larger than this many bytes
+#[derive(Clone, Copy, Debug)]
+pub struct MaxEventSize(pub usize);
+
+impl Policy for MaxEventSize {
+    fn check(&self, event: &Event, _now: Unixtime) -> PolicyDecision {
+        let size = match serde_json::to_string(event) {
+            Ok(s) => s.len(),
+            Err(e) => return PolicyDecision::reject(OkPrefix::Error, format!("{e}")),
+        };
+        if size > self.0 {
+            PolicyDecision::reject(
+                OkPrefix::Invalid,
+                format!("event is {size} bytes, the maximum is {}", self.0),
+            )
+        } else {
+            PolicyDecision::Accept
+        }
+    }
+}
+
+/// Only accept events of these kinds
+#[derive(Clone, Debug)]
+pub struct KindAllowlist(pub Vec<EventKind>);
+
+impl Policy for KindAllowlist {
+    fn check(&self, event: &Event, _now: Unixtime) -> PolicyDecision {
+        if self.0.contains(&event.kind) {
+            PolicyDecision::Accept
+        } else {
+            PolicyDecision::reject(
+                OkPrefix::Blocked,
+                format!("kind {} is not accepted", u64::from(event.kind)),
+            )
+        }
+    }
+}
+
+/// Require at least this much proof of work (NIP-13), in leading zero bits
+#[derive(Clone, Copy, Debug)]
+pub struct MinimumPow(pub u8);
+
+impl Policy for MinimumPow {
+    fn check(&self, event: &Event, _now: Unixtime) -> PolicyDecision {
+        let pow = event.pow();
+        if pow < self.0 {
+            PolicyDecision::reject(
+                OkPrefix::Pow,
+                format!("difficulty {pow} is less than {}", self.0),
+            )
+        } else {
+            PolicyDecision::Accept
+        }
+    }
+}
+
+/// Reject events whose created_at is too far from the current time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CreatedAtBounds {
+    /// How far in the past created_at may be, if limited
+    pub max_age: Option<Duration>,
+
+    /// How far in the future created_at may be, if limited
+    pub max_future: Option<Duration>,
+}
+
+impl Policy for CreatedAtBounds {
+    fn check(&self, event: &Event, now: Unixtime) -> PolicyDecision {
+        if let Some(max_age) = self.max_age {
+            if event.created_at < now - max_age {
+                return PolicyDecision::reject(OkPrefix::Invalid, "created_at is too old");
+            }
+        }
+        if let Some(max_future) = self.max_future {
+            if event.created_at > now + max_future {
+                return PolicyDecision::reject(
+                    OkPrefix::Invalid,
+                    "created_at is too far in the future",
+                );
+            }
+        }
+        PolicyDecision::Accept
+    }
+}
+
+/// Limit each pubkey to `max_events` events in any `window`.
+///
+/// Every event checked counts against its author, so place this after the
+/// other policies in a `PolicySet` to count only events that passed them.
+#[derive(Debug)]
+pub struct PubkeyRateLimit {
+    max_events: usize,
+    window: Duration,
+    history: Mutex<HashMap<PublicKey, VecDeque<Unixtime>>>,
+}
+
+impl PubkeyRateLimit {
+    /// Allow `max_events` per pubkey in any `window`
+    pub fn new(max_events: usize, window: Duration) -> PubkeyRateLimit {
+        PubkeyRateLimit {
+            max_events,
+            window,
+            history: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Forget pubkeys that have not published within the window, to bound memory
+    pub fn prune(&self, now: Unixtime) {
+        let cutoff = now - self.window;
+        let mut history = self.history.lock().unwrap();
+        history.retain(|_, times| times.back().map(|t| *t > cutoff).unwrap_or(false));
+    }
+}
+
+impl Policy for PubkeyRateLimit {
+    fn check(&self, event: &Event, now: Unixtime) -> PolicyDecision {
+        let cutoff = now - self.window;
+        let mut history = self.history.lock().unwrap();
+        let times = history.entry(event.pubkey).or_default();
+        while times.front().map(|t| *t <= cutoff).unwrap_or(false) {
+            let _ = times.pop_front();
+        }
+        if times.len() >= self.max_events {
+            return PolicyDecision::reject(
+                OkPrefix::RateLimited,
+                format!(
+                    "no more than {} events per {} seconds",
+                    self.max_events,
+                    self.window.as_secs()
+                ),
+            );
+        }
+        times.push_back(now);
+        PolicyDecision::Accept
+    }
+}
+
+/// Reject events authored by these pubkeys
+#[derive(Clone, Debug, Default)]
+pub struct BannedPubkeys(pub PubkeySet);
+
+impl Policy for BannedPubkeys {
+    fn check(&self, event: &Event, _now: Unixtime) -> PolicyDecision {
+        if self.0.contains(&event.pubkey) {
+            PolicyDecision::reject(OkPrefix::Blocked, "pubkey is banned")
+        } else {
+            PolicyDecision::Accept
+        }
+    }
+}
+
+/// Reject events whose content matches the filter
+impl Policy for WordFilter {
+    fn check(&self, event: &Event, _now: Unixtime) -> PolicyDecision {
+        if self.is_match_event(event) {
+            PolicyDecision::reject(OkPrefix::Blocked, "content contains a blocked term")
+        } else {
+            PolicyDecision::Accept
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_policy_set() {
+        let event = Event::mock();
+        let now = event.created_at;
+
+        let policies = PolicySet::new()
+            .with(MaxEventSize(65536))
+            .with(KindAllowlist(vec![event.kind]))
+            .with(CreatedAtBounds {
+                max_age: Some(Duration::from_secs(3600)),
+                max_future: Some(Duration::from_secs(60)),
+            })
+            .with(PubkeyRateLimit::new(2, Duration::from_secs(60)));
+        assert!(policies.check(&event, now).is_accept());
+        assert!(policies.check(&event, now).is_accept());
+        let decision = policies.check(&event, now);
+        assert!(decision.ok_message().starts_with("rate-limited: "));
+        assert!(policies
+            .check(&event, now + Duration::from_secs(61))
+            .is_accept());
+
+        let decision = policies.check(&event, now + Duration::from_secs(7200));
+        assert_eq!(
+            decision,
+            PolicyDecision::reject(OkPrefix::Invalid, "created_at is too old")
+        );
+        match decision.to_relay_message(&event) {
+            RelayMessage::Ok(id, ok, message) => {
+                assert_eq!(id, event.id);
+                assert!(!ok);
+                assert_eq!(message, "invalid: created_at is too old");
+            }
+            _ => panic!("Expected an OK message"),
+        }
+    }
+
+    #[test]
+    fn test_builtin_rejections() {
+        let event = Event::mock();
+        let now = event.created_at;
+
+        assert!(!MaxEventSize(10).check(&event, now).is_accept());
+        assert!(!KindAllowlist(vec![]).check(&event, now).is_accept());
+        assert_eq!(
+            MinimumPow(255).check(&event, now).ok_message(),
+            format!("pow: difficulty {} is less than 255", event.pow())
+        );
+
+        let mut banned = BannedPubkeys::default();
+        assert!(banned.check(&event, now).is_accept());
+        let _ = banned.0.insert(event.pubkey);
+        assert_eq!(
+            banned.check(&event, now).ok_message(),
+            "blocked: pubkey is banned"
+        );
+
+        let mut event = event;
+        event.content = "Claim your AIRDROP".to_owned();
+        let words = WordFilter::new(["airdrop"]).unwrap();
+        assert_eq!(
+            words.check(&event, now).ok_message(),
+            "blocked: content contains a blocked term"
+        );
+
+        let closure = |e: &Event, _: Unixtime| {
+            if e.content.is_empty() {
+                PolicyDecision::reject(OkPrefix::Invalid, "empty content")
+            } else {
+                PolicyDecision::Accept
+            }
+        };
+        let policies = PolicySet::new().with(closure);
+        assert_eq!(
+            policies.check(&event, now).is_accept(),
+            !event.content.is_empty()
+        );
+    }
+}
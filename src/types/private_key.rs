@@ -1,3 +1,4 @@
+use super::crypto_provider::ProviderRng;
 use crate::{Error, Id, PublicKey, Signature};
 use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 use base64::Engine;
@@ -13,17 +14,34 @@ use k256::ecdsa::signature::Signer;
 use k256::schnorr::signature::hazmat::PrehashSigner;
 use k256::schnorr::SigningKey;
 use pbkdf2::pbkdf2;
-use rand_core::{OsRng, RngCore};
+use rand_core::{CryptoRngCore, RngCore};
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
+use sha2::{Digest, Sha256, Sha512};
 use std::convert::TryFrom;
 use std::ops::Deref;
+use unicode_normalization::UnicodeNormalization;
 use zeroize::Zeroize;
 
 // This allows us to detect bad decryptions with wrong passwords.
 const V1_CHECK_VALUE: [u8; 11] = [15, 91, 241, 148, 90, 143, 101, 12, 172, 255, 103];
 const V1_HMAC_ROUNDS: u32 = 100_000;
 
+// BIP-44 purpose and the NIP-06 coin type, for app identity derivation
+const BIP44_PURPOSE: u32 = 44;
+const NOSTR_COIN_TYPE: u32 = 1237;
+const HARDENED: u32 = 0x8000_0000;
+
+// Fields that key backups from various clients keep the secret key in
+const BACKUP_KEY_FIELDS: [&str; 7] = [
+    "private_key",
+    "privateKey",
+    "nostrPrivateKey",
+    "privkey",
+    "secret_key",
+    "nsec",
+    "ncryptsec",
+];
+
 /// This is an encrypted private key.
 #[derive(Clone, Debug, Display, Serialize, Deserialize)]
 pub struct EncryptedPrivateKey(pub String);
@@ -74,11 +92,8 @@ impl EncryptedPrivateKey {
     ///    encode = bech32('ncryptsec')
     pub fn version(&self) -> Result<i8, Error> {
         if self.0.starts_with("ncryptsec1") {
-            let data = bech32::decode(&self.0)?;
-            if data.0 != "ncryptsec" {
-                return Err(Error::WrongBech32("ncryptsec".to_string(), data.0));
-            }
-            let data = Vec::<u8>::from_base32(&data.1)?;
+            let data = crate::error::bech32_decode_as(&self.0, "ncryptsec", None)?;
+            let data = Vec::<u8>::from_base32(&data)?;
             Ok(data[0] as i8)
         } else if self.0.len() == 64 {
             Ok(-1)
@@ -138,7 +153,13 @@ pub struct PrivateKey(SigningKey, KeySecurity);
 impl PrivateKey {
     /// Generate a new `PrivateKey` (which can be used to get the `PublicKey`)
     pub fn generate() -> PrivateKey {
-        let signing_key = SigningKey::random(&mut OsRng);
+        let signing_key = SigningKey::random(&mut ProviderRng);
+        PrivateKey(signing_key, KeySecurity::Medium)
+    }
+
+    // Generate from the given source of randomness, for reproducible tests
+    pub(crate) fn generate_with_rng(rng: &mut impl CryptoRngCore) -> PrivateKey {
+        let signing_key = SigningKey::random(rng);
         PrivateKey(signing_key, KeySecurity::Medium)
     }
 
@@ -189,16 +210,169 @@ impl PrivateKey {
     /// This creates a key with `KeySecurity::Weak`.  Use `generate()` or
     /// `import_encrypted()` for `KeySecurity::Medium`
     pub fn try_from_bech32_string(s: &str) -> Result<PrivateKey, Error> {
-        let data = bech32::decode(s)?;
-        if data.0 != "nsec" {
-            Err(Error::WrongBech32("nsec".to_string(), data.0))
-        } else {
-            let decoded = Vec::<u8>::from_base32(&data.1)?;
-            Ok(PrivateKey(
-                SigningKey::from_bytes(&decoded)?,
-                KeySecurity::Weak,
-            ))
+        let data = crate::error::bech32_decode_as(s, "nsec", Some(63))?;
+        let decoded = Vec::<u8>::from_base32(&data)?;
+        Ok(PrivateKey(
+            SigningKey::from_bytes(&decoded)?,
+            KeySecurity::Weak,
+        ))
+    }
+
+    /// Export as hex with a 4 byte checksum appended, the first 4 bytes of
+    /// the double SHA-256 of the key as in Bitcoin's WIF, so that typos are
+    /// caught on import
+    ///
+    /// WARNING: This weakens the security of your key. Your key will be marked
+    /// with `KeySecurity::Weak` if you execute this.
+    pub fn as_checksummed_hex_string(&mut self) -> String {
+        self.1 = KeySecurity::Weak;
+        let mut bytes = self.0.to_bytes().to_vec();
+        let checksum = Sha256::digest(Sha256::digest(&bytes));
+        bytes.extend_from_slice(&checksum[..4]);
+        let output = hex::encode(&bytes);
+        bytes.zeroize();
+        output
+    }
+
+    /// Import from the form given by `as_checksummed_hex_string()`
+    ///
+    /// This creates a key with `KeySecurity::Weak`.
+    pub fn try_from_checksummed_hex_string(v: &str) -> Result<PrivateKey, Error> {
+        let mut bytes: Vec<u8> = hex::decode(v)?;
+        if bytes.len() != 36 {
+            bytes.zeroize();
+            return Err(Error::WrongLengthHexString);
+        }
+        let checksum = Sha256::digest(Sha256::digest(&bytes[..32]));
+        if checksum[..4] != bytes[32..] {
+            bytes.zeroize();
+            return Err(Error::InvalidKeyBackup("Checksum mismatch".to_owned()));
+        }
+        let signing_key = SigningKey::from_bytes(&bytes[..32]);
+        bytes.zeroize();
+        Ok(PrivateKey(signing_key?, KeySecurity::Weak))
+    }
+
+    /// Import a key backup from another client, detecting its format:
+    ///  * a JSON export such as those of nos2x or Alby, with the key under a
+    ///    field like `private_key`, `privateKey` or `nsec`
+    ///  * an encrypted export (`ncryptsec`, NIP-49) such as Amethyst's
+    ///  * a bare `nsec`, as Damus shows it
+    ///  * hex, with or without the checksum of `as_checksummed_hex_string()`
+    ///
+    /// Encrypted exports need the `password`, and keep the key security they
+    /// were exported with. Every other format was in plain text, so gives a
+    /// key with `KeySecurity::Weak`.
+    ///
+    /// We recommend you zeroize() the password you pass in after you are
+    /// done with it.
+    pub fn import_backup(backup: &str, password: Option<&str>) -> Result<PrivateKey, Error> {
+        let backup = backup.trim();
+        if backup.starts_with('{') {
+            let json: serde_json::Value = serde_json::from_str(backup)?;
+            let key = find_backup_key(&json)
+                .ok_or_else(|| Error::InvalidKeyBackup("No key found in JSON".to_owned()))?;
+            return Self::import_backup(key, password);
+        }
+        if backup.starts_with("ncryptsec1") {
+            let password =
+                password.ok_or_else(|| Error::InvalidKeyBackup("Password required".to_owned()))?;
+            return Self::import_encrypted(&EncryptedPrivateKey(backup.to_owned()), password);
+        }
+        if backup.starts_with("nsec1") {
+            return Self::try_from_bech32_string(backup);
+        }
+        match backup.len() {
+            64 => Self::try_from_hex_string(backup),
+            72 => Self::try_from_checksummed_hex_string(backup),
+            _ => Err(Error::InvalidKeyBackup("Unrecognized format".to_owned())),
+        }
+    }
+
+    /// Derive a key for one app from this master key, so that a user can keep
+    /// separate identities for separate apps and still restore all of them
+    /// from the one key. The same master key, app name and index always give
+    /// the same key.
+    ///
+    /// This is BIP-32 derivation with the master key's 32 bytes as the seed,
+    /// hardened at every level, along the path `m/44'/1237'/<app>'/<index>'`.
+    /// 1237 is the nostr coin type from NIP-06, and `<app>` is the first 4
+    /// bytes of the SHA-256 of `app_name` as a big-endian number with the top
+    /// bit cleared. `index` must be below 2^31.
+    ///
+    /// The derived key has the key security of this key.
+    pub fn derive_app_identity(&self, app_name: &str, index: u32) -> Result<PrivateKey, Error> {
+        if index >= HARDENED {
+            return Err(Error::AssertionFailed(
+                "App identity index must be below 2^31".to_owned(),
+            ));
+        }
+        let app_hash = Sha256::digest(app_name.as_bytes());
+        let app =
+            u32::from_be_bytes([app_hash[0], app_hash[1], app_hash[2], app_hash[3]]) & !HARDENED;
+
+        let mut seed = self.0.to_bytes().to_vec();
+        let key = bip32_derive(
+            &seed,
+            &[
+                BIP44_PURPOSE | HARDENED,
+                NOSTR_COIN_TYPE | HARDENED,
+                app | HARDENED,
+                index | HARDENED,
+            ],
+        );
+        seed.zeroize();
+        let mut key = key?;
+
+        let signing_key = SigningKey::from_bytes(&key);
+        key.zeroize();
+        Ok(PrivateKey(signing_key?, self.1))
+    }
+
+    /// The key for `account` from a BIP-39 mnemonic and optional
+    /// `passphrase`, as NIP-06 specifies: BIP-32 derivation from the
+    /// mnemonic's seed along `m/44'/1237'/<account>'/0/0`. Other signers
+    /// following NIP-06 derive the same key. `account` must be below 2^31.
+    ///
+    /// Like every `PrivateKey`, it is kept in the form whose public key has
+    /// an even y (BIP-340), so `as_hex_string()` gives n - k rather than
+    /// the derived k when k's public key has an odd y. The public key is the
+    /// same either way.
+    ///
+    /// The key has `KeySecurity::Medium`. Requires the `nip06` feature.
+    ///
+    /// We recommend you zeroize() the phrase and passphrase you pass in
+    /// after you are done with them.
+    #[cfg(feature = "nip06")]
+    pub fn from_mnemonic(
+        phrase: &str,
+        passphrase: &str,
+        account: u32,
+    ) -> Result<PrivateKey, Error> {
+        if account >= HARDENED {
+            return Err(Error::AssertionFailed(
+                "Account must be below 2^31".to_owned(),
+            ));
         }
+        let mnemonic = bip39::Mnemonic::parse(phrase)
+            .map_err(|e| Error::AssertionFailed(format!("Invalid mnemonic: {e}")))?;
+        let mut seed = mnemonic.to_seed(passphrase);
+        let key = bip32_derive(
+            &seed,
+            &[
+                BIP44_PURPOSE | HARDENED,
+                NOSTR_COIN_TYPE | HARDENED,
+                account | HARDENED,
+                0,
+                0,
+            ],
+        );
+        seed.zeroize();
+        let mut key = key?;
+
+        let signing_key = SigningKey::from_bytes(&key);
+        key.zeroize();
+        Ok(PrivateKey(signing_key?, KeySecurity::Medium))
     }
 
     /// Sign a 32-bit hash
@@ -218,22 +392,24 @@ impl PrivateKey {
         k256::ecdh::diffie_hellman(self.0.as_nonzero_scalar(), other.0.as_affine())
     }
 
+    // The x coordinate of the shared secret, for `Signer::ecdh()`
+    pub(crate) fn shared_secret_bytes(&self, other: &PublicKey) -> [u8; 32] {
+        self.shared_secret(other)
+            .raw_secret_bytes()
+            .to_owned()
+            .into()
+    }
+
     /// Encrypt content via a shared secret according to NIP-04. Returns (IV, Ciphertext) pair.
     pub fn nip04_encrypt(
         &self,
         other: &PublicKey,
         plaintext: &[u8],
     ) -> Result<([u8; 16], Vec<u8>), Error> {
-        let shared_secret = self.shared_secret(other);
-        let raw_shared_secret_bytes = shared_secret.raw_secret_bytes();
-        let iv = {
-            let mut iv: [u8; 16] = [0; 16];
-            OsRng.fill_bytes(&mut iv);
-            iv
-        };
-        let ciphertext = cbc::Encryptor::<aes::Aes256>::new(raw_shared_secret_bytes, &iv.into())
-            .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
-        Ok((iv, ciphertext))
+        Ok(nip04_encrypt_with_shared_secret(
+            &self.shared_secret_bytes(other),
+            plaintext,
+        ))
     }
 
     /// Decrypt content via a shared secret according to NIP-04
@@ -243,12 +419,7 @@ impl PrivateKey {
         ciphertext: &[u8],
         iv: [u8; 16],
     ) -> Result<Vec<u8>, Error> {
-        let shared_secret = self.shared_secret(other);
-        let raw_shared_secret_bytes = shared_secret.raw_secret_bytes();
-        Ok(
-            cbc::Decryptor::<aes::Aes256>::new(raw_shared_secret_bytes, &iv.into())
-                .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)?,
-        )
+        nip04_decrypt_with_shared_secret(&self.shared_secret_bytes(other), ciphertext, iv)
     }
 
     /// Export in a (non-portable) encrypted form. This does not downgrade
@@ -257,7 +428,8 @@ impl PrivateKey {
     /// it, or something similar in another library/client which also respects key
     /// security.
     ///
-    /// This currently exports into EncryptedPrivateKey version 2.
+    /// This currently exports into EncryptedPrivateKey version 2, with the
+    /// password NFKC normalized as NIP-49 requires.
     ///
     /// We recommend you zeroize() the password you pass in after you are
     /// done with it.
@@ -269,11 +441,11 @@ impl PrivateKey {
         // Generate a random 16-byte salt
         let salt = {
             let mut salt: [u8; 16] = [0; 16];
-            OsRng.fill_bytes(&mut salt);
+            ProviderRng.fill_bytes(&mut salt);
             salt
         };
 
-        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut ProviderRng);
 
         let associated_data: Vec<u8> = {
             let key_security: u8 = match self.1 {
@@ -344,17 +516,39 @@ impl PrivateKey {
         }
     }
 
+    /// Export the key in the NIP-49 format ("ncryptsec1..."), encrypted with
+    /// `password` stretched by scrypt with 2^`log_n` rounds. The password is
+    /// NFKC normalized first, as NIP-49 requires, so that it can be typed in
+    /// again on any system.
+    ///
+    /// We recommend you zeroize() the password you pass in after you are
+    /// done with it.
+    pub fn export_ncryptsec(&self, password: &str, log_n: u8) -> Result<String, Error> {
+        Ok(self.export_encrypted(password, log_n)?.0)
+    }
+
+    /// Import a key in the NIP-49 format ("ncryptsec1..."). Older exports
+    /// from this library are refused; use `import_encrypted()` for those.
+    ///
+    /// We recommend you zeroize() the password you pass in after you are
+    /// done with it.
+    pub fn import_ncryptsec(ncryptsec: &str, password: &str) -> Result<PrivateKey, Error> {
+        let data = crate::error::bech32_decode_as(ncryptsec, "ncryptsec", None)?;
+        let data = Vec::<u8>::from_base32(&data)?;
+        if data.first() != Some(&2) {
+            return Err(Error::InvalidEncryptedPrivateKey);
+        }
+        Self::import_encrypted_v2(data, password)
+    }
+
     // Current
     fn import_encrypted_bech32(
         encrypted: &EncryptedPrivateKey,
         password: &str,
     ) -> Result<PrivateKey, Error> {
         // bech32 decode
-        let data = bech32::decode(&encrypted.0)?;
-        if data.0 != "ncryptsec" {
-            return Err(Error::WrongBech32("ncryptsec".to_string(), data.0));
-        }
-        let data = Vec::<u8>::from_base32(&data.1)?;
+        let data = crate::error::bech32_decode_as(&encrypted.0, "ncryptsec", None)?;
+        let data = Vec::<u8>::from_base32(&data)?;
         match data[0] {
             1 => Self::import_encrypted_v1(data, password),
             2 => Self::import_encrypted_v2(data, password),
@@ -397,7 +591,8 @@ impl PrivateKey {
         }
         let key_security = match associated_data[0] {
             0 => KeySecurity::Weak,
-            1 => KeySecurity::Medium,
+            // 2 means the exporter did not track it (NIP-49)
+            1 | 2 => KeySecurity::Medium,
             _ => return Err(Error::InvalidEncryptedPrivateKey),
         };
 
@@ -505,15 +700,19 @@ impl PrivateKey {
         Ok(key)
     }
 
-    // Hash/Stretch password with scrypt into a 32-byte (256-bit) key
+    // Hash/Stretch password with scrypt into a 32-byte (256-bit) key. The
+    // password is NFKC normalized first, as NIP-49 requires.
     fn password_to_key_v2(password: &str, salt: &[u8; 16], log_n: u8) -> Result<[u8; 32], Error> {
         let params = match scrypt::Params::new(log_n, 8, 1, 32) {
             // r=8, p=1
             Ok(p) => p,
             Err(_) => return Err(Error::Scrypt),
         };
+        let mut password: String = password.nfkc().collect();
         let mut key: [u8; 32] = [0; 32];
-        if scrypt::scrypt(password.as_bytes(), salt, &params, &mut key).is_err() {
+        let result = scrypt::scrypt(password.as_bytes(), salt, &params, &mut key);
+        password.zeroize();
+        if result.is_err() {
             return Err(Error::Scrypt);
         }
         Ok(key)
@@ -526,6 +725,91 @@ impl PrivateKey {
     }
 }
 
+// HMAC-SHA512 over the concatenated `parts`, split into its two halves
+fn hmac_sha512_split(key: &[u8], parts: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+    use hmac::Mac;
+    let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(key).unwrap(); // any key length works
+    for part in parts {
+        mac.update(part);
+    }
+    let output = mac.finalize().into_bytes();
+    let mut left = [0; 32];
+    let mut right = [0; 32];
+    left.copy_from_slice(&output[..32]);
+    right.copy_from_slice(&output[32..]);
+    (left, right)
+}
+
+// BIP-32 derivation of the secret key at `path` from `seed`. Children with
+// the HARDENED bit set are hardened.
+fn bip32_derive(seed: &[u8], path: &[u32]) -> Result<[u8; 32], Error> {
+    use k256::elliptic_curve::sec1::ToEncodedPoint;
+    let (mut key, mut chain_code) = hmac_sha512_split(b"Bitcoin seed", &[seed]);
+    for child in path {
+        let (tweak, next_chain_code) = if child & HARDENED != 0 {
+            hmac_sha512_split(&chain_code, &[&[0], &key, &child.to_be_bytes()])
+        } else {
+            let public = k256::SecretKey::from_bytes(&key.into())
+                .map_err(|_| Error::AssertionFailed("Invalid derived key".to_owned()))?
+                .public_key()
+                .to_encoded_point(true);
+            hmac_sha512_split(&chain_code, &[public.as_bytes(), &child.to_be_bytes()])
+        };
+        let sum = add_scalars(&tweak, &key);
+        key.zeroize();
+        key = sum?;
+        chain_code = next_chain_code;
+    }
+    Ok(key)
+}
+
+// Add two secret keys modulo the curve order, as BIP-32 child derivation does
+fn add_scalars(tweak: &[u8; 32], key: &[u8; 32]) -> Result<[u8; 32], Error> {
+    use k256::elliptic_curve::PrimeField;
+    let invalid = || Error::AssertionFailed("Invalid derived key, try another index".to_owned());
+    let tweak = Option::<k256::Scalar>::from(k256::Scalar::from_repr((*tweak).into()))
+        .ok_or_else(invalid)?;
+    let key =
+        Option::<k256::Scalar>::from(k256::Scalar::from_repr((*key).into())).ok_or_else(invalid)?;
+    Ok((tweak + key).to_bytes().into())
+}
+
+// Find the secret key in a JSON backup, at the top level or in an object
+// (such as an account) one level down
+fn find_backup_key(json: &serde_json::Value) -> Option<&str> {
+    fn field_of(value: &serde_json::Value) -> Option<&str> {
+        let object = value.as_object()?;
+        BACKUP_KEY_FIELDS
+            .iter()
+            .find_map(|field| object.get(*field).and_then(|v| v.as_str()))
+    }
+    field_of(json).or_else(|| json.as_object()?.values().find_map(field_of))
+}
+
+// NIP-04 with the x coordinate of the shared secret, for `Signer`s which only
+// expose `ecdh()`
+pub(crate) fn nip04_encrypt_with_shared_secret(
+    shared_secret: &[u8; 32],
+    plaintext: &[u8],
+) -> ([u8; 16], Vec<u8>) {
+    let mut iv: [u8; 16] = [0; 16];
+    ProviderRng.fill_bytes(&mut iv);
+    let ciphertext = cbc::Encryptor::<aes::Aes256>::new(&(*shared_secret).into(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+    (iv, ciphertext)
+}
+
+pub(crate) fn nip04_decrypt_with_shared_secret(
+    shared_secret: &[u8; 32],
+    ciphertext: &[u8],
+    iv: [u8; 16],
+) -> Result<Vec<u8>, Error> {
+    Ok(
+        cbc::Decryptor::<aes::Aes256>::new(&(*shared_secret).into(), &iv.into())
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)?,
+    )
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -545,6 +829,82 @@ mod test {
         assert_eq!(pk.key_security(), KeySecurity::Medium)
     }
 
+    #[cfg(feature = "nip06")]
+    #[test]
+    fn test_from_mnemonic() {
+        // The test vectors from NIP-06. The private keys are not compared,
+        // since both have odd-y public keys and so are stored negated.
+        for (phrase, hex) in [
+            (
+                "leader monkey parrot ring guide accident before fence cannon height naive bean",
+                "17162c921dc4d2518f9a101db33695df1afb56ab82f5ff3e5da6eec3ca5cd917",
+            ),
+            (
+                "what bleak badge arrange retreat wolf trade produce cricket blur garlic valid proud rude strong choose busy staff weather area salt hollow arm fade",
+                "d41b22899549e1f3d335a31002cfd382174006e166d3e658e3a5eecdb6463573",
+            ),
+        ] {
+            let key = PrivateKey::from_mnemonic(phrase, "", 0).unwrap();
+            assert_eq!(key.public_key().as_hex_string(), hex);
+            assert_eq!(key.key_security(), KeySecurity::Medium);
+        }
+
+        // Computed independently from the BIP-32 spec
+        let phrase =
+            "leader monkey parrot ring guide accident before fence cannon height naive bean";
+        let account_1 = PrivateKey::from_mnemonic(phrase, "", 1).unwrap();
+        assert_eq!(
+            account_1.public_key().as_hex_string(),
+            "d977a6cf0f831dc4720780b5f51460eaf6dca08e32d1f6e89b60344d63af4e04"
+        );
+        let with_passphrase = PrivateKey::from_mnemonic(phrase, "TREZOR", 0).unwrap();
+        assert_eq!(
+            with_passphrase.public_key().as_hex_string(),
+            "fd8ffc5352a8e815b2209f1936fac8a8709015cccc5c9474b75becfe9a930b11"
+        );
+
+        // A bad checksum
+        assert!(PrivateKey::from_mnemonic(
+            "leader monkey parrot ring guide accident before fence cannon height naive naive",
+            "",
+            0
+        )
+        .is_err());
+        assert!(PrivateKey::from_mnemonic(phrase, "", 1 << 31).is_err());
+    }
+
+    #[test]
+    fn test_ncryptsec() {
+        // The test vector from NIP-49
+        let mut pk = PrivateKey::import_ncryptsec(
+            "ncryptsec1qgg9947rlpvqu76pj5ecreduf9jxhselq2nae2kghhvd5g7dgjtcxfqtd67p9m0w57lspw8gsq6yphnm8623nsl8xn9j4jdzz84zm3frztj3z7s35vpzmqf6ksu8r89qk5z2zxfmu5gv8th8wclt0h4p",
+            "nostr",
+        )
+        .unwrap();
+        assert_eq!(
+            pk.as_hex_string(),
+            "3501454135014541350145413501453fefb02227e449e57cf4d3a3ce05378683"
+        );
+
+        // The password is normalized, so either form of "Å" works
+        let pk = PrivateKey::generate();
+        let exported = pk.export_ncryptsec("\u{212B}ngstr\u{F6}m", 13).unwrap();
+        assert!(exported.starts_with("ncryptsec1"));
+        let imported = PrivateKey::import_ncryptsec(&exported, "A\u{30A}ngstro\u{308}m").unwrap();
+        assert_eq!(pk.public_key(), imported.public_key());
+        assert!(PrivateKey::import_ncryptsec(&exported, "angstrom").is_err());
+
+        // The same goes for export_encrypted and import_encrypted, which agree
+        // with the ncryptsec functions
+        let imported =
+            PrivateKey::import_encrypted(&EncryptedPrivateKey(exported), "A\u{30A}ngstro\u{308}m")
+                .unwrap();
+        assert_eq!(pk.public_key(), imported.public_key());
+        let exported = pk.export_encrypted("\u{212B}ngstr\u{F6}m", 13).unwrap();
+        let imported = PrivateKey::import_ncryptsec(&exported.0, "A\u{30A}ngstro\u{308}m").unwrap();
+        assert_eq!(pk.public_key(), imported.public_key());
+    }
+
     #[test]
     fn test_import_old_formats() {
         let decrypted = "a28129ab0b70c8d5e75aaf510ec00bff47fde7ca4ab9e3d9315c77edc86f037f";
@@ -607,6 +967,73 @@ mod test {
         assert_eq!(decoded.1, KeySecurity::Weak);
     }
 
+    #[test]
+    fn test_import_backup() {
+        let mut pk = PrivateKey::mock();
+        let hex = pk.as_hex_string();
+        let public_key = pk.public_key();
+
+        let checksummed = pk.as_checksummed_hex_string();
+        assert_eq!(checksummed.len(), 72);
+        assert!(checksummed.starts_with(&hex));
+        let mut typo = checksummed.clone().into_bytes();
+        typo[3] = if typo[3] == b'0' { b'1' } else { b'0' };
+        assert!(
+            PrivateKey::try_from_checksummed_hex_string(std::str::from_utf8(&typo).unwrap())
+                .is_err()
+        );
+
+        let nsec = pk.as_bech32_string();
+        let nos2x = format!(r#"{{"private_key":"{hex}","relays":{{}}}}"#);
+        let alby =
+            format!(r#"{{"version":1,"account":{{"name":"me","nostrPrivateKey":"{nsec}"}}}}"#);
+        for backup in [&hex, &checksummed, &nsec, &nos2x, &alby] {
+            let imported = PrivateKey::import_backup(backup, None).unwrap();
+            assert_eq!(imported.public_key(), public_key);
+            assert_eq!(imported.key_security(), KeySecurity::Weak);
+        }
+
+        let medium = PrivateKey::generate();
+        let ncryptsec = medium.export_encrypted("nostr", 13).unwrap();
+        assert!(PrivateKey::import_backup(&ncryptsec, None).is_err());
+        let amethyst = format!(r#"{{"ncryptsec":"{}"}}"#, ncryptsec.0);
+        let imported = PrivateKey::import_backup(&amethyst, Some("nostr")).unwrap();
+        assert_eq!(imported.public_key(), medium.public_key());
+        assert_eq!(imported.key_security(), KeySecurity::Medium);
+
+        assert!(PrivateKey::import_backup(r#"{"name":"me"}"#, None).is_err());
+        assert!(PrivateKey::import_backup("not a key", None).is_err());
+    }
+
+    #[test]
+    fn test_derive_app_identity() {
+        let master = PrivateKey::try_from_hex_string(
+            "fc613b4dfd6736a7bd268c8a0e74ed0d1c04a959f59dd74ef2874983fd443fc9",
+        )
+        .unwrap();
+
+        // Computed independently from the BIP-32 spec
+        let derived = master.derive_app_identity("example.app", 3).unwrap();
+        assert_eq!(
+            derived.public_key().as_hex_string(),
+            "a00fc90575eaf7cc99dbee286dc7b5fac9b95121fb55207ea03c45b55c368688"
+        );
+        assert_eq!(derived.key_security(), KeySecurity::Weak);
+
+        let again = master.derive_app_identity("example.app", 3).unwrap();
+        assert_eq!(again.public_key(), derived.public_key());
+        let other_index = master.derive_app_identity("example.app", 4).unwrap();
+        assert_ne!(other_index.public_key(), derived.public_key());
+        let other_app = master.derive_app_identity("other.app", 3).unwrap();
+        assert_ne!(other_app.public_key(), derived.public_key());
+
+        assert!(master.derive_app_identity("example.app", 1 << 31).is_err());
+        let medium = PrivateKey::generate()
+            .derive_app_identity("example.app", 0)
+            .unwrap();
+        assert_eq!(medium.key_security(), KeySecurity::Medium);
+    }
+
     #[test]
     fn test_privkey_nip04() {
         let private_key = PrivateKey::mock();
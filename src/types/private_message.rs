@@ -0,0 +1,316 @@
+use super::{
+    giftwrap_for_recipients, Event, EventKind, GiftWrap, Id, PreEvent, PrivateKey, PublicKey,
+    Rumor, Tag, UncheckedUrl, Unixtime,
+};
+use crate::Error;
+
+/// An encrypted file sent in a private file message (kind 15, NIP-17)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedFile {
+    /// Where the encrypted file is
+    pub url: UncheckedUrl,
+
+    /// The MIME type of the file before encryption
+    pub file_type: String,
+
+    /// How it is encrypted, such as "aes-gcm"
+    pub encryption_algorithm: String,
+
+    /// The key to decrypt it
+    pub decryption_key: String,
+
+    /// The nonce to decrypt it
+    pub decryption_nonce: String,
+
+    /// The SHA-256 of the encrypted file, in hex
+    pub hash: String,
+
+    /// The SHA-256 of the file before encryption, in hex
+    pub original_hash: Option<String>,
+
+    /// The size of the encrypted file in bytes
+    pub size: Option<u64>,
+
+    /// The dimensions, in "<width>x<height>" form
+    pub dimensions: Option<String>,
+}
+
+/// What a private direct message carries
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PrivateMessageBody {
+    /// A chat message (kind 14)
+    Chat(String),
+
+    /// A file (kind 15)
+    File(EncryptedFile),
+}
+
+/// A private direct message (NIP-17): the rumor inside the gift wraps sent
+/// to each member of a conversation.
+///
+/// The conversation is the sender and the recipients together; any message
+/// with the same set of people belongs to the same one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrivateDirectMessage {
+    /// Who wrote it
+    pub sender: PublicKey,
+
+    /// Who it is for, each with a relay where they may be found
+    pub recipients: Vec<(PublicKey, Option<UncheckedUrl>)>,
+
+    /// The conversation's subject, which a message can change
+    pub subject: Option<String>,
+
+    /// The message this replies to, with a relay where it may be found
+    pub reply_to: Option<(Id, Option<UncheckedUrl>)>,
+
+    /// When it was written
+    pub created_at: Unixtime,
+
+    /// The message
+    pub body: PrivateMessageBody,
+}
+
+impl PrivateDirectMessage {
+    /// A chat message from `sender` to `recipients`, written at `now`
+    pub fn chat(
+        sender: PublicKey,
+        recipients: Vec<PublicKey>,
+        message: String,
+        now: Unixtime,
+    ) -> PrivateDirectMessage {
+        PrivateDirectMessage {
+            sender,
+            recipients: recipients.into_iter().map(|p| (p, None)).collect(),
+            subject: None,
+            reply_to: None,
+            created_at: now,
+            body: PrivateMessageBody::Chat(message),
+        }
+    }
+
+    /// Everyone in the conversation, the sender included, sorted
+    pub fn conversation(&self) -> Vec<PublicKey> {
+        let mut members: Vec<PublicKey> = self.recipients.iter().map(|(p, _)| *p).collect();
+        members.push(self.sender);
+        members.sort_by_key(|p| p.as_bytes());
+        members.dedup();
+        members
+    }
+
+    /// The unsigned rumor, to seal and wrap
+    pub fn to_rumor(&self) -> Result<Rumor, Error> {
+        let mut tags: Vec<Tag> = self
+            .recipients
+            .iter()
+            .map(|(pubkey, relay)| Tag::Pubkey {
+                pubkey: (*pubkey).into(),
+                recommended_relay_url: relay.clone(),
+                petname: None,
+            })
+            .collect();
+        if let Some((id, relay)) = &self.reply_to {
+            tags.push(Tag::Event {
+                id: *id,
+                recommended_relay_url: relay.clone(),
+                marker: None,
+                pubkey: None,
+            });
+        }
+        if let Some(subject) = &self.subject {
+            tags.push(Tag::Subject(subject.clone()));
+        }
+
+        let (kind, content) = match &self.body {
+            PrivateMessageBody::Chat(message) => (EventKind::PrivateDirectMessage, message.clone()),
+            PrivateMessageBody::File(file) => {
+                let other = |tag: &str, value: &str| Tag::Other {
+                    tag: tag.to_owned(),
+                    data: vec![value.to_owned()],
+                };
+                tags.push(other("file-type", &file.file_type));
+                tags.push(other("encryption-algorithm", &file.encryption_algorithm));
+                tags.push(other("decryption-key", &file.decryption_key));
+                tags.push(other("decryption-nonce", &file.decryption_nonce));
+                tags.push(other("x", &file.hash));
+                if let Some(original_hash) = &file.original_hash {
+                    tags.push(other("ox", original_hash));
+                }
+                if let Some(size) = file.size {
+                    tags.push(other("size", &size.to_string()));
+                }
+                if let Some(dimensions) = &file.dimensions {
+                    tags.push(other("dim", dimensions));
+                }
+                (EventKind::FileMessage, file.url.0.clone())
+            }
+        };
+
+        Rumor::new(PreEvent {
+            pubkey: self.sender,
+            created_at: self.created_at,
+            kind,
+            tags,
+            content,
+            ots: None,
+        })
+    }
+
+    /// Read a kind 14 or 15 rumor. A file message must have the tags needed
+    /// to fetch and decrypt the file.
+    pub fn from_rumor(rumor: &Rumor) -> Result<PrivateDirectMessage, Error> {
+        if rumor.kind != EventKind::PrivateDirectMessage && rumor.kind != EventKind::FileMessage {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut recipients: Vec<(PublicKey, Option<UncheckedUrl>)> = Vec::new();
+        let mut subject: Option<String> = None;
+        let mut reply_to: Option<(Id, Option<UncheckedUrl>)> = None;
+        for tag in rumor.tags.iter() {
+            match tag {
+                Tag::Pubkey {
+                    pubkey,
+                    recommended_relay_url,
+                    ..
+                } => recipients.push((
+                    PublicKey::try_from_hex_string(pubkey.as_str())?,
+                    recommended_relay_url.clone(),
+                )),
+                Tag::Subject(s) => subject = Some(s.clone()),
+                Tag::Event {
+                    id,
+                    recommended_relay_url,
+                    ..
+                } => reply_to = Some((*id, recommended_relay_url.clone())),
+                _ => {}
+            }
+        }
+
+        let body = if rumor.kind == EventKind::PrivateDirectMessage {
+            PrivateMessageBody::Chat(rumor.content.clone())
+        } else {
+            let get = |name: &str| {
+                rumor.tags.iter().find_map(|tag| match tag {
+                    Tag::Other { tag, data } if tag == name => data.first().cloned(),
+                    _ => None,
+                })
+            };
+            let required = |name: &str| {
+                get(name).ok_or_else(|| {
+                    Error::AssertionFailed(format!("File message has no '{name}' tag"))
+                })
+            };
+            PrivateMessageBody::File(EncryptedFile {
+                url: UncheckedUrl(rumor.content.clone()),
+                file_type: required("file-type")?,
+                encryption_algorithm: required("encryption-algorithm")?,
+                decryption_key: required("decryption-key")?,
+                decryption_nonce: required("decryption-nonce")?,
+                hash: required("x")?,
+                original_hash: get("ox"),
+                size: get("size").and_then(|s| s.parse().ok()),
+                dimensions: get("dim"),
+            })
+        };
+
+        Ok(PrivateDirectMessage {
+            sender: rumor.pubkey,
+            recipients,
+            subject,
+            reply_to,
+            created_at: rumor.created_at,
+            body,
+        })
+    }
+
+    /// Seal and gift wrap the message for each recipient and for the
+    /// sender, whose key `signer` must be, ready to publish to each one's
+    /// DM relays. The sender's own copy is last.
+    pub fn giftwrap(&self, signer: &PrivateKey) -> Result<Vec<Event>, Error> {
+        let recipients: Vec<PublicKey> = self.recipients.iter().map(|(p, _)| *p).collect();
+        giftwrap_for_recipients(&self.to_rumor()?, signer, &recipients)
+    }
+
+    /// Unwrap a gift wrap with the recipient's key and read the message
+    pub fn from_giftwrap(
+        wrap: &GiftWrap,
+        private_key: &PrivateKey,
+    ) -> Result<PrivateDirectMessage, Error> {
+        PrivateDirectMessage::from_rumor(&wrap.unwrap_rumor(private_key)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_private_direct_message() {
+        let alice = PrivateKey::generate();
+        let bob = PrivateKey::generate();
+        let carol = PrivateKey::generate();
+
+        let mut message = PrivateDirectMessage::chat(
+            alice.public_key(),
+            vec![bob.public_key(), carol.public_key()],
+            "hi both".to_owned(),
+            Unixtime(1700000000),
+        );
+        message.subject = Some("plans".to_owned());
+        message.reply_to = Some((Event::mock().id, None));
+
+        let rumor = message.to_rumor().unwrap();
+        assert_eq!(rumor.kind, EventKind::PrivateDirectMessage);
+        assert_eq!(PrivateDirectMessage::from_rumor(&rumor).unwrap(), message);
+
+        let wraps = message.giftwrap(&alice).unwrap();
+        assert_eq!(wraps.len(), 3);
+        for (wrap, key) in wraps.iter().zip([&bob, &carol, &alice]) {
+            let wrap = GiftWrap::from_event(wrap.clone()).unwrap();
+            assert_eq!(wrap.recipient(), Some(key.public_key()));
+            let read = PrivateDirectMessage::from_giftwrap(&wrap, key).unwrap();
+            assert_eq!(read, message);
+            assert_eq!(read.conversation(), message.conversation());
+        }
+        assert!(message.giftwrap(&bob).is_err());
+    }
+
+    #[test]
+    fn test_private_file_message() {
+        let alice = PrivateKey::generate();
+        let bob = PrivateKey::generate();
+        let file = EncryptedFile {
+            url: UncheckedUrl::from_str("https://files.example.com/abc"),
+            file_type: "image/jpeg".to_owned(),
+            encryption_algorithm: "aes-gcm".to_owned(),
+            decryption_key: "key".to_owned(),
+            decryption_nonce: "nonce".to_owned(),
+            hash: "ab".repeat(32),
+            original_hash: None,
+            size: Some(1234),
+            dimensions: Some("640x480".to_owned()),
+        };
+        let message = PrivateDirectMessage {
+            body: PrivateMessageBody::File(file),
+            ..PrivateDirectMessage::chat(
+                alice.public_key(),
+                vec![bob.public_key()],
+                String::new(),
+                Unixtime(1700000000),
+            )
+        };
+
+        let mut rumor = message.to_rumor().unwrap();
+        assert_eq!(rumor.kind, EventKind::FileMessage);
+        assert_eq!(rumor.content, "https://files.example.com/abc");
+        assert_eq!(PrivateDirectMessage::from_rumor(&rumor).unwrap(), message);
+
+        rumor.tags.retain(|tag| tag.tagname() != "decryption-key");
+        assert!(PrivateDirectMessage::from_rumor(&rumor).is_err());
+        rumor.kind = EventKind::TextNote;
+        assert!(matches!(
+            PrivateDirectMessage::from_rumor(&rumor),
+            Err(Error::WrongEventKind)
+        ));
+    }
+}
@@ -36,35 +36,31 @@ impl Profile {
 
     /// Import from a bech32 encoded string ("nprofile")
     pub fn try_from_bech32_string(s: &str) -> Result<Profile, Error> {
-        let data = bech32::decode(s)?;
-        if data.0 != "nprofile" {
-            Err(Error::WrongBech32("nprofile".to_string(), data.0))
-        } else {
-            let tlv = Vec::<u8>::from_base32(&data.1)?;
-            if tlv[0] != 0 || tlv[1] != 32 {
+        let data = crate::error::bech32_decode_as(s, "nprofile", None)?;
+        let tlv = Vec::<u8>::from_base32(&data)?;
+        if tlv[0] != 0 || tlv[1] != 32 {
+            return Err(Error::InvalidProfile);
+        }
+        let pubkey = PublicKey::from_bytes(&tlv[2..2 + 32])?;
+        let mut relays: Vec<UncheckedUrl> = Vec::new();
+        let mut pos = 2 + 32;
+        while tlv.len() >= pos + 2 {
+            let typ = tlv[pos];
+            let len = tlv[pos + 1];
+            pos += 2;
+            if typ != 1 {
                 return Err(Error::InvalidProfile);
             }
-            let pubkey = PublicKey::from_bytes(&tlv[2..2 + 32])?;
-            let mut relays: Vec<UncheckedUrl> = Vec::new();
-            let mut pos = 2 + 32;
-            while tlv.len() >= pos + 2 {
-                let typ = tlv[pos];
-                let len = tlv[pos + 1];
-                pos += 2;
-                if typ != 1 {
-                    return Err(Error::InvalidProfile);
-                }
-                if tlv.len() < pos + len as usize {
-                    return Err(Error::InvalidProfile);
-                }
-                let relay_bytes = &tlv[pos..pos + (len as usize)];
-                let relay_str = std::str::from_utf8(relay_bytes)?;
-                let relay = UncheckedUrl::from_str(relay_str);
-                relays.push(relay);
-                pos += len as usize;
+            if tlv.len() < pos + len as usize {
+                return Err(Error::InvalidProfile);
             }
-            Ok(Profile { pubkey, relays })
+            let relay_bytes = &tlv[pos..pos + (len as usize)];
+            let relay_str = std::str::from_utf8(relay_bytes)?;
+            let relay = UncheckedUrl::from_str(relay_str);
+            relays.push(relay);
+            pos += len as usize;
         }
+        Ok(Profile { pubkey, relays })
     }
 
     // Mock data for testing
@@ -0,0 +1,219 @@
+use super::PublicKey;
+use crate::Error;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{Read, Write};
+
+// Bloom filter bits per key and hashes per key, for a false positive rate
+// just under 1%
+const BITS_PER_KEY: usize = 10;
+const HASHES: u64 = 7;
+const MIN_CAPACITY: usize = 1024;
+
+const MAGIC: &[u8; 4] = b"PKS1";
+
+/// A set of public keys, such as a ban list or a mute list, built to stay fast
+/// with millions of keys.
+///
+/// Lookups first check a bloom filter, which answers most misses without
+/// touching the keys themselves, and confirm hits exactly, so there are no
+/// false positives. Public keys are already uniformly random, so the filter
+/// uses their bytes directly rather than hashing them.
+///
+/// The set can be saved with `write_to()` and loaded with `read_from()`.
+#[derive(Clone)]
+pub struct PubkeySet {
+    keys: HashSet<[u8; 32]>,
+    bloom: Vec<u64>,
+    capacity: usize,
+}
+
+impl Default for PubkeySet {
+    fn default() -> PubkeySet {
+        PubkeySet::with_capacity(MIN_CAPACITY)
+    }
+}
+
+impl fmt::Debug for PubkeySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PubkeySet")
+            .field("len", &self.keys.len())
+            .field("bloom_bits", &(self.bloom.len() * 64))
+            .finish()
+    }
+}
+
+impl PartialEq for PubkeySet {
+    fn eq(&self, other: &PubkeySet) -> bool {
+        self.keys == other.keys
+    }
+}
+
+impl Eq for PubkeySet {}
+
+impl PubkeySet {
+    /// Create an empty set
+    pub fn new() -> PubkeySet {
+        Default::default()
+    }
+
+    /// Create an empty set sized for `capacity` keys. It still grows past that.
+    pub fn with_capacity(capacity: usize) -> PubkeySet {
+        let capacity = capacity.max(MIN_CAPACITY);
+        PubkeySet {
+            keys: HashSet::with_capacity(capacity),
+            bloom: vec![0; (capacity * BITS_PER_KEY).div_ceil(64)],
+            capacity,
+        }
+    }
+
+    /// Add a key. Returns false if it was already present.
+    pub fn insert(&mut self, pubkey: PublicKey) -> bool {
+        let bytes: [u8; 32] = pubkey.0.to_bytes().into();
+        if !self.keys.insert(bytes) {
+            return false;
+        }
+        if self.keys.len() > self.capacity {
+            self.rebuild(self.capacity * 2);
+        } else {
+            self.set_bits(&bytes);
+        }
+        true
+    }
+
+    /// Remove a key. Returns false if it was not present.
+    pub fn remove(&mut self, pubkey: &PublicKey) -> bool {
+        // The bloom filter bits stay set; the exact check covers them
+        let bytes: [u8; 32] = pubkey.0.to_bytes().into();
+        self.keys.remove(&bytes)
+    }
+
+    /// Is the key in the set?
+    pub fn contains(&self, pubkey: &PublicKey) -> bool {
+        let bytes: [u8; 32] = pubkey.0.to_bytes().into();
+        bloom_indices(&bytes, self.bloom.len() * 64)
+            .all(|i| self.bloom[i / 64] & (1 << (i % 64)) != 0)
+            && self.keys.contains(&bytes)
+    }
+
+    /// The number of keys
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Save the set: a 4 byte magic number, the number of keys as a
+    /// little-endian u64, then the 32 bytes of each key
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(self.keys.len() as u64).to_le_bytes())?;
+        for key in self.keys.iter() {
+            writer.write_all(key)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load a set saved by `write_to()`
+    pub fn read_from<R: Read>(mut reader: R) -> Result<PubkeySet, Error> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(Error::AssertionFailed("Not a saved pubkey set".to_owned()));
+        }
+        let mut count = [0; 8];
+        reader.read_exact(&mut count)?;
+        let count = u64::from_le_bytes(count) as usize;
+
+        let mut set = PubkeySet {
+            keys: HashSet::new(),
+            bloom: vec![],
+            capacity: 0,
+        };
+        let mut key = [0; 32];
+        for _ in 0..count {
+            reader.read_exact(&mut key)?;
+            let _ = set.keys.insert(key);
+        }
+        set.rebuild(set.keys.len());
+        Ok(set)
+    }
+
+    // Resize the bloom filter for `capacity` keys and refill it
+    fn rebuild(&mut self, capacity: usize) {
+        self.capacity = capacity.max(MIN_CAPACITY);
+        self.bloom = vec![0; (self.capacity * BITS_PER_KEY).div_ceil(64)];
+        let keys = std::mem::take(&mut self.keys);
+        for key in keys.iter() {
+            self.set_bits(key);
+        }
+        self.keys = keys;
+    }
+
+    fn set_bits(&mut self, bytes: &[u8; 32]) {
+        for i in bloom_indices(bytes, self.bloom.len() * 64) {
+            self.bloom[i / 64] |= 1 << (i % 64);
+        }
+    }
+}
+
+// The bit positions for a key, by double hashing with two words of the key
+fn bloom_indices(bytes: &[u8; 32], bits: usize) -> impl Iterator<Item = usize> {
+    let word = |n: usize| {
+        let mut w = [0; 8];
+        w.copy_from_slice(&bytes[n * 8..n * 8 + 8]);
+        u64::from_le_bytes(w)
+    };
+    let h1 = word(0);
+    let h2 = word(1) | 1;
+    (0..HASHES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits as u64) as usize)
+}
+
+impl FromIterator<PublicKey> for PubkeySet {
+    fn from_iter<I: IntoIterator<Item = PublicKey>>(iter: I) -> PubkeySet {
+        let mut set = PubkeySet::new();
+        for pubkey in iter {
+            let _ = set.insert(pubkey);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn test_pubkey_set() {
+        let keys: Vec<PublicKey> = (0..3000)
+            .map(|_| PrivateKey::generate().public_key())
+            .collect();
+        let (inside, outside) = keys.split_at(2000);
+
+        let mut set: PubkeySet = inside.iter().copied().collect();
+        assert_eq!(set.len(), 2000);
+        assert!(!set.insert(inside[0]));
+        assert!(inside.iter().all(|k| set.contains(k)));
+        assert!(!outside.iter().any(|k| set.contains(k)));
+
+        assert!(set.remove(&inside[0]));
+        assert!(!set.remove(&inside[0]));
+        assert!(!set.contains(&inside[0]));
+
+        let mut saved: Vec<u8> = Vec::new();
+        set.write_to(&mut saved).unwrap();
+        assert_eq!(saved.len(), 4 + 8 + 1999 * 32);
+        let loaded = PubkeySet::read_from(saved.as_slice()).unwrap();
+        assert_eq!(loaded, set);
+        assert!(loaded.contains(&inside[1]));
+        assert!(!loaded.contains(&outside[0]));
+
+        assert!(PubkeySet::read_from(&b"nope"[..]).is_err());
+        assert!(PubkeySet::read_from(&saved[..100]).is_err());
+    }
+}
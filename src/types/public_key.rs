@@ -3,6 +3,7 @@ use bech32::{FromBase32, ToBase32};
 use derive_more::{AsMut, AsRef, Deref, Display, From, FromStr, Into};
 use k256::ecdsa::signature::Verifier;
 use k256::schnorr::VerifyingKey;
+use k256::sha2::{Digest, Sha256};
 use serde::de::{Deserializer, Visitor};
 use serde::ser::Serializer;
 use serde::{Deserialize, Serialize};
@@ -44,16 +45,12 @@ impl PublicKey {
 
     /// Import from a bech32 encoded string
     pub fn try_from_bech32_string(s: &str) -> Result<PublicKey, Error> {
-        let data = bech32::decode(s)?;
-        if data.0 != "npub" {
-            Err(Error::WrongBech32("npub".to_string(), data.0))
+        let data = crate::error::bech32_decode_as(s, "npub", Some(63))?;
+        let decoded = Vec::<u8>::from_base32(&data)?;
+        if decoded.len() != 32 {
+            Err(Error::InvalidPublicKey)
         } else {
-            let decoded = Vec::<u8>::from_base32(&data.1)?;
-            if decoded.len() != 32 {
-                Err(Error::InvalidPublicKey)
-            } else {
-                Ok(PublicKey(VerifyingKey::from_bytes(&decoded)?))
-            }
+            Ok(PublicKey(VerifyingKey::from_bytes(&decoded)?))
         }
     }
 
@@ -71,6 +68,35 @@ impl PublicKey {
         self.0.to_bytes().to_vec()
     }
 
+    /// Does this public key start with the hex `prefix`? The prefix must be whole
+    /// bytes (an even number of hex digits) and no longer than the key. Case is
+    /// ignored.
+    pub fn matches_hex_prefix(&self, prefix: &str) -> Result<bool, Error> {
+        if prefix.len() > 64 || prefix.len() % 2 != 0 {
+            return Err(Error::InvalidPublicKeyPrefix);
+        }
+        let bytes = hex::decode(prefix).map_err(|_| Error::InvalidPublicKeyPrefix)?;
+        Ok(self.as_bytes().starts_with(&bytes))
+    }
+
+    /// A short form of the npub for display, its first and last few
+    /// characters, like "npub1abcde…vwxyz"
+    pub fn abbrev(&self) -> String {
+        let npub = self.as_bech32_string();
+        let head = npub.get(..10).unwrap_or_default();
+        let tail = npub.get(npub.len() - 5..).unwrap_or_default();
+        format!("{head}…{tail}")
+    }
+
+    /// A short fingerprint for logs and UIs: the first 8 hex digits of the
+    /// SHA-256 of the key. It tells keys apart at a glance, but at 32 bits a
+    /// matching key can be ground out in minutes, so it must not be used to
+    /// check that a key is the expected one; compare the full key for that.
+    pub fn fingerprint(&self) -> String {
+        let hash = Sha256::digest(self.0.to_bytes());
+        hex::encode(hash.get(..4).unwrap_or_default())
+    }
+
     /// Verify a signed message
     pub fn verify(&self, message: &[u8], signature: Signature) -> Result<(), Error> {
         Ok(self.0.verify(message, &signature.0)?)
@@ -323,4 +349,27 @@ mod test {
 
         assert_eq!(pk, decoded);
     }
+
+    #[test]
+    fn test_pubkey_matches_hex_prefix() {
+        let pk = PublicKey::mock();
+        let hex = pk.as_hex_string();
+        assert!(pk.matches_hex_prefix(hex.get(..8).unwrap()).unwrap());
+        assert!(pk.matches_hex_prefix(&hex.to_uppercase()).unwrap());
+        assert!(pk.matches_hex_prefix(hex.get(..7).unwrap()).is_err());
+        assert!(pk.matches_hex_prefix("zz").is_err());
+    }
+
+    #[test]
+    fn test_pubkey_short_forms() {
+        let pk = PublicKey::try_from_hex_string(
+            "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d",
+        )
+        .unwrap();
+        assert_eq!(pk.abbrev(), "npub180cvv…jh6w6");
+
+        let fingerprint = pk.fingerprint();
+        assert_eq!(fingerprint, "1093b285");
+        assert_ne!(fingerprint, PublicKey::mock().fingerprint());
+    }
 }
@@ -0,0 +1,450 @@
+use super::{ClientMessage, Event, Id, OkPrefix, PrivateKey, RelayMessage, RelayUrl, Unixtime};
+use crate::Error;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How a publish ended
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PublishOutcome {
+    /// The relay accepted the event
+    Published,
+
+    /// The relay required authentication (NIP-42), then accepted the event
+    PublishedAfterAuth,
+
+    /// The relay rejected the event, or the authentication. This holds the
+    /// message from the relay's OK.
+    Rejected(String),
+}
+
+/// Publishes an event to one relay, authenticating (NIP-42) and retrying
+/// once if the relay answers `auth-required:`.
+///
+/// This does no I/O. Send the message from `start()`, then pass each message
+/// from the relay to `handle()` and send whatever it returns, until
+/// `outcome()` is known.
+#[derive(Clone, Debug)]
+pub struct Publisher {
+    relay: RelayUrl,
+    event: Event,
+    challenge: Option<String>,
+    awaiting_challenge: bool,
+    auth_id: Option<Id>,
+    retried: bool,
+    outcome: Option<PublishOutcome>,
+}
+
+impl Publisher {
+    /// Prepare to publish `event` to `relay`
+    pub fn new(relay: RelayUrl, event: Event) -> Publisher {
+        Publisher {
+            relay,
+            event,
+            challenge: None,
+            awaiting_challenge: false,
+            auth_id: None,
+            retried: false,
+            outcome: None,
+        }
+    }
+
+    /// The EVENT message to send first
+    pub fn start(&self) -> ClientMessage {
+        ClientMessage::Event(Box::new(self.event.clone()))
+    }
+
+    /// How the publish ended, once it has
+    pub fn outcome(&self) -> Option<&PublishOutcome> {
+        self.outcome.as_ref()
+    }
+
+    /// Handle a message from the relay, returning a message to send back if
+    /// one is needed. Authentication events are signed with `private_key`,
+    /// created at `now`.
+    pub fn handle(
+        &mut self,
+        message: &RelayMessage,
+        private_key: &PrivateKey,
+        now: Unixtime,
+    ) -> Result<Option<ClientMessage>, Error> {
+        if self.outcome.is_some() {
+            return Ok(None);
+        }
+
+        match message {
+            RelayMessage::Auth(challenge) => {
+                self.challenge = Some(challenge.to_owned());
+                if self.awaiting_challenge {
+                    self.awaiting_challenge = false;
+                    return self.authenticate(private_key, now).map(Some);
+                }
+                Ok(None)
+            }
+            RelayMessage::Ok(id, ok, reason) if Some(*id) == self.auth_id => {
+                self.auth_id = None;
+                if *ok {
+                    self.retried = true;
+                    Ok(Some(self.start()))
+                } else {
+                    self.outcome = Some(PublishOutcome::Rejected(reason.to_owned()));
+                    Ok(None)
+                }
+            }
+            RelayMessage::Ok(id, ok, reason) if *id == self.event.id => {
+                if *ok {
+                    self.outcome = Some(if self.retried {
+                        PublishOutcome::PublishedAfterAuth
+                    } else {
+                        PublishOutcome::Published
+                    });
+                    return Ok(None);
+                }
+
+                let auth_required = reason
+                    .strip_prefix(OkPrefix::AuthRequired.as_str())
+                    .map(|rest| rest.starts_with(':'))
+                    .unwrap_or(false);
+                if !auth_required || self.retried {
+                    self.outcome = Some(PublishOutcome::Rejected(reason.to_owned()));
+                    Ok(None)
+                } else if self.challenge.is_some() {
+                    self.authenticate(private_key, now).map(Some)
+                } else {
+                    // The relay should send its challenge shortly
+                    self.awaiting_challenge = true;
+                    Ok(None)
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn authenticate(
+        &mut self,
+        private_key: &PrivateKey,
+        now: Unixtime,
+    ) -> Result<ClientMessage, Error> {
+        let challenge = self.challenge.as_deref().unwrap_or("");
+        let auth = Event::new_auth(private_key, &self.relay, challenge, now)?;
+        self.auth_id = Some(auth.id);
+        Ok(ClientMessage::Auth(Box::new(auth)))
+    }
+}
+
+/// What became of an event sent to several relays, from `PendingPublishes`
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PublishReport {
+    /// The relays that accepted it
+    pub accepted_on: Vec<RelayUrl>,
+
+    /// The relays that rejected it, with the message from their OK
+    pub rejected_on: Vec<(RelayUrl, String)>,
+
+    /// The relays that did not answer in time
+    pub timed_out: Vec<RelayUrl>,
+}
+
+impl PublishReport {
+    /// Did any relay accept it?
+    pub fn accepted(&self) -> bool {
+        !self.accepted_on.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct Tracked {
+    waiting: Vec<(RelayUrl, Unixtime)>,
+    report: PublishReport,
+}
+
+/// Matches the OK messages coming back from relays with the events sent to
+/// them, for any number of events and relays at once.
+///
+/// This does no I/O. Call `sent()` for each relay an event is sent to, pass
+/// each OK to `handle()` with the relay it came from, and call `expire()`
+/// now and then. An event's `PublishReport` is ready from `take_finished()`
+/// once every relay has answered or timed out.
+#[derive(Clone, Debug)]
+pub struct PendingPublishes {
+    timeout: Duration,
+    events: HashMap<Id, Tracked>,
+}
+
+impl PendingPublishes {
+    /// Track publishes, giving up on a relay that has not answered within
+    /// `timeout`
+    pub fn new(timeout: Duration) -> PendingPublishes {
+        PendingPublishes {
+            timeout,
+            events: HashMap::new(),
+        }
+    }
+
+    /// Record that event `id` was sent to `relay` at `now`. Sending it to the
+    /// same relay again restarts the wait, unless that relay already answered.
+    pub fn sent(&mut self, id: Id, relay: RelayUrl, now: Unixtime) {
+        let tracked = self.events.entry(id).or_default();
+        let report = &tracked.report;
+        if report.accepted_on.contains(&relay)
+            || report.rejected_on.iter().any(|(r, _)| *r == relay)
+        {
+            return;
+        }
+        tracked.report.timed_out.retain(|r| *r != relay);
+        tracked.waiting.retain(|(r, _)| *r != relay);
+        tracked.waiting.push((relay, now));
+    }
+
+    /// Handle a message from `relay`. Returns true if it was the OK for an
+    /// event sent there. A late OK from a relay already timed out still
+    /// counts.
+    pub fn handle(&mut self, relay: &RelayUrl, message: &RelayMessage) -> bool {
+        let (id, ok, reason) = match message {
+            RelayMessage::Ok(id, ok, reason) => (id, *ok, reason),
+            _ => return false,
+        };
+        let tracked = match self.events.get_mut(id) {
+            Some(tracked) => tracked,
+            None => return false,
+        };
+        let before = tracked.waiting.len() + tracked.report.timed_out.len();
+        tracked.waiting.retain(|(r, _)| r != relay);
+        tracked.report.timed_out.retain(|r| r != relay);
+        if tracked.waiting.len() + tracked.report.timed_out.len() == before {
+            return false; // not sent there, or answered already
+        }
+        if ok {
+            tracked.report.accepted_on.push(relay.to_owned());
+        } else {
+            tracked
+                .report
+                .rejected_on
+                .push((relay.to_owned(), reason.to_owned()));
+        }
+        true
+    }
+
+    /// Give up on relays that have not answered within the timeout by `now`
+    pub fn expire(&mut self, now: Unixtime) {
+        let timeout = self.timeout;
+        for tracked in self.events.values_mut() {
+            let (expired, waiting): (Vec<_>, Vec<_>) = tracked
+                .waiting
+                .drain(..)
+                .partition(|(_, sent_at)| *sent_at + timeout <= now);
+            tracked.waiting = waiting;
+            tracked
+                .report
+                .timed_out
+                .extend(expired.into_iter().map(|(relay, _)| relay));
+        }
+    }
+
+    /// How many relays have yet to answer about event `id`
+    pub fn waiting(&self, id: &Id) -> usize {
+        self.events.get(id).map(|t| t.waiting.len()).unwrap_or(0)
+    }
+
+    /// Take the reports of events that every relay has answered about or
+    /// timed out on, and stop tracking them
+    pub fn take_finished(&mut self) -> Vec<(Id, PublishReport)> {
+        let finished: Vec<Id> = self
+            .events
+            .iter()
+            .filter(|(_, t)| t.waiting.is_empty())
+            .map(|(id, _)| *id)
+            .collect();
+        finished
+            .into_iter()
+            .filter_map(|id| self.events.remove(&id).map(|t| (id, t.report)))
+            .collect()
+    }
+
+    /// Stop tracking event `id` and report on it now, counting relays that
+    /// have not answered as timed out
+    pub fn finish(&mut self, id: &Id) -> Option<PublishReport> {
+        let mut tracked = self.events.remove(id)?;
+        tracked
+            .report
+            .timed_out
+            .extend(tracked.waiting.into_iter().map(|(relay, _)| relay));
+        Some(tracked.report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EventKind, PreEvent, Tag};
+
+    fn note(private_key: &PrivateKey) -> Event {
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::TextNote,
+            tags: vec![],
+            content: "hello".to_owned(),
+            ots: None,
+        };
+        Event::new(pre, private_key).unwrap()
+    }
+
+    #[test]
+    fn test_publish_after_auth() {
+        let private_key = PrivateKey::generate();
+        let relay = RelayUrl::try_from_str("wss://relay.example.com").unwrap();
+        let event = note(&private_key);
+        let now = Unixtime(1700000100);
+
+        let mut publisher = Publisher::new(relay, event.clone());
+        assert!(matches!(publisher.start(), ClientMessage::Event(_)));
+
+        // The challenge arrives after the rejection
+        let reply = publisher
+            .handle(
+                &RelayMessage::Ok(event.id, false, "auth-required: please".to_owned()),
+                &private_key,
+                now,
+            )
+            .unwrap();
+        assert!(reply.is_none());
+        let auth = match publisher
+            .handle(&RelayMessage::Auth("abc".to_owned()), &private_key, now)
+            .unwrap()
+        {
+            Some(ClientMessage::Auth(auth)) => auth,
+            other => panic!("expected AUTH, got {other:?}"),
+        };
+        assert_eq!(auth.kind, EventKind::Auth);
+        assert!(auth.tags.contains(&Tag::Other {
+            tag: "challenge".to_owned(),
+            data: vec!["abc".to_owned()],
+        }));
+
+        let retry = publisher
+            .handle(
+                &RelayMessage::Ok(auth.id, true, "".to_owned()),
+                &private_key,
+                now,
+            )
+            .unwrap();
+        assert_eq!(retry, Some(ClientMessage::Event(Box::new(event.clone()))));
+        assert!(publisher.outcome().is_none());
+
+        let _ = publisher
+            .handle(
+                &RelayMessage::Ok(event.id, true, "".to_owned()),
+                &private_key,
+                now,
+            )
+            .unwrap();
+        assert_eq!(
+            publisher.outcome(),
+            Some(&PublishOutcome::PublishedAfterAuth)
+        );
+    }
+
+    #[test]
+    fn test_publish_rejected() {
+        let private_key = PrivateKey::generate();
+        let relay = RelayUrl::try_from_str("wss://relay.example.com").unwrap();
+        let event = note(&private_key);
+        let now = Unixtime(1700000100);
+
+        let mut publisher = Publisher::new(relay.clone(), event.clone());
+        let _ = publisher
+            .handle(&RelayMessage::Auth("abc".to_owned()), &private_key, now)
+            .unwrap();
+        let auth = match publisher
+            .handle(
+                &RelayMessage::Ok(event.id, false, "auth-required:".to_owned()),
+                &private_key,
+                now,
+            )
+            .unwrap()
+        {
+            Some(ClientMessage::Auth(auth)) => auth,
+            other => panic!("expected AUTH, got {other:?}"),
+        };
+        let _ = publisher
+            .handle(
+                &RelayMessage::Ok(auth.id, true, "".to_owned()),
+                &private_key,
+                now,
+            )
+            .unwrap();
+
+        // Only one retry
+        let reply = publisher
+            .handle(
+                &RelayMessage::Ok(event.id, false, "auth-required: still".to_owned()),
+                &private_key,
+                now,
+            )
+            .unwrap();
+        assert!(reply.is_none());
+        assert_eq!(
+            publisher.outcome(),
+            Some(&PublishOutcome::Rejected("auth-required: still".to_owned()))
+        );
+
+        let mut publisher = Publisher::new(relay, event.clone());
+        let _ = publisher
+            .handle(
+                &RelayMessage::Ok(event.id, true, "duplicate: have it".to_owned()),
+                &private_key,
+                now,
+            )
+            .unwrap();
+        assert_eq!(publisher.outcome(), Some(&PublishOutcome::Published));
+    }
+
+    #[test]
+    fn test_pending_publishes() {
+        let private_key = PrivateKey::generate();
+        let event = note(&private_key);
+        let relay = |n: u8| RelayUrl::try_from_str(&format!("wss://relay{n}.example.com")).unwrap();
+        let now = Unixtime(1700000100);
+
+        let mut pending = PendingPublishes::new(Duration::from_secs(10));
+        for n in 1..=4 {
+            pending.sent(event.id, relay(n), now);
+        }
+        assert_eq!(pending.waiting(&event.id), 4);
+
+        let ok = |ok: bool, reason: &str| RelayMessage::Ok(event.id, ok, reason.to_owned());
+        assert!(pending.handle(&relay(1), &ok(true, "")));
+        assert!(pending.handle(&relay(2), &ok(false, "blocked: spam")));
+        // A repeated OK, an OK from a relay it wasn't sent to, and other messages
+        assert!(!pending.handle(&relay(1), &ok(true, "duplicate:")));
+        assert!(!pending.handle(&relay(9), &ok(true, "")));
+        assert!(!pending.handle(&relay(3), &RelayMessage::Notice("hi".to_owned())));
+
+        pending.expire(now + Duration::from_secs(9));
+        assert!(pending.take_finished().is_empty());
+        pending.expire(now + Duration::from_secs(10));
+        assert_eq!(pending.waiting(&event.id), 0);
+
+        // A late answer still counts
+        assert!(pending.handle(&relay(3), &ok(true, "")));
+
+        let finished = pending.take_finished();
+        assert_eq!(
+            finished,
+            vec![(
+                event.id,
+                PublishReport {
+                    accepted_on: vec![relay(1), relay(3)],
+                    rejected_on: vec![(relay(2), "blocked: spam".to_owned())],
+                    timed_out: vec![relay(4)],
+                }
+            )]
+        );
+        assert!(finished[0].1.accepted());
+        assert!(!pending.handle(&relay(4), &ok(true, "")));
+
+        pending.sent(event.id, relay(1), now);
+        assert_eq!(pending.finish(&event.id).unwrap().timed_out, vec![relay(1)]);
+        assert!(pending.finish(&event.id).is_none());
+    }
+}
@@ -0,0 +1,382 @@
+use super::RelayUrl;
+use crate::Error;
+use std::fmt;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Which address family to try first when a relay has both
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressPreference {
+    /// IPv6 first, as RFC 8305 recommends
+    #[default]
+    Ipv6,
+
+    /// IPv4 first
+    Ipv4,
+}
+
+/// Timing for `connect_relay()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConnectConfig {
+    /// Which address family to try first
+    pub prefer: AddressPreference,
+
+    /// How long to wait on an attempt before also trying the next address
+    pub attempt_delay: Duration,
+
+    /// Give up after this long, counting from the start of DNS resolution
+    pub timeout: Duration,
+}
+
+impl Default for ConnectConfig {
+    fn default() -> ConnectConfig {
+        ConnectConfig {
+            prefer: AddressPreference::Ipv6,
+            attempt_delay: Duration::from_millis(250),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// One connection attempt made by `connect_relay()`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectAttempt {
+    /// The address tried
+    pub address: SocketAddr,
+
+    /// When the attempt started, after DNS resolution finished
+    pub started_after: Duration,
+
+    /// How long the attempt took, if it finished before another won
+    pub elapsed: Option<Duration>,
+
+    /// Why the attempt failed, if it did
+    pub error: Option<String>,
+}
+
+/// What `connect_relay()` did, successful or not, for diagnosing relays
+/// that work on one network but not another
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectReport {
+    /// The host looked up
+    pub host: String,
+
+    /// The port connected to
+    pub port: u16,
+
+    /// How long DNS resolution took
+    pub dns_time: Duration,
+
+    /// The addresses resolved, in the order they were to be tried
+    pub addresses: Vec<SocketAddr>,
+
+    /// The attempts made, in the order they started
+    pub attempts: Vec<ConnectAttempt>,
+
+    /// The address connected to, if any
+    pub connected: Option<SocketAddr>,
+
+    /// How long connecting took after DNS resolution
+    pub connect_time: Duration,
+}
+
+impl fmt::Display for ConnectReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}:{} resolved to {} address(es) in {:?}",
+            self.host,
+            self.port,
+            self.addresses.len(),
+            self.dns_time
+        )?;
+        for attempt in &self.attempts {
+            write!(
+                f,
+                "  {} started at +{:?}",
+                attempt.address, attempt.started_after
+            )?;
+            match (&attempt.error, attempt.elapsed) {
+                (Some(error), _) => writeln!(f, ", failed: {error}")?,
+                (None, Some(elapsed)) => writeln!(f, ", connected in {elapsed:?}")?,
+                (None, None) => writeln!(f, ", abandoned")?,
+            }
+        }
+        match self.connected {
+            Some(address) => write!(f, "connected to {address} in {:?}", self.connect_time),
+            None => write!(f, "could not connect"),
+        }
+    }
+}
+
+/// Open a TCP connection to a relay, for a websocket handshake over it.
+///
+/// All A and AAAA records are resolved, then tried alternating between
+/// families starting with the preferred one, a new attempt starting every
+/// `attempt_delay` (or as soon as one fails) until one connects (RFC 8305).
+/// The report is returned whether or not this succeeds.
+///
+/// The system resolver can't be cancelled, so DNS resolution runs on its own
+/// thread; if it outlasts the timeout, this returns and leaves that thread to
+/// finish in the background.
+pub fn connect_relay(
+    relay: &RelayUrl,
+    config: &ConnectConfig,
+) -> (Result<TcpStream, Error>, ConnectReport) {
+    let mut report = ConnectReport::default();
+    let start = Instant::now();
+
+    let url = match url::Url::parse(relay.as_str()) {
+        Ok(url) => url,
+        Err(e) => return (Err(e.into()), report),
+    };
+    report.host = match url.host() {
+        Some(url::Host::Domain(domain)) => domain.to_owned(),
+        Some(url::Host::Ipv4(ip)) => ip.to_string(),
+        Some(url::Host::Ipv6(ip)) => ip.to_string(),
+        None => return (Err(Error::InvalidUrlMissingAuthority), report),
+    };
+    report.port = url.port_or_known_default().unwrap_or(443);
+
+    let resolved = resolve(&report.host, report.port, config.timeout);
+    report.dns_time = start.elapsed();
+    let resolved: Vec<SocketAddr> = match resolved {
+        Ok(addresses) => addresses,
+        Err(e) => return (Err(e), report),
+    };
+    report.addresses = order_addresses(&resolved, config.prefer);
+
+    let connect_start = Instant::now();
+    let result = race(&report.addresses, config, start, &mut report.attempts);
+    report.connect_time = connect_start.elapsed();
+    report.connected = result.as_ref().ok().and_then(|s| s.peer_addr().ok());
+    (result, report)
+}
+
+// Look up the host on a thread, giving up after the timeout
+fn resolve(host: &str, port: u16, timeout: Duration) -> Result<Vec<SocketAddr>, Error> {
+    let (sender, receiver) = mpsc::channel();
+    let host = host.to_owned();
+    let _ = thread::spawn(move || {
+        let result = (host.as_str(), port)
+            .to_socket_addrs()
+            .map(|addresses| addresses.collect::<Vec<SocketAddr>>());
+        // The receiver is gone if resolution took too long
+        let _ = sender.send(result);
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "DNS resolution timed out",
+        )
+        .into()),
+    }
+}
+
+// Start attempts in order, staggered, and take the first to connect
+fn race(
+    addresses: &[SocketAddr],
+    config: &ConnectConfig,
+    start: Instant,
+    attempts: &mut Vec<ConnectAttempt>,
+) -> Result<TcpStream, Error> {
+    if addresses.is_empty() {
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "no addresses").into());
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let race_start = Instant::now();
+    let mut last_started: Option<Instant> = None;
+    let mut pending = 0;
+    let mut last_error: Option<std::io::Error> = None;
+
+    loop {
+        let remaining = match config.timeout.checked_sub(start.elapsed()) {
+            Some(remaining) if !remaining.is_zero() => remaining,
+            _ => break,
+        };
+
+        let next_due = last_started
+            .map(|t| t.elapsed() >= config.attempt_delay)
+            .unwrap_or(true);
+        if attempts.len() < addresses.len() && next_due {
+            let index = attempts.len();
+            let address = addresses[index];
+            attempts.push(ConnectAttempt {
+                address,
+                started_after: race_start.elapsed(),
+                elapsed: None,
+                error: None,
+            });
+            let sender = sender.clone();
+            let _ = thread::spawn(move || {
+                let attempt_start = Instant::now();
+                let result = TcpStream::connect_timeout(&address, remaining);
+                // The receiver is gone if another attempt already won
+                let _ = sender.send((index, attempt_start.elapsed(), result));
+            });
+            last_started = Some(Instant::now());
+            pending += 1;
+            continue;
+        }
+
+        if pending == 0 {
+            break;
+        }
+
+        let wait = match last_started {
+            Some(t) if attempts.len() < addresses.len() => {
+                config.attempt_delay.saturating_sub(t.elapsed())
+            }
+            _ => remaining,
+        };
+        match receiver.recv_timeout(wait.min(remaining)) {
+            Ok((index, elapsed, result)) => {
+                pending -= 1;
+                attempts[index].elapsed = Some(elapsed);
+                match result {
+                    Ok(stream) => return Ok(stream),
+                    Err(e) => {
+                        attempts[index].error = Some(e.to_string());
+                        last_error = Some(e);
+                        // Start the next attempt at once
+                        last_started = None;
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    Err(match last_error {
+        Some(e) if pending == 0 => e.into(),
+        _ => std::io::Error::new(std::io::ErrorKind::TimedOut, "connecting timed out").into(),
+    })
+}
+
+// Alternate between address families, the preferred one first
+fn order_addresses(addresses: &[SocketAddr], prefer: AddressPreference) -> Vec<SocketAddr> {
+    let mut v6: Vec<SocketAddr> = Vec::new();
+    let mut v4: Vec<SocketAddr> = Vec::new();
+    for address in addresses {
+        let family = if address.is_ipv6() { &mut v6 } else { &mut v4 };
+        if !family.contains(address) {
+            family.push(*address);
+        }
+    }
+    let (first, second) = match prefer {
+        AddressPreference::Ipv6 => (v6, v4),
+        AddressPreference::Ipv4 => (v4, v6),
+    };
+
+    let mut ordered = Vec::with_capacity(first.len() + second.len());
+    let mut first = first.into_iter();
+    let mut second = second.into_iter();
+    loop {
+        match (first.next(), second.next()) {
+            (None, None) => break,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_order_addresses() {
+        let a4: SocketAddr = "192.0.2.1:443".parse().unwrap();
+        let b4: SocketAddr = "192.0.2.2:443".parse().unwrap();
+        let c4: SocketAddr = "192.0.2.3:443".parse().unwrap();
+        let a6: SocketAddr = "[2001:db8::1]:443".parse().unwrap();
+        let resolved = [a4, b4, a4, c4, a6];
+
+        assert_eq!(
+            order_addresses(&resolved, AddressPreference::Ipv6),
+            vec![a6, a4, b4, c4]
+        );
+        assert_eq!(
+            order_addresses(&resolved, AddressPreference::Ipv4),
+            vec![a4, a6, b4, c4]
+        );
+        assert!(order_addresses(&[], AddressPreference::Ipv6).is_empty());
+    }
+
+    #[test]
+    fn test_race() {
+        // RelayUrl refuses loopback hosts, so race the addresses directly
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let live = listener.local_addr().unwrap();
+        let dead = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let config = ConnectConfig::default();
+
+        let mut attempts = Vec::new();
+        let stream = race(&[live], &config, Instant::now(), &mut attempts).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), live);
+        assert_eq!(attempts.len(), 1);
+        assert!(attempts[0].error.is_none());
+
+        // A refused attempt starts the next one at once
+        let mut attempts = Vec::new();
+        let stream = race(&[dead, live], &config, Instant::now(), &mut attempts).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), live);
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts[0].error.is_some());
+        assert!(attempts[1].started_after < config.attempt_delay);
+
+        let report = ConnectReport {
+            host: "127.0.0.1".to_owned(),
+            port: live.port(),
+            addresses: vec![dead, live],
+            attempts,
+            connected: Some(live),
+            ..Default::default()
+        };
+        assert!(report.to_string().contains("connected to 127.0.0.1"));
+
+        // Nothing listens once the listener is dropped
+        drop(listener);
+        let mut attempts = Vec::new();
+        assert!(race(&[live], &config, Instant::now(), &mut attempts).is_err());
+        assert!(attempts[0].error.is_some());
+        assert!(race(&[], &config, Instant::now(), &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_connect_relay() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = ConnectConfig {
+            prefer: AddressPreference::Ipv4,
+            ..Default::default()
+        };
+
+        // RelayUrl::try_from_str() refuses loopback hosts, so build them directly
+        for host in ["127.0.0.1", "localhost"] {
+            let relay = RelayUrl(format!("ws://{host}:{port}/"));
+            let (result, report) = connect_relay(&relay, &config);
+            let stream = result.unwrap();
+            assert_eq!(stream.peer_addr().unwrap().port(), port);
+            assert_eq!(report.host, host);
+            assert_eq!(report.port, port);
+            assert!(!report.addresses.is_empty());
+            assert_eq!(report.connected, Some(stream.peer_addr().unwrap()));
+        }
+
+        let relay = RelayUrl("ws://nonexistent.invalid/".to_owned());
+        let (result, report) = connect_relay(&relay, &config);
+        assert!(result.is_err());
+        assert!(report.addresses.is_empty());
+        assert!(report.connected.is_none());
+    }
+}
@@ -1,9 +1,10 @@
-use super::{PublicKeyHexPrefix, Url};
+use super::{EventKind, PublicKeyHexPrefix, Url};
 use serde::de::Error as DeError;
 use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
 use serde::ser::{Serialize, SerializeMap, Serializer};
 use serde_json::{json, Map, Value};
 use std::fmt;
+use std::time::Duration;
 
 /// Relay limitations
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -42,6 +43,147 @@ pub struct RelayLimitation {
     pub payment_required: bool,
 }
 
+/// Limits a relay puts on what clients send it. None means no limit is known.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RelayLimits {
+    /// Maximum length of a message, in bytes of JSON
+    pub max_message_length: Option<usize>,
+
+    /// Maximum number of filters in a REQ
+    pub max_filters: Option<usize>,
+
+    /// Maximum `limit` a filter may ask for
+    pub max_limit: Option<usize>,
+
+    /// Maximum length of a subscription id, in characters
+    pub max_subid_length: Option<usize>,
+}
+
+impl RelayLimits {
+    /// No limits
+    pub fn new() -> RelayLimits {
+        Default::default()
+    }
+}
+
+impl From<RelayLimitation> for RelayLimits {
+    fn from(limitation: RelayLimitation) -> RelayLimits {
+        // Relays report 0 when they do not impose a limit
+        let nonzero = |n: usize| if n == 0 { None } else { Some(n) };
+        RelayLimits {
+            max_message_length: nonzero(limitation.max_message_length),
+            max_filters: nonzero(limitation.max_filters),
+            max_limit: nonzero(limitation.max_limit),
+            max_subid_length: nonzero(limitation.max_subid_length),
+        }
+    }
+}
+
+/// A fee a relay charges (NIP-11)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelayFee {
+    /// The amount, in `unit`s
+    pub amount: u64,
+
+    /// The unit of the amount, such as "msats"
+    pub unit: String,
+
+    /// How long the fee pays for, in seconds, if it is for a period
+    pub period: Option<u64>,
+
+    /// The event kinds the fee applies to. Empty means all kinds.
+    pub kinds: Vec<EventKind>,
+}
+
+/// The fees a relay charges (NIP-11)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RelayFees {
+    /// Fees to be admitted to the relay
+    pub admission: Vec<RelayFee>,
+
+    /// Fees to subscribe to the relay for a period
+    pub subscription: Vec<RelayFee>,
+
+    /// Fees to publish events
+    pub publication: Vec<RelayFee>,
+}
+
+impl RelayFees {
+    /// Does the relay charge no fees (that we know of)?
+    pub fn is_empty(&self) -> bool {
+        self.admission.is_empty() && self.subscription.is_empty() && self.publication.is_empty()
+    }
+}
+
+/// How long a relay keeps some kinds of events (NIP-11 "retention")
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// The ranges of kinds this applies to, inclusive. Empty means all
+    /// kinds.
+    pub kinds: Vec<(u64, u64)>,
+
+    /// How long events are kept. None means forever, and zero that they are
+    /// not stored at all.
+    pub time: Option<Duration>,
+
+    /// How many events are kept, if limited
+    pub count: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Does this policy apply to `kind`?
+    pub fn applies_to(&self, kind: EventKind) -> bool {
+        let kind = u64::from(kind);
+        self.kinds.is_empty()
+            || self
+                .kinds
+                .iter()
+                .any(|(first, last)| (*first..=*last).contains(&kind))
+    }
+
+    /// Might the relay still have an event of `kind` that is `age` old under
+    /// this policy? Policies that do not apply to the kind keep everything.
+    /// A count limit cannot be predicted from age, so only a zero count
+    /// rules an event out.
+    pub fn keeps(&self, kind: EventKind, age: Duration) -> bool {
+        if !self.applies_to(kind) {
+            return true;
+        }
+        if self.count == Some(0) {
+            return false;
+        }
+        match self.time {
+            Some(time) => !time.is_zero() && age <= time,
+            None => true,
+        }
+    }
+
+    fn from_json(value: &Value) -> Option<RetentionPolicy> {
+        let map = value.as_object()?;
+        let mut policy = RetentionPolicy::default();
+        if let Some(Value::Array(kinds)) = map.get("kinds") {
+            for kind in kinds.iter() {
+                match kind {
+                    Value::Number(n) => {
+                        let n = n.as_u64()?;
+                        policy.kinds.push((n, n));
+                    }
+                    Value::Array(range) if range.len() == 2 => {
+                        policy.kinds.push((range[0].as_u64()?, range[1].as_u64()?));
+                    }
+                    _ => return None,
+                }
+            }
+        }
+        policy.time = map
+            .get("time")
+            .and_then(|t| t.as_u64())
+            .map(Duration::from_secs);
+        policy.count = map.get("count").and_then(|c| c.as_u64());
+        Some(policy)
+    }
+}
+
 /// Relay information document as described in NIP-11, supplied by a relay
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RelayInformationDocument {
@@ -100,6 +242,140 @@ impl RelayInformationDocument {
         self.supported_nips.contains(&nip)
     }
 
+    /// The limits the relay advertises, from `limitation` or, if that is not
+    /// set, the "limitation" object among the other fields
+    pub fn limits(&self) -> RelayLimits {
+        if let Some(limitation) = self.limitation {
+            return limitation.into();
+        }
+
+        let mut limits = RelayLimits::new();
+        if let Some(Value::Object(map)) = self.other.get("limitation") {
+            let get = |key: &str| {
+                map.get(key)
+                    .and_then(|v| v.as_u64())
+                    .filter(|n| *n > 0)
+                    .map(|n| n as usize)
+            };
+            limits.max_message_length = get("max_message_length");
+            limits.max_filters = get("max_filters");
+            limits.max_limit = get("max_limit");
+            limits.max_subid_length = get("max_subid_length");
+        }
+        limits
+    }
+
+    /// Where to pay the relay, from `payments_url` or, if that is not set,
+    /// the "payments_url" among the other fields
+    pub fn payments_url(&self) -> Option<Url> {
+        if let Some(url) = &self.payments_url {
+            return Some(url.clone());
+        }
+        self.other_url("payments_url")
+    }
+
+    /// The fees the relay charges, from the "fees" object among the other
+    /// fields. Fees without an amount or a unit are skipped.
+    pub fn fees(&self) -> RelayFees {
+        let mut fees = RelayFees::default();
+        if let Some(Value::Object(map)) = self.other.get("fees") {
+            let list = |key: &str| -> Vec<RelayFee> {
+                let entries = match map.get(key) {
+                    Some(Value::Array(entries)) => entries,
+                    _ => return vec![],
+                };
+                entries
+                    .iter()
+                    .filter_map(|entry| {
+                        Some(RelayFee {
+                            amount: entry.get("amount")?.as_u64()?,
+                            unit: entry.get("unit")?.as_str()?.to_owned(),
+                            period: entry.get("period").and_then(|p| p.as_u64()),
+                            kinds: entry
+                                .get("kinds")
+                                .and_then(|k| k.as_array())
+                                .map(|kinds| {
+                                    kinds
+                                        .iter()
+                                        .filter_map(|k| k.as_u64())
+                                        .map(EventKind::from)
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                        })
+                    })
+                    .collect()
+            };
+            fees.admission = list("admission");
+            fees.subscription = list("subscription");
+            fees.publication = list("publication");
+        }
+        fees
+    }
+
+    /// The retention policies the relay advertises, from the "retention"
+    /// array among the other fields. Malformed entries are skipped.
+    pub fn retention(&self) -> Vec<RetentionPolicy> {
+        match self.other.get("retention") {
+            Some(Value::Array(entries)) => entries
+                .iter()
+                .filter_map(RetentionPolicy::from_json)
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Might the relay still have an event of `kind` that is `age` old? The
+    /// policies that name the kind take precedence over those for all kinds.
+    pub fn keeps(&self, kind: EventKind, age: Duration) -> bool {
+        let retention = self.retention();
+        let (specific, general): (Vec<&RetentionPolicy>, Vec<&RetentionPolicy>) = retention
+            .iter()
+            .filter(|p| p.applies_to(kind))
+            .partition(|p| !p.kinds.is_empty());
+        let policies = if specific.is_empty() {
+            general
+        } else {
+            specific
+        };
+        policies.iter().all(|p| p.keeps(kind, age))
+    }
+
+    /// The URL of the relay's icon, from the "icon" among the other fields
+    pub fn icon_url(&self) -> Option<Url> {
+        self.other_url("icon")
+    }
+
+    /// Is `content_type`, from the response when fetching the icon, an image
+    /// type that a client can safely display? Parameters such as a charset
+    /// are ignored.
+    pub fn is_valid_icon_content_type(content_type: &str) -> bool {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        matches!(
+            mime.as_str(),
+            "image/png"
+                | "image/jpeg"
+                | "image/gif"
+                | "image/webp"
+                | "image/avif"
+                | "image/svg+xml"
+                | "image/x-icon"
+                | "image/vnd.microsoft.icon"
+        )
+    }
+
+    fn other_url(&self, key: &str) -> Option<Url> {
+        match self.other.get(key) {
+            Some(Value::String(s)) => Url::try_from_str(s).ok(),
+            _ => None,
+        }
+    }
+
     #[allow(dead_code)]
     pub(crate) fn mock() -> RelayInformationDocument {
         let mut m = Map::new();
@@ -257,4 +533,101 @@ mod test {
 
         assert_eq!(json2, expected_json2);
     }
+
+    #[test]
+    fn test_relay_information_document_limits() {
+        let json = r##"{ "name": "A Relay", "limitation": { "max_filters": 10, "max_subid_length": 64, "max_limit": 0 } }"##;
+        let rid: RelayInformationDocument = serde_json::from_str(json).unwrap();
+        let limits = rid.limits();
+        assert_eq!(limits.max_filters, Some(10));
+        assert_eq!(limits.max_subid_length, Some(64));
+        assert_eq!(limits.max_limit, None);
+        assert_eq!(limits.max_message_length, None);
+
+        let rid: RelayInformationDocument = Default::default();
+        assert_eq!(rid.limits(), RelayLimits::new());
+    }
+
+    #[test]
+    fn test_relay_information_document_fees_and_icon() {
+        let json = r##"{
+            "name": "A Paid Relay",
+            "icon": "https://relay.example.com/icon.png",
+            "payments_url": "https://relay.example.com/payments",
+            "fees": {
+                "admission": [{ "amount": 1000000, "unit": "msats" }],
+                "subscription": [{ "amount": 5000000, "unit": "msats", "period": 2592000 }],
+                "publication": [{ "kinds": [4], "amount": 100, "unit": "msats" }, { "unit": "msats" }]
+            }
+        }"##;
+        let rid: RelayInformationDocument = serde_json::from_str(json).unwrap();
+
+        let fees = rid.fees();
+        assert_eq!(fees.admission.len(), 1);
+        assert_eq!(fees.admission[0].amount, 1000000);
+        assert_eq!(fees.admission[0].unit, "msats");
+        assert_eq!(fees.subscription[0].period, Some(2592000));
+        assert_eq!(fees.publication.len(), 1);
+        assert_eq!(
+            fees.publication[0].kinds,
+            vec![EventKind::EncryptedDirectMessage]
+        );
+        assert!(!fees.is_empty());
+        assert!(RelayInformationDocument::default().fees().is_empty());
+
+        assert_eq!(
+            rid.payments_url().map(|u| u.as_str().to_owned()),
+            Some("https://relay.example.com/payments".to_owned())
+        );
+        assert_eq!(
+            rid.icon_url().map(|u| u.as_str().to_owned()),
+            Some("https://relay.example.com/icon.png".to_owned())
+        );
+
+        assert!(RelayInformationDocument::is_valid_icon_content_type(
+            "image/PNG; charset=binary"
+        ));
+        assert!(RelayInformationDocument::is_valid_icon_content_type(
+            "image/svg+xml"
+        ));
+        assert!(!RelayInformationDocument::is_valid_icon_content_type(
+            "text/html"
+        ));
+        assert!(!RelayInformationDocument::is_valid_icon_content_type(""));
+    }
+
+    #[test]
+    fn test_relay_information_document_retention() {
+        let json = r#"{
+            "retention": [
+                { "kinds": [0, 1, [5, 7], [40, 49]], "time": 3600 },
+                { "kinds": [[40000, 49999]], "time": 100 },
+                { "kinds": [[30000, 39999]], "count": 1000 },
+                { "kinds": [4], "time": 0 },
+                { "time": 86400, "count": 10000 },
+                { "kinds": ["bad"] }
+            ]
+        }"#;
+        let rid: RelayInformationDocument = serde_json::from_str(json).unwrap();
+        let retention = rid.retention();
+        assert_eq!(retention.len(), 5);
+        assert_eq!(retention[0].kinds, vec![(0, 0), (1, 1), (5, 7), (40, 49)]);
+        assert_eq!(retention[2].count, Some(1000));
+
+        let hours = |h: u64| Duration::from_secs(h * 3600);
+        assert!(retention[0].keeps(EventKind::Reaction, hours(1)));
+        assert!(!retention[0].keeps(EventKind::Reaction, hours(2)));
+        assert!(retention[0].keeps(EventKind::ContactList, hours(1000)));
+
+        assert!(rid.keeps(EventKind::TextNote, hours(1)));
+        assert!(!rid.keeps(EventKind::TextNote, hours(2)));
+        assert!(!rid.keeps(EventKind::EncryptedDirectMessage, hours(0)));
+        // Only the general policy applies to contact lists
+        assert!(rid.keeps(EventKind::ContactList, hours(24)));
+        assert!(!rid.keeps(EventKind::ContactList, hours(25)));
+        // The count limit of addressable events can't be predicted
+        assert!(rid.keeps(EventKind::LongFormContent, hours(1000)));
+
+        assert!(RelayInformationDocument::default().keeps(EventKind::TextNote, hours(1000)));
+    }
 }
@@ -1,7 +1,9 @@
-use super::{Event, Id, SubscriptionId};
+use super::{Event, Id, ParseProfile, SubscriptionId};
+use crate::Error;
 use serde::de::Error as DeError;
 use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
 use serde::ser::{Serialize, SerializeSeq, Serializer};
+use serde_json::value::RawValue;
 use std::fmt;
 
 /// A message from a relay to a client
@@ -24,6 +26,56 @@ pub enum RelayMessage {
 }
 
 impl RelayMessage {
+    /// If this is a NOTICE, what sort of notice it seems to be
+    pub fn notice_kind(&self) -> Option<NoticeKind> {
+        match self {
+            RelayMessage::Notice(text) => Some(NoticeKind::classify(text)),
+            _ => None,
+        }
+    }
+
+    /// Deserialize a message from JSON under a `ParseProfile`, which applies to
+    /// the event of an EVENT. Events are not verified.
+    pub fn from_json_with_profile(
+        json: &str,
+        profile: ParseProfile,
+    ) -> Result<RelayMessage, Error> {
+        let mut value: serde_json::Value = serde_json::from_str(json)?;
+        if let Some(array) = value.as_array_mut() {
+            let length = match array.first().and_then(|w| w.as_str()) {
+                Some("EVENT") => {
+                    if let Some(event) = array.get_mut(2) {
+                        profile.prepare_event(event)?;
+                    }
+                    3
+                }
+                Some("OK") => {
+                    if array.len() == 3 && profile.fills_missing_fields() {
+                        array.push(serde_json::Value::String("".to_owned()));
+                    }
+                    4
+                }
+                Some("NOTICE" | "EOSE" | "AUTH") => 2,
+                _ => array.len(),
+            };
+            if profile.fills_missing_fields() {
+                array.truncate(length);
+            }
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Deserialize a message from JSON under a `ParseProfile`, first
+    /// rejecting it with `Error::TooLarge` if it is over the size limits.
+    pub fn from_json_with_limits(
+        json: &str,
+        limits: MessageSizeLimits,
+        profile: ParseProfile,
+    ) -> Result<RelayMessage, Error> {
+        limits.check(json)?;
+        RelayMessage::from_json_with_profile(json, profile)
+    }
+
     // Mock data for testing
     #[allow(dead_code)]
     pub(crate) fn mock() -> RelayMessage {
@@ -31,6 +83,128 @@ impl RelayMessage {
     }
 }
 
+/// Limits on the size of messages from a relay, so that a malicious relay
+/// cannot exhaust memory with huge messages. Sizes are of the JSON, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MessageSizeLimits {
+    /// The largest message accepted
+    pub max_message: usize,
+
+    /// The largest event accepted in an EVENT message
+    pub max_event: usize,
+}
+
+impl Default for MessageSizeLimits {
+    fn default() -> MessageSizeLimits {
+        MessageSizeLimits {
+            max_message: 1024 * 1024,
+            max_event: 512 * 1024,
+        }
+    }
+}
+
+impl MessageSizeLimits {
+    /// Check a message against the limits without fully parsing it. The
+    /// message length is checked before anything else, and the event of an
+    /// EVENT is measured without building it.
+    pub fn check(&self, json: &str) -> Result<(), Error> {
+        if json.len() > self.max_message {
+            return Err(Error::TooLarge {
+                size: json.len(),
+                max: self.max_message,
+            });
+        }
+        if json.len() > self.max_event {
+            let parts: Vec<&RawValue> = serde_json::from_str(json)?;
+            if parts.first().map(|w| w.get()) == Some("\"EVENT\"") {
+                if let Some(event) = parts.get(2) {
+                    let size = event.get().len();
+                    if size > self.max_event {
+                        return Err(Error::TooLarge {
+                            size,
+                            max: self.max_event,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What a NOTICE is about. Notice texts are not standardized, so this is a
+/// best guess from the wording of popular relays (strfry, nostr-rs-relay,
+/// nostream) and the NIP-01 machine-readable prefixes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NoticeKind {
+    /// The client is sending too much, too fast
+    RateLimited,
+
+    /// An event or message was invalid or could not be parsed
+    InvalidEvent,
+
+    /// The client must AUTH (NIP-42) first
+    AuthRequired,
+
+    /// The relay does not accept ephemeral events
+    EphemeralUnsupported,
+
+    /// Anything else
+    Other,
+}
+
+impl NoticeKind {
+    /// Classify the text of a NOTICE
+    pub fn classify(text: &str) -> NoticeKind {
+        let text = text.to_lowercase();
+        let has = |words: &[&str]| words.iter().any(|w| text.contains(w));
+
+        if has(&[
+            "auth-required",
+            "auth required",
+            "authentication required",
+            "must authenticate",
+            "not authenticated",
+            "unauthenticated",
+            "please authenticate",
+        ]) {
+            NoticeKind::AuthRequired
+        } else if has(&["ephemeral"])
+            && has(&[
+                "not supported",
+                "unsupported",
+                "not accepted",
+                "not allowed",
+            ])
+        {
+            NoticeKind::EphemeralUnsupported
+        } else if has(&[
+            "rate-limited",
+            "rate limit",
+            "ratelimit",
+            "slow down",
+            "too fast",
+            "too many",
+            "too frequent",
+        ]) {
+            NoticeKind::RateLimited
+        } else if has(&[
+            "invalid",
+            "bad msg",
+            "bad event",
+            "bad message",
+            "could not parse",
+            "failed to parse",
+            "malformed",
+            "signature",
+        ]) {
+            NoticeKind::InvalidEvent
+        } else {
+            NoticeKind::Other
+        }
+    }
+}
+
 impl Serialize for RelayMessage {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -96,7 +270,7 @@ impl<'de> Visitor<'de> for RelayMessageVisitor {
     where
         A: SeqAccess<'de>,
     {
-        let word: &str = seq
+        let word: String = seq
             .next_element()?
             .ok_or_else(|| DeError::custom("Message missing initial string field"))?;
         if word == "EVENT" {
@@ -144,4 +318,86 @@ mod test {
     use super::*;
 
     test_serde! {RelayMessage, test_relay_message_serde}
+
+    #[test]
+    fn test_message_size_limits() {
+        let message = RelayMessage::mock();
+        let json = serde_json::to_string(&message).unwrap();
+        let event_size = match &message {
+            RelayMessage::Event(_, event) => serde_json::to_string(event).unwrap().len(),
+            _ => unreachable!(),
+        };
+
+        let roomy = MessageSizeLimits::default();
+        assert_eq!(
+            RelayMessage::from_json_with_limits(&json, roomy, ParseProfile::Interop).unwrap(),
+            message
+        );
+
+        let tight = MessageSizeLimits {
+            max_message: json.len() - 1,
+            max_event: event_size,
+        };
+        assert!(matches!(
+            tight.check(&json),
+            Err(Error::TooLarge { size, .. }) if size == json.len()
+        ));
+
+        let small_event = MessageSizeLimits {
+            max_message: json.len(),
+            max_event: event_size - 1,
+        };
+        assert!(matches!(
+            RelayMessage::from_json_with_limits(&json, small_event, ParseProfile::Interop),
+            Err(Error::TooLarge { max, .. }) if max == event_size - 1
+        ));
+        assert!(small_event.check(r#"["NOTICE","hello"]"#).is_ok());
+
+        // Every profile parses through a Value
+        for profile in [
+            ParseProfile::Strict,
+            ParseProfile::Interop,
+            ParseProfile::Permissive,
+        ] {
+            assert_eq!(
+                RelayMessage::from_json_with_limits(r#"["NOTICE","hello"]"#, roomy, profile)
+                    .unwrap(),
+                RelayMessage::Notice("hello".to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn test_notice_kind() {
+        let samples = [
+            // strfry
+            ("ERROR: bad msg: invalid message", NoticeKind::InvalidEvent),
+            ("ERROR: too many concurrent REQs", NoticeKind::RateLimited),
+            // nostr-rs-relay
+            ("rate limit exceeded, slow down", NoticeKind::RateLimited),
+            ("could not parse command", NoticeKind::InvalidEvent),
+            (
+                "auth-required: authentication required to read",
+                NoticeKind::AuthRequired,
+            ),
+            // nostream
+            ("Rate limited", NoticeKind::RateLimited),
+            (
+                "invalid: event signature verification failed",
+                NoticeKind::InvalidEvent,
+            ),
+            (
+                "Ephemeral events are not supported",
+                NoticeKind::EphemeralUnsupported,
+            ),
+            ("Welcome to the relay!", NoticeKind::Other),
+        ];
+        for (text, kind) in samples {
+            assert_eq!(NoticeKind::classify(text), kind, "{}", text);
+        }
+
+        let notice = RelayMessage::Notice("rate-limited: hold on".to_owned());
+        assert_eq!(notice.notice_kind(), Some(NoticeKind::RateLimited));
+        assert_eq!(RelayMessage::mock().notice_kind(), None);
+    }
 }
@@ -0,0 +1,560 @@
+use super::{DmRelayList, Event, EventKind, PublicKey, RelayStats, RelayUrl, Tag};
+use crate::Error;
+use std::collections::{HashMap, HashSet};
+
+/// Chooses which relays to read followed authors from, following the outbox
+/// (gossip) model: each author is read from the relays they write to, as
+/// advertised in their NIP-65 relay list.
+///
+/// `pick()` computes a small set of relays that covers every author, using a
+/// greedy set cover which repeatedly takes the relay serving the most authors
+/// not yet covered. Relays with `RelayStats` set are weighted by their
+/// score, so a healthy relay can win over a slightly bigger flaky one.
+#[derive(Clone, Debug)]
+pub struct RelayPicker {
+    outboxes: HashMap<PublicKey, Vec<RelayUrl>>,
+    excluded: HashSet<RelayUrl>,
+    scores: HashMap<RelayUrl, f64>,
+    relays_per_author: usize,
+    max_authors_per_relay: Option<usize>,
+}
+
+impl Default for RelayPicker {
+    fn default() -> RelayPicker {
+        RelayPicker {
+            outboxes: HashMap::new(),
+            excluded: HashSet::new(),
+            scores: HashMap::new(),
+            relays_per_author: 1,
+            max_authors_per_relay: None,
+        }
+    }
+}
+
+impl RelayPicker {
+    /// Create a picker which covers each author once, with no cap per relay
+    pub fn new() -> RelayPicker {
+        Default::default()
+    }
+
+    /// Try to cover each author on this many relays, for redundancy
+    pub fn relays_per_author(mut self, count: usize) -> RelayPicker {
+        self.relays_per_author = count.max(1);
+        self
+    }
+
+    /// Assign no more than this many authors to any one relay
+    pub fn max_authors_per_relay(mut self, max: usize) -> RelayPicker {
+        self.max_authors_per_relay = Some(max.max(1));
+        self
+    }
+
+    /// Never pick this relay (e.g. because it is down or blocked)
+    pub fn exclude(&mut self, relay: RelayUrl) {
+        let _ = self.excluded.insert(relay);
+    }
+
+    /// Weight a relay by its health. Relays without stats are scored as if
+    /// they had no history.
+    pub fn set_stats(&mut self, relay: RelayUrl, stats: &RelayStats) {
+        let _ = self.scores.insert(relay, stats.score());
+    }
+
+    /// Set the relays an author writes to, replacing any previously set
+    pub fn set_outboxes(&mut self, pubkey: PublicKey, relays: Vec<RelayUrl>) {
+        let _ = self.outboxes.insert(pubkey, relays);
+    }
+
+    /// Set an author's outboxes from their NIP-65 relay list event. The write
+    /// relays are the 'r' tags marked "write" or not marked at all. Invalid
+    /// relay urls are skipped.
+    pub fn add_relay_list(&mut self, event: &Event) -> Result<(), Error> {
+        if event.kind != EventKind::RelayList {
+            return Err(Error::WrongEventKind);
+        }
+
+        let relays = write_relays(event);
+        self.set_outboxes(event.pubkey, relays);
+        Ok(())
+    }
+
+    /// Choose relays covering the `authors`
+    pub fn pick(&self, authors: &[PublicKey]) -> RelayPicks {
+        // How many more relays each author should be read from
+        let mut needed: HashMap<PublicKey, usize> = HashMap::new();
+        // The authors each candidate relay could serve
+        let mut candidates: HashMap<&RelayUrl, Vec<PublicKey>> = HashMap::new();
+        let mut uncovered: Vec<PublicKey> = Vec::new();
+
+        for author in authors.iter() {
+            if needed.contains_key(author) {
+                continue;
+            }
+            let relays: Vec<&RelayUrl> = self
+                .outboxes
+                .get(author)
+                .map(|relays| {
+                    relays
+                        .iter()
+                        .filter(|r| !self.excluded.contains(*r))
+                        .collect()
+                })
+                .unwrap_or_default();
+            if relays.is_empty() {
+                uncovered.push(*author);
+                continue;
+            }
+            let _ = needed.insert(*author, self.relays_per_author.min(relays.len()));
+            for relay in relays {
+                candidates.entry(relay).or_default().push(*author);
+            }
+        }
+
+        let mut by_relay: HashMap<RelayUrl, Vec<PublicKey>> = HashMap::new();
+        loop {
+            // Find the relay serving the most authors still needing coverage,
+            // weighted by its score. Ties go to the lowest url so that picks
+            // are deterministic.
+            let unknown = RelayStats::default().score();
+            let mut best: Option<(&RelayUrl, f64)> = None;
+            for (relay, relay_authors) in candidates.iter() {
+                let count = relay_authors
+                    .iter()
+                    .filter(|a| needed.get(*a).copied().unwrap_or(0) > 0)
+                    .count();
+                let count = match self.max_authors_per_relay {
+                    Some(max) => count.min(max),
+                    None => count,
+                };
+                if count == 0 {
+                    continue;
+                }
+                let weight = count as f64 * self.scores.get(*relay).copied().unwrap_or(unknown);
+                match best {
+                    Some((best_relay, best_weight))
+                        if best_weight > weight
+                            || (best_weight == weight && best_relay < *relay) => {}
+                    _ => best = Some((*relay, weight)),
+                }
+            }
+
+            let relay = match best {
+                Some((relay, _)) => relay,
+                None => break,
+            };
+            let mut relay_authors = candidates.remove(&relay).unwrap_or_default();
+            relay_authors.retain(|a| needed.get(a).copied().unwrap_or(0) > 0);
+
+            // When capped, prefer the authors with the fewest other options
+            if let Some(max) = self.max_authors_per_relay {
+                relay_authors.sort_by_key(|a| {
+                    (
+                        candidates.values().filter(|list| list.contains(a)).count(),
+                        a.as_hex_string(),
+                    )
+                });
+                relay_authors.truncate(max);
+            }
+
+            for author in relay_authors.iter() {
+                if let Some(n) = needed.get_mut(author) {
+                    *n -= 1;
+                }
+            }
+            let _ = by_relay.insert(relay.clone(), relay_authors);
+        }
+
+        // Authors that the relay caps left with no relay at all
+        for (author, n) in needed.iter() {
+            if *n > 0 && !by_relay.values().any(|list| list.contains(author)) {
+                uncovered.push(*author);
+            }
+        }
+
+        RelayPicks {
+            by_relay,
+            uncovered,
+        }
+    }
+}
+
+/// The relays chosen by a `RelayPicker`
+#[derive(Clone, Debug, Default)]
+pub struct RelayPicks {
+    /// The authors to query on each relay
+    pub by_relay: HashMap<RelayUrl, Vec<PublicKey>>,
+
+    /// Authors that could not be assigned any relay, because we do not know
+    /// their outboxes or all of them were excluded or full
+    pub uncovered: Vec<PublicKey>,
+}
+
+impl RelayPicks {
+    /// The relays to query an author on
+    pub fn relays_for(&self, pubkey: &PublicKey) -> Vec<&RelayUrl> {
+        let mut relays: Vec<&RelayUrl> = self
+            .by_relay
+            .iter()
+            .filter(|(_, authors)| authors.contains(pubkey))
+            .map(|(relay, _)| relay)
+            .collect();
+        relays.sort();
+        relays
+    }
+
+    /// The relays that were picked
+    pub fn relays(&self) -> Vec<&RelayUrl> {
+        let mut relays: Vec<&RelayUrl> = self.by_relay.keys().collect();
+        relays.sort();
+        relays
+    }
+}
+
+/// Choose the relays to publish `event` to, following NIP-65: the author's
+/// write relays, so their followers find it, plus the read relays of each user
+/// it tags with 'p', so they see it. At most 3 read relays are used per tagged
+/// user, and at most 4 write relays, in the order the relay lists give them.
+///
+/// `author_relay_list` and `mentioned_relay_lists` are kind 10002 events.
+/// Relay lists of users the event does not tag, or of the wrong kind, are
+/// ignored.
+pub fn plan_publish(
+    event: &Event,
+    author_relay_list: &Event,
+    mentioned_relay_lists: &[Event],
+) -> Vec<RelayUrl> {
+    const MAX_WRITE_RELAYS: usize = 4;
+    const MAX_READ_RELAYS_PER_MENTION: usize = 3;
+
+    let mut relays: Vec<RelayUrl> = Vec::new();
+    let mut add = |candidates: Vec<RelayUrl>, max: usize| {
+        for relay in candidates.into_iter().take(max) {
+            if !relays.contains(&relay) {
+                relays.push(relay);
+            }
+        }
+    };
+
+    if author_relay_list.kind == EventKind::RelayList && author_relay_list.pubkey == event.pubkey {
+        add(write_relays(author_relay_list), MAX_WRITE_RELAYS);
+    }
+
+    let mentioned: HashSet<PublicKey> = event
+        .tags
+        .iter()
+        .filter_map(|tag| match tag {
+            Tag::Pubkey { pubkey, .. } => PublicKey::try_from(pubkey.clone()).ok(),
+            _ => None,
+        })
+        .filter(|pubkey| *pubkey != event.pubkey)
+        .collect();
+    for relay_list in mentioned_relay_lists.iter() {
+        if relay_list.kind == EventKind::RelayList && mentioned.contains(&relay_list.pubkey) {
+            add(read_relays(relay_list), MAX_READ_RELAYS_PER_MENTION);
+        }
+    }
+
+    relays
+}
+
+/// Plan where to send gift wraps (NIP-17): each goes to the DM relays (kind
+/// 10050) of the user its 'p' tag names, since relays not chosen for DMs often
+/// refuse or lose wraps addressed to others. Users with no DM relay list get
+/// no relays: NIP-17 says not to send them DMs, and falling back to their
+/// general relays would leak the wraps' metadata to relays they did not pick.
+///
+/// The newest list of each user with a valid signature is used. The plan is
+/// in the order of `wraps`.
+pub fn plan_giftwrap_publish<'a>(
+    wraps: &'a [Event],
+    dm_relay_lists: &[Event],
+) -> Vec<(&'a Event, Vec<RelayUrl>)> {
+    let mut newest: HashMap<PublicKey, &Event> = HashMap::new();
+    for list in dm_relay_lists
+        .iter()
+        .filter(|e| e.kind == EventKind::DmRelayList && e.verify(None).is_ok())
+    {
+        let entry = newest.entry(list.pubkey).or_insert(list);
+        if list.created_at > entry.created_at {
+            *entry = list;
+        }
+    }
+
+    wraps
+        .iter()
+        .map(|wrap| {
+            let recipient = wrap.tags.iter().find_map(|tag| match tag {
+                Tag::Pubkey { pubkey, .. } => PublicKey::try_from(pubkey.clone()).ok(),
+                _ => None,
+            });
+            let relays = recipient
+                .and_then(|recipient| newest.get(&recipient))
+                .and_then(|list| DmRelayList::try_from_event(list).ok())
+                .map(|list| list.relays)
+                .unwrap_or_default();
+            (wrap, relays)
+        })
+        .collect()
+}
+
+// The write relays of a NIP-65 relay list event: the 'r' tags marked "write"
+// or not marked at all, skipping invalid relay urls
+pub(crate) fn write_relays(event: &Event) -> Vec<RelayUrl> {
+    marked_relays(event, "write")
+}
+
+// The read relays of a NIP-65 relay list event, likewise
+fn read_relays(event: &Event) -> Vec<RelayUrl> {
+    marked_relays(event, "read")
+}
+
+fn marked_relays(event: &Event, wanted: &str) -> Vec<RelayUrl> {
+    let mut relays: Vec<RelayUrl> = Vec::new();
+    for tag in event.tags.iter() {
+        if let Tag::Reference { url, marker } = tag {
+            if marker.as_deref().unwrap_or(wanted) != wanted {
+                continue;
+            }
+            if let Ok(relay) = RelayUrl::try_from_unchecked_url(url) {
+                if !relays.contains(&relay) {
+                    relays.push(relay);
+                }
+            }
+        }
+    }
+    relays
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PreEvent, PrivateKey, UncheckedUrl, Unixtime};
+
+    fn relay(s: &str) -> RelayUrl {
+        RelayUrl::try_from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_add_relay_list() {
+        let private_key = PrivateKey::generate();
+        let reference = |url: &str, marker: Option<&str>| Tag::Reference {
+            url: UncheckedUrl::from_str(url),
+            marker: marker.map(|m| m.to_owned()),
+        };
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::RelayList,
+            tags: vec![
+                reference("wss://both.example.com", None),
+                reference("wss://write.example.com", Some("write")),
+                reference("wss://read.example.com", Some("read")),
+                reference("not a url", None),
+            ],
+            content: "".to_owned(),
+            ots: None,
+        };
+        let event = Event::new(pre, &private_key).unwrap();
+
+        let mut picker = RelayPicker::new();
+        picker.add_relay_list(&event).unwrap();
+        let picks = picker
+            .relays_per_author(5)
+            .pick(&[private_key.public_key()]);
+        assert_eq!(
+            picks.relays(),
+            vec![
+                &relay("wss://both.example.com"),
+                &relay("wss://write.example.com")
+            ]
+        );
+        assert!(picks.uncovered.is_empty());
+    }
+
+    #[test]
+    fn test_pick() {
+        let keys: Vec<PublicKey> = (0..4)
+            .map(|_| PrivateKey::generate().public_key())
+            .collect();
+        let big = relay("wss://big.example.com");
+        let small = relay("wss://small.example.com");
+        let own = relay("wss://own.example.com");
+
+        let mut picker = RelayPicker::new();
+        picker.set_outboxes(keys[0], vec![big.clone(), small.clone()]);
+        picker.set_outboxes(keys[1], vec![big.clone()]);
+        picker.set_outboxes(keys[2], vec![big.clone(), own.clone()]);
+        // keys[3] has no known outboxes
+
+        let picks = picker.clone().pick(&keys);
+        assert_eq!(picks.relays(), vec![&big]);
+        assert_eq!(picks.relays_for(&keys[0]), vec![&big]);
+        assert_eq!(picks.uncovered, vec![keys[3]]);
+
+        // Redundancy adds the smaller relays
+        let picks = picker.clone().relays_per_author(2).pick(&keys);
+        assert_eq!(picks.relays(), vec![&big, &own, &small]);
+        assert_eq!(picks.relays_for(&keys[1]), vec![&big]);
+
+        // With a cap, the author with only one option keeps it
+        let picks = picker.clone().max_authors_per_relay(1).pick(&keys[..3]);
+        assert_eq!(picks.relays_for(&keys[1]), vec![&big]);
+        assert_eq!(picks.relays_for(&keys[0]), vec![&small]);
+        assert_eq!(picks.relays_for(&keys[2]), vec![&own]);
+
+        // Excluded relays are never picked
+        picker.exclude(big.clone());
+        let picks = picker.pick(&keys);
+        assert_eq!(picks.relays(), vec![&own, &small]);
+        assert_eq!(picks.uncovered.len(), 2);
+    }
+
+    #[test]
+    fn test_pick_prefers_healthy_relays() {
+        let keys: Vec<PublicKey> = (0..3)
+            .map(|_| PrivateKey::generate().public_key())
+            .collect();
+        let big = relay("wss://big.example.com");
+        let healthy = relay("wss://healthy.example.com");
+
+        let mut picker = RelayPicker::new();
+        picker.set_outboxes(keys[0], vec![big.clone(), healthy.clone()]);
+        picker.set_outboxes(keys[1], vec![big.clone(), healthy.clone()]);
+        picker.set_outboxes(keys[2], vec![big.clone()]);
+        assert_eq!(picker.pick(&keys).relays(), vec![&big]);
+
+        let mut good = RelayStats::new();
+        let mut bad = RelayStats::new();
+        for _ in 0..10 {
+            good.record_connect(true);
+            bad.record_connect(false);
+        }
+        picker.set_stats(healthy.clone(), &good);
+        picker.set_stats(big.clone(), &bad);
+        let picks = picker.pick(&keys);
+        assert_eq!(picks.relays_for(&keys[0]), vec![&healthy]);
+        assert_eq!(picks.relays_for(&keys[2]), vec![&big]);
+    }
+
+    #[test]
+    fn test_plan_publish() {
+        let relay_list = |key: &PrivateKey, relays: &[(&str, Option<&str>)]| {
+            let tags = relays
+                .iter()
+                .map(|(url, marker)| Tag::Reference {
+                    url: UncheckedUrl::from_str(url),
+                    marker: marker.map(|m| m.to_owned()),
+                })
+                .collect();
+            let pre = PreEvent {
+                pubkey: key.public_key(),
+                created_at: Unixtime(1700000000),
+                kind: EventKind::RelayList,
+                tags,
+                content: "".to_owned(),
+                ots: None,
+            };
+            Event::new(pre, key).unwrap()
+        };
+        let me = PrivateKey::generate();
+        let friend = PrivateKey::generate();
+        let stranger = PrivateKey::generate();
+
+        let mine = relay_list(
+            &me,
+            &[
+                ("wss://mine.example.com", Some("write")),
+                ("wss://inbox.example.com", Some("read")),
+                ("wss://shared.example.com", None),
+            ],
+        );
+        let friends = relay_list(
+            &friend,
+            &[
+                ("wss://shared.example.com", None),
+                ("wss://friend-out.example.com", Some("write")),
+                ("wss://friend-1.example.com", Some("read")),
+                ("wss://friend-2.example.com", Some("read")),
+                ("wss://friend-3.example.com", Some("read")),
+            ],
+        );
+        let strangers = relay_list(&stranger, &[("wss://stranger.example.com", None)]);
+
+        let pre = PreEvent {
+            pubkey: me.public_key(),
+            created_at: Unixtime(1700000000),
+            kind: EventKind::TextNote,
+            tags: vec![Tag::Pubkey {
+                pubkey: friend.public_key().into(),
+                recommended_relay_url: None,
+                petname: None,
+            }],
+            content: "hi friend".to_owned(),
+            ots: None,
+        };
+        let note = Event::new(pre, &me).unwrap();
+
+        assert_eq!(
+            plan_publish(&note, &mine, &[strangers, friends]),
+            vec![
+                relay("wss://mine.example.com"),
+                relay("wss://shared.example.com"),
+                relay("wss://friend-1.example.com"),
+                relay("wss://friend-2.example.com"),
+            ]
+        );
+
+        // Someone else's relay list is not used as our outbox
+        assert!(plan_publish(&note, &note, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_plan_giftwrap_publish() {
+        let alice = PrivateKey::generate();
+        let bob = PrivateKey::generate();
+        let carol = PrivateKey::generate();
+        let signed = |key: &PrivateKey, pre: PreEvent| Event::new(pre, key).unwrap();
+
+        let wrap = |recipient: &PrivateKey| {
+            let wrapper = PrivateKey::generate();
+            let pre = PreEvent {
+                pubkey: wrapper.public_key(),
+                created_at: Unixtime(1700000000),
+                kind: EventKind::GiftWrap,
+                tags: vec![Tag::Pubkey {
+                    pubkey: recipient.public_key().into(),
+                    recommended_relay_url: None,
+                    petname: None,
+                }],
+                content: "".to_owned(),
+                ots: None,
+            };
+            signed(&wrapper, pre)
+        };
+        let wraps = vec![wrap(&alice), wrap(&bob), wrap(&carol)];
+
+        let dm_list = |key: &PrivateKey, url: &str, at: i64| {
+            let list = DmRelayList::new(vec![relay(url)]);
+            signed(key, list.to_pre_event(key.public_key(), Unixtime(at)))
+        };
+        // A newer list for alice that she did not sign
+        let mut forged = dm_list(&carol, "wss://forged.example.com", 1800000000);
+        forged.pubkey = alice.public_key();
+        let dm_relay_lists = vec![
+            dm_list(&alice, "wss://alice-new.example.com", 1700000000),
+            dm_list(&alice, "wss://alice-old.example.com", 1600000000),
+            forged,
+        ];
+
+        // Bob has no DM relay list, so he gets no relays
+        let plan = plan_giftwrap_publish(&wraps, &dm_relay_lists);
+        assert_eq!(
+            plan,
+            vec![
+                (&wraps[0], vec![relay("wss://alice-new.example.com")]),
+                (&wraps[1], vec![]),
+                (&wraps[2], vec![]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,412 @@
+use super::{Event, Id, PrivateKey, RelayInformationDocument, RelayUrl, Unixtime};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+// The subscription ids the probe uses
+const LIMIT_ID: &str = "probe-limit";
+const COUNT_ID: &str = "probe-count";
+const NEG_ID: &str = "probe-neg";
+
+// A NIP-77 initial message for an empty set: protocol version 0x61, then one
+// range up to infinity in IdList mode holding no ids
+const NEG_EMPTY_SET: &str = "6100000200";
+
+// The limit asked for when the relay does not advertise one
+const DEFAULT_LIMIT: usize = 500;
+
+/// What a relay was found to do, from `RelayProbe`, alongside what its
+/// NIP-11 document claims
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RelayCapabilities {
+    /// Whether the relay sent an AUTH challenge (NIP-42)
+    pub auth_challenge: bool,
+
+    /// Whether it accepted the probe's answer to that challenge, if it
+    /// answered at all
+    pub auth: Option<bool>,
+
+    /// Whether it answered a COUNT (NIP-45), if it answered at all
+    pub count: Option<bool>,
+
+    /// Whether it understood NEG-OPEN (NIP-77), if it answered at all
+    pub negentropy: Option<bool>,
+
+    /// The `limit` asked for in the probe's REQ: a little over the relay's
+    /// advertised `max_limit`, to see whether it is enforced
+    pub limit_asked: usize,
+
+    /// How many events came back for that REQ before EOSE, if EOSE came
+    pub limit_returned: Option<usize>,
+
+    /// How long EOSE took
+    pub eose_time: Option<Duration>,
+
+    /// The relay's NIP-11 document, if given
+    pub nip11: Option<RelayInformationDocument>,
+
+    /// Where what the relay does differs from what its NIP-11 claims
+    pub discrepancies: Vec<String>,
+}
+
+impl fmt::Display for RelayCapabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let answer = |found: Option<bool>| match found {
+            Some(true) => "yes",
+            Some(false) => "no",
+            None => "no answer",
+        };
+        writeln!(f, "AUTH challenge: {}", answer(Some(self.auth_challenge)))?;
+        if self.auth_challenge {
+            writeln!(f, "AUTH accepted: {}", answer(self.auth))?;
+        }
+        writeln!(f, "COUNT: {}", answer(self.count))?;
+        writeln!(f, "Negentropy: {}", answer(self.negentropy))?;
+        match (self.limit_returned, self.eose_time) {
+            (Some(returned), Some(time)) => writeln!(
+                f,
+                "Asked for {} events, got {} in {:?}",
+                self.limit_asked, returned, time
+            )?,
+            _ => writeln!(f, "No EOSE for a REQ of {} events", self.limit_asked)?,
+        }
+        for discrepancy in &self.discrepancies {
+            writeln!(f, "Discrepancy: {discrepancy}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    Limit,
+    Count,
+    Negentropy,
+    Done,
+}
+
+/// Finds out empirically what a relay supports, since NIP-11 documents are
+/// often stale or wrong.
+///
+/// The probes run one at a time so that a NOTICE can be put down to the one
+/// in flight: a REQ asking for a little more than the advertised `max_limit`
+/// (timing its EOSE), a COUNT, and a NEG-OPEN. An AUTH challenge is
+/// answered whenever it comes, with a throwaway key, to see whether the
+/// relay accepts it. Relays may only challenge when they need to, so no
+/// challenge is not held against a relay that claims NIP-42.
+///
+/// This does no I/O, and works on raw text frames, since a relay may answer
+/// in ways `RelayMessage` does not cover. Send the frames from `start()`,
+/// pass each text frame received to `handle()` and send what it returns,
+/// and call `poll()` now and then so that silence times out, until
+/// `is_done()`. Then take the report with `finish()`.
+#[derive(Clone, Debug)]
+pub struct RelayProbe {
+    relay: RelayUrl,
+    timeout: Duration,
+    step: Step,
+    step_started: Instant,
+    report: RelayCapabilities,
+    returned: usize,
+    auth_id: Option<Id>,
+}
+
+impl RelayProbe {
+    /// Prepare to probe `relay`, whose NIP-11 document, if fetched, is
+    /// `nip11`. Each probe gets `timeout` to be answered.
+    pub fn new(
+        relay: RelayUrl,
+        nip11: Option<RelayInformationDocument>,
+        timeout: Duration,
+        now: Instant,
+    ) -> RelayProbe {
+        let limit_asked = nip11
+            .as_ref()
+            .and_then(|doc| doc.limits().max_limit)
+            .map(|max| max.saturating_add(10))
+            .unwrap_or(DEFAULT_LIMIT);
+        RelayProbe {
+            relay,
+            timeout,
+            step: Step::Limit,
+            step_started: now,
+            report: RelayCapabilities {
+                limit_asked,
+                nip11,
+                ..Default::default()
+            },
+            returned: 0,
+            auth_id: None,
+        }
+    }
+
+    /// The first frame to send
+    pub fn start(&mut self, now: Instant) -> Vec<String> {
+        self.step_started = now;
+        vec![format!(
+            r#"["REQ","{LIMIT_ID}",{{"limit":{}}}]"#,
+            self.report.limit_asked
+        )]
+    }
+
+    /// Whether every probe has been answered or timed out
+    pub fn is_done(&self) -> bool {
+        self.step == Step::Done
+    }
+
+    /// Handle a text frame from the relay, returning frames to send
+    pub fn handle(&mut self, frame: &str, now: Instant) -> Vec<String> {
+        let value: serde_json::Value = match serde_json::from_str(frame) {
+            Ok(value) => value,
+            Err(_) => return vec![],
+        };
+        let word = value.get(0).and_then(|w| w.as_str()).unwrap_or("");
+        let id = value.get(1).and_then(|w| w.as_str()).unwrap_or("");
+
+        if word == "AUTH" {
+            self.report.auth_challenge = true;
+            let created_at = Unixtime::now().unwrap_or(Unixtime(0));
+            return match Event::new_auth(&PrivateKey::generate(), &self.relay, id, created_at) {
+                Ok(event) => {
+                    self.auth_id = Some(event.id);
+                    serde_json::to_string(&event)
+                        .map(|event| vec![format!(r#"["AUTH",{event}]"#)])
+                        .unwrap_or_default()
+                }
+                Err(_) => vec![],
+            };
+        }
+        if word == "OK"
+            && self
+                .auth_id
+                .map(|auth| auth.as_hex_string() == id)
+                .unwrap_or(false)
+        {
+            self.report.auth = value.get(2).and_then(|ok| ok.as_bool());
+            return vec![];
+        }
+
+        match (self.step, word) {
+            (Step::Limit, "EVENT") if id == LIMIT_ID => {
+                self.returned += 1;
+                vec![]
+            }
+            (Step::Limit, "EOSE") if id == LIMIT_ID => {
+                self.report.limit_returned = Some(self.returned);
+                self.report.eose_time = Some(now.duration_since(self.step_started));
+                let mut frames = vec![format!(r#"["CLOSE","{LIMIT_ID}"]"#)];
+                frames.extend(self.next(now));
+                frames
+            }
+            (Step::Limit, "CLOSED" | "NOTICE") if word == "NOTICE" || id == LIMIT_ID => {
+                self.next(now)
+            }
+            (Step::Count, "COUNT") if id == COUNT_ID => {
+                self.report.count = Some(true);
+                self.next(now)
+            }
+            (Step::Count, "CLOSED" | "NOTICE") if word == "NOTICE" || id == COUNT_ID => {
+                self.report.count = Some(false);
+                self.next(now)
+            }
+            (Step::Negentropy, "NEG-MSG" | "NEG-ERR") if id == NEG_ID => {
+                // NEG-ERR is a refusal, but in the protocol's own terms
+                self.report.negentropy = Some(true);
+                let mut frames = vec![format!(r#"["NEG-CLOSE","{NEG_ID}"]"#)];
+                frames.extend(self.next(now));
+                frames
+            }
+            (Step::Negentropy, "CLOSED" | "NOTICE") if word == "NOTICE" || id == NEG_ID => {
+                self.report.negentropy = Some(false);
+                self.next(now)
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Give up on a probe that has not been answered in time, returning
+    /// frames to send
+    pub fn poll(&mut self, now: Instant) -> Vec<String> {
+        if self.step == Step::Done || now.duration_since(self.step_started) < self.timeout {
+            return vec![];
+        }
+        let mut frames = match self.step {
+            Step::Limit => vec![format!(r#"["CLOSE","{LIMIT_ID}"]"#)],
+            Step::Negentropy => vec![format!(r#"["NEG-CLOSE","{NEG_ID}"]"#)],
+            _ => vec![],
+        };
+        frames.extend(self.next(now));
+        frames
+    }
+
+    /// The findings, compared with the NIP-11 claims
+    pub fn finish(self) -> RelayCapabilities {
+        let mut report = self.report;
+        let mut discrepancies: Vec<String> = Vec::new();
+        if let Some(doc) = &report.nip11 {
+            let mut compare =
+                |nip: u32, what: &str, found: Option<bool>| match (doc.supports_nip(nip), found) {
+                    (true, Some(false)) => {
+                        discrepancies.push(format!("claims NIP-{nip} but refused {what}"))
+                    }
+                    (true, None) => {
+                        discrepancies.push(format!("claims NIP-{nip} but did not answer {what}"))
+                    }
+                    (false, Some(true)) => {
+                        discrepancies.push(format!("supports {what} but does not claim NIP-{nip}"))
+                    }
+                    _ => {}
+                };
+            // Only a challenge shows whether AUTH works
+            if report.auth_challenge {
+                compare(42, "AUTH", report.auth);
+                if !doc.supports_nip(42) && report.auth != Some(true) {
+                    discrepancies
+                        .push("sent an AUTH challenge but does not claim NIP-42".to_owned());
+                }
+            }
+            compare(45, "COUNT", report.count);
+            compare(77, "NEG-OPEN", report.negentropy);
+
+            if let (Some(max), Some(returned)) = (doc.limits().max_limit, report.limit_returned) {
+                if returned > max {
+                    discrepancies.push(format!(
+                        "returned {returned} events though its max_limit is {max}"
+                    ));
+                }
+            }
+        }
+        report.discrepancies = discrepancies;
+        report
+    }
+
+    // Move on to the next probe, returning the frame that starts it
+    fn next(&mut self, now: Instant) -> Vec<String> {
+        self.step_started = now;
+        match self.step {
+            Step::Limit => {
+                self.step = Step::Count;
+                vec![format!(r#"["COUNT","{COUNT_ID}",{{"kinds":[1]}}]"#)]
+            }
+            Step::Count => {
+                self.step = Step::Negentropy;
+                vec![format!(
+                    r#"["NEG-OPEN","{NEG_ID}",{{"kinds":[1],"limit":1}},"{NEG_EMPTY_SET}"]"#
+                )]
+            }
+            Step::Negentropy | Step::Done => {
+                self.step = Step::Done;
+                vec![]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_relay_probe() {
+        let mut nip11 = RelayInformationDocument {
+            supported_nips: vec![1, 45, 77],
+            ..Default::default()
+        };
+        let _ = nip11
+            .other
+            .insert("limitation".to_owned(), serde_json::json!({"max_limit": 2}));
+        let start = Instant::now();
+        let later = |ms: u64| start + Duration::from_millis(ms);
+
+        let relay = RelayUrl::try_from_str("wss://relay.example.com").unwrap();
+        let mut probe = RelayProbe::new(relay, Some(nip11), Duration::from_secs(5), start);
+        assert_eq!(
+            probe.start(start),
+            vec![r#"["REQ","probe-limit",{"limit":12}]"#.to_owned()]
+        );
+
+        // The challenge is answered, and the answer accepted
+        let frames = probe.handle(r#"["AUTH","challenge"]"#, later(10));
+        let answer: serde_json::Value = serde_json::from_str(&frames[0]).unwrap();
+        assert_eq!(answer[0], "AUTH");
+        let auth: Event = serde_json::from_value(answer[1].clone()).unwrap();
+        assert!(auth.verify(None).is_ok());
+        let ok = format!(r#"["OK","{}",true,""]"#, auth.id.as_hex_string());
+        assert!(probe.handle(&ok, later(15)).is_empty());
+
+        for _ in 0..3 {
+            assert!(probe
+                .handle(r#"["EVENT","probe-limit",{}]"#, later(20))
+                .is_empty());
+        }
+        let frames = probe.handle(r#"["EOSE","probe-limit"]"#, later(50));
+        assert_eq!(frames[0], r#"["CLOSE","probe-limit"]"#);
+        assert!(frames[1].starts_with(r#"["COUNT","probe-count""#));
+
+        // The relay does not know COUNT
+        let frames = probe.handle(r#"["NOTICE","unknown command"]"#, later(60));
+        assert!(frames[0].starts_with(r#"["NEG-OPEN","probe-neg""#));
+        assert!(probe.poll(later(2000)).is_empty());
+        let frames = probe.poll(later(6000));
+        assert_eq!(frames, vec![r#"["NEG-CLOSE","probe-neg"]"#.to_owned()]);
+        assert!(probe.is_done());
+
+        let report = probe.finish();
+        assert!(report.auth_challenge);
+        assert_eq!(report.auth, Some(true));
+        assert_eq!(report.count, Some(false));
+        assert_eq!(report.negentropy, None);
+        assert_eq!(report.limit_returned, Some(3));
+        assert_eq!(report.eose_time, Some(Duration::from_millis(50)));
+        assert_eq!(
+            report.discrepancies,
+            vec![
+                "supports AUTH but does not claim NIP-42".to_owned(),
+                "claims NIP-45 but refused COUNT".to_owned(),
+                "claims NIP-77 but did not answer NEG-OPEN".to_owned(),
+                "returned 3 events though its max_limit is 2".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relay_probe_auth() {
+        let mut nip11 = RelayInformationDocument {
+            supported_nips: vec![1, 42],
+            ..Default::default()
+        };
+        let _ = nip11.other.insert(
+            "limitation".to_owned(),
+            serde_json::json!({ "max_limit": usize::MAX }),
+        );
+        let start = Instant::now();
+        let relay = RelayUrl::try_from_str("wss://relay.example.com").unwrap();
+        let timeout = Duration::from_secs(5);
+
+        // A huge max_limit does not overflow
+        let probe = RelayProbe::new(relay.clone(), Some(nip11.clone()), timeout, start);
+        assert_eq!(probe.report.limit_asked, usize::MAX);
+
+        // A relay that never challenges is not taken to lack AUTH
+        let mut probe = RelayProbe::new(relay.clone(), Some(nip11.clone()), timeout, start);
+        let _ = probe.start(start);
+        let _ = probe.handle(r#"["EOSE","probe-limit"]"#, start);
+        let _ = probe.handle(r#"["COUNT","probe-count",{"count":0}]"#, start);
+        let _ = probe.handle(r#"["NEG-ERR","probe-neg","blocked"]"#, start);
+        let report = probe.finish();
+        assert!(!report.auth_challenge);
+        assert!(!report.discrepancies.iter().any(|d| d.contains("AUTH")));
+
+        // One that challenges and then refuses the answer is
+        let mut probe = RelayProbe::new(relay, Some(nip11), timeout, start);
+        let _ = probe.start(start);
+        let frames = probe.handle(r#"["AUTH","challenge"]"#, start);
+        let answer: serde_json::Value = serde_json::from_str(&frames[0]).unwrap();
+        let ok = format!(r#"["OK",{},false,"restricted: no"]"#, answer[1]["id"]);
+        let _ = probe.handle(&ok, start);
+        let report = probe.finish();
+        assert_eq!(report.auth, Some(false));
+        assert!(report
+            .discrepancies
+            .contains(&"claims NIP-42 but refused AUTH".to_owned()));
+    }
+}
@@ -0,0 +1,152 @@
+use super::Unixtime;
+use std::time::Duration;
+
+/// Health metrics for a relay, accumulated as a client uses it.
+///
+/// `score()` condenses them into a number between 0 and 1, which
+/// `RelayPicker::set_stats` uses to prefer healthy relays.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RelayStats {
+    connect_successes: u64,
+    connect_failures: u64,
+    eose_total: Duration,
+    eose_count: u64,
+    events_received: u64,
+    oks_accepted: u64,
+    oks_rejected: u64,
+    last_auth_required: Option<Unixtime>,
+}
+
+impl RelayStats {
+    /// No history
+    pub fn new() -> RelayStats {
+        Default::default()
+    }
+
+    /// Record an attempt to connect
+    pub fn record_connect(&mut self, success: bool) {
+        if success {
+            self.connect_successes += 1;
+        } else {
+            self.connect_failures += 1;
+        }
+    }
+
+    /// Record how long a subscription took to reach EOSE
+    pub fn record_eose(&mut self, latency: Duration) {
+        self.eose_total += latency;
+        self.eose_count += 1;
+    }
+
+    /// Record events received
+    pub fn record_events(&mut self, count: u64) {
+        self.events_received += count;
+    }
+
+    /// Record the OK answer to a published event
+    pub fn record_ok(&mut self, accepted: bool) {
+        if accepted {
+            self.oks_accepted += 1;
+        } else {
+            self.oks_rejected += 1;
+        }
+    }
+
+    /// Record that the relay required AUTH (NIP-42)
+    pub fn record_auth_required(&mut self, now: Unixtime) {
+        self.last_auth_required = Some(now);
+    }
+
+    /// Successful connections
+    pub fn connect_successes(&self) -> u64 {
+        self.connect_successes
+    }
+
+    /// Failed connections
+    pub fn connect_failures(&self) -> u64 {
+        self.connect_failures
+    }
+
+    /// The average time to EOSE, if any were recorded
+    pub fn average_eose_latency(&self) -> Option<Duration> {
+        if self.eose_count == 0 {
+            return None;
+        }
+        Some(self.eose_total / self.eose_count as u32)
+    }
+
+    /// Events received
+    pub fn events_received(&self) -> u64 {
+        self.events_received
+    }
+
+    /// The fraction of published events rejected, if any were published
+    pub fn rejection_rate(&self) -> Option<f64> {
+        let total = self.oks_accepted + self.oks_rejected;
+        if total == 0 {
+            return None;
+        }
+        Some(self.oks_rejected as f64 / total as f64)
+    }
+
+    /// When the relay last required AUTH
+    pub fn last_auth_required(&self) -> Option<Unixtime> {
+        self.last_auth_required
+    }
+
+    /// How healthy the relay is, from 0 to 1: the product of its connection
+    /// success rate, its acceptance rate and a factor which halves with each
+    /// two seconds of EOSE latency. Rates start at one half and move with
+    /// experience, so a relay with no history scores 0.25.
+    pub fn score(&self) -> f64 {
+        let rate = |good: u64, bad: u64| (good as f64 + 1.0) / ((good + bad) as f64 + 2.0);
+        let connect = rate(self.connect_successes, self.connect_failures);
+        let accept = rate(self.oks_accepted, self.oks_rejected);
+        let latency = match self.average_eose_latency() {
+            Some(latency) => 2.0 / (2.0 + latency.as_secs_f64()),
+            None => 1.0,
+        };
+        connect * accept * latency
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_relay_stats() {
+        let mut stats = RelayStats::new();
+        assert_eq!(stats.score(), 0.25);
+        assert_eq!(stats.average_eose_latency(), None);
+        assert_eq!(stats.rejection_rate(), None);
+
+        for _ in 0..8 {
+            stats.record_connect(true);
+            stats.record_ok(true);
+        }
+        stats.record_ok(false);
+        stats.record_eose(Duration::from_millis(200));
+        stats.record_eose(Duration::from_millis(400));
+        stats.record_events(50);
+        stats.record_auth_required(Unixtime(1700000000));
+
+        assert_eq!(stats.connect_successes(), 8);
+        assert_eq!(stats.events_received(), 50);
+        assert_eq!(
+            stats.average_eose_latency(),
+            Some(Duration::from_millis(300))
+        );
+        assert_eq!(stats.rejection_rate(), Some(1.0 / 9.0));
+        assert_eq!(stats.last_auth_required(), Some(Unixtime(1700000000)));
+        // 9/10 connecting, 9/11 accepted and 2/2.3 for latency
+        let healthy = stats.score();
+        assert!((healthy - 0.6403).abs() < 0.0001);
+
+        let mut flaky = stats;
+        for _ in 0..20 {
+            flaky.record_connect(false);
+        }
+        assert!(flaky.score() < healthy / 2.0);
+    }
+}
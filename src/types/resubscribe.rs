@@ -0,0 +1,174 @@
+use super::{ClientMessage, Filter, RelayMessage, SubscriptionId, Unixtime};
+use std::collections::BTreeMap;
+
+/// A subscription re-issued after a reconnect, from
+/// `Resubscriber::reconnected()`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resubscribed {
+    /// The subscription
+    pub subscription_id: SubscriptionId,
+
+    /// The newest event seen on it before the connection dropped, which the
+    /// new REQ asks to start from. None if no event had been seen, in which
+    /// case the original filters were sent again.
+    pub since: Option<Unixtime>,
+
+    /// The REQ to send
+    pub message: ClientMessage,
+}
+
+/// Remembers the subscriptions open on a relay connection so they can be
+/// re-issued when it reconnects, asking only for events from the newest one
+/// already seen.
+///
+/// This does no I/O. Send the messages from `subscribe()` and `unsubscribe()`,
+/// pass each message from the relay to `handle()` with the time it was
+/// received, and after reconnecting send
+/// the messages from `reconnected()`. Events at exactly the newest time seen
+/// will be sent again, so that none from that second are missed.
+#[derive(Clone, Debug, Default)]
+pub struct Resubscriber {
+    subscriptions: BTreeMap<String, (Vec<Filter>, Option<Unixtime>)>,
+}
+
+impl Resubscriber {
+    /// Create one with no subscriptions
+    pub fn new() -> Resubscriber {
+        Resubscriber::default()
+    }
+
+    /// Open (or replace) a subscription, returning the REQ to send
+    pub fn subscribe(
+        &mut self,
+        subscription_id: SubscriptionId,
+        filters: Vec<Filter>,
+    ) -> ClientMessage {
+        let _ = self
+            .subscriptions
+            .insert(subscription_id.0.clone(), (filters.clone(), None));
+        ClientMessage::Req(subscription_id, filters)
+    }
+
+    /// Close a subscription, returning the CLOSE to send if it was open
+    pub fn unsubscribe(&mut self, subscription_id: &SubscriptionId) -> Option<ClientMessage> {
+        self.subscriptions
+            .remove(&subscription_id.0)
+            .map(|_| ClientMessage::Close(subscription_id.clone()))
+    }
+
+    /// The subscriptions open
+    pub fn subscription_ids(&self) -> Vec<SubscriptionId> {
+        self.subscriptions
+            .keys()
+            .map(|id| SubscriptionId(id.clone()))
+            .collect()
+    }
+
+    /// Handle a message from the relay, received at `now`, remembering the
+    /// newest event seen on each subscription. Events dated after `now` count
+    /// as seen at `now`, so that one with a clock far ahead can't make the
+    /// subscription skip the events in between.
+    pub fn handle(&mut self, message: &RelayMessage, now: Unixtime) {
+        if let RelayMessage::Event(sub, event) = message {
+            if let Some((_, last_seen)) = self.subscriptions.get_mut(&sub.0) {
+                let seen = event.created_at.min(now);
+                if last_seen.map(|t| seen > t).unwrap_or(true) {
+                    *last_seen = Some(seen);
+                }
+            }
+        }
+    }
+
+    /// The REQs to send after reconnecting, with each filter's `since`
+    /// moved up to the newest event seen on its subscription
+    pub fn reconnected(&self) -> Vec<Resubscribed> {
+        self.subscriptions
+            .iter()
+            .map(|(id, (filters, last_seen))| {
+                let filters: Vec<Filter> = filters
+                    .iter()
+                    .map(|filter| {
+                        let mut filter = filter.clone();
+                        if let Some(last_seen) = last_seen {
+                            filter.since =
+                                Some(filter.since.map_or(*last_seen, |s| s.max(*last_seen)));
+                        }
+                        filter
+                    })
+                    .collect();
+                let subscription_id = SubscriptionId(id.clone());
+                Resubscribed {
+                    subscription_id: subscription_id.clone(),
+                    since: *last_seen,
+                    message: ClientMessage::Req(subscription_id, filters),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Event, EventKind};
+
+    #[test]
+    fn test_resubscriber() {
+        let mut resubscriber = Resubscriber::new();
+        let feed = SubscriptionId("feed".to_owned());
+        let quiet = SubscriptionId("quiet".to_owned());
+        let mut filter = Filter::new();
+        filter.add_event_kind(EventKind::TextNote);
+        filter.since = Some(Unixtime(1600000000));
+
+        let req = resubscriber.subscribe(feed.clone(), vec![filter.clone()]);
+        assert_eq!(req, ClientMessage::Req(feed.clone(), vec![filter.clone()]));
+        let _ = resubscriber.subscribe(quiet.clone(), vec![filter.clone()]);
+        let _ = resubscriber.subscribe(SubscriptionId("gone".to_owned()), vec![]);
+        assert!(resubscriber
+            .unsubscribe(&SubscriptionId("gone".to_owned()))
+            .is_some());
+        assert!(resubscriber
+            .unsubscribe(&SubscriptionId("gone".to_owned()))
+            .is_none());
+
+        for created_at in [1700000100, 1700000300, 1700000200] {
+            let mut event = Event::mock();
+            event.created_at = Unixtime(created_at);
+            resubscriber.handle(
+                &RelayMessage::Event(feed.clone(), Box::new(event)),
+                Unixtime(1700000400),
+            );
+        }
+
+        let resubscribed = resubscriber.reconnected();
+        assert_eq!(resubscribed.len(), 2);
+        let mut adjusted = filter.clone();
+        adjusted.since = Some(Unixtime(1700000300));
+        assert_eq!(
+            resubscribed[0],
+            Resubscribed {
+                subscription_id: feed.clone(),
+                since: Some(Unixtime(1700000300)),
+                message: ClientMessage::Req(feed, vec![adjusted]),
+            }
+        );
+        assert_eq!(resubscribed[1].since, None);
+        assert_eq!(
+            resubscribed[1].message,
+            ClientMessage::Req(quiet.clone(), vec![filter])
+        );
+
+        // An event from the future counts as seen when it arrived
+        let mut event = Event::mock();
+        event.created_at = Unixtime(1900000000);
+        resubscriber.handle(
+            &RelayMessage::Event(quiet, Box::new(event)),
+            Unixtime(1700000500),
+        );
+        assert_eq!(
+            resubscriber.reconnected()[1].since,
+            Some(Unixtime(1700000500))
+        );
+    }
+}
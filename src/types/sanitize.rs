@@ -0,0 +1,182 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// How `Event::content_sanitized()` escapes text for where it is shown
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Escape {
+    /// Plain text, no escaping
+    #[default]
+    None,
+
+    /// HTML text or attribute values
+    Html,
+
+    /// Markdown, with a backslash before characters that would format
+    Markdown,
+}
+
+/// What `Event::content_sanitized()` does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SanitizeOptions {
+    /// Allow at most this many line breaks in a row, if set
+    pub max_newlines: Option<usize>,
+
+    /// How to escape the result
+    pub escape: Escape,
+}
+
+impl Default for SanitizeOptions {
+    fn default() -> SanitizeOptions {
+        SanitizeOptions {
+            max_newlines: Some(2),
+            escape: Escape::None,
+        }
+    }
+}
+
+/// What sanitizing changed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SanitizeReport {
+    /// Whether unicode normalization (NFC) changed the text
+    pub normalized: bool,
+
+    /// How many control characters were removed (other than line breaks and
+    /// tabs)
+    pub control_chars: usize,
+
+    /// How many bidirectional override or isolate characters were removed,
+    /// which can make text display in a misleading order
+    pub bidi_controls: usize,
+
+    /// How many line breaks were removed from long runs
+    pub newlines: usize,
+
+    /// How many characters were escaped
+    pub escaped: usize,
+}
+
+impl SanitizeReport {
+    /// Was anything removed or changed, other than escaping?
+    pub fn is_clean(&self) -> bool {
+        !self.normalized && self.control_chars == 0 && self.bidi_controls == 0 && self.newlines == 0
+    }
+}
+
+/// Text made safe to display, from `Event::content_sanitized()`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SanitizedContent {
+    /// The sanitized text
+    pub text: String,
+
+    /// What was changed
+    pub report: SanitizeReport,
+}
+
+// Sanitize `content`: normalize, strip controls, limit newlines, escape
+pub(crate) fn sanitize(content: &str, options: &SanitizeOptions) -> SanitizedContent {
+    let mut report = SanitizeReport::default();
+
+    let normalized: String = content.nfc().collect();
+    report.normalized = normalized != content;
+
+    let mut text = String::with_capacity(normalized.len());
+    let mut run = 0;
+    for c in normalized.chars() {
+        if is_bidi_control(c) {
+            report.bidi_controls += 1;
+            continue;
+        }
+        if c.is_control() && c != '\n' && c != '\t' {
+            report.control_chars += 1;
+            continue;
+        }
+        if c == '\n' {
+            run += 1;
+            if options.max_newlines.map(|max| run > max).unwrap_or(false) {
+                report.newlines += 1;
+                continue;
+            }
+        } else if c != ' ' && c != '\t' {
+            run = 0;
+        }
+        escape_into(&mut text, c, options.escape, &mut report.escaped);
+    }
+
+    SanitizedContent { text, report }
+}
+
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+fn escape_into(text: &mut String, c: char, escape: Escape, escaped: &mut usize) {
+    match escape {
+        Escape::None => text.push(c),
+        Escape::Html => {
+            let entity = match c {
+                '&' => "&amp;",
+                '<' => "&lt;",
+                '>' => "&gt;",
+                '"' => "&quot;",
+                '\'' => "&#39;",
+                _ => {
+                    text.push(c);
+                    return;
+                }
+            };
+            text.push_str(entity);
+            *escaped += 1;
+        }
+        Escape::Markdown => {
+            if "\\`*_{}[]<>()#+-!|~".contains(c) {
+                text.push('\\');
+                *escaped += 1;
+            }
+            text.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sanitize() {
+        // "e" followed by a combining acute accent becomes "é"
+        let content = "Cafe\u{301}\u{0007} \u{202E}txt.exe\r\n\n\n\n\tend";
+        let sanitized = sanitize(content, &SanitizeOptions::default());
+        assert_eq!(sanitized.text, "Café txt.exe\n\n\tend");
+        assert_eq!(
+            sanitized.report,
+            SanitizeReport {
+                normalized: true,
+                control_chars: 2, // the bell and the carriage return
+                bidi_controls: 1,
+                newlines: 2,
+                escaped: 0,
+            }
+        );
+        assert!(!sanitized.report.is_clean());
+
+        let options = SanitizeOptions {
+            max_newlines: None,
+            escape: Escape::Html,
+        };
+        let sanitized = sanitize("<b>\"Tom & Jerry\"</b>\n\n\n", &options);
+        assert_eq!(
+            sanitized.text,
+            "&lt;b&gt;&quot;Tom &amp; Jerry&quot;&lt;/b&gt;\n\n\n"
+        );
+        assert_eq!(sanitized.report.escaped, 7);
+        assert!(sanitized.report.is_clean());
+
+        let options = SanitizeOptions {
+            escape: Escape::Markdown,
+            ..Default::default()
+        };
+        assert_eq!(
+            sanitize("*hi* [x](y)", &options).text,
+            "\\*hi\\* \\[x\\]\\(y\\)"
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use super::{Event, Id, RelayUrl, Unixtime};
+use std::collections::HashMap;
+
+/// An event along with the relays it has been seen on
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SeenEvent {
+    /// The event
+    pub event: Event,
+
+    /// The relays the event was received from, in the order first received
+    pub seen_on: Vec<RelayUrl>,
+
+    /// When the event was first received
+    pub first_seen: Unixtime,
+}
+
+impl SeenEvent {
+    /// An event just received from `relay`
+    pub fn new(event: Event, relay: RelayUrl, now: Unixtime) -> SeenEvent {
+        SeenEvent {
+            event,
+            seen_on: vec![relay],
+            first_seen: now,
+        }
+    }
+
+    /// Note that the event was also received from `relay`. Returns false if
+    /// it had already been seen there.
+    pub fn add_relay(&mut self, relay: RelayUrl) -> bool {
+        if self.seen_on.contains(&relay) {
+            return false;
+        }
+        self.seen_on.push(relay);
+        true
+    }
+}
+
+/// Tracks which relays each event has been received from, for "seen on" UI
+/// and for scoring relays. Feed it every event as it arrives from a relay.
+#[derive(Clone, Debug, Default)]
+pub struct SeenEvents {
+    events: HashMap<Id, SeenEvent>,
+}
+
+impl SeenEvents {
+    /// Create an empty tracker
+    pub fn new() -> SeenEvents {
+        Default::default()
+    }
+
+    /// Record that `event` was received from `relay`. Returns true if this is
+    /// the first time the event has been seen on any relay.
+    pub fn record(&mut self, event: Event, relay: RelayUrl, now: Unixtime) -> bool {
+        match self.events.get_mut(&event.id) {
+            Some(seen) => {
+                let _ = seen.add_relay(relay);
+                false
+            }
+            None => {
+                let _ = self
+                    .events
+                    .insert(event.id, SeenEvent::new(event, relay, now));
+                true
+            }
+        }
+    }
+
+    /// Get an event and where it was seen
+    pub fn get(&self, id: Id) -> Option<&SeenEvent> {
+        self.events.get(&id)
+    }
+
+    /// The relays an event was seen on, empty if it has not been seen
+    pub fn seen_on(&self, id: Id) -> &[RelayUrl] {
+        self.events
+            .get(&id)
+            .map(|seen| seen.seen_on.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Stop tracking an event
+    pub fn remove(&mut self, id: Id) -> Option<SeenEvent> {
+        self.events.remove(&id)
+    }
+
+    /// Stop tracking events first seen before `time`
+    pub fn forget_before(&mut self, time: Unixtime) {
+        self.events.retain(|_, seen| seen.first_seen >= time);
+    }
+
+    /// For each relay, how many of the tracked events it delivered, and how
+    /// many of those it delivered first
+    pub fn relay_counts(&self) -> HashMap<RelayUrl, (usize, usize)> {
+        let mut counts: HashMap<RelayUrl, (usize, usize)> = HashMap::new();
+        for seen in self.events.values() {
+            for (i, relay) in seen.seen_on.iter().enumerate() {
+                let count = counts.entry(relay.clone()).or_default();
+                count.0 += 1;
+                if i == 0 {
+                    count.1 += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// The number of events tracked
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Are no events tracked?
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_seen_events() {
+        let a = RelayUrl::try_from_str("wss://a.example.com").unwrap();
+        let b = RelayUrl::try_from_str("wss://b.example.com").unwrap();
+        let event = Event::mock();
+        let mut seen = SeenEvents::new();
+
+        assert!(seen.record(event.clone(), b.clone(), Unixtime(100)));
+        assert!(!seen.record(event.clone(), a.clone(), Unixtime(101)));
+        assert!(!seen.record(event.clone(), b.clone(), Unixtime(102)));
+        assert_eq!(seen.seen_on(event.id), &[b.clone(), a.clone()]);
+        assert_eq!(seen.get(event.id).unwrap().first_seen, Unixtime(100));
+        assert!(seen.seen_on(Id::mock()).is_empty());
+
+        let counts = seen.relay_counts();
+        assert_eq!(counts.get(&b), Some(&(1, 1)));
+        assert_eq!(counts.get(&a), Some(&(1, 0)));
+
+        seen.forget_before(Unixtime(100));
+        assert_eq!(seen.len(), 1);
+        seen.forget_before(Unixtime(101));
+        assert!(seen.is_empty());
+    }
+}
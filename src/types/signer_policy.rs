@@ -0,0 +1,204 @@
+use super::{Event, EventKind, PreEvent, PublicKey, Signer};
+use crate::Error;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Wraps a `Signer` to limit what it will do: which event kinds it signs,
+/// which peers it computes ECDH secrets with, and how many events it signs a
+/// minute. Refusals are `Error::SignerRefused`.
+///
+/// This suits a NIP-46 bunker (wrap the signer given to `Nip46Service`) or
+/// any context handing signing to code it does not fully trust.
+///
+/// ```
+/// # use nostr_types::{EventKind, PrivateKey, SignerPolicy};
+/// let signer = SignerPolicy::new(PrivateKey::generate())
+///     .allow_kinds([EventKind::TextNote, EventKind::Reaction])
+///     .max_events_per_minute(10);
+/// ```
+pub struct SignerPolicy<S: Signer> {
+    signer: S,
+    allowed_kinds: Option<Vec<EventKind>>,
+    denied_kinds: Vec<EventKind>,
+    allowed_peers: Option<HashSet<PublicKey>>,
+    max_per_minute: Option<usize>,
+    recent: Mutex<VecDeque<Instant>>,
+}
+
+impl<S: Signer> fmt::Debug for SignerPolicy<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SignerPolicy")
+            .field("signer", &self.signer.public_key())
+            .field("allowed_kinds", &self.allowed_kinds)
+            .field("denied_kinds", &self.denied_kinds)
+            .field("allowed_peers", &self.allowed_peers)
+            .field("max_per_minute", &self.max_per_minute)
+            .finish()
+    }
+}
+
+impl<S: Signer> SignerPolicy<S> {
+    /// Wrap `signer`, at first allowing everything
+    pub fn new(signer: S) -> SignerPolicy<S> {
+        SignerPolicy {
+            signer,
+            allowed_kinds: None,
+            denied_kinds: vec![],
+            allowed_peers: None,
+            max_per_minute: None,
+            recent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Only sign events of these kinds (and not of denied kinds)
+    pub fn allow_kinds<I: IntoIterator<Item = EventKind>>(mut self, kinds: I) -> SignerPolicy<S> {
+        self.allowed_kinds
+            .get_or_insert_with(Vec::new)
+            .extend(kinds);
+        self
+    }
+
+    /// Never sign events of these kinds, even if allowed
+    pub fn deny_kinds<I: IntoIterator<Item = EventKind>>(mut self, kinds: I) -> SignerPolicy<S> {
+        self.denied_kinds.extend(kinds);
+        self
+    }
+
+    /// Only compute ECDH secrets with these peers
+    pub fn allow_peers<I: IntoIterator<Item = PublicKey>>(mut self, peers: I) -> SignerPolicy<S> {
+        self.allowed_peers
+            .get_or_insert_with(HashSet::new)
+            .extend(peers);
+        self
+    }
+
+    /// Sign no more than this many events in any minute
+    pub fn max_events_per_minute(mut self, max: usize) -> SignerPolicy<S> {
+        self.max_per_minute = Some(max);
+        self
+    }
+
+    /// Would an event of this kind be signed (rate limits aside)?
+    pub fn allows_kind(&self, kind: EventKind) -> bool {
+        !self.denied_kinds.contains(&kind)
+            && self
+                .allowed_kinds
+                .as_ref()
+                .map(|kinds| kinds.contains(&kind))
+                .unwrap_or(true)
+    }
+
+    /// Would ECDH with this peer be allowed?
+    pub fn allows_peer(&self, peer: &PublicKey) -> bool {
+        self.allowed_peers
+            .as_ref()
+            .map(|peers| peers.contains(peer))
+            .unwrap_or(true)
+    }
+
+    /// The wrapped signer
+    pub fn inner(&self) -> &S {
+        &self.signer
+    }
+
+    // Count a signature against the rate limit, if there is room
+    fn take_rate_slot(&self) -> Result<(), Error> {
+        let max = match self.max_per_minute {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+        let now = Instant::now();
+        let mut recent = self.recent.lock().unwrap();
+        while recent
+            .front()
+            .map(|t| now.duration_since(*t) >= Duration::from_secs(60))
+            .unwrap_or(false)
+        {
+            let _ = recent.pop_front();
+        }
+        if recent.len() >= max {
+            return Err(Error::SignerRefused(format!(
+                "no more than {max} events per minute"
+            )));
+        }
+        recent.push_back(now);
+        Ok(())
+    }
+}
+
+impl<S: Signer> Signer for SignerPolicy<S> {
+    fn public_key(&self) -> PublicKey {
+        self.signer.public_key()
+    }
+
+    fn sign_event(&self, input: PreEvent) -> Result<Event, Error> {
+        if !self.allows_kind(input.kind) {
+            return Err(Error::SignerRefused(format!(
+                "kind {} may not be signed",
+                u64::from(input.kind)
+            )));
+        }
+        self.take_rate_slot()?;
+        self.signer.sign_event(input)
+    }
+
+    fn ecdh(&self, peer: &PublicKey) -> Result<[u8; 32], Error> {
+        if !self.allows_peer(peer) {
+            return Err(Error::SignerRefused(format!(
+                "ECDH with {} is not allowed",
+                peer.as_hex_string()
+            )));
+        }
+        self.signer.ecdh(peer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PrivateKey, Unixtime};
+
+    #[test]
+    fn test_signer_policy() {
+        let friend = PrivateKey::generate().public_key();
+        let stranger = PrivateKey::generate().public_key();
+        let signer = SignerPolicy::new(PrivateKey::generate())
+            .allow_kinds([
+                EventKind::TextNote,
+                EventKind::Reaction,
+                EventKind::Metadata,
+            ])
+            .deny_kinds([EventKind::Metadata])
+            .allow_peers([friend])
+            .max_events_per_minute(2);
+        let pubkey = signer.public_key();
+
+        let pre = |kind: EventKind| PreEvent {
+            pubkey,
+            created_at: Unixtime(1700000000),
+            kind,
+            tags: vec![],
+            content: "".to_owned(),
+            ots: None,
+        };
+
+        assert!(matches!(
+            signer.sign_event(pre(EventKind::Metadata)),
+            Err(Error::SignerRefused(_))
+        ));
+        assert!(signer.sign_event(pre(EventKind::ContactList)).is_err());
+        assert!(signer.sign_event(pre(EventKind::TextNote)).is_ok());
+        assert!(signer.sign_event(pre(EventKind::Reaction)).is_ok());
+        // Refused kinds did not use up the rate limit, but these did
+        assert!(signer.sign_event(pre(EventKind::TextNote)).is_err());
+
+        assert_eq!(
+            signer.ecdh(&friend).unwrap(),
+            signer.inner().ecdh(&friend).unwrap()
+        );
+        assert!(signer.ecdh(&stranger).is_err());
+        assert!(signer.inner().ecdh(&stranger).is_ok());
+    }
+}
@@ -0,0 +1,611 @@
+use super::{
+    Event, EventAddr, EventKind, EventReference, Filter, Id, IdHex, IdSetDigest, PublicKey,
+    PublicKeyHex, RelayUrl, Unixtime, VanishRequest,
+};
+use crate::Error;
+
+/// Persistent storage for events.
+///
+/// Implementors provide the raw operations (`insert`, `get`, `query`, `delete`).
+/// `put` is provided on top of them and applies the nostr storage rules:
+/// ephemeral events are not stored, replaceable and addressable events replace
+/// older versions (see `Event::replaces`), and deletions (NIP-09) remove the
+/// author's own events, by id or by address, and keep them from being stored
+/// again later.
+pub trait EventStorage {
+    /// Write the event as is, without applying any rules
+    fn insert(&self, event: &Event) -> Result<(), Error>;
+
+    /// Get an event by its id
+    fn get(&self, id: Id) -> Result<Option<Event>, Error>;
+
+    /// Get the events matching the filter, newest first, no more than the
+    /// filter's `limit`
+    fn query(&self, filter: &Filter) -> Result<Vec<Event>, Error>;
+
+    /// Remove an event, returning whether it was present. This does not
+    /// prevent it from being stored again.
+    fn delete(&self, id: Id) -> Result<bool, Error>;
+
+    /// Whether `put` may store an event at all. By default ephemeral kinds
+    /// (20000 to 29999) are never stored; override to keep out more, such as
+    /// other short-lived events.
+    fn persists(&self, event: &Event) -> bool {
+        !event.kind.is_ephemeral()
+    }
+
+    /// Store an event, applying replacement and deletion rules. Returns whether
+    /// the event was stored; it is not if `persists` refuses it, it is a
+    /// duplicate, has been deleted, or has been replaced by a newer event.
+    fn put(&self, event: &Event) -> Result<bool, Error> {
+        if !self.persists(event) {
+            return Ok(false);
+        }
+
+        if self.get(event.id)?.is_some() {
+            return Ok(false);
+        }
+
+        if self.is_deleted(event)? {
+            return Ok(false);
+        }
+
+        if event.kind.is_replaceable() || event.kind.is_addressable() {
+            if let Some(existing) =
+                self.replaceable(event.kind, event.pubkey, event.parameter().as_deref())?
+            {
+                if !event.replaces(&existing) {
+                    return Ok(false);
+                }
+                let _ = self.delete(existing.id)?;
+            }
+        }
+
+        for target in deleted_by(self, event)? {
+            let _ = self.delete(target.id)?;
+        }
+
+        self.insert(event)?;
+        Ok(true)
+    }
+
+    /// Get the current version of a replaceable or addressable event. The
+    /// `parameter` (the 'd' tag) is only used for addressable kinds.
+    fn replaceable(
+        &self,
+        kind: EventKind,
+        pubkey: PublicKey,
+        parameter: Option<&str>,
+    ) -> Result<Option<Event>, Error> {
+        let mut filter = Filter::new();
+        filter.add_event_kind(kind);
+        filter.add_author(PublicKeyHex::from(pubkey));
+
+        // Not filtering on 'd' here, since a missing 'd' tag is an implicit
+        // empty parameter which a tag filter would not match
+        let parameter = if kind.is_addressable() {
+            Some(parameter.unwrap_or("").to_owned())
+        } else {
+            None
+        };
+
+        let mut current: Option<Event> = None;
+        for event in self.query(&filter)? {
+            if event.parameter() != parameter {
+                continue;
+            }
+            match current {
+                Some(ref c) if !event.replaces(c) => {}
+                _ => current = Some(event),
+            }
+        }
+        Ok(current)
+    }
+
+    /// Remove events whose expiration (NIP-40) is at or before `now`,
+    /// returning their ids. This scans every event, so implementors with an
+    /// index on expiration should override it.
+    fn sweep_expired(&self, now: Unixtime) -> Result<Vec<Id>, Error> {
+        let mut removed: Vec<Id> = Vec::new();
+        for event in self.query(&Filter::new())? {
+            if event.expiration().map(|t| t <= now).unwrap_or(false) && self.delete(event.id)? {
+                removed.push(event.id);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Digest the ids of the events matching the filter, to compare with
+    /// another store's digest for the same filter
+    fn id_digest(&self, filter: &Filter) -> Result<IdSetDigest, Error> {
+        Ok(self.query(filter)?.iter().map(|e| e.id).collect())
+    }
+
+    /// Has the event been deleted by a stored deletion from its author? A
+    /// replaceable or addressable event is also deleted by a deletion of its
+    /// address made at or after it.
+    fn is_deleted(&self, event: &Event) -> Result<bool, Error> {
+        let mut filter = Filter::new();
+        filter.add_event_kind(EventKind::EventDeletion);
+        filter.add_author(PublicKeyHex::from(event.pubkey));
+        filter.add_e_tag_ids(IdHex::from(event.id));
+        if !self.query(&filter)?.is_empty() {
+            return Ok(true);
+        }
+
+        if let Ok(addr) = EventAddr::for_event(event, vec![]) {
+            let mut filter = Filter::new();
+            filter.add_event_kind(EventKind::EventDeletion);
+            filter.add_author(PublicKeyHex::from(event.pubkey));
+            filter.a = vec![addr.as_address()];
+            filter.since = Some(event.created_at);
+            return Ok(!self.query(&filter)?.is_empty());
+        }
+
+        Ok(false)
+    }
+}
+
+// The stored events a deletion removes (NIP-09): the author's own events by
+// id, and the current version of the author's addresses if it is no newer
+// than the deletion. Deleting a deletion has no effect.
+fn deleted_by<S: EventStorage + ?Sized>(
+    storage: &S,
+    deletion: &Event,
+) -> Result<Vec<Event>, Error> {
+    let mut deleted: Vec<Event> = Vec::new();
+    for target in deletion.deletion_targets() {
+        let found = match &target {
+            EventReference::Pointer(pointer) => storage.get(pointer.id)?,
+            EventReference::Addr(addr) => storage
+                .replaceable(addr.kind, addr.author, Some(&addr.d))?
+                .filter(|e| e.created_at <= deletion.created_at),
+        };
+        if let Some(event) = found {
+            if event.pubkey == deletion.pubkey && event.kind != EventKind::EventDeletion {
+                deleted.push(event);
+            }
+        }
+    }
+    Ok(deleted)
+}
+
+/// What a `Sweeper` removed from storage
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SweepReport {
+    /// Events that had expired
+    pub expired: Vec<Id>,
+
+    /// Events covered by a request to vanish
+    pub vanished: Vec<Id>,
+
+    /// Events deleted by their author
+    pub deleted: Vec<Id>,
+}
+
+impl SweepReport {
+    /// The number of events removed
+    pub fn len(&self) -> usize {
+        self.expired.len() + self.vanished.len() + self.deleted.len()
+    }
+
+    /// Was nothing removed?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Removes events that should no longer be kept: expired events (NIP-40),
+/// events covered by stored requests to vanish (NIP-62), and events deleted
+/// by stored deletions (NIP-09).
+///
+/// `EventStorage::put` already applies deletions as they arrive, but events
+/// inserted by other means, or before a rule was enforced, are only caught by
+/// a sweep. Run `sweep()` periodically, e.g. from a timer thread.
+#[derive(Clone, Debug)]
+pub struct Sweeper {
+    expired: bool,
+    vanish: bool,
+    deletions: bool,
+    relay: Option<RelayUrl>,
+}
+
+impl Default for Sweeper {
+    fn default() -> Sweeper {
+        Sweeper {
+            expired: true,
+            vanish: true,
+            deletions: true,
+            relay: None,
+        }
+    }
+}
+
+impl Sweeper {
+    /// Create a sweeper which applies all of the rules
+    pub fn new() -> Sweeper {
+        Default::default()
+    }
+
+    /// Whether to remove expired events
+    pub fn expired(mut self, enabled: bool) -> Sweeper {
+        self.expired = enabled;
+        self
+    }
+
+    /// Whether to apply requests to vanish
+    pub fn vanish(mut self, enabled: bool) -> Sweeper {
+        self.vanish = enabled;
+        self
+    }
+
+    /// Whether to apply deletions
+    pub fn deletions(mut self, enabled: bool) -> Sweeper {
+        self.deletions = enabled;
+        self
+    }
+
+    /// Only apply requests to vanish addressed to this relay (or to all
+    /// relays). Without this every request to vanish is applied.
+    pub fn relay(mut self, relay: RelayUrl) -> Sweeper {
+        self.relay = Some(relay);
+        self
+    }
+
+    /// Sweep the storage, returning what was removed
+    pub fn sweep<S: EventStorage + ?Sized>(
+        &self,
+        storage: &S,
+        now: Unixtime,
+    ) -> Result<SweepReport, Error> {
+        let mut report = SweepReport::default();
+
+        if self.expired {
+            report.expired = storage.sweep_expired(now)?;
+        }
+
+        if self.vanish {
+            let mut filter = Filter::new();
+            filter.add_event_kind(EventKind::RequestToVanish);
+            for request in storage.query(&filter)? {
+                if let Some(relay) = &self.relay {
+                    match VanishRequest::try_from_event(&request) {
+                        Ok(vanish) if vanish.targets(relay) => {}
+                        _ => continue,
+                    }
+                }
+
+                // Their own events, and gift wraps addressed to them
+                let mut authored = Filter::new();
+                authored.add_author(PublicKeyHex::from(request.pubkey));
+                authored.until = Some(request.created_at);
+                let mut wrapped = Filter::new();
+                wrapped.add_event_kind(EventKind::GiftWrap);
+                wrapped.add_p_tag_public_key(PublicKeyHex::from(request.pubkey));
+
+                for event in storage
+                    .query(&authored)?
+                    .into_iter()
+                    .chain(storage.query(&wrapped)?)
+                {
+                    // The requests themselves are kept, to refuse the events
+                    // if they come back
+                    if event.kind == EventKind::RequestToVanish
+                        || !VanishRequest::covers(&request, &event)
+                    {
+                        continue;
+                    }
+                    if storage.delete(event.id)? {
+                        report.vanished.push(event.id);
+                    }
+                }
+            }
+        }
+
+        if self.deletions {
+            let mut filter = Filter::new();
+            filter.add_event_kind(EventKind::EventDeletion);
+            for deletion in storage.query(&filter)? {
+                for target in deleted_by(storage, &deletion)? {
+                    if storage.delete(target.id)? {
+                        report.deleted.push(target.id);
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PreEvent, PrivateKey, Tag, UncheckedUrl};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryStorage(Mutex<HashMap<Id, Event>>);
+
+    impl EventStorage for MemoryStorage {
+        fn insert(&self, event: &Event) -> Result<(), Error> {
+            let _ = self.0.lock().unwrap().insert(event.id, event.clone());
+            Ok(())
+        }
+
+        fn get(&self, id: Id) -> Result<Option<Event>, Error> {
+            Ok(self.0.lock().unwrap().get(&id).cloned())
+        }
+
+        fn query(&self, filter: &Filter) -> Result<Vec<Event>, Error> {
+            let mut events: Vec<Event> = self
+                .0
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|e| filter.event_matches(e))
+                .cloned()
+                .collect();
+            events.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+            if let Some(limit) = filter.limit {
+                events.truncate(limit);
+            }
+            Ok(events)
+        }
+
+        fn delete(&self, id: Id) -> Result<bool, Error> {
+            Ok(self.0.lock().unwrap().remove(&id).is_some())
+        }
+    }
+
+    fn event(private_key: &PrivateKey, kind: EventKind, created_at: i64, tags: Vec<Tag>) -> Event {
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(created_at),
+            kind,
+            tags,
+            content: "".to_owned(),
+            ots: None,
+        };
+        Event::new(pre, private_key).unwrap()
+    }
+
+    #[test]
+    fn test_put_replaceable() {
+        let storage = MemoryStorage::default();
+        let private_key = PrivateKey::generate();
+        let pubkey = private_key.public_key();
+
+        let old = event(&private_key, EventKind::Metadata, 100, vec![]);
+        let new = event(&private_key, EventKind::Metadata, 200, vec![]);
+        assert!(storage.put(&new).unwrap());
+        assert!(!storage.put(&new).unwrap());
+        assert!(!storage.put(&old).unwrap());
+        assert!(storage.get(old.id).unwrap().is_none());
+
+        let newest = event(&private_key, EventKind::Metadata, 300, vec![]);
+        assert!(storage.put(&newest).unwrap());
+        assert!(storage.get(new.id).unwrap().is_none());
+        assert_eq!(
+            storage
+                .replaceable(EventKind::Metadata, pubkey, None)
+                .unwrap()
+                .map(|e| e.id),
+            Some(newest.id)
+        );
+
+        // Addressable events are replaced per parameter
+        let kind = EventKind::LongFormContent;
+        let a = event(
+            &private_key,
+            kind,
+            100,
+            vec![Tag::Identifier("a".to_owned())],
+        );
+        let b = event(
+            &private_key,
+            kind,
+            100,
+            vec![Tag::Identifier("b".to_owned())],
+        );
+        let implicit = event(&private_key, kind, 100, vec![]);
+        assert!(storage.put(&a).unwrap());
+        assert!(storage.put(&b).unwrap());
+        assert!(storage.put(&implicit).unwrap());
+        assert_eq!(
+            storage
+                .replaceable(kind, pubkey, Some(""))
+                .unwrap()
+                .map(|e| e.id),
+            Some(implicit.id)
+        );
+        let a2 = event(
+            &private_key,
+            kind,
+            200,
+            vec![Tag::Identifier("a".to_owned())],
+        );
+        assert!(storage.put(&a2).unwrap());
+        assert!(storage.get(a.id).unwrap().is_none());
+        assert!(storage.get(b.id).unwrap().is_some());
+
+        // Ephemeral events are not stored
+        let ephemeral = event(&private_key, EventKind::Ephemeral(20001), 100, vec![]);
+        assert!(!storage.put(&ephemeral).unwrap());
+    }
+
+    #[test]
+    fn test_put_deletion() {
+        let storage = MemoryStorage::default();
+        let author = PrivateKey::generate();
+        let other = PrivateKey::generate();
+
+        let note = event(&author, EventKind::TextNote, 100, vec![]);
+        let late_note = event(&author, EventKind::TextNote, 101, vec![]);
+        let others_note = event(&other, EventKind::TextNote, 100, vec![]);
+        assert!(storage.put(&note).unwrap());
+        assert!(storage.put(&others_note).unwrap());
+
+        let e_tag = |id: Id| Tag::Event {
+            id,
+            recommended_relay_url: None,
+            marker: None,
+            pubkey: None,
+        };
+        let deletion = event(
+            &author,
+            EventKind::EventDeletion,
+            200,
+            vec![e_tag(note.id), e_tag(late_note.id), e_tag(others_note.id)],
+        );
+        assert!(storage.put(&deletion).unwrap());
+        assert!(storage.get(note.id).unwrap().is_none());
+        assert!(storage.get(others_note.id).unwrap().is_some());
+
+        // Deleted events are not stored again, even if they arrive later
+        assert!(!storage.put(&note).unwrap());
+        assert!(!storage.put(&late_note).unwrap());
+    }
+
+    #[test]
+    fn test_put_address_deletion() {
+        let storage = MemoryStorage::default();
+        let author = PrivateKey::generate();
+        let kind = EventKind::LongFormContent;
+        let d = || Tag::Identifier("post".to_owned());
+
+        let article = event(&author, kind, 100, vec![d()]);
+        let other_article = event(&author, kind, 100, vec![Tag::Identifier("x".to_owned())]);
+        assert!(storage.put(&article).unwrap());
+        assert!(storage.put(&other_article).unwrap());
+
+        let address = EventAddr::for_event(&article, vec![]).unwrap();
+        let deletion = event(
+            &author,
+            EventKind::EventDeletion,
+            200,
+            vec![Tag::Address {
+                address,
+                marker: None,
+            }],
+        );
+        assert!(storage.put(&deletion).unwrap());
+        assert!(storage.get(article.id).unwrap().is_none());
+        assert!(storage.get(other_article.id).unwrap().is_some());
+
+        // Versions up to the deletion stay deleted, later ones are kept
+        let edited = event(&author, kind, 150, vec![d()]);
+        assert!(!storage.put(&edited).unwrap());
+        let republished = event(&author, kind, 300, vec![d()]);
+        assert!(storage.put(&republished).unwrap());
+    }
+
+    #[test]
+    fn test_sweeper() {
+        let storage = MemoryStorage::default();
+        let author = PrivateKey::generate();
+        let other = PrivateKey::generate();
+        let now = Unixtime(1000);
+
+        let expired = event(
+            &other,
+            EventKind::TextNote,
+            100,
+            vec![Tag::Expiration(Unixtime(500))],
+        );
+        let unexpired = event(
+            &other,
+            EventKind::TextNote,
+            100,
+            vec![Tag::Expiration(Unixtime(2000))],
+        );
+        let note = event(&author, EventKind::TextNote, 100, vec![]);
+        let later_note = event(&author, EventKind::TextNote, 300, vec![]);
+        let wrap = event(
+            &other,
+            EventKind::GiftWrap,
+            100,
+            vec![Tag::Pubkey {
+                pubkey: PublicKeyHex::from(author.public_key()),
+                recommended_relay_url: None,
+                petname: None,
+            }],
+        );
+        let others_note = event(&other, EventKind::TextNote, 100, vec![]);
+        let vanish = event(
+            &author,
+            EventKind::RequestToVanish,
+            200,
+            vec![Tag::Relay(UncheckedUrl::from_str(
+                VanishRequest::ALL_RELAYS,
+            ))],
+        );
+        let deletion = event(
+            &other,
+            EventKind::EventDeletion,
+            200,
+            vec![Tag::Event {
+                id: others_note.id,
+                recommended_relay_url: None,
+                marker: None,
+                pubkey: None,
+            }],
+        );
+        // Inserted directly, bypassing the rules in put()
+        for e in [
+            &expired,
+            &unexpired,
+            &note,
+            &later_note,
+            &wrap,
+            &others_note,
+            &vanish,
+            &deletion,
+        ] {
+            storage.insert(e).unwrap();
+        }
+
+        let report = Sweeper::new().sweep(&storage, now).unwrap();
+        assert_eq!(report.expired, vec![expired.id]);
+        let mut vanished = report.vanished.clone();
+        vanished.sort();
+        let mut expected = vec![note.id, wrap.id];
+        expected.sort();
+        assert_eq!(vanished, expected);
+        assert_eq!(report.deleted, vec![others_note.id]);
+        assert_eq!(report.len(), 4);
+
+        assert!(storage.get(unexpired.id).unwrap().is_some());
+        assert!(storage.get(later_note.id).unwrap().is_some());
+        assert!(storage.get(vanish.id).unwrap().is_some());
+        assert!(storage.get(deletion.id).unwrap().is_some());
+
+        assert!(Sweeper::new().sweep(&storage, now).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sweeper_relay() {
+        let storage = MemoryStorage::default();
+        let author = PrivateKey::generate();
+        let note = event(&author, EventKind::TextNote, 100, vec![]);
+        let vanish = event(
+            &author,
+            EventKind::RequestToVanish,
+            200,
+            vec![Tag::Relay(UncheckedUrl::from_str(
+                "wss://elsewhere.example.com",
+            ))],
+        );
+        assert!(storage.put(&note).unwrap());
+        assert!(storage.put(&vanish).unwrap());
+
+        let relay = RelayUrl::try_from_str("wss://here.example.com").unwrap();
+        let report = Sweeper::new()
+            .relay(relay)
+            .sweep(&storage, Unixtime(1000))
+            .unwrap();
+        assert!(report.is_empty());
+        assert!(storage.get(note.id).unwrap().is_some());
+    }
+}
@@ -3,7 +3,19 @@ use serde::{Deserialize, Serialize};
 
 /// A random client-chosen string used to refer to a subscription
 #[derive(
-    AsMut, AsRef, Clone, Debug, Deref, Deserialize, Eq, From, FromStr, Into, PartialEq, Serialize,
+    AsMut,
+    AsRef,
+    Clone,
+    Debug,
+    Deref,
+    Deserialize,
+    Eq,
+    From,
+    FromStr,
+    Hash,
+    Into,
+    PartialEq,
+    Serialize,
 )]
 pub struct SubscriptionId(pub String);
 
@@ -1,8 +1,55 @@
-use crate::{DelegationConditions, Id, PublicKeyHex, SignatureHex, UncheckedUrl, Unixtime};
+use crate::{
+    DelegationConditions, Error, EventAddr, EventKind, Id, ParseProfile, PublicKeyHex,
+    SignatureHex, UncheckedUrl, Unixtime,
+};
 use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
 use serde::ser::{Serialize, SerializeSeq, Serializer};
 use std::fmt;
 
+/// The protocol a bridged event came from (NIP-48)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ProxyProtocol {
+    /// ActivityPub, with the object's URL as the id
+    ActivityPub,
+
+    /// AT Protocol, with an AT URI as the id
+    AtProto,
+
+    /// An RSS feed, with the item's guid (a URL with a fragment) as the id
+    Rss,
+
+    /// The web, with a URL as the id
+    Web,
+
+    /// Any other protocol
+    Other(String),
+}
+
+impl ProxyProtocol {
+    /// Parse the protocol name as used in the tag
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> ProxyProtocol {
+        match s {
+            "activitypub" => ProxyProtocol::ActivityPub,
+            "atproto" => ProxyProtocol::AtProto,
+            "rss" => ProxyProtocol::Rss,
+            "web" => ProxyProtocol::Web,
+            _ => ProxyProtocol::Other(s.to_owned()),
+        }
+    }
+
+    /// The protocol name as used in the tag
+    pub fn as_str(&self) -> &str {
+        match self {
+            ProxyProtocol::ActivityPub => "activitypub",
+            ProxyProtocol::AtProto => "atproto",
+            ProxyProtocol::Rss => "rss",
+            ProxyProtocol::Web => "web",
+            ProxyProtocol::Other(s) => s,
+        }
+    }
+}
+
 /// A tag on an Event
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Tag {
@@ -33,6 +80,9 @@ pub enum Tag {
 
         /// A marker (commonly things like 'reply')
         marker: Option<String>,
+
+        /// The public key of the author of the referenced event (NIP-10)
+        pubkey: Option<PublicKeyHex>,
     },
 
     /// A time when the event should be considered expired
@@ -85,6 +135,70 @@ pub enum Tag {
     /// Parameter of a parameterized replaceable event
     Parameter(String),
 
+    /// 'title' The title of an article or other titled content (NIP-23)
+    Title(String),
+
+    /// 'summary' A summary of an article (NIP-23)
+    Summary(String),
+
+    /// 'image' An image representing the content (NIP-23, NIP-58)
+    Image {
+        /// The URL of the image
+        url: UncheckedUrl,
+
+        /// Optional dimensions, in "<width>x<height>" form
+        dimensions: Option<String>,
+    },
+
+    /// 'published_at' The time the content was first published (NIP-23)
+    PublishedAt(Unixtime),
+
+    /// 'client' The client which created the event (NIP-89)
+    Client {
+        /// The name of the client
+        name: String,
+
+        /// The address of the client's handler information event ("31990:<pubkey>:<d>")
+        address: Option<String>,
+
+        /// A relay where that handler information event can be found
+        relay_url: Option<UncheckedUrl>,
+    },
+
+    /// '-' The event is protected and may only be published by its author (NIP-70)
+    Protected,
+
+    /// 'alt' A short human-readable description of events of unknown kinds (NIP-31)
+    Alt(String),
+
+    /// 'relay' A relay URL, such as the relay being authenticated to (NIP-42) or the
+    /// relay a request to vanish is addressed to (NIP-62)
+    Relay(UncheckedUrl),
+
+    /// 'a' A reference to an addressable event, by its "<kind>:<pubkey>:<d>"
+    /// address. A relay hint is kept as the only relay of the address.
+    Address {
+        /// The address of the event
+        address: EventAddr,
+
+        /// A marker (such as a wiki relation, NIP-54)
+        marker: Option<String>,
+    },
+
+    /// 'k' The kind of a referenced event, as in generic reposts, reactions,
+    /// comments and DVM results
+    KindReference(EventKind),
+
+    /// 'proxy' The source of an event bridged from another protocol (NIP-48)
+    Proxy {
+        /// The id of the object in the source protocol, e.g. an ActivityPub
+        /// URL or an AT URI
+        id: String,
+
+        /// The source protocol
+        protocol: ProxyProtocol,
+    },
+
     /// Any other tag
     Other {
         /// The tag name
@@ -114,11 +228,32 @@ impl Tag {
             Tag::Subject(_) => "subject".to_string(),
             Tag::Nonce { .. } => "nonce".to_string(),
             Tag::Parameter(_) => "parameter".to_string(),
+            Tag::Title(_) => "title".to_string(),
+            Tag::Summary(_) => "summary".to_string(),
+            Tag::Image { .. } => "image".to_string(),
+            Tag::PublishedAt(_) => "published_at".to_string(),
+            Tag::Client { .. } => "client".to_string(),
+            Tag::Protected => "-".to_string(),
+            Tag::Alt(_) => "alt".to_string(),
+            Tag::Relay(_) => "relay".to_string(),
+            Tag::Address { .. } => "a".to_string(),
+            Tag::KindReference(_) => "k".to_string(),
+            Tag::Proxy { .. } => "proxy".to_string(),
             Tag::Other { tag, .. } => tag.clone(),
             Tag::Empty => panic!("empty tags have no tagname"),
         }
     }
 
+    /// Is this tag understood, either as one of the typed variants or by a custom
+    /// `ParsedTag` type registered with `register_tag_type()`?
+    pub fn is_recognized(&self) -> bool {
+        match self {
+            Tag::Other { .. } => super::parsed_tag::registered_tag_recognizes(self),
+            Tag::Empty => false,
+            _ => true,
+        }
+    }
+
     // Mock data for testing
     #[allow(dead_code)]
     pub(crate) fn mock() -> Tag {
@@ -126,137 +261,252 @@ impl Tag {
             id: Id::mock(),
             recommended_relay_url: Some(UncheckedUrl::mock()),
             marker: None,
+            pubkey: None,
         }
     }
 }
 
-impl Serialize for Tag {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        match self {
-            Tag::ContentWarning(msg) => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("content-warning")?;
-                seq.serialize_element(msg)?;
-                seq.end()
+impl Tag {
+    /// Convert into the array of strings that represents this tag on the wire
+    pub fn to_strings(&self) -> Vec<String> {
+        fn opt_pair(a: &Option<String>, b: &Option<String>) -> Vec<String> {
+            match (a, b) {
+                (Some(a), Some(b)) => vec![a.to_owned(), b.to_owned()],
+                (Some(a), None) => vec![a.to_owned()],
+                (None, Some(b)) => vec!["".to_owned(), b.to_owned()],
+                (None, None) => vec![],
             }
+        }
+
+        let mut output: Vec<String> = match self {
+            Tag::Empty => return vec![],
+            Tag::Other { tag, data } => {
+                let mut v = vec![tag.to_owned()];
+                v.extend(data.iter().cloned());
+                return v;
+            }
+            _ => vec![self.tagname()],
+        };
+
+        match self {
+            Tag::ContentWarning(msg) => output.push(msg.to_owned()),
             Tag::Delegation {
                 pubkey,
                 conditions,
                 sig,
             } => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("delegation")?;
-                seq.serialize_element(pubkey)?;
-                seq.serialize_element(conditions)?;
-                seq.serialize_element(sig)?;
-                seq.end()
+                output.push(pubkey.as_str().to_owned());
+                output.push(conditions.as_string());
+                output.push(sig.0.to_owned());
             }
             Tag::Event {
                 id,
                 recommended_relay_url,
                 marker,
+                pubkey,
             } => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("e")?;
-                seq.serialize_element(id)?;
-                if let Some(rru) = recommended_relay_url {
-                    seq.serialize_element(rru)?;
-                } else if marker.is_some() {
-                    seq.serialize_element("")?;
+                output.push(id.as_hex_string());
+                let rru = recommended_relay_url.as_ref().map(|u| u.0.to_owned());
+                if let Some(pk) = pubkey {
+                    output.push(rru.unwrap_or_default());
+                    output.push(marker.to_owned().unwrap_or_default());
+                    output.push(pk.as_str().to_owned());
+                } else {
+                    output.extend(opt_pair(&rru, marker));
                 }
-                if let Some(m) = marker {
-                    seq.serialize_element(m)?;
-                }
-                seq.end()
-            }
-            Tag::Expiration(time) => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("expiration")?;
-                seq.serialize_element(time)?;
-                seq.end()
             }
+            Tag::Expiration(time) => output.push(format!("{}", time.0)),
             Tag::Pubkey {
                 pubkey,
                 recommended_relay_url,
                 petname,
             } => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("p")?;
-                seq.serialize_element(pubkey)?;
-                if let Some(rru) = recommended_relay_url {
-                    seq.serialize_element(rru)?;
-                } else if petname.is_some() {
-                    seq.serialize_element("")?;
-                }
-                if let Some(pn) = petname {
-                    seq.serialize_element(pn)?;
-                }
-                seq.end()
-            }
-            Tag::Hashtag(hashtag) => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("t")?;
-                seq.serialize_element(hashtag)?;
-                seq.end()
+                output.push(pubkey.as_str().to_owned());
+                output.extend(opt_pair(
+                    &recommended_relay_url.as_ref().map(|u| u.0.to_owned()),
+                    petname,
+                ));
             }
+            Tag::Hashtag(hashtag) => output.push(hashtag.to_owned()),
             Tag::Reference { url, marker } => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("r")?;
-                seq.serialize_element(url)?;
+                output.push(url.0.to_owned());
                 if let Some(m) = marker {
-                    seq.serialize_element(m)?
+                    output.push(m.to_owned());
                 }
-                seq.end()
-            }
-            Tag::Geohash(geohash) => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("g")?;
-                seq.serialize_element(geohash)?;
-                seq.end()
-            }
-            Tag::Identifier(id) => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("d")?;
-                seq.serialize_element(id)?;
-                seq.end()
-            }
-            Tag::Subject(subject) => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("subject")?;
-                seq.serialize_element(subject)?;
-                seq.end()
             }
+            Tag::Geohash(geohash) => output.push(geohash.to_owned()),
+            Tag::Identifier(id) => output.push(id.to_owned()),
+            Tag::Subject(subject) => output.push(subject.to_owned()),
             Tag::Nonce { nonce, target } => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("nonce")?;
-                seq.serialize_element(nonce)?;
+                output.push(nonce.to_owned());
                 if let Some(t) = target {
-                    seq.serialize_element(t)?;
+                    output.push(t.to_owned());
                 }
-                seq.end()
             }
-            Tag::Parameter(parameter) => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element("parameter")?;
-                seq.serialize_element(parameter)?;
-                seq.end()
-            }
-            Tag::Other { tag, data } => {
-                let mut seq = serializer.serialize_seq(None)?;
-                seq.serialize_element(tag)?;
-                for s in data.iter() {
-                    seq.serialize_element(s)?;
+            Tag::Parameter(parameter) => output.push(parameter.to_owned()),
+            Tag::Title(title) => output.push(title.to_owned()),
+            Tag::Summary(summary) => output.push(summary.to_owned()),
+            Tag::Image { url, dimensions } => {
+                output.push(url.0.to_owned());
+                if let Some(d) = dimensions {
+                    output.push(d.to_owned());
                 }
-                seq.end()
             }
-            Tag::Empty => {
-                let seq = serializer.serialize_seq(Some(0))?;
-                seq.end()
+            Tag::PublishedAt(time) => output.push(format!("{}", time.0)),
+            Tag::Client {
+                name,
+                address,
+                relay_url,
+            } => {
+                output.push(name.to_owned());
+                output.extend(opt_pair(
+                    address,
+                    &relay_url.as_ref().map(|u| u.0.to_owned()),
+                ));
+            }
+            Tag::Protected => {}
+            Tag::Alt(alt) => output.push(alt.to_owned()),
+            Tag::Relay(url) => output.push(url.0.to_owned()),
+            Tag::Address { address, marker } => {
+                output.push(address.as_address());
+                output.extend(opt_pair(
+                    &address.relays.first().map(|u| u.0.to_owned()),
+                    marker,
+                ));
+            }
+            Tag::KindReference(kind) => output.push(format!("{}", u64::from(*kind))),
+            Tag::Proxy { id, protocol } => {
+                output.push(id.to_owned());
+                output.push(protocol.as_str().to_owned());
+            }
+            Tag::Other { .. } | Tag::Empty => unreachable!(),
+        }
+
+        output
+    }
+
+    /// Parse from the array of strings that represents this tag on the wire.
+    ///
+    /// Tags that are not recognized, that are malformed, or that carry extra data
+    /// we do not model, become `Tag::Other` so that they reserialize exactly as
+    /// they were received. Altering them would invalidate the event signature.
+    pub fn from_strings(strings: Vec<String>) -> Tag {
+        if strings.is_empty() {
+            return Tag::Empty;
+        }
+
+        if let Some(tag) = Self::parse_known(&strings) {
+            if tag.to_strings() == strings {
+                return tag;
+            }
+        }
+
+        let mut iter = strings.into_iter();
+        let tag = iter.next().unwrap();
+        Tag::Other {
+            tag,
+            data: iter.collect(),
+        }
+    }
+
+    /// Parse from the array of strings under a `ParseProfile`. Only `Strict`
+    /// differs from `from_strings()`, by rejecting well-known tags with the
+    /// wrong number of fields or with uppercase hex.
+    pub fn from_strings_with_profile(
+        strings: Vec<String>,
+        profile: ParseProfile,
+    ) -> Result<Tag, Error> {
+        profile.check_tag(&strings)?;
+        Ok(Self::from_strings(strings))
+    }
+
+    // Try to interpret the strings as a well-known tag. This may be lossy, which
+    // from_strings() guards against.
+    fn parse_known(strings: &[String]) -> Option<Tag> {
+        let get = |n: usize| strings.get(n).map(|s| s.to_owned());
+        let tag = match strings[0].as_str() {
+            "content-warning" => Tag::ContentWarning(get(1)?),
+            "delegation" => Tag::Delegation {
+                pubkey: PublicKeyHex::try_from_string(get(1)?).ok()?,
+                conditions: DelegationConditions::try_from_str(strings.get(2)?).ok()?,
+                sig: SignatureHex(get(3)?),
+            },
+            "e" => Tag::Event {
+                id: Id::try_from_hex_string(strings.get(1)?).ok()?,
+                recommended_relay_url: get(2).map(UncheckedUrl),
+                marker: get(3),
+                pubkey: match get(4) {
+                    Some(pk) => Some(PublicKeyHex::try_from_string(pk).ok()?),
+                    None => None,
+                },
+            },
+            "expiration" => Tag::Expiration(Unixtime(strings.get(1)?.parse::<i64>().ok()?)),
+            "p" => Tag::Pubkey {
+                pubkey: PublicKeyHex::try_from_string(get(1)?).ok()?,
+                recommended_relay_url: get(2).map(UncheckedUrl),
+                petname: get(3),
+            },
+            "t" => Tag::Hashtag(get(1)?),
+            "r" => Tag::Reference {
+                url: UncheckedUrl(get(1)?),
+                marker: get(2),
+            },
+            "g" => Tag::Geohash(get(1)?),
+            "d" => Tag::Identifier(get(1)?),
+            "subject" => Tag::Subject(get(1)?),
+            "nonce" => Tag::Nonce {
+                nonce: get(1)?,
+                target: get(2),
+            },
+            "parameter" => Tag::Parameter(get(1)?),
+            "title" => Tag::Title(get(1)?),
+            "summary" => Tag::Summary(get(1)?),
+            "image" => Tag::Image {
+                url: UncheckedUrl(get(1)?),
+                dimensions: get(2),
+            },
+            "published_at" => Tag::PublishedAt(Unixtime(strings.get(1)?.parse::<i64>().ok()?)),
+            "client" => Tag::Client {
+                name: get(1)?,
+                address: get(2),
+                relay_url: get(3).map(UncheckedUrl),
+            },
+            "-" => Tag::Protected,
+            "alt" => Tag::Alt(get(1)?),
+            "relay" => Tag::Relay(UncheckedUrl(get(1)?)),
+            "a" => {
+                let mut address = EventAddr::try_from_address(strings.get(1)?).ok()?;
+                if !address.kind.is_addressable() {
+                    return None;
+                }
+                address.relays.extend(get(2).map(UncheckedUrl));
+                Tag::Address {
+                    address,
+                    marker: get(3),
+                }
             }
+            "k" => Tag::KindReference(EventKind::from(strings.get(1)?.parse::<u64>().ok()?)),
+            "proxy" => Tag::Proxy {
+                id: get(1)?,
+                protocol: ProxyProtocol::from_str(strings.get(2)?),
+            },
+            _ => return None,
+        };
+        Some(tag)
+    }
+}
+
+impl Serialize for Tag {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let strings = self.to_strings();
+        let mut seq = serializer.serialize_seq(Some(strings.len()))?;
+        for s in strings.iter() {
+            seq.serialize_element(s)?;
         }
+        seq.end()
     }
 }
 
@@ -282,185 +532,11 @@ impl<'de> Visitor<'de> for TagVisitor {
     where
         A: SeqAccess<'de>,
     {
-        let tagname: &str = match seq.next_element()? {
-            Some(e) => e,
-            None => return Ok(Tag::Empty),
-        };
-        if tagname == "content-warning" {
-            let msg = match seq.next_element()? {
-                Some(s) => s,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![],
-                    });
-                }
-            };
-            Ok(Tag::ContentWarning(msg))
-        } else if tagname == "delegation" {
-            let pubkey: PublicKeyHex = match seq.next_element()? {
-                Some(pk) => pk,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![],
-                    });
-                }
-            };
-            let conditions: DelegationConditions = match seq.next_element()? {
-                Some(c) => c,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![pubkey.into_string()],
-                    });
-                }
-            };
-            let sig: SignatureHex = match seq.next_element()? {
-                Some(s) => s,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![pubkey.into_string(), conditions.as_string()],
-                    });
-                }
-            };
-            Ok(Tag::Delegation {
-                pubkey,
-                conditions,
-                sig,
-            })
-        } else if tagname == "e" {
-            let id: Id = match seq.next_element()? {
-                Some(id) => id,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![],
-                    });
-                }
-            };
-            let recommended_relay_url: Option<UncheckedUrl> = seq.next_element()?;
-            let marker: Option<String> = seq.next_element()?;
-            Ok(Tag::Event {
-                id,
-                recommended_relay_url,
-                marker,
-            })
-        } else if tagname == "expiration" {
-            let time = match seq.next_element()? {
-                Some(t) => t,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![],
-                    });
-                }
-            };
-            Ok(Tag::Expiration(time))
-        } else if tagname == "p" {
-            let pubkey: PublicKeyHex = match seq.next_element()? {
-                Some(p) => p,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![],
-                    });
-                }
-            };
-            let recommended_relay_url: Option<UncheckedUrl> = seq.next_element()?;
-            let petname: Option<String> = seq.next_element()?;
-            Ok(Tag::Pubkey {
-                pubkey,
-                recommended_relay_url,
-                petname,
-            })
-        } else if tagname == "t" {
-            let tag = match seq.next_element()? {
-                Some(t) => t,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![],
-                    });
-                }
-            };
-            Ok(Tag::Hashtag(tag))
-        } else if tagname == "r" {
-            let refr: UncheckedUrl = match seq.next_element()? {
-                Some(r) => r,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![],
-                    });
-                }
-            };
-            let marker: Option<String> = seq.next_element()?;
-            Ok(Tag::Reference { url: refr, marker })
-        } else if tagname == "g" {
-            let geo = match seq.next_element()? {
-                Some(g) => g,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![],
-                    });
-                }
-            };
-            Ok(Tag::Geohash(geo))
-        } else if tagname == "d" {
-            let id = match seq.next_element()? {
-                Some(id) => id,
-                None => {
-                    // Implicit empty value
-                    return Ok(Tag::Identifier("".to_string()));
-                }
-            };
-            Ok(Tag::Identifier(id))
-        } else if tagname == "subject" {
-            let sub = match seq.next_element()? {
-                Some(s) => s,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![],
-                    });
-                }
-            };
-            Ok(Tag::Subject(sub))
-        } else if tagname == "nonce" {
-            let nonce = match seq.next_element()? {
-                Some(n) => n,
-                None => {
-                    return Ok(Tag::Other {
-                        tag: tagname.to_string(),
-                        data: vec![],
-                    });
-                }
-            };
-            let target: Option<String> = seq.next_element()?;
-            Ok(Tag::Nonce { nonce, target })
-        } else if tagname == "parameter" {
-            let param = match seq.next_element()? {
-                Some(s) => s,
-                None => "".to_owned(), // implicit parameter
-            };
-            Ok(Tag::Parameter(param))
-        } else {
-            let mut data = Vec::new();
-            loop {
-                match seq.next_element()? {
-                    None => {
-                        return Ok(Tag::Other {
-                            tag: tagname.to_string(),
-                            data,
-                        })
-                    }
-                    Some(s) => data.push(s),
-                }
-            }
+        let mut strings: Vec<String> = Vec::new();
+        while let Some(s) = seq.next_element()? {
+            strings.push(s);
         }
+        Ok(Tag::from_strings(strings))
     }
 }
 
@@ -469,4 +545,149 @@ mod test {
     use super::*;
 
     test_serde! {Tag, test_tag_serde}
+
+    #[test]
+    fn test_weird_tags_round_trip() {
+        let samples = [
+            r#"[]"#,
+            r#"[""]"#,
+            r#"["d"]"#,
+            r#"["parameter"]"#,
+            r#"["t"]"#,
+            r#"["t","nostr","extra"]"#,
+            r#"["content-warning"]"#,
+            r#"["content-warning","nsfw","extra"]"#,
+            r#"["e","5df64b33303d62afc799bdc36d178c07b2e1f0d824f31b7dc812219440affab6"]"#,
+            r#"["e","5df64b33303d62afc799bdc36d178c07b2e1f0d824f31b7dc812219440affab6",""]"#,
+            r#"["e","5DF64B33303D62AFC799BDC36D178C07B2E1F0D824F31B7DC812219440AFFAB6"]"#,
+            r#"["e","not-an-id","wss://relay.example.com","reply"]"#,
+            r#"["p","221115830ced1ca94352002485fcc7a75dcfe30d1b07f5f6fbe9c0407cfa59a1","","",""]"#,
+            r#"["p","npub1sn0wdenkukak0d9dfczzeacvhkrgz92ak56egt7vdgzn8pv2wfqqhrjdv9"]"#,
+            r#"["expiration","1600000000"]"#,
+            r#"["expiration","soon"]"#,
+            r#"["expiration","01600000000"]"#,
+            r#"["delegation","8e0d3d3eb2881ec137a11debe736a9086715a8c8beeeda615780064d68bc25dd","kind=1&created_at>1674834236&created_at<1677426236","6f44d7fe4f1c09f3954640fb58bd12bae8bb8ff4120853c4693106c82e920e2b898f1f9ba9bd65449a987c39c0423426ab7b53910c0c6abfb41b356bc26ac0"]"#,
+            r#"["delegation","8e0d3d3eb2881ec137a11debe736a9086715a8c8beeeda615780064d68bc25dd","created_at<1677426236&kind=1","sig"]"#,
+            r#"["r","wss://relay.example.com","read","extra"]"#,
+            r#"["nonce","776797","20"]"#,
+            r#"["-"]"#,
+            r#"["-","extra"]"#,
+            r#"["title","A title"]"#,
+            r#"["image","https://example.com/a.png","256x256"]"#,
+            r#"["published_at","1296962229"]"#,
+            r#"["published_at",""]"#,
+            r#"["client","gossip"]"#,
+            r#"["client","","31990:c6ad1d0e24e8b8f2e5cd07b2a36e7f7c8e4fd3e2dae4d4a0d9c1a1b2c3d4e5f6:1686066542546","wss://relay.example.com"]"#,
+            r#"["alt","A short note"]"#,
+            r#"["a","30023:b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9:my-article"]"#,
+            r#"["a","30023:b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9:","","fork"]"#,
+            r#"["a","10002:b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9:"]"#,
+            r#"["a","30023:B0635D6A9851D3AED0CD6C495B282167ACF761729078D975FC341B22650B07B9:x"]"#,
+            r#"["k","1"]"#,
+            r#"["k","01"]"#,
+            r#"["k","note"]"#,
+            r#"["proxy","https://gleasonator.com/objects/8f6fac53-4f66-4c6e-ac7d-92e5e78c3e79","activitypub"]"#,
+            r#"["proxy","at://did:plc:zhbjlbmir5dganqhueg7y4i3/app.bsky.feed.post/3jt5hlibeol2i","atproto"]"#,
+            r#"["proxy","https://example.com/1","gopher"]"#,
+            r#"["proxy","https://example.com/1"]"#,
+            r#"["proxy","https://example.com/1","web","extra"]"#,
+            r#"["emoji","soapbox","https://example.com/soapbox.png"]"#,
+            r#"["imeta","url https://example.com/a.jpg","m image/jpeg","dim 3024x4032"]"#,
+        ];
+        for sample in samples.iter() {
+            let tag: Tag = serde_json::from_str(sample).unwrap();
+            let reserialized = serde_json::to_string(&tag).unwrap();
+            assert_eq!(&reserialized, sample);
+        }
+    }
+
+    #[test]
+    fn test_event_tag_pubkey_hint() {
+        let wire = r#"["e","5df64b33303d62afc799bdc36d178c07b2e1f0d824f31b7dc812219440affab6","","root","221115830ced1ca94352002485fcc7a75dcfe30d1b07f5f6fbe9c0407cfa59a1"]"#;
+        let tag: Tag = serde_json::from_str(wire).unwrap();
+        if let Tag::Event {
+            recommended_relay_url,
+            marker,
+            pubkey,
+            ..
+        } = &tag
+        {
+            assert_eq!(recommended_relay_url, &Some(UncheckedUrl("".to_owned())));
+            assert_eq!(marker.as_deref(), Some("root"));
+            assert_eq!(
+                pubkey.as_ref().map(|pk| pk.as_str()),
+                Some("221115830ced1ca94352002485fcc7a75dcfe30d1b07f5f6fbe9c0407cfa59a1")
+            );
+        } else {
+            panic!("Expected an Event tag, got {tag:?}");
+        }
+        assert_eq!(serde_json::to_string(&tag).unwrap(), wire);
+
+        // A pubkey hint without a relay or marker still fills in the gaps
+        let tag = Tag::Event {
+            id: Id::mock(),
+            recommended_relay_url: None,
+            marker: None,
+            pubkey: Some(PublicKeyHex::mock()),
+        };
+        let strings = tag.to_strings();
+        assert_eq!(strings.len(), 5);
+        assert_eq!(strings[2], "");
+        assert_eq!(strings[3], "");
+        assert_eq!(Tag::from_strings(strings.clone()).to_strings(), strings);
+    }
+
+    #[test]
+    fn test_tags_parse_typed() {
+        let tag: Tag = serde_json::from_str(r#"["expiration","1600000000"]"#).unwrap();
+        assert_eq!(tag, Tag::Expiration(Unixtime(1600000000)));
+
+        let tag: Tag = serde_json::from_str(r#"["t","nostr","extra"]"#).unwrap();
+        assert_eq!(
+            tag,
+            Tag::Other {
+                tag: "t".to_owned(),
+                data: vec!["nostr".to_owned(), "extra".to_owned()]
+            }
+        );
+
+        let tag: Tag = serde_json::from_str(r#"["-"]"#).unwrap();
+        assert_eq!(tag, Tag::Protected);
+
+        let tag: Tag = serde_json::from_str(r#"["published_at","1296962229"]"#).unwrap();
+        assert_eq!(tag, Tag::PublishedAt(Unixtime(1296962229)));
+
+        let tag: Tag = serde_json::from_str(r#"["d",""]"#).unwrap();
+        assert_eq!(tag, Tag::Identifier("".to_owned()));
+
+        let wire = r#"["a","30023:b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9:my-article","wss://relay.example.com"]"#;
+        let tag: Tag = serde_json::from_str(wire).unwrap();
+        assert_eq!(
+            tag,
+            Tag::Address {
+                address: EventAddr::mock(),
+                marker: None
+            }
+        );
+
+        // Replaceable but not addressable kinds are left alone
+        let tag: Tag = serde_json::from_str(
+            r#"["a","10002:b0635d6a9851d3aed0cd6c495b282167acf761729078d975fc341b22650b07b9:"]"#,
+        )
+        .unwrap();
+        assert!(matches!(tag, Tag::Other { .. }));
+
+        let tag: Tag = serde_json::from_str(r#"["k","30023"]"#).unwrap();
+        assert_eq!(tag, Tag::KindReference(EventKind::LongFormContent));
+
+        let tag: Tag =
+            serde_json::from_str(r#"["proxy","https://example.com/feed#item1","rss"]"#).unwrap();
+        assert_eq!(
+            tag,
+            Tag::Proxy {
+                id: "https://example.com/feed#item1".to_owned(),
+                protocol: ProxyProtocol::Rss
+            }
+        );
+    }
 }
@@ -0,0 +1,302 @@
+use super::{Event, Id};
+use std::collections::{HashMap, HashSet};
+
+/// An event within a `ThreadTree`, in display order
+#[derive(Clone, Copy, Debug)]
+pub struct ThreadNode<'a> {
+    /// The event
+    pub event: &'a Event,
+
+    /// How deep in the tree the event is. Direct replies to the root are at 1.
+    pub depth: usize,
+
+    /// The event replies to an event we do not have, so it was placed directly
+    /// under the root
+    pub missing_parent: bool,
+}
+
+/// The reply tree of a thread (NIP-10), assembled from events in any order.
+///
+/// Events are added as they arrive, and the tree is worked out when it is
+/// iterated. Replies whose parent has not been seen yet are shown directly under
+/// the root if they name this root, and `missing()` lists the events worth
+/// fetching to fill the gaps.
+#[derive(Clone, Debug)]
+pub struct ThreadTree {
+    root: Id,
+    events: HashMap<Id, Event>,
+}
+
+impl ThreadTree {
+    /// Create an empty tree for the thread starting at `root`
+    pub fn new(root: Id) -> ThreadTree {
+        ThreadTree {
+            root,
+            events: HashMap::new(),
+        }
+    }
+
+    /// The id of the root event
+    pub fn root_id(&self) -> Id {
+        self.root
+    }
+
+    /// The root event, if we have it
+    pub fn root(&self) -> Option<&Event> {
+        self.events.get(&self.root)
+    }
+
+    /// Get an event in the tree
+    pub fn get(&self, id: Id) -> Option<&Event> {
+        self.events.get(&id)
+    }
+
+    /// Add an event. Returns false if it could not be part of a thread (it is
+    /// not the root and does not reply to anything) or was already present.
+    ///
+    /// Events that reply to other threads are kept, since it is not known until
+    /// their parents arrive where they lead, but they are never iterated.
+    pub fn insert(&mut self, event: Event) -> bool {
+        if event.id != self.root && parent_id(&event).is_none() {
+            return false;
+        }
+        if self.events.contains_key(&event.id) {
+            return false;
+        }
+        let _ = self.events.insert(event.id, event);
+        true
+    }
+
+    /// The ids of the events the thread refers to but we do not have, including
+    /// the root
+    pub fn missing(&self) -> Vec<Id> {
+        let mut missing: Vec<Id> = Vec::new();
+        if !self.events.contains_key(&self.root) {
+            missing.push(self.root);
+        }
+        for node in self.iter() {
+            if node.missing_parent {
+                if let Some(parent) = parent_id(node.event) {
+                    if !missing.contains(&parent) {
+                        missing.push(parent);
+                    }
+                }
+            }
+        }
+        missing
+    }
+
+    /// The replies directly under an event, oldest first
+    pub fn children(&self, id: Id) -> Vec<&Event> {
+        self.iter()
+            .filter(|node| self.parent_in_tree(node) == Some(id))
+            .map(|node| node.event)
+            .collect()
+    }
+
+    /// Iterate over the thread in display order: depth first, with replies
+    /// oldest first. The root comes first, if we have it.
+    pub fn iter(&self) -> std::vec::IntoIter<ThreadNode<'_>> {
+        // Replies under each parent that we have (or the root)
+        let mut children: HashMap<Id, Vec<&Event>> = HashMap::new();
+        let mut orphans: Vec<&Event> = Vec::new();
+        for event in self.events.values() {
+            if event.id == self.root {
+                continue;
+            }
+            let parent = match parent_id(event) {
+                Some(parent) => parent,
+                None => continue,
+            };
+            if parent == self.root || self.events.contains_key(&parent) {
+                children.entry(parent).or_default().push(event);
+            } else if event.thread_root().and_then(|r| r.id()) == Some(self.root) {
+                orphans.push(event);
+            }
+        }
+        for list in children.values_mut() {
+            sort_replies(list);
+        }
+        sort_replies(&mut orphans);
+
+        let mut nodes: Vec<ThreadNode<'_>> = Vec::new();
+        let mut visited: HashSet<Id> = HashSet::new();
+        if let Some(root) = self.root() {
+            nodes.push(ThreadNode {
+                event: root,
+                depth: 0,
+                missing_parent: false,
+            });
+        }
+        let _ = visited.insert(self.root);
+
+        // Orphans go after the replies that are properly attached to the root
+        let mut stack: Vec<(&Event, usize, bool)> = Vec::new();
+        for orphan in orphans.iter().rev() {
+            stack.push((*orphan, 1, true));
+        }
+        if let Some(replies) = children.get(&self.root) {
+            for reply in replies.iter().rev() {
+                stack.push((*reply, 1, false));
+            }
+        }
+
+        while let Some((event, depth, missing_parent)) = stack.pop() {
+            // Events can claim to reply to each other, so guard against cycles
+            if !visited.insert(event.id) {
+                continue;
+            }
+            nodes.push(ThreadNode {
+                event,
+                depth,
+                missing_parent,
+            });
+            if let Some(replies) = children.get(&event.id) {
+                for reply in replies.iter().rev() {
+                    stack.push((*reply, depth + 1, false));
+                }
+            }
+        }
+
+        nodes.into_iter()
+    }
+
+    /// The number of events in the thread, as iterated
+    pub fn len(&self) -> usize {
+        self.iter().len()
+    }
+
+    /// Are there no events in the thread?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn parent_in_tree(&self, node: &ThreadNode<'_>) -> Option<Id> {
+        if node.depth == 0 {
+            None
+        } else if node.missing_parent {
+            Some(self.root)
+        } else {
+            parent_id(node.event)
+        }
+    }
+}
+
+// The id of the event this replies to. Replies to addresses are not part of a
+// thread of ids.
+fn parent_id(event: &Event) -> Option<Id> {
+    event.reply_target().and_then(|r| r.id())
+}
+
+fn sort_replies(replies: &mut [&Event]) {
+    replies.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EventKind, PreEvent, PrivateKey, Tag, Unixtime};
+
+    fn note(private_key: &PrivateKey, created_at: i64, e_tags: Vec<(Id, Option<&str>)>) -> Event {
+        let tags = e_tags
+            .into_iter()
+            .map(|(id, marker)| Tag::Event {
+                id,
+                recommended_relay_url: None,
+                marker: marker.map(|m| m.to_owned()),
+                pubkey: None,
+            })
+            .collect();
+        let pre = PreEvent {
+            pubkey: private_key.public_key(),
+            created_at: Unixtime(created_at),
+            kind: EventKind::TextNote,
+            tags,
+            content: format!("note at {created_at}"),
+            ots: None,
+        };
+        Event::new(pre, private_key).unwrap()
+    }
+
+    #[test]
+    fn test_thread_tree() {
+        let private_key = PrivateKey::generate();
+        let root = note(&private_key, 100, vec![]);
+        // Marked reply to the root, using only the 'root' marker
+        let a = note(&private_key, 110, vec![(root.id, Some("root"))]);
+        // Deprecated positional tags: first is root, last is the reply
+        let b = note(&private_key, 120, vec![(root.id, None), (a.id, None)]);
+        // Single positional tag replies to the root
+        let c = note(&private_key, 105, vec![(root.id, None)]);
+        // Replies to an event we never receive
+        let lost = note(&private_key, 111, vec![(root.id, Some("root"))]);
+        let d = note(
+            &private_key,
+            130,
+            vec![(root.id, Some("root")), (lost.id, Some("reply"))],
+        );
+        // Belongs to some other thread
+        let other = note(&private_key, 140, vec![(Id::mock(), None)]);
+        // Not a reply at all
+        let unrelated = note(&private_key, 150, vec![]);
+
+        let mut tree = ThreadTree::new(root.id);
+        // Out of order arrival
+        for event in [&d, &b, &other, &a, &c, &root] {
+            assert!(tree.insert(event.clone()));
+        }
+        assert!(!tree.insert(unrelated));
+        assert!(!tree.insert(a.clone()));
+
+        let order: Vec<(Id, usize, bool)> = tree
+            .iter()
+            .map(|n| (n.event.id, n.depth, n.missing_parent))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                (root.id, 0, false),
+                (c.id, 1, false),
+                (a.id, 1, false),
+                (b.id, 2, false),
+                (d.id, 1, true),
+            ]
+        );
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.missing(), vec![lost.id]);
+        let children: Vec<Id> = tree.children(root.id).iter().map(|e| e.id).collect();
+        assert_eq!(children, vec![c.id, a.id, d.id]);
+
+        // When the missing parent arrives, the orphan moves under it
+        assert!(tree.insert(lost.clone()));
+        let order: Vec<(Id, usize)> = tree.iter().map(|n| (n.event.id, n.depth)).collect();
+        assert_eq!(
+            order,
+            vec![
+                (root.id, 0),
+                (c.id, 1),
+                (a.id, 1),
+                (b.id, 2),
+                (lost.id, 1),
+                (d.id, 2),
+            ]
+        );
+        assert!(tree.missing().is_empty());
+    }
+
+    #[test]
+    fn test_thread_tree_without_root() {
+        let private_key = PrivateKey::generate();
+        let root = note(&private_key, 100, vec![]);
+        let a = note(&private_key, 110, vec![(root.id, Some("root"))]);
+
+        let mut tree = ThreadTree::new(root.id);
+        assert!(tree.is_empty());
+        assert!(tree.insert(a.clone()));
+        assert_eq!(tree.missing(), vec![root.id]);
+        let nodes: Vec<ThreadNode<'_>> = tree.iter().collect();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].event.id, a.id);
+        assert_eq!(nodes[0].depth, 1);
+    }
+}
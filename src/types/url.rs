@@ -1,9 +1,10 @@
 use crate::error::Error;
+use bech32::{FromBase32, ToBase32};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
 /// A string that is supposed to represent a URL but which might be invalid
-#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, PartialOrd, Serialize, Ord)]
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, PartialOrd, Serialize, Ord)]
 pub struct UncheckedUrl(pub String);
 
 impl fmt::Display for UncheckedUrl {
@@ -31,6 +32,46 @@ impl UncheckedUrl {
         &self.0
     }
 
+    /// Export as a bech32 encoded string ("nrelay"). This fails for urls
+    /// longer than 255 bytes, which the format can't hold.
+    pub fn as_bech32_string(&self) -> Result<String, Error> {
+        let len = u8::try_from(self.0.len()).map_err(|_| Error::TooLarge {
+            size: self.0.len(),
+            max: u8::MAX as usize,
+        })?;
+        let mut tlv: Vec<u8> = Vec::new();
+
+        // Push the relay url
+        tlv.push(0);
+        tlv.push(len);
+        tlv.extend(self.0.as_bytes());
+
+        Ok(bech32::encode("nrelay", tlv.to_base32(), bech32::Variant::Bech32).unwrap())
+    }
+
+    /// Import from a bech32 encoded string ("nrelay")
+    pub fn try_from_bech32_string(s: &str) -> Result<UncheckedUrl, Error> {
+        let data = crate::error::bech32_decode_as(s, "nrelay", None)?;
+        let tlv = Vec::<u8>::from_base32(&data)?;
+
+        let mut url: Option<UncheckedUrl> = None;
+        let mut pos = 0;
+        while tlv.len() >= pos + 2 {
+            let typ = tlv[pos];
+            let len = tlv[pos + 1] as usize;
+            pos += 2;
+            let value = tlv
+                .get(pos..pos + len)
+                .ok_or_else(|| Error::Url("truncated nrelay".to_owned()))?;
+            // Unknown types are ignored, as NIP-19 asks
+            if typ == 0 && url.is_none() {
+                url = Some(UncheckedUrl::from_str(std::str::from_utf8(value)?));
+            }
+            pos += len;
+        }
+        url.ok_or_else(|| Error::Url("nrelay without a url".to_owned()))
+    }
+
     // Mock data for testing
     #[allow(dead_code)]
     pub(crate) fn mock() -> UncheckedUrl {
@@ -197,6 +238,35 @@ mod test {
 
     test_serde! {UncheckedUrl, test_unchecked_url_serde}
 
+    #[test]
+    fn test_unchecked_url_bech32() {
+        let url = UncheckedUrl::from_str("wss://relay.example.com");
+        let bech32 = url.as_bech32_string().unwrap();
+        assert!(bech32.starts_with("nrelay1"));
+        assert_eq!(UncheckedUrl::try_from_bech32_string(&bech32).unwrap(), url);
+        assert!(
+            UncheckedUrl::try_from_bech32_string(&crate::Profile::mock().as_bech32_string())
+                .is_err()
+        );
+
+        // Longer urls can't be held, rather than being cut short
+        let long = UncheckedUrl(format!("wss://{}.com/", "a".repeat(250)));
+        assert!(matches!(
+            long.as_bech32_string(),
+            Err(Error::TooLarge { size: 261, .. })
+        ));
+        let truncated = bech32::encode(
+            "nrelay",
+            [0u8, 10, b'w'].to_base32(),
+            bech32::Variant::Bech32,
+        )
+        .unwrap();
+        assert!(matches!(
+            UncheckedUrl::try_from_bech32_string(&truncated),
+            Err(Error::Url(_))
+        ));
+    }
+
     #[test]
     fn test_url_case() {
         let url = Url::try_from_str("Wss://MyRelay.example.COM/PATH?Query").unwrap();
@@ -0,0 +1,165 @@
+use super::{Event, EventKind, PreEvent, PrivateKey, RelayUrl, Tag, UncheckedUrl, Unixtime};
+use crate::Error;
+
+/// The relays that a request to vanish is addressed to
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VanishTarget {
+    /// Every relay that sees the request
+    AllRelays,
+
+    /// Only these specific relays
+    Relays(Vec<UncheckedUrl>),
+}
+
+/// A request to vanish (NIP-62), kind 62.
+///
+/// This asks relays to delete everything from the author up to the time of the
+/// request, and to refuse to accept those events again.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VanishRequest {
+    /// The relays this request is addressed to
+    pub target: VanishTarget,
+
+    /// An optional reason or legal notice for the relay operator
+    pub reason: String,
+}
+
+impl VanishRequest {
+    /// The value of the 'relay' tag that addresses every relay
+    pub const ALL_RELAYS: &'static str = "ALL_RELAYS";
+
+    /// Create and sign a kind 62 event for this request
+    pub fn to_event(&self, privkey: &PrivateKey) -> Result<Event, Error> {
+        let tags: Vec<Tag> = match &self.target {
+            VanishTarget::AllRelays => vec![Tag::Relay(UncheckedUrl::from_str(Self::ALL_RELAYS))],
+            VanishTarget::Relays(relays) => relays.iter().cloned().map(Tag::Relay).collect(),
+        };
+
+        let pre_event = PreEvent {
+            pubkey: privkey.public_key(),
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::RequestToVanish,
+            tags,
+            content: self.reason.clone(),
+            ots: None,
+        };
+
+        Event::new(pre_event, privkey)
+    }
+
+    /// Interpret a kind 62 event as a request to vanish
+    pub fn try_from_event(event: &Event) -> Result<VanishRequest, Error> {
+        if event.kind != EventKind::RequestToVanish {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut relays: Vec<UncheckedUrl> = Vec::new();
+        for tag in event.tags.iter() {
+            if let Tag::Relay(url) = tag {
+                if url.as_str() == Self::ALL_RELAYS {
+                    return Ok(VanishRequest {
+                        target: VanishTarget::AllRelays,
+                        reason: event.content.clone(),
+                    });
+                }
+                relays.push(url.clone());
+            }
+        }
+
+        if relays.is_empty() {
+            return Err(Error::AssertionFailed(
+                "Request to vanish has no relay tags".to_owned(),
+            ));
+        }
+
+        Ok(VanishRequest {
+            target: VanishTarget::Relays(relays),
+            reason: event.content.clone(),
+        })
+    }
+
+    /// For relays: is this request addressed to the given relay?
+    pub fn targets(&self, relay: &RelayUrl) -> bool {
+        match &self.target {
+            VanishTarget::AllRelays => true,
+            VanishTarget::Relays(relays) => relays.iter().any(|u| {
+                RelayUrl::try_from_unchecked_url(u)
+                    .map(|r| r == *relay)
+                    .unwrap_or(false)
+            }),
+        }
+    }
+
+    /// For relays: should `event` be deleted (and refused in future) because of the
+    /// vanish `request` event? This covers everything the requester authored up to
+    /// the time of the request, plus any gift wraps addressed to them.
+    pub fn covers(request: &Event, event: &Event) -> bool {
+        if request.kind != EventKind::RequestToVanish {
+            return false;
+        }
+        if event.pubkey == request.pubkey {
+            return event.created_at <= request.created_at;
+        }
+        if event.kind == EventKind::GiftWrap {
+            let requester: String = request.pubkey.as_hex_string();
+            return event.tags.iter().any(|t| match t {
+                Tag::Pubkey { pubkey, .. } => pubkey.as_str() == requester,
+                _ => false,
+            });
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vanish_request_all_relays() {
+        let privkey = PrivateKey::mock();
+        let request = VanishRequest {
+            target: VanishTarget::AllRelays,
+            reason: "Goodbye".to_owned(),
+        };
+        let event = request.to_event(&privkey).unwrap();
+        assert!(event.verify(None).is_ok());
+        assert_eq!(
+            serde_json::to_string(&event.tags).unwrap(),
+            r#"[["relay","ALL_RELAYS"]]"#
+        );
+
+        let parsed = VanishRequest::try_from_event(&event).unwrap();
+        assert_eq!(parsed, request);
+        assert!(parsed.targets(&RelayUrl::try_from_str("wss://relay.example.com").unwrap()));
+    }
+
+    #[test]
+    fn test_vanish_request_specific_relay() {
+        let privkey = PrivateKey::mock();
+        let request = VanishRequest {
+            target: VanishTarget::Relays(vec![UncheckedUrl::from_str("wss://Relay.Example.com")]),
+            reason: "".to_owned(),
+        };
+        let request_event = request.to_event(&privkey).unwrap();
+        let parsed = VanishRequest::try_from_event(&request_event).unwrap();
+        assert!(parsed.targets(&RelayUrl::try_from_str("wss://relay.example.com/").unwrap()));
+        assert!(!parsed.targets(&RelayUrl::try_from_str("wss://other.example.com").unwrap()));
+
+        let old_note = Event::new(
+            PreEvent {
+                pubkey: privkey.public_key(),
+                created_at: Unixtime(request_event.created_at.0 - 100),
+                kind: EventKind::TextNote,
+                tags: vec![],
+                content: "".to_owned(),
+                ots: None,
+            },
+            &privkey,
+        )
+        .unwrap();
+        assert!(VanishRequest::covers(&request_event, &old_note));
+        assert!(!VanishRequest::covers(&request_event, &Event::mock()));
+        assert!(VanishRequest::try_from_event(&old_note).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use super::Event;
+use crate::Error;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The order a `VerifierPool` gives back events in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerifyOrder {
+    /// The order they were submitted in
+    #[default]
+    Submitted,
+
+    /// As soon as each is verified
+    Completed,
+}
+
+/// How a `VerifierPool` is set up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifierConfig {
+    /// How many threads verify
+    pub threads: usize,
+
+    /// How many events may wait to be verified before `submit()` blocks
+    pub queue: usize,
+
+    /// The order verified events are given back in
+    pub order: VerifyOrder,
+}
+
+impl Default for VerifierConfig {
+    fn default() -> VerifierConfig {
+        VerifierConfig {
+            threads: num_cpus::get(),
+            queue: 1024,
+            order: VerifyOrder::Submitted,
+        }
+    }
+}
+
+/// An event given back by a `VerifierPool`, with what it was submitted with
+#[derive(Debug)]
+pub struct VerifiedEvent<T> {
+    /// What the event was submitted with, such as the relay it came from
+    pub tag: T,
+
+    /// The event
+    pub event: Event,
+
+    /// Whether it verified
+    pub result: Result<(), Error>,
+}
+
+type Job<T> = (u64, T, Event);
+
+/// Threads that verify event signatures, so that a relay client can verify
+/// off its read loop.
+///
+/// Submit events as they are read, each with a tag (such as the relay or
+/// subscription it came from), and take them back with `recv()` or
+/// `try_recv()` once verified. `submit()` blocks while the queue is full.
+pub struct VerifierPool<T> {
+    input: Option<SyncSender<Job<T>>>,
+    output: Receiver<(u64, VerifiedEvent<T>)>,
+    workers: Vec<JoinHandle<()>>,
+    order: VerifyOrder,
+    submitted: u64,
+    emitted: u64,
+    held: BTreeMap<u64, VerifiedEvent<T>>,
+}
+
+impl<T> fmt::Debug for VerifierPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifierPool")
+            .field("threads", &self.workers.len())
+            .field("order", &self.order)
+            .field("pending", &self.pending())
+            .finish()
+    }
+}
+
+impl<T> VerifierPool<T> {
+    /// How many submitted events have not been given back yet
+    pub fn pending(&self) -> u64 {
+        self.submitted - self.emitted
+    }
+}
+
+impl<T: Send + 'static> VerifierPool<T> {
+    /// Start the threads
+    pub fn new(config: VerifierConfig) -> VerifierPool<T> {
+        let (input, jobs) = mpsc::sync_channel::<Job<T>>(config.queue);
+        let (results, output) = mpsc::channel();
+        let jobs = Arc::new(Mutex::new(jobs));
+
+        let workers = (0..config.threads.max(1))
+            .map(|_| {
+                let jobs = jobs.clone();
+                let results = results.clone();
+                thread::spawn(move || loop {
+                    // Hold the lock only while waiting for the next job
+                    let job = match jobs.lock() {
+                        Ok(jobs) => jobs.recv(),
+                        Err(_) => return,
+                    };
+                    let (sequence, tag, event) = match job {
+                        Ok(job) => job,
+                        Err(_) => return, // the pool was closed
+                    };
+                    let result = event.verify(None);
+                    let verified = VerifiedEvent { tag, event, result };
+                    if results.send((sequence, verified)).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+
+        VerifierPool {
+            input: Some(input),
+            output,
+            workers,
+            order: config.order,
+            submitted: 0,
+            emitted: 0,
+            held: BTreeMap::new(),
+        }
+    }
+
+    /// Queue an event to verify, blocking while the queue is full. Fails if
+    /// the pool was closed.
+    pub fn submit(&mut self, tag: T, event: Event) -> Result<(), Error> {
+        let input = self
+            .input
+            .as_ref()
+            .ok_or_else(|| Error::AssertionFailed("Verifier pool is closed".to_owned()))?;
+        input
+            .send((self.submitted, tag, event))
+            .map_err(|_| Error::AssertionFailed("Verifier threads have stopped".to_owned()))?;
+        self.submitted += 1;
+        Ok(())
+    }
+
+    /// The next verified event, waiting for it. `None` if nothing is pending.
+    pub fn recv(&mut self) -> Option<VerifiedEvent<T>> {
+        loop {
+            if let Some(verified) = self.take_ready() {
+                return Some(verified);
+            }
+            if self.pending() == 0 {
+                return None;
+            }
+            match self.output.recv() {
+                Ok((sequence, verified)) => {
+                    let _ = self.held.insert(sequence, verified);
+                }
+                Err(_) => return self.take_any(),
+            }
+        }
+    }
+
+    /// The next verified event if one is ready, without waiting
+    pub fn try_recv(&mut self) -> Option<VerifiedEvent<T>> {
+        loop {
+            if let Some(verified) = self.take_ready() {
+                return Some(verified);
+            }
+            match self.output.try_recv() {
+                Ok((sequence, verified)) => {
+                    let _ = self.held.insert(sequence, verified);
+                }
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => return self.take_any(),
+            }
+        }
+    }
+
+    /// The next verified event, waiting up to `timeout` for it
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Option<VerifiedEvent<T>> {
+        let deadline = std::time::Instant::now() + timeout;
+        loop {
+            if let Some(verified) = self.take_ready() {
+                return Some(verified);
+            }
+            if self.pending() == 0 {
+                return None;
+            }
+            let wait = deadline.saturating_duration_since(std::time::Instant::now());
+            match self.output.recv_timeout(wait) {
+                Ok((sequence, verified)) => {
+                    let _ = self.held.insert(sequence, verified);
+                }
+                Err(RecvTimeoutError::Timeout) => return None,
+                Err(RecvTimeoutError::Disconnected) => return self.take_any(),
+            }
+        }
+    }
+
+    /// Stop taking events. Those already submitted are still verified and
+    /// can be received.
+    pub fn close(&mut self) {
+        self.input = None;
+    }
+
+    // The next held event that may be given back in this order
+    fn take_ready(&mut self) -> Option<VerifiedEvent<T>> {
+        let sequence = match self.order {
+            VerifyOrder::Submitted => self.emitted,
+            VerifyOrder::Completed => *self.held.keys().next()?,
+        };
+        let verified = self.held.remove(&sequence)?;
+        self.emitted += 1;
+        Some(verified)
+    }
+
+    // Any held event, once the threads are gone and order cannot be kept
+    fn take_any(&mut self) -> Option<VerifiedEvent<T>> {
+        let sequence = *self.held.keys().next()?;
+        self.emitted += 1;
+        self.held.remove(&sequence)
+    }
+}
+
+impl<T> Drop for VerifierPool<T> {
+    fn drop(&mut self) {
+        self.input = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{EventKind, PreEvent, PrivateKey, Unixtime};
+
+    #[test]
+    fn test_verifier_pool() {
+        let key = PrivateKey::generate();
+        let events: Vec<Event> = (0..40)
+            .map(|n| {
+                let pre = PreEvent {
+                    pubkey: key.public_key(),
+                    created_at: Unixtime(1700000000 + n),
+                    kind: EventKind::TextNote,
+                    tags: vec![],
+                    content: format!("note {n}"),
+                    ots: None,
+                };
+                let mut event = Event::new(pre, &key).unwrap();
+                if n % 10 == 3 {
+                    event.content = "tampered".to_owned();
+                }
+                event
+            })
+            .collect();
+
+        let mut pool = VerifierPool::new(VerifierConfig {
+            threads: 4,
+            queue: 8,
+            order: VerifyOrder::Submitted,
+        });
+        assert!(pool.recv().is_none());
+        for (n, event) in events.iter().enumerate().take(8) {
+            pool.submit(n, event.clone()).unwrap();
+        }
+        assert_eq!(pool.pending(), 8);
+        let first = pool.recv().unwrap();
+        assert_eq!(first.tag, 0);
+        assert_eq!(first.event, events[0]);
+
+        // Keep submitting while receiving, as a read loop would
+        let mut received = vec![first];
+        for (n, event) in events.iter().enumerate().skip(8) {
+            pool.submit(n, event.clone()).unwrap();
+            received.extend(pool.try_recv());
+        }
+        pool.close();
+        assert!(pool.submit(99, events[0].clone()).is_err());
+        while let Some(verified) = pool.recv() {
+            received.push(verified);
+        }
+        let tags: Vec<usize> = received.iter().map(|v| v.tag).collect();
+        assert_eq!(tags, (0..40).collect::<Vec<usize>>());
+        let invalid: Vec<usize> = received
+            .iter()
+            .filter(|v| v.result.is_err())
+            .map(|v| v.tag)
+            .collect();
+        assert_eq!(invalid, vec![3, 13, 23, 33]);
+        assert_eq!(pool.pending(), 0);
+
+        let mut pool = VerifierPool::new(VerifierConfig {
+            order: VerifyOrder::Completed,
+            ..Default::default()
+        });
+        for event in &events {
+            pool.submit((), event.clone()).unwrap();
+        }
+        let mut valid = 0;
+        while let Some(verified) = pool.recv_timeout(Duration::from_secs(10)) {
+            if verified.result.is_ok() {
+                valid += 1;
+            }
+        }
+        assert_eq!(valid, 36);
+    }
+}
@@ -0,0 +1,196 @@
+use super::{
+    Event, EventKind, Imeta, ParsedTag, PreEvent, PublicKey, PublicKeyHex, Tag, UncheckedUrl,
+    Unixtime,
+};
+use crate::Error;
+
+/// A video event (NIP-71)
+///
+/// Normal videos are kind 21 and short-form portrait videos are kind 22. If an
+/// `identifier` is set, the addressable kinds 34235 and 34236 are used instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Video {
+    /// Whether this is a short-form portrait video
+    pub short: bool,
+
+    /// The 'd' identifier, for addressable video events
+    pub identifier: Option<String>,
+
+    /// The title of the video
+    pub title: String,
+
+    /// A summary or description of the video (the event content)
+    pub summary: String,
+
+    /// When the video was first published
+    pub published_at: Option<Unixtime>,
+
+    /// A short description for clients that do not support video events
+    pub alt: Option<String>,
+
+    /// The video duration in seconds
+    pub duration: Option<f64>,
+
+    /// The available variants of the video (resolutions, formats, fallbacks), one per
+    /// 'imeta' tag
+    pub variants: Vec<Imeta>,
+
+    /// People participating in the video, with an optional relay hint
+    pub participants: Vec<(PublicKeyHex, Option<UncheckedUrl>)>,
+
+    /// Hashtags
+    pub hashtags: Vec<String>,
+}
+
+impl Video {
+    /// The kind of event this video is published as
+    pub fn kind(&self) -> EventKind {
+        match (self.short, self.identifier.is_some()) {
+            (false, false) => EventKind::Video,
+            (true, false) => EventKind::ShortVideo,
+            (false, true) => EventKind::AddressableVideo,
+            (true, true) => EventKind::AddressableShortVideo,
+        }
+    }
+
+    /// Create a `PreEvent` for this video
+    pub fn to_pre_event(&self, pubkey: PublicKey) -> PreEvent {
+        let mut tags: Vec<Tag> = Vec::new();
+        if let Some(d) = &self.identifier {
+            tags.push(Tag::Identifier(d.clone()));
+        }
+        tags.push(Tag::Title(self.title.clone()));
+        if let Some(published_at) = self.published_at {
+            tags.push(Tag::PublishedAt(published_at));
+        }
+        if let Some(alt) = &self.alt {
+            tags.push(Tag::Alt(alt.clone()));
+        }
+        if let Some(duration) = self.duration {
+            tags.push(Tag::Other {
+                tag: "duration".to_owned(),
+                data: vec![format!("{duration}")],
+            });
+        }
+        for variant in self.variants.iter() {
+            tags.push(variant.to_tag());
+        }
+        for (pubkey, relay) in self.participants.iter() {
+            tags.push(Tag::Pubkey {
+                pubkey: pubkey.clone(),
+                recommended_relay_url: relay.clone(),
+                petname: None,
+            });
+        }
+        for hashtag in self.hashtags.iter() {
+            tags.push(Tag::Hashtag(hashtag.clone()));
+        }
+
+        PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: self.kind(),
+            tags,
+            content: self.summary.clone(),
+            ots: None,
+        }
+    }
+
+    /// Interpret an event as a video
+    pub fn try_from_event(event: &Event) -> Result<Video, Error> {
+        let (short, addressable) = match event.kind {
+            EventKind::Video => (false, false),
+            EventKind::ShortVideo => (true, false),
+            EventKind::AddressableVideo => (false, true),
+            EventKind::AddressableShortVideo => (true, true),
+            _ => return Err(Error::WrongEventKind),
+        };
+
+        let mut duration: Option<f64> = None;
+        let mut participants: Vec<(PublicKeyHex, Option<UncheckedUrl>)> = Vec::new();
+        for tag in event.tags.iter() {
+            match tag {
+                Tag::Other { tag, data } if tag == "duration" && duration.is_none() => {
+                    duration = data.first().and_then(|d| d.parse().ok());
+                }
+                Tag::Pubkey {
+                    pubkey,
+                    recommended_relay_url,
+                    ..
+                } => participants.push((pubkey.clone(), recommended_relay_url.clone())),
+                _ => {}
+            }
+        }
+
+        let variants: Vec<Imeta> = event.tags_of_type::<Imeta>();
+        if variants.is_empty() {
+            return Err(Error::AssertionFailed(
+                "Video event has no imeta tags".to_owned(),
+            ));
+        }
+        if duration.is_none() {
+            duration = variants.iter().find_map(|v| v.duration);
+        }
+
+        Ok(Video {
+            short,
+            identifier: if addressable { event.parameter() } else { None },
+            title: event.title().unwrap_or_default(),
+            summary: event.content.clone(),
+            published_at: event.published_at(),
+            alt: event.alt(),
+            duration,
+            variants,
+            participants,
+            hashtags: event
+                .tags
+                .iter()
+                .filter_map(|t| match t {
+                    Tag::Hashtag(h) => Some(h.clone()),
+                    _ => None,
+                })
+                .collect(),
+        })
+    }
+
+    // Mock data for testing
+    #[allow(dead_code)]
+    pub(crate) fn mock() -> Video {
+        Video {
+            short: false,
+            identifier: None,
+            title: "A video".to_owned(),
+            summary: "What this video is about".to_owned(),
+            published_at: Some(Unixtime::mock()),
+            alt: Some("A video about things".to_owned()),
+            duration: Some(29.5),
+            variants: vec![Imeta::mock()],
+            participants: vec![(PublicKeyHex::mock(), None)],
+            hashtags: vec!["video".to_owned()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn test_video_round_trip() {
+        let privkey = PrivateKey::mock();
+        let video = Video::mock();
+        let event = Event::new(video.to_pre_event(privkey.public_key()), &privkey).unwrap();
+        assert_eq!(event.kind, EventKind::Video);
+        assert_eq!(Video::try_from_event(&event).unwrap(), video);
+
+        let mut short = Video::mock();
+        short.short = true;
+        short.identifier = Some("my-short".to_owned());
+        let event = Event::new(short.to_pre_event(privkey.public_key()), &privkey).unwrap();
+        assert_eq!(event.kind, EventKind::AddressableShortVideo);
+        assert_eq!(Video::try_from_event(&event).unwrap(), short);
+
+        assert!(Video::try_from_event(&Event::mock()).is_err());
+    }
+}
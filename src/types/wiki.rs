@@ -0,0 +1,381 @@
+use super::{
+    Event, EventAddr, EventKind, Id, PreEvent, PublicKey, PublicKeyHex, Tag, UncheckedUrl, Unixtime,
+};
+use crate::Error;
+
+/// How a wiki article relates to another article it references
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WikiRelation {
+    /// This article was forked from the other
+    Fork,
+
+    /// This article defers to the other as a better version
+    Defer,
+}
+
+impl WikiRelation {
+    /// The marker string used in the tag
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            WikiRelation::Fork => "fork",
+            WikiRelation::Defer => "defer",
+        }
+    }
+
+    fn try_from_str(s: &str) -> Option<WikiRelation> {
+        match s {
+            "fork" => Some(WikiRelation::Fork),
+            "defer" => Some(WikiRelation::Defer),
+            _ => None,
+        }
+    }
+}
+
+/// A reference from one wiki article to another
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WikiReference {
+    /// An 'a' tag reference to an article address. Only its first relay is
+    /// kept in the tag.
+    Address(EventAddr),
+
+    /// An 'e' tag reference to a specific version of an article
+    Event {
+        /// The event id of that version
+        id: Id,
+
+        /// A relay where that version may be found
+        relay: Option<UncheckedUrl>,
+    },
+}
+
+/// A wiki article (NIP-54), kind 30818
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WikiArticle {
+    /// The normalized 'd' identifier, which is the topic of the article
+    pub identifier: String,
+
+    /// A title for display, which may keep the capitalization the identifier lost
+    pub title: Option<String>,
+
+    /// A summary of the article
+    pub summary: Option<String>,
+
+    /// The article content, in Asciidoc
+    pub content: String,
+
+    /// Forks and deferrals to other articles
+    pub relations: Vec<(WikiRelation, WikiReference)>,
+}
+
+impl WikiArticle {
+    /// Normalize a topic into a 'd' identifier: every letter is lowercased and
+    /// every non-letter is converted into a dash
+    pub fn normalize_identifier(topic: &str) -> String {
+        topic
+            .chars()
+            .flat_map(|c| {
+                if c.is_alphabetic() {
+                    c.to_lowercase().collect::<Vec<char>>()
+                } else {
+                    vec!['-']
+                }
+            })
+            .collect()
+    }
+
+    /// Create a new wiki article on a topic. The topic is normalized into the
+    /// identifier and kept as the title.
+    pub fn new(topic: &str, content: String) -> WikiArticle {
+        WikiArticle {
+            identifier: Self::normalize_identifier(topic),
+            title: Some(topic.to_owned()),
+            summary: None,
+            content,
+            relations: vec![],
+        }
+    }
+
+    /// Create a `PreEvent` for this article
+    pub fn to_pre_event(&self, pubkey: PublicKey) -> PreEvent {
+        let mut tags: Vec<Tag> = vec![Tag::Identifier(Self::normalize_identifier(
+            &self.identifier,
+        ))];
+        if let Some(title) = &self.title {
+            tags.push(Tag::Title(title.clone()));
+        }
+        if let Some(summary) = &self.summary {
+            tags.push(Tag::Summary(summary.clone()));
+        }
+        for (relation, reference) in self.relations.iter() {
+            tags.push(match reference {
+                WikiReference::Address(address) => Tag::Address {
+                    address: address.clone(),
+                    marker: Some(relation.as_str().to_owned()),
+                },
+                WikiReference::Event { id, relay } => Tag::Event {
+                    id: *id,
+                    recommended_relay_url: Some(relay.clone().unwrap_or_default()),
+                    marker: Some(relation.as_str().to_owned()),
+                    pubkey: None,
+                },
+            });
+        }
+
+        PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::WikiArticle,
+            tags,
+            content: self.content.clone(),
+            ots: None,
+        }
+    }
+
+    /// Interpret an event as a wiki article
+    pub fn try_from_event(event: &Event) -> Result<WikiArticle, Error> {
+        if event.kind != EventKind::WikiArticle {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut relations: Vec<(WikiRelation, WikiReference)> = Vec::new();
+        for tag in event.tags.iter() {
+            match tag {
+                Tag::Address {
+                    address,
+                    marker: Some(marker),
+                } => {
+                    if let Some(relation) = WikiRelation::try_from_str(marker) {
+                        relations.push((relation, WikiReference::Address(without_empty(address))));
+                    }
+                }
+                Tag::Event {
+                    id,
+                    recommended_relay_url,
+                    marker: Some(marker),
+                    ..
+                } => {
+                    if let Some(relation) = WikiRelation::try_from_str(marker) {
+                        relations.push((
+                            relation,
+                            WikiReference::Event {
+                                id: *id,
+                                relay: recommended_relay_url
+                                    .as_ref()
+                                    .and_then(|u| non_empty_url(&u.0)),
+                            },
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(WikiArticle {
+            identifier: Self::normalize_identifier(&event.parameter().unwrap_or_default()),
+            title: event.title(),
+            summary: event.summary(),
+            content: event.content.clone(),
+            relations,
+        })
+    }
+}
+
+/// A request to merge one wiki article version into another author's article
+/// (NIP-54), kind 818
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WikiMergeRequest {
+    /// The address of the article to merge into. Only its first relay is kept
+    /// in the tag.
+    pub destination: EventAddr,
+
+    /// The author of the destination article
+    pub destination_pubkey: PublicKeyHex,
+
+    /// The version of the destination article the changes are based on, if known
+    pub base_version: Option<Id>,
+
+    /// The article version to be merged
+    pub source: Id,
+
+    /// A relay where the source version may be found
+    pub source_relay: Option<UncheckedUrl>,
+
+    /// An optional explanation of the changes
+    pub message: String,
+}
+
+impl WikiMergeRequest {
+    /// Create a `PreEvent` for this merge request
+    pub fn to_pre_event(&self, pubkey: PublicKey) -> PreEvent {
+        let mut tags: Vec<Tag> = vec![
+            Tag::Address {
+                address: self.destination.clone(),
+                marker: None,
+            },
+            Tag::Pubkey {
+                pubkey: self.destination_pubkey.clone(),
+                recommended_relay_url: None,
+                petname: None,
+            },
+        ];
+        if let Some(base) = self.base_version {
+            tags.push(Tag::Event {
+                id: base,
+                recommended_relay_url: None,
+                marker: None,
+                pubkey: None,
+            });
+        }
+        tags.push(Tag::Event {
+            id: self.source,
+            recommended_relay_url: Some(self.source_relay.clone().unwrap_or_default()),
+            marker: Some("source".to_owned()),
+            pubkey: None,
+        });
+
+        PreEvent {
+            pubkey,
+            created_at: Unixtime::now().unwrap(),
+            kind: EventKind::WikiMergeRequest,
+            tags,
+            content: self.message.clone(),
+            ots: None,
+        }
+    }
+
+    /// Interpret an event as a wiki merge request
+    pub fn try_from_event(event: &Event) -> Result<WikiMergeRequest, Error> {
+        if event.kind != EventKind::WikiMergeRequest {
+            return Err(Error::WrongEventKind);
+        }
+
+        let mut destination: Option<EventAddr> = None;
+        let mut destination_pubkey: Option<PublicKeyHex> = None;
+        let mut base_version: Option<Id> = None;
+        let mut source: Option<(Id, Option<UncheckedUrl>)> = None;
+        for tag in event.tags.iter() {
+            match tag {
+                Tag::Address { address, .. } if destination.is_none() => {
+                    destination = Some(without_empty(address));
+                }
+                Tag::Pubkey { pubkey, .. } if destination_pubkey.is_none() => {
+                    destination_pubkey = Some(pubkey.clone());
+                }
+                Tag::Event {
+                    id,
+                    recommended_relay_url,
+                    marker,
+                    ..
+                } => {
+                    if marker.as_deref() == Some("source") {
+                        source = Some((
+                            *id,
+                            recommended_relay_url
+                                .as_ref()
+                                .and_then(|u| non_empty_url(&u.0)),
+                        ));
+                    } else if base_version.is_none() {
+                        base_version = Some(*id);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let missing = |what: &str| Error::AssertionFailed(format!("Merge request has no {what}"));
+        let destination = destination.ok_or_else(|| missing("'a' tag"))?;
+        let (source, source_relay) = source.ok_or_else(|| missing("source 'e' tag"))?;
+
+        Ok(WikiMergeRequest {
+            destination,
+            destination_pubkey: destination_pubkey.ok_or_else(|| missing("'p' tag"))?,
+            base_version,
+            source,
+            source_relay,
+            message: event.content.clone(),
+        })
+    }
+}
+
+// The address without the empty relay a tag has when it has a marker but no
+// relay hint
+fn without_empty(address: &EventAddr) -> EventAddr {
+    let mut address = address.clone();
+    address.relays.retain(|u| !u.0.is_empty());
+    address
+}
+
+fn non_empty_url(s: &str) -> Option<UncheckedUrl> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(UncheckedUrl::from_str(s))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::PrivateKey;
+
+    #[test]
+    fn test_normalize_identifier() {
+        assert_eq!(
+            WikiArticle::normalize_identifier("Wiki Article"),
+            "wiki-article"
+        );
+        assert_eq!(WikiArticle::normalize_identifier("NIP-54"), "nip---");
+        assert_eq!(WikiArticle::normalize_identifier("Ærø"), "ærø");
+    }
+
+    #[test]
+    fn test_wiki_article_round_trip() {
+        let privkey = PrivateKey::mock();
+        let mut article = WikiArticle::new("Bitcoin Mining", "= Bitcoin Mining".to_owned());
+        article.relations.push((
+            WikiRelation::Fork,
+            WikiReference::Address(EventAddr {
+                d: "bitcoin-mining".to_owned(),
+                relays: vec![UncheckedUrl::from_str("wss://relay.example.com")],
+                kind: EventKind::WikiArticle,
+                author: PublicKey::mock(),
+            }),
+        ));
+        article.relations.push((
+            WikiRelation::Defer,
+            WikiReference::Event {
+                id: Id::mock(),
+                relay: None,
+            },
+        ));
+        let event = Event::new(article.to_pre_event(privkey.public_key()), &privkey).unwrap();
+        assert_eq!(event.parameter().as_deref(), Some("bitcoin-mining"));
+        assert_eq!(WikiArticle::try_from_event(&event).unwrap(), article);
+
+        // The same after the 'a' tags are parsed from the wire
+        let event: Event = serde_json::from_str(&serde_json::to_string(&event).unwrap()).unwrap();
+        assert_eq!(WikiArticle::try_from_event(&event).unwrap(), article);
+    }
+
+    #[test]
+    fn test_wiki_merge_request_round_trip() {
+        let privkey = PrivateKey::mock();
+        let request = WikiMergeRequest {
+            destination: EventAddr {
+                d: "bitcoin-mining".to_owned(),
+                relays: vec![],
+                kind: EventKind::WikiArticle,
+                author: PublicKey::mock(),
+            },
+            destination_pubkey: PublicKeyHex::mock(),
+            base_version: Some(Id::mock()),
+            source: Id::mock(),
+            source_relay: Some(UncheckedUrl::from_str("wss://relay.example.com")),
+            message: "Fixed a typo".to_owned(),
+        };
+        let event = Event::new(request.to_pre_event(privkey.public_key()), &privkey).unwrap();
+        assert_eq!(WikiMergeRequest::try_from_event(&event).unwrap(), request);
+        let event: Event = serde_json::from_str(&serde_json::to_string(&event).unwrap()).unwrap();
+        assert_eq!(WikiMergeRequest::try_from_event(&event).unwrap(), request);
+    }
+}
@@ -0,0 +1,177 @@
+use super::{ClientMessage, RelayMessage, Unixtime};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Write};
+
+/// Which way a frame went
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum WireDirection {
+    /// From the relay to the client
+    #[serde(rename = "in")]
+    Inbound,
+
+    /// From the client to the relay
+    #[serde(rename = "out")]
+    Outbound,
+}
+
+/// A raw websocket text frame, as recorded by `WireLog`
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WireFrame {
+    /// When it was sent or received
+    pub at: Unixtime,
+
+    /// Which way it went
+    #[serde(rename = "dir")]
+    pub direction: WireDirection,
+
+    /// The text of the frame, exactly as it was on the wire
+    pub frame: String,
+}
+
+/// A frame parsed by the message parser for its direction
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WireMessage {
+    /// An outbound frame
+    Client(ClientMessage),
+
+    /// An inbound frame
+    Relay(RelayMessage),
+}
+
+impl WireFrame {
+    /// Feed the frame through the parser for its direction, as a client or
+    /// relay would on receiving it
+    pub fn parse(&self) -> Result<WireMessage, Error> {
+        Ok(match self.direction {
+            WireDirection::Inbound => WireMessage::Relay(serde_json::from_str(&self.frame)?),
+            WireDirection::Outbound => WireMessage::Client(serde_json::from_str(&self.frame)?),
+        })
+    }
+}
+
+/// Records the raw frames a relay connection sends and receives, as
+/// newline-delimited JSON, so that a user can send the log of a misbehaving
+/// relay and it can be replayed with `WireReplay`.
+///
+/// Frames are logged before parsing, so frames the parsers reject are kept
+/// too.
+#[derive(Debug)]
+pub struct WireLog<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> WireLog<W> {
+    /// Log to `writer`
+    pub fn new(writer: W) -> WireLog<W> {
+        WireLog { writer }
+    }
+
+    /// Record a frame received from the relay
+    pub fn inbound(&mut self, frame: &str, now: Unixtime) -> Result<(), Error> {
+        self.record(WireDirection::Inbound, frame, now)
+    }
+
+    /// Record a frame sent to the relay
+    pub fn outbound(&mut self, frame: &str, now: Unixtime) -> Result<(), Error> {
+        self.record(WireDirection::Outbound, frame, now)
+    }
+
+    /// Record a frame. Each is flushed, so the log survives a crash.
+    pub fn record(
+        &mut self,
+        direction: WireDirection,
+        frame: &str,
+        now: Unixtime,
+    ) -> Result<(), Error> {
+        let frame = WireFrame {
+            at: now,
+            direction,
+            frame: frame.to_owned(),
+        };
+        serde_json::to_writer(&mut self.writer, &frame)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Get the underlying writer back
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads back the frames recorded by a `WireLog`, in order. Call
+/// `WireFrame::parse()` on each to run it through the message parsers.
+///
+/// Blank lines are ignored. A line that is not a recorded frame yields an
+/// error, and reading carries on with the next line.
+#[derive(Debug)]
+pub struct WireReplay<R> {
+    reader: R,
+}
+
+impl<R: BufRead> WireReplay<R> {
+    /// Replay the log in `reader`
+    pub fn new(reader: R) -> WireReplay<R> {
+        WireReplay { reader }
+    }
+}
+
+impl<R: BufRead> Iterator for WireReplay<R> {
+    type Item = Result<WireFrame, Error>;
+
+    fn next(&mut self) -> Option<Result<WireFrame, Error>> {
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            match self.reader.read_line(&mut buf) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e.into())),
+            }
+            let line = buf.trim();
+            if !line.is_empty() {
+                return Some(serde_json::from_str(line).map_err(|e| e.into()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Event, Filter, SubscriptionId};
+
+    #[test]
+    fn test_wire_log_replay() {
+        let req = ClientMessage::Req(SubscriptionId::mock(), vec![Filter::mock()]);
+        let event = RelayMessage::Event(SubscriptionId::mock(), Box::new(Event::mock()));
+        let now = Unixtime(1700000000);
+
+        let mut log = WireLog::new(Vec::new());
+        log.outbound(&serde_json::to_string(&req).unwrap(), now)
+            .unwrap();
+        log.inbound(&serde_json::to_string(&event).unwrap(), now)
+            .unwrap();
+        log.inbound("[\"NOTICE\",", now).unwrap();
+        let mut output = log.into_inner();
+        output.extend(b"\nnot a frame\n");
+
+        let frames: Vec<Result<WireFrame, Error>> = WireReplay::new(&output[..]).collect();
+        assert_eq!(frames.len(), 4);
+        assert!(frames[3].is_err());
+
+        let first = frames[0].as_ref().unwrap();
+        assert_eq!(first.direction, WireDirection::Outbound);
+        assert_eq!(first.at, now);
+        assert_eq!(first.parse().unwrap(), WireMessage::Client(req));
+        let second = frames[1].as_ref().unwrap();
+        assert_eq!(second.parse().unwrap(), WireMessage::Relay(event));
+
+        // The broken frame was kept, so the parser failure can be reproduced
+        let third = frames[2].as_ref().unwrap();
+        assert_eq!(third.frame, "[\"NOTICE\",");
+        assert!(third.parse().is_err());
+    }
+}
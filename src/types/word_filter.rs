@@ -0,0 +1,234 @@
+use super::Event;
+use crate::Error;
+use regex::{RegexSet, RegexSetBuilder};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Flags text containing any of a list of terms, for muting and for relay
+/// content policies.
+///
+/// Terms and text are both normalized: lowercased, accents dropped from Latin
+/// letters, fullwidth forms folded to ASCII, and zero-width characters
+/// removed. They are then split into words of letters and digits, so a term
+/// only matches whole words ("ass" does not match "class"). A `*` within a
+/// term's word matches any run of letters and digits ("spam*" matches
+/// "spammer"), and a term of several words matches them in sequence.
+///
+/// Single words without wildcards are looked up in a hash table, and all other
+/// terms are compiled together into one automaton, so large term lists stay
+/// fast.
+#[derive(Clone)]
+pub struct WordFilter {
+    terms: Vec<String>,
+    exact: HashMap<String, Vec<usize>>,
+    patterns: Vec<usize>,
+    set: RegexSet,
+}
+
+impl fmt::Debug for WordFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WordFilter")
+            .field("terms", &self.terms.len())
+            .finish()
+    }
+}
+
+impl PartialEq for WordFilter {
+    fn eq(&self, other: &WordFilter) -> bool {
+        self.terms == other.terms
+    }
+}
+
+impl Eq for WordFilter {}
+
+impl Default for WordFilter {
+    fn default() -> WordFilter {
+        WordFilter {
+            terms: vec![],
+            exact: HashMap::new(),
+            patterns: vec![],
+            set: RegexSet::empty(),
+        }
+    }
+}
+
+impl WordFilter {
+    /// Compile a filter from terms. Terms with no letters or digits are
+    /// skipped, and a word made only of wildcards is an error.
+    pub fn new<I, S>(terms: I) -> Result<WordFilter, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut filter = WordFilter::default();
+        let mut regexes: Vec<String> = Vec::new();
+        for term in terms {
+            let term = term.as_ref();
+            let words = words(term, true);
+            if words.is_empty() {
+                continue;
+            }
+            if words.iter().any(|w| w.chars().all(|c| c == '*')) {
+                return Err(Error::InvalidWordFilter(format!(
+                    "\"{term}\" has a word that is only wildcards"
+                )));
+            }
+            let index = filter.terms.len();
+            filter.terms.push(term.to_owned());
+            if words.len() == 1 && !words[0].contains('*') {
+                filter
+                    .exact
+                    .entry(words[0].clone())
+                    .or_default()
+                    .push(index);
+            } else {
+                let words: Vec<String> = words
+                    .iter()
+                    .map(|w| regex::escape(w).replace("\\*", "[^ ]*"))
+                    .collect();
+                regexes.push(format!(" {} ", words.join(" ")));
+                filter.patterns.push(index);
+            }
+        }
+        if !regexes.is_empty() {
+            filter.set = RegexSetBuilder::new(regexes)
+                .size_limit(1 << 30)
+                .dfa_size_limit(1 << 26)
+                .build()
+                .map_err(|e| Error::InvalidWordFilter(format!("{e}")))?;
+        }
+        Ok(filter)
+    }
+
+    /// The terms, as given
+    pub fn terms(&self) -> &[String] {
+        &self.terms
+    }
+
+    /// Are there no terms?
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Does `text` contain any of the terms?
+    pub fn is_match(&self, text: &str) -> bool {
+        let words = words(text, false);
+        if !self.exact.is_empty() && words.iter().any(|w| self.exact.contains_key(w)) {
+            return true;
+        }
+        !self.patterns.is_empty() && self.set.is_match(&joined(&words))
+    }
+
+    /// The terms that `text` contains, in the order they were given
+    pub fn matches(&self, text: &str) -> Vec<&str> {
+        let words = words(text, false);
+        let mut indices: Vec<usize> = Vec::new();
+        for word in words.iter() {
+            if let Some(found) = self.exact.get(word) {
+                indices.extend(found);
+            }
+        }
+        if !self.patterns.is_empty() {
+            indices.extend(
+                self.set
+                    .matches(&joined(&words))
+                    .into_iter()
+                    .map(|i| self.patterns[i]),
+            );
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        indices.iter().map(|i| self.terms[*i].as_str()).collect()
+    }
+
+    /// Does the content of `event` contain any of the terms?
+    pub fn is_match_event(&self, event: &Event) -> bool {
+        self.is_match(&event.content)
+    }
+}
+
+// Normalize and split into words, keeping '*' within words if requested
+fn words(text: &str, wildcards: bool) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        let c = match c {
+            '\u{00AD}' | '\u{200B}'..='\u{200D}' | '\u{2060}' | '\u{FEFF}' => continue,
+            '\u{0300}'..='\u{036F}' => continue,
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            _ => fold_latin(c),
+        };
+        if c.is_alphanumeric() || (wildcards && c == '*') {
+            word.extend(c.to_lowercase());
+        } else if !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+// Words separated and surrounded by single spaces, for the patterns
+fn joined(words: &[String]) -> String {
+    format!(" {} ", words.join(" "))
+}
+
+// Drop the accent from an accented Latin letter
+fn fold_latin(c: char) -> char {
+    match c {
+        'À'..='Å' => 'A',
+        'à'..='å' => 'a',
+        'Ç' => 'C',
+        'ç' => 'c',
+        'È'..='Ë' => 'E',
+        'è'..='ë' => 'e',
+        'Ì'..='Ï' => 'I',
+        'ì'..='ï' => 'i',
+        'Ñ' => 'N',
+        'ñ' => 'n',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'ò'..='ö' | 'ø' => 'o',
+        'Ù'..='Ü' => 'U',
+        'ù'..='ü' => 'u',
+        'Ý' => 'Y',
+        'ý' | 'ÿ' => 'y',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_word_filter() {
+        let filter = WordFilter::new(["Airdrop", "spam*", "free money", "*coin"]).unwrap();
+        assert_eq!(filter.terms().len(), 4);
+
+        assert!(filter.is_match("Free AIRDROP now"));
+        assert!(filter.is_match("ａｉｒｄｒｏｐ"));
+        assert!(filter.is_match("air\u{200B}drop"));
+        assert!(filter.is_match("you're a spammer"));
+        assert!(filter.is_match("get FREE  money!"));
+        assert!(filter.is_match("buy shitcoin"));
+        assert!(!filter.is_match("airdrops"));
+        assert!(!filter.is_match("free the money"));
+        assert!(!filter.is_match("coins"));
+        assert!(!filter.is_match(""));
+
+        let accents = WordFilter::new(["cafe"]).unwrap();
+        assert!(accents.is_match("Café"));
+        assert!(accents.is_match("Cafe\u{0301}"));
+
+        assert_eq!(
+            filter.matches("spam spam, free money and an airdrop"),
+            vec!["Airdrop", "spam*", "free money"]
+        );
+
+        assert!(WordFilter::new(["!!!"]).unwrap().is_empty());
+        assert!(WordFilter::new(["free *"]).is_err());
+        assert!(!WordFilter::default().is_match("anything"));
+    }
+}